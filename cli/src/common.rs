@@ -20,6 +20,15 @@ pub enum CliError {
         argument: String,
         source: anyhow::Error,
     },
+    #[error("{count} values of {width} bytes at {address:#010x} exceed the limit of {max} values or the 32 bit address space.")]
+    CountOutOfRange {
+        address: u32,
+        count: usize,
+        width: usize,
+        max: usize,
+    },
     #[error(transparent)]
     ProbeRs(#[from] Error),
+    #[error(transparent)]
+    Rtt(#[from] probe_rs_rtt::Error),
 }
@@ -3,8 +3,10 @@ use crate::common::CliError;
 use capstone::Capstone;
 use num_traits::Num;
 use probe_rs::architecture::arm::Dump;
+use probe_rs::config::MemoryRegion;
 use probe_rs::debug::DebugInfo;
-use probe_rs::{Core, CoreRegisterAddress, MemoryInterface};
+use probe_rs::{Core, CoreRegisterAddress, MemoryInterface, RegisterDescription};
+use probe_rs_rtt::Rtt;
 
 use std::fs::File;
 use std::{io::prelude::*, time::Duration};
@@ -29,6 +31,116 @@ where
     })
 }
 
+/// Find a register of the core by its name, ignoring the case.
+fn find_register(core: &Core, name: &str) -> Option<&'static RegisterDescription> {
+    let registers = core.registers();
+
+    let aliases = [
+        registers.program_counter(),
+        registers.stack_pointer(),
+        registers.return_address(),
+    ];
+
+    registers
+        .registers()
+        .chain(registers.special_registers())
+        .chain(aliases.iter().copied())
+        .find(|register| register.name().eq_ignore_ascii_case(name))
+}
+
+/// The maximum number of values which are read or disassembled by a single command.
+const MAX_COUNT: usize = 0x1_0000;
+
+/// The length in bytes of `count` values of `width` bytes at `address`, if the values
+/// don't exceed [`MAX_COUNT`] or the end of the address space.
+fn checked_length(address: u32, count: usize, width: usize) -> Result<usize, CliError> {
+    let out_of_range = || CliError::CountOutOfRange {
+        address,
+        count,
+        width,
+        max: MAX_COUNT,
+    };
+
+    if count > MAX_COUNT {
+        return Err(out_of_range());
+    }
+
+    let length = count.checked_mul(width).ok_or_else(out_of_range)?;
+
+    match (length as u64).checked_add(u64::from(address)) {
+        Some(end) if end <= 1 << 32 => Ok(length),
+        _ => Err(out_of_range()),
+    }
+}
+
+/// Read `count` values of `width` bytes, starting at the address in the first argument.
+fn read_memory(cli_data: &mut CliData, args: &[&str], width: usize) -> Result<(), CliError> {
+    let address: u32 = get_int_argument(args, 0)?;
+
+    let count = if args.len() > 1 {
+        get_int_argument(args, 1)?
+    } else {
+        1
+    };
+
+    let mut data = vec![0u8; checked_length(address, count, width)?];
+    cli_data.core.read_8(address, &mut data)?;
+
+    for (offset, value) in data.chunks(width).enumerate() {
+        let value = value
+            .iter()
+            .rev()
+            .fold(0u32, |value, &byte| value << 8 | u32::from(byte));
+
+        println!(
+            "0x{:08x} = 0x{:0width$x}",
+            address + (offset * width) as u32,
+            value,
+            width = width * 2
+        );
+    }
+
+    Ok(())
+}
+
+/// Write the value in the second argument with `width` bytes, to the address in the first argument.
+fn write_memory(cli_data: &mut CliData, args: &[&str], width: usize) -> Result<(), CliError> {
+    let address: u32 = get_int_argument(args, 0)?;
+    let value: u32 = get_int_argument(args, 1)?;
+
+    cli_data
+        .core
+        .write_8(address, &value.to_le_bytes()[..width])?;
+
+    Ok(())
+}
+
+/// Print `count` instructions, starting at `address`.
+fn disassemble(cli_data: &mut CliData, address: u32, count: usize) -> Result<(), CliError> {
+    // Thumb instructions are at most 4 bytes long.
+    let mut code = vec![0u8; checked_length(address, count, 4)?];
+    cli_data.core.read_8(address, &mut code)?;
+
+    match cli_data
+        .capstone
+        .disasm_count(&code, u64::from(address), count)
+    {
+        Ok(instructions) => {
+            for instruction in instructions.iter() {
+                println!(
+                    "{:#010x}: {:<8} {}",
+                    instruction.address(),
+                    instruction.mnemonic().unwrap_or(""),
+                    instruction.op_str().unwrap_or("")
+                );
+            }
+        }
+        Err(e) => println!("Failed to disassemble the code: {}", e),
+    }
+
+    Ok(())
+}
+
 impl DebugCli {
     pub fn new() -> DebugCli {
         let mut cli = DebugCli {
@@ -55,28 +167,7 @@ impl DebugCli {
                 let cpu_info = cli_data.core.halt(Duration::from_millis(100))?;
                 println!("Core stopped at address 0x{:08x}", cpu_info.pc);
 
-                let mut code = [0u8; 16 * 2];
-
-                cli_data.core.read(cpu_info.pc, &mut code)?;
-
-                /*
-                let instructions = cli_data
-                    .capstone
-                    .disasm_all(&code, u64::from(cpu_info.pc))
-                    .unwrap();
-
-                for i in instructions.iter() {
-                    println!("{}", i);
-                }
-                 */
-
-                for (offset, instruction) in code.iter().enumerate() {
-                    println!(
-                        "{:#010x}: {:010x}",
-                        cpu_info.pc + offset as u32,
-                        instruction
-                    );
-                }
+                disassemble(cli_data, cpu_info.pc, 8)?;
 
                 Ok(CliState::Continue)
             },
@@ -208,6 +299,28 @@ impl DebugCli {
             },
         });
 
+        cli.add_command(Command {
+            name: "read8",
+            help_text: "Read 8bit values from memory: read8 <address> [count]",
+
+            function: |cli_data, args| {
+                read_memory(cli_data, args, 1)?;
+
+                Ok(CliState::Continue)
+            },
+        });
+
+        cli.add_command(Command {
+            name: "read16",
+            help_text: "Read 16bit values from memory: read16 <address> [count]",
+
+            function: |cli_data, args| {
+                read_memory(cli_data, args, 2)?;
+
+                Ok(CliState::Continue)
+            },
+        });
+
         cli.add_command(Command {
             name: "write",
             help_text: "Write a 32bit value to memory",
@@ -222,6 +335,52 @@ impl DebugCli {
             },
         });
 
+        cli.add_command(Command {
+            name: "write8",
+            help_text: "Write an 8bit value to memory: write8 <address> <value>",
+
+            function: |cli_data, args| {
+                write_memory(cli_data, args, 1)?;
+
+                Ok(CliState::Continue)
+            },
+        });
+
+        cli.add_command(Command {
+            name: "write16",
+            help_text: "Write a 16bit value to memory: write16 <address> <value>",
+
+            function: |cli_data, args| {
+                write_memory(cli_data, args, 2)?;
+
+                Ok(CliState::Continue)
+            },
+        });
+
+        cli.add_command(Command {
+            name: "disasm",
+            help_text: "Disassemble instructions: disasm [address] [count], at the PC by default",
+
+            function: |cli_data, args| {
+                let address = if args.is_empty() {
+                    let pc = cli_data.core.registers().program_counter();
+                    cli_data.core.read_core_reg(pc)?
+                } else {
+                    get_int_argument(args, 0)?
+                };
+
+                let count = if args.len() > 1 {
+                    get_int_argument(args, 1)?
+                } else {
+                    8
+                };
+
+                disassemble(cli_data, address, count)?;
+
+                Ok(CliState::Continue)
+            },
+        });
+
         cli.add_command(Command {
             name: "break",
             help_text: "Set a breakpoint at a specifc address",
@@ -250,6 +409,24 @@ impl DebugCli {
             },
         });
 
+        cli.add_command(Command {
+            name: "breakpoints",
+            help_text: "List the breakpoints",
+
+            function: |cli_data, _args| {
+                let breakpoints = cli_data.core.hw_breakpoints()?;
+
+                for (unit, address) in breakpoints.iter().enumerate() {
+                    match address {
+                        Some(address) => println!("#{}: {:#010x}", unit, address),
+                        None => println!("#{}: unused", unit),
+                    }
+                }
+
+                Ok(CliState::Continue)
+            },
+        });
+
         cli.add_command(Command {
             name: "bt",
             help_text: "Show backtrace",
@@ -291,6 +468,71 @@ impl DebugCli {
                     println!("{}: {:#010x}", register.name(), value)
                 }
 
+                // Some special registers are only accessible depending on the
+                // security state, so errors are not fatal here.
+                for register in register_file.special_registers() {
+                    match cli_data.core.read_core_reg(register) {
+                        Ok(value) => println!("{}: {:#010x}", register.name(), value),
+                        Err(e) => println!("{}: <{}>", register.name(), e),
+                    }
+                }
+
+                Ok(CliState::Continue)
+            },
+        });
+
+        cli.add_command(Command {
+            name: "reg",
+            help_text: "Read or write a register by name: reg <name> [value]",
+
+            function: |cli_data, args| {
+                let name = args.first().ok_or(CliError::MissingArgument)?;
+
+                let register = match find_register(&cli_data.core, name) {
+                    Some(register) => register,
+                    None => {
+                        println!("Unknown register '{}'", name);
+                        return Ok(CliState::Continue);
+                    }
+                };
+
+                if args.len() > 1 {
+                    let value = get_int_argument(args, 1)?;
+                    cli_data.core.write_core_reg(register.into(), value)?;
+                } else {
+                    let value = cli_data.core.read_core_reg(register)?;
+                    println!("{}: {:#010x}", register.name(), value);
+                }
+
+                Ok(CliState::Continue)
+            },
+        });
+
+        cli.add_command(Command {
+            name: "rtt",
+            help_text: "Attach to RTT, and print the data of the up channels",
+
+            function: |cli_data, _args| {
+                if cli_data.rtt.is_none() {
+                    let mut rtt = Rtt::attach(&mut cli_data.core, &cli_data.memory_map)?;
+
+                    for channel in rtt.up_channels().iter() {
+                        println!(
+                            "Up channel {}: {}",
+                            channel.number(),
+                            channel.name().unwrap_or("(unnamed)")
+                        );
+                    }
+
+                    cli_data.rtt = Some(rtt);
+                }
+
+                if let Some(rtt) = &mut cli_data.rtt {
+                    for (channel, data) in rtt.read_all(&mut cli_data.core)? {
+                        println!("{}: {}", channel, String::from_utf8_lossy(&data));
+                    }
+                }
+
                 Ok(CliState::Continue)
             },
         });
@@ -368,7 +610,7 @@ impl DebugCli {
                 println!("The following commands are available:");
 
                 for cmd in &self.commands {
-                    println!(" - {}", cmd.name);
+                    println!(" - {:<12} {}", cmd.name, cmd.help_text);
                 }
 
                 Ok(CliState::Continue)
@@ -417,8 +659,10 @@ impl DebugCli {
 
 pub struct CliData<'p> {
     pub core: Core<'p>,
+    pub memory_map: Vec<MemoryRegion>,
     pub debug_info: Option<DebugInfo>,
     pub capstone: Capstone,
+    pub rtt: Option<Rtt>,
 }
 
 pub enum CliState {
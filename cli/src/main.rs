@@ -5,6 +5,8 @@ mod info;
 use debugger::CliState;
 
 use probe_rs::{
+    architecture::arm::{SwoConfig, SwoDecoder, SwoReader, SwvFraming, SwvServer},
+    benchmark::BenchmarkOptions,
     debug::DebugInfo,
     flashing::{erase_all, BinOptions, FileDownloadError, Format},
     MemoryInterface, Probe,
@@ -107,6 +109,28 @@ enum Cli {
         /// The path to the file to be downloaded to the flash
         path: String,
     },
+    /// Measure the memory and flash throughput of the attached probe and target
+    #[structopt(name = "benchmark")]
+    Benchmark {
+        #[structopt(flatten)]
+        common: ProbeOptions,
+
+        /// The address of the RAM used for the benchmark.
+        #[structopt(long, parse(try_from_str = parse_u32))]
+        address: u32,
+        /// The amount of memory (in words) written and read back.
+        #[structopt(long, default_value = "4096", parse(try_from_str = parse_u32))]
+        words: u32,
+        /// Also measure flash programming at this address. This overwrites the flash contents!
+        #[structopt(long, parse(try_from_str = parse_u32))]
+        flash_address: Option<u32>,
+        /// The protocol speeds in kHz to benchmark. If none are given, the current speed is used.
+        #[structopt(long, parse(try_from_str = parse_u32))]
+        speeds: Vec<u32>,
+        /// Store the results as RON in this file.
+        #[structopt(long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
     /// Erase all nonvolatile memory of attached target
     #[structopt(name = "erase")]
     Erase {
@@ -125,6 +149,36 @@ enum Cli {
         #[structopt(parse(try_from_str = parse_u32))]
         loc: u32,
     },
+    /// Stream the decoded SWV trace of the target to TCP clients
+    #[structopt(name = "swv-server")]
+    SwvServer {
+        #[structopt(flatten)]
+        shared: CoreOptions,
+
+        #[structopt(flatten)]
+        common: ProbeOptions,
+
+        /// The clock of the TPIU in Hz, which is usually the core clock.
+        #[structopt(long, parse(try_from_str = parse_u32))]
+        clock: u32,
+        /// The baud rate of SWO.
+        #[structopt(long, default_value = "1000000", parse(try_from_str = parse_u32))]
+        baud: u32,
+        /// The ITM stimulus ports to enable, as a bit mask.
+        #[structopt(long, default_value = "0xffffffff", parse(try_from_str = parse_u32))]
+        ports: u32,
+        /// The address the server listens on.
+        #[structopt(long, default_value = "127.0.0.1:3443")]
+        address: String,
+        /// How the events are sent to the clients. Possible values are case-insensitive.
+        #[structopt(
+            possible_values = &SwvFramingType::variants(),
+            case_insensitive = true,
+            default_value = "json",
+            long
+        )]
+        framing: SwvFramingType,
+    },
 }
 
 /// Shared options for core selection, shared between commands
@@ -166,12 +220,45 @@ fn main() -> Result<()> {
             skip_bytes,
             path,
         } => download_program_fast(common, format.into(base_address, skip_bytes), &path),
+        Cli::Benchmark {
+            common,
+            address,
+            words,
+            flash_address,
+            speeds,
+            output,
+        } => benchmark(
+            &common,
+            &BenchmarkOptions {
+                address,
+                word_count: words as usize,
+                flash_address,
+            },
+            &speeds,
+            output.as_deref(),
+        ),
         Cli::Erase { common } => erase(&common),
         Cli::Trace {
             shared,
             common,
             loc,
         } => trace_u32_on_target(&shared, &common, loc),
+        Cli::SwvServer {
+            shared,
+            common,
+            clock,
+            baud,
+            ports,
+            address,
+            framing,
+        } => swv_server(
+            &shared,
+            &common,
+            &SwoConfig::new(clock).set_baud(baud),
+            ports,
+            &address,
+            framing.into(),
+        ),
     }
 }
 
@@ -253,6 +340,9 @@ fn download_program_fast(common: ProbeOptions, format: Format, path: &str) -> Re
             reset_halt: false,
             log: None,
             restore_unwritten: false,
+            disable_double_buffering: false,
+            erase_all: false,
+            verify: false,
             flash_layout_output_path: None,
             elf: None,
             work_dir: None,
@@ -265,6 +355,61 @@ fn download_program_fast(common: ProbeOptions, format: Format, path: &str) -> Re
     Ok(())
 }
 
+fn benchmark(
+    common: &ProbeOptions,
+    options: &BenchmarkOptions,
+    speeds: &[u32],
+    output: Option<&Path>,
+) -> Result<()> {
+    let mut results = Vec::new();
+
+    // A benchmark with an empty speed means the speed of the probe is not changed.
+    let speeds: Vec<Option<u32>> = if speeds.is_empty() {
+        vec![None]
+    } else {
+        speeds.iter().copied().map(Some).collect()
+    };
+
+    for speed in speeds {
+        let mut probe = common.attach_probe()?;
+
+        if let Some(speed) = speed {
+            probe.set_speed(speed)?;
+        }
+
+        let result = probe_rs::benchmark::run(probe, common.get_target_selector()?, options)?;
+
+        println!(
+            "{} @ {} kHz: write {:.2} KiB/s, read {:.2} KiB/s{}",
+            result.probe,
+            result.speed_khz,
+            result.write.bytes_per_second() / 1024.0,
+            result.read.bytes_per_second() / 1024.0,
+            if result.verified {
+                ""
+            } else {
+                " (verification failed)"
+            }
+        );
+
+        if let Some(flash) = &result.flash {
+            println!("  flash {:.2} KiB/s", flash.bytes_per_second() / 1024.0);
+        }
+
+        results.push(result);
+    }
+
+    if let Some(path) = output {
+        let serialized = ron::ser::to_string_pretty(&results, Default::default())
+            .context("Failed to serialize benchmark results")?;
+
+        std::fs::write(path, serialized)
+            .with_context(|| format!("Failed to write results to {}", path.display()))?;
+    }
+
+    Ok(())
+}
+
 fn erase(common: &ProbeOptions) -> Result<()> {
     let mut session = common.simple_attach()?;
 
@@ -333,6 +478,30 @@ fn trace_u32_on_target(
     }
 }
 
+fn swv_server(
+    shared_options: &CoreOptions,
+    common: &ProbeOptions,
+    config: &SwoConfig,
+    ports: u32,
+    address: &str,
+    framing: SwvFraming,
+) -> Result<()> {
+    let mut session = common.simple_attach()?;
+
+    session.setup_swv(shared_options.core, config)?;
+    session.configure_itm(shared_options.core, ports, true)?;
+
+    let mut server = SwvServer::bind(address, framing)?;
+    let mut reader = SwoReader::new(SwoDecoder::new());
+
+    println!("Streaming SWV data on {}", server.local_addr()?);
+
+    loop {
+        reader.poll(session.get_arm_interface()?)?;
+        server.publish(&reader.decode())?;
+    }
+}
+
 fn debug(shared_options: &CoreOptions, common: &ProbeOptions, exe: Option<PathBuf>) -> Result<()> {
     let mut session = common.simple_attach()?;
 
@@ -349,12 +518,15 @@ fn debug(shared_options: &CoreOptions, common: &ProbeOptions, exe: Option<PathBu
 
     let cli = debugger::DebugCli::new();
 
+    let memory_map = session.target().memory_map.clone();
     let core = session.core(shared_options.core)?;
 
     let mut cli_data = debugger::CliData {
         core,
+        memory_map,
         debug_info: di,
         capstone: cs,
+        rtt: None,
     };
 
     let mut rl = Editor::<()>::new();
@@ -413,6 +585,23 @@ impl DownloadFileType {
     }
 }
 
+arg_enum! {
+    #[derive(Debug, Clone, Copy)]
+    enum SwvFramingType {
+        Json,
+        Binary,
+    }
+}
+
+impl From<SwvFramingType> for SwvFraming {
+    fn from(framing: SwvFramingType) -> Self {
+        match framing {
+            SwvFramingType::Json => SwvFraming::JsonLines,
+            SwvFramingType::Binary => SwvFraming::Binary,
+        }
+    }
+}
+
 fn parse_u32(input: &str) -> Result<u32, ParseIntError> {
     parse_int::parse(input)
 }
@@ -1,7 +1,6 @@
-use probe_rs::{
-    config::{CoreType, MemoryRegion},
-    Core, CoreRegisterAddress,
-};
+use std::ops::Range;
+
+use probe_rs::{config::CoreType, Core, CoreRegisterAddress, MemoryKind, MemoryMapRegion, Target};
 
 /// Extension trait for probe_rs::Core, which adds some GDB -> probe-rs internal translation functions.
 ///
@@ -67,6 +66,23 @@ impl<'probe> GdbArchitectureExt for Core<'probe> {
                     return None;
                 }
             },
+            probe_rs::Architecture::Xtensa => match gdb_reg_number {
+                // Program counter
+                0 => {
+                    let addr: CoreRegisterAddress = self.registers().program_counter().into();
+                    (addr.0, 4)
+                }
+                // Physical address registers ar0 to ar63
+                x @ 1..=64 => (0x200 + x as u16 - 1, 4),
+                // LBEG, LEND, LCOUNT, SAR
+                x @ 65..=68 => (0x100 + x as u16 - 65, 4),
+                // WINDOWBASE, WINDOWSTART
+                x @ 69..=70 => (0x148 + x as u16 - 69, 4),
+                other => {
+                    log::warn!("Request for unsupported register with number {}", other);
+                    return None;
+                }
+            },
         };
 
         Some((CoreRegisterAddress(probe_rs_number as u16), bytesize))
@@ -76,61 +92,106 @@ impl<'probe> GdbArchitectureExt for Core<'probe> {
         match self.architecture() {
             probe_rs::Architecture::Arm => 24,
             probe_rs::Architecture::Riscv => 33,
+            probe_rs::Architecture::Xtensa => 71,
         }
     }
 }
 
-/// Extension trait for probe_rs::Target, to get XML-based target description and
-/// memory map.
-pub trait GdbTargetExt {
-    /// Memory map in GDB XML format.
-    ///
-    /// See https://sourceware.org/gdb/onlinedocs/gdb/Memory-Map-Format.html#Memory-Map-Format
-    fn gdb_memory_map(&self) -> String;
-
-    /// Target description in GDB XML Format.
-    ///
-    /// See https://sourceware.org/gdb/onlinedocs/gdb/Target-Descriptions.html#Target-Descriptions
-    fn target_description(&self) -> String;
-}
-
-impl GdbTargetExt for probe_rs::Target {
-    fn gdb_memory_map(&self) -> String {
-        let mut xml_map = r#"<?xml version="1.0"?>
+/// Memory map in GDB XML format.
+///
+/// See https://sourceware.org/gdb/onlinedocs/gdb/Memory-Map-Format.html#Memory-Map-Format
+pub(crate) fn gdb_memory_map(memory_map: &[MemoryMapRegion], target: &Target) -> String {
+    let mut xml_map = r#"<?xml version="1.0"?>
 <!DOCTYPE memory-map PUBLIC "+//IDN gnu.org//DTD GDB Memory Map V1.0//EN" "http://sourceware.org/gdb/gdb-memory-map.dtd">
 <memory-map>
 "#.to_owned();
 
-        for region in &self.memory_map {
-            let region_entry = match region {
-                MemoryRegion::Ram(ram) => format!(
-                    r#"<memory type="ram" start="{:#x}" length="{:#x}"/>\n"#,
-                    ram.range.start,
-                    ram.range.end - ram.range.start
-                ),
-                MemoryRegion::Generic(region) => format!(
-                    r#"<memory type="rom" start="{:#x}" length="{:#x}"/>\n"#,
-                    region.range.start,
-                    region.range.end - region.range.start
-                ),
-                MemoryRegion::Nvm(region) => {
-                    // TODO: Use flash with block size
-                    format!(
-                        r#"<memory type="rom" start="{:#x}" length="{:#x}"/>\n"#,
-                        region.range.start,
-                        region.range.end - region.range.start
-                    )
+    for region in memory_map {
+        let memory_type = match region.kind {
+            MemoryKind::Ram => "ram",
+            MemoryKind::Nvm => {
+                let blocks = flash_blocks(region, target);
+
+                // GDB can only program flash if it knows the size of the erase blocks.
+                if !blocks.is_empty() {
+                    for (range, block_size) in blocks {
+                        xml_map.push_str(&format!(
+                            r#"<memory type="flash" start="{:#x}" length="{:#x}"><property name="blocksize">{:#x}</property></memory>\n"#,
+                            range.start,
+                            range.end - range.start,
+                            block_size
+                        ));
+                    }
+                    continue;
                 }
-            };
-
-            xml_map.push_str(&region_entry);
-        }
 
-        xml_map.push_str(r#"</memory-map>"#);
+                "rom"
+            }
+            MemoryKind::Generic => "rom",
+        };
 
-        xml_map
+        xml_map.push_str(&format!(
+            r#"<memory type="{}" start="{:#x}" length="{:#x}"/>\n"#,
+            memory_type,
+            region.range.start,
+            region.range.end - region.range.start
+        ));
     }
 
+    xml_map.push_str(r#"</memory-map>"#);
+
+    xml_map
+}
+
+/// Split the NVM `region` into the parts with the same sector size, according to the flash
+/// algorithm of the region.
+///
+/// Returns no parts if the region has no flash algorithm.
+fn flash_blocks(region: &MemoryMapRegion, target: &Target) -> Vec<(Range<u32>, u32)> {
+    let algorithm = region.flash_algorithm.as_ref().and_then(|name| {
+        target
+            .flash_algorithms
+            .iter()
+            .find(|algorithm| &algorithm.name == name)
+    });
+
+    let properties = match algorithm {
+        Some(algorithm) => &algorithm.flash_properties,
+        None => return Vec::new(),
+    };
+
+    let flash_start = properties.address_range.start;
+    let flash_end = properties.address_range.end;
+
+    properties
+        .sectors
+        .iter()
+        .enumerate()
+        .filter_map(|(index, sector)| {
+            let start = flash_start + sector.address;
+            let end = properties
+                .sectors
+                .get(index + 1)
+                .map(|next| flash_start + next.address)
+                .unwrap_or(flash_end);
+
+            let start = start.max(region.range.start);
+            let end = end.min(region.range.end);
+
+            (start < end).then_some((start..end, sector.size))
+        })
+        .collect()
+}
+
+/// Extension trait for probe_rs::Target, to get the XML-based target description.
+pub trait GdbTargetExt {
+    /// Target description in GDB XML Format.
+    ///
+    /// See https://sourceware.org/gdb/onlinedocs/gdb/Target-Descriptions.html#Target-Descriptions
+    fn target_description(&self) -> String;
+}
+
+impl GdbTargetExt for probe_rs::Target {
     fn target_description(&self) -> String {
         // GDB-architectures
         //
@@ -140,6 +201,7 @@ impl GdbTargetExt for probe_rs::Target {
         // - armv8-m.base -> Core-M23
         // - armv8-m.main -> Core-M33
         // - riscv:rv32   -> RISCV
+        // - xtensa       -> Xtensa
 
         // TODO: what if they're not all equal?
         let architecture = match self.cores[0].core_type {
@@ -148,6 +210,7 @@ impl GdbTargetExt for probe_rs::Target {
             CoreType::Armv7em => "armv7e-m",
             CoreType::Armv8m => "armv8-m.main",
             CoreType::Riscv => "riscv:rv32",
+            CoreType::Xtensa => "xtensa",
         };
 
         // Only target.xml is supported
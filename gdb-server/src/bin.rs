@@ -2,12 +2,16 @@ use anyhow::{anyhow, Result};
 use colored::*;
 use std::sync::Mutex;
 use std::{
+    path::PathBuf,
     process::{self},
     time::Duration,
 };
 use structopt::StructOpt;
 
-use probe_rs::{config::TargetSelector, DebugProbeInfo, DebugProbeSelector, Probe};
+use probe_rs::{
+    DebugProbeInfo, DebugProbeSelector, GdbConfig, GdbResetBehavior, Probe, ResetConfig,
+    SessionConfig,
+};
 
 #[derive(Debug, StructOpt)]
 struct Opt {
@@ -25,12 +29,45 @@ struct Opt {
         help = "Use this flag to reset and halt (instead of just a halt) the attached core after attaching to the target."
     )]
     reset_halt: bool,
+    #[structopt(
+        name = "allow-erase-all",
+        long = "allow-erase-all",
+        help = "Use this flag to allow erasing the whole chip, if it is locked and can only be unlocked by erasing it."
+    )]
+    allow_erase_all: bool,
     #[structopt(
         name = "gdb-connection-string",
         long = "gdb-connection-string",
-        help = "Use this flag to override the default GDB connection string (localhost:1337)."
+        help = "Use this flag to override the default GDB connection string (127.0.0.1:1337)."
     )]
     gdb_connection_string: Option<String>,
+    #[structopt(
+        name = "connect-back",
+        long = "connect-back",
+        help = "Connect to a GDB which listens on the GDB connection string, instead of listening for GDB."
+    )]
+    connect_back: bool,
+    #[structopt(
+        name = "restart-image",
+        long = "restart-image",
+        parse(from_os_str),
+        help = "The ELF file which is flashed when the program is restarted with 'run' in extended-remote mode."
+    )]
+    restart_image: Option<PathBuf>,
+    #[structopt(
+        name = "verify",
+        long = "verify",
+        help = "Verify the flash after it was programmed with 'load'."
+    )]
+    verify: bool,
+    #[structopt(
+        name = "reset-behavior",
+        long = "reset-behavior",
+        default_value = "halt",
+        parse(try_from_str),
+        help = "What 'monitor reset' does: 'halt', 'run' or 'run-to-main'. 'run-to-main' needs the symbols of the restart image."
+    )]
+    reset_behavior: GdbResetBehavior,
     #[structopt(
         name = "list-probes",
         long = "list-probes",
@@ -111,32 +148,57 @@ fn main_try() -> Result<()> {
         return Ok(());
     }
 
+    let mut config = SessionConfig {
+        chip: opt.chip,
+        chip_descriptions: opt
+            .chip_description_path
+            .into_iter()
+            .map(PathBuf::from)
+            .collect(),
+        allow_erase_all: opt.allow_erase_all,
+        reset: ResetConfig {
+            enabled: opt.reset_halt,
+            halt_afterwards: opt.reset_halt,
+        },
+        gdb: GdbConfig {
+            enabled: true,
+            connect_back: opt.connect_back,
+            restart_image: opt.restart_image,
+            verify: opt.verify,
+            reset: opt.reset_behavior,
+            ..GdbConfig::default()
+        },
+        ..SessionConfig::default()
+    };
+
+    if let Some(connection) = opt.gdb_connection_string {
+        config.gdb.connection = connection;
+    }
+
+    config.load_chip_descriptions()?;
+
     let probe = open_probe(opt.probe_index, &available_probes)?;
 
-    let target_selector = match opt.chip {
-        Some(identifier) => identifier.into(),
-        None => TargetSelector::Auto,
-    };
+    let session =
+        Mutex::new(probe.attach_with_permissions(config.target_selector(), config.permissions())?);
 
-    let session = Mutex::new(probe.attach(target_selector)?);
+    if let Some(image) = &config.gdb.restart_image {
+        session.lock().unwrap().load_symbols(image)?;
+    }
 
-    if opt.reset_halt {
-        session
-            .lock()
-            .unwrap()
-            .core(0)?
-            .reset_and_halt(Duration::from_millis(100))?;
+    if config.reset.enabled {
+        let mut session = session.lock().unwrap();
+        let mut core = session.core(0)?;
+
+        if config.reset.halt_afterwards {
+            core.reset_and_halt(Duration::from_millis(100))?;
+        } else {
+            core.reset()?;
+        }
     }
 
-    let gdb_connection_string = opt
-        .gdb_connection_string
-        .or_else(|| Some("localhost:1337".to_string()));
-    // This next unwrap will always resolve as the connection string is always Some(T).
-    println!(
-        "Firing up GDB stub at {}",
-        gdb_connection_string.as_ref().unwrap()
-    );
-    if let Err(e) = probe_rs_gdb_server::run(gdb_connection_string, &session) {
+    println!("Firing up GDB stub at {}", config.gdb.connection);
+    if let Err(e) = probe_rs_gdb_server::serve(&config.gdb, &session) {
         eprintln!("During the execution of GDB an error was encountered:");
         eprintln!("{:?}", e);
     }
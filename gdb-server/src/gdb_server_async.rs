@@ -7,7 +7,7 @@ use async_std::{
 };
 use futures::channel::mpsc;
 use gdb_protocol::packet::CheckedPacket;
-use probe_rs::Session;
+use probe_rs::{GdbConfig, Session};
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
 type Sender<T> = mpsc::UnboundedSender<T>;
@@ -22,17 +22,42 @@ pub fn run(connection_string: Option<impl Into<String>>, session: &Mutex<Session
     let connection_string = connection_string
         .map(|cs| cs.into())
         .unwrap_or_else(|| CONNECTION_STRING.to_owned());
-    log::info!("GDB stub listening on {}", connection_string);
-    task::block_on(accept_loop(connection_string, session))
+    let config = GdbConfig {
+        connection: connection_string,
+        ..GdbConfig::default()
+    };
+    log::info!("GDB stub listening on {}", config.connection);
+    task::block_on(accept_loop(&config.connection, session, &config))
+}
+
+/// Start the GDB stub with the settings in `config`.
+///
+/// If [`GdbConfig::connect_back`] is set, the stub connects to a GDB which listens on
+/// [`GdbConfig::connection`], and returns when this connection is closed. Otherwise, it
+/// listens for GDB like [`run`].
+///
+/// This function is blocking, like [`run`].
+pub fn serve(config: &GdbConfig, session: &Mutex<Session>) -> Result<()> {
+    if config.connect_back {
+        log::info!("GDB stub connecting to {}", config.connection);
+        task::block_on(connect(&config.connection, session, config))
+    } else {
+        log::info!("GDB stub listening on {}", config.connection);
+        task::block_on(accept_loop(&config.connection, session, config))
+    }
 }
 
 /// This function accepts any incomming connection.
-async fn accept_loop(addr: impl ToSocketAddrs, session: &Mutex<Session>) -> Result<()> {
+async fn accept_loop(
+    addr: impl ToSocketAddrs,
+    session: &Mutex<Session>,
+    config: &GdbConfig,
+) -> Result<()> {
     let listener = TcpListener::bind(addr).await?;
 
     let mut incoming = listener.incoming();
     while let Some(stream) = incoming.next().await {
-        if let Err(e) = handle_connection(stream?, session).await {
+        if let Err(e) = handle_connection(stream?, session, config).await {
             log::error!(
                 "An error with the current connection has been encountered. It has been closed."
             );
@@ -42,12 +67,27 @@ async fn accept_loop(addr: impl ToSocketAddrs, session: &Mutex<Session>) -> Resu
     Ok(())
 }
 
+/// Connect to a GDB which listens on `addr`, and handle this connection.
+async fn connect(
+    addr: impl ToSocketAddrs,
+    session: &Mutex<Session>,
+    config: &GdbConfig,
+) -> Result<()> {
+    let stream = TcpStream::connect(addr).await?;
+
+    handle_connection(stream, session, config).await
+}
+
 /// Handle a single connection of a client
-async fn handle_connection(stream: TcpStream, session: &Mutex<Session>) -> Result<()> {
+async fn handle_connection(
+    stream: TcpStream,
+    session: &Mutex<Session>,
+    config: &GdbConfig,
+) -> Result<()> {
     let (packet_stream_sender, packet_stream_receiver) = mpsc::unbounded();
     let (tbd_sender, tbd_receiver) = mpsc::unbounded();
 
-    log::info!("Accepted a new connection from: {}", stream.peer_addr()?);
+    log::info!("Connected to: {}", stream.peer_addr()?);
 
     let inbound_broker_handle = task::spawn(inbound_broker_loop(
         stream,
@@ -55,7 +95,7 @@ async fn handle_connection(stream: TcpStream, session: &Mutex<Session>) -> Resul
         packet_stream_receiver,
     ));
 
-    super::worker::worker(tbd_receiver, packet_stream_sender, session).await?;
+    super::worker::worker(tbd_receiver, packet_stream_sender, session, config).await?;
 
     inbound_broker_handle.await?;
 
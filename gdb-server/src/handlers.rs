@@ -1,10 +1,15 @@
-use crate::architecture::{GdbArchitectureExt, GdbTargetExt};
-use probe_rs::{Core, CoreStatus, MemoryInterface, Session};
+use crate::architecture::{gdb_memory_map, GdbArchitectureExt, GdbTargetExt};
+use crate::memory_cache::MemoryCache;
+use crate::parser::{ThreadId, ThreadOperation};
+use crate::worker::{thread_id, SelectedCores};
+use probe_rs::flashing::{download_file, DownloadOptions, FlashLoader, Format};
+use probe_rs::{Core, CoreStatus, GdbResetBehavior, MemoryInterface, Session};
+use std::path::Path;
 use std::time::Duration;
 
 pub(crate) fn q_supported() -> Option<String> {
     Some(
-        "PacketSize=2048;swbreak-;hwbreak+;vContSupported+;qXfer:features:read+;qXfer:memory-map:read+"
+        "PacketSize=2048;swbreak-;hwbreak+;vContSupported+;qXfer:features:read+;qXfer:memory-map:read+;qXfer:threads:read+"
             .into(),
     )
 }
@@ -21,8 +26,77 @@ pub(crate) fn q_attached() -> Option<String> {
     Some("1".into())
 }
 
-pub(crate) fn halt_reason() -> Option<String> {
-    Some("S05".into())
+pub(crate) fn halt_reason(core: usize) -> Option<String> {
+    Some(format!("T05thread:{:x};", thread_id(core)))
+}
+
+pub(crate) fn select_thread(
+    operation: ThreadOperation,
+    thread: ThreadId,
+    selected: &mut SelectedCores,
+    num_cores: usize,
+) -> Option<String> {
+    let core = match thread {
+        // Keep the selected core, all cores can't be accessed at once.
+        ThreadId::Any | ThreadId::All => return reply_ok(),
+        ThreadId::Id(id) if id as usize <= num_cores => id as usize - 1,
+        ThreadId::Id(id) => {
+            log::warn!(
+                "Thread {} does not exist, there are {} cores",
+                id,
+                num_cores
+            );
+            return Some("E22".into());
+        }
+    };
+
+    match operation {
+        ThreadOperation::General => selected.general = core,
+        ThreadOperation::Continue => selected.execution = core,
+    }
+
+    reply_ok()
+}
+
+pub(crate) fn thread_alive(thread: ThreadId, num_cores: usize) -> Option<String> {
+    match thread {
+        ThreadId::Id(id) if id as usize <= num_cores => reply_ok(),
+        _ => Some("E01".into()),
+    }
+}
+
+pub(crate) fn current_thread(selected: &SelectedCores) -> Option<String> {
+    Some(format!("QC{:x}", thread_id(selected.general)))
+}
+
+pub(crate) fn first_thread_info(num_cores: usize) -> Option<String> {
+    let threads: Vec<String> = (0..num_cores)
+        .map(|core| format!("{:x}", thread_id(core)))
+        .collect();
+
+    Some(format!("m{}", threads.join(",")))
+}
+
+pub(crate) fn subsequent_thread_info() -> Option<String> {
+    // All threads are listed in the reply to qfThreadInfo.
+    Some("l".into())
+}
+
+pub(crate) fn read_threads(session: &Session, offset: u32, length: u32) -> Option<String> {
+    let mut threads = String::from("<?xml version=\"1.0\"?>\n<threads>\n");
+
+    for (core, config) in session.target().cores.iter().enumerate() {
+        threads.push_str(&format!(
+            "<thread id=\"{:x}\" core=\"{}\" name=\"{}\"/>\n",
+            thread_id(core),
+            core,
+            config.name
+        ));
+    }
+
+    threads.push_str("</threads>\n");
+
+    Some(String::from_utf8(gdb_sanitize_file(threads.as_bytes(), offset, length)).unwrap())
 }
 
 pub(crate) fn read_general_registers(mut core: Core) -> Option<String> {
@@ -50,13 +124,23 @@ pub(crate) fn read_general_registers(mut core: Core) -> Option<String> {
         }
     }
 
-    let mut general_registers_value = String::new();
+    let mut registers = Vec::with_capacity(core.num_general_registers());
 
     for reg in 0..core.num_general_registers() {
-        let (probe_rs_number, bytesize) = core.translate_gdb_register_number(reg as u32)?;
+        registers.push(core.translate_gdb_register_number(reg as u32)?);
+    }
+
+    let addresses: Vec<_> = registers
+        .iter()
+        .map(|(probe_rs_number, _)| *probe_rs_number)
+        .collect();
 
-        let mut value = core.read_core_reg(probe_rs_number).unwrap();
+    // All registers are read together, which is much faster than reading them one by one.
+    let values = core.read_core_regs(&addresses).unwrap();
 
+    let mut general_registers_value = String::new();
+
+    for ((_, bytesize), mut value) in registers.into_iter().zip(values) {
         for _ in 0..bytesize {
             let byte = value as u8;
             general_registers_value.push_str(&format!("{:02x}", byte));
@@ -136,6 +220,7 @@ pub(crate) fn write_general_registers(reg_values: &str, mut core: Core) -> Optio
     }
 
     let mut current_str_regval_offset = 0;
+    let mut registers = Vec::with_capacity(core.num_general_registers());
 
     for reg_num in (0..core.num_general_registers() as u32).into_iter() {
         let (addr, bytesize) = core.translate_gdb_register_number(reg_num)?;
@@ -171,7 +256,7 @@ pub(crate) fn write_general_registers(reg_values: &str, mut core: Core) -> Optio
                 u32::from_str_radix(std::str::from_utf8(ch).unwrap(), 16).unwrap() << (8 * exp);
         }
 
-        core.write_core_reg(addr, value).unwrap();
+        registers.push((addr, value));
 
         current_str_regval_offset = current_str_regval_end;
 
@@ -180,6 +265,8 @@ pub(crate) fn write_general_registers(reg_values: &str, mut core: Core) -> Optio
         }
     }
 
+    core.write_core_regs(&registers).unwrap();
+
     reply_ok()
 }
 
@@ -236,10 +323,20 @@ pub(crate) fn write_register(register: u32, hex_value: &str, mut core: Core) ->
     reply_ok()
 }
 
-pub(crate) fn read_memory(address: u32, length: u32, mut core: Core) -> Option<String> {
-    let mut readback_data = vec![0u8; length as usize];
-    match core.read(address, &mut readback_data) {
-        Ok(_) => Some(
+/// Read the memory for an `m` packet, through `cache`, see [`MemoryCache::read`].
+pub(crate) fn read_memory(
+    address: u32,
+    length: u32,
+    mut core: Core,
+    cache: &mut MemoryCache,
+    limit: Option<u32>,
+) -> Option<String> {
+    let result = cache.read(core.id(), address, length, limit, |address, data| {
+        core.read(address, data)
+    });
+
+    match result {
+        Ok(readback_data) => Some(
             readback_data
                 .iter()
                 .map(|s| format!("{:02x?}", s))
@@ -283,7 +380,7 @@ pub(crate) fn stop(mut core: Core, awaits_halt: &mut bool) -> Option<String> {
 pub(crate) fn step(mut core: Core, awaits_halt: &mut bool) -> Option<String> {
     core.step().unwrap();
     *awaits_halt = false;
-    Some("S05".into())
+    Some(format!("T05thread:{:x};", thread_id(core.id())))
 }
 
 pub(crate) fn insert_hardware_break(address: u32, _kind: u32, mut core: Core) -> Option<String> {
@@ -302,16 +399,16 @@ pub(crate) fn write_memory(address: u32, data: &[u8], mut core: Core) -> Option<
     Some("OK".into())
 }
 
-pub(crate) fn get_memory_map(session: &Session) -> Option<String> {
-    let memory_map = session.target().gdb_memory_map();
+pub(crate) fn get_memory_map(session: &Session, offset: u32, length: u32) -> Option<String> {
+    let memory_map = gdb_memory_map(&session.memory_map(), session.target());
 
-    Some(String::from_utf8(gdb_sanitize_file(memory_map.as_bytes(), 0, 1000)).unwrap())
+    Some(String::from_utf8(gdb_sanitize_file(memory_map.as_bytes(), offset, length)).unwrap())
 }
 
 pub(crate) fn user_halt(mut core: Core, awaits_halt: &mut bool) -> Option<String> {
     let _ = core.halt(Duration::from_millis(100));
     *awaits_halt = false;
-    Some("T02".into())
+    Some(format!("T02thread:{:x};", thread_id(core.id())))
 }
 
 pub(crate) fn detach(break_due: &mut bool) -> Option<String> {
@@ -319,9 +416,204 @@ pub(crate) fn detach(break_due: &mut bool) -> Option<String> {
     Some("OK".into())
 }
 
-pub(crate) fn reset_halt(mut core: Core) -> Option<String> {
-    let _cpu_info = core.reset_and_halt(Duration::from_millis(400));
-    Some("OK".into())
+/// Flash `image`, if there is one, then reset and halt the target, to restart the program
+/// in extended-remote mode.
+///
+/// Returns `false` if flashing or the reset failed.
+pub(crate) fn restart(
+    session: &mut Session,
+    image: Option<&Path>,
+    selected: &mut SelectedCores,
+    awaits_halt: &mut bool,
+) -> bool {
+    *awaits_halt = false;
+    *selected = SelectedCores::default();
+
+    if let Some(image) = image {
+        log::info!("Flashing {}", image.display());
+
+        if let Err(e) = download_file(session, image, Format::Elf) {
+            log::error!("Failed to flash {}: {}", image.display(), e);
+            return false;
+        }
+
+        // Keep the symbols in sync with the program, e.g. for `monitor reset` to `main`.
+        if let Err(e) = session.load_symbols(image) {
+            log::warn!("Failed to load the symbols of {}: {}", image.display(), e);
+        }
+    }
+
+    match session
+        .core(0)
+        .and_then(|mut core| core.reset_and_halt(Duration::from_millis(400)))
+    {
+        Ok(_) => true,
+        Err(e) => {
+            log::error!("Failed to reset the target: {}", e);
+            false
+        }
+    }
+}
+
+/// Restart the program for `vRun`.
+///
+/// The program in `filename` is flashed if GDB specifies one, otherwise `restart_image`.
+pub(crate) fn run_program(
+    session: &mut Session,
+    filename: &[u8],
+    restart_image: Option<&Path>,
+    selected: &mut SelectedCores,
+    awaits_halt: &mut bool,
+) -> Option<String> {
+    let filename = String::from_utf8_lossy(filename);
+
+    let image = if filename.is_empty() {
+        restart_image
+    } else {
+        Some(Path::new(filename.as_ref()))
+    };
+
+    if restart(session, image, selected, awaits_halt) {
+        halt_reason(selected.execution)
+    } else {
+        Some("E01".into())
+    }
+}
+
+/// Halt all cores for `vKill`, as the program can't be killed until it is restarted.
+pub(crate) fn kill(session: &mut Session, awaits_halt: &mut bool) -> Option<String> {
+    *awaits_halt = false;
+
+    for core in 0..session.list_cores().len() {
+        if let Err(e) = session
+            .core(core)
+            .and_then(|mut core| core.halt(Duration::from_millis(100)))
+        {
+            log::warn!("Failed to halt core {}: {}", core, e);
+        }
+    }
+
+    reply_ok()
+}
+
+/// Reset `core` for `monitor reset`, and run or halt it according to `behavior`.
+pub(crate) fn reset(
+    session: &mut Session,
+    behavior: GdbResetBehavior,
+    core: usize,
+) -> Option<String> {
+    let main = session.symbol_address("main");
+    let mut core = match session.core(core) {
+        Ok(core) => core,
+        Err(e) => {
+            log::error!("Failed to reset the target: {}", e);
+            return Some("E01".into());
+        }
+    };
+
+    let result = match behavior {
+        GdbResetBehavior::Halt => core.reset_and_halt(Duration::from_millis(400)).map(|_| ()),
+        GdbResetBehavior::Run => core.reset(),
+        GdbResetBehavior::RunToMain => match main {
+            Some(main) => run_to(&mut core, main),
+            None => {
+                log::warn!("There is no symbol for 'main', the target is halted after the reset");
+                core.reset_and_halt(Duration::from_millis(400)).map(|_| ())
+            }
+        },
+    };
+
+    match result {
+        Ok(()) => Some("OK".into()),
+        Err(e) => {
+            log::error!("Failed to reset the target: {}", e);
+            Some("E01".into())
+        }
+    }
+}
+
+/// Reset and halt `core`, then run it until it reaches `address`.
+fn run_to(core: &mut Core, address: u32) -> Result<(), probe_rs::Error> {
+    core.reset_and_halt(Duration::from_millis(400))?;
+
+    core.set_hw_breakpoint(address)?;
+    core.run()?;
+
+    let result = core.wait_for_core_halted(Duration::from_secs(1));
+    if result.is_err() {
+        log::warn!("The target didn't reach {:#010x}, halting it", address);
+        core.halt(Duration::from_millis(100))?;
+    }
+
+    core.clear_hw_breakpoint(address)
+}
+
+/// Start collecting the data of a `load` command for `vFlashErase`.
+///
+/// The flash is erased together with writing it when GDB sends `vFlashDone`, so only the
+/// sectors which are written are erased.
+pub(crate) fn flash_erase(
+    session: &Session,
+    pending_flash: &mut Option<FlashLoader>,
+    address: u32,
+    length: u32,
+) -> Option<String> {
+    log::debug!(
+        "Erase request for {:#010x}..{:#010x}",
+        address,
+        address + length
+    );
+
+    pending_flash.get_or_insert_with(|| session.target().flash_loader());
+
+    reply_ok()
+}
+
+/// Add the data of `vFlashWrite` to the data which is flashed by `vFlashDone`.
+pub(crate) fn flash_write(
+    session: &Session,
+    pending_flash: &mut Option<FlashLoader>,
+    address: u32,
+    data: &[u8],
+) -> Option<String> {
+    let loader = pending_flash.get_or_insert_with(|| session.target().flash_loader());
+
+    match loader.add_data(address, data) {
+        Ok(()) => reply_ok(),
+        Err(e) => {
+            log::error!(
+                "Failed to add {} bytes at {:#010x}: {}",
+                data.len(),
+                address,
+                e
+            );
+            Some("E01".into())
+        }
+    }
+}
+
+/// Flash the data of the `load` command for `vFlashDone`, and read it back if `verify`
+/// is set.
+pub(crate) fn flash_done(
+    session: &mut Session,
+    pending_flash: &mut Option<FlashLoader>,
+    verify: bool,
+) -> Option<String> {
+    let loader = match pending_flash.take() {
+        Some(loader) => loader,
+        None => return reply_ok(),
+    };
+
+    let mut options = DownloadOptions::new();
+    options.verify = verify;
+
+    match loader.commit(session, options) {
+        Ok(()) => reply_ok(),
+        Err(e) => {
+            log::error!("Failed to flash the data of the load command: {}", e);
+            Some("E01".into())
+        }
+    }
 }
 
 fn gdb_sanitize_file(data: &[u8], offset: u32, len: u32) -> Vec<u8> {
@@ -3,9 +3,10 @@
 mod architecture;
 mod gdb_server_async;
 mod handlers;
+mod memory_cache;
 mod parser;
 mod reader;
 mod worker;
 mod writer;
 
-pub use gdb_server_async::run;
+pub use gdb_server_async::{run, serve};
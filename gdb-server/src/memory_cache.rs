@@ -0,0 +1,210 @@
+//! Read-ahead for the memory reads of GDB.
+//!
+//! GDB splits large reads, e.g. for `x/1000x` or the memory views of IDEs, into many `m`
+//! packets of a few hundred bytes, and each of them costs at least one round trip to the
+//! probe. Instead, the server reads ahead of the requested range, and serves the following
+//! `m` packets from the data which was read ahead, until the target runs or its memory is
+//! written.
+
+use std::time::{Duration, Instant};
+
+use probe_rs::config::MemoryRegion;
+use probe_rs::Target;
+
+/// The number of bytes which are read for an `m` packet, if the packet requests less.
+const READ_AHEAD: u32 = 0x1000;
+
+/// The time after which the data which was read ahead is not used anymore, as it might
+/// have been changed by DMA or by other cores.
+const MAX_AGE: Duration = Duration::from_millis(500);
+
+/// The memory of a core, which was read ahead.
+#[derive(Debug)]
+struct Block {
+    core: usize,
+    address: u32,
+    data: Vec<u8>,
+    read_at: Instant,
+}
+
+impl Block {
+    /// The data of `core` at `address`, if it was read ahead and is recent enough.
+    fn get(&self, core: usize, address: u32, length: u32) -> Option<&[u8]> {
+        if self.core != core || self.read_at.elapsed() > MAX_AGE {
+            return None;
+        }
+
+        let offset = address.checked_sub(self.address)? as usize;
+        self.data.get(offset..offset.checked_add(length as usize)?)
+    }
+}
+
+/// The memory which was read ahead for `m` packets.
+///
+/// The worker invalidates the cache for every packet which can change the memory, i.e.
+/// which runs or steps the target, or writes its memory or registers.
+#[derive(Debug, Default)]
+pub(crate) struct MemoryCache {
+    block: Option<Block>,
+}
+
+impl MemoryCache {
+    /// Drop the data which was read ahead.
+    pub(crate) fn invalidate(&mut self) {
+        self.block = None;
+    }
+
+    /// Read `length` bytes at `address` of `core`.
+    ///
+    /// The data is returned from the cache if it was read ahead before. Otherwise, `read`
+    /// reads [`READ_AHEAD`] bytes, but not past `limit`, and the data after the requested
+    /// range is kept for the following reads. If `limit` is `None`, only the requested
+    /// range is read, and nothing is cached.
+    pub(crate) fn read<E>(
+        &mut self,
+        core: usize,
+        address: u32,
+        length: u32,
+        limit: Option<u32>,
+        mut read: impl FnMut(u32, &mut [u8]) -> Result<(), E>,
+    ) -> Result<Vec<u8>, E> {
+        if let Some(data) = self
+            .block
+            .as_ref()
+            .and_then(|block| block.get(core, address, length))
+        {
+            log::debug!(
+                "Reading {} bytes at {:#010x} from the cache",
+                length,
+                address
+            );
+            return Ok(data.to_vec());
+        }
+
+        self.block = None;
+
+        let end = address.saturating_add(length);
+
+        if let Some(limit) = limit {
+            let read_ahead_end = address.saturating_add(READ_AHEAD).min(limit);
+
+            if read_ahead_end > end {
+                let mut data = vec![0; (read_ahead_end - address) as usize];
+
+                // The memory after the requested range might not be readable, so the
+                // requested range is read on its own if this fails.
+                if read(address, &mut data).is_ok() {
+                    let requested = data[..length as usize].to_vec();
+
+                    self.block = Some(Block {
+                        core,
+                        address,
+                        data,
+                        read_at: Instant::now(),
+                    });
+
+                    return Ok(requested);
+                }
+            }
+        }
+
+        let mut data = vec![0; length as usize];
+        read(address, &mut data)?;
+
+        Ok(data)
+    }
+}
+
+/// The end of the RAM or flash region of `target` which contains `address`, which limits
+/// how far it is read ahead.
+///
+/// Returns `None` for other memory, e.g. peripherals, where reads can have side effects.
+pub(crate) fn read_ahead_limit(target: &Target, address: u32) -> Option<u32> {
+    target.memory_map.iter().find_map(|region| match region {
+        MemoryRegion::Ram(region) if region.range.contains(&address) => Some(region.range.end),
+        MemoryRegion::Nvm(region) if region.range.contains(&address) => Some(region.range.end),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MemoryCache, READ_AHEAD};
+
+    /// Memory where each byte is the lowest byte of its address. The address and length of
+    /// each read are recorded in `reads`.
+    fn memory(reads: &mut Vec<(u32, usize)>) -> impl FnMut(u32, &mut [u8]) -> Result<(), ()> + '_ {
+        move |address, data| {
+            reads.push((address, data.len()));
+
+            for (offset, byte) in data.iter_mut().enumerate() {
+                *byte = (address as usize + offset) as u8;
+            }
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn read_from_cache() {
+        let mut cache = MemoryCache::default();
+        let mut reads = Vec::new();
+
+        let first = cache
+            .read(0, 0x2000_0000, 0x10, Some(0x2001_0000), memory(&mut reads))
+            .unwrap();
+        let second = cache
+            .read(0, 0x2000_0010, 0x10, Some(0x2001_0000), memory(&mut reads))
+            .unwrap();
+
+        assert_eq!(first, (0x00..0x10).collect::<Vec<u8>>());
+        assert_eq!(second, (0x10..0x20).collect::<Vec<u8>>());
+        assert_eq!(reads, vec![(0x2000_0000, READ_AHEAD as usize)]);
+    }
+
+    #[test]
+    fn read_ahead_up_to_limit() {
+        let mut cache = MemoryCache::default();
+        let mut reads = Vec::new();
+
+        cache
+            .read(0, 0x2000_0000, 0x10, Some(0x2000_0100), memory(&mut reads))
+            .unwrap();
+        cache
+            .read(0, 0x2000_00f8, 0x10, Some(0x2000_0100), memory(&mut reads))
+            .unwrap();
+
+        assert_eq!(reads, vec![(0x2000_0000, 0x100), (0x2000_00f8, 0x10)]);
+    }
+
+    #[test]
+    fn invalidated_and_uncached_reads() {
+        let mut cache = MemoryCache::default();
+        let mut reads = Vec::new();
+
+        cache
+            .read(0, 0x2000_0000, 0x10, Some(0x2001_0000), memory(&mut reads))
+            .unwrap();
+        cache.invalidate();
+        cache
+            .read(0, 0x2000_0010, 0x10, Some(0x2001_0000), memory(&mut reads))
+            .unwrap();
+        // The memory of core 0 is not used for core 1.
+        cache
+            .read(1, 0x2000_0020, 0x10, Some(0x2001_0000), memory(&mut reads))
+            .unwrap();
+        cache
+            .read(1, 0x4000_0000, 0x4, None, memory(&mut reads))
+            .unwrap();
+
+        assert_eq!(
+            reads,
+            vec![
+                (0x2000_0000, READ_AHEAD as usize),
+                (0x2000_0010, READ_AHEAD as usize),
+                (0x2000_0020, READ_AHEAD as usize),
+                (0x4000_0000, 0x4),
+            ]
+        );
+    }
+}
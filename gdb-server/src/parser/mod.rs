@@ -20,7 +20,8 @@ use query::query_packet;
 use v_packet::v_packet;
 
 pub use query::{Pid, QueryPacket};
-use util::hex_u64;
+pub use util::ThreadId;
+use util::{hex_u64, thread_id};
 pub use v_packet::VPacket;
 
 #[allow(dead_code)]
@@ -61,7 +62,10 @@ pub enum Packet {
         reg_values: String,
     },
     /// Packet `H`
-    SelectThread,
+    SelectThread {
+        operation: ThreadOperation,
+        thread: ThreadId,
+    },
     /// Packet `i`
     StepClockCycle,
     /// Packet `I`
@@ -97,7 +101,7 @@ pub enum Packet {
     // Packet 't'
     SearchBackwards,
     // Packet 'T'
-    ThreadInfo,
+    ThreadAlive(ThreadId),
     // Packet 'v'
     V(VPacket),
     // Packet 'X'
@@ -121,6 +125,15 @@ pub enum Packet {
     Interrupt,
 }
 
+/// The operations for which a thread is selected with the `H` packet.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ThreadOperation {
+    /// `Hg`, for register and memory accesses.
+    General,
+    /// `Hc`, for step and continue.
+    Continue,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum BreakpointType {
     Software,
@@ -147,6 +160,9 @@ pub fn parse_packet(input: &[u8]) -> Result<Packet> {
         continue_packet,
         write_register,
         write_register_hex,
+        select_thread,
+        thread_alive,
+        restart,
     ))(input);
 
     match parse_result {
@@ -169,6 +185,13 @@ fn continue_packet(input: &[u8]) -> IResult<&[u8], Packet> {
     Ok((input, Packet::Continue))
 }
 
+fn restart(input: &[u8]) -> IResult<&[u8], Packet> {
+    let (_input, _) = char('R')(input)?;
+
+    // The argument of the packet has no meaning, and is ignored.
+    Ok((&[], Packet::Restart))
+}
+
 fn detach(input: &[u8]) -> IResult<&[u8], Packet> {
     value(Packet::Detach, char('D'))(input)
 }
@@ -218,6 +241,27 @@ fn write_register_hex(input: &[u8]) -> IResult<&[u8], Packet> {
     ))
 }
 
+fn select_thread(input: &[u8]) -> IResult<&[u8], Packet> {
+    let (input, _) = char('H')(input)?;
+
+    let (input, operation) = alt((
+        value(ThreadOperation::General, char('g')),
+        value(ThreadOperation::Continue, char('c')),
+    ))(input)?;
+
+    let (input, thread) = thread_id(input)?;
+
+    Ok((input, Packet::SelectThread { operation, thread }))
+}
+
+fn thread_alive(input: &[u8]) -> IResult<&[u8], Packet> {
+    let (input, _) = char('T')(input)?;
+
+    let (input, thread) = thread_id(input)?;
+
+    Ok((input, Packet::ThreadAlive(thread)))
+}
+
 fn query(input: &[u8]) -> IResult<&[u8], Packet> {
     let (input, _) = char('q')(input)?;
     let (input, packet) = query_packet(input)?;
@@ -337,6 +381,7 @@ mod test {
             ("c", Packet::Continue),
             ("g", Packet::ReadGeneralRegister),
             ("D", Packet::Detach),
+            ("R00", Packet::Restart),
             ("qSupported", Packet::Query(QueryPacket::Supported(vec![]))),
             ("qHostInfo", Packet::Query(QueryPacket::HostInfo)),
             ("vCont?", Packet::V(VPacket::QueryContSupport)),
@@ -452,6 +497,33 @@ mod test {
         );
     }
 
+    #[test]
+    fn parse_select_thread() {
+        assert_eq!(
+            parse_packet(b"Hg2").unwrap(),
+            Packet::SelectThread {
+                operation: ThreadOperation::General,
+                thread: ThreadId::Id(2),
+            }
+        );
+
+        assert_eq!(
+            parse_packet(b"Hc-1").unwrap(),
+            Packet::SelectThread {
+                operation: ThreadOperation::Continue,
+                thread: ThreadId::All,
+            }
+        );
+    }
+
+    #[test]
+    fn parse_thread_alive() {
+        assert_eq!(
+            parse_packet(b"T1").unwrap(),
+            Packet::ThreadAlive(ThreadId::Id(1))
+        );
+    }
+
     #[test]
     fn parse_interrupt() {
         assert_eq!(parse_packet(&[0x03]).unwrap(), Packet::Interrupt);
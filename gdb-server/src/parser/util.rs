@@ -1,4 +1,37 @@
-use nom::{bytes::complete::take_while_m_n, character::is_hex_digit, multi::many1, IResult};
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_while_m_n},
+    character::is_hex_digit,
+    combinator::{map, value},
+    multi::many1,
+    number::complete::hex_u32,
+    IResult,
+};
+
+/// A thread ID in a GDB packet.
+///
+/// probe-rs only supports a single process, so the multiprocess syntax `pPID.TID` is not
+/// supported.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ThreadId {
+    /// `-1`, all threads.
+    All,
+    /// `0`, any thread.
+    Any,
+    /// A specific thread, which is never zero.
+    Id(u32),
+}
+
+/// Parse a thread ID, which is a hex number, or `-1` for all threads.
+pub fn thread_id(input: &[u8]) -> IResult<&[u8], ThreadId> {
+    alt((
+        value(ThreadId::All, tag("-1")),
+        map(hex_u32, |id| match id {
+            0 => ThreadId::Any,
+            id => ThreadId::Id(id),
+        }),
+    ))(input)
+}
 
 /// Parse bytes encoded as a ASCII hex string.
 ///
@@ -62,6 +95,13 @@ mod test {
         assert_eq!(hex_byte(b"853").unwrap(), ("3".as_bytes(), 0x85));
     }
 
+    #[test]
+    fn parse_thread_id() {
+        assert_eq!(thread_id(b"-1").unwrap(), (EMPTY, ThreadId::All));
+        assert_eq!(thread_id(b"0").unwrap(), (EMPTY, ThreadId::Any));
+        assert_eq!(thread_id(b"1f").unwrap(), (EMPTY, ThreadId::Id(0x1f)));
+    }
+
     #[test]
     fn parse_hex_u64() {
         assert_eq!(hex_u64(b"0").unwrap(), (EMPTY, 0x0));
@@ -1,12 +1,34 @@
 use super::{query::pid, Pid};
 use nom::{
-    branch::alt, bytes::complete::tag, character::complete::char, combinator::value, IResult,
+    branch::alt, bytes::complete::tag, character::complete::char, combinator::value,
+    error::ErrorKind, number::complete::hex_u32, IResult,
 };
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum VPacket {
     Attach(Pid),
     Continue(Action),
+    /// `vKill`, kill the process, in extended mode.
+    Kill(Pid),
+    /// `vRun`, restart the program, in extended mode.
+    ///
+    /// The filename is empty if GDB doesn't specify a program.
+    Run {
+        filename: Vec<u8>,
+        arguments: Vec<Vec<u8>>,
+    },
+    /// `vFlashErase`, erase the flash in the given range.
+    FlashErase {
+        address: u32,
+        length: u32,
+    },
+    /// `vFlashWrite`, write `data` to the flash, after it was erased.
+    FlashWrite {
+        address: u32,
+        data: Vec<u8>,
+    },
+    /// `vFlashDone`, all data of the `load` command was written with `vFlashWrite`.
+    FlashDone,
     Unknown(Vec<u8>),
     QueryContSupport,
 }
@@ -23,7 +45,16 @@ pub enum Action {
 }
 
 pub fn v_packet(input: &[u8]) -> IResult<&[u8], VPacket> {
-    let parse_result = alt((v_attach, v_cont_support, v_cont))(input);
+    let parse_result = alt((
+        v_attach,
+        v_cont_support,
+        v_cont,
+        v_kill,
+        v_run,
+        v_flash_erase,
+        v_flash_write,
+        v_flash_done,
+    ))(input);
 
     match parse_result {
         Ok((input, packet)) => Ok((input, packet)),
@@ -44,6 +75,68 @@ fn v_attach(input: &[u8]) -> IResult<&[u8], VPacket> {
     Ok((input, VPacket::Attach(pid)))
 }
 
+fn v_kill(input: &[u8]) -> IResult<&[u8], VPacket> {
+    let (input, _) = tag("Kill;")(input)?;
+
+    let (input, pid) = pid(input)?;
+
+    Ok((input, VPacket::Kill(pid)))
+}
+
+fn v_run(input: &[u8]) -> IResult<&[u8], VPacket> {
+    let (input, _) = tag("Run;")(input)?;
+
+    // The filename and the arguments are hex encoded, and separated by ';'.
+    let mut parts = input.split(|&c| c == b';').map(|part| {
+        hex::decode(part)
+            .map_err(|_e| nom::Err::Failure(nom::error::Error::new(part, ErrorKind::HexDigit)))
+    });
+
+    // note(unwrap): split always returns at least one part.
+    let filename = parts.next().unwrap()?;
+    let arguments = parts.collect::<Result<_, _>>()?;
+
+    Ok((
+        &[],
+        VPacket::Run {
+            filename,
+            arguments,
+        },
+    ))
+}
+
+fn v_flash_erase(input: &[u8]) -> IResult<&[u8], VPacket> {
+    let (input, _) = tag("FlashErase:")(input)?;
+
+    let (input, address) = hex_u32(input)?;
+    let (input, _) = char(',')(input)?;
+    let (input, length) = hex_u32(input)?;
+
+    Ok((input, VPacket::FlashErase { address, length }))
+}
+
+fn v_flash_write(input: &[u8]) -> IResult<&[u8], VPacket> {
+    let (input, _) = tag("FlashWrite:")(input)?;
+
+    let (input, address) = hex_u32(input)?;
+    let (input, _) = char(':')(input)?;
+
+    // The data is binary, and takes the rest of the packet.
+    Ok((
+        &[],
+        VPacket::FlashWrite {
+            address,
+            data: input.to_owned(),
+        },
+    ))
+}
+
+fn v_flash_done(input: &[u8]) -> IResult<&[u8], VPacket> {
+    let (input, _) = tag("FlashDone")(input)?;
+
+    Ok((input, VPacket::FlashDone))
+}
+
 fn v_cont_support(input: &[u8]) -> IResult<&[u8], VPacket> {
     let (input, _) = tag("Cont?")(input)?;
 
@@ -101,6 +194,69 @@ mod test {
         );
     }
 
+    #[test]
+    fn parse_v_run() {
+        assert_eq!(
+            v_packet(b"Run;").unwrap(),
+            (
+                EMPTY,
+                VPacket::Run {
+                    filename: vec![],
+                    arguments: vec![]
+                }
+            )
+        );
+
+        assert_eq!(
+            v_packet(b"Run;612e656c66;2d76").unwrap(),
+            (
+                EMPTY,
+                VPacket::Run {
+                    filename: b"a.elf".to_vec(),
+                    arguments: vec![b"-v".to_vec()]
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn parse_v_kill() {
+        assert_eq!(v_packet(b"Kill;1").unwrap(), (EMPTY, VPacket::Kill(1)));
+    }
+
+    #[test]
+    fn parse_v_flash_erase() {
+        assert_eq!(
+            v_packet(b"FlashErase:08000000,4000").unwrap(),
+            (
+                EMPTY,
+                VPacket::FlashErase {
+                    address: 0x800_0000,
+                    length: 0x4000
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn parse_v_flash_write() {
+        assert_eq!(
+            v_packet(b"FlashWrite:8000000:\x00:;\xff").unwrap(),
+            (
+                EMPTY,
+                VPacket::FlashWrite {
+                    address: 0x800_0000,
+                    data: b"\x00:;\xff".to_vec()
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn parse_v_flash_done() {
+        assert_eq!(v_packet(b"FlashDone").unwrap(), (EMPTY, VPacket::FlashDone));
+    }
+
     #[test]
     fn parse_v_cont_stop() {
         assert_eq!(
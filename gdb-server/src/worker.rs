@@ -4,11 +4,12 @@ use futures::channel::mpsc;
 use futures::future::FutureExt;
 use futures::select;
 use gdb_protocol::packet::{CheckedPacket, Kind as PacketKind};
-use probe_rs::Session;
+use probe_rs::{flashing::FlashLoader, GdbConfig, Session};
 use std::convert::TryFrom;
 use std::{sync::Mutex, time::Duration};
 
-use crate::parser::parse_packet;
+use crate::memory_cache::{read_ahead_limit, MemoryCache};
+use crate::parser::{parse_packet, Packet, QueryPacket};
 
 use crate::handlers;
 
@@ -16,34 +17,87 @@ type ServerResult<T> = std::result::Result<T, Box<dyn std::error::Error + Send +
 type Sender<T> = mpsc::UnboundedSender<T>;
 type Receiver<T> = mpsc::UnboundedReceiver<T>;
 
+/// The cores selected by the `Hg` and `Hc` packets, as indices of the cores of the session.
+///
+/// Each core is shown as a thread in GDB, see [`thread_id`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SelectedCores {
+    /// The core for register and memory accesses, selected with `Hg`.
+    pub general: usize,
+    /// The core which is stepped and continued, selected with `Hc`.
+    pub execution: usize,
+}
+
+/// The state of a GDB connection, which is kept between packets.
+#[derive(Default)]
+pub struct ConnectionState {
+    /// The cores selected by `Hg` and `Hc`.
+    pub selected: SelectedCores,
+    /// The data of a `load` command, which is flashed when GDB sends `vFlashDone`.
+    pub pending_flash: Option<FlashLoader>,
+    /// The memory which was read ahead for `m` packets.
+    pub(crate) memory_cache: MemoryCache,
+}
+
+/// The GDB thread ID of a core. Thread IDs start at 1, because GDB uses 0 for any thread.
+pub fn thread_id(core: usize) -> usize {
+    core + 1
+}
+
 pub async fn worker(
     mut input_stream: Receiver<CheckedPacket>,
     output_stream: Sender<CheckedPacket>,
     session: &Mutex<Session>,
+    config: &GdbConfig,
 ) -> ServerResult<()> {
     // When we first attach to the core, GDB expects us to halt the core, so we do this here when a new client connects.
     // If the core is already halted, nothing happens if we issue a halt command again, so we always do this no matter of core state.
-    session
-        .lock()
-        .unwrap()
-        .core(0)?
-        .halt(Duration::from_millis(100))?;
+    {
+        let mut session = session.lock().unwrap();
+
+        session.core(0)?.halt(Duration::from_millis(100))?;
+
+        // The other cores are shown as threads, which GDB expects to be halted as well.
+        for core in 1..session.list_cores().len() {
+            if let Err(e) = session
+                .core(core)
+                .and_then(|mut core| core.halt(Duration::from_millis(100)))
+            {
+                log::warn!("Failed to halt core {}: {}", core, e);
+            }
+        }
+    }
 
     let mut awaits_halt = false;
+    let mut state = ConnectionState::default();
 
     loop {
         select! {
             potential_packet = input_stream.next().fuse() => {
                 if let Some(packet) = potential_packet {
                     log::warn!("WORKING {}", String::from_utf8_lossy(&packet.data));
-                    if handler(session, &output_stream, &mut awaits_halt, packet).await? {
+                    let break_due = handler(
+                        session,
+                        &output_stream,
+                        &mut awaits_halt,
+                        &mut state,
+                        config,
+                        packet,
+                    )
+                    .await?;
+                    if break_due {
                         break;
                     }
                 } else {
                     break
                 }
             },
-            _ = await_halt(session, &output_stream, &mut awaits_halt).fuse() => {}
+            _ = await_halt(
+                session,
+                &output_stream,
+                &mut awaits_halt,
+                state.selected.execution,
+            ).fuse() => {}
         }
     }
     Ok(())
@@ -53,30 +107,80 @@ pub async fn handler(
     session: &Mutex<Session>,
     output_stream: &Sender<CheckedPacket>,
     awaits_halt: &mut bool,
+    state: &mut ConnectionState,
+    config: &GdbConfig,
     packet: CheckedPacket,
 ) -> ServerResult<bool> {
     let parsed_packet = parse_packet(&packet.data);
     let mut break_due = false;
 
+    let ConnectionState {
+        selected,
+        pending_flash,
+        memory_cache,
+    } = state;
+
+    // The memory read ahead can only be used while the target is halted, and until a packet
+    // changes its memory.
+    match &parsed_packet {
+        Ok(parsed_packet) if !*awaits_halt && keeps_memory(parsed_packet) => {}
+        _ => memory_cache.invalidate(),
+    }
+
     use crate::parser::v_packet::Action;
     use crate::parser::BreakpointType;
     use crate::parser::Packet::*;
-    use crate::parser::QueryPacket;
     use crate::parser::VPacket;
 
     let response: Option<String> = match parsed_packet {
         Ok(parsed_packet) => {
             log::debug!("Parsed packet: {:?}", parsed_packet);
             let mut session = session.lock().expect("Poisoned Mutex");
+            let num_cores = session.list_cores().len();
             match parsed_packet {
-                HaltReason => handlers::halt_reason(),
-                Continue => handlers::run(session.core(0)?, awaits_halt),
+                HaltReason => handlers::halt_reason(selected.execution),
+                EnableExtendedMode => handlers::reply_ok(),
+                Restart => {
+                    // GDB doesn't expect a reply to `R`.
+                    handlers::restart(
+                        &mut session,
+                        config.restart_image.as_deref(),
+                        selected,
+                        awaits_halt,
+                    );
+                    None
+                }
+                V(VPacket::Run { filename, .. }) => handlers::run_program(
+                    &mut session,
+                    &filename,
+                    config.restart_image.as_deref(),
+                    selected,
+                    awaits_halt,
+                ),
+                V(VPacket::Kill(_)) => handlers::kill(&mut session, awaits_halt),
+                V(VPacket::FlashErase { address, length }) => {
+                    handlers::flash_erase(&session, pending_flash, address, length)
+                }
+                V(VPacket::FlashWrite { address, data }) => {
+                    handlers::flash_write(&session, pending_flash, address, &data)
+                }
+                V(VPacket::FlashDone) => {
+                    handlers::flash_done(&mut session, pending_flash, config.verify)
+                }
+                Continue => handlers::run(session.core(selected.execution)?, awaits_halt),
+                SelectThread { operation, thread } => {
+                    handlers::select_thread(operation, thread, selected, num_cores)
+                }
+                ThreadAlive(thread) => handlers::thread_alive(thread, num_cores),
+                Query(QueryPacket::ThreadId) => handlers::current_thread(selected),
+                Query(QueryPacket::FirstThreadInfo) => handlers::first_thread_info(num_cores),
+                Query(QueryPacket::SubsequentThreadInfo) => handlers::subsequent_thread_info(),
                 V(VPacket::QueryContSupport) => handlers::vcont_supported(),
                 Query(QueryPacket::Supported { .. }) => handlers::q_supported(),
                 Query(QueryPacket::Attached { .. }) => handlers::q_attached(),
                 Query(QueryPacket::Command(cmd)) => {
                     if cmd == b"reset" {
-                        handlers::reset_halt(session.core(0)?)
+                        handlers::reset(&mut session, config.reset, selected.execution)
                     } else {
                         log::debug!("Unknown monitor command: '{:?}'", cmd);
                         Some(hex::encode(
@@ -86,20 +190,37 @@ pub async fn handler(
                     }
                 }
                 Query(QueryPacket::HostInfo) => handlers::host_info(),
-                ReadGeneralRegister => handlers::read_general_registers(session.core(0)?),
-                ReadRegisterHex(register) => handlers::read_register(register, session.core(0)?),
+                ReadGeneralRegister => {
+                    handlers::read_general_registers(session.core(selected.general)?)
+                }
+                ReadRegisterHex(register) => {
+                    handlers::read_register(register, session.core(selected.general)?)
+                }
                 WriteGeneralRegister { reg_values } => {
-                    handlers::write_general_registers(&reg_values, session.core(0)?)
+                    handlers::write_general_registers(&reg_values, session.core(selected.general)?)
                 }
                 WriteRegisterHex { address, value } => {
-                    handlers::write_register(address, &value, session.core(0)?)
+                    handlers::write_register(address, &value, session.core(selected.general)?)
                 }
                 ReadMemory { address, length } => {
                     // LLDB will send 64 bit addresses, which are not supported by probe-rs
                     // yet.
 
                     if let Ok(address) = u32::try_from(address) {
-                        handlers::read_memory(address, length, session.core(0)?)
+                        // Reading ahead while the target runs would return outdated data.
+                        let limit = if *awaits_halt {
+                            None
+                        } else {
+                            read_ahead_limit(session.target(), address)
+                        };
+
+                        handlers::read_memory(
+                            address,
+                            length,
+                            session.core(selected.general)?,
+                            memory_cache,
+                            limit,
+                        )
                     } else {
                         //
                         handlers::reply_empty()
@@ -107,9 +228,11 @@ pub async fn handler(
                 }
                 Detach => handlers::detach(&mut break_due),
                 V(VPacket::Continue(action)) => match action {
-                    Action::Continue => handlers::run(session.core(0)?, awaits_halt),
-                    Action::Stop => handlers::stop(session.core(0)?, awaits_halt),
-                    Action::Step => handlers::step(session.core(0)?, awaits_halt),
+                    Action::Continue => {
+                        handlers::run(session.core(selected.execution)?, awaits_halt)
+                    }
+                    Action::Stop => handlers::stop(session.core(selected.execution)?, awaits_halt),
+                    Action::Step => handlers::step(session.core(selected.execution)?, awaits_halt),
                     other => {
                         log::warn!("vCont with action {:?} not supported", other);
                         handlers::reply_empty()
@@ -120,9 +243,11 @@ pub async fn handler(
                     address,
                     kind,
                 } => match breakpoint_type {
-                    BreakpointType::Hardware => {
-                        handlers::insert_hardware_break(address, kind, session.core(0)?)
-                    }
+                    BreakpointType::Hardware => handlers::insert_hardware_break(
+                        address,
+                        kind,
+                        session.core(selected.general)?,
+                    ),
                     other => {
                         log::warn!("Breakpoint type {:?} is not supported.", other);
                         handlers::reply_empty()
@@ -133,16 +258,18 @@ pub async fn handler(
                     address,
                     kind,
                 } => match breakpoint_type {
-                    BreakpointType::Hardware => {
-                        handlers::remove_hardware_break(address, kind, session.core(0)?)
-                    }
+                    BreakpointType::Hardware => handlers::remove_hardware_break(
+                        address,
+                        kind,
+                        session.core(selected.general)?,
+                    ),
                     other => {
                         log::warn!("Breakpoint type {:?} is not supported.", other);
                         handlers::reply_empty()
                     }
                 },
                 WriteMemoryBinary { address, data } => {
-                    handlers::write_memory(address, &data, session.core(0)?)
+                    handlers::write_memory(address, &data, session.core(selected.general)?)
                 }
                 Query(QueryPacket::Transfer { object, operation }) => {
                     use crate::parser::query::TransferOperation;
@@ -150,8 +277,19 @@ pub async fn handler(
                     match object.as_slice() {
                         b"memory-map" => {
                             match operation {
-                                TransferOperation::Read { .. } => {
-                                    handlers::get_memory_map(&session)
+                                TransferOperation::Read { offset, length, .. } => {
+                                    handlers::get_memory_map(&session, offset, length)
+                                }
+                                TransferOperation::Write { .. } => {
+                                    // not supported
+                                    handlers::reply_empty()
+                                }
+                            }
+                        }
+                        b"threads" => {
+                            match operation {
+                                TransferOperation::Read { offset, length, .. } => {
+                                    handlers::read_threads(&session, offset, length)
                                 }
                                 TransferOperation::Write { .. } => {
                                     // not supported
@@ -176,7 +314,7 @@ pub async fn handler(
                         }
                     }
                 }
-                Interrupt => handlers::user_halt(session.core(0)?, awaits_halt),
+                Interrupt => handlers::user_halt(session.core(selected.execution)?, awaits_halt),
                 other => {
                     log::warn!("Unknown command: '{:?}'", other);
 
@@ -211,16 +349,39 @@ pub async fn handler(
     Ok(break_due)
 }
 
+/// Check if `packet` leaves the memory of the target unchanged, so the memory which was
+/// read ahead for `m` packets can still be used after it.
+fn keeps_memory(packet: &Packet) -> bool {
+    match packet {
+        Packet::ReadMemory { .. }
+        | Packet::ReadGeneralRegister
+        | Packet::ReadRegisterHex(_)
+        | Packet::HaltReason
+        | Packet::SelectThread { .. }
+        | Packet::ThreadAlive(_)
+        | Packet::InsertBreakpoint { .. }
+        | Packet::RemoveBreakpoint { .. } => true,
+        // Monitor commands can reset the target.
+        Packet::Query(QueryPacket::Command(_)) => false,
+        Packet::Query(_) => true,
+        _ => false,
+    }
+}
+
 pub async fn await_halt(
     session: &Mutex<Session>,
     output_stream: &Sender<CheckedPacket>,
     await_halt: &mut bool,
+    core: usize,
 ) -> ServerResult<()> {
     task::sleep(Duration::from_millis(10)).await;
     if *await_halt {
         let mut session = session.lock().expect("Poisoned Mutex");
-        if session.core(0)?.core_halted().unwrap() {
-            let response = CheckedPacket::from_data(PacketKind::Packet, b"T05hwbreak:;".to_vec());
+        if session.core(core)?.core_halted().unwrap() {
+            let response = CheckedPacket::from_data(
+                PacketKind::Packet,
+                format!("T05hwbreak:;thread:{:x};", thread_id(core)).into_bytes(),
+            );
 
             let mut bytes = Vec::new();
             response.encode(&mut bytes).unwrap();
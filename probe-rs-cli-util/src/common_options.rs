@@ -38,7 +38,8 @@ use std::{fs::File, io::Write, path::Path, path::PathBuf};
 use probe_rs::{
     config::{RegistryError, TargetSelector},
     flashing::{FileDownloadError, FlashError, FlashLoader},
-    DebugProbeError, DebugProbeSelector, FakeProbe, Probe, Session, Target, WireProtocol,
+    DebugProbeError, DebugProbeSelector, FakeProbe, Probe, ProtocolTracer, Session, SessionConfig,
+    Target, WireProtocol,
 };
 use structopt::StructOpt;
 
@@ -77,6 +78,24 @@ pub struct FlashOptions {
         help = "Enable this flag to restore all bytes erased in the sector erase but not overwritten by any page."
     )]
     pub restore_unwritten: bool,
+    #[structopt(
+        name = "disable-double-buffering",
+        long = "disable-double-buffering",
+        help = "Use this flag to program one page at a time, instead of loading the next page while the current one is programmed."
+    )]
+    pub disable_double_buffering: bool,
+    #[structopt(
+        name = "erase-all",
+        long = "erase-all",
+        help = "Use this flag to erase the whole chip before flashing, instead of only the sectors which are written."
+    )]
+    pub erase_all: bool,
+    #[structopt(
+        name = "verify",
+        long = "verify",
+        help = "Use this flag to read back and compare the flash contents after programming."
+    )]
+    pub verify: bool,
     #[structopt(
         name = "filename",
         long = "flash-layout",
@@ -130,8 +149,14 @@ impl FlashOptions {
 pub struct ProbeOptions {
     #[structopt(name = "chip", long = "chip")]
     pub chip: Option<String>,
-    #[structopt(name = "chip description file path", long = "chip-description-path")]
-    pub chip_description_path: Option<PathBuf>,
+    #[structopt(
+        name = "chip description file path",
+        long = "chip-description-path",
+        number_of_values = 1,
+        help = "Add the targets of a chip description file to the registry.\n\
+        The flag can be given multiple times to load several files."
+    )]
+    pub chip_description_path: Vec<PathBuf>,
     #[structopt(name = "protocol", long = "protocol")]
     pub protocol: Option<WireProtocol>,
     #[structopt(
@@ -145,27 +170,55 @@ pub struct ProbeOptions {
         help = "Use this flag to assert the nreset & ntrst pins during attaching the probe to the chip."
     )]
     pub connect_under_reset: bool,
+    #[structopt(
+        long = "allow-erase-all",
+        help = "Use this flag to allow erasing the whole chip, if it is locked and can only be unlocked by erasing it."
+    )]
+    pub allow_erase_all: bool,
     #[structopt(name = "speed", long = "speed", help = "The protocol speed in kHz.")]
     pub speed: Option<u32>,
-    #[structopt(long = "dry-run")]
+    #[structopt(
+        long = "protocol-trace",
+        help = "Write a trace of the communication with the probe to this file.\n\
+        Please attach it to bug reports about problems with the probe or the target."
+    )]
+    pub protocol_trace: Option<PathBuf>,
+    #[structopt(
+        long = "dry-run",
+        help = "Show what would be erased and programmed, without connecting to a probe or writing anything."
+    )]
     pub dry_run: bool,
 }
 
 impl ProbeOptions {
-    /// Add targets contained in file given by --chip-description-path
+    /// Add targets contained in the files given by --chip-description-path
     /// to probe-rs registery.
     ///
     /// Note: should be called before [FlashOptions::early_exit] and any other functions in [ProbeOptions].
     pub fn maybe_load_chip_desc(&self) -> Result<(), OperationError> {
-        if let Some(ref cdp) = self.chip_description_path {
+        for cdp in &self.chip_description_path {
             probe_rs::config::add_target_from_yaml(Path::new(cdp)).map_err(|error| {
                 OperationError::FailedChipDescriptionParsing {
                     source: error,
                     path: cdp.clone(),
                 }
-            })
-        } else {
-            Ok(())
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// The [SessionConfig] of the probe and chip selected by the [ProbeOptions].
+    pub fn session_config(&self) -> SessionConfig {
+        SessionConfig {
+            chip: self.chip.clone(),
+            chip_descriptions: self.chip_description_path.clone(),
+            probe: self.probe_selector.clone(),
+            protocol: self.protocol,
+            speed: self.speed,
+            connect_under_reset: self.connect_under_reset,
+            allow_erase_all: self.allow_erase_all,
+            ..SessionConfig::default()
         }
     }
 
@@ -190,8 +243,9 @@ impl ProbeOptions {
     /// Attaches to specified probe and configures it.
     pub fn attach_probe(&self) -> Result<Probe, OperationError> {
         let mut probe = {
+            // A dry run doesn't access the target, so no probe is needed.
             if self.dry_run {
-                Probe::from_specific_probe(Box::new(FakeProbe::new()));
+                return Ok(Probe::from_specific_probe(Box::new(FakeProbe::new())));
             }
 
             // If we got a probe selector as an argument, open the probe
@@ -217,6 +271,17 @@ impl ProbeOptions {
             }
         }?;
 
+        if let Some(path) = &self.protocol_trace {
+            let tracer = ProtocolTracer::to_file(path).map_err(|error| {
+                OperationError::FailedToCreateProtocolTrace {
+                    source: error,
+                    path: path.clone(),
+                }
+            })?;
+
+            probe.set_protocol_tracer(Some(tracer));
+        }
+
         if let Some(protocol) = self.protocol {
             // Select protocol and speed
             probe.select_protocol(protocol).map_err(|error| {
@@ -240,16 +305,19 @@ impl ProbeOptions {
     }
 
     /// Attaches to target device session. Attaches under reset if
-    /// specified by [ProbeOptions::connect_under_reset].
+    /// specified by [ProbeOptions::connect_under_reset], and erases a locked
+    /// chip only if allowed by [ProbeOptions::allow_erase_all].
     pub fn attach_session(
         &self,
         probe: Probe,
         target: TargetSelector,
     ) -> Result<Session, OperationError> {
+        let permissions = self.session_config().permissions();
+
         let session = if self.connect_under_reset {
-            probe.attach_under_reset(target)
+            probe.attach_under_reset_with_permissions(target, permissions)
         } else {
-            probe.attach(target)
+            probe.attach_with_permissions(target, permissions)
         }
         .map_err(|error| OperationError::AttachingFailed {
             source: error,
@@ -474,6 +542,12 @@ pub enum OperationError {
         source: DebugProbeError,
         speed: u32,
     },
+    #[error("Failed to create the protocol trace '{path}'.")]
+    FailedToCreateProtocolTrace {
+        #[source]
+        source: std::io::Error,
+        path: PathBuf,
+    },
     #[error("Connecting to the chip was unsuccessful.")]
     AttachingFailed {
         #[source]
@@ -26,6 +26,28 @@ pub fn run_flash_download(
     let mut download_option = DownloadOptions::default();
     download_option.keep_unwritten_bytes = opt.restore_unwritten;
     download_option.dry_run = opt.probe_options.dry_run;
+    download_option.disable_double_buffering = opt.disable_double_buffering;
+    download_option.do_chip_erase = opt.erase_all;
+    download_option.verify = opt.verify;
+
+    if download_option.dry_run {
+        let plan = loader
+            .plan(session.target(), &download_option)
+            .map_err(|error| OperationError::FlashingFailed {
+                source: error,
+                target: session.target().clone(),
+                target_spec: opt.probe_options.chip.clone(),
+                path: path.to_path_buf(),
+            })?;
+
+        logging::println(format!(
+            "    {} nothing is written",
+            "Dry run,".green().bold()
+        ));
+        logging::println(plan.to_string());
+
+        return Ok(());
+    }
 
     if !opt.disable_progressbars {
         // Create progress bars.
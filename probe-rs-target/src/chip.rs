@@ -1,4 +1,4 @@
-use super::memory::MemoryRegion;
+use super::memory::{FlashPartition, MemoryRegion};
 use crate::CoreType;
 use serde::{Deserialize, Serialize};
 
@@ -30,6 +30,40 @@ pub struct Chip {
     ///
     /// [`ChipFamily::flash_algorithms`]: crate::ChipFamily::flash_algorithms
     pub flash_algorithms: Vec<String>,
+    /// The kind of reset used by default for this chip.
+    ///
+    /// Some chips misbehave with specific reset styles. If this is not set,
+    /// the default reset of the architecture is used.
+    #[serde(default)]
+    #[cfg_attr(
+        not(feature = "bincode"),
+        serde(skip_serializing_if = "Option::is_none")
+    )]
+    pub default_reset: Option<ResetKind>,
+    /// Named partitions of the non-volatile memory.
+    ///
+    /// Chips usually don't have partitions, they are used by target descriptions of
+    /// products, which divide the flash e.g. into a bootloader and an application.
+    #[serde(default)]
+    #[cfg_attr(not(feature = "bincode"), serde(skip_serializing_if = "Vec::is_empty"))]
+    pub partitions: Vec<FlashPartition>,
+}
+
+/// The different ways to reset a core.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResetKind {
+    /// Reset the whole chip using the hardware reset pin (nSRST) of the probe.
+    Hardware,
+    /// A system reset requested by software, e.g. using `AIRCR.SYSRESETREQ`
+    /// on ARM or `ndmreset` on RISC-V.
+    System,
+    /// A reset of the core using `AIRCR.VECTRESET`. This is only available on ARMv7-M cores.
+    Vector,
+    /// A reset of the core only, which leaves the rest of the system untouched.
+    ///
+    /// On ARM, this uses the `ResetProcessor` debug sequence, on RISC-V the `hartreset` bit.
+    Core,
 }
 
 /// An individual core inside a chip
@@ -54,6 +88,8 @@ pub enum CoreAccessOptions {
     Arm(ArmCoreAccessOptions),
     /// Riscv specific options
     Riscv(RiscvCoreAccessOptions),
+    /// Xtensa specific options
+    Xtensa(XtensaCoreAccessOptions),
 }
 
 /// The data required to access an ARM core
@@ -67,4 +103,12 @@ pub struct ArmCoreAccessOptions {
 
 /// The data required to access a Risc-V core
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct RiscvCoreAccessOptions {}
+pub struct RiscvCoreAccessOptions {
+    /// The index of the hart in the debug module, selected using `hartsel`.
+    #[serde(default)]
+    pub hart_id: u32,
+}
+
+/// The data required to access an Xtensa core
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct XtensaCoreAccessOptions {}
@@ -36,6 +36,8 @@ pub enum CoreType {
     Armv8m,
     /// RISC-V
     Riscv,
+    /// Xtensa LX6 and LX7, e.g. the cores of the ESP32 and ESP32-S3
+    Xtensa,
 }
 
 /// The architecture family of a specific [`CoreType`].
@@ -45,6 +47,8 @@ pub enum Architecture {
     Arm,
     /// A RISC-V core.
     Riscv,
+    /// An Xtensa core.
+    Xtensa,
 }
 
 impl CoreType {
@@ -52,6 +56,7 @@ impl CoreType {
     pub fn architecture(&self) -> Architecture {
         match self {
             CoreType::Riscv => Architecture::Riscv,
+            CoreType::Xtensa => Architecture::Xtensa,
             _ => Architecture::Arm,
         }
     }
@@ -0,0 +1,399 @@
+use super::chip_family::ChipFamily;
+use super::memory::{MemoryRange, MemoryRegion};
+use serde::Serialize;
+use std::fmt;
+use std::ops::Range;
+
+/// The severity of a [`Diagnostic`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// The description is probably correct, but unusual.
+    Warning,
+    /// The description is wrong, and using it will fail or flash garbage.
+    Error,
+}
+
+/// A problem in a target description, found by [`ChipFamily::lint`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    /// How bad the problem is.
+    pub severity: Severity,
+    /// The name of the variant with the problem, if it is specific to a variant.
+    pub variant: Option<String>,
+    /// The name of the flash algorithm with the problem, if it is specific to an algorithm.
+    pub algorithm: Option<String>,
+    /// The problem.
+    pub kind: DiagnosticKind,
+}
+
+/// The kinds of problems in a target description, see [`Diagnostic`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticKind {
+    /// The variant uses a flash algorithm which is not part of the family.
+    UnknownFlashAlgorithm,
+    /// The variant has no cores.
+    NoCores,
+    /// The cores of the variant have different architectures.
+    MixedArchitectures,
+    /// Two memory regions of the variant overlap.
+    OverlappingRegions {
+        /// The range of the first region.
+        first: Range<u32>,
+        /// The range of the second region.
+        second: Range<u32>,
+    },
+    /// The code of the flash algorithm is not loaded into a RAM region of the variant.
+    LoadAddressNotInRam {
+        /// The range of the code of the algorithm.
+        range: Range<u32>,
+    },
+    /// The page size of the flash algorithm is zero.
+    ZeroPageSize,
+    /// A sector description of the flash algorithm has a size of zero.
+    ZeroSectorSize {
+        /// The address of the sector description, relative to the start of the flash.
+        address: u32,
+    },
+    /// The sector descriptions of the flash algorithm are not sorted by their address.
+    UnsortedSectors,
+    /// The first sector description of the flash algorithm does not start at the start
+    /// of the flash, so the flash before it has no sectors.
+    MissingFirstSector {
+        /// The address of the first sector description, relative to the start of the flash.
+        address: u32,
+    },
+    /// The sector size is not a multiple of the page size, so pages cross sectors.
+    SectorNotMultipleOfPage {
+        /// The size of the sector.
+        sector_size: u32,
+        /// The size of a page.
+        page_size: u32,
+    },
+}
+
+impl fmt::Display for DiagnosticKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiagnosticKind::UnknownFlashAlgorithm => write!(f, "unknown flash algorithm"),
+            DiagnosticKind::NoCores => write!(f, "no cores"),
+            DiagnosticKind::MixedArchitectures => write!(f, "mixed core architectures"),
+            DiagnosticKind::OverlappingRegions { first, second } => write!(
+                f,
+                "the memory regions {:#010x}..{:#010x} and {:#010x}..{:#010x} overlap",
+                first.start, first.end, second.start, second.end
+            ),
+            DiagnosticKind::LoadAddressNotInRam { range } => write!(
+                f,
+                "the code at {:#010x}..{:#010x} is not in a RAM region",
+                range.start, range.end
+            ),
+            DiagnosticKind::ZeroPageSize => write!(f, "the page size is zero"),
+            DiagnosticKind::ZeroSectorSize { address } => {
+                write!(
+                    f,
+                    "the sectors at offset {:#x} have a size of zero",
+                    address
+                )
+            }
+            DiagnosticKind::UnsortedSectors => {
+                write!(f, "the sectors are not sorted by their address")
+            }
+            DiagnosticKind::MissingFirstSector { address } => write!(
+                f,
+                "the first sectors start at offset {:#x} instead of 0",
+                address
+            ),
+            DiagnosticKind::SectorNotMultipleOfPage {
+                sector_size,
+                page_size,
+            } => write!(
+                f,
+                "the sector size {:#x} is not a multiple of the page size {:#x}",
+                sector_size, page_size
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.severity {
+            Severity::Warning => write!(f, "warning")?,
+            Severity::Error => write!(f, "error")?,
+        }
+
+        if let Some(variant) = &self.variant {
+            write!(f, " in variant `{}`", variant)?;
+        }
+
+        if let Some(algorithm) = &self.algorithm {
+            write!(f, " in flash algorithm `{}`", algorithm)?;
+        }
+
+        write!(f, ": {}", self.kind)
+    }
+}
+
+impl ChipFamily {
+    /// Check the [`ChipFamily`] for problems, and return all of them.
+    ///
+    /// In addition to the checks of [`validate`](Self::validate), this checks the memory
+    /// maps of the variants and the flash properties of the algorithms, which probe-rs
+    /// relies on when flashing. This is meant for tools which generate target descriptions,
+    /// and for users who add their own, to find mistakes before flashing.
+    pub fn lint(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+
+        for variant in &self.variants {
+            let mut report = |severity, algorithm: Option<&str>, kind| {
+                diagnostics.push(Diagnostic {
+                    severity,
+                    variant: Some(variant.name.clone()),
+                    algorithm: algorithm.map(str::to_owned),
+                    kind,
+                })
+            };
+
+            match variant.cores.first() {
+                Some(core) => {
+                    let architecture = core.core_type.architecture();
+
+                    if variant
+                        .cores
+                        .iter()
+                        .any(|core| core.core_type.architecture() != architecture)
+                    {
+                        report(Severity::Error, None, DiagnosticKind::MixedArchitectures);
+                    }
+                }
+                None => report(Severity::Error, None, DiagnosticKind::NoCores),
+            }
+
+            for (index, first) in variant.memory_map.iter().enumerate() {
+                for second in &variant.memory_map[index + 1..] {
+                    if first.range().intersects_range(second.range()) {
+                        report(
+                            Severity::Error,
+                            None,
+                            DiagnosticKind::OverlappingRegions {
+                                first: first.range().clone(),
+                                second: second.range().clone(),
+                            },
+                        );
+                    }
+                }
+            }
+
+            for name in &variant.flash_algorithms {
+                let algorithm = match self.get_algorithm(name) {
+                    Some(algorithm) => algorithm,
+                    None => {
+                        report(
+                            Severity::Error,
+                            Some(name.as_str()),
+                            DiagnosticKind::UnknownFlashAlgorithm,
+                        );
+                        continue;
+                    }
+                };
+
+                if let Some(load_address) = algorithm.load_address {
+                    let range = load_address..load_address + algorithm.instructions.len() as u32;
+
+                    let in_ram = variant.memory_map.iter().any(|region| match region {
+                        MemoryRegion::Ram(ram) => ram.range.contains_range(&range),
+                        _ => false,
+                    });
+
+                    if !in_ram {
+                        report(
+                            Severity::Error,
+                            Some(name.as_str()),
+                            DiagnosticKind::LoadAddressNotInRam { range },
+                        );
+                    }
+                }
+            }
+        }
+
+        for algorithm in &self.flash_algorithms {
+            let mut report = |severity, kind| {
+                diagnostics.push(Diagnostic {
+                    severity,
+                    variant: None,
+                    algorithm: Some(algorithm.name.clone()),
+                    kind,
+                })
+            };
+
+            let properties = &algorithm.flash_properties;
+
+            if properties.page_size == 0 {
+                report(Severity::Error, DiagnosticKind::ZeroPageSize);
+            }
+
+            if let Some(first) = properties.sectors.first() {
+                if first.address != 0 {
+                    report(
+                        Severity::Error,
+                        DiagnosticKind::MissingFirstSector {
+                            address: first.address,
+                        },
+                    );
+                }
+            }
+
+            if properties
+                .sectors
+                .windows(2)
+                .any(|sectors| sectors[0].address >= sectors[1].address)
+            {
+                report(Severity::Error, DiagnosticKind::UnsortedSectors);
+            }
+
+            for sector in &properties.sectors {
+                if sector.size == 0 {
+                    report(
+                        Severity::Error,
+                        DiagnosticKind::ZeroSectorSize {
+                            address: sector.address,
+                        },
+                    );
+                } else if properties.page_size != 0 && sector.size % properties.page_size != 0 {
+                    report(
+                        Severity::Warning,
+                        DiagnosticKind::SectorNotMultipleOfPage {
+                            sector_size: sector.size,
+                            page_size: properties.page_size,
+                        },
+                    );
+                }
+            }
+        }
+
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        ArmCoreAccessOptions, Chip, Core, CoreAccessOptions, CoreType, FlashProperties, NvmRegion,
+        RamRegion, RawFlashAlgorithm, SectorDescription, TargetDescriptionSource,
+    };
+
+    fn family() -> ChipFamily {
+        ChipFamily {
+            name: "Test".to_owned(),
+            manufacturer: None,
+            variants: vec![Chip {
+                name: "test".to_owned(),
+                part: None,
+                cores: vec![Core {
+                    name: "main".to_owned(),
+                    core_type: CoreType::Armv7em,
+                    core_access_options: CoreAccessOptions::Arm(ArmCoreAccessOptions {
+                        ap: 0,
+                        psel: 0,
+                    }),
+                }],
+                memory_map: vec![
+                    MemoryRegion::Nvm(NvmRegion {
+                        range: 0x0000_0000..0x0010_0000,
+                        is_boot_memory: true,
+                        cores: vec!["main".to_owned()],
+                        access_port: None,
+                        alias: None,
+                    }),
+                    MemoryRegion::Ram(RamRegion {
+                        range: 0x2000_0000..0x2004_0000,
+                        is_boot_memory: false,
+                        cores: vec!["main".to_owned()],
+                        access_port: None,
+                        alias: None,
+                    }),
+                ],
+                flash_algorithms: vec!["algorithm".to_owned()],
+                default_reset: None,
+                partitions: vec![],
+            }],
+            flash_algorithms: vec![RawFlashAlgorithm {
+                name: "algorithm".to_owned(),
+                instructions: vec![0; 0x100],
+                load_address: Some(0x2000_0020),
+                flash_properties: FlashProperties {
+                    address_range: 0x0000_0000..0x0010_0000,
+                    page_size: 0x1000,
+                    sectors: vec![SectorDescription {
+                        size: 0x1000,
+                        address: 0,
+                    }],
+                    ..Default::default()
+                },
+                ..Default::default()
+            }],
+            source: TargetDescriptionSource::External,
+        }
+    }
+
+    #[test]
+    fn valid_family() {
+        assert_eq!(family().lint(), vec![]);
+    }
+
+    #[test]
+    fn invalid_family() {
+        let mut family = family();
+
+        let variant = &mut family.variants[0];
+        variant.memory_map.push(MemoryRegion::Ram(RamRegion {
+            range: 0x2003_0000..0x2005_0000,
+            is_boot_memory: false,
+            cores: vec!["main".to_owned()],
+            access_port: None,
+            alias: None,
+        }));
+
+        let algorithm = &mut family.flash_algorithms[0];
+        algorithm.load_address = Some(0x1000_0000);
+        algorithm.flash_properties.sectors.push(SectorDescription {
+            size: 0x800,
+            address: 0x8000,
+        });
+
+        let kinds = family
+            .lint()
+            .into_iter()
+            .map(|diagnostic| (diagnostic.severity, diagnostic.kind))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            kinds,
+            vec![
+                (
+                    Severity::Error,
+                    DiagnosticKind::OverlappingRegions {
+                        first: 0x2000_0000..0x2004_0000,
+                        second: 0x2003_0000..0x2005_0000,
+                    }
+                ),
+                (
+                    Severity::Error,
+                    DiagnosticKind::LoadAddressNotInRam {
+                        range: 0x1000_0000..0x1000_0100,
+                    }
+                ),
+                (
+                    Severity::Warning,
+                    DiagnosticKind::SectorNotMultipleOfPage {
+                        sector_size: 0x800,
+                        page_size: 0x1000,
+                    }
+                ),
+            ]
+        );
+    }
+}
@@ -32,6 +32,8 @@ pub struct RawFlashAlgorithm {
     pub pc_erase_sector: u32,
     /// Address of the `EraseAll()` entry point. Optional.
     pub pc_erase_all: Option<u32>,
+    /// Address of the `BlankCheck()` entry point. Optional.
+    pub pc_blank_check: Option<u32>,
     /// The offset from the start of RAM to the data section.
     pub data_section_offset: u32,
     /// The properties of the flash on the device.
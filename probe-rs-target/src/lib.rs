@@ -13,14 +13,20 @@
 
 mod chip;
 mod chip_family;
+mod diagnostics;
 mod flash_algorithm;
 mod flash_properties;
 mod memory;
 
-pub use chip::{ArmCoreAccessOptions, Chip, Core, CoreAccessOptions, RiscvCoreAccessOptions};
+pub use chip::{
+    ArmCoreAccessOptions, Chip, Core, CoreAccessOptions, ResetKind, RiscvCoreAccessOptions,
+    XtensaCoreAccessOptions,
+};
 pub use chip_family::{Architecture, ChipFamily, CoreType, TargetDescriptionSource};
+pub use diagnostics::{Diagnostic, DiagnosticKind, Severity};
 pub use flash_algorithm::RawFlashAlgorithm;
 pub use flash_properties::FlashProperties;
 pub use memory::{
-    MemoryRange, MemoryRegion, NvmRegion, PageInfo, RamRegion, SectorDescription, SectorInfo,
+    FlashPartition, MemoryRange, MemoryRegion, NvmRegion, PageInfo, RamRegion, SectorDescription,
+    SectorInfo,
 };
@@ -10,6 +10,22 @@ pub struct NvmRegion {
     pub is_boot_memory: bool,
     /// List of cores that can access this region
     pub cores: Vec<String>,
+    /// The access port through which this region is accessed, if it is not the
+    /// access port of the core.
+    #[serde(default)]
+    #[cfg_attr(
+        not(feature = "bincode"),
+        serde(skip_serializing_if = "Option::is_none")
+    )]
+    pub access_port: Option<u8>,
+    /// The start address of an alias of this region, through which the same memory is
+    /// accessed, e.g. the secure alias of a non-secure region on ARMv8-M chips with an IDAU.
+    #[serde(default)]
+    #[cfg_attr(
+        not(feature = "bincode"),
+        serde(skip_serializing_if = "Option::is_none")
+    )]
+    pub alias: Option<u32>,
 }
 
 impl NvmRegion {
@@ -30,6 +46,22 @@ pub struct RamRegion {
     pub is_boot_memory: bool,
     /// List of cores that can access this region
     pub cores: Vec<String>,
+    /// The access port through which this region is accessed, if it is not the
+    /// access port of the core.
+    #[serde(default)]
+    #[cfg_attr(
+        not(feature = "bincode"),
+        serde(skip_serializing_if = "Option::is_none")
+    )]
+    pub access_port: Option<u8>,
+    /// The start address of an alias of this region, through which the same memory is
+    /// accessed, e.g. the secure alias of a non-secure region on ARMv8-M chips with an IDAU.
+    #[serde(default)]
+    #[cfg_attr(
+        not(feature = "bincode"),
+        serde(skip_serializing_if = "Option::is_none")
+    )]
+    pub alias: Option<u32>,
 }
 
 /// Represents a generic region.
@@ -39,6 +71,36 @@ pub struct GenericRegion {
     pub range: Range<u32>,
     /// List of cores that can access this region
     pub cores: Vec<String>,
+    /// The access port through which this region is accessed, if it is not the
+    /// access port of the core.
+    #[serde(default)]
+    #[cfg_attr(
+        not(feature = "bincode"),
+        serde(skip_serializing_if = "Option::is_none")
+    )]
+    pub access_port: Option<u8>,
+    /// The start address of an alias of this region, through which the same memory is
+    /// accessed, e.g. the secure alias of a non-secure region on ARMv8-M chips with an IDAU.
+    #[serde(default)]
+    #[cfg_attr(
+        not(feature = "bincode"),
+        serde(skip_serializing_if = "Option::is_none")
+    )]
+    pub alias: Option<u32>,
+}
+
+/// A named partition of the non-volatile memory, e.g. for a bootloader, the application
+/// or the settings of a device.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FlashPartition {
+    /// The name of the partition.
+    pub name: String,
+    /// Address range of the partition
+    pub range: Range<u32>,
+    /// Protected partitions are not written by downloads, e.g. to keep a bootloader or the
+    /// settings of a device. They can only be written and erased by their name.
+    #[serde(default)]
+    pub protected: bool,
 }
 
 /// Holds information about a specific, individual flash
@@ -128,10 +190,91 @@ pub enum MemoryRegion {
     Nvm(NvmRegion),
 }
 
+impl MemoryRegion {
+    /// The access port through which the region is accessed, if it is not the
+    /// access port of the core.
+    pub fn access_port(&self) -> Option<u8> {
+        match self {
+            MemoryRegion::Ram(region) => region.access_port,
+            MemoryRegion::Generic(region) => region.access_port,
+            MemoryRegion::Nvm(region) => region.access_port,
+        }
+    }
+
+    /// The address range of the region.
+    pub fn range(&self) -> &Range<u32> {
+        match self {
+            MemoryRegion::Ram(region) => &region.range,
+            MemoryRegion::Generic(region) => &region.range,
+            MemoryRegion::Nvm(region) => &region.range,
+        }
+    }
+
+    /// The start address of the alias of the region, if it has one.
+    pub fn alias(&self) -> Option<u32> {
+        match self {
+            MemoryRegion::Ram(region) => region.alias,
+            MemoryRegion::Generic(region) => region.alias,
+            MemoryRegion::Nvm(region) => region.alias,
+        }
+    }
+
+    /// The address range of the alias of the region, if it has one.
+    pub fn alias_range(&self) -> Option<Range<u32>> {
+        let range = self.range();
+
+        self.alias()
+            .map(|alias| alias..alias + (range.end - range.start))
+    }
+
+    /// Translate `address` in the alias of the region to the same location in the region.
+    ///
+    /// Returns `None` if the region has no alias, or `address` is not in the alias.
+    pub fn address_from_alias(&self, address: u32) -> Option<u32> {
+        let alias = self.alias_range()?;
+
+        if alias.contains(&address) {
+            Some(self.range().start + (address - alias.start))
+        } else {
+            None
+        }
+    }
+
+    /// Translate `address` in the region to the same location in the alias of the region.
+    ///
+    /// Returns `None` if the region has no alias, or `address` is not in the region.
+    pub fn address_to_alias(&self, address: u32) -> Option<u32> {
+        let range = self.range();
+
+        if range.contains(&address) {
+            self.alias().map(|alias| alias + (address - range.start))
+        } else {
+            None
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test]
+    fn alias_translation() {
+        let region = MemoryRegion::Nvm(NvmRegion {
+            range: 0x0000_0000..0x0009_8000,
+            is_boot_memory: true,
+            cores: vec!["main".into()],
+            access_port: None,
+            alias: Some(0x1000_0000),
+        });
+
+        assert_eq!(region.alias_range(), Some(0x1000_0000..0x1009_8000));
+        assert_eq!(region.address_from_alias(0x1000_0100), Some(0x100));
+        assert_eq!(region.address_from_alias(0x1009_8000), None);
+        assert_eq!(region.address_to_alias(0x100), Some(0x1000_0100));
+        assert_eq!(region.address_to_alias(0x1000_0100), None);
+    }
+
     #[test]
     fn contains_range1() {
         let range1 = 0..1;
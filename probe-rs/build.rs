@@ -1,8 +1,10 @@
 use std::env;
 use std::fs::{read_dir, read_to_string};
 use std::io;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 
+use miniz_oxide::deflate::compress_to_vec;
 use probe_rs_target::ChipFamily;
 
 fn main() {
@@ -33,14 +35,45 @@ fn main() {
         }
     }
 
-    let families_bin =
-        bincode::serialize(&families).expect("Failed to serialize families as bincode");
+    // Each family is compressed on its own, so the registry only has to decompress and
+    // deserialize the families which are used. The index holds the summaries of the families,
+    // which contain everything needed to find a chip, and where the compressed families are.
+    let mut index: Vec<(ChipFamily, Range<usize>)> = Vec::new();
+    let mut data = Vec::new();
+
+    for family in &families {
+        let family_bin = bincode::serialize(family).expect("Failed to serialize family as bincode");
+        let compressed = compress_to_vec(&family_bin, 9);
+
+        let start = data.len();
+        data.extend_from_slice(&compressed);
+
+        index.push((summary(family), start..data.len()));
+    }
+
+    let index_bin = bincode::serialize(&index).expect("Failed to serialize index as bincode");
 
     let out_dir = env::var("OUT_DIR").unwrap();
-    let dest_path = Path::new(&out_dir).join("targets.bincode");
-    std::fs::write(dest_path, &families_bin).unwrap();
+    let index_path = Path::new(&out_dir).join("targets.index.bincode");
+    std::fs::write(index_path, &index_bin).unwrap();
+    let dest_path = Path::new(&out_dir).join("targets.bincode.deflate");
+    std::fs::write(dest_path, &data).unwrap();
+}
+
+/// The family without the parts which are not needed to find a chip, i.e. only the names,
+/// the manufacturer and the part numbers.
+fn summary(family: &ChipFamily) -> ChipFamily {
+    let mut summary = family.clone();
+    summary.flash_algorithms.clear();
+
+    for chip in &mut summary.variants {
+        chip.cores.clear();
+        chip.memory_map.clear();
+        chip.flash_algorithms.clear();
+        chip.partitions.clear();
+    }
 
-    let _: Vec<ChipFamily> = bincode::deserialize(&families_bin).unwrap();
+    summary
 }
 
 /// One possible implementation of walking a directory only visiting files.
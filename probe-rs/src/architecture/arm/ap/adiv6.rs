@@ -0,0 +1,216 @@
+//! Discovery of the access ports of ADIv6 debug ports.
+//!
+//! DPv3 (ADIv6) debug ports don't select APs by number. Instead, the APs are mapped into
+//! the address space of the debug port, and are described by a ROM table at the address
+//! in the `BASEPTR` registers. See chapter C2 of the [ARM Debug Interface Architecture
+//! Specification ADIv6.0].
+//!
+//! probe-rs still identifies these APs by a number, which is their index in the order they
+//! are found in the ROM table, so the same AP numbers can be used for both versions.
+//!
+//! [ARM Debug Interface Architecture Specification ADIv6.0]: https://developer.arm.com/documentation/ihi0074/latest
+
+/// The offset of the AP registers in the 4 KB block of an ADIv6 AP.
+///
+/// The registers have the same offsets in this block as the ADIv5 registers, e.g. `CSW` is
+/// at `0xD00` and `IDR` at `0xDFC`.
+pub(crate) const AP_REGISTER_OFFSET: u64 = 0xD00;
+
+/// The maximum depth of nested ROM tables, to stop on ROM tables which refer to themselves.
+const MAX_ROM_TABLE_DEPTH: usize = 8;
+
+/// The JEP106 code of ARM in the `ARCHITECT` field of `DEVARCH`.
+const ARCHITECT_ARM: u32 = 0x23b;
+
+/// The `ARCHID` of a class 0x9 ROM table.
+const ARCHID_ROM_TABLE: u32 = 0x0af7;
+
+/// The `ARCHID`s of MEM-APs, JTAG-APs and other APs.
+const ARCHID_ACCESS_PORTS: [u32; 3] = [0x0a17, 0x0a27, 0x0a47];
+
+/// The kind of a CoreSight component, as far as it matters for finding APs.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum ComponentKind {
+    /// A class 0x1 ROM table, as used by ADIv5.
+    RomTable,
+    /// A class 0x9 ROM table, with 32 bit or 64 bit entries.
+    CoreSightRomTable {
+        wide_entries: bool,
+    },
+    AccessPort,
+    Other,
+}
+
+/// Identify the component at `base`.
+fn component_kind<E>(
+    base: u64,
+    read: &mut impl FnMut(u64) -> Result<u32, E>,
+) -> Result<ComponentKind, E> {
+    let mut cidr = 0;
+    for (index, offset) in (0xff0..=0xffc).step_by(4).enumerate() {
+        cidr |= (read(base + offset)? & 0xff) << (8 * index);
+    }
+
+    // The preamble is 0xB105_000D, with the component class in bits 15 to 12.
+    if cidr & 0xffff_0fff != 0xb105_000d {
+        log::debug!("No component at {:#x}, CIDR is {:#010x}", base, cidr);
+        return Ok(ComponentKind::Other);
+    }
+
+    match (cidr >> 12) & 0xf {
+        0x1 => Ok(ComponentKind::RomTable),
+        0x9 => {
+            let devarch = read(base + 0xfbc)?;
+
+            let present = devarch & (1 << 20) != 0;
+            if !present || devarch >> 21 != ARCHITECT_ARM {
+                return Ok(ComponentKind::Other);
+            }
+
+            let archid = devarch & 0xffff;
+            if archid == ARCHID_ROM_TABLE {
+                let devid = read(base + 0xfc8)?;
+
+                Ok(ComponentKind::CoreSightRomTable {
+                    wide_entries: devid & 0xf == 1,
+                })
+            } else if ARCHID_ACCESS_PORTS.contains(&archid) {
+                Ok(ComponentKind::AccessPort)
+            } else {
+                Ok(ComponentKind::Other)
+            }
+        }
+        _ => Ok(ComponentKind::Other),
+    }
+}
+
+/// Find the APs in the address space of a DPv3 debug port, starting at the component at
+/// `base`, which is the address in `BASEPTR`.
+///
+/// `read` reads a word from the address space of the debug port. The base addresses of
+/// the APs are returned in the order they were found.
+pub(crate) fn find_access_ports<E>(
+    base: u64,
+    read: &mut impl FnMut(u64) -> Result<u32, E>,
+) -> Result<Vec<u64>, E> {
+    let mut access_ports = Vec::new();
+
+    find_access_ports_at(base, 0, read, &mut access_ports)?;
+
+    Ok(access_ports)
+}
+
+fn find_access_ports_at<E>(
+    base: u64,
+    depth: usize,
+    read: &mut impl FnMut(u64) -> Result<u32, E>,
+    access_ports: &mut Vec<u64>,
+) -> Result<(), E> {
+    if depth > MAX_ROM_TABLE_DEPTH {
+        log::warn!("Ignoring ROM table at {:#x}, it is nested too deeply", base);
+        return Ok(());
+    }
+
+    let kind = component_kind(base, read)?;
+    log::debug!("Component at {:#x}: {:?}", base, kind);
+
+    match kind {
+        ComponentKind::AccessPort => access_ports.push(base),
+        ComponentKind::RomTable => {
+            for offset in (0..0xf00).step_by(4) {
+                let entry = read(base + offset)?;
+
+                // An empty entry marks the end of the table.
+                if entry == 0 {
+                    break;
+                }
+
+                if entry & 0x1 == 0x1 {
+                    let child = entry_address(base, u64::from(entry), 32);
+                    find_access_ports_at(child, depth + 1, read, access_ports)?;
+                }
+            }
+        }
+        ComponentKind::CoreSightRomTable { wide_entries } => {
+            let entry_size = if wide_entries { 8 } else { 4 };
+
+            for offset in (0..0x800).step_by(entry_size) {
+                let mut entry = u64::from(read(base + offset)?);
+                if wide_entries {
+                    entry |= u64::from(read(base + offset + 4)?) << 32;
+                }
+
+                match entry & 0x3 {
+                    // Present.
+                    0x3 => {
+                        let child = entry_address(base, entry, entry_size as u32 * 8);
+                        find_access_ports_at(child, depth + 1, read, access_ports)?;
+                    }
+                    // Not present, but not the last entry.
+                    0x2 => {}
+                    // Not present, and the last entry.
+                    _ => break,
+                }
+            }
+        }
+        ComponentKind::Other => {}
+    }
+
+    Ok(())
+}
+
+/// The address of the component of a ROM table entry, from the signed offset in the
+/// entry, which is `bits` wide.
+fn entry_address(base: u64, entry: u64, bits: u32) -> u64 {
+    let offset = entry & !0xfff;
+
+    // Sign-extend the offset.
+    let offset = ((offset << (64 - bits)) as i64) >> (64 - bits);
+
+    base.wrapping_add(offset as u64)
+}
+
+#[cfg(test)]
+mod test {
+    use super::find_access_ports;
+    use std::collections::HashMap;
+
+    /// Add the component ID registers of a component of `class` at `base`.
+    fn add_component(memory: &mut HashMap<u64, u32>, base: u64, class: u32, devarch: u32) {
+        memory.insert(base + 0xff0, 0x0d);
+        memory.insert(base + 0xff4, class << 4);
+        memory.insert(base + 0xff8, 0x05);
+        memory.insert(base + 0xffc, 0xb1);
+        memory.insert(base + 0xfbc, devarch);
+    }
+
+    #[test]
+    fn find_aps_in_rom_tables() {
+        let mut memory = HashMap::new();
+
+        // A class 0x9 ROM table at 0x8000, with a MEM-AP at 0x0, a missing entry,
+        // a class 0x1 ROM table at 0xa000, and a component which is not an AP.
+        add_component(&mut memory, 0x8000, 0x9, 0x4770_0af7);
+        memory.insert(0x8000, 0xffff_8003);
+        memory.insert(0x8004, 0x0000_0002);
+        memory.insert(0x8008, 0x0000_2003);
+        memory.insert(0x800c, 0x0000_4003);
+
+        add_component(&mut memory, 0x0, 0x9, 0x4776_0a17);
+        add_component(&mut memory, 0xc000, 0x9, 0x4770_1a14);
+
+        // The class 0x1 ROM table contains two MEM-APs, the second one is not present.
+        add_component(&mut memory, 0xa000, 0x1, 0);
+        memory.insert(0xa000, 0x0000_1003);
+        memory.insert(0xa004, 0x0000_3002);
+
+        add_component(&mut memory, 0xb000, 0x9, 0x4776_0a17);
+        add_component(&mut memory, 0xd000, 0x9, 0x4776_0a17);
+
+        let mut read = |address: u64| -> Result<u32, ()> {
+            Ok(memory.get(&address).copied().unwrap_or_default())
+        };
+
+        assert_eq!(find_access_ports(0x8000, &mut read), Ok(vec![0x0, 0xb000]));
+    }
+}
@@ -2,7 +2,7 @@ use anyhow::anyhow;
 
 use super::super::{ApAccess, Register};
 use super::{AddressIncrement, ApRegister, DataSize, CSW, DRW, TAR};
-use crate::architecture::arm::{ap::AccessPort, DpAddress};
+use crate::architecture::arm::{ap::AccessPort, DapTransfer, DpAddress};
 use crate::{
     architecture::arm::dp::{DebugPortError, DpAccess, DpRegister},
     CommunicationInterface, DebugProbeError,
@@ -234,6 +234,19 @@ impl ApAccess for MockMemoryAp {
 
         Ok(())
     }
+
+    fn transfer_ap_batch<PORT>(
+        &mut self,
+        _port: PORT,
+        _transfers: &mut [DapTransfer],
+    ) -> Result<(), DebugProbeError>
+    where
+        PORT: AccessPort,
+    {
+        Err(DebugProbeError::CommandNotSupportedByProbe(
+            "transfer_ap_batch",
+        ))
+    }
 }
 
 impl DpAccess for MockMemoryAp {
@@ -1,5 +1,6 @@
 #[macro_use]
 pub mod register_generation;
+pub(crate) mod adiv6;
 pub(crate) mod generic_ap;
 pub(crate) mod memory_ap;
 
@@ -12,7 +13,7 @@ pub use memory_ap::{
 };
 use probe_rs_target::Core;
 
-use super::{ApAddress, DapAccess, DpAddress, Register};
+use super::{ApAddress, DapAccess, DapTransfer, DpAddress, Register};
 
 #[derive(Debug, thiserror::Error)]
 pub enum AccessPortError {
@@ -112,9 +113,19 @@ pub trait ApAccess {
     where
         PORT: AccessPort,
         R: ApRegister<PORT>;
+
+    /// Execute a batch of reads and writes of registers of the same AP, in as few
+    /// round trips to the probe as possible.
+    fn transfer_ap_batch<PORT>(
+        &mut self,
+        port: PORT,
+        transfers: &mut [DapTransfer],
+    ) -> Result<(), DebugProbeError>
+    where
+        PORT: AccessPort;
 }
 
-impl<T: DapAccess> ApAccess for T {
+impl<T: DapAccess + ?Sized> ApAccess for T {
     fn read_ap_register<PORT, R>(&mut self, port: impl Into<PORT>) -> Result<R, DebugProbeError>
     where
         PORT: AccessPort,
@@ -177,6 +188,19 @@ impl<T: DapAccess> ApAccess for T {
 
         self.read_raw_ap_register_repeated(port.into().ap_address(), R::ADDRESS, values)
     }
+
+    fn transfer_ap_batch<PORT>(
+        &mut self,
+        port: PORT,
+        transfers: &mut [DapTransfer],
+    ) -> Result<(), DebugProbeError>
+    where
+        PORT: AccessPort,
+    {
+        log::debug!("Executing batch of {} transfers", transfers.len());
+
+        self.transfer_raw_ap_batch(port.ap_address(), transfers)
+    }
 }
 
 /// Determine if an AP exists with the given AP number.
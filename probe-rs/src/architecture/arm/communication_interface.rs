@@ -1,16 +1,20 @@
 use super::{
     ap::{
-        valid_access_ports, AccessPort, ApAccess, ApClass, BaseaddrFormat, GenericAp, MemoryAp,
-        BASE, BASE2, CSW, IDR,
+        adiv6::{self, AP_REGISTER_OFFSET},
+        valid_access_ports, AccessPort, ApAccess, ApClass, ApType, BaseaddrFormat, GenericAp,
+        MemoryAp, BASE, BASE2, CSW, IDR,
+    },
+    dp::{
+        Abort, Ctrl, DebugPortError, DebugPortVersion, DpAccess, Select, Select1, BASEPTR0,
+        BASEPTR1, DPIDR, DPIDR1,
     },
-    dp::{Abort, Ctrl, DebugPortError, DebugPortVersion, DpAccess, Select, DPIDR},
     memory::{adi_v5_memory_interface::ADIMemoryInterface, Component},
     sequences::{ArmDebugSequence, DefaultArmSequence},
-    ApAddress, DapAccess, DpAddress, PortType, RawDapAccess, SwoAccess, SwoConfig,
+    ApAddress, DapAccess, DapTransfer, DpAddress, PortType, RawDapAccess, SwoAccess, SwoConfig,
 };
 use crate::{
     architecture::arm::ap::DataSize, CommunicationInterface, DebugProbe, DebugProbeError,
-    Error as ProbeRsError, Memory, Probe,
+    Error as ProbeRsError, Memory, Probe, ProbeBridge, TraceEvent,
 };
 use anyhow::anyhow;
 use jep106::JEP106Code;
@@ -36,6 +40,78 @@ pub enum DapError {
     TargetPowerUpFailed,
     #[error("Incorrect parity on READ request.")]
     IncorrectParity,
+    #[error("Transfer to register {register:#04x} of {dp:x?}, {ap:x?} failed with '{response}' after {retries} retries (CTRL/STAT: {ctrl_stat:x?}).")]
+    RecoveryFailed {
+        /// The last response of the target.
+        response: Box<DapError>,
+        /// The number of retries done.
+        retries: usize,
+        /// The debug port used for the transfer.
+        dp: DpAddress,
+        /// The access port used for the transfer, `None` for debug port registers.
+        ap: Option<ApAddress>,
+        /// The address of the accessed register.
+        register: u8,
+        /// The contents of the CTRL/STAT register after the last failure, if it could be read.
+        ctrl_stat: Option<u32>,
+    },
+}
+
+/// Policy for retrying transfers which failed with a WAIT or FAULT response.
+///
+/// By default, only transfers which failed with a WAIT response are retried. If the
+/// transfer still fails after `max_retries` retries, a [`DapError::RecoveryFailed`] error
+/// containing the state of the DP is returned.
+///
+/// Retrying after a FAULT response has to be enabled with `retry_fault`. The sticky error
+/// flags are cleared using the ABORT register before the retry, which would also hide a
+/// FAULT caused by an earlier write that the probe queued without reporting its result.
+/// To attribute the FAULT to the retried transfer, the queued transfers of the probe are
+/// flushed before it, and a failure of the flush is returned without a retry.
+///
+/// Only single register accesses are retried. Block transfers are not retried,
+/// because the target address of the transfer is automatically incremented.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// The maximum number of retries. Setting this to zero disables retries.
+    pub max_retries: usize,
+    /// The time to wait before each retry.
+    pub delay: Duration,
+    /// Also retry transfers which failed with a FAULT response.
+    pub retry_fault: bool,
+}
+
+impl RetryPolicy {
+    /// A policy which never retries transfers.
+    pub fn disabled() -> Self {
+        Self {
+            max_retries: 0,
+            delay: Duration::from_millis(0),
+            retry_fault: false,
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            delay: Duration::from_millis(1),
+            retry_fault: false,
+        }
+    }
+}
+
+/// Extract a WAIT or FAULT response from an error returned by a probe.
+fn transient_dap_error(error: &DebugProbeError) -> Option<DapError> {
+    match error {
+        DebugProbeError::ArchitectureSpecific(error) => match error.downcast_ref::<DapError>() {
+            Some(DapError::WaitResponse) => Some(DapError::WaitResponse),
+            Some(DapError::FaultResponse) => Some(DapError::FaultResponse),
+            _ => None,
+        },
+        _ => None,
+    }
 }
 
 impl From<DapError> for DebugProbeError {
@@ -59,6 +135,26 @@ pub trait ArmProbeInterface: DapAccess + SwdSequence + SwoAccess + Send {
     fn read_from_rom_table(&mut self, dp: DpAddress) -> Result<Option<ArmChipInfo>, ProbeRsError>;
 
     fn close(self: Box<Self>) -> Probe;
+
+    /// Set the policy for retrying transfers which failed with a WAIT or FAULT response.
+    ///
+    /// Probes which handle these responses themselves ignore the policy.
+    fn set_retry_policy(&mut self, _policy: RetryPolicy) {}
+
+    /// The bridge of the probe to the SPI, I2C and UART interfaces of the target board,
+    /// if the probe has one.
+    fn probe_bridge(&mut self) -> Option<&mut dyn ProbeBridge> {
+        None
+    }
+
+    /// The value of the `DPIDR` register of the debug port `dp`, which identifies the
+    /// debug port and its version.
+    ///
+    /// Returns `None` for DPv0 debug ports, which don't implement `DPIDR`, and for probes
+    /// which don't give access to the registers of the debug port.
+    fn debug_port_id(&mut self, _dp: DpAddress) -> Result<Option<u32>, ProbeRsError> {
+        Ok(None)
+    }
 }
 
 // TODO: Rename trait!
@@ -101,6 +197,7 @@ pub struct Initialized {
     dps: HashMap<DpAddress, DpState>,
     use_overrun_detect: bool,
     sequence: Arc<dyn ArmDebugSequence>,
+    retry_policy: RetryPolicy,
 }
 
 impl Initialized {
@@ -110,6 +207,7 @@ impl Initialized {
             dps: HashMap::new(),
             use_overrun_detect,
             sequence,
+            retry_policy: RetryPolicy::default(),
         }
     }
 }
@@ -121,12 +219,21 @@ impl ArmDebugState for Initialized {}
 #[derive(Debug)]
 pub(crate) struct DpState {
     pub debug_port_version: DebugPortVersion,
+    /// The value of `DPIDR`, `None` for DPv0 debug ports, which don't implement it.
+    pub dpidr: Option<u32>,
 
     pub current_dpbanksel: u8,
 
     pub current_apsel: u8,
     pub current_apbanksel: u8,
 
+    /// The selected address in `SELECT` and `SELECT1` of DPv3 debug ports, `None` if unknown.
+    pub current_address: Option<u64>,
+    /// Whether the debug port has more than 32 address bits, and implements `SELECT1`.
+    pub wide_addresses: bool,
+    /// The base addresses of the APs of DPv3 debug ports, in the order of their numbers.
+    pub ap_base_addresses: Vec<u64>,
+
     /// Information about the APs of the target.
     /// APs are identified by a number, starting from zero.
     pub ap_information: Vec<ApInformation>,
@@ -136,12 +243,50 @@ impl DpState {
     pub fn new() -> Self {
         Self {
             debug_port_version: DebugPortVersion::Unsupported(0xFF),
+            dpidr: None,
             current_dpbanksel: 0,
             current_apsel: 0,
             current_apbanksel: 0,
+            current_address: None,
+            wide_addresses: false,
+            ap_base_addresses: Vec::new(),
             ap_information: Vec::new(),
         }
     }
+
+    /// Update the cached selection after `select` was written to the `SELECT` register.
+    fn set_select(&mut self, select: Select) {
+        if self.debug_port_version == DebugPortVersion::DPv3 {
+            let low = u64::from(select.addr() << 4);
+
+            self.current_address = match self.current_address {
+                Some(address) => Some(address & !0xffff_ffff | low),
+                None if !self.wide_addresses => Some(low),
+                None => None,
+            };
+        } else {
+            self.current_apsel = select.ap_sel();
+            self.current_apbanksel = select.ap_bank_sel();
+        }
+
+        self.current_dpbanksel = select.dp_bank_sel();
+    }
+
+    /// The value of the `SELECT` register for the cached selection.
+    fn select(&self) -> Select {
+        let mut select = Select(0);
+
+        if self.debug_port_version == DebugPortVersion::DPv3 {
+            select.set_addr(self.current_address.unwrap_or_default() as u32 >> 4);
+        } else {
+            select.set_ap_sel(self.current_apsel);
+            select.set_ap_bank_sel(self.current_apbanksel);
+        }
+
+        select.set_dp_bank_sel(self.current_dpbanksel);
+
+        select
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -205,11 +350,19 @@ impl ApInformation {
 
             log::debug!("HNONSEC supported: {}", supports_hnonsec);
 
+            // AXI does not allow bursts to cross 4 KB boundaries, so the TAR of AXI-APs
+            // increments in 4 KB blocks. The architecture only guarantees 1 KB.
+            let auto_increment_wrap = match idr.TYPE {
+                ApType::AmbaAxi3Axi4 | ApType::AmbaAxi5 => 0x1000,
+                _ => DEFAULT_AUTO_INCREMENT_WRAP,
+            };
+
             Ok(ApInformation::MemoryAp(MemoryApInformation {
                 address: access_port.ap_address(),
                 only_32bit_data_size,
                 debug_base_address: base_address,
                 supports_hnonsec,
+                auto_increment_wrap,
             }))
         } else {
             Ok(ApInformation::Other {
@@ -240,8 +393,20 @@ pub struct MemoryApInformation {
     ///
     /// [ARM Debug Interface Architecture Specification]: https://developer.arm.com/documentation/ihi0031/d/
     pub supports_hnonsec: bool,
+
+    /// The size of the blocks in bytes the auto-increment of the TAR register wraps around in.
+    ///
+    /// Transfers are split at the boundaries of these blocks, and the TAR is written again.
+    /// See section C2.2.2, [ARM Debug Interface Architecture Specification].
+    ///
+    /// [ARM Debug Interface Architecture Specification]: https://developer.arm.com/documentation/ihi0031/d/
+    pub auto_increment_wrap: u32,
 }
 
+/// The size of the blocks the TAR auto-increment wraps around in, which is guaranteed
+/// by the architecture for all memory APs.
+pub(crate) const DEFAULT_AUTO_INCREMENT_WRAP: u32 = 0x400;
+
 #[derive(Debug)]
 pub struct ArmCommunicationInterface<S: ArmDebugState> {
     probe: Box<dyn DapProbe>,
@@ -274,6 +439,21 @@ impl ArmProbeInterface for ArmCommunicationInterface<Initialized> {
     fn close(self: Box<Self>) -> Probe {
         Probe::from_attached_probe(RawDapAccess::into_probe(self.probe))
     }
+
+    fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.state.retry_policy = policy;
+    }
+
+    fn probe_bridge(&mut self) -> Option<&mut dyn ProbeBridge> {
+        self.probe.get_bridge_interface_mut()
+    }
+
+    fn debug_port_id(&mut self, dp: DpAddress) -> Result<Option<u32>, ProbeRsError> {
+        self.select_dp(dp)?;
+
+        // note(unwrap): the state is inserted when the DP is selected.
+        Ok(self.state.dps.get(&dp).unwrap().dpidr)
+    }
 }
 
 impl<S: ArmDebugState> SwdSequence for ArmCommunicationInterface<S> {
@@ -334,6 +514,11 @@ impl UninitializedArmProbe for ArmCommunicationInterface<Uninitialized> {
 }
 
 impl<S: ArmDebugState> ArmCommunicationInterface<S> {
+    /// Direct access to the probe, e.g. to control the reset pin.
+    pub(crate) fn dap_probe_mut(&mut self) -> &mut dyn DapProbe {
+        self.probe.as_mut()
+    }
+
     fn _get_debug_port_version(&mut self) -> Result<DebugPortVersion, DebugProbeError> {
         let dpidr = DPIDR(self.probe.raw_read_register(PortType::DebugPort, 0)?);
 
@@ -377,13 +562,104 @@ impl<'interface> ArmCommunicationInterface<Initialized> {
 
                 Ok(Memory::new(adi_v5_memory_interface, access_port))
             }
-            ApInformation::Other { address } => Err(ProbeRsError::Other(anyhow!(format!(
-                "AP {:x?} is not a memory AP",
-                address
-            )))),
+            ApInformation::Other { address } => Err(ProbeRsError::NotAMemoryAp(*address)),
         }
     }
 
+    /// Run a single register transfer, and retry it according to the retry policy
+    /// if it fails with a WAIT or FAULT response.
+    fn with_retry<T>(
+        &mut self,
+        dp: DpAddress,
+        ap: Option<ApAddress>,
+        register: u8,
+        mut transfer: impl FnMut(&mut Self) -> Result<T, DebugProbeError>,
+    ) -> Result<T, DebugProbeError> {
+        let policy = self.state.retry_policy;
+        let mut retries = 0;
+
+        // A FAULT of a queued transfer is reported by the flush, and not retried.
+        if policy.retry_fault && policy.max_retries > 0 {
+            self.probe.raw_flush()?;
+        }
+
+        loop {
+            let error = match transfer(self) {
+                Ok(value) => return Ok(value),
+                Err(error) => error,
+            };
+
+            let response = match transient_dap_error(&error) {
+                Some(DapError::FaultResponse) if !policy.retry_fault => return Err(error),
+                Some(response) if policy.max_retries > 0 => response,
+                _ => return Err(error),
+            };
+
+            self.recover_from(&response, retries == policy.max_retries);
+
+            if retries == policy.max_retries {
+                return Err(DapError::RecoveryFailed {
+                    response: Box::new(response),
+                    retries,
+                    dp,
+                    ap,
+                    register,
+                    ctrl_stat: self.read_ctrl_stat(dp),
+                }
+                .into());
+            }
+
+            retries += 1;
+
+            log::debug!(
+                "Retrying transfer to register {:#04x} after {} ({}/{})",
+                register,
+                response,
+                retries,
+                policy.max_retries
+            );
+
+            std::thread::sleep(policy.delay);
+        }
+    }
+
+    /// Bring the DP back into a usable state after a WAIT or FAULT response.
+    ///
+    /// After a FAULT response, the sticky error flags have to be cleared, otherwise all
+    /// following transfers fail as well. A transfer which keeps responding with WAIT
+    /// is aborted when `abort` is set.
+    fn recover_from(&mut self, response: &DapError, abort: bool) {
+        let mut request = Abort::default();
+
+        match response {
+            DapError::FaultResponse => {
+                request.set_stkerrclr(true);
+                request.set_stkcmpclr(true);
+                request.set_wderrclr(true);
+                request.set_orunerrclr(true);
+            }
+            DapError::WaitResponse if abort => request.set_dapabort(true),
+            _ => return,
+        }
+
+        // The ABORT register is not banked, and can always be written.
+        if let Err(error) =
+            self.probe
+                .raw_write_register(PortType::DebugPort, Abort::ADDRESS, request.into())
+        {
+            log::warn!("Failed to write ABORT register: {}", error);
+        }
+    }
+
+    /// Read the CTRL/STAT register for error reporting, without retrying.
+    fn read_ctrl_stat(&mut self, dp: DpAddress) -> Option<u32> {
+        self.select_dp_and_dp_bank(dp, Ctrl::ADDRESS).ok()?;
+
+        self.probe
+            .raw_read_register(PortType::DebugPort, Ctrl::ADDRESS)
+            .ok()
+    }
+
     fn select_dp(&mut self, dp: DpAddress) -> Result<(), DebugProbeError> {
         if self.state.current_dp == Some(dp) {
             return Ok(());
@@ -410,6 +686,28 @@ impl<'interface> ArmCommunicationInterface<Initialized> {
             ctrl_reg.set_orun_detect(self.state.use_overrun_detect);
             self.write_dp_register(dp, ctrl_reg)?;
 
+            // DPv0 debug ports don't implement DPIDR, they are handled like DPv1.
+            let (version, dpidr) = match self.read_dp_register::<DPIDR>(dp) {
+                Ok(dpidr) => (
+                    DebugPortVersion::from(dpidr.version()),
+                    Some(u32::from(dpidr)),
+                ),
+                Err(e) => {
+                    log::debug!("Failed to read DPIDR of DP {:x?}: {}", dp, e);
+                    (DebugPortVersion::DPv0, None)
+                }
+            };
+            log::debug!("DP {:x?} is a {}", dp, version);
+
+            // note(unwrap): we have inserted the state above, it must exist.
+            let state = self.state.dps.get_mut(&dp).unwrap();
+            state.debug_port_version = version;
+            state.dpidr = dpidr;
+
+            if version == DebugPortVersion::DPv3 {
+                self.find_access_ports_v3(dp)?;
+            }
+
             /* determine the number and type of available APs */
             log::trace!("Searching valid APs");
 
@@ -438,26 +736,24 @@ impl<'interface> ArmCommunicationInterface<Initialized> {
 
         // DP register addresses are 4 bank bits, 4 address bits. Lowest 2 address bits are
         // always 0, so this leaves only 4 possible addresses: 0x0, 0x4, 0x8, 0xC.
-        // Only address 0x4 is banked, the rest are don't care.
+        // Only address 0x4 is banked, the rest are don't care. DPv3 also banks reads of
+        // address 0x0, for DPIDR1 and BASEPTR.
 
         let bank = dp_register_address >> 4;
         let addr = dp_register_address & 0xF;
 
-        if addr != 4 {
+        let banked =
+            addr == 4 || (addr == 0 && dp_state.debug_port_version == DebugPortVersion::DPv3);
+        if !banked {
             return Ok(());
         }
 
         if bank != dp_state.current_dpbanksel {
             dp_state.current_dpbanksel = bank;
 
-            let mut select = Select(0);
-
             log::debug!("Changing DP_BANK_SEL to {}", dp_state.current_dpbanksel);
 
-            select.set_ap_sel(dp_state.current_apsel);
-            select.set_ap_bank_sel(dp_state.current_apbanksel);
-            select.set_dp_bank_sel(dp_state.current_dpbanksel);
-
+            let select = dp_state.select();
             self.write_dp_register(dp, select)?;
         }
 
@@ -474,6 +770,18 @@ impl<'interface> ArmCommunicationInterface<Initialized> {
         // NOTE(unwrap): select_dp adds the dp to state if not present.
         let dp_state = self.state.dps.get_mut(&ap.dp).unwrap();
 
+        if dp_state.debug_port_version == DebugPortVersion::DPv3 {
+            let base = *dp_state
+                .ap_base_addresses
+                .get(ap.ap as usize)
+                .ok_or(DebugPortError::AccessPortNotFound(ap.ap))?;
+
+            return self.select_address(
+                ap.dp,
+                base + AP_REGISTER_OFFSET + u64::from(ap_register_address & 0xF0),
+            );
+        }
+
         let port = ap.ap;
         let ap_bank = ap_register_address >> 4;
 
@@ -490,24 +798,105 @@ impl<'interface> ArmCommunicationInterface<Initialized> {
         }
 
         if cache_changed {
-            let mut select = Select(0);
-
             log::debug!(
                 "Changing AP to {}, AP_BANK_SEL to {}",
                 dp_state.current_apsel,
                 dp_state.current_apbanksel
             );
 
-            select.set_ap_sel(dp_state.current_apsel);
-            select.set_ap_bank_sel(dp_state.current_apbanksel);
-            select.set_dp_bank_sel(dp_state.current_dpbanksel);
-
+            let select = dp_state.select();
             self.write_dp_register(ap.dp, select)?;
         }
 
         Ok(())
     }
 
+    /// Select the 16 byte block containing `address` in the address space of a DPv3
+    /// debug port, which is then accessed with AP transfers.
+    fn select_address(&mut self, dp: DpAddress, address: u64) -> Result<(), DebugProbeError> {
+        self.select_dp(dp)?;
+
+        // NOTE(unwrap): select_dp adds the dp to state if not present.
+        let dp_state = self.state.dps.get_mut(&dp).unwrap();
+
+        let address = address & !0xF;
+        if dp_state.current_address == Some(address) {
+            return Ok(());
+        }
+
+        log::debug!("Changing the selected address to {:#x}", address);
+
+        let high_changed = dp_state.wide_addresses
+            && dp_state.current_address.map(|current| current >> 32) != Some(address >> 32);
+
+        if high_changed {
+            self.write_dp_register(dp, Select1((address >> 32) as u32))?;
+        }
+
+        // NOTE(unwrap): select_dp adds the dp to state if not present.
+        let dp_state = self.state.dps.get_mut(&dp).unwrap();
+        dp_state.current_address = Some(address);
+
+        let select = dp_state.select();
+        self.write_dp_register(dp, select)?;
+
+        Ok(())
+    }
+
+    /// Read a word from the address space of a DPv3 debug port.
+    fn read_dp_address(&mut self, dp: DpAddress, address: u64) -> Result<u32, DebugProbeError> {
+        self.with_retry(dp, None, (address & 0xFF) as u8, |interface| {
+            interface.select_address(dp, address)?;
+            interface
+                .probe
+                .raw_read_register(PortType::AccessPort, (address & 0xC) as u8)
+        })
+    }
+
+    /// Find the APs of a DPv3 debug port in the ROM table `BASEPTR` points to.
+    fn find_access_ports_v3(&mut self, dp: DpAddress) -> Result<(), DebugProbeError> {
+        let dpidr1: DPIDR1 = self.read_dp_register(dp)?;
+        let wide_addresses = dpidr1.asize() > 32;
+
+        // NOTE(unwrap): select_dp adds the dp to state if not present.
+        let dp_state = self.state.dps.get_mut(&dp).unwrap();
+        dp_state.wide_addresses = wide_addresses;
+        // SELECT was written before the version was known, so its contents are unknown.
+        dp_state.current_address = None;
+
+        let baseptr0: BASEPTR0 = self.read_dp_register(dp)?;
+        if !baseptr0.valid() {
+            log::warn!("DP {:x?} has no valid BASEPTR, no APs can be used", dp);
+            return Ok(());
+        }
+
+        let mut base = u64::from(baseptr0.ptr() << 12);
+        if wide_addresses {
+            let baseptr1: BASEPTR1 = self.read_dp_register(dp)?;
+            base |= u64::from(baseptr1.ptr()) << 32;
+        }
+
+        let mut ap_base_addresses =
+            adiv6::find_access_ports(base, &mut |address| self.read_dp_address(dp, address))?;
+
+        if ap_base_addresses.len() > 256 {
+            log::warn!(
+                "DP {:x?} has {} APs, only the first 256 can be used",
+                dp,
+                ap_base_addresses.len()
+            );
+            ap_base_addresses.truncate(256);
+        }
+
+        log::debug!("APs of DP {:x?}: {:#x?}", dp, ap_base_addresses);
+
+        // NOTE(unwrap): select_dp adds the dp to state if not present.
+        let dp_state = self.state.dps.get_mut(&dp).unwrap();
+        dp_state.ap_base_addresses = ap_base_addresses;
+
+        Ok(())
+    }
+
     /// Determine the type and additional information about an AP.
     pub(crate) fn ap_information(
         &mut self,
@@ -565,13 +954,41 @@ impl SwoAccess for ArmCommunicationInterface<Initialized> {
             None => Err(ProbeRsError::ArchitectureRequired(&["ARMv7", "ARMv8"])),
         }
     }
+
+    fn swo_poll_interval_hint(&mut self, config: &SwoConfig) -> Option<Duration> {
+        self.probe
+            .get_swo_interface_mut()
+            .and_then(|interface| interface.swo_poll_interval_hint(config))
+    }
+
+    fn swo_buffer_size(&mut self) -> Option<usize> {
+        self.probe
+            .get_swo_interface_mut()
+            .and_then(|interface| interface.swo_buffer_size())
+    }
+
+    fn swo_overflows(&mut self) -> Option<u64> {
+        self.probe
+            .get_swo_interface_mut()
+            .and_then(|interface| interface.swo_overflows())
+    }
 }
 
 impl DapAccess for ArmCommunicationInterface<Initialized> {
     fn read_raw_dp_register(&mut self, dp: DpAddress, address: u8) -> Result<u32, DebugProbeError> {
-        self.select_dp_and_dp_bank(dp, address)?;
-        let result = self.probe.raw_read_register(PortType::DebugPort, address)?;
-        Ok(result)
+        let result = self.with_retry(dp, None, address, |interface| {
+            interface.select_dp_and_dp_bank(dp, address)?;
+            interface
+                .probe
+                .raw_read_register(PortType::DebugPort, address)
+        });
+
+        self.trace(|| TraceEvent::DpRead {
+            address,
+            value: result.as_ref().ok().copied(),
+        });
+
+        result
     }
 
     fn write_raw_dp_register(
@@ -580,20 +997,46 @@ impl DapAccess for ArmCommunicationInterface<Initialized> {
         address: u8,
         value: u32,
     ) -> Result<(), DebugProbeError> {
-        self.select_dp_and_dp_bank(dp, address)?;
-        self.probe
-            .raw_write_register(PortType::DebugPort, address, value)?;
+        let result = self.with_retry(dp, None, address, |interface| {
+            interface.select_dp_and_dp_bank(dp, address)?;
+            interface
+                .probe
+                .raw_write_register(PortType::DebugPort, address, value)
+        });
+
+        self.trace(|| TraceEvent::DpWrite {
+            address,
+            value,
+            ok: result.is_ok(),
+        });
+        result?;
+
+        // SELECT can also be written by users of the interface,
+        // the cached selection has to match the new value.
+        if address & 0xF == Select::ADDRESS {
+            if let Some(dp_state) = self.state.dps.get_mut(&dp) {
+                dp_state.set_select(Select(value));
+            }
+        }
+
         Ok(())
     }
 
     fn read_raw_ap_register(&mut self, ap: ApAddress, address: u8) -> Result<u32, DebugProbeError> {
-        self.select_ap_and_ap_bank(ap, address)?;
-
-        let result = self
-            .probe
-            .raw_read_register(PortType::AccessPort, address)?;
-
-        Ok(result)
+        let result = self.with_retry(ap.dp, Some(ap), address, |interface| {
+            interface.select_ap_and_ap_bank(ap, address)?;
+            interface
+                .probe
+                .raw_read_register(PortType::AccessPort, address)
+        });
+
+        self.trace(|| TraceEvent::ApRead {
+            ap: ap.ap,
+            address,
+            value: result.as_ref().ok().copied(),
+        });
+
+        result
     }
 
     fn read_raw_ap_register_repeated(
@@ -604,9 +1047,18 @@ impl DapAccess for ArmCommunicationInterface<Initialized> {
     ) -> Result<(), DebugProbeError> {
         self.select_ap_and_ap_bank(ap, address)?;
 
-        self.probe
-            .raw_read_block(PortType::AccessPort, address, values)?;
-        Ok(())
+        let result = self
+            .probe
+            .raw_read_block(PortType::AccessPort, address, values);
+
+        self.trace(|| TraceEvent::ApReadBlock {
+            ap: ap.ap,
+            address,
+            values: values.to_vec(),
+            ok: result.is_ok(),
+        });
+
+        result
     }
 
     fn write_raw_ap_register(
@@ -615,10 +1067,21 @@ impl DapAccess for ArmCommunicationInterface<Initialized> {
         address: u8,
         value: u32,
     ) -> Result<(), DebugProbeError> {
-        self.select_ap_and_ap_bank(ap, address)?;
-
-        self.probe
-            .raw_write_register(PortType::AccessPort, address, value)
+        let result = self.with_retry(ap.dp, Some(ap), address, |interface| {
+            interface.select_ap_and_ap_bank(ap, address)?;
+            interface
+                .probe
+                .raw_write_register(PortType::AccessPort, address, value)
+        });
+
+        self.trace(|| TraceEvent::ApWrite {
+            ap: ap.ap,
+            address,
+            value,
+            ok: result.is_ok(),
+        });
+
+        result
     }
 
     fn write_raw_ap_register_repeated(
@@ -629,9 +1092,82 @@ impl DapAccess for ArmCommunicationInterface<Initialized> {
     ) -> Result<(), DebugProbeError> {
         self.select_ap_and_ap_bank(ap, address)?;
 
-        self.probe
-            .raw_write_block(PortType::AccessPort, address, values)?;
-        Ok(())
+        let result = self
+            .probe
+            .raw_write_block(PortType::AccessPort, address, values);
+
+        self.trace(|| TraceEvent::ApWriteBlock {
+            ap: ap.ap,
+            address,
+            values: values.to_vec(),
+            ok: result.is_ok(),
+        });
+
+        result
+    }
+
+    fn transfer_raw_ap_batch(
+        &mut self,
+        ap: ApAddress,
+        transfers: &mut [DapTransfer],
+    ) -> Result<(), DebugProbeError> {
+        let address = match transfers.first() {
+            Some(transfer) => transfer.addr(),
+            None => return Ok(()),
+        };
+
+        // The bank can only be switched between the transfers by the caller.
+        if transfers
+            .iter()
+            .any(|transfer| transfer.addr() >> 4 != address >> 4)
+        {
+            for transfer in transfers {
+                match transfer {
+                    DapTransfer::Read { addr, value } => {
+                        *value = self.read_raw_ap_register(ap, *addr)?;
+                    }
+                    DapTransfer::Write { addr, value } => {
+                        self.write_raw_ap_register(ap, *addr, *value)?;
+                    }
+                }
+            }
+
+            return Ok(());
+        }
+
+        let result = self.with_retry(ap.dp, Some(ap), address, |interface| {
+            interface.select_ap_and_ap_bank(ap, address)?;
+            interface
+                .probe
+                .raw_transfer_batch(PortType::AccessPort, transfers)
+        });
+
+        for transfer in transfers.iter() {
+            self.trace(|| match *transfer {
+                DapTransfer::Read { addr, value } => TraceEvent::ApRead {
+                    ap: ap.ap,
+                    address: addr,
+                    value: result.as_ref().ok().map(|_| value),
+                },
+                DapTransfer::Write { addr, value } => TraceEvent::ApWrite {
+                    ap: ap.ap,
+                    address: addr,
+                    value,
+                    ok: result.is_ok(),
+                },
+            });
+        }
+
+        result
+    }
+}
+
+impl ArmCommunicationInterface<Initialized> {
+    /// Record the event returned by `event` in the protocol tracer of the probe, if it has one.
+    fn trace(&self, event: impl FnOnce() -> TraceEvent) {
+        if let Some(tracer) = self.probe.protocol_tracer() {
+            tracer.record(event());
+        }
     }
 }
 
@@ -713,3 +1249,184 @@ impl std::fmt::Display for ArmChipInfo {
         write!(f, "{} 0x{:04x}", manu, self.part)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        ArmCommunicationInterface, DapError, DpState, Initialized, RetryPolicy, Uninitialized,
+    };
+    use crate::{
+        architecture::arm::{sequences::DefaultArmSequence, DapAccess, DpAddress, PortType},
+        probe::fake_probe::FakeProbe,
+        DebugProbeError,
+    };
+    use std::{
+        collections::VecDeque,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    type Writes = Arc<Mutex<Vec<(PortType, u8, u32)>>>;
+
+    /// An interface to a fake probe, which answers the reads of DP registers with
+    /// `responses`, in order, and records all register writes.
+    fn interface_with_responses(
+        responses: Vec<Result<u32, DapError>>,
+    ) -> (ArmCommunicationInterface<Initialized>, Writes) {
+        let responses = Mutex::new(VecDeque::from(responses));
+        let writes = Writes::default();
+
+        let mut probe = FakeProbe::new();
+        probe.handle_dap_register_read(Box::new(move |port, _address| {
+            assert_eq!(port, PortType::DebugPort);
+
+            match responses.lock().unwrap().pop_front() {
+                Some(response) => response.map_err(DebugProbeError::from),
+                None => panic!("Unexpected read of a DP register"),
+            }
+        }));
+
+        let recorded = writes.clone();
+        probe.handle_dap_register_write(Box::new(move |port, address, value| {
+            recorded.lock().unwrap().push((port, address, value));
+            Ok(())
+        }));
+
+        let interface = ArmCommunicationInterface::<Uninitialized>::new(Box::new(probe), false);
+        let mut interface = interface
+            .into_initialized(DefaultArmSequence::create())
+            .map_err(|(_, error)| error)
+            .unwrap();
+
+        // Skip the power up of the debug port, the fake probe doesn't implement it.
+        interface.state.current_dp = Some(DpAddress::Default);
+        interface
+            .state
+            .dps
+            .insert(DpAddress::Default, DpState::new());
+
+        interface.state.retry_policy = RetryPolicy {
+            max_retries: 2,
+            delay: Duration::from_millis(0),
+            retry_fault: false,
+        };
+
+        (interface, writes)
+    }
+
+    fn abort_writes(writes: &Writes) -> Vec<u32> {
+        writes
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(port, address, _)| *port == PortType::DebugPort && *address == 0x0)
+            .map(|(_, _, value)| *value)
+            .collect()
+    }
+
+    #[test]
+    fn retry_after_wait() {
+        let (mut interface, writes) = interface_with_responses(vec![
+            Err(DapError::WaitResponse),
+            Err(DapError::WaitResponse),
+            Ok(0x1234_5678),
+        ]);
+
+        let value = interface
+            .read_raw_dp_register(DpAddress::Default, 0xc)
+            .unwrap();
+
+        assert_eq!(value, 0x1234_5678);
+        // WAIT responses don't need a recovery before the last retry.
+        assert!(abort_writes(&writes).is_empty());
+    }
+
+    #[test]
+    fn fault_is_not_retried_by_default() {
+        let (mut interface, writes) =
+            interface_with_responses(vec![Err(DapError::FaultResponse), Ok(0x42)]);
+
+        let error = interface
+            .read_raw_dp_register(DpAddress::Default, 0xc)
+            .unwrap_err();
+
+        assert!(matches!(
+            error,
+            DebugProbeError::ArchitectureSpecific(ref error)
+                if matches!(error.downcast_ref::<DapError>(), Some(DapError::FaultResponse))
+        ));
+        // The sticky errors are kept, so the FAULT is not hidden from later transfers.
+        assert!(abort_writes(&writes).is_empty());
+    }
+
+    #[test]
+    fn recover_after_fault() {
+        let (mut interface, writes) =
+            interface_with_responses(vec![Err(DapError::FaultResponse), Ok(0x42)]);
+        interface.state.retry_policy.retry_fault = true;
+
+        let value = interface
+            .read_raw_dp_register(DpAddress::Default, 0xc)
+            .unwrap();
+
+        assert_eq!(value, 0x42);
+        // STKCMPCLR, STKERRCLR, WDERRCLR and ORUNERRCLR
+        assert_eq!(abort_writes(&writes), vec![0x1e]);
+    }
+
+    #[test]
+    fn recovery_fails_after_max_retries() {
+        let (mut interface, writes) = interface_with_responses(vec![
+            Err(DapError::WaitResponse),
+            Err(DapError::WaitResponse),
+            Err(DapError::WaitResponse),
+            // CTRL/STAT, read for the error
+            Ok(0xf000_0000),
+        ]);
+
+        let error = interface
+            .read_raw_dp_register(DpAddress::Default, 0xc)
+            .unwrap_err();
+
+        match error {
+            DebugProbeError::ArchitectureSpecific(error) => {
+                match error.downcast_ref::<DapError>() {
+                    Some(DapError::RecoveryFailed {
+                        response,
+                        retries,
+                        register,
+                        ctrl_stat,
+                        ..
+                    }) => {
+                        assert_eq!(**response, DapError::WaitResponse);
+                        assert_eq!(*retries, 2);
+                        assert_eq!(*register, 0xc);
+                        assert_eq!(*ctrl_stat, Some(0xf000_0000));
+                    }
+                    other => panic!("Unexpected error: {:?}", other),
+                }
+            }
+            other => panic!("Unexpected error: {:?}", other),
+        }
+
+        // The transfer is aborted after the last WAIT response.
+        assert_eq!(abort_writes(&writes), vec![0x1]);
+    }
+
+    #[test]
+    fn disabled_retry_policy_returns_the_response() {
+        let (mut interface, writes) = interface_with_responses(vec![Err(DapError::FaultResponse)]);
+        interface.state.retry_policy = RetryPolicy::disabled();
+
+        let error = interface
+            .read_raw_dp_register(DpAddress::Default, 0xc)
+            .unwrap_err();
+
+        assert!(matches!(
+            error,
+            DebugProbeError::ArchitectureSpecific(ref error)
+                if matches!(error.downcast_ref::<DapError>(), Some(DapError::FaultResponse))
+        ));
+        assert!(abort_writes(&writes).is_empty());
+    }
+}
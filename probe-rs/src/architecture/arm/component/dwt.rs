@@ -66,6 +66,34 @@ impl<'probe: 'core, 'core> Dwt<'probe, 'core> {
         function.store_unit(self.component, self.core, unit)
     }
 
+    /// Emits the values of all accesses to `size` bytes at `address` on a specific DWT unit.
+    ///
+    /// `size` has to be a power of two, and `address` has to be aligned to it.
+    pub fn enable_data_value_trace(
+        &mut self,
+        unit: usize,
+        address: u32,
+        size: u32,
+    ) -> Result<(), Error> {
+        let mut comp = Comp::load_unit(self.component, self.core, unit)?;
+        comp.set_comp(address);
+        comp.store_unit(self.component, self.core, unit)?;
+
+        // Ignore the lower address bits, so that every access to the value matches.
+        let mut mask = Mask::load_unit(self.component, self.core, unit)?;
+        mask.set_mask(size.trailing_zeros());
+        mask.store_unit(self.component, self.core, unit)?;
+
+        let mut function = Function::load_unit(self.component, self.core, unit)?;
+        function.set_emitrange(false);
+        function.set_datavmatch(false);
+        function.set_cycmatch(false);
+        // Emit the data value on read or write.
+        function.set_function(0b10);
+
+        function.store_unit(self.component, self.core, unit)
+    }
+
     /// Disables data tracing on the given unit.
     pub fn disable_data_trace(&mut self, unit: usize) -> Result<(), Error> {
         let mut function = Function::load_unit(self.component, self.core, unit)?;
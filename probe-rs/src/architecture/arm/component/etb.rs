@@ -0,0 +1,93 @@
+use super::super::memory::romtable::Component;
+use crate::{Core, Error};
+
+const REGISTER_OFFSET_ETB_RDP: u32 = 0x004;
+const REGISTER_OFFSET_ETB_STS: u32 = 0x00C;
+const REGISTER_OFFSET_ETB_RRD: u32 = 0x010;
+const REGISTER_OFFSET_ETB_RRP: u32 = 0x014;
+const REGISTER_OFFSET_ETB_RWP: u32 = 0x018;
+const REGISTER_OFFSET_ETB_CTL: u32 = 0x020;
+const REGISTER_OFFSET_ETB_FFCR: u32 = 0x304;
+const REGISTER_OFFSET_ETB_LAR: u32 = 0xFB0;
+
+const ETB_STS_FULL: u32 = 1 << 0;
+
+/// ETB unit
+///
+/// Embedded trace buffer unit, which stores formatted trace data on the chip.
+pub struct Etb<'probe: 'core, 'core> {
+    component: &'core Component,
+    core: &'core mut Core<'probe>,
+}
+
+impl<'probe: 'core, 'core> Etb<'probe, 'core> {
+    pub fn new(core: &'core mut Core<'probe>, component: &'core Component) -> Self {
+        Etb { component, core }
+    }
+
+    pub fn unlock(&mut self) -> Result<(), Error> {
+        self.component
+            .write_reg(self.core, REGISTER_OFFSET_ETB_LAR, 0xC5AC_CE55)?;
+        Ok(())
+    }
+
+    /// Clear the buffer and start capturing trace data.
+    pub fn enable(&mut self) -> Result<(), Error> {
+        self.unlock()?;
+
+        self.component
+            .write_reg(self.core, REGISTER_OFFSET_ETB_CTL, 0)?;
+        self.component
+            .write_reg(self.core, REGISTER_OFFSET_ETB_RWP, 0)?;
+        // Enable the formatter and continuous formatting.
+        self.component
+            .write_reg(self.core, REGISTER_OFFSET_ETB_FFCR, 0x3)?;
+        self.component
+            .write_reg(self.core, REGISTER_OFFSET_ETB_CTL, 1)?;
+        Ok(())
+    }
+
+    /// Stop capturing trace data.
+    pub fn disable(&mut self) -> Result<(), Error> {
+        self.component
+            .write_reg(self.core, REGISTER_OFFSET_ETB_CTL, 0)?;
+        Ok(())
+    }
+
+    /// Read the captured trace data from the oldest to the newest byte.
+    ///
+    /// Capturing is stopped while the buffer is read.
+    pub fn read(&mut self) -> Result<Vec<u8>, Error> {
+        self.disable()?;
+
+        let depth = self
+            .component
+            .read_reg(self.core, REGISTER_OFFSET_ETB_RDP)?;
+        let status = self
+            .component
+            .read_reg(self.core, REGISTER_OFFSET_ETB_STS)?;
+        let write_pointer = self
+            .component
+            .read_reg(self.core, REGISTER_OFFSET_ETB_RWP)?;
+
+        // If the buffer is full, the oldest data is at the write pointer.
+        let (start, words) = if status & ETB_STS_FULL != 0 {
+            (write_pointer, depth)
+        } else {
+            (0, write_pointer)
+        };
+
+        self.component
+            .write_reg(self.core, REGISTER_OFFSET_ETB_RRP, start)?;
+
+        let mut data = Vec::with_capacity(words as usize * 4);
+        for _ in 0..words {
+            let word = self
+                .component
+                .read_reg(self.core, REGISTER_OFFSET_ETB_RRD)?;
+            data.extend_from_slice(&word.to_le_bytes());
+        }
+
+        Ok(data)
+    }
+}
@@ -0,0 +1,92 @@
+use super::super::memory::romtable::Component;
+use crate::{Core, DebugProbeError, Error};
+use std::time::Duration;
+use web_time::Instant;
+
+const REGISTER_OFFSET_ETM_CR: u32 = 0x000;
+const REGISTER_OFFSET_ETM_SR: u32 = 0x010;
+const REGISTER_OFFSET_ETM_TEEVR: u32 = 0x020;
+const REGISTER_OFFSET_ETM_TECR1: u32 = 0x024;
+const REGISTER_OFFSET_ETM_TRACEIDR: u32 = 0x200;
+const REGISTER_OFFSET_ETM_LAR: u32 = 0xFB0;
+
+const ETM_CR_POWER_DOWN: u32 = 1 << 0;
+const ETM_CR_BRANCH_OUTPUT: u32 = 1 << 8;
+const ETM_CR_PROG_BIT: u32 = 1 << 10;
+const ETM_SR_PROG_BIT: u32 = 1 << 1;
+
+/// The event which is always true, used as trace enable event.
+const ETM_EVENT_ALWAYS: u32 = 0x6F;
+/// Exclude the address ranges selected in TECR1, which traces everything if no range is selected.
+const ETM_TECR1_EXCLUDE: u32 = 1 << 25;
+
+/// ETM unit
+///
+/// Embedded trace macrocell (ETMv3) unit, which generates instruction trace.
+pub struct Etm<'probe: 'core, 'core> {
+    component: &'core Component,
+    core: &'core mut Core<'probe>,
+}
+
+impl<'probe: 'core, 'core> Etm<'probe, 'core> {
+    pub fn new(core: &'core mut Core<'probe>, component: &'core Component) -> Self {
+        Etm { component, core }
+    }
+
+    pub fn unlock(&mut self) -> Result<(), Error> {
+        self.component
+            .write_reg(self.core, REGISTER_OFFSET_ETM_LAR, 0xC5AC_CE55)?;
+        Ok(())
+    }
+
+    /// Enable tracing of all instructions, with the trace source ID `trace_id`.
+    ///
+    /// Branch broadcasting is enabled, so that every branch address is part of the trace.
+    pub fn enable_instruction_trace(&mut self, trace_id: u8) -> Result<(), Error> {
+        self.unlock()?;
+
+        let control = self.component.read_reg(self.core, REGISTER_OFFSET_ETM_CR)?;
+        let control = (control | ETM_CR_PROG_BIT) & !ETM_CR_POWER_DOWN;
+        self.component
+            .write_reg(self.core, REGISTER_OFFSET_ETM_CR, control)?;
+        self.wait_for_programming(true)?;
+
+        self.component
+            .write_reg(self.core, REGISTER_OFFSET_ETM_TRACEIDR, trace_id as u32)?;
+        self.component
+            .write_reg(self.core, REGISTER_OFFSET_ETM_TEEVR, ETM_EVENT_ALWAYS)?;
+        self.component
+            .write_reg(self.core, REGISTER_OFFSET_ETM_TECR1, ETM_TECR1_EXCLUDE)?;
+
+        let control = (control | ETM_CR_BRANCH_OUTPUT) & !ETM_CR_PROG_BIT;
+        self.component
+            .write_reg(self.core, REGISTER_OFFSET_ETM_CR, control)?;
+        self.wait_for_programming(false)
+    }
+
+    /// Stop tracing and power down the ETM.
+    pub fn disable(&mut self) -> Result<(), Error> {
+        self.unlock()?;
+
+        let control = self.component.read_reg(self.core, REGISTER_OFFSET_ETM_CR)?;
+        self.component.write_reg(
+            self.core,
+            REGISTER_OFFSET_ETM_CR,
+            control | ETM_CR_PROG_BIT | ETM_CR_POWER_DOWN,
+        )?;
+        Ok(())
+    }
+
+    fn wait_for_programming(&mut self, programming: bool) -> Result<(), Error> {
+        let start = Instant::now();
+
+        while start.elapsed() < Duration::from_millis(100) {
+            let status = self.component.read_reg(self.core, REGISTER_OFFSET_ETM_SR)?;
+            if (status & ETM_SR_PROG_BIT != 0) == programming {
+                return Ok(());
+            }
+        }
+
+        Err(Error::Probe(DebugProbeError::Timeout))
+    }
+}
@@ -2,9 +2,12 @@
 //!
 //! ITM = Instrumentation Trace Macrocell
 
+use std::time::Duration;
+use web_time::Instant;
+
 use super::super::memory::romtable::Component;
-use super::DebugRegister;
-use crate::{Core, Error};
+use super::{ComponentError, DebugRegister};
+use crate::{Core, DebugProbeError, Error, MemoryInterface};
 
 pub const _ITM_PID: [u8; 8] = [0x1, 0xB0, 0x3b, 0x0, 0x4, 0x0, 0x0, 0x0];
 
@@ -17,6 +20,12 @@ const _REGISTER_OFFSET_ITM_TPR: u32 = 0xE40;
 const REGISTER_OFFSET_ITM_TCR: u32 = 0xE80;
 const REGISTER_OFFSET_ACCESS: u32 = 0xFB0;
 
+/// The number of stimulus ports of the ITM.
+pub const STIMULUS_PORTS: u8 = 32;
+
+/// How long to wait for the stimulus port FIFO to accept more data.
+const STIMULUS_TIMEOUT: Duration = Duration::from_millis(100);
+
 impl<'probe: 'core, 'core> Itm<'probe, 'core> {
     pub fn new(core: &'core mut Core<'probe>, component: &'core Component) -> Self {
         Itm { component, core }
@@ -52,6 +61,82 @@ impl<'probe: 'core, 'core> Itm<'probe, 'core> {
 
         Ok(())
     }
+
+    /// Enables the stimulus ports set in `mask`, and disables all others.
+    pub fn set_enabled_ports(&mut self, mask: u32) -> Result<(), Error> {
+        self.component.write_reg(
+            self.core,
+            register::ITM_TER::ADDRESS,
+            register::ITM_TER::from(mask).into(),
+        )
+    }
+
+    /// Enables or disables the local timestamps of the ITM packets.
+    pub fn set_timestamps(&mut self, enabled: bool) -> Result<(), Error> {
+        let mut value = self
+            .component
+            .read_reg(self.core, REGISTER_OFFSET_ITM_TCR)?;
+
+        // TSENA
+        if enabled {
+            value |= 1 << 1;
+        } else {
+            value &= !(1 << 1);
+        }
+
+        self.component
+            .write_reg(self.core, REGISTER_OFFSET_ITM_TCR, value)
+    }
+
+    /// Writes `data` to the stimulus port `port`, as if the target wrote it.
+    ///
+    /// The data is written in words, and the remaining bytes one at a time. Before each
+    /// write, this waits until the stimulus port FIFO can accept it.
+    pub fn write_stimulus(&mut self, port: u8, data: &[u8]) -> Result<(), Error> {
+        if port >= STIMULUS_PORTS {
+            return Err(Error::architecture_specific(
+                ComponentError::InvalidStimulusPort(port),
+            ));
+        }
+
+        let enabled = register::ITM_TER::load(self.component, self.core)?;
+        if u32::from(enabled) & (1 << port) == 0 {
+            return Err(Error::architecture_specific(
+                ComponentError::StimulusPortDisabled(port),
+            ));
+        }
+
+        let address = self.component.id().component_address() as u32 + 4 * port as u32;
+
+        let mut words = data.chunks_exact(4);
+        for word in &mut words {
+            self.wait_for_fifo(address)?;
+            self.core.write_word_32(
+                address,
+                u32::from_le_bytes([word[0], word[1], word[2], word[3]]),
+            )?;
+        }
+
+        for &byte in words.remainder() {
+            self.wait_for_fifo(address)?;
+            self.core.write_word_8(address, byte)?;
+        }
+
+        Ok(())
+    }
+
+    /// Waits until the FIFOREADY bit of the stimulus port at `address` is set.
+    fn wait_for_fifo(&mut self, address: u32) -> Result<(), Error> {
+        let start = Instant::now();
+
+        while self.core.read_word_32(address)? & 1 == 0 {
+            if start.elapsed() > STIMULUS_TIMEOUT {
+                return Err(DebugProbeError::Timeout.into());
+            }
+        }
+
+        Ok(())
+    }
 }
 
 mod register {
@@ -1,19 +1,53 @@
 mod dwt;
+mod etb;
+mod etm;
 mod itm;
+mod mtb;
 mod tpiu;
 
 use super::memory::romtable::{Component, PeripheralType, RomTableError};
+use super::trace::{deformat, MtbPacket};
 use crate::architecture::arm::core::armv6m::Demcr;
 use crate::architecture::arm::{SwoConfig, SwoMode};
 use crate::{Core, CoreRegister, Error, MemoryInterface};
 pub use dwt::Dwt;
+pub use etb::Etb;
+pub use etm::Etm;
 pub use itm::Itm;
+pub use mtb::{Mtb, MtbConfig};
 pub use tpiu::Tpiu;
 
+/// The trace source ID used for the instruction trace of the ETM.
+pub const ETM_TRACE_ID: u8 = 0x10;
+
 #[derive(thiserror::Error, Debug)]
 pub enum ComponentError {
     #[error("Nordic does not support TPIU CLK value of {0}")]
     NordicUnsupportedTPUICLKValue(u32),
+    #[error("The MTB buffer size {0} is not a power of two of at least 16 bytes")]
+    InvalidMtbBufferSize(u32),
+    #[error("The trace port size {0} is not supported, it has to be between 1 and 32 bits")]
+    InvalidTracePortSize(u8),
+    #[error("The {size} bytes at {address:#010x} can not be traced, the size has to be a power of two and the address aligned to it")]
+    InvalidDataTraceRange { address: u32, size: u32 },
+    #[error("The ITM stimulus port {0} does not exist, there are only 32 ports")]
+    InvalidStimulusPort(u8),
+    #[error("The ITM stimulus port {0} is disabled")]
+    StimulusPortDisabled(u8),
+}
+
+/// Where the instruction trace of the ETM is sent to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceSink {
+    /// The parallel trace port of the TPIU, with a width of `port_size` bits.
+    ///
+    /// The trace data has to be captured by a probe supporting parallel trace.
+    Tpiu {
+        /// The number of data pins of the trace port.
+        port_size: u8,
+    },
+    /// The embedded trace buffer, which can be read with [`read_etb_trace`].
+    Etb,
 }
 
 pub trait DebugRegister: Clone + From<u32> + Into<u32> + Sized + std::fmt::Debug {
@@ -145,6 +179,106 @@ fn setup_swv_vendor(
     Ok(())
 }
 
+/// Enables the ITM stimulus ports set in `ports`, disables all others, and enables or
+/// disables the local timestamps of the ITM packets.
+///
+/// [`setup_swv`] has to be called before, to configure the ITM.
+pub fn configure_itm(
+    core: &mut Core,
+    components: &[Component],
+    ports: u32,
+    timestamps: bool,
+) -> Result<(), Error> {
+    let mut itm = Itm::new(core, find_component(components, PeripheralType::Itm)?);
+    itm.set_enabled_ports(ports)?;
+    itm.set_timestamps(timestamps)
+}
+
+/// Writes `data` from the host to the ITM stimulus port `port`.
+///
+/// The data is sent over SWO like data written by the target, so it appears in the same trace.
+pub fn write_itm_stimulus(
+    core: &mut Core,
+    components: &[Component],
+    port: u8,
+    data: &[u8],
+) -> Result<(), Error> {
+    let mut itm = Itm::new(core, find_component(components, PeripheralType::Itm)?);
+    itm.write_stimulus(port, data)
+}
+
+/// Configures the MTB to record the non-sequential instruction flow.
+pub fn setup_mtb(
+    core: &mut Core,
+    components: &[Component],
+    config: &MtbConfig,
+) -> Result<(), Error> {
+    let mut mtb = Mtb::new(core, find_component(components, PeripheralType::Mtb)?);
+    mtb.enable(config)
+}
+
+/// Stops recording into the MTB.
+pub fn disable_mtb(core: &mut Core, components: &[Component]) -> Result<(), Error> {
+    let mut mtb = Mtb::new(core, find_component(components, PeripheralType::Mtb)?);
+    mtb.disable()
+}
+
+/// Reads the packets recorded by the MTB, from the oldest to the newest.
+pub fn read_mtb_trace(core: &mut Core, components: &[Component]) -> Result<Vec<MtbPacket>, Error> {
+    let mut mtb = Mtb::new(core, find_component(components, PeripheralType::Mtb)?);
+    mtb.read_trace()
+}
+
+/// Configures the ETM to emit instruction trace to `sink`, with the trace source ID [`ETM_TRACE_ID`].
+pub fn setup_etm_trace(
+    core: &mut Core,
+    components: &[Component],
+    sink: TraceSink,
+) -> Result<(), Error> {
+    enable_tracing(core)?;
+
+    match sink {
+        TraceSink::Tpiu { port_size } => {
+            if !(1..=32).contains(&port_size) {
+                return Err(Error::architecture_specific(
+                    ComponentError::InvalidTracePortSize(port_size),
+                ));
+            }
+
+            let mut tpiu = Tpiu::new(core, find_component(components, PeripheralType::Tpiu)?);
+            tpiu.set_port_size(1 << (port_size - 1))?;
+            tpiu.set_pin_protocol(0)?;
+            // The formatter is required to separate the trace sources.
+            tpiu.set_formatter(0x102)?;
+        }
+        TraceSink::Etb => {
+            let mut etb = Etb::new(core, find_component(components, PeripheralType::Etb)?);
+            etb.enable()?;
+        }
+    }
+
+    let mut etm = Etm::new(core, find_component(components, PeripheralType::Etm)?);
+    etm.enable_instruction_trace(ETM_TRACE_ID)?;
+
+    core.flush()
+}
+
+/// Stops the instruction trace of the ETM.
+pub fn disable_etm_trace(core: &mut Core, components: &[Component]) -> Result<(), Error> {
+    let mut etm = Etm::new(core, find_component(components, PeripheralType::Etm)?);
+    etm.disable()
+}
+
+/// Reads the ETM instruction trace captured by the ETB.
+///
+/// The formatter frames are removed, so only the data of the ETM is returned.
+pub fn read_etb_trace(core: &mut Core, components: &[Component]) -> Result<Vec<u8>, Error> {
+    let mut etb = Etb::new(core, find_component(components, PeripheralType::Etb)?);
+    let frames = etb.read()?;
+
+    Ok(deformat(&frames, ETM_TRACE_ID))
+}
+
 /// Configures DWT trace unit `unit` to begin tracing `address`.
 pub fn add_swv_data_trace(
     core: &mut Core,
@@ -156,6 +290,24 @@ pub fn add_swv_data_trace(
     dwt.enable_data_trace(unit, address)
 }
 
+/// Configures DWT trace unit `unit` to emit the values of all accesses to `size` bytes at `address`.
+pub fn add_swv_data_value_trace(
+    core: &mut Core,
+    components: &[Component],
+    unit: usize,
+    address: u32,
+    size: u32,
+) -> Result<(), Error> {
+    if !size.is_power_of_two() || address.checked_rem(size) != Some(0) {
+        return Err(Error::architecture_specific(
+            ComponentError::InvalidDataTraceRange { address, size },
+        ));
+    }
+
+    let mut dwt = Dwt::new(core, find_component(components, PeripheralType::Dwt)?);
+    dwt.enable_data_value_trace(unit, address, size)
+}
+
 pub fn remove_swv_data_trace(
     core: &mut Core,
     components: &[Component],
@@ -0,0 +1,235 @@
+//! Interface with the MTB (Micro Trace Buffer).
+//!
+//! The MTB of ARMv6-M and ARMv8-M Baseline cores records every non-sequential
+//! change of the program counter into a circular buffer in the RAM of the target.
+//!
+//! See the CoreSight MTB-M0+ technical reference manual for details.
+
+use bitfield::bitfield;
+
+use super::super::memory::romtable::Component;
+use super::super::trace::{decode_mtb_buffer, MtbPacket};
+use super::{ComponentError, DebugRegister};
+use crate::{Core, Error, MemoryInterface};
+
+/// The configuration of the MTB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MtbConfig {
+    /// The size of the trace buffer in bytes, which has to be a power of two of at least 16.
+    ///
+    /// The buffer starts at the base address of the MTB, and is part of the RAM of the target,
+    /// so it must not be used by the program.
+    pub buffer_size: u32,
+    /// Stop tracing when the buffer is full, instead of overwriting the oldest packets.
+    pub stop_when_full: bool,
+    /// Halt the core when the buffer is full.
+    pub halt_when_full: bool,
+}
+
+impl Default for MtbConfig {
+    fn default() -> Self {
+        Self {
+            buffer_size: 1024,
+            stop_when_full: false,
+            halt_when_full: false,
+        }
+    }
+}
+
+/// A struct representing an MTB unit on target.
+pub struct Mtb<'probe: 'core, 'core> {
+    component: &'core Component,
+    core: &'core mut Core<'probe>,
+}
+
+impl<'probe: 'core, 'core> Mtb<'probe, 'core> {
+    /// Creates a new MTB component representation.
+    pub fn new(core: &'core mut Core<'probe>, component: &'core Component) -> Self {
+        Mtb { component, core }
+    }
+
+    /// The address of the trace buffer.
+    pub fn base(&mut self) -> Result<u32, Error> {
+        Ok(Base::load(self.component, self.core)?.0)
+    }
+
+    /// Configure and enable tracing, and clear the trace buffer.
+    pub fn enable(&mut self, config: &MtbConfig) -> Result<(), Error> {
+        if config.buffer_size < 16 || !config.buffer_size.is_power_of_two() {
+            return Err(Error::architecture_specific(
+                ComponentError::InvalidMtbBufferSize(config.buffer_size),
+            ));
+        }
+
+        let mut master = Master::load(self.component, self.core)?;
+        master.set_en(false);
+        master.store(self.component, self.core)?;
+
+        let base = self.base()?;
+
+        // The pointer is masked to the buffer, so this starts at the beginning of it.
+        let mut position = Position(0);
+        position.set_pointer(base >> 3);
+        position.store(self.component, self.core)?;
+
+        let mut flow = Flow(0);
+        if config.stop_when_full || config.halt_when_full {
+            // The watermark is the last packet of the buffer.
+            flow.set_watermark((base + config.buffer_size - 8) >> 3);
+            flow.set_autostop(config.stop_when_full);
+            flow.set_autohalt(config.halt_when_full);
+        }
+        flow.store(self.component, self.core)?;
+
+        master.set_mask(config.buffer_size.trailing_zeros() - 4);
+        master.set_tstarten(false);
+        master.set_tstopen(false);
+        master.set_en(true);
+        master.store(self.component, self.core)
+    }
+
+    /// Stop tracing.
+    pub fn disable(&mut self) -> Result<(), Error> {
+        let mut master = Master::load(self.component, self.core)?;
+        master.set_en(false);
+        master.store(self.component, self.core)
+    }
+
+    /// Read the recorded packets, from the oldest to the newest.
+    ///
+    /// Tracing is paused while the buffer is read, and enabled again afterwards
+    /// if it was enabled before.
+    pub fn read_trace(&mut self) -> Result<Vec<MtbPacket>, Error> {
+        let mut master = Master::load(self.component, self.core)?;
+        let enabled = master.en();
+
+        if enabled {
+            master.set_en(false);
+            master.store(self.component, self.core)?;
+        }
+
+        let size = 1u32 << (master.mask() + 4);
+        let base = self.base()?;
+        let position = Position::load(self.component, self.core)?;
+
+        let mut buffer = vec![0u8; size as usize];
+        self.core.read_8(base, &mut buffer)?;
+
+        if enabled {
+            master.set_en(true);
+            master.store(self.component, self.core)?;
+        }
+
+        let offset = (position.pointer() << 3).wrapping_sub(base) & (size - 1);
+
+        Ok(decode_mtb_buffer(&buffer, offset as usize, position.wrap()))
+    }
+}
+
+bitfield! {
+    #[derive(Clone)]
+    pub struct Position(u32);
+    impl Debug;
+    /// Bits [31:3] of the address of the next packet.
+    pub pointer, set_pointer: 31, 3;
+    /// Set when the pointer wrapped around the end of the buffer.
+    pub wrap, set_wrap: 2;
+}
+
+impl From<u32> for Position {
+    fn from(raw: u32) -> Self {
+        Position(raw)
+    }
+}
+
+impl From<Position> for u32 {
+    fn from(raw: Position) -> Self {
+        raw.0
+    }
+}
+
+impl DebugRegister for Position {
+    const ADDRESS: u32 = 0x00;
+    const NAME: &'static str = "MTB/POSITION";
+}
+
+bitfield! {
+    #[derive(Clone)]
+    pub struct Master(u32);
+    impl Debug;
+    pub en, set_en: 31;
+    pub haltreq, set_haltreq: 9;
+    pub rampriv, set_rampriv: 8;
+    pub sfrwpriv, set_sfrwpriv: 7;
+    pub tstopen, set_tstopen: 6;
+    pub tstarten, set_tstarten: 5;
+    /// The size of the buffer is 2^(MASK + 4) bytes.
+    pub mask, set_mask: 4, 0;
+}
+
+impl From<u32> for Master {
+    fn from(raw: u32) -> Self {
+        Master(raw)
+    }
+}
+
+impl From<Master> for u32 {
+    fn from(raw: Master) -> Self {
+        raw.0
+    }
+}
+
+impl DebugRegister for Master {
+    const ADDRESS: u32 = 0x04;
+    const NAME: &'static str = "MTB/MASTER";
+}
+
+bitfield! {
+    #[derive(Clone)]
+    pub struct Flow(u32);
+    impl Debug;
+    /// Bits [31:3] of the address at which the `autostop` and `autohalt` actions happen.
+    pub watermark, set_watermark: 31, 3;
+    pub autohalt, set_autohalt: 1;
+    pub autostop, set_autostop: 0;
+}
+
+impl From<u32> for Flow {
+    fn from(raw: u32) -> Self {
+        Flow(raw)
+    }
+}
+
+impl From<Flow> for u32 {
+    fn from(raw: Flow) -> Self {
+        raw.0
+    }
+}
+
+impl DebugRegister for Flow {
+    const ADDRESS: u32 = 0x08;
+    const NAME: &'static str = "MTB/FLOW";
+}
+
+bitfield! {
+    #[derive(Clone)]
+    pub struct Base(u32);
+    impl Debug;
+}
+
+impl From<u32> for Base {
+    fn from(raw: u32) -> Self {
+        Base(raw)
+    }
+}
+
+impl From<Base> for u32 {
+    fn from(raw: Base) -> Self {
+        raw.0
+    }
+}
+
+impl DebugRegister for Base {
+    const ADDRESS: u32 = 0x0C;
+    const NAME: &'static str = "MTB/BASE";
+}
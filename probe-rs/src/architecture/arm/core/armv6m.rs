@@ -1,20 +1,21 @@
-use super::{Dfsr, State, ARM_REGISTER_FILE};
+use super::{register, Dfsr, State, ARM_REGISTER_FILE};
 
-use crate::architecture::arm::sequences::ArmDebugSequence;
-use crate::core::{RegisterDescription, RegisterFile, RegisterKind};
+use crate::architecture::arm::sequences::{reset_with_kind, ArmDebugSequence};
+use crate::core::{
+    HaltDetails, RegisterDescription, RegisterFile, RegisterKind, StepInterruptBehavior,
+};
 use crate::error::Error;
 use crate::memory::Memory;
 use crate::{
     Architecture, CoreInformation, CoreInterface, CoreRegister, CoreRegisterAddress, CoreStatus,
     DebugProbeError, HaltReason, MemoryInterface,
 };
+use crate::{CoreType, ResetKind};
 use anyhow::Result;
 use bitfield::bitfield;
 use std::sync::Arc;
-use std::{
-    mem::size_of,
-    time::{Duration, Instant},
-};
+use std::{mem::size_of, time::Duration};
+use web_time::Instant;
 
 bitfield! {
     #[derive(Copy, Clone)]
@@ -165,7 +166,7 @@ impl BpCompx {
         } else if bp_val.bp_match() == 0b10 {
             Ok((bp_val.comp() << 2) | 0x2)
         } else {
-            return Err(Error::ArchitectureSpecific(Box::new(DebugProbeError::Other(anyhow::anyhow!("Unsupported breakpoint comparator value {:#08x} for HW breakpoint. Breakpoint must be on half-word boundaries", bp_val.0)))));
+            return Err(Error::UnsupportedBreakpointComparator(bp_val.0));
         }
     }
 }
@@ -261,8 +262,8 @@ const REGISTERS: RegisterFile = RegisterFile {
 };
 */
 
-pub const MSP: CoreRegisterAddress = CoreRegisterAddress(0b01001);
-pub const PSP: CoreRegisterAddress = CoreRegisterAddress(0b01010);
+pub const MSP: CoreRegisterAddress = register::MSP.address;
+pub const PSP: CoreRegisterAddress = register::PSP.address;
 
 const PC: RegisterDescription = RegisterDescription {
     name: "PC",
@@ -291,6 +292,10 @@ impl<'probe> Armv6m<'probe> {
         sequence: Arc<dyn ArmDebugSequence>,
     ) -> Result<Self, Error> {
         if !state.initialized() {
+            // Only the first core is started when the session is created,
+            // the debug system of other cores is started when they are attached.
+            sequence.debug_core_start(&mut memory)?;
+
             // determine current state
             let dhcsr = Dhcsr(memory.read_word_32(Dhcsr::ADDRESS)?);
 
@@ -332,7 +337,7 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
         let start = Instant::now();
 
         while start.elapsed() < timeout {
-            let dhcsr_val = Dhcsr(self.memory.read_word_32(Dhcsr::ADDRESS)?);
+            let dhcsr_val = Dhcsr(super::read_dhcsr(&mut self.memory, self.state)?);
 
             if dhcsr_val.s_halt() {
                 return Ok(());
@@ -344,7 +349,7 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
 
     fn core_halted(&mut self) -> Result<bool, Error> {
         // Wait until halted state is active again.
-        let dhcsr_val = Dhcsr(self.memory.read_word_32(Dhcsr::ADDRESS)?);
+        let dhcsr_val = Dhcsr(super::read_dhcsr(&mut self.memory, self.state)?);
 
         if dhcsr_val.s_halt() {
             Ok(true)
@@ -410,7 +415,7 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
         value.set_c_step(true);
         value.set_c_halt(false);
         value.set_c_debugen(true);
-        value.set_c_maskints(true);
+        value.set_c_maskints(self.state.mask_interrupts_while_stepping);
         value.enable_write();
 
         self.memory.write_word_32(Dhcsr::ADDRESS, value.into())?;
@@ -432,9 +437,38 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
         self.sequence.reset_system(&mut self.memory)
     }
 
-    fn reset_and_halt(&mut self, _timeout: Duration) -> Result<CoreInformation, Error> {
-        self.sequence.reset_catch_set(&mut self.memory)?;
-        self.sequence.reset_system(&mut self.memory)?;
+    fn reset_and_halt(&mut self, timeout: Duration) -> Result<CoreInformation, Error> {
+        self.reset_and_halt_with(ResetKind::System, timeout)
+    }
+
+    fn reset_with(&mut self, kind: ResetKind) -> Result<(), Error> {
+        reset_with_kind(
+            self.sequence.as_ref(),
+            &mut self.memory,
+            CoreType::Armv6m,
+            kind,
+        )
+    }
+
+    fn reset_and_halt_with(
+        &mut self,
+        kind: ResetKind,
+        timeout: Duration,
+    ) -> Result<CoreInformation, Error> {
+        // A reset catch which was already enabled, e.g. by the user, is kept.
+        let catch_enabled = Demcr(self.memory.read_word_32(Demcr::ADDRESS)?).vc_corereset();
+
+        self.enable_reset_catch()?;
+
+        // Disable the reset catch again, even if the core did not halt.
+        let halted = self
+            .reset_with(kind)
+            .and_then(|_| self.wait_for_core_halted(timeout));
+
+        if !catch_enabled {
+            self.disable_reset_catch()?;
+        }
+        halted?;
 
         // Update core status
         let _ = self.status()?;
@@ -445,8 +479,6 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
             self.write_core_reg(XPSR.address, xpsr_value | XPSR_THUMB)?;
         }
 
-        self.sequence.reset_catch_clear(&mut self.memory)?;
-
         // try to read the program counter
         let pc_value = self.read_core_reg(PC.address)?;
 
@@ -454,6 +486,14 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
         Ok(CoreInformation { pc: pc_value })
     }
 
+    fn enable_reset_catch(&mut self) -> Result<(), Error> {
+        self.sequence.reset_catch_set(&mut self.memory)
+    }
+
+    fn disable_reset_catch(&mut self) -> Result<(), Error> {
+        self.sequence.reset_catch_clear(&mut self.memory)
+    }
+
     fn get_available_breakpoint_units(&mut self) -> Result<u32, Error> {
         let result = self.memory.read_word_32(BpCtrl::ADDRESS)?;
 
@@ -482,7 +522,7 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
         // The highest 3 bits of the address have to be zero, otherwise the breakpoint cannot
         // be set at the address.
         if addr >= 0x2000_0000 {
-            return Err(Error::ArchitectureSpecific(Box::new(DebugProbeError::Other(anyhow::anyhow!("Unsupported address {:#08x} for HW breakpoint. Breakpoint must be at address < 0x2000_0000.", addr)))));
+            return Err(Error::UnsupportedBreakpointAddress(addr));
         }
 
         let mut value = BpCompx(0);
@@ -522,12 +562,46 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
         self.state.hw_breakpoints_enabled
     }
 
+    fn reset_detected(&mut self) -> Result<bool, Error> {
+        super::reset_detected(&mut self.memory, self.state)
+    }
+
+    fn reinitialize_debug(&mut self) -> Result<(), Error> {
+        super::reinitialize_debug(&mut self.memory, self.state, self.sequence.as_ref())
+    }
+
+    fn set_step_interrupt_behavior(
+        &mut self,
+        behavior: StepInterruptBehavior,
+    ) -> Result<(), Error> {
+        self.state.mask_interrupts_while_stepping = behavior == StepInterruptBehavior::Masked;
+
+        Ok(())
+    }
+
+    fn set_watchdog_freeze(&mut self, freeze: bool) -> Result<(), Error> {
+        super::set_watchdog_freeze(&mut self.memory, self.state, self.sequence.as_ref(), freeze)
+    }
+
+    fn halt_details(&mut self) -> Result<HaltDetails, Error> {
+        super::halt_details(self)
+    }
+
+    fn can_read_while_running(&mut self) -> bool {
+        // Memory is accessed through the MEM-AP, independent of the core.
+        true
+    }
+
+    fn can_write_while_running(&mut self) -> bool {
+        true
+    }
+
     fn architecture(&self) -> Architecture {
         Architecture::Arm
     }
 
     fn status(&mut self) -> Result<crate::core::CoreStatus, Error> {
-        let dhcsr = Dhcsr(self.memory.read_word_32(Dhcsr::ADDRESS)?);
+        let dhcsr = Dhcsr(super::read_dhcsr(&mut self.memory, self.state)?);
 
         if dhcsr.s_lockup() {
             log::warn!("The core is in locked up status as a result of an unrecoverable exception");
@@ -601,6 +675,18 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
         Ok(())
     }
 
+    fn read_core_regs(&mut self, addresses: &[CoreRegisterAddress]) -> Result<Vec<u32>, Error> {
+        let mut values = vec![0; addresses.len()];
+
+        self.memory.read_core_regs(addresses, &mut values)?;
+
+        Ok(values)
+    }
+
+    fn write_core_regs(&mut self, registers: &[(CoreRegisterAddress, u32)]) -> Result<(), Error> {
+        self.memory.write_core_regs(registers)
+    }
+
     /// See docs on the [`CoreInterface::get_hw_breakpoints`] trait
     fn get_hw_breakpoints(&mut self) -> Result<Vec<Option<u32>>, Error> {
         let mut breakpoints = vec![];
@@ -634,17 +720,26 @@ impl<'probe> MemoryInterface for Armv6m<'probe> {
         self.memory.read_8(address, data)
     }
     fn write_word_32(&mut self, address: u32, data: u32) -> Result<(), Error> {
-        self.memory.write_word_32(address, data)
+        self.memory.write_word_32(address, data)?;
+        super::save_demcr(self.state, address, &[data]);
+
+        Ok(())
     }
     fn write_word_8(&mut self, address: u32, data: u8) -> Result<(), Error> {
         self.memory.write_word_8(address, data)
     }
     fn write_32(&mut self, address: u32, data: &[u32]) -> Result<(), Error> {
-        self.memory.write_32(address, data)
+        self.memory.write_32(address, data)?;
+        super::save_demcr(self.state, address, data);
+
+        Ok(())
     }
     fn write_8(&mut self, address: u32, data: &[u8]) -> Result<(), Error> {
         self.memory.write_8(address, data)
     }
+    fn fill_32(&mut self, address: u32, value: u32, count: usize) -> Result<(), Error> {
+        self.memory.fill_32(address, value, count)
+    }
     fn flush(&mut self) -> Result<(), Error> {
         self.memory.flush()
     }
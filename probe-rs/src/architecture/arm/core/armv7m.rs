@@ -1,22 +1,25 @@
-use crate::architecture::arm::sequences::ArmDebugSequence;
+use crate::architecture::arm::sequences::{reset_with_kind, ArmDebugSequence};
 use crate::core::{
-    CoreInformation, CoreInterface, CoreRegister, CoreRegisterAddress, RegisterFile,
+    CoreInformation, CoreInterface, CoreRegister, CoreRegisterAddress, FaultStatus, HaltDetails,
+    RegisterFile, StepInterruptBehavior,
 };
 use crate::error::Error;
 use crate::memory::Memory;
 use crate::DebugProbeError;
+use crate::{CoreType, ResetKind};
 
 use super::{register, Dfsr, State, ARM_REGISTER_FILE};
 use crate::{
     core::{Architecture, CoreStatus, HaltReason},
     MemoryInterface,
 };
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 
 use bitfield::bitfield;
 use std::mem::size_of;
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::Duration;
+use web_time::Instant;
 
 bitfield! {
     #[derive(Copy, Clone)]
@@ -251,7 +254,7 @@ impl FpRev1CompX {
         } else if fp1_val.replace() == 0b10 {
             Ok((fp1_val.comp() << 2) | 0x2)
         } else {
-            return Err(Error::ArchitectureSpecific(Box::new(DebugProbeError::Other(anyhow::anyhow!("Unsupported breakpoint comparator value {:#08x} for HW breakpoint. Breakpoint must be on half-word boundaries", fp1_val.0)))));
+            return Err(Error::UnsupportedBreakpointComparator(fp1_val.0));
         }
     }
     /// Get the correct register configuration which enables
@@ -263,7 +266,7 @@ impl FpRev1CompX {
         // The highest 3 bits of the address have to be zero, otherwise the breakpoint cannot
         // be set at the address.
         if address >= 0x2000_0000 {
-            return Err(Error::ArchitectureSpecific(Box::new(DebugProbeError::Other(anyhow::anyhow!("Unsupported address {:#08x} for HW breakpoint. Breakpoint must be at address < 0x2000_0000.", address)))));
+            return Err(Error::UnsupportedBreakpointAddress(address));
         }
 
         let comp_val = (address & 0x1f_ff_ff_fc) >> 2;
@@ -323,8 +326,8 @@ impl FpRev2CompX {
     }
 }
 
-pub const MSP: CoreRegisterAddress = CoreRegisterAddress(0b000_1001);
-pub const PSP: CoreRegisterAddress = CoreRegisterAddress(0b000_1010);
+pub const MSP: CoreRegisterAddress = register::MSP.address;
+pub const PSP: CoreRegisterAddress = register::PSP.address;
 
 pub struct Armv7m<'probe> {
     memory: Memory<'probe>,
@@ -341,6 +344,10 @@ impl<'probe> Armv7m<'probe> {
         sequence: Arc<dyn ArmDebugSequence>,
     ) -> Result<Self, Error> {
         if !state.initialized() {
+            // Only the first core is started when the session is created,
+            // the debug system of other cores is started when they are attached.
+            sequence.debug_core_start(&mut memory)?;
+
             // determine current state
             let dhcsr = Dhcsr(memory.read_word_32(Dhcsr::ADDRESS)?);
 
@@ -382,7 +389,7 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
         let start = Instant::now();
 
         while start.elapsed() < timeout {
-            let dhcsr_val = Dhcsr(self.memory.read_word_32(Dhcsr::ADDRESS)?);
+            let dhcsr_val = Dhcsr(super::read_dhcsr(&mut self.memory, self.state)?);
             if dhcsr_val.s_halt() {
                 // update halted state
                 self.status()?;
@@ -396,7 +403,7 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
 
     fn core_halted(&mut self) -> Result<bool, Error> {
         // Wait until halted state is active again.
-        let dhcsr_val = Dhcsr(self.memory.read_word_32(Dhcsr::ADDRESS)?);
+        let dhcsr_val = Dhcsr(super::read_dhcsr(&mut self.memory, self.state)?);
 
         if dhcsr_val.s_halt() {
             Ok(true)
@@ -406,7 +413,7 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
     }
 
     fn status(&mut self) -> Result<CoreStatus, Error> {
-        let dhcsr = Dhcsr(self.memory.read_word_32(Dhcsr::ADDRESS)?);
+        let dhcsr = Dhcsr(super::read_dhcsr(&mut self.memory, self.state)?);
 
         if dhcsr.s_lockup() {
             log::error!(
@@ -481,6 +488,18 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
         Ok(())
     }
 
+    fn read_core_regs(&mut self, addresses: &[CoreRegisterAddress]) -> Result<Vec<u32>, Error> {
+        let mut values = vec![0; addresses.len()];
+
+        self.memory.read_core_regs(addresses, &mut values)?;
+
+        Ok(values)
+    }
+
+    fn write_core_regs(&mut self, registers: &[(CoreRegisterAddress, u32)]) -> Result<(), Error> {
+        self.memory.write_core_regs(registers)
+    }
+
     fn halt(&mut self, timeout: Duration) -> Result<CoreInformation, Error> {
         // TODO: Generic halt support
 
@@ -507,7 +526,7 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
         // Before we run, we always perform a single instruction step, to account for possible breakpoints that might get us stuck on the current instruction.
         self.step()?;
 
-        let mut dhcsr = Dhcsr(self.memory.read_word_32(Dhcsr::ADDRESS)?);
+        let mut dhcsr = Dhcsr(super::read_dhcsr(&mut self.memory, self.state)?);
 
         // First disable the DHCSR->C_MASKINTS.
         if dhcsr.c_maskints() {
@@ -540,14 +559,15 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
                 false
             };
 
-        let mut dhcsr = Dhcsr(self.memory.read_word_32(Dhcsr::ADDRESS)?);
+        let mut dhcsr = Dhcsr(super::read_dhcsr(&mut self.memory, self.state)?);
 
         // Follow the rules of the ... ARMv7-M Architecture reference, C1.6 Debug System Registers - DHCSR, with respect to setting maskints
         if !dhcsr.c_debugen() {
             log::warn!("Attempting to STEP while DHCSR->C_DEBUGEN is false");
         }
-        if !dhcsr.c_maskints() {
-            dhcsr.set_c_maskints(true); // This must be reset to false when we run() again.
+        let mask_interrupts = self.state.mask_interrupts_while_stepping;
+        if dhcsr.c_maskints() != mask_interrupts {
+            dhcsr.set_c_maskints(mask_interrupts); // This must be reset to false when we run() again.
             dhcsr.enable_write();
             self.memory.write_word_32(Dhcsr::ADDRESS, dhcsr.into())?;
             self.memory.flush()?;
@@ -579,12 +599,38 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
         self.sequence.reset_system(&mut self.memory)
     }
 
-    fn reset_and_halt(&mut self, _timeout: Duration) -> Result<CoreInformation, Error> {
-        // Set the vc_corereset bit in the DEMCR register.
-        // This will halt the core after reset.
+    fn reset_and_halt(&mut self, timeout: Duration) -> Result<CoreInformation, Error> {
+        self.reset_and_halt_with(ResetKind::System, timeout)
+    }
+
+    fn reset_with(&mut self, kind: ResetKind) -> Result<(), Error> {
+        reset_with_kind(
+            self.sequence.as_ref(),
+            &mut self.memory,
+            CoreType::Armv7m,
+            kind,
+        )
+    }
+
+    fn reset_and_halt_with(
+        &mut self,
+        kind: ResetKind,
+        timeout: Duration,
+    ) -> Result<CoreInformation, Error> {
+        // A reset catch which was already enabled, e.g. by the user, is kept.
+        let catch_enabled = Demcr(self.memory.read_word_32(Demcr::ADDRESS)?).vc_corereset();
 
-        self.sequence.reset_catch_set(&mut self.memory)?;
-        self.sequence.reset_system(&mut self.memory)?;
+        self.enable_reset_catch()?;
+
+        // Disable the reset catch again, even if the core did not halt.
+        let halted = self
+            .reset_with(kind)
+            .and_then(|_| self.wait_for_core_halted(timeout));
+
+        if !catch_enabled {
+            self.disable_reset_catch()?;
+        }
+        halted?;
 
         // Update core status
         let _ = self.status()?;
@@ -595,8 +641,6 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
             self.write_core_reg(register::XPSR.address, xpsr_value | XPSR_THUMB)?;
         }
 
-        self.sequence.reset_catch_clear(&mut self.memory)?;
-
         // try to read the program counter
         let pc_value = self.read_core_reg(register::PC.address)?;
 
@@ -604,6 +648,14 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
         Ok(CoreInformation { pc: pc_value })
     }
 
+    fn enable_reset_catch(&mut self) -> Result<(), Error> {
+        self.sequence.reset_catch_set(&mut self.memory)
+    }
+
+    fn disable_reset_catch(&mut self) -> Result<(), Error> {
+        self.sequence.reset_catch_clear(&mut self.memory)
+    }
+
     fn get_available_breakpoint_units(&mut self) -> Result<u32, Error> {
         let raw_val = self.memory.read_word_32(FpCtrl::ADDRESS)?;
 
@@ -635,10 +687,7 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
     fn set_hw_breakpoint(&mut self, bp_unit_index: usize, addr: u32) -> Result<(), Error> {
         // First make sure they are asking for a breakpoint on a half-word boundary.
         if (addr & 0x1) > 0 {
-            return Err(Error::Other(anyhow!(
-                "The requested breakpoint address 0x{:08x} is not on a half-word boundary",
-                addr
-            )));
+            return Err(Error::UnsupportedBreakpointAddress(addr));
         }
 
         let raw_val = self.memory.read_word_32(FpCtrl::ADDRESS)?;
@@ -651,7 +700,7 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
             val = FpRev2CompX::breakpoint_configuration(addr).into();
         } else {
             log::warn!("This chip uses FPBU revision {}, which is not yet supported. HW breakpoints are not available.", ctrl_reg.rev());
-            return Err(Error::Other(anyhow!("This chip uses FPBU revision {}, which is not yet supported. HW breakpoints are not available.", ctrl_reg.rev())));
+            return Err(Error::UnsupportedFpbRevision(ctrl_reg.rev()));
         }
 
         // This is fine as FpRev1CompX and Rev2CompX are just two different
@@ -683,6 +732,44 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
         self.state.hw_breakpoints_enabled
     }
 
+    fn reset_detected(&mut self) -> Result<bool, Error> {
+        super::reset_detected(&mut self.memory, self.state)
+    }
+
+    fn reinitialize_debug(&mut self) -> Result<(), Error> {
+        super::reinitialize_debug(&mut self.memory, self.state, self.sequence.as_ref())
+    }
+
+    fn set_step_interrupt_behavior(
+        &mut self,
+        behavior: StepInterruptBehavior,
+    ) -> Result<(), Error> {
+        self.state.mask_interrupts_while_stepping = behavior == StepInterruptBehavior::Masked;
+
+        Ok(())
+    }
+
+    fn set_watchdog_freeze(&mut self, freeze: bool) -> Result<(), Error> {
+        super::set_watchdog_freeze(&mut self.memory, self.state, self.sequence.as_ref(), freeze)
+    }
+
+    fn halt_details(&mut self) -> Result<HaltDetails, Error> {
+        super::halt_details(self)
+    }
+
+    fn fault_status(&mut self) -> Result<Option<FaultStatus>, Error> {
+        super::fault_status(&mut self.memory)
+    }
+
+    fn can_read_while_running(&mut self) -> bool {
+        // Memory is accessed through the MEM-AP, independent of the core.
+        true
+    }
+
+    fn can_write_while_running(&mut self) -> bool {
+        true
+    }
+
     fn architecture(&self) -> Architecture {
         Architecture::Arm
     }
@@ -708,7 +795,7 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
                     breakpoint = FpRev2CompX::from(register_value).bpaddr() << 1;
                 } else {
                     log::warn!("This chip uses FPBU revision {}, which is not yet supported. HW breakpoints are not available.", ctrl_reg.rev());
-                    return Err(Error::Other(anyhow!("This chip uses FPBU revision {}, which is not yet supported. HW breakpoints are not available.", ctrl_reg.rev())));
+                    return Err(Error::UnsupportedFpbRevision(ctrl_reg.rev()));
                 }
                 breakpoints.push(Some(breakpoint));
             } else {
@@ -734,17 +821,26 @@ impl<'probe> MemoryInterface for Armv7m<'probe> {
         self.memory.read_8(address, data)
     }
     fn write_word_32(&mut self, address: u32, data: u32) -> Result<(), Error> {
-        self.memory.write_word_32(address, data)
+        self.memory.write_word_32(address, data)?;
+        super::save_demcr(self.state, address, &[data]);
+
+        Ok(())
     }
     fn write_word_8(&mut self, address: u32, data: u8) -> Result<(), Error> {
         self.memory.write_word_8(address, data)
     }
     fn write_32(&mut self, address: u32, data: &[u32]) -> Result<(), Error> {
-        self.memory.write_32(address, data)
+        self.memory.write_32(address, data)?;
+        super::save_demcr(self.state, address, data);
+
+        Ok(())
     }
     fn write_8(&mut self, address: u32, data: &[u8]) -> Result<(), Error> {
         self.memory.write_8(address, data)
     }
+    fn fill_32(&mut self, address: u32, value: u32, count: usize) -> Result<(), Error> {
+        self.memory.fill_32(address, value, count)
+    }
     fn flush(&mut self) -> Result<(), Error> {
         self.memory.flush()
     }
@@ -1,8 +1,11 @@
 //! Support for Cortex-M33
 //!
 
-use crate::architecture::arm::sequences::ArmDebugSequence;
-use crate::core::RegisterFile;
+use crate::architecture::arm::sequences::{reset_with_kind, ArmDebugSequence};
+use crate::core::{
+    FaultStatus, HaltDetails, RegisterDescription, RegisterFile, RegisterKind, SecurityState,
+    StepInterruptBehavior,
+};
 use crate::error::Error;
 use crate::memory::Memory;
 use crate::CoreRegisterAddress;
@@ -11,16 +14,15 @@ use crate::{
 };
 use crate::{Architecture, CoreInformation};
 use crate::{CoreInterface, CoreRegister};
+use crate::{CoreType, ResetKind};
 use anyhow::Result;
 
 use bitfield::bitfield;
 
-use super::{Dfsr, State, ARM_REGISTER_FILE};
+use super::{Dfsr, State, ARMV8M_REGISTER_FILE};
 use std::sync::Arc;
-use std::{
-    mem::size_of,
-    time::{Duration, Instant},
-};
+use std::{mem::size_of, time::Duration};
+use web_time::Instant;
 
 pub struct Armv8m<'probe> {
     memory: Memory<'probe>,
@@ -37,6 +39,10 @@ impl<'probe> Armv8m<'probe> {
         sequence: Arc<dyn ArmDebugSequence>,
     ) -> Result<Self, Error> {
         if !state.initialized() {
+            // Only the first core is started when the session is created,
+            // the debug system of other cores is started when they are attached.
+            sequence.debug_core_start(&mut memory)?;
+
             // determine current state
             let dhcsr = Dhcsr(memory.read_word_32(Dhcsr::ADDRESS)?);
 
@@ -66,11 +72,33 @@ impl<'probe> Armv8m<'probe> {
             state.initialize();
         }
 
-        Ok(Self {
+        let mut core = Self {
             memory,
             state,
             sequence,
-        })
+        };
+
+        if core.has_security_extension()? && !core.secure_debug_enabled()? {
+            log::warn!(
+                "Secure debug is disabled, the core can only be debugged in non-secure state"
+            );
+        }
+
+        Ok(core)
+    }
+
+    /// Check if the core implements the security extension (TrustZone-M).
+    fn has_security_extension(&mut self) -> Result<bool, Error> {
+        let id_pfr1 = IdPfr1(self.memory.read_word_32(IdPfr1::ADDRESS)?);
+
+        Ok(id_pfr1.security() != 0)
+    }
+
+    /// Check if halting debug is allowed in secure state.
+    fn secure_debug_enabled(&mut self) -> Result<bool, Error> {
+        let dhcsr = Dhcsr(super::read_dhcsr(&mut self.memory, self.state)?);
+
+        Ok(dhcsr.s_sde())
     }
 }
 
@@ -80,7 +108,7 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
         let start = Instant::now();
 
         while start.elapsed() < timeout {
-            let dhcsr_val = Dhcsr(self.memory.read_word_32(Dhcsr::ADDRESS)?);
+            let dhcsr_val = Dhcsr(super::read_dhcsr(&mut self.memory, self.state)?);
             if dhcsr_val.s_halt() {
                 return Ok(());
             }
@@ -91,7 +119,7 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
 
     fn core_halted(&mut self) -> Result<bool, Error> {
         // Wait until halted state is active again.
-        let dhcsr_val = Dhcsr(self.memory.read_word_32(Dhcsr::ADDRESS)?);
+        let dhcsr_val = Dhcsr(super::read_dhcsr(&mut self.memory, self.state)?);
 
         if dhcsr_val.s_halt() {
             Ok(true)
@@ -108,7 +136,16 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
 
         self.memory.write_word_32(Dhcsr::ADDRESS, value.into())?;
 
-        self.wait_for_core_halted(timeout)?;
+        match self.wait_for_core_halted(timeout) {
+            // A halt request is delayed while the core is executing in secure state,
+            // if secure debug is disabled.
+            Err(Error::Probe(DebugProbeError::Timeout))
+                if self.has_security_extension()? && !self.secure_debug_enabled()? =>
+            {
+                return Err(Error::SecureDebugDisabled);
+            }
+            result => result?,
+        }
 
         // Update core status
         let _ = self.status()?;
@@ -142,12 +179,48 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
         self.sequence.reset_system(&mut self.memory)
     }
 
-    fn reset_and_halt(&mut self, _timeout: Duration) -> Result<CoreInformation, Error> {
-        // Set the vc_corereset bit in the DEMCR register.
-        // This will halt the core after reset.
+    fn reset_and_halt(&mut self, timeout: Duration) -> Result<CoreInformation, Error> {
+        self.reset_and_halt_with(ResetKind::System, timeout)
+    }
+
+    fn reset_with(&mut self, kind: ResetKind) -> Result<(), Error> {
+        reset_with_kind(
+            self.sequence.as_ref(),
+            &mut self.memory,
+            CoreType::Armv8m,
+            kind,
+        )
+    }
+
+    fn reset_and_halt_with(
+        &mut self,
+        kind: ResetKind,
+        timeout: Duration,
+    ) -> Result<CoreInformation, Error> {
+        // A reset catch which was already enabled, e.g. by the user, is kept.
+        let catch_enabled = Demcr(self.memory.read_word_32(Demcr::ADDRESS)?).vc_corereset();
+
+        self.enable_reset_catch()?;
+
+        // Disable the reset catch again, even if the core did not halt.
+        let halted = self
+            .reset_with(kind)
+            .and_then(|_| self.wait_for_core_halted(timeout));
 
-        self.sequence.reset_catch_set(&mut self.memory)?;
-        self.sequence.reset_system(&mut self.memory)?;
+        if !catch_enabled {
+            self.disable_reset_catch()?;
+        }
+
+        match halted {
+            // The reset catch is delayed until the core leaves secure state,
+            // if secure debug is disabled.
+            Err(Error::Probe(DebugProbeError::Timeout))
+                if self.has_security_extension()? && !self.secure_debug_enabled()? =>
+            {
+                return Err(Error::SecureDebugDisabled);
+            }
+            result => result?,
+        }
 
         // Update core status
         let _ = self.status()?;
@@ -158,8 +231,6 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
             self.write_core_reg(register::XPSR.address, xpsr_value | XPSR_THUMB)?;
         }
 
-        self.sequence.reset_catch_clear(&mut self.memory)?;
-
         // try to read the program counter
         let pc_value = self.read_core_reg(register::PC.address)?;
 
@@ -167,6 +238,14 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
         Ok(CoreInformation { pc: pc_value })
     }
 
+    fn enable_reset_catch(&mut self) -> Result<(), Error> {
+        self.sequence.reset_catch_set(&mut self.memory)
+    }
+
+    fn disable_reset_catch(&mut self) -> Result<(), Error> {
+        self.sequence.reset_catch_clear(&mut self.memory)
+    }
+
     fn step(&mut self) -> Result<CoreInformation, Error> {
         // First check if we stopped on a breakpoint, because this requires special handling before we can continue.
         let was_breakpoint =
@@ -184,7 +263,7 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
         value.set_c_step(true);
         value.set_c_halt(false);
         value.set_c_debugen(true);
-        value.set_c_maskints(true);
+        value.set_c_maskints(self.state.mask_interrupts_while_stepping);
         value.enable_write();
 
         self.memory.write_word_32(Dhcsr::ADDRESS, value.into())?;
@@ -213,6 +292,18 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
         Ok(())
     }
 
+    fn read_core_regs(&mut self, addresses: &[CoreRegisterAddress]) -> Result<Vec<u32>, Error> {
+        let mut values = vec![0; addresses.len()];
+
+        self.memory.read_core_regs(addresses, &mut values)?;
+
+        Ok(values)
+    }
+
+    fn write_core_regs(&mut self, registers: &[(CoreRegisterAddress, u32)]) -> Result<(), Error> {
+        self.memory.write_core_regs(registers)
+    }
+
     fn get_available_breakpoint_units(&mut self) -> Result<u32, Error> {
         let raw_val = self.memory.read_word_32(FpCtrl::ADDRESS)?;
 
@@ -251,7 +342,7 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
     }
 
     fn registers(&self) -> &'static RegisterFile {
-        &ARM_REGISTER_FILE
+        &ARMV8M_REGISTER_FILE
     }
 
     fn clear_hw_breakpoint(&mut self, bp_unit_index: usize) -> Result<(), Error> {
@@ -270,12 +361,83 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
         self.state.hw_breakpoints_enabled
     }
 
+    fn reset_detected(&mut self) -> Result<bool, Error> {
+        super::reset_detected(&mut self.memory, self.state)
+    }
+
+    fn reinitialize_debug(&mut self) -> Result<(), Error> {
+        super::reinitialize_debug(&mut self.memory, self.state, self.sequence.as_ref())
+    }
+
+    fn set_step_interrupt_behavior(
+        &mut self,
+        behavior: StepInterruptBehavior,
+    ) -> Result<(), Error> {
+        self.state.mask_interrupts_while_stepping = behavior == StepInterruptBehavior::Masked;
+
+        Ok(())
+    }
+
+    fn set_watchdog_freeze(&mut self, freeze: bool) -> Result<(), Error> {
+        super::set_watchdog_freeze(&mut self.memory, self.state, self.sequence.as_ref(), freeze)
+    }
+
+    fn halt_details(&mut self) -> Result<HaltDetails, Error> {
+        super::halt_details(self)
+    }
+
+    fn fault_status(&mut self) -> Result<Option<FaultStatus>, Error> {
+        super::fault_status(&mut self.memory)
+    }
+
+    fn can_read_while_running(&mut self) -> bool {
+        // Memory is accessed through the MEM-AP, independent of the core.
+        true
+    }
+
+    fn can_write_while_running(&mut self) -> bool {
+        true
+    }
+
     fn architecture(&self) -> Architecture {
         Architecture::Arm
     }
 
+    fn security_state(&mut self) -> Result<Option<SecurityState>, Error> {
+        if !self.has_security_extension()? {
+            return Ok(None);
+        }
+
+        let dscsr = Dscsr(self.memory.read_word_32(Dscsr::ADDRESS)?);
+
+        Ok(Some(if dscsr.cds() {
+            SecurityState::Secure
+        } else {
+            SecurityState::NonSecure
+        }))
+    }
+
+    fn set_security_state(&mut self, state: SecurityState) -> Result<(), Error> {
+        if !self.has_security_extension()? {
+            return Err(Error::NoSecurityExtension);
+        }
+
+        if state == SecurityState::Secure && !self.secure_debug_enabled()? {
+            return Err(Error::SecureDebugDisabled);
+        }
+
+        let mut dscsr = Dscsr(self.memory.read_word_32(Dscsr::ADDRESS)?);
+        // CDS is only updated if CDSKEY is written as zero.
+        dscsr.set_cdskey(false);
+        dscsr.set_cds(state == SecurityState::Secure);
+
+        self.memory.write_word_32(Dscsr::ADDRESS, dscsr.into())?;
+
+        Ok(())
+    }
+
     fn status(&mut self) -> Result<crate::core::CoreStatus, Error> {
-        let dhcsr = Dhcsr(self.memory.read_word_32(Dhcsr::ADDRESS)?);
+        let dhcsr = Dhcsr(super::read_dhcsr(&mut self.memory, self.state)?);
 
         if dhcsr.s_lockup() {
             log::warn!("The core is in locked up status as a result of an unrecoverable exception");
@@ -374,17 +536,26 @@ impl<'probe> MemoryInterface for Armv8m<'probe> {
         self.memory.read_8(address, data)
     }
     fn write_word_32(&mut self, address: u32, data: u32) -> Result<(), Error> {
-        self.memory.write_word_32(address, data)
+        self.memory.write_word_32(address, data)?;
+        super::save_demcr(self.state, address, &[data]);
+
+        Ok(())
     }
     fn write_word_8(&mut self, address: u32, data: u8) -> Result<(), Error> {
         self.memory.write_word_8(address, data)
     }
     fn write_32(&mut self, address: u32, data: &[u32]) -> Result<(), Error> {
-        self.memory.write_32(address, data)
+        self.memory.write_32(address, data)?;
+        super::save_demcr(self.state, address, data);
+
+        Ok(())
     }
     fn write_8(&mut self, address: u32, data: &[u8]) -> Result<(), Error> {
         self.memory.write_8(address, data)
     }
+    fn fill_32(&mut self, address: u32, value: u32, count: usize) -> Result<(), Error> {
+        self.memory.fill_32(address, value, count)
+    }
     fn flush(&mut self) -> Result<(), Error> {
         self.memory.flush()
     }
@@ -409,6 +580,78 @@ pub const REGISTERS: BasicRegisterAddresses = BasicRegisterAddresses {
 };
 */
 
+/// The non-secure main stack pointer, only available with the security extension.
+pub const MSP_NS: RegisterDescription = RegisterDescription {
+    name: "MSP_NS",
+    kind: RegisterKind::General,
+    address: CoreRegisterAddress(0b001_1000),
+};
+
+/// The non-secure process stack pointer, only available with the security extension.
+pub const PSP_NS: RegisterDescription = RegisterDescription {
+    name: "PSP_NS",
+    kind: RegisterKind::General,
+    address: CoreRegisterAddress(0b001_1001),
+};
+
+/// The secure main stack pointer, only accessible if secure debug is enabled.
+pub const MSP_S: RegisterDescription = RegisterDescription {
+    name: "MSP_S",
+    kind: RegisterKind::General,
+    address: CoreRegisterAddress(0b001_1010),
+};
+
+/// The secure process stack pointer, only accessible if secure debug is enabled.
+pub const PSP_S: RegisterDescription = RegisterDescription {
+    name: "PSP_S",
+    kind: RegisterKind::General,
+    address: CoreRegisterAddress(0b001_1011),
+};
+
+/// The secure main stack limit, only accessible if secure debug is enabled.
+pub const MSPLIM_S: RegisterDescription = RegisterDescription {
+    name: "MSPLIM_S",
+    kind: RegisterKind::General,
+    address: CoreRegisterAddress(0b001_1100),
+};
+
+/// The secure process stack limit, only accessible if secure debug is enabled.
+pub const PSPLIM_S: RegisterDescription = RegisterDescription {
+    name: "PSPLIM_S",
+    kind: RegisterKind::General,
+    address: CoreRegisterAddress(0b001_1101),
+};
+
+/// The non-secure main stack limit.
+pub const MSPLIM_NS: RegisterDescription = RegisterDescription {
+    name: "MSPLIM_NS",
+    kind: RegisterKind::General,
+    address: CoreRegisterAddress(0b001_1110),
+};
+
+/// The non-secure process stack limit.
+pub const PSPLIM_NS: RegisterDescription = RegisterDescription {
+    name: "PSPLIM_NS",
+    kind: RegisterKind::General,
+    address: CoreRegisterAddress(0b001_1111),
+};
+
+/// The secure `CONTROL`, `FAULTMASK`, `BASEPRI` and `PRIMASK` registers, only accessible if
+/// secure debug is enabled. See [`Cfbp`](super::Cfbp) for the layout.
+pub const CFBP_S: RegisterDescription = RegisterDescription {
+    name: "CFBP_S",
+    kind: RegisterKind::General,
+    address: CoreRegisterAddress(0b010_0010),
+};
+
+/// The non-secure `CONTROL`, `FAULTMASK`, `BASEPRI` and `PRIMASK` registers, only available
+/// with the security extension. See [`Cfbp`](super::Cfbp) for the layout.
+pub const CFBP_NS: RegisterDescription = RegisterDescription {
+    name: "CFBP_NS",
+    kind: RegisterKind::General,
+    address: CoreRegisterAddress(0b010_0011),
+};
+
 bitfield! {
     #[derive(Copy, Clone)]
     pub struct Dhcsr(u32);
@@ -494,6 +737,64 @@ impl CoreRegister for Aircr {
     const NAME: &'static str = "AIRCR";
 }
 
+bitfield! {
+    /// Debug Security Control and Status Register
+    #[derive(Copy, Clone)]
+    pub struct Dscsr(u32);
+    impl Debug;
+    /// Has to be written as zero to update the `CDS` bit.
+    pub cdskey, set_cdskey: 17;
+    /// The current security state of the processor, set if the processor is in secure state.
+    pub cds, set_cds: 16;
+    /// Selects the banked registers accessed by the debugger, if `SBRSELEN` is set.
+    pub sbrsel, set_sbrsel: 1;
+    /// Use `SBRSEL` instead of the current security state to select the banked registers.
+    pub sbrselen, set_sbrselen: 0;
+}
+
+impl From<u32> for Dscsr {
+    fn from(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Dscsr> for u32 {
+    fn from(value: Dscsr) -> Self {
+        value.0
+    }
+}
+
+impl CoreRegister for Dscsr {
+    const ADDRESS: u32 = 0xE000_EE08;
+    const NAME: &'static str = "DSCSR";
+}
+
+bitfield! {
+    /// Processor Feature Register 1
+    #[derive(Copy, Clone)]
+    pub struct IdPfr1(u32);
+    impl Debug;
+    /// Non-zero if the security extension is implemented.
+    pub security, _: 7, 4;
+}
+
+impl From<u32> for IdPfr1 {
+    fn from(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+impl From<IdPfr1> for u32 {
+    fn from(value: IdPfr1) -> Self {
+        value.0
+    }
+}
+
+impl CoreRegister for IdPfr1 {
+    const ADDRESS: u32 = 0xE000_ED44;
+    const NAME: &'static str = "ID_PFR1";
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct Dcrdr(u32);
 
@@ -1,8 +1,13 @@
 use crate::{
-    core::{CoreRegister, CoreRegisterAddress, RegisterDescription, RegisterFile, RegisterKind},
-    CoreStatus, HaltReason,
+    core::{
+        BreakpointCause, CoreInterface, CoreRegister, CoreRegisterAddress, FaultStatus,
+        HaltDetails, RegisterDescription, RegisterFile, RegisterKind,
+    },
+    CoreStatus, Error, HaltReason,
 };
 
+use crate::architecture::arm::sequences::ArmDebugSequence;
+use crate::memory::Memory;
 use bitfield::bitfield;
 
 pub mod armv6m;
@@ -55,133 +60,224 @@ pub(crate) mod register {
         kind: RegisterKind::General,
         address: CoreRegisterAddress(14),
     };
+
+    /// The main stack pointer. On ARMv8-M, this is the one of the current security state.
+    pub const MSP: RegisterDescription = RegisterDescription {
+        name: "MSP",
+        kind: RegisterKind::General,
+        address: CoreRegisterAddress(0b1_0001),
+    };
+
+    /// The process stack pointer. On ARMv8-M, this is the one of the current security state.
+    pub const PSP: RegisterDescription = RegisterDescription {
+        name: "PSP",
+        kind: RegisterKind::General,
+        address: CoreRegisterAddress(0b1_0010),
+    };
+
+    /// `CONTROL`, `FAULTMASK`, `BASEPRI` and `PRIMASK`, which are combined into a single
+    /// register by the debug interface, see [`Cfbp`](super::Cfbp).
+    pub const CFBP: RegisterDescription = RegisterDescription {
+        name: "CFBP",
+        kind: RegisterKind::General,
+        address: CoreRegisterAddress(0b1_0100),
+    };
 }
 
-static ARM_REGISTER_FILE: RegisterFile = RegisterFile {
-    platform_registers: &[
-        RegisterDescription {
-            name: "R0",
-            kind: RegisterKind::General,
-            address: CoreRegisterAddress(0),
-        },
-        RegisterDescription {
-            name: "R1",
-            kind: RegisterKind::General,
-            address: CoreRegisterAddress(1),
-        },
-        RegisterDescription {
-            name: "R2",
-            kind: RegisterKind::General,
-            address: CoreRegisterAddress(2),
-        },
-        RegisterDescription {
-            name: "R3",
-            kind: RegisterKind::General,
-            address: CoreRegisterAddress(3),
-        },
-        RegisterDescription {
-            name: "R4",
-            kind: RegisterKind::General,
-            address: CoreRegisterAddress(4),
-        },
-        RegisterDescription {
-            name: "R5",
-            kind: RegisterKind::General,
-            address: CoreRegisterAddress(5),
-        },
-        RegisterDescription {
-            name: "R6",
-            kind: RegisterKind::General,
-            address: CoreRegisterAddress(6),
-        },
-        RegisterDescription {
-            name: "R7",
-            kind: RegisterKind::General,
-            address: CoreRegisterAddress(7),
-        },
-        RegisterDescription {
-            name: "R8",
-            kind: RegisterKind::General,
-            address: CoreRegisterAddress(8),
-        },
-        RegisterDescription {
-            name: "R9",
-            kind: RegisterKind::General,
-            address: CoreRegisterAddress(9),
-        },
-        RegisterDescription {
-            name: "R10",
-            kind: RegisterKind::General,
-            address: CoreRegisterAddress(10),
-        },
-        RegisterDescription {
-            name: "R11",
-            kind: RegisterKind::General,
-            address: CoreRegisterAddress(11),
-        },
-        RegisterDescription {
-            name: "R12",
-            kind: RegisterKind::General,
-            address: CoreRegisterAddress(12),
-        },
-        RegisterDescription {
-            name: "R13",
-            kind: RegisterKind::General,
-            address: CoreRegisterAddress(13),
-        },
-        RegisterDescription {
-            name: "R14",
-            kind: RegisterKind::General,
-            address: CoreRegisterAddress(14),
-        },
-        RegisterDescription {
-            name: "R15",
-            kind: RegisterKind::General,
-            address: CoreRegisterAddress(15),
-        },
-    ],
+const PLATFORM_REGISTERS: &[RegisterDescription] = &[
+    RegisterDescription {
+        name: "R0",
+        kind: RegisterKind::General,
+        address: CoreRegisterAddress(0),
+    },
+    RegisterDescription {
+        name: "R1",
+        kind: RegisterKind::General,
+        address: CoreRegisterAddress(1),
+    },
+    RegisterDescription {
+        name: "R2",
+        kind: RegisterKind::General,
+        address: CoreRegisterAddress(2),
+    },
+    RegisterDescription {
+        name: "R3",
+        kind: RegisterKind::General,
+        address: CoreRegisterAddress(3),
+    },
+    RegisterDescription {
+        name: "R4",
+        kind: RegisterKind::General,
+        address: CoreRegisterAddress(4),
+    },
+    RegisterDescription {
+        name: "R5",
+        kind: RegisterKind::General,
+        address: CoreRegisterAddress(5),
+    },
+    RegisterDescription {
+        name: "R6",
+        kind: RegisterKind::General,
+        address: CoreRegisterAddress(6),
+    },
+    RegisterDescription {
+        name: "R7",
+        kind: RegisterKind::General,
+        address: CoreRegisterAddress(7),
+    },
+    RegisterDescription {
+        name: "R8",
+        kind: RegisterKind::General,
+        address: CoreRegisterAddress(8),
+    },
+    RegisterDescription {
+        name: "R9",
+        kind: RegisterKind::General,
+        address: CoreRegisterAddress(9),
+    },
+    RegisterDescription {
+        name: "R10",
+        kind: RegisterKind::General,
+        address: CoreRegisterAddress(10),
+    },
+    RegisterDescription {
+        name: "R11",
+        kind: RegisterKind::General,
+        address: CoreRegisterAddress(11),
+    },
+    RegisterDescription {
+        name: "R12",
+        kind: RegisterKind::General,
+        address: CoreRegisterAddress(12),
+    },
+    RegisterDescription {
+        name: "R13",
+        kind: RegisterKind::General,
+        address: CoreRegisterAddress(13),
+    },
+    RegisterDescription {
+        name: "R14",
+        kind: RegisterKind::General,
+        address: CoreRegisterAddress(14),
+    },
+    RegisterDescription {
+        name: "R15",
+        kind: RegisterKind::General,
+        address: CoreRegisterAddress(15),
+    },
+];
+
+const ARGUMENT_REGISTERS: &[RegisterDescription] = &[
+    RegisterDescription {
+        name: "a1",
+        kind: RegisterKind::General,
+        address: CoreRegisterAddress(0),
+    },
+    RegisterDescription {
+        name: "a2",
+        kind: RegisterKind::General,
+        address: CoreRegisterAddress(1),
+    },
+    RegisterDescription {
+        name: "a3",
+        kind: RegisterKind::General,
+        address: CoreRegisterAddress(2),
+    },
+    RegisterDescription {
+        name: "a4",
+        kind: RegisterKind::General,
+        address: CoreRegisterAddress(3),
+    },
+];
+
+const RESULT_REGISTERS: &[RegisterDescription] = &[
+    RegisterDescription {
+        name: "a1",
+        kind: RegisterKind::General,
+        address: CoreRegisterAddress(0),
+    },
+    RegisterDescription {
+        name: "a2",
+        kind: RegisterKind::General,
+        address: CoreRegisterAddress(1),
+    },
+];
+
+pub(crate) static ARM_REGISTER_FILE: RegisterFile = RegisterFile {
+    platform_registers: PLATFORM_REGISTERS,
 
     program_counter: &register::PC,
     stack_pointer: &register::SP,
     return_address: &register::LR,
 
-    argument_registers: &[
-        RegisterDescription {
-            name: "a1",
-            kind: RegisterKind::General,
-            address: CoreRegisterAddress(0),
-        },
-        RegisterDescription {
-            name: "a2",
-            kind: RegisterKind::General,
-            address: CoreRegisterAddress(1),
-        },
-        RegisterDescription {
-            name: "a3",
-            kind: RegisterKind::General,
-            address: CoreRegisterAddress(2),
-        },
-        RegisterDescription {
-            name: "a4",
-            kind: RegisterKind::General,
-            address: CoreRegisterAddress(3),
-        },
-    ],
+    argument_registers: ARGUMENT_REGISTERS,
+    result_registers: RESULT_REGISTERS,
+
+    special_registers: &[register::MSP, register::PSP, register::CFBP],
+};
+
+/// The register file of ARMv8-M cores, which additionally contains the stack pointers
+/// and stack limits of both security states.
+pub(crate) static ARMV8M_REGISTER_FILE: RegisterFile = RegisterFile {
+    platform_registers: PLATFORM_REGISTERS,
+
+    program_counter: &register::PC,
+    stack_pointer: &register::SP,
+    return_address: &register::LR,
+
+    argument_registers: ARGUMENT_REGISTERS,
+    result_registers: RESULT_REGISTERS,
 
-    result_registers: &[
-        RegisterDescription {
-            name: "a1",
-            kind: RegisterKind::General,
-            address: CoreRegisterAddress(0),
-        },
-        RegisterDescription {
-            name: "a2",
-            kind: RegisterKind::General,
-            address: CoreRegisterAddress(1),
-        },
+    special_registers: &[
+        register::MSP,
+        register::PSP,
+        register::CFBP,
+        armv8m::MSP_NS,
+        armv8m::PSP_NS,
+        armv8m::MSP_S,
+        armv8m::PSP_S,
+        armv8m::MSPLIM_S,
+        armv8m::PSPLIM_S,
+        armv8m::MSPLIM_NS,
+        armv8m::PSPLIM_NS,
+        armv8m::CFBP_S,
+        armv8m::CFBP_NS,
     ],
 };
 
+bitfield! {
+    /// The `CONTROL`, `FAULTMASK`, `BASEPRI` and `PRIMASK` registers, as they are
+    /// combined in the [`CFBP`](register::CFBP) register.
+    ///
+    /// ARMv6-M cores don't implement `FAULTMASK` and `BASEPRI`, they read as zero.
+    #[derive(Copy, Clone)]
+    pub struct Cfbp(u32);
+    impl Debug;
+    pub u8, control, set_control: 31, 24;
+    pub u8, faultmask, set_faultmask: 23, 16;
+    pub u8, basepri, set_basepri: 15, 8;
+    pub u8, primask, set_primask: 7, 0;
+}
+
+impl From<u32> for Cfbp {
+    fn from(value: u32) -> Self {
+        Cfbp(value)
+    }
+}
+
+impl From<Cfbp> for u32 {
+    fn from(value: Cfbp) -> Self {
+        value.0
+    }
+}
+
+impl Cfbp {
+    /// Returns `true` if the process stack pointer is the active one, i.e. `CONTROL.SPSEL` is set.
+    pub fn process_stack_active(&self) -> bool {
+        self.control() & 0b10 != 0
+    }
+}
+
 bitfield! {
     #[derive(Copy, Clone)]
     pub struct Dfsr(u32);
@@ -261,6 +357,16 @@ pub struct State {
     hw_breakpoints_enabled: bool,
 
     current_state: CoreStatus,
+
+    /// The last value written to DEMCR through the core, which is restored after a reset.
+    saved_demcr: Option<u32>,
+    /// `S_RESET_ST` was set in a read of DHCSR, since the last check for resets.
+    reset_latched: bool,
+    /// The watchdogs are stopped while the core is halted, and have to be stopped again after a reset.
+    watchdog_freeze: bool,
+
+    /// Interrupts are masked with `C_MASKINTS` while the core is stepped.
+    mask_interrupts_while_stepping: bool,
 }
 
 impl State {
@@ -269,6 +375,10 @@ impl State {
             initialized: false,
             hw_breakpoints_enabled: false,
             current_state: CoreStatus::Unknown,
+            saved_demcr: None,
+            reset_latched: false,
+            watchdog_freeze: false,
+            mask_interrupts_while_stepping: true,
         }
     }
 
@@ -280,3 +390,172 @@ impl State {
         self.initialized
     }
 }
+
+/// The address of `DHCSR`.
+const DHCSR: u32 = 0xE000_EDF0;
+/// The sticky `S_RESET_ST` bit of `DHCSR`, which is cleared when read.
+const DHCSR_S_RESET_ST: u32 = 1 << 25;
+/// The address of `DEMCR`, which contains the vector catch and trace enable bits.
+const DEMCR: u32 = 0xE000_EDFC;
+
+/// Read DHCSR of an ARM core.
+///
+/// `S_RESET_ST` is cleared by every read of DHCSR, so it is latched in the state,
+/// until it is consumed by [`reset_detected`].
+pub(crate) fn read_dhcsr(memory: &mut Memory, state: &mut State) -> Result<u32, Error> {
+    let dhcsr = memory.read_word_32(DHCSR)?;
+
+    if dhcsr & DHCSR_S_RESET_ST != 0 {
+        state.reset_latched = true;
+    }
+
+    Ok(dhcsr)
+}
+
+/// Check the sticky reset bit of an ARM core, see [`CoreInterface::reset_detected`].
+pub(crate) fn reset_detected(memory: &mut Memory, state: &mut State) -> Result<bool, Error> {
+    read_dhcsr(memory, state)?;
+
+    Ok(std::mem::replace(&mut state.reset_latched, false))
+}
+
+/// Save the values written to DEMCR through an ARM core, so that [`reinitialize_debug`]
+/// can restore the vector catch and trace enable bits after a reset.
+pub(crate) fn save_demcr(state: &mut State, address: u32, data: &[u32]) {
+    let offset = DEMCR.wrapping_sub(address) as usize / 4;
+
+    if address <= DEMCR && address % 4 == 0 && offset < data.len() {
+        state.saved_demcr = Some(data[offset]);
+    }
+}
+
+/// Enable debugging of an ARM core again after a reset, see [`CoreInterface::reinitialize_debug`].
+pub(crate) fn reinitialize_debug(
+    memory: &mut Memory,
+    state: &mut State,
+    sequence: &dyn ArmDebugSequence,
+) -> Result<(), Error> {
+    sequence.debug_core_start(memory)?;
+
+    // A power-on reset clears the vector catch and trace enable bits.
+    if let Some(demcr) = state.saved_demcr {
+        memory.write_word_32(DEMCR, demcr)?;
+    }
+
+    if state.watchdog_freeze {
+        sequence.debug_watchdog_freeze(memory, true)?;
+    }
+
+    // A system reset disables the breakpoint unit, it is enabled again
+    // when the breakpoints are set.
+    state.hw_breakpoints_enabled = false;
+
+    Ok(())
+}
+
+/// Stop the watchdogs while an ARM core is halted, see [`CoreInterface::set_watchdog_freeze`].
+pub(crate) fn set_watchdog_freeze(
+    memory: &mut Memory,
+    state: &mut State,
+    sequence: &dyn ArmDebugSequence,
+    freeze: bool,
+) -> Result<(), Error> {
+    sequence.debug_watchdog_freeze(memory, freeze)?;
+    state.watchdog_freeze = freeze;
+
+    Ok(())
+}
+
+/// The address of `DWT_CTRL`, which contains the number of DWT comparators.
+const DWT_CTRL: u32 = 0xE000_1000;
+/// The address of `DWT_COMP0`, the registers of the other comparators follow every 16 bytes.
+const DWT_COMP0: u32 = 0xE000_1020;
+/// The address of `DWT_FUNCTION0`.
+const DWT_FUNCTION0: u32 = 0xE000_1028;
+/// The `MATCHED` bit of `DWT_FUNCTION`, which is set when the comparator matched.
+const DWT_FUNCTION_MATCHED: u32 = 1 << 24;
+
+/// Details about the halt of an ARM core, see [`CoreInterface::halt_details`].
+pub(crate) fn halt_details(core: &mut impl CoreInterface) -> Result<HaltDetails, Error> {
+    let reason = match core.status()? {
+        CoreStatus::Halted(reason) => reason,
+        _ => HaltReason::Unknown,
+    };
+
+    let mut details = HaltDetails::new(reason);
+
+    // The exception number is stored in the IPSR bits of the xPSR.
+    let exception = core.read_core_reg(register::XPSR.address)? & 0x1ff;
+    if exception != 0 {
+        details.exception = Some(exception);
+    }
+
+    if reason == HaltReason::Breakpoint {
+        let pc = core.read_core_reg(register::PC.address)?;
+
+        details.breakpoint = if core.get_hw_breakpoints()?.contains(&Some(pc)) {
+            Some(BreakpointCause::Hardware)
+        } else {
+            let mut instruction = [0u8; 2];
+            core.read_8(pc, &mut instruction)?;
+
+            // A BKPT instruction.
+            (instruction[1] == 0xbe).then_some(BreakpointCause::Software)
+        };
+    }
+
+    if matches!(reason, HaltReason::Watchpoint | HaltReason::Multiple) {
+        let comparators = core.read_word_32(DWT_CTRL)? >> 28;
+
+        // Reading DWT_FUNCTION clears the MATCHED bit.
+        for comparator in 0..comparators {
+            let function = core.read_word_32(DWT_FUNCTION0 + 16 * comparator)?;
+
+            if function & DWT_FUNCTION_MATCHED != 0 {
+                details.watchpoint_address = Some(core.read_word_32(DWT_COMP0 + 16 * comparator)?);
+                break;
+            }
+        }
+    }
+
+    Ok(details)
+}
+
+/// The address of `CFSR`, the Configurable Fault Status Register.
+const CFSR: u32 = 0xE000_ED28;
+/// The address of `HFSR`, the HardFault Status Register.
+const HFSR: u32 = 0xE000_ED2C;
+/// The address of `MMFAR`, the MemManage Fault Address Register.
+const MMFAR: u32 = 0xE000_ED34;
+/// The address of `BFAR`, the BusFault Address Register.
+const BFAR: u32 = 0xE000_ED38;
+/// The `MMARVALID` bit of `CFSR`, which is set when `MMFAR` is valid.
+const CFSR_MMARVALID: u32 = 1 << 7;
+/// The `BFARVALID` bit of `CFSR`, which is set when `BFAR` is valid.
+const CFSR_BFARVALID: u32 = 1 << 15;
+
+/// Read the fault status registers of an ARMv7-M or ARMv8-M core, see
+/// [`CoreInterface::fault_status`].
+pub(crate) fn fault_status(memory: &mut Memory) -> Result<Option<FaultStatus>, Error> {
+    let cfsr = memory.read_word_32(CFSR)?;
+    let hfsr = memory.read_word_32(HFSR)?;
+
+    let mmfar = if cfsr & CFSR_MMARVALID != 0 {
+        Some(memory.read_word_32(MMFAR)?)
+    } else {
+        None
+    };
+
+    let bfar = if cfsr & CFSR_BFARVALID != 0 {
+        Some(memory.read_word_32(BFAR)?)
+    } else {
+        None
+    };
+
+    Ok(Some(FaultStatus {
+        cfsr,
+        hfsr,
+        mmfar,
+        bfar,
+    }))
+}
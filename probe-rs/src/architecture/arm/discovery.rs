@@ -0,0 +1,261 @@
+//! Discovery of the debug topology of an ARM chip.
+//!
+//! [`Topology::discover`] scans all access ports of a debug port, and parses the
+//! CoreSight ROM tables of all memory APs. The result describes the components of the
+//! chip, e.g. to show the topology in a tool, or to locate the memory APs and cross
+//! trigger interfaces in a debug sequence, without hard coding their addresses.
+
+use super::{
+    ap::{GenericAp, MemoryAp},
+    communication_interface::ArmProbeInterface,
+    memory::{Component, PartInfo, PeripheralType, RomTableError},
+    ApAddress, ApInformation, DpAddress, MemoryApInformation,
+};
+use crate::Error;
+
+/// The class of a CoreSight component, as described in table D1-2 in the ADIv5.2 spec.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ComponentClass {
+    /// A generic verification component.
+    GenericVerification,
+    /// A ROM table, which lists further components.
+    RomTable,
+    /// A CoreSight component, e.g. a DWT or a CTI.
+    CoreSight,
+    /// A peripheral test block.
+    PeripheralTestBlock,
+    /// A generic IP component.
+    GenericIp,
+    /// A CoreLink, PrimeCell or system component without standard registers.
+    CoreLinkOrPrimeCellOrSystem,
+}
+
+/// A CoreSight component found while parsing the ROM tables.
+#[derive(Debug, Clone)]
+pub struct CoreSightComponent {
+    /// The access port through which the component is accessed.
+    pub access_port: ApAddress,
+    /// The base address of the component.
+    pub address: u64,
+    /// The nesting level in the ROM tables, `0` for the component at the debug base address of the AP.
+    pub depth: usize,
+    /// The class of the component.
+    pub class: ComponentClass,
+    /// The designer of the component, or `None` for legacy components.
+    pub designer: Option<jep106::JEP106Code>,
+    /// The part number of the component, assigned by the designer.
+    pub part: u16,
+    /// Information about the component, if it is a known part.
+    pub part_info: Option<PartInfo>,
+}
+
+impl CoreSightComponent {
+    /// Check if the component is a known part of the given type.
+    pub fn is_of_type(&self, peripheral_type: PeripheralType) -> bool {
+        self.part_info
+            .map(|info| info.peripheral_type() == peripheral_type)
+            .unwrap_or(false)
+    }
+}
+
+/// An access port found on a debug port.
+#[derive(Debug)]
+pub struct DiscoveredAccessPort {
+    /// Information about the access port.
+    pub information: ApInformation,
+    /// The components found in the ROM tables of a memory AP.
+    ///
+    /// This is empty for access ports which are not memory APs, or which have no debug base address.
+    /// If the ROM tables could not be parsed, the error is stored here instead of
+    /// aborting the discovery of the other access ports.
+    pub components: Result<Vec<CoreSightComponent>, RomTableError>,
+}
+
+impl DiscoveredAccessPort {
+    /// The address of the access port.
+    pub fn address(&self) -> ApAddress {
+        match &self.information {
+            ApInformation::MemoryAp(MemoryApInformation { address, .. }) => *address,
+            ApInformation::Other { address } => *address,
+        }
+    }
+}
+
+/// The access ports and CoreSight components of a debug port.
+#[derive(Debug)]
+pub struct Topology {
+    /// The debug port which was scanned.
+    pub dp: DpAddress,
+    /// All access ports of the debug port.
+    pub access_ports: Vec<DiscoveredAccessPort>,
+}
+
+impl Topology {
+    /// Scan all access ports of the debug port `dp`, and parse the ROM tables of all memory APs.
+    pub fn discover(interface: &mut dyn ArmProbeInterface, dp: DpAddress) -> Result<Self, Error> {
+        let mut access_ports = Vec::new();
+
+        for ap in 0..(interface.num_access_ports(dp)? as u8) {
+            let information = interface
+                .ap_information(GenericAp::new(ApAddress { dp, ap }))?
+                .clone();
+
+            let components = match &information {
+                ApInformation::MemoryAp(MemoryApInformation {
+                    address,
+                    debug_base_address,
+                    ..
+                }) if *debug_base_address != 0 => {
+                    let mut memory = interface.memory_interface(MemoryAp::new(*address))?;
+
+                    Component::try_parse(&mut memory, *debug_base_address).map(|component| {
+                        let mut components = Vec::new();
+                        flatten(&component, *address, 0, &mut components);
+                        components
+                    })
+                }
+                _ => Ok(Vec::new()),
+            };
+
+            if let Err(error) = &components {
+                log::warn!("Failed to parse the ROM table of AP {}: {}", ap, error);
+            }
+
+            access_ports.push(DiscoveredAccessPort {
+                information,
+                components,
+            });
+        }
+
+        Ok(Self { dp, access_ports })
+    }
+
+    /// All memory APs of the debug port.
+    pub fn memory_aps(&self) -> impl Iterator<Item = &MemoryApInformation> {
+        self.access_ports
+            .iter()
+            .filter_map(|ap| match &ap.information {
+                ApInformation::MemoryAp(information) => Some(information),
+                ApInformation::Other { .. } => None,
+            })
+    }
+
+    /// All components found in the ROM tables, in the order in which they were found.
+    pub fn components(&self) -> impl Iterator<Item = &CoreSightComponent> {
+        self.access_ports
+            .iter()
+            .filter_map(|ap| ap.components.as_ref().ok())
+            .flatten()
+    }
+
+    /// All components which are known to be of the given type, e.g. all CTIs.
+    pub fn find_components(
+        &self,
+        peripheral_type: PeripheralType,
+    ) -> impl Iterator<Item = &CoreSightComponent> {
+        self.components()
+            .filter(move |component| component.is_of_type(peripheral_type))
+    }
+}
+
+/// Add `component` and all components in its ROM table to `components`.
+fn flatten(
+    component: &Component,
+    access_port: ApAddress,
+    depth: usize,
+    components: &mut Vec<CoreSightComponent>,
+) {
+    let class = match component {
+        Component::GenericVerificationComponent(_) => ComponentClass::GenericVerification,
+        Component::Class1RomTable(..) => ComponentClass::RomTable,
+        Component::Class9RomTable(_) => ComponentClass::CoreSight,
+        Component::PeripheralTestBlock(_) => ComponentClass::PeripheralTestBlock,
+        Component::GenericIPComponent(_) => ComponentClass::GenericIp,
+        Component::CoreLinkOrPrimeCellOrSystemComponent(_) => {
+            ComponentClass::CoreLinkOrPrimeCellOrSystem
+        }
+    };
+
+    let id = component.id();
+
+    components.push(CoreSightComponent {
+        access_port,
+        address: id.component_address(),
+        depth,
+        class,
+        designer: id.peripheral_id().jep106(),
+        part: id.peripheral_id().part(),
+        part_info: id.peripheral_id().determine_part(),
+    });
+
+    if let Component::Class1RomTable(_, rom_table) = component {
+        for child in rom_table.components() {
+            flatten(child, access_port, depth + 1, components);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ComponentClass, CoreSightComponent, DiscoveredAccessPort, Topology};
+    use crate::architecture::arm::memory::{PartInfo, PeripheralType};
+    use crate::architecture::arm::{ApAddress, ApInformation, DpAddress};
+
+    fn component(ap: u8, address: u64, part_info: Option<PartInfo>) -> CoreSightComponent {
+        CoreSightComponent {
+            access_port: ApAddress {
+                dp: DpAddress::Default,
+                ap,
+            },
+            address,
+            depth: 1,
+            class: ComponentClass::CoreSight,
+            designer: None,
+            part: 0,
+            part_info,
+        }
+    }
+
+    #[test]
+    fn find_components_by_type() {
+        let cti = PartInfo::new("CoreSight CTI", PeripheralType::Cti);
+        let dwt = PartInfo::new("Cortex-M0 DWT", PeripheralType::Dwt);
+
+        let topology = Topology {
+            dp: DpAddress::Default,
+            access_ports: vec![
+                DiscoveredAccessPort {
+                    information: ApInformation::Other {
+                        address: ApAddress {
+                            dp: DpAddress::Default,
+                            ap: 0,
+                        },
+                    },
+                    components: Ok(vec![
+                        component(0, 0xe004_2000, Some(cti)),
+                        component(0, 0xe000_1000, Some(dwt)),
+                        component(0, 0xe00f_f000, None),
+                    ]),
+                },
+                DiscoveredAccessPort {
+                    information: ApInformation::Other {
+                        address: ApAddress {
+                            dp: DpAddress::Default,
+                            ap: 1,
+                        },
+                    },
+                    components: Ok(vec![component(1, 0xe004_2000, Some(cti))]),
+                },
+            ],
+        };
+
+        let ctis: Vec<_> = topology
+            .find_components(PeripheralType::Cti)
+            .map(|c| c.access_port.ap)
+            .collect();
+
+        assert_eq!(ctis, vec![0, 1]);
+        assert_eq!(topology.components().count(), 4);
+        assert_eq!(topology.memory_aps().count(), 0);
+    }
+}
@@ -15,6 +15,8 @@ pub enum DebugPortError {
         register: &'static str,
         version: DebugPortVersion,
     },
+    #[error("Access port {0} was not found in the ROM table of the debug port")]
+    AccessPortNotFound(u8),
     #[error("A Debug Probe Error occured")]
     DebugProbe(#[from] DebugProbeError),
 }
@@ -35,7 +37,7 @@ pub trait DpAccess {
     ) -> Result<(), DebugPortError>;
 }
 
-impl<T: DapAccess> DpAccess for T {
+impl<T: DapAccess + ?Sized> DpAccess for T {
     fn read_dp_register<R: DpRegister>(&mut self, dp: DpAddress) -> Result<R, DebugPortError> {
         log::debug!("Reading DP register {}", R::NAME);
         let result = self.read_raw_dp_register(dp, R::ADDRESS)?;
@@ -151,6 +153,9 @@ bitfield! {
     impl Debug;
     pub u8, ap_sel, set_ap_sel: 31, 24;
     pub u8, ap_bank_sel, set_ap_bank_sel: 7, 4;
+    /// Bits 31 to 4 of the address of the selected AP register, only for DPv3 (ADIv6),
+    /// which replaces `APSEL` and `APBANKSEL`.
+    pub u32, addr, set_addr: 31, 4;
     pub u8, dp_bank_sel, set_dp_bank_sel: 3, 0;
 }
 
@@ -175,6 +180,36 @@ impl Register for Select {
     const NAME: &'static str = "SELECT";
 }
 
+bitfield! {
+    /// The upper 32 bits of the address of the selected AP register, only for DPv3 (ADIv6)
+    /// debug ports with more than 32 address bits.
+    #[derive(Clone)]
+    pub struct Select1(u32);
+    impl Debug;
+    pub u32, addr, set_addr: 31, 0;
+}
+
+impl From<u32> for Select1 {
+    fn from(raw: u32) -> Self {
+        Select1(raw)
+    }
+}
+
+impl From<Select1> for u32 {
+    fn from(raw: Select1) -> Self {
+        raw.0
+    }
+}
+
+impl DpRegister for Select1 {
+    const VERSION: DebugPortVersion = DebugPortVersion::DPv3;
+}
+
+impl Register for Select1 {
+    const ADDRESS: u8 = 0x54;
+    const NAME: &'static str = "SELECT1";
+}
+
 bitfield! {
     #[derive(Clone)]
     pub struct DPIDR(u32);
@@ -209,6 +244,97 @@ impl Register for DPIDR {
     const NAME: &'static str = "DPIDR";
 }
 
+bitfield! {
+    /// The second identification register of DPv3 (ADIv6) debug ports.
+    #[derive(Clone)]
+    pub struct DPIDR1(u32);
+    impl Debug;
+    pub errmode, _: 7;
+    pub u8, asize, _: 6, 0;
+}
+
+impl From<u32> for DPIDR1 {
+    fn from(raw: u32) -> Self {
+        Self(raw)
+    }
+}
+
+impl From<DPIDR1> for u32 {
+    fn from(raw: DPIDR1) -> Self {
+        raw.0
+    }
+}
+
+impl DpRegister for DPIDR1 {
+    const VERSION: DebugPortVersion = DebugPortVersion::DPv3;
+}
+
+impl Register for DPIDR1 {
+    const ADDRESS: u8 = 0x10;
+    const NAME: &'static str = "DPIDR1";
+}
+
+bitfield! {
+    /// The lower 32 bits of the address of the top-level component of a DPv3 (ADIv6)
+    /// debug port, which is either an AP or a ROM table describing the APs.
+    #[derive(Clone)]
+    pub struct BASEPTR0(u32);
+    impl Debug;
+    pub u32, ptr, _: 31, 12;
+    pub valid, _: 0;
+}
+
+impl From<u32> for BASEPTR0 {
+    fn from(raw: u32) -> Self {
+        Self(raw)
+    }
+}
+
+impl From<BASEPTR0> for u32 {
+    fn from(raw: BASEPTR0) -> Self {
+        raw.0
+    }
+}
+
+impl DpRegister for BASEPTR0 {
+    const VERSION: DebugPortVersion = DebugPortVersion::DPv3;
+}
+
+impl Register for BASEPTR0 {
+    const ADDRESS: u8 = 0x20;
+    const NAME: &'static str = "BASEPTR0";
+}
+
+bitfield! {
+    /// The upper 32 bits of the address of the top-level component of a DPv3 (ADIv6)
+    /// debug port, see [`BASEPTR0`].
+    #[derive(Clone)]
+    pub struct BASEPTR1(u32);
+    impl Debug;
+    pub u32, ptr, _: 31, 0;
+}
+
+impl From<u32> for BASEPTR1 {
+    fn from(raw: u32) -> Self {
+        Self(raw)
+    }
+}
+
+impl From<BASEPTR1> for u32 {
+    fn from(raw: BASEPTR1) -> Self {
+        raw.0
+    }
+}
+
+impl DpRegister for BASEPTR1 {
+    const VERSION: DebugPortVersion = DebugPortVersion::DPv3;
+}
+
+impl Register for BASEPTR1 {
+    const ADDRESS: u8 = 0x30;
+    const NAME: &'static str = "BASEPTR1";
+}
+
 bitfield! {
     #[derive(Clone)]
     pub struct TARGETID(u32);
@@ -306,6 +432,7 @@ pub enum DebugPortVersion {
     DPv0,
     DPv1,
     DPv2,
+    DPv3,
     Unsupported(u8),
 }
 
@@ -317,6 +444,7 @@ impl From<DebugPortVersion> for u8 {
             DPv0 => 0,
             DPv1 => 1,
             DPv2 => 2,
+            DPv3 => 3,
             Unsupported(val) => val,
         }
     }
@@ -339,6 +467,7 @@ impl Display for DebugPortVersion {
             DPv0 => write!(f, "DPv0"),
             DPv1 => write!(f, "DPv1"),
             DPv2 => write!(f, "DPv2"),
+            DPv3 => write!(f, "DPv3"),
             Unsupported(version) => write!(f, "<unsupported Debugport Version {}>", version),
         }
     }
@@ -350,6 +479,7 @@ impl From<u8> for DebugPortVersion {
             0 => DebugPortVersion::DPv0,
             1 => DebugPortVersion::DPv1,
             2 => DebugPortVersion::DPv2,
+            3 => DebugPortVersion::DPv3,
             value => DebugPortVersion::Unsupported(value),
         }
     }
@@ -1,20 +1,25 @@
 use super::super::ap::{
-    AccessPortError, AddressIncrement, ApAccess, ApRegister, DataSize, MemoryAp, CSW, DRW, TAR,
+    AccessPort, AccessPortError, AddressIncrement, ApAccess, ApRegister, DataSize, MemoryAp, CSW,
+    DRW, TAR,
 };
 use crate::architecture::arm::ArmCommunicationInterface;
 use crate::architecture::arm::{
-    communication_interface::Initialized, dp::DpAccess, MemoryApInformation,
+    communication_interface::Initialized, dp::DpAccess, ApAddress, DapTransfer, MemoryApInformation,
 };
 use crate::{CommunicationInterface, CoreRegister, CoreRegisterAddress, DebugProbeError, Error};
 use scroll::{Pread, Pwrite, LE};
 use std::convert::TryInto;
-use std::{
-    ops::Range,
-    time::{Duration, Instant},
-};
+use std::{ops::Range, time::Duration};
+use web_time::Instant;
 
 use bitfield::bitfield;
 
+/// The banked data registers of a MEM-AP, through which DHCSR, DCRSR and DCRDR
+/// are accessed while `TAR` points to DHCSR.
+const BD_DHCSR: u8 = 0x10;
+const BD_DCRSR: u8 = 0x14;
+const BD_DCRDR: u8 = 0x18;
+
 pub trait ArmProbe {
     fn read_core_reg(&mut self, ap: MemoryAp, addr: CoreRegisterAddress) -> Result<u32, Error>;
     fn write_core_reg(
@@ -24,12 +29,74 @@ pub trait ArmProbe {
         value: u32,
     ) -> Result<(), Error>;
 
+    /// Read the core registers at `addresses` into `values`.
+    ///
+    /// The default implementation reads them one after another with [`ArmProbe::read_core_reg`].
+    fn read_core_regs(
+        &mut self,
+        ap: MemoryAp,
+        addresses: &[CoreRegisterAddress],
+        values: &mut [u32],
+    ) -> Result<(), Error> {
+        for (address, value) in addresses.iter().zip(values) {
+            *value = self.read_core_reg(ap, *address)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the values of the core `registers`.
+    ///
+    /// The default implementation writes them one after another with [`ArmProbe::write_core_reg`].
+    fn write_core_regs(
+        &mut self,
+        ap: MemoryAp,
+        registers: &[(CoreRegisterAddress, u32)],
+    ) -> Result<(), Error> {
+        for (address, value) in registers {
+            self.write_core_reg(ap, *address, *value)?;
+        }
+
+        Ok(())
+    }
+
     fn read_8(&mut self, ap: MemoryAp, address: u32, data: &mut [u8]) -> Result<(), Error>;
     fn read_32(&mut self, ap: MemoryAp, address: u32, data: &mut [u32]) -> Result<(), Error>;
 
     fn write_8(&mut self, ap: MemoryAp, address: u32, data: &[u8]) -> Result<(), Error>;
     fn write_32(&mut self, ap: MemoryAp, address: u32, data: &[u32]) -> Result<(), Error>;
 
+    /// Fill `count` 32bit words starting at `address` with `value`.
+    ///
+    /// The default implementation writes the pattern in chunks using [`ArmProbe::write_32`].
+    fn fill_32(
+        &mut self,
+        ap: MemoryAp,
+        address: u32,
+        value: u32,
+        count: usize,
+    ) -> Result<(), Error> {
+        const CHUNK_SIZE_WORDS: usize = 256;
+
+        if u64::from(address) + count as u64 * 4 > 1 << 32 {
+            return Err(AccessPortError::OutOfBoundsError.into());
+        }
+
+        let buffer = [value; CHUNK_SIZE_WORDS];
+        let mut address = address;
+        let mut remaining = count;
+
+        while remaining > 0 {
+            let chunk_size = remaining.min(CHUNK_SIZE_WORDS);
+            self.write_32(ap, address, &buffer[..chunk_size])?;
+
+            remaining -= chunk_size;
+            address = address.wrapping_add((chunk_size * 4) as u32);
+        }
+
+        Ok(())
+    }
+
     fn flush(&mut self) -> Result<(), Error>;
 
     fn get_arm_communication_interface(
@@ -50,12 +117,16 @@ where
     // to be set to 1 at all times.
     supports_hnonsec: bool,
 
-    // Cached value of the CSW register, to avoid unecessary writes.
+    // The size of the blocks the TAR auto-increment wraps around in.
+    auto_increment_wrap: u32,
+
+    // Cached value of the CSW register, and the AP it was written to,
+    // to avoid unecessary writes.
     //
     // TODO: This is the wrong location for this, it should actually be
     // cached on a lower level, where the other Memory AP information is
     // stored.
-    cached_csw_value: Option<CSW>,
+    cached_csw_value: Option<(ApAddress, CSW)>,
 }
 
 impl<'interface, AP> ADIMemoryInterface<'interface, AP>
@@ -71,6 +142,7 @@ where
             interface,
             only_32bit_data_size: ap_information.only_32bit_data_size,
             supports_hnonsec: ap_information.supports_hnonsec,
+            auto_increment_wrap: ap_information.auto_increment_wrap,
             cached_csw_value: None,
         })
     }
@@ -118,11 +190,15 @@ where
     ) -> Result<(), AccessPortError> {
         // Check if the write is necessary
         match self.cached_csw_value {
-            Some(cached_value) if cached_value == value => Ok(()),
+            Some((address, cached_value))
+                if address == access_port.ap_address() && cached_value == value =>
+            {
+                Ok(())
+            }
             _ => {
                 self.write_ap_register(access_port, value)?;
 
-                self.cached_csw_value = Some(value);
+                self.cached_csw_value = Some((access_port.ap_address(), value));
 
                 Ok(())
             }
@@ -148,6 +224,30 @@ where
         Err(Error::Probe(DebugProbeError::Timeout))
     }
 
+    /// Execute `transfers` of the banked data registers, with `TAR` pointing to DHCSR.
+    ///
+    /// DHCSR, DCRSR and DCRDR are in the same 16 byte block, so they are accessed through
+    /// the banked data registers, and a batch of core register transfers does not have to
+    /// write `TAR` in between.
+    fn transfer_debug_registers(
+        &mut self,
+        access_port: MemoryAp,
+        transfers: &mut [DapTransfer],
+    ) -> Result<(), Error> {
+        let csw = self.build_csw_register(DataSize::U32);
+        self.write_csw_register(access_port, csw)?;
+        self.write_ap_register(
+            access_port,
+            TAR {
+                address: Dhcsr::ADDRESS,
+            },
+        )?;
+
+        self.interface
+            .transfer_ap_batch(access_port, transfers)
+            .map_err(Error::Probe)
+    }
+
     /// Read a 32 bit register on the given AP.
     fn read_ap_register<R>(&mut self, access_port: MemoryAp) -> Result<R, AccessPortError>
     where
@@ -284,68 +384,18 @@ where
         let csw = self.build_csw_register(DataSize::U32);
         self.write_csw_register(access_port, csw)?;
 
-        let mut address = start_address;
-        let tar = TAR { address };
-        self.write_ap_register(access_port, tar)?;
-
-        // figure out how many words we can write before the
-        // data overflows
-
-        // maximum chunk size
-        let max_chunk_size_bytes = 0x400;
-
-        let mut remaining_data_len = data.len();
-
-        let first_chunk_size_bytes = std::cmp::min(
-            max_chunk_size_bytes - (address as usize % max_chunk_size_bytes),
-            data.len() * 4,
-        );
-
-        let mut data_offset = 0;
-
-        log::debug!(
-            "Read first block with len {} at address {:#08x}",
-            first_chunk_size_bytes,
-            address
-        );
-
-        let first_chunk_size_words = first_chunk_size_bytes / 4;
-
-        self.read_ap_register_repeated(
-            access_port,
-            DRW { data: 0 },
-            &mut data[data_offset..first_chunk_size_words],
-        )?;
-
-        remaining_data_len -= first_chunk_size_words;
-        address += (4 * first_chunk_size_words) as u32;
-        data_offset += first_chunk_size_words;
-
-        while remaining_data_len > 0 {
-            // the autoincrement is limited to the 10 lowest bits so we need to write the address
-            // every time it overflows
+        for (address, range) in tar_chunks(start_address, data.len(), self.auto_increment_wrap) {
+            // The TAR has to be written again every time the auto-increment wraps.
             let tar = TAR { address };
             self.write_ap_register(access_port, tar)?;
 
-            let next_chunk_size_bytes = std::cmp::min(max_chunk_size_bytes, remaining_data_len * 4);
-
             log::debug!(
                 "Reading chunk with len {} at address {:#08x}",
-                next_chunk_size_bytes,
+                range.len() * 4,
                 address
             );
 
-            let next_chunk_size_words = next_chunk_size_bytes / 4;
-
-            self.read_ap_register_repeated(
-                access_port,
-                DRW { data: 0 },
-                &mut data[data_offset..(data_offset + next_chunk_size_words)],
-            )?;
-
-            remaining_data_len -= next_chunk_size_words;
-            address += (4 * next_chunk_size_words) as u32;
-            data_offset += next_chunk_size_words;
+            self.read_ap_register_repeated(access_port, DRW { data: 0 }, &mut data[range])?;
         }
 
         log::debug!("Finished reading block");
@@ -471,71 +521,73 @@ where
 
         self.write_csw_register(access_port, csw)?;
 
-        let mut address = start_address;
-        let tar = TAR { address };
-        self.write_ap_register(access_port, tar)?;
+        for (address, range) in tar_chunks(start_address, data.len(), self.auto_increment_wrap) {
+            // The TAR has to be written again every time the auto-increment wraps.
+            let tar = TAR { address };
+            self.write_ap_register(access_port, tar)?;
 
-        // figure out how many words we can write before the
-        // data overflows
+            log::debug!(
+                "Writing chunk with len {} at address {:#08x}",
+                range.len() * 4,
+                address
+            );
 
-        // maximum chunk size
-        let max_chunk_size_bytes = 0x400_usize;
+            self.write_ap_register_repeated(access_port, DRW { data: 0 }, &data[range])?;
+        }
 
-        let mut remaining_data_len = data.len();
+        log::debug!("Finished writing block");
 
-        let first_chunk_size_bytes = std::cmp::min(
-            max_chunk_size_bytes - (address as usize % max_chunk_size_bytes),
-            data.len() * 4,
-        );
+        Ok(())
+    }
+
+    /// Fill `count` 32bit words starting at `addr` with `value`.
+    ///
+    /// This uses the auto-increment feature of the TAR register, so the
+    /// value only has to be transferred once per DRW write, and no buffer
+    /// the size of the region is required on the host.
+    ///
+    /// The address where the write should be performed at has to be word aligned.
+    /// Returns `AccessPortError::MemoryNotAligned` if this does not hold true.
+    pub fn fill_32(
+        &mut self,
+        access_port: MemoryAp,
+        start_address: u32,
+        value: u32,
+        count: usize,
+    ) -> Result<(), AccessPortError> {
+        if count == 0 {
+            return Ok(());
+        }
+
+        if (start_address % 4) != 0 {
+            return Err(AccessPortError::alignment_error(start_address, 4));
+        }
 
-        let mut data_offset = 0;
+        if u64::from(start_address) + count as u64 * 4 > 1 << 32 {
+            return Err(AccessPortError::OutOfBoundsError);
+        }
 
         log::debug!(
-            "Write first block with len {} at address {:#08x}",
-            first_chunk_size_bytes,
-            address
+            "Fill block with total size {} bytes at address {:#08x} with {:#010x}",
+            count * 4,
+            start_address,
+            value
         );
 
-        let first_chunk_size_words = first_chunk_size_bytes / 4;
-
-        self.write_ap_register_repeated(
-            access_port,
-            DRW { data: 0 },
-            &data[data_offset..first_chunk_size_words],
-        )?;
+        let csw = self.build_csw_register(DataSize::U32);
+        self.write_csw_register(access_port, csw)?;
 
-        remaining_data_len -= first_chunk_size_words;
-        address += (4 * first_chunk_size_words) as u32;
-        data_offset += first_chunk_size_words;
+        let pattern = vec![value; (self.auto_increment_wrap / 4) as usize];
 
-        while remaining_data_len > 0 {
-            // the autoincrement is limited to the 10 lowest bits so we need to write the address
-            // every time it overflows
+        for (address, range) in tar_chunks(start_address, count, self.auto_increment_wrap) {
+            // The TAR has to be written again every time the auto-increment wraps.
             let tar = TAR { address };
             self.write_ap_register(access_port, tar)?;
 
-            let next_chunk_size_bytes = std::cmp::min(max_chunk_size_bytes, remaining_data_len * 4);
-
-            log::debug!(
-                "Writing chunk with len {} at address {:#08x}",
-                next_chunk_size_bytes,
-                address
-            );
-
-            let next_chunk_size_words = next_chunk_size_bytes / 4;
-
-            self.write_ap_register_repeated(
-                access_port,
-                DRW { data: 0 },
-                &data[data_offset..(data_offset + next_chunk_size_words)],
-            )?;
-
-            remaining_data_len -= next_chunk_size_words;
-            address += (4 * next_chunk_size_words) as u32;
-            data_offset += next_chunk_size_words;
+            self.write_ap_register_repeated(access_port, DRW { data: 0 }, &pattern[..range.len()])?;
         }
 
-        log::debug!("Finished writing block");
+        log::debug!("Finished filling block");
 
         Ok(())
     }
@@ -630,6 +682,86 @@ where
         Ok(())
     }
 
+    fn read_core_regs(
+        &mut self,
+        ap: MemoryAp,
+        addresses: &[CoreRegisterAddress],
+        values: &mut [u32],
+    ) -> Result<(), Error> {
+        let mut transfers = Vec::with_capacity(addresses.len() * 3);
+
+        for address in addresses {
+            let mut dcrsr_val = Dcrsr(0);
+            dcrsr_val.set_regwnr(false); // Perform a read.
+            dcrsr_val.set_regsel((*address).into());
+
+            transfers.push(DapTransfer::write(BD_DCRSR, dcrsr_val.into()));
+            transfers.push(DapTransfer::read(BD_DHCSR));
+            transfers.push(DapTransfer::read(BD_DCRDR));
+        }
+
+        self.transfer_debug_registers(ap, &mut transfers)?;
+
+        for (index, transfers) in transfers.chunks(3).enumerate() {
+            // If the transfer was not finished when DHCSR was read, the following transfers
+            // were started too early, so the remaining registers are read one after another.
+            if !Dhcsr(transfers[1].value()).s_regrdy() {
+                log::debug!(
+                    "Core register transfer {} of {} was not ready, reading the remaining registers one by one",
+                    index,
+                    addresses.len()
+                );
+
+                for (address, value) in addresses[index..].iter().zip(&mut values[index..]) {
+                    *value = ArmProbe::read_core_reg(self, ap, *address)?;
+                }
+
+                break;
+            }
+
+            values[index] = transfers[2].value();
+        }
+
+        Ok(())
+    }
+
+    fn write_core_regs(
+        &mut self,
+        ap: MemoryAp,
+        registers: &[(CoreRegisterAddress, u32)],
+    ) -> Result<(), Error> {
+        let mut transfers = Vec::with_capacity(registers.len() * 3);
+
+        for (address, value) in registers {
+            let mut dcrsr_val = Dcrsr(0);
+            dcrsr_val.set_regwnr(true); // Perform a write.
+            dcrsr_val.set_regsel((*address).into());
+
+            transfers.push(DapTransfer::write(BD_DCRDR, *value));
+            transfers.push(DapTransfer::write(BD_DCRSR, dcrsr_val.into()));
+            transfers.push(DapTransfer::read(BD_DHCSR));
+        }
+
+        self.transfer_debug_registers(ap, &mut transfers)?;
+
+        if let Some(index) = transfers
+            .chunks(3)
+            .position(|transfers| !Dhcsr(transfers[2].value()).s_regrdy())
+        {
+            log::debug!(
+                "Core register transfer {} of {} was not ready, writing the remaining registers one by one",
+                index,
+                registers.len()
+            );
+
+            for (address, value) in &registers[index..] {
+                ArmProbe::write_core_reg(self, ap, *address, *value)?;
+            }
+        }
+
+        Ok(())
+    }
+
     fn read_8(&mut self, ap: MemoryAp, address: u32, data: &mut [u8]) -> Result<(), Error> {
         if data.len() == 1 {
             data[0] = self.read_word_8(ap, address)?;
@@ -670,6 +802,18 @@ where
         Ok(())
     }
 
+    fn fill_32(
+        &mut self,
+        ap: MemoryAp,
+        address: u32,
+        value: u32,
+        count: usize,
+    ) -> Result<(), Error> {
+        self.fill_32(ap, address, value, count)?;
+
+        Ok(())
+    }
+
     fn flush(&mut self) -> Result<(), Error> {
         self.interface.flush()?;
 
@@ -734,7 +878,7 @@ bitfield! {
     pub struct Dcrsr(u32);
     impl Debug;
     pub _, set_regwnr: 16;
-    pub _, set_regsel: 4,0;
+    pub _, set_regsel: 6,0;
 }
 
 impl From<u32> for Dcrsr {
@@ -774,6 +918,30 @@ impl CoreRegister for Dcrdr {
     const NAME: &'static str = "DCRDR";
 }
 
+/// Splits a transfer of `words` 32-bit words at `address` into the chunks which can be
+/// transferred with a single write of the TAR, because the auto-increment of the TAR
+/// wraps around in blocks of `wrap` bytes.
+///
+/// Returns the address and the range of the words of each chunk.
+fn tar_chunks(address: u32, words: usize, wrap: u32) -> impl Iterator<Item = (u32, Range<usize>)> {
+    let mut address = address;
+    let mut offset = 0;
+
+    std::iter::from_fn(move || {
+        if offset >= words {
+            return None;
+        }
+
+        let chunk_words = (((wrap - address % wrap) / 4) as usize).min(words - offset);
+        let chunk = (address, offset..offset + chunk_words);
+
+        address = address.wrapping_add(4 * chunk_words as u32);
+        offset += chunk_words;
+
+        Some(chunk)
+    })
+}
+
 /// Calculates a 32-bit word aligned range from an address/length pair.
 fn aligned_range(address: u32, len: usize) -> Result<Range<u32>, AccessPortError> {
     // Round start address down to the nearest multiple of 4
@@ -795,6 +963,7 @@ fn aligned_range(address: u32, len: usize) -> Result<Range<u32>, AccessPortError
 
 #[cfg(test)]
 mod tests {
+    use crate::architecture::arm::communication_interface::DEFAULT_AUTO_INCREMENT_WRAP;
     use crate::architecture::arm::{ap::AccessPort, ApAddress, DpAddress, MemoryApInformation};
 
     use super::super::super::ap::memory_ap::mock::MockMemoryAp;
@@ -816,6 +985,7 @@ mod tests {
                 only_32bit_data_size: false,
                 supports_hnonsec: false,
                 debug_base_address: 0xf000_0000,
+                auto_increment_wrap: DEFAULT_AUTO_INCREMENT_WRAP,
             };
 
             Self::new(mock, &ap_information).unwrap()
@@ -1026,7 +1196,63 @@ mod tests {
         }
     }
 
-    use super::aligned_range;
+    #[test]
+    fn fill_32() {
+        for &address in &[0, 4] {
+            for count in 0..3 {
+                let mut mock = MockMemoryAp::with_pattern();
+                let mut mi = ADIMemoryInterface::new_mock(&mut mock);
+
+                let mut expected = Vec::from(mi.mock_memory());
+                for word in
+                    expected[address as usize..(address as usize) + count * 4].chunks_exact_mut(4)
+                {
+                    word.copy_from_slice(&DATA8[..4]);
+                }
+
+                mi.fill_32(DUMMY_AP, address, DATA32[0], count)
+                    .unwrap_or_else(|_| {
+                        panic!("fill_32 failed, address = {}, count = {}", address, count)
+                    });
+
+                assert_eq!(
+                    mi.mock_memory(),
+                    expected.as_slice(),
+                    "address = {}, count = {}",
+                    address,
+                    count
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn fill_32_unaligned_should_error() {
+        let mut mock = MockMemoryAp::with_pattern();
+        let mut mi = ADIMemoryInterface::new_mock(&mut mock);
+
+        for &address in &[1, 3, 127] {
+            assert!(mi.fill_32(DUMMY_AP, address, 0xDEAD_BEEF, 2).is_err());
+        }
+    }
+
+    use super::{aligned_range, tar_chunks};
+
+    #[test]
+    fn tar_chunks_wrap() {
+        let chunks: Vec<_> = tar_chunks(0x2000_03f8, 4, 0x400).collect();
+        assert_eq!(chunks, vec![(0x2000_03f8, 0..2), (0x2000_0400, 2..4)]);
+
+        // A transfer of 4 KB is not split if the TAR wraps at 4 KB boundaries.
+        let chunks: Vec<_> = tar_chunks(0x2000_0000, 1024, 0x1000).collect();
+        assert_eq!(chunks, vec![(0x2000_0000, 0..1024)]);
+
+        let chunks: Vec<_> = tar_chunks(0x2000_0000, 1024, 0x400).collect();
+        assert_eq!(chunks.len(), 4);
+        assert_eq!(chunks[3], (0x2000_0c00, 768..1024));
+
+        assert_eq!(tar_chunks(0x2000_0000, 0, 0x400).count(), 0);
+    }
 
     #[test]
     fn aligned_range_at_limit_does_not_panic() {
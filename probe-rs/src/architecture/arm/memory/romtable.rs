@@ -145,6 +145,11 @@ impl RomTable {
 
         Ok(RomTable { entries })
     }
+
+    /// The components listed in this ROM table, without the components of nested ROM tables.
+    pub(crate) fn components(&self) -> impl Iterator<Item = &Component> {
+        self.entries.iter().map(|entry| &entry.component)
+    }
 }
 
 /// A ROM table entry with raw information parsed.
@@ -628,6 +633,7 @@ impl PeripheralID {
             ("ARM Ltd", 0x471, 0x00, 0x0000) => Some(PartInfo::new("Cortex-M0  ROM", PeripheralType::Rom)),
             ("ARM Ltd", 0x4C0, 0x00, 0x0000) => Some(PartInfo::new("Cortex-M0+ ROM", PeripheralType::Rom)),
             ("ARM Ltd", 0x4C4, 0x00, 0x0000) => Some(PartInfo::new("Cortex-M4 ROM", PeripheralType::Rom)),
+            ("ARM Ltd", 0x906, 0x14, 0x0000) => Some(PartInfo::new("CoreSight CTI", PeripheralType::Cti)),
             ("ARM Ltd", 0x907, 0x21, 0x0000) => Some(PartInfo::new("CoreSight ETB", PeripheralType::Etb)),
             ("ARM Ltd", 0x910, 0x00, 0x0000) => Some(PartInfo::new("CoreSight ETM9", PeripheralType::Etm)),
             ("ARM Ltd", 0x912, 0x11, 0x0000) => Some(PartInfo::new("CoreSight TPIU", PeripheralType::Tpiu)),
@@ -637,6 +643,8 @@ impl PeripheralID {
             ("ARM Ltd", 0x923, 0x11, 0x0000) => Some(PartInfo::new("Cortex-M3 TPIU", PeripheralType::Tpiu)),
             ("ARM Ltd", 0x924, 0x13, 0x0000) => Some(PartInfo::new("Cortex-M3 ETM", PeripheralType::Etm)),
             ("ARM Ltd", 0x925, 0x13, 0x0000) => Some(PartInfo::new("Cortex-M4 ETM", PeripheralType::Etm)),
+            ("ARM Ltd", 0x932, 0x31, 0x0000) => Some(PartInfo::new("CoreSight MTB-M0+", PeripheralType::Mtb)),
+            ("ARM Ltd", 0x932, 0x31, 0x0A31) => Some(PartInfo::new("CoreSight MTB-M0+", PeripheralType::Mtb)),
             ("ARM Ltd", 0x962, 0x00, 0x0000) => Some(PartInfo::new("CoreSight STM", PeripheralType::Stm)),
             ("ARM Ltd", 0x963, 0x63, 0x0a63) => Some(PartInfo::new("CoreSight STM", PeripheralType::Stm)),
             ("ARM Ltd", 0x975, 0x13, 0x4a13) => Some(PartInfo::new("Cortex-M7 ETM", PeripheralType::Etm)),
@@ -647,12 +655,14 @@ impl PeripheralID {
             ("ARM Ltd", 0xD20, 0x13, 0x0000) => Some(PartInfo::new("Cortex-M23 ETM", PeripheralType::Etm)),
             ("ARM Ltd", 0xD20, 0x00, 0x1A02) => Some(PartInfo::new("Cortex-M23 DWT", PeripheralType::Dwt)),
             ("ARM Ltd", 0xD20, 0x00, 0x1A03) => Some(PartInfo::new("Cortex-M23 BPU", PeripheralType::Bpu)),
+            ("ARM Ltd", 0xD20, 0x31, 0x0A31) => Some(PartInfo::new("Cortex-M23 MTB", PeripheralType::Mtb)),
             ("ARM Ltd", 0xD21, 0x00, 0x2A04) => Some(PartInfo::new("Cortex-M33 SCS", PeripheralType::Scs)),
             ("ARM Ltd", 0xD21, 0x43, 0x1A01) => Some(PartInfo::new("Cortex-M33 ITM", PeripheralType::Itm)),
             ("ARM Ltd", 0xD21, 0x00, 0x1A02) => Some(PartInfo::new("Cortex-M33 DWT", PeripheralType::Dwt)),
             ("ARM Ltd", 0xD21, 0x00, 0x1A03) => Some(PartInfo::new("Cortex-M33 BPU", PeripheralType::Bpu)),
             ("ARM Ltd", 0xD21, 0x13, 0x4A13) => Some(PartInfo::new("Cortex-M33 ETM", PeripheralType::Etm)),
             ("ARM Ltd", 0xD21, 0x11, 0x0000) => Some(PartInfo::new("Cortex-M33 TPIU", PeripheralType::Tpiu)),
+            ("ARM Ltd", 0xD21, 0x14, 0x1A14) => Some(PartInfo::new("Cortex-M33 CTI", PeripheralType::Cti)),
             _ => None,
         }
     }
@@ -707,6 +717,8 @@ pub enum PeripheralType {
     Swo,
     Stm,
     Tsgen,
+    Cti,
+    Mtb,
 }
 
 impl std::fmt::Display for PeripheralType {
@@ -724,6 +736,8 @@ impl std::fmt::Display for PeripheralType {
             PeripheralType::Swo => write!(f, "Swo (Single Wire Output)"),
             PeripheralType::Stm => write!(f, "Stm (System Trace Macrocell)"),
             PeripheralType::Tsgen => write!(f, "Tsgen (Time Stamp Generator)"),
+            PeripheralType::Cti => write!(f, "Cti (Cross Trigger Interface)"),
+            PeripheralType::Mtb => write!(f, "Mtb (Micro Trace Buffer)"),
         }
     }
 }
@@ -2,21 +2,27 @@ pub mod ap;
 pub(crate) mod communication_interface;
 pub mod component;
 pub(crate) mod core;
+pub mod discovery;
 pub mod dp;
 pub mod memory;
 pub mod sequences;
 pub mod swo;
+pub mod trace;
 mod traits;
 
 pub use communication_interface::{
     ApInformation, ArmChipInfo, ArmCommunicationInterface, DapError, MemoryApInformation, Register,
+    RetryPolicy,
+};
+pub use swo::{
+    DataAccess, DataLoss, DataTraceDecoder, DataTraceEvent, SwoAccess, SwoConfig, SwoDecode,
+    SwoDecoder, SwoEvent, SwoMode, SwoPacket, SwoReader, SwoStatistics, SwvFraming, SwvServer,
 };
-pub use swo::{SwoAccess, SwoConfig, SwoMode};
 pub use traits::*;
 
 pub use self::core::armv6m;
 pub use self::core::armv7m;
 pub use self::core::armv8m;
-pub use self::core::Dump;
+pub use self::core::{Cfbp, Dump};
 
 pub use communication_interface::ArmProbeInterface;
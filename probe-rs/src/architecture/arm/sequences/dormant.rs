@@ -0,0 +1,129 @@
+//! Sequences to enter and leave the dormant state of a debug port.
+//!
+//! Debug ports of ADIv5.2 and later can be in a dormant state, in which they ignore all
+//! traffic on the wires except the selection alert sequence. Some parts, e.g. the RP2040,
+//! start up with the debug port dormant, and multi-drop SW-DPs use the dormant state to
+//! share the wires. See section B5.3 of the ADIv5.2 specification.
+//!
+//! The sequences are sent with [`DebugProbe::swj_sequence`], so they work with any probe
+//! which supports raw SWJ sequences.
+
+use crate::{DebugProbe, DebugProbeError, WireProtocol};
+
+/// The selection alert sequence, which is sent least significant bit first.
+const SELECTION_ALERT: u128 = 0x19bc_0ea2_e3dd_afe9_8685_2d95_6209_f392;
+
+/// The activation code which selects SWD, after the selection alert.
+const ACTIVATION_CODE_SWD: (u8, u64) = (8, 0x1a);
+
+/// The activation code which selects JTAG, after the selection alert.
+const ACTIVATION_CODE_JTAG: (u8, u64) = (12, 0x00a);
+
+/// The sequence which switches from SWD to the dormant state, after a line reset.
+const SWD_TO_DORMANT: (u8, u64) = (16, 0xe3bc);
+
+/// The sequence which switches from JTAG to the dormant state, after a JTAG reset.
+const JTAG_TO_DORMANT: (u8, u64) = (31, 0x33bb_bbba);
+
+/// SWDIO/TMS high for 51 cycles, which is a line reset in SWD, and resets the TAP in JTAG.
+const LINE_RESET: (u8, u64) = (51, 0x0007_ffff_ffff_ffff);
+
+/// The steps of the sequence which wakes a dormant debug port and selects `protocol`.
+fn leave_dormant_sequence(protocol: WireProtocol) -> Vec<(u8, u64)> {
+    let mut sequence = vec![
+        // At least 8 cycles SWDIO/TMS high.
+        (8, 0xff),
+        (64, SELECTION_ALERT as u64),
+        (64, (SELECTION_ALERT >> 64) as u64),
+        // 4 cycles SWDIO/TMS low.
+        (4, 0x0),
+    ];
+
+    match protocol {
+        WireProtocol::Swd => sequence.extend_from_slice(&[
+            ACTIVATION_CODE_SWD,
+            LINE_RESET,
+            // At least 2 idle cycles.
+            (3, 0x0),
+        ]),
+        WireProtocol::Jtag => sequence.extend_from_slice(&[ACTIVATION_CODE_JTAG, LINE_RESET]),
+    }
+
+    sequence
+}
+
+fn send_sequence<P: DebugProbe + ?Sized>(
+    probe: &mut P,
+    sequence: &[(u8, u64)],
+) -> Result<(), DebugProbeError> {
+    for (bit_len, bits) in sequence {
+        probe.swj_sequence(*bit_len, *bits)?;
+    }
+
+    Ok(())
+}
+
+/// Switch the debug port from `protocol` to the dormant state.
+pub fn enter_dormant<P: DebugProbe + ?Sized>(
+    probe: &mut P,
+    protocol: WireProtocol,
+) -> Result<(), DebugProbeError> {
+    match protocol {
+        WireProtocol::Swd => send_sequence(probe, &[LINE_RESET, SWD_TO_DORMANT]),
+        WireProtocol::Jtag => send_sequence(probe, &[LINE_RESET, JTAG_TO_DORMANT]),
+    }
+}
+
+/// Wake the debug port from the dormant state, and select `protocol`.
+///
+/// For SWD, this ends with a line reset, so the next transfer has to be a read of `DPIDR`,
+/// or a write to `TARGETSEL` for multi-drop debug ports.
+pub fn leave_dormant<P: DebugProbe + ?Sized>(
+    probe: &mut P,
+    protocol: WireProtocol,
+) -> Result<(), DebugProbeError> {
+    send_sequence(probe, &leave_dormant_sequence(protocol))
+}
+
+/// Select SWD on a debug port in an unknown state.
+///
+/// A debug port in JTAG is switched to the dormant state first, which debug ports in SWD
+/// or in the dormant state ignore, and is then woken with SWD selected. This is the
+/// sequence recommended by ADIv5.2 for debug ports which support the dormant state.
+pub fn select_swd<P: DebugProbe + ?Sized>(probe: &mut P) -> Result<(), DebugProbeError> {
+    enter_dormant(probe, WireProtocol::Jtag)?;
+    leave_dormant(probe, WireProtocol::Swd)
+}
+
+#[cfg(test)]
+mod test {
+    use super::leave_dormant_sequence;
+    use crate::WireProtocol;
+
+    #[test]
+    fn dormant_to_swd() {
+        let bits: Vec<bool> = leave_dormant_sequence(WireProtocol::Swd)
+            .into_iter()
+            .flat_map(|(bit_len, bits)| (0..bit_len).map(move |bit| (bits >> bit) & 1 == 1))
+            .collect();
+
+        // The sequence up to the activation code, as specified in ADIv5.2, least
+        // significant bit of each byte first.
+        let expected: &[u8] = &[
+            0xff, 0x92, 0xf3, 0x09, 0x62, 0x95, 0x2d, 0x85, 0x86, 0xe9, 0xaf, 0xdd, 0xe3, 0xa2,
+            0x0e, 0xbc, 0x19, 0xa0,
+        ];
+        let expected: Vec<bool> = expected
+            .iter()
+            .flat_map(|byte| (0..8).map(move |bit| (byte >> bit) & 1 == 1))
+            .collect();
+
+        assert_eq!(bits[..expected.len()], expected[..]);
+
+        // The activation code 0x1a ends with a zero bit, followed by the line reset.
+        assert!(!bits[expected.len() + 3]);
+        assert!(bits[expected.len() + 4..expected.len() + 4 + 51]
+            .iter()
+            .all(|bit| *bit));
+    }
+}
@@ -1,15 +1,18 @@
+pub mod dormant;
+pub mod nordic;
 pub mod nxp;
+pub mod raspberrypi;
+pub mod stm32;
 
-use std::{
-    sync::Arc,
-    thread,
-    time::{Duration, Instant},
-};
+use std::{sync::Arc, thread, time::Duration};
+use web_time::Instant;
+
+use probe_rs_target::{CoreType, ResetKind};
 
 use crate::{architecture::arm::DapError, core::CoreRegister, DebugProbeError, Memory};
 
 use super::{
-    communication_interface::{DapProbe, Initialized, SwdSequence},
+    communication_interface::{ArmProbeInterface, DapProbe, Initialized, SwdSequence},
     dp::{Abort, Ctrl, DpAccess, Select, DPIDR},
     ArmCommunicationInterface, DpAddress, Pins, PortType, Register,
 };
@@ -196,6 +199,35 @@ pub trait ArmDebugSequence: Send + Sync {
         Ok(())
     }
 
+    /// Keep the debug connection alive while the core is in a low power mode, e.g. by enabling
+    /// the debug clocks during sleep. This is called after [`debug_core_start`], and is not part
+    /// of the [ARM SVD Debug Description].
+    ///
+    /// This is empty by default, because the necessary steps are specific to the chip.
+    ///
+    /// [`debug_core_start`]: ArmDebugSequence::debug_core_start
+    /// [ARM SVD Debug Description]: http://www.keil.com/pack/doc/cmsis/Pack/html/debug_description.html
+    fn debug_sleep_enable(&self, _memory: &mut Memory) -> Result<(), crate::Error> {
+        // Empty by default
+        Ok(())
+    }
+
+    /// Stop or restart the watchdogs of the chip while the core is halted, so that a halted
+    /// core is not reset by a watchdog while it is debugged. This is not part of the
+    /// [ARM SVD Debug Description].
+    ///
+    /// This is empty by default, because the watchdogs are specific to the chip.
+    ///
+    /// [ARM SVD Debug Description]: http://www.keil.com/pack/doc/cmsis/Pack/html/debug_description.html
+    fn debug_watchdog_freeze(
+        &self,
+        _memory: &mut Memory,
+        _freeze: bool,
+    ) -> Result<(), crate::Error> {
+        // Empty by default
+        Ok(())
+    }
+
     /// Configure the target to stop code execution after a reset. After this, the core will halt when it comes
     /// out of reset. This is based on the `ResetCatchSet` function from
     /// the [ARM SVD Debug Description].
@@ -263,14 +295,37 @@ pub trait ArmDebugSequence: Send + Sync {
         Err(crate::Error::Probe(DebugProbeError::Timeout))
     }
 
+    /// Executes a local reset of the processor only, without resetting the rest of the system.
+    /// This is based on the `ResetProcessor` function from the [ARM SVD Debug Description].
+    ///
+    /// The default implementation uses AIRCR.VECTRESET, which is only available on ARMv7-M cores.
+    ///
+    /// [ARM SVD Debug Description]: http://www.keil.com/pack/doc/cmsis/Pack/html/debug_description.html#resetProcessor
+    #[doc(alias = "ResetProcessor")]
+    fn reset_processor(
+        &self,
+        interface: &mut Memory,
+        core_type: CoreType,
+    ) -> Result<(), crate::Error> {
+        match core_type {
+            CoreType::Armv7m | CoreType::Armv7em => reset_vector(interface),
+            _ => Err(crate::Error::UnsupportedResetKind(ResetKind::Core)),
+        }
+    }
+
     /// Check if the device is in a locked state and unlock it.
-    /// Use query command elements for user confirmation.
+    /// Unlocking operations which destroy data, e.g. a mass erase, are only done if
+    /// they are granted by `permissions`, instead of asking the user for confirmation.
     /// Executed after having powered up the debug port. This is based on the
     /// `DebugDeviceUnlock` function from the [ARM SVD Debug Description].
     ///
     /// [ARM SVD Debug Description]: http://www.keil.com/pack/doc/cmsis/Pack/html/debug_description.html#debugDeviceUnlock
     #[doc(alias = "DebugDeviceUnlock")]
-    fn debug_device_unlock(&self, _interface: &mut crate::Memory) -> Result<(), crate::Error> {
+    fn debug_device_unlock(
+        &self,
+        _interface: &mut crate::Memory,
+        _permissions: &crate::Permissions,
+    ) -> Result<(), crate::Error> {
         // Empty by default
         Ok(())
     }
@@ -284,4 +339,85 @@ pub trait ArmDebugSequence: Send + Sync {
         // Empty by default
         Ok(())
     }
+
+    /// Unlock the debug access to a chip which restricts it, by authenticating the debugger
+    /// with `credentials`. This is not part of the [ARM SVD Debug Description].
+    ///
+    /// Debug authentication is specific to the chip, so it is not supported by default.
+    ///
+    /// [ARM SVD Debug Description]: http://www.keil.com/pack/doc/cmsis/Pack/html/debug_description.html
+    fn debug_unlock(
+        &self,
+        _interface: &mut dyn ArmProbeInterface,
+        _dp: DpAddress,
+        _credentials: &dyn DebugCredentials,
+    ) -> Result<(), crate::Error> {
+        Err(DebugProbeError::NotImplemented("debug authentication").into())
+    }
+}
+
+/// Credentials used to authenticate the debugger to a chip which restricts debug access.
+///
+/// The chip sends a challenge, which has to be answered with a response signed by a key
+/// the chip trusts. The signing is done by the implementation of this trait, e.g. with a
+/// key from a file, or by a hardware security module.
+///
+/// This is implemented for closures taking the challenge and returning the response.
+pub trait DebugCredentials {
+    /// Compute the response to the `challenge` sent by the chip.
+    fn respond(&self, challenge: &[u8]) -> Result<Vec<u8>, crate::Error>;
+}
+
+impl<F> DebugCredentials for F
+where
+    F: Fn(&[u8]) -> Result<Vec<u8>, crate::Error>,
+{
+    fn respond(&self, challenge: &[u8]) -> Result<Vec<u8>, crate::Error> {
+        self(challenge)
+    }
+}
+
+/// Reset a core of the given type with the given kind of reset, using the functions of `sequence`.
+pub(crate) fn reset_with_kind(
+    sequence: &dyn ArmDebugSequence,
+    interface: &mut Memory,
+    core_type: CoreType,
+    kind: ResetKind,
+) -> Result<(), crate::Error> {
+    match kind {
+        ResetKind::Hardware => {
+            sequence.reset_hardware_assert(interface.get_arm_interface()?.dap_probe_mut())?;
+            sequence.reset_hardware_deassert(interface)
+        }
+        ResetKind::System => sequence.reset_system(interface),
+        ResetKind::Vector => match core_type {
+            CoreType::Armv7m | CoreType::Armv7em => reset_vector(interface),
+            _ => Err(crate::Error::UnsupportedResetKind(ResetKind::Vector)),
+        },
+        ResetKind::Core => sequence.reset_processor(interface, core_type),
+    }
+}
+
+/// Reset the processor using AIRCR.VECTRESET, which is only available on ARMv7-M cores.
+fn reset_vector(interface: &mut Memory) -> Result<(), crate::Error> {
+    use crate::architecture::arm::core::armv7m::{Aircr, Dhcsr};
+
+    let mut aircr = Aircr(0);
+    aircr.vectkey();
+    aircr.set_vectreset(true);
+
+    interface.write_word_32(Aircr::ADDRESS, aircr.into())?;
+
+    let start = Instant::now();
+
+    while start.elapsed() < Duration::from_millis(500) {
+        let dhcsr = Dhcsr(interface.read_word_32(Dhcsr::ADDRESS)?);
+
+        // Wait until the S_RESET_ST bit is cleared on a read
+        if !dhcsr.s_reset_st() {
+            return Ok(());
+        }
+    }
+
+    Err(crate::Error::Probe(DebugProbeError::Timeout))
 }
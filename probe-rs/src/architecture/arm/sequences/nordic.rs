@@ -0,0 +1,227 @@
+//! Sequences for Nordic Semiconductor chips.
+
+use std::sync::Arc;
+use std::time::Duration;
+use web_time::Instant;
+
+use crate::architecture::arm::{ApAddress, DapAccess, DpAddress};
+use crate::{DebugProbeError, Memory, Permissions};
+
+use super::ArmDebugSequence;
+
+/// Address of the `TASKS_CONSTLAT` register of the POWER peripheral.
+const POWER_TASKS_CONSTLAT: u32 = 0x4000_0078;
+
+/// Address of the `CONFIG` register of the WDT peripheral.
+const WDT_CONFIG: u32 = 0x4001_0504;
+
+/// The `HALT` bit of `WDT_CONFIG`, which keeps the watchdog running while the core is halted.
+const WDT_CONFIG_HALT: u32 = 1 << 3;
+
+/// Address of the `TASKS_CONSTLAT` register of the secure POWER peripheral of the nRF53 and nRF91.
+const POWER_S_TASKS_CONSTLAT: u32 = 0x5000_5078;
+
+/// Address of the `CONFIG` register of the secure WDT0 peripheral of the nRF53 and nRF91.
+const WDT_S_CONFIG: u32 = 0x5001_8504;
+
+/// Address of the `NETWORK.FORCEOFF` register of the secure RESET peripheral of the nRF53.
+const RESET_S_NETWORK_FORCEOFF: u32 = 0x5000_5614;
+
+/// Registers of the CTRL-AP, which is accessible even if the AHB-AP of a core is protected.
+const CTRL_AP_RESET: u8 = 0x00;
+const CTRL_AP_ERASEALL: u8 = 0x04;
+const CTRL_AP_ERASEALLSTATUS: u8 = 0x08;
+const CTRL_AP_APPROTECTSTATUS: u8 = 0x0C;
+
+/// Maximum duration of an `ERASEALL` through the CTRL-AP.
+const ERASEALL_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Errors of the sequences for Nordic chips.
+#[derive(thiserror::Error, Debug)]
+pub enum NordicError {
+    #[error("The AHB-AP {0} is still protected after erasing the chip through the CTRL-AP {1}")]
+    UnlockFailed(u8, u8),
+}
+
+/// nRF51 and nRF52 chips, which have to be kept in the constant latency mode,
+/// so that the debug connection is not lost when the chip enters the System ON sleep mode.
+pub struct Nrf(());
+
+impl Nrf {
+    pub fn create() -> Arc<dyn ArmDebugSequence> {
+        Arc::new(Self(()))
+    }
+}
+
+impl ArmDebugSequence for Nrf {
+    fn debug_sleep_enable(&self, memory: &mut Memory) -> Result<(), crate::Error> {
+        memory.write_word_32(POWER_TASKS_CONSTLAT, 1)?;
+
+        Ok(())
+    }
+
+    /// The configuration of the watchdog can't be changed after it was started, so this
+    /// only has an effect if it is done before the program starts the watchdog.
+    fn debug_watchdog_freeze(&self, memory: &mut Memory, freeze: bool) -> Result<(), crate::Error> {
+        watchdog_freeze(memory, WDT_CONFIG, freeze)
+    }
+}
+
+/// Check if the AHB-AP of a core is accessible, using the `APPROTECTSTATUS` register of its CTRL-AP.
+fn is_ap_unlocked(memory: &mut Memory, ctrl_ap: u8) -> Result<bool, crate::Error> {
+    let interface = memory.get_arm_interface()?;
+
+    let status = interface.read_raw_ap_register(
+        ApAddress {
+            dp: DpAddress::Default,
+            ap: ctrl_ap,
+        },
+        CTRL_AP_APPROTECTSTATUS,
+    )?;
+
+    // The APPROTECT bit reads as 0 if the protection is enabled.
+    Ok(status & 1 != 0)
+}
+
+/// Erase the flash, RAM and UICR of the core of `ctrl_ap`, which removes the protection of its AHB-AP.
+fn erase_all(memory: &mut Memory, ctrl_ap: u8) -> Result<(), crate::Error> {
+    let interface = memory.get_arm_interface()?;
+    let ctrl_ap = ApAddress {
+        dp: DpAddress::Default,
+        ap: ctrl_ap,
+    };
+
+    interface.write_raw_ap_register(ctrl_ap, CTRL_AP_ERASEALL, 1)?;
+
+    let start = Instant::now();
+    while interface.read_raw_ap_register(ctrl_ap, CTRL_AP_ERASEALLSTATUS)? != 0 {
+        if start.elapsed() > ERASEALL_TIMEOUT {
+            return Err(DebugProbeError::Timeout.into());
+        }
+    }
+
+    // The protection is only removed by a reset after the erase.
+    interface.write_raw_ap_register(ctrl_ap, CTRL_AP_RESET, 1)?;
+    interface.write_raw_ap_register(ctrl_ap, CTRL_AP_RESET, 0)?;
+
+    Ok(())
+}
+
+/// Unlock the cores of a chip, which are given as pairs of the AHB-AP of the core
+/// and its CTRL-AP. Locked cores are erased completely, if `permissions` allow it.
+fn unlock_cores(
+    memory: &mut Memory,
+    permissions: &Permissions,
+    cores: &[(u8, u8)],
+) -> Result<(), crate::Error> {
+    for &(ahb_ap, ctrl_ap) in cores {
+        if is_ap_unlocked(memory, ctrl_ap)? {
+            continue;
+        }
+
+        // Unlocking erases all data of the chip, so it is never done without permission.
+        permissions.erase_all()?;
+
+        log::warn!(
+            "The AHB-AP {} is protected, erasing the chip through the CTRL-AP {} to unlock it",
+            ahb_ap,
+            ctrl_ap
+        );
+
+        erase_all(memory, ctrl_ap)?;
+
+        if !is_ap_unlocked(memory, ctrl_ap)? {
+            return Err(crate::Error::architecture_specific(
+                NordicError::UnlockFailed(ahb_ap, ctrl_ap),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Freeze or run the watchdog with the `CONFIG` register at `config_address` while the core is halted.
+fn watchdog_freeze(
+    memory: &mut Memory,
+    config_address: u32,
+    freeze: bool,
+) -> Result<(), crate::Error> {
+    let mut config = memory.read_word_32(config_address)?;
+
+    if freeze {
+        config &= !WDT_CONFIG_HALT;
+    } else {
+        config |= WDT_CONFIG_HALT;
+    }
+
+    memory.write_word_32(config_address, config)?;
+
+    Ok(())
+}
+
+/// The nRF5340, with an application core behind AP 0 and a network core behind AP 1.
+///
+/// Both cores are unlocked through their CTRL-AP if their APPROTECT is enabled and
+/// erasing the chip is permitted, and the network core is released from the forced-off state, so that it can be attached.
+pub struct Nrf53(());
+
+impl Nrf53 {
+    pub fn create() -> Arc<dyn ArmDebugSequence> {
+        Arc::new(Self(()))
+    }
+}
+
+impl ArmDebugSequence for Nrf53 {
+    fn debug_device_unlock(
+        &self,
+        memory: &mut Memory,
+        permissions: &Permissions,
+    ) -> Result<(), crate::Error> {
+        // The CTRL-APs of the application and the network core are AP 2 and AP 3.
+        unlock_cores(memory, permissions, &[(0, 2), (1, 3)])?;
+
+        // The network core is kept off after a reset, until the application core releases it.
+        memory.write_word_32(RESET_S_NETWORK_FORCEOFF, 0)?;
+
+        Ok(())
+    }
+
+    fn debug_sleep_enable(&self, memory: &mut Memory) -> Result<(), crate::Error> {
+        memory.write_word_32(POWER_S_TASKS_CONSTLAT, 1)?;
+
+        Ok(())
+    }
+
+    fn debug_watchdog_freeze(&self, memory: &mut Memory, freeze: bool) -> Result<(), crate::Error> {
+        watchdog_freeze(memory, WDT_S_CONFIG, freeze)
+    }
+}
+
+/// The nRF9160, with its core behind AP 0 and the CTRL-AP at AP 4.
+pub struct Nrf91(());
+
+impl Nrf91 {
+    pub fn create() -> Arc<dyn ArmDebugSequence> {
+        Arc::new(Self(()))
+    }
+}
+
+impl ArmDebugSequence for Nrf91 {
+    fn debug_device_unlock(
+        &self,
+        memory: &mut Memory,
+        permissions: &Permissions,
+    ) -> Result<(), crate::Error> {
+        unlock_cores(memory, permissions, &[(0, 4)])
+    }
+
+    fn debug_sleep_enable(&self, memory: &mut Memory) -> Result<(), crate::Error> {
+        memory.write_word_32(POWER_S_TASKS_CONSTLAT, 1)?;
+
+        Ok(())
+    }
+
+    fn debug_watchdog_freeze(&self, memory: &mut Memory, freeze: bool) -> Result<(), crate::Error> {
+        watchdog_freeze(memory, WDT_S_CONFIG, freeze)
+    }
+}
@@ -1,13 +1,10 @@
-use std::{
-    sync::Arc,
-    thread,
-    time::{Duration, Instant},
-};
+use std::{sync::Arc, thread, time::Duration};
+use web_time::Instant;
 
 use crate::{
     architecture::arm::{
         ap::{ApAccess, GenericAp, IDR},
-        communication_interface::Initialized,
+        communication_interface::{ArmProbeInterface, Initialized},
         dp::{Abort, Ctrl, DpAccess, Select, DPIDR},
         ApAddress, ArmCommunicationInterface, DapAccess, DpAddress,
     },
@@ -15,7 +12,246 @@ use crate::{
     CommunicationInterface, DebugProbeError,
 };
 
-use super::ArmDebugSequence;
+use super::{ArmDebugSequence, DebugCredentials};
+
+/// The index of the debug mailbox access port (DM-AP).
+const DEBUG_MAILBOX_AP: u8 = 2;
+
+/// Registers of the debug mailbox access port.
+const DM_CSW: u8 = 0x0;
+const DM_REQUEST: u8 = 0x4;
+const DM_RETURN: u8 = 0x8;
+
+/// The `RESYNCH_REQ` and `CHIP_RESET_REQ` bits of the `CSW` register of the DM-AP.
+const DM_CSW_RESYNCH: u32 = (1 << 5) | (1 << 0);
+/// The `REQ_PENDING` bit of the `CSW` register of the DM-AP.
+const DM_CSW_REQ_PENDING: u32 = 1 << 1;
+
+/// The token exchanged to acknowledge each data word of a request or a response.
+const DM_ACK_TOKEN: u32 = 0xA5A5;
+
+/// Maximum time the ROM takes to process a command of the debug mailbox.
+const DM_COMMAND_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Commands of the debug mailbox, handled by the ROM of the chip.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u16)]
+pub enum DebugMailboxCommand {
+    /// Start the debug mailbox.
+    Start = 0x01,
+    /// Read the code read protection (CRP) level.
+    GetCrpLevel = 0x02,
+    /// Erase the flash, which also removes the CRP.
+    EraseFlash = 0x03,
+    /// Leave the debug mailbox, and continue booting.
+    Exit = 0x04,
+    /// Enter the in-system programming (ISP) mode of the ROM.
+    EnterIspMode = 0x05,
+    /// Set the fault analysis mode.
+    SetFaultAnalysisMode = 0x06,
+    /// Start a debug session, which enables the debug access to the cores.
+    StartDebugSession = 0x07,
+    /// Start the debug authentication, which returns the debug authentication challenge.
+    DebugAuthenticationStart = 0x10,
+    /// Send the response to the debug authentication challenge.
+    DebugAuthenticationResponse = 0x11,
+}
+
+/// Errors of the debug mailbox.
+#[derive(thiserror::Error, Debug)]
+pub enum DebugMailboxError {
+    #[error("The debug mailbox command {command:?} failed with status {status:#06x}")]
+    CommandFailed {
+        command: DebugMailboxCommand,
+        status: u16,
+    },
+    #[error("The ROM did not respond to the debug mailbox command {0:?}")]
+    Timeout(DebugMailboxCommand),
+}
+
+impl From<DebugMailboxError> for crate::Error {
+    fn from(error: DebugMailboxError) -> Self {
+        crate::Error::architecture_specific(error)
+    }
+}
+
+/// The debug mailbox of NXP chips like the LPC55xx, RT5xx and RT6xx.
+///
+/// The debug mailbox is an access port (DM-AP), through which commands are sent to the ROM
+/// of the chip. It is used to enable debug access, to authenticate the debugger on chips
+/// which restrict debug access, and to enter the ISP mode or erase the flash.
+pub struct DebugMailbox<'interface, I: DapAccess + ?Sized> {
+    interface: &'interface mut I,
+    ap: ApAddress,
+}
+
+impl<'interface, I: DapAccess + ?Sized> DebugMailbox<'interface, I> {
+    /// Access the debug mailbox of the chip behind `dp`.
+    pub fn new(interface: &'interface mut I, dp: DpAddress) -> Self {
+        Self {
+            interface,
+            ap: ApAddress {
+                dp,
+                ap: DEBUG_MAILBOX_AP,
+            },
+        }
+    }
+
+    /// Reset the chip, and synchronize with the ROM, which then waits for commands.
+    pub fn resync(&mut self) -> Result<(), crate::Error> {
+        self.interface
+            .write_raw_ap_register(self.ap, DM_CSW, DM_CSW_RESYNCH)?;
+
+        // The ROM needs some time after the reset, before it handles the debug mailbox.
+        thread::sleep(Duration::from_millis(30));
+
+        let _ = self.interface.read_raw_ap_register(self.ap, DM_CSW)?;
+
+        Ok(())
+    }
+
+    /// Send `command` with the data words in `data`, and return the data words of the response.
+    pub fn command(
+        &mut self,
+        command: DebugMailboxCommand,
+        data: &[u32],
+    ) -> Result<Vec<u32>, crate::Error> {
+        log::debug!(
+            "Debug mailbox command {:?} with {} words",
+            command,
+            data.len()
+        );
+
+        self.write_request(command, ((data.len() as u32) << 16) | command as u32)?;
+
+        for word in data {
+            self.wait_for_ack(command)?;
+            self.write_request(command, *word)?;
+        }
+
+        let header = self.read_return(command)?;
+        let status = (header & 0xffff) as u16;
+
+        if status != 0 {
+            return Err(DebugMailboxError::CommandFailed { command, status }.into());
+        }
+
+        let length = header >> 16;
+        let mut response = Vec::with_capacity(length as usize);
+
+        for remaining in (0..length).rev() {
+            self.write_request(command, (remaining << 16) | DM_ACK_TOKEN)?;
+            response.push(self.read_return(command)?);
+        }
+
+        Ok(response)
+    }
+
+    /// Start a debug session, which enables the debug access to the cores.
+    pub fn start_debug_session(&mut self) -> Result<(), crate::Error> {
+        self.command(DebugMailboxCommand::StartDebugSession, &[])?;
+
+        Ok(())
+    }
+
+    /// Read the code read protection (CRP) level of the chip.
+    pub fn crp_level(&mut self) -> Result<u32, crate::Error> {
+        let response = self.command(DebugMailboxCommand::GetCrpLevel, &[])?;
+
+        Ok(response.first().copied().unwrap_or(0))
+    }
+
+    /// Erase the complete flash, which also removes the code read protection.
+    pub fn erase_flash(&mut self) -> Result<(), crate::Error> {
+        self.command(DebugMailboxCommand::EraseFlash, &[])?;
+
+        Ok(())
+    }
+
+    /// Enter the ISP mode of the ROM. The values of `mode`, which select the
+    /// interface used by the ISP, are listed in the user manual of the chip.
+    pub fn enter_isp_mode(&mut self, mode: u32) -> Result<(), crate::Error> {
+        self.command(DebugMailboxCommand::EnterIspMode, &[mode])?;
+
+        Ok(())
+    }
+
+    /// Leave the debug mailbox, so that the ROM continues to boot the chip.
+    pub fn exit(&mut self) -> Result<(), crate::Error> {
+        self.command(DebugMailboxCommand::Exit, &[])?;
+
+        Ok(())
+    }
+
+    /// Authenticate the debugger, by answering the debug authentication
+    /// challenge of the chip with the response computed by `credentials`.
+    pub fn authenticate(&mut self, credentials: &dyn DebugCredentials) -> Result<(), crate::Error> {
+        let challenge: Vec<u8> = self
+            .command(DebugMailboxCommand::DebugAuthenticationStart, &[])?
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect();
+
+        let response = credentials.respond(&challenge)?;
+
+        let words: Vec<u32> = response
+            .chunks(4)
+            .map(|chunk| {
+                let mut word = [0; 4];
+                word[..chunk.len()].copy_from_slice(chunk);
+                u32::from_le_bytes(word)
+            })
+            .collect();
+
+        self.command(DebugMailboxCommand::DebugAuthenticationResponse, &words)?;
+
+        Ok(())
+    }
+
+    fn write_request(
+        &mut self,
+        command: DebugMailboxCommand,
+        value: u32,
+    ) -> Result<(), crate::Error> {
+        self.interface
+            .write_raw_ap_register(self.ap, DM_REQUEST, value)?;
+
+        let start = Instant::now();
+        while self.interface.read_raw_ap_register(self.ap, DM_CSW)? & DM_CSW_REQ_PENDING != 0 {
+            if start.elapsed() > DM_COMMAND_TIMEOUT {
+                return Err(DebugMailboxError::Timeout(command).into());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_return(&mut self, command: DebugMailboxCommand) -> Result<u32, crate::Error> {
+        let start = Instant::now();
+
+        // The ROM does not respond to reads of RETURN while it processes the request.
+        loop {
+            match self.interface.read_raw_ap_register(self.ap, DM_RETURN) {
+                Ok(value) => return Ok(value),
+                Err(_) if start.elapsed() < DM_COMMAND_TIMEOUT => continue,
+                Err(_) => return Err(DebugMailboxError::Timeout(command).into()),
+            }
+        }
+    }
+
+    /// Wait until the ROM acknowledged the previous data word of a request.
+    fn wait_for_ack(&mut self, command: DebugMailboxCommand) -> Result<(), crate::Error> {
+        let start = Instant::now();
+
+        while self.read_return(command)? & 0xffff != DM_ACK_TOKEN {
+            if start.elapsed() > DM_COMMAND_TIMEOUT {
+                return Err(DebugMailboxError::Timeout(command).into());
+            }
+        }
+
+        Ok(())
+    }
+}
 
 pub struct LPC55S69(());
 
@@ -201,6 +437,22 @@ impl ArmDebugSequence for LPC55S69 {
 
         wait_for_stop_after_reset(interface)
     }
+
+    fn debug_unlock(
+        &self,
+        interface: &mut dyn ArmProbeInterface,
+        dp: DpAddress,
+        credentials: &dyn DebugCredentials,
+    ) -> Result<(), crate::Error> {
+        let mut mailbox = DebugMailbox::new(interface, dp);
+
+        mailbox.resync()?;
+        mailbox.authenticate(credentials)?;
+
+        log::info!("Debug authentication succeeded");
+
+        Ok(())
+    }
 }
 
 fn wait_for_stop_after_reset(memory: &mut crate::Memory) -> Result<(), crate::Error> {
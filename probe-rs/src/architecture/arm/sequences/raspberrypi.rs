@@ -0,0 +1,147 @@
+//! Sequences for Raspberry Pi chips.
+
+use std::sync::Arc;
+
+use crate::architecture::arm::{
+    communication_interface::DapProbe, dp::Ctrl, DpAddress, PortType, Register,
+};
+use crate::{DebugProbeError, Memory, Probe};
+
+use super::{dormant, ArmDebugSequence};
+
+/// The `TARGETSEL` value of the rescue DP of the RP2040.
+const RP2040_RESCUE_DP: u32 = 0xf100_2927;
+
+/// Address of the `CTRL` register of the watchdog.
+const WATCHDOG_CTRL: u32 = 0x4005_8000;
+
+/// The `PAUSE_DBG0`, `PAUSE_DBG1` and `PAUSE_JTAG` bits of `WATCHDOG_CTRL`, which pause the
+/// watchdog while either core is halted, or while the debugger accesses the chip.
+const WATCHDOG_CTRL_PAUSE: u32 = 0b111 << 24;
+
+/// Address of the second stage bootloader (boot2) in the flash.
+const BOOT2_ADDRESS: u32 = 0x1000_0000;
+
+/// Size of boot2, including the CRC in its last 4 bytes.
+const BOOT2_SIZE: usize = 256;
+
+/// The RP2040, with two Cortex-M0+ cores behind separate DPs on a multi-drop SWD bus.
+///
+/// The cores are selected with `TARGETSEL`, using the `psel` of the cores in the target
+/// description. Both cores can be attached, the second one is started when it is attached.
+pub struct Rp2040(());
+
+impl Rp2040 {
+    pub fn create() -> Arc<dyn ArmDebugSequence> {
+        Arc::new(Self(()))
+    }
+
+    /// Reset the chip through the rescue DP.
+    ///
+    /// After this reset, the boot ROM halts both cores instead of running the program in the flash.
+    /// This allows attaching to the chip again, if the program in the flash prevents it, e.g. by
+    /// disabling the clocks of the debug system. The reset flag is cleared by the next reset.
+    ///
+    /// The probe has to be attached with [`Probe::attach_to_unspecified`], and has to support
+    /// multi-drop SWD.
+    pub fn rescue(probe: &mut Probe) -> Result<(), crate::Error> {
+        let interface = probe
+            .try_as_dap_probe()
+            .ok_or(DebugProbeError::InterfaceNotAvailable("multi-drop SWD"))?;
+
+        interface.select_dp(DpAddress::Multidrop(RP2040_RESCUE_DP))?;
+
+        // Setting and clearing the power up request of the rescue DP resets the chip.
+        let mut ctrl = Ctrl(0);
+        ctrl.set_cdbgpwrupreq(true);
+
+        interface.raw_write_register(PortType::DebugPort, Ctrl::ADDRESS, ctrl.into())?;
+        interface.raw_write_register(PortType::DebugPort, Ctrl::ADDRESS, 0)?;
+
+        log::info!("Reset the RP2040 through the rescue DP");
+
+        Ok(())
+    }
+}
+
+impl ArmDebugSequence for Rp2040 {
+    fn debug_port_setup(&self, interface: &mut Box<dyn DapProbe>) -> Result<(), crate::Error> {
+        // The DPs of the RP2040 are dormant after a power-on reset, and don't react to the
+        // JTAG-to-SWD sequence. They are selected with `TARGETSEL` afterwards.
+        dormant::select_swd(&mut **interface)?;
+
+        Ok(())
+    }
+
+    fn debug_watchdog_freeze(&self, memory: &mut Memory, freeze: bool) -> Result<(), crate::Error> {
+        let mut ctrl = memory.read_word_32(WATCHDOG_CTRL)?;
+
+        if freeze {
+            ctrl |= WATCHDOG_CTRL_PAUSE;
+        } else {
+            ctrl &= !WATCHDOG_CTRL_PAUSE;
+        }
+
+        memory.write_word_32(WATCHDOG_CTRL, ctrl)?;
+
+        Ok(())
+    }
+}
+
+/// Check the CRC of the boot2 at the start of an image for the flash of the RP2040.
+///
+/// The boot ROM only runs the program in the flash if the last 4 bytes of boot2 are the
+/// CRC-32/MPEG-2 of the other 252 bytes. Returns `None` if `address` is not the start of
+/// the flash, or `data` is too short to contain boot2.
+pub(crate) fn rp2040_boot2_valid(address: u32, data: &[u8]) -> Option<bool> {
+    if address != BOOT2_ADDRESS || data.len() < BOOT2_SIZE {
+        return None;
+    }
+
+    let (boot2, crc) = data[..BOOT2_SIZE].split_at(BOOT2_SIZE - 4);
+    let crc = u32::from_le_bytes([crc[0], crc[1], crc[2], crc[3]]);
+
+    Some(crc32_mpeg2(boot2) == crc)
+}
+
+fn crc32_mpeg2(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+
+    for byte in data {
+        crc ^= u32::from(*byte) << 24;
+
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04c1_1db7
+            } else {
+                crc << 1
+            };
+        }
+    }
+
+    crc
+}
+
+#[cfg(test)]
+mod test {
+    use super::{crc32_mpeg2, rp2040_boot2_valid};
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32_mpeg2(b"123456789"), 0x0376_e6e7);
+    }
+
+    #[test]
+    fn boot2_crc() {
+        let mut image = vec![0x55; 512];
+        let crc = crc32_mpeg2(&image[..252]);
+        image[252..256].copy_from_slice(&crc.to_le_bytes());
+
+        assert_eq!(rp2040_boot2_valid(0x1000_0000, &image), Some(true));
+        assert_eq!(rp2040_boot2_valid(0x1000_0100, &image), None);
+        assert_eq!(rp2040_boot2_valid(0x1000_0000, &image[..128]), None);
+
+        image[0] = 0;
+        assert_eq!(rp2040_boot2_valid(0x1000_0000, &image), Some(false));
+    }
+}
@@ -0,0 +1,301 @@
+//! Sequences for STM32 chips.
+
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use web_time::Instant;
+
+use bitfield::bitfield;
+
+use crate::architecture::arm::{ap::MemoryAp, ApAddress};
+use crate::{DebugProbeError, Memory};
+
+use super::ArmDebugSequence;
+
+bitfield! {
+    /// The debug configuration register of the DBGMCU peripheral.
+    #[derive(Copy, Clone)]
+    pub struct DbgmcuCr(u32);
+    impl Debug;
+    /// Keep the debug clocks enabled in STANDBY mode.
+    pub dbg_standby, set_dbg_standby: 2;
+    /// Keep the debug clocks enabled in STOP mode.
+    pub dbg_stop, set_dbg_stop: 1;
+    /// Keep the debug clocks enabled in SLEEP mode.
+    pub dbg_sleep, set_dbg_sleep: 0;
+}
+
+impl From<u32> for DbgmcuCr {
+    fn from(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+impl From<DbgmcuCr> for u32 {
+    fn from(value: DbgmcuCr) -> Self {
+        value.0
+    }
+}
+
+impl DbgmcuCr {
+    /// The address of the register on the Cortex-M3, M4 and M7 based families.
+    ///
+    /// The Cortex-M0 based families have the register on the APB bus, where
+    /// it can only be accessed after enabling the clock of the DBGMCU peripheral.
+    const ADDRESS: u32 = 0xE004_2004;
+}
+
+/// The register of the DBGMCU peripheral which stops the watchdogs while the core is
+/// halted, and the mask of the `DBG_IWDG_STOP` and `DBG_WWDG_STOP` bits.
+fn watchdog_freeze_register(chip_name: &str) -> Option<(u32, u32)> {
+    if chip_name.starts_with("STM32F1") {
+        // The bits are part of DBGMCU_CR.
+        Some((DbgmcuCr::ADDRESS, (1 << 8) | (1 << 9)))
+    } else if chip_name.starts_with("STM32WB") || chip_name.starts_with("STM32WL") {
+        // DBGMCU_APB1FZR1 of the first core.
+        Some((0xE004_203C, (1 << 11) | (1 << 12)))
+    } else if [
+        "STM32F2", "STM32F3", "STM32F4", "STM32F7", "STM32G4", "STM32L1", "STM32L4",
+    ]
+    .iter()
+    .any(|family| chip_name.starts_with(family))
+    {
+        // DBGMCU_APB1_FZ, called DBGMCU_APB1FZR1 on some families.
+        Some((0xE004_2008, (1 << 11) | (1 << 12)))
+    } else {
+        None
+    }
+}
+
+/// STM32 chips, which need the debug clocks to be enabled in the
+/// DBGMCU peripheral to keep the debug connection in low power modes.
+pub struct Stm32 {
+    /// The register and bits which stop the watchdogs, if they are known for the family.
+    watchdog_freeze: Option<(u32, u32)>,
+}
+
+impl Stm32 {
+    pub fn create(chip_name: &str) -> Arc<dyn ArmDebugSequence> {
+        Arc::new(Self {
+            watchdog_freeze: watchdog_freeze_register(chip_name),
+        })
+    }
+}
+
+impl ArmDebugSequence for Stm32 {
+    fn debug_sleep_enable(&self, memory: &mut Memory) -> Result<(), crate::Error> {
+        let mut cr = DbgmcuCr(memory.read_word_32(DbgmcuCr::ADDRESS)?);
+        cr.set_dbg_sleep(true);
+        cr.set_dbg_stop(true);
+        cr.set_dbg_standby(true);
+
+        memory.write_word_32(DbgmcuCr::ADDRESS, cr.into())?;
+
+        Ok(())
+    }
+
+    fn debug_watchdog_freeze(&self, memory: &mut Memory, freeze: bool) -> Result<(), crate::Error> {
+        let (address, mask) = match self.watchdog_freeze {
+            Some(register) => register,
+            None => {
+                log::debug!("Stopping the watchdogs is not supported for this STM32 family");
+                return Ok(());
+            }
+        };
+
+        let mut value = memory.read_word_32(address)?;
+
+        if freeze {
+            value |= mask;
+        } else {
+            value &= !mask;
+        }
+
+        memory.write_word_32(address, value)?;
+
+        Ok(())
+    }
+}
+
+bitfield! {
+    /// The debug configuration register of the DBGMCU peripheral of the STM32H7.
+    #[derive(Copy, Clone)]
+    pub struct H7DbgmcuCr(u32);
+    impl Debug;
+    /// Enable the debug clock of the D3 domain.
+    pub d3dbgcken, set_d3dbgcken: 22;
+    /// Enable the debug clock of the D1 domain.
+    pub d1dbgcken, set_d1dbgcken: 21;
+    /// Keep the debug clocks of the D2 domain enabled in STANDBY mode.
+    pub dbgstby_d2, set_dbgstby_d2: 5;
+    /// Keep the debug clocks of the D2 domain enabled in STOP mode.
+    pub dbgstop_d2, set_dbgstop_d2: 4;
+    /// Keep the debug clocks of the D2 domain enabled in SLEEP mode.
+    pub dbgsleep_d2, set_dbgsleep_d2: 3;
+    /// Keep the debug clocks of the D1 domain enabled in STANDBY mode.
+    pub dbgstby_d1, set_dbgstby_d1: 2;
+    /// Keep the debug clocks of the D1 domain enabled in STOP mode.
+    pub dbgstop_d1, set_dbgstop_d1: 1;
+    /// Keep the debug clocks of the D1 domain enabled in SLEEP mode.
+    pub dbgsleep_d1, set_dbgsleep_d1: 0;
+}
+
+impl From<u32> for H7DbgmcuCr {
+    fn from(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+impl From<H7DbgmcuCr> for u32 {
+    fn from(value: H7DbgmcuCr) -> Self {
+        value.0
+    }
+}
+
+impl H7DbgmcuCr {
+    /// The address of the register, as seen from the APB-AP of the D3 domain.
+    const ADDRESS: u32 = 0xE00E_1004;
+}
+
+/// The access port to the APB bus of the D3 domain, where the DBGMCU of the STM32H7 is located.
+///
+/// This AP is accessible while the D1 and D2 domains are in a low power mode.
+const H7_APB_D3_AP: u8 = 2;
+
+/// The access port of the Cortex-M4 of the dual-core STM32H7 chips.
+const H7_CM4_AP: u8 = 3;
+
+/// `DBGMCU_APB3FZ1`, `DBGMCU_APB1LFZ1` and `DBGMCU_APB4FZ1` of the STM32H7, with the
+/// bits which stop WWDG1, WWDG2, IWDG1 and IWDG2 while the cores are halted.
+const H7_WATCHDOG_FREEZE: [(u32, u32); 3] = [
+    (0xE00E_1034, 1 << 6),
+    (0xE00E_103C, 1 << 11),
+    (0xE00E_1054, (1 << 18) | (1 << 19)),
+];
+
+/// Time for the debug clocks of a domain to start after they were enabled.
+const H7_DOMAIN_WAKEUP_DELAY: Duration = Duration::from_millis(10);
+
+/// Maximum time for the AP of a core to respond after its domain was woken up.
+const H7_CORE_AP_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// STM32H7 chips, which are split into the D1, D2 and D3 power domains.
+///
+/// The DBGMCU is accessed through the APB-AP of the D3 domain, because the AHB-APs of the cores
+/// are only accessible once the debug clocks of their domains are enabled. On the dual-core
+/// chips, the Cortex-M7 is in the D1 domain and attached first, the Cortex-M4 in the D2 domain.
+pub struct Stm32h7(());
+
+impl Stm32h7 {
+    pub fn create() -> Arc<dyn ArmDebugSequence> {
+        Arc::new(Self(()))
+    }
+
+    /// Modify the DBGMCU register at `address`, through the APB-AP of the D3 domain.
+    fn modify_dbgmcu(
+        memory: &mut Memory,
+        address: u32,
+        modify: impl FnOnce(u32) -> u32,
+    ) -> Result<(), crate::Error> {
+        let dp = memory.get_ap().dp;
+        let interface = memory.get_arm_interface()?;
+        let mut dbgmcu = interface.memory_interface(MemoryAp::new(ApAddress {
+            dp,
+            ap: H7_APB_D3_AP,
+        }))?;
+
+        let value = dbgmcu.read_word_32(address)?;
+        dbgmcu.write_word_32(address, modify(value))?;
+
+        Ok(())
+    }
+}
+
+impl ArmDebugSequence for Stm32h7 {
+    fn debug_device_unlock(
+        &self,
+        memory: &mut Memory,
+        _permissions: &crate::Permissions,
+    ) -> Result<(), crate::Error> {
+        // Wake up the debug system of the D1 and D3 domains, which is
+        // needed to access the cores, even if they are in a low power mode.
+        Self::modify_dbgmcu(memory, H7DbgmcuCr::ADDRESS, |value| {
+            let mut cr = H7DbgmcuCr(value);
+            cr.set_d1dbgcken(true);
+            cr.set_d3dbgcken(true);
+            cr.into()
+        })?;
+
+        thread::sleep(H7_DOMAIN_WAKEUP_DELAY);
+
+        Ok(())
+    }
+
+    fn debug_core_start(&self, core: &mut Memory) -> Result<(), crate::Error> {
+        use crate::architecture::arm::core::armv7m::Dhcsr;
+        use crate::core::CoreRegister;
+
+        // The AP of the Cortex-M4 only responds once the D2 domain is running,
+        // which takes a while after the debug clocks were enabled.
+        let start = Instant::now();
+        let current_dhcsr = loop {
+            match core.read_word_32(Dhcsr::ADDRESS) {
+                Ok(value) => break Dhcsr(value),
+                Err(error) if core.get_ap().ap == H7_CM4_AP => {
+                    if start.elapsed() > H7_CORE_AP_TIMEOUT {
+                        log::warn!("The AP of the Cortex-M4 does not respond: {}", error);
+                        return Err(DebugProbeError::Timeout.into());
+                    }
+
+                    thread::sleep(Duration::from_millis(1));
+                }
+                Err(error) => return Err(error),
+            }
+        };
+
+        if current_dhcsr.c_debugen() {
+            log::debug!("Core is already in debug mode, no need to enable it again");
+            return Ok(());
+        }
+
+        let mut dhcsr = Dhcsr(0);
+        dhcsr.set_c_debugen(true);
+        dhcsr.enable_write();
+
+        core.write_word_32(Dhcsr::ADDRESS, dhcsr.into())?;
+
+        Ok(())
+    }
+
+    fn debug_sleep_enable(&self, memory: &mut Memory) -> Result<(), crate::Error> {
+        Self::modify_dbgmcu(memory, H7DbgmcuCr::ADDRESS, |value| {
+            let mut cr = H7DbgmcuCr(value);
+            cr.set_dbgsleep_d1(true);
+            cr.set_dbgstop_d1(true);
+            cr.set_dbgstby_d1(true);
+            cr.set_dbgsleep_d2(true);
+            cr.set_dbgstop_d2(true);
+            cr.set_dbgstby_d2(true);
+            cr.into()
+        })?;
+
+        // Keeping the D2 domain running also lets the AP of the Cortex-M4 respond.
+        thread::sleep(H7_DOMAIN_WAKEUP_DELAY);
+
+        Ok(())
+    }
+
+    fn debug_watchdog_freeze(&self, memory: &mut Memory, freeze: bool) -> Result<(), crate::Error> {
+        for &(address, mask) in &H7_WATCHDOG_FREEZE {
+            Self::modify_dbgmcu(memory, address, |value| {
+                if freeze {
+                    value | mask
+                } else {
+                    value & !mask
+                }
+            })?;
+        }
+
+        Ok(())
+    }
+}
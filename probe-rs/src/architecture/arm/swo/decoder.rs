@@ -0,0 +1,403 @@
+//! Decoding of the ITM and DWT packets received over SWO.
+//!
+//! The packet format is described in the ARMv7-M architecture reference manual, appendix D4.
+
+use serde::Serialize;
+
+/// The kind of memory access which caused a data trace packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataAccess {
+    /// The value was read.
+    Read,
+    /// The value was written.
+    Write,
+}
+
+/// A packet received over SWO.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SwoPacket {
+    /// A synchronization packet.
+    Sync,
+    /// The ITM could not emit all packets, some packets were lost.
+    Overflow,
+    /// Data written to a stimulus port of the ITM.
+    Instrumentation {
+        /// The number of the stimulus port.
+        port: u8,
+        /// The written data.
+        payload: Vec<u8>,
+    },
+    /// The time since the last local timestamp, in ticks of the timestamp counter.
+    LocalTimestamp {
+        /// The number of ticks.
+        delta: u32,
+    },
+    /// The value of a data access matched by a DWT comparator.
+    DataValue {
+        /// The number of the comparator.
+        comparator: u8,
+        /// The kind of the access.
+        access: DataAccess,
+        /// The accessed value.
+        value: u32,
+        /// The size of the value in bytes.
+        size: u8,
+    },
+    /// The address of the instruction which made a data access matched by a DWT comparator.
+    DataPc {
+        /// The number of the comparator.
+        comparator: u8,
+        /// The address of the instruction.
+        pc: u32,
+    },
+    /// The lower 16 bits of the address of a data access matched by a DWT comparator.
+    DataAddress {
+        /// The number of the comparator.
+        comparator: u8,
+        /// The lower bits of the address.
+        offset: u16,
+    },
+    /// A packet of another hardware source, e.g. exception trace or PC sampling.
+    Hardware {
+        /// The discriminator ID of the source.
+        discriminator: u8,
+        /// The payload of the packet.
+        payload: u32,
+    },
+    /// A protocol packet which is not decoded, e.g. a global timestamp or an extension packet.
+    Other {
+        /// The header of the packet.
+        header: u8,
+    },
+}
+
+/// Decodes the packets from a stream of SWO data.
+///
+/// The data can be split at any point, packets which are not yet complete are kept
+/// until the rest of them is passed to [`SwoDecoder::feed`].
+#[derive(Debug, Default)]
+pub struct SwoDecoder {
+    buffer: Vec<u8>,
+}
+
+impl SwoDecoder {
+    /// Creates a new decoder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decode the packets of `data`.
+    pub fn feed(&mut self, data: &[u8]) -> Vec<SwoPacket> {
+        self.buffer.extend_from_slice(data);
+
+        let mut packets = Vec::new();
+        let mut position = 0;
+
+        while let Some((packet, length)) = parse_packet(&self.buffer[position..]) {
+            position += length;
+
+            if let Some(packet) = packet {
+                packets.push(packet);
+            }
+        }
+
+        self.buffer.drain(..position);
+
+        packets
+    }
+}
+
+/// Parse the packet at the start of `data`, and return it with its length.
+///
+/// Returns `None` if the packet is not complete. The packet is `None` if
+/// the bytes are not a packet, e.g. zeroes which are not followed by
+/// the end of a synchronization packet.
+fn parse_packet(data: &[u8]) -> Option<(Option<SwoPacket>, usize)> {
+    let header = *data.first()?;
+
+    if header & 0b11 != 0 {
+        // A source packet, with a payload of 1, 2 or 4 bytes.
+        let size = match header & 0b11 {
+            0b01 => 1,
+            0b10 => 2,
+            _ => 4,
+        };
+        let payload = data.get(1..=size)?;
+        let id = header >> 3;
+
+        let packet = if header & 0b100 == 0 {
+            SwoPacket::Instrumentation {
+                port: id,
+                payload: payload.to_vec(),
+            }
+        } else {
+            hardware_packet(id, payload)
+        };
+
+        return Some((Some(packet), size + 1));
+    }
+
+    match header {
+        0x00 => {
+            let zeroes = data.iter().take_while(|&&byte| byte == 0).count();
+
+            match data.get(zeroes) {
+                None => None,
+                Some(0x80) => Some((Some(SwoPacket::Sync), zeroes + 1)),
+                Some(_) => Some((None, zeroes)),
+            }
+        }
+        0x70 => Some((Some(SwoPacket::Overflow), 1)),
+        _ if header & 0x0f == 0 => {
+            if header & 0x80 == 0 {
+                // A short timestamp, with the value in the header.
+                let delta = ((header >> 4) & 0b111) as u32;
+                Some((Some(SwoPacket::LocalTimestamp { delta }), 1))
+            } else {
+                let (delta, length) = continuation_value(&data[1..])?;
+                Some((Some(SwoPacket::LocalTimestamp { delta }), length + 1))
+            }
+        }
+        _ => {
+            // Global timestamps and extension packets are followed by continuation bytes.
+            let length = if header & 0x80 != 0 {
+                continuation_value(&data[1..])?.1
+            } else {
+                0
+            };
+
+            Some((Some(SwoPacket::Other { header }), length + 1))
+        }
+    }
+}
+
+/// Decode a value which is split into 7 bit chunks, where the highest bit
+/// of each byte is set if another byte follows.
+fn continuation_value(data: &[u8]) -> Option<(u32, usize)> {
+    let mut value = 0u32;
+
+    for (index, byte) in data.iter().enumerate().take(5) {
+        value |= ((byte & 0x7f) as u32) << (7 * index);
+
+        if byte & 0x80 == 0 {
+            return Some((value, index + 1));
+        }
+    }
+
+    None
+}
+
+fn hardware_packet(discriminator: u8, payload: &[u8]) -> SwoPacket {
+    let mut bytes = [0u8; 4];
+    bytes[..payload.len()].copy_from_slice(payload);
+    let value = u32::from_le_bytes(bytes);
+
+    let comparator = (discriminator >> 1) & 0b11;
+
+    match discriminator {
+        8..=15 if discriminator & 1 == 0 => SwoPacket::DataPc {
+            comparator,
+            pc: value,
+        },
+        8..=15 => SwoPacket::DataAddress {
+            comparator,
+            offset: value as u16,
+        },
+        16..=23 => SwoPacket::DataValue {
+            comparator,
+            access: if discriminator & 1 == 0 {
+                DataAccess::Read
+            } else {
+                DataAccess::Write
+            },
+            value,
+            size: payload.len() as u8,
+        },
+        _ => SwoPacket::Hardware {
+            discriminator,
+            payload: value,
+        },
+    }
+}
+
+/// A data value traced by a DWT comparator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataTraceEvent {
+    /// The sum of the local timestamps received before the value, in ticks of the timestamp counter.
+    pub timestamp: u64,
+    /// The number of the comparator.
+    pub comparator: u8,
+    /// The kind of the access.
+    pub access: DataAccess,
+    /// The accessed value.
+    pub value: u32,
+}
+
+/// Extracts the values traced by DWT comparators from a stream of SWO data.
+///
+/// This is used with [`Session::read_swv_data_trace`](crate::Session::read_swv_data_trace)
+/// to follow changes of variables without halting the core.
+#[derive(Debug, Default)]
+pub struct DataTraceDecoder {
+    decoder: SwoDecoder,
+    timestamp: u64,
+}
+
+impl DataTraceDecoder {
+    /// Creates a new decoder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decode the traced values of `data`.
+    pub fn feed(&mut self, data: &[u8]) -> Vec<DataTraceEvent> {
+        let mut events = Vec::new();
+
+        for packet in self.decoder.feed(data) {
+            match packet {
+                SwoPacket::LocalTimestamp { delta } => self.timestamp += delta as u64,
+                SwoPacket::DataValue {
+                    comparator,
+                    access,
+                    value,
+                    ..
+                } => events.push(DataTraceEvent {
+                    timestamp: self.timestamp,
+                    comparator,
+                    access,
+                    value,
+                }),
+                _ => {}
+            }
+        }
+
+        events
+    }
+}
+
+/// A decoder for the data received with a [`SwoReader`](super::SwoReader).
+pub trait SwoDecode {
+    /// The decoded items.
+    type Item;
+
+    /// Decode the items of `data`, which directly follows the previously fed data.
+    fn feed(&mut self, data: &[u8]) -> Vec<Self::Item>;
+
+    /// Discard the state of incomplete items, because data was lost.
+    fn reset(&mut self);
+}
+
+impl SwoDecode for SwoDecoder {
+    type Item = SwoPacket;
+
+    fn feed(&mut self, data: &[u8]) -> Vec<SwoPacket> {
+        SwoDecoder::feed(self, data)
+    }
+
+    fn reset(&mut self) {
+        *self = SwoDecoder::new();
+    }
+}
+
+impl SwoDecode for DataTraceDecoder {
+    type Item = DataTraceEvent;
+
+    fn feed(&mut self, data: &[u8]) -> Vec<DataTraceEvent> {
+        DataTraceDecoder::feed(self, data)
+    }
+
+    fn reset(&mut self) {
+        // The timestamps of the lost packets are lost as well, so the timestamp
+        // is only kept as a lower bound.
+        self.decoder = SwoDecoder::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DataAccess, DataTraceDecoder, DataTraceEvent, SwoDecoder, SwoPacket};
+
+    #[test]
+    fn decode_packets() {
+        let mut decoder = SwoDecoder::new();
+
+        let data = [
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x80, // Sync
+            0x01, b'a', // Instrumentation on port 0
+            0x9f, 0x10, 0x20, 0x00, 0x08, // Write of a word, comparator 1
+            0x70, // Overflow
+        ];
+
+        assert_eq!(
+            decoder.feed(&data),
+            vec![
+                SwoPacket::Sync,
+                SwoPacket::Instrumentation {
+                    port: 0,
+                    payload: vec![b'a']
+                },
+                SwoPacket::DataValue {
+                    comparator: 1,
+                    access: DataAccess::Write,
+                    value: 0x0800_2010,
+                    size: 4,
+                },
+                SwoPacket::Overflow,
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_split_packets() {
+        let mut decoder = SwoDecoder::new();
+
+        // A data PC packet of comparator 0, split over two reads.
+        assert!(decoder.feed(&[0x47, 0x34, 0x12]).is_empty());
+        assert_eq!(
+            decoder.feed(&[0x00, 0x08]),
+            vec![SwoPacket::DataPc {
+                comparator: 0,
+                pc: 0x0800_1234
+            }]
+        );
+
+        // A local timestamp with continuation bytes, split after the header.
+        assert_eq!(decoder.feed(&[0x01, 0x41, 0xc0]).len(), 1);
+        assert_eq!(
+            decoder.feed(&[0x81, 0x01]),
+            vec![SwoPacket::LocalTimestamp { delta: 0x81 }]
+        );
+    }
+
+    #[test]
+    fn trace_data_values() {
+        let mut decoder = DataTraceDecoder::new();
+
+        let data = [
+            0x85, 0x2a, // Read of a byte, comparator 0
+            0x30, // Local timestamp of 3 ticks
+            0x02, 0x01, 0x02, // Instrumentation packet, ignored
+            0x86, 0x34, 0x12, // Read of a halfword, comparator 0
+        ];
+
+        assert_eq!(
+            decoder.feed(&data),
+            vec![
+                DataTraceEvent {
+                    timestamp: 0,
+                    comparator: 0,
+                    access: DataAccess::Read,
+                    value: 0x2a,
+                },
+                DataTraceEvent {
+                    timestamp: 3,
+                    comparator: 0,
+                    access: DataAccess::Read,
+                    value: 0x1234,
+                },
+            ]
+        );
+    }
+}
@@ -1,5 +1,13 @@
+mod decoder;
+mod reader;
+mod server;
+
 use crate::Error;
 
+pub use decoder::{DataAccess, DataTraceDecoder, DataTraceEvent, SwoDecode, SwoDecoder, SwoPacket};
+pub use reader::{DataLoss, SwoEvent, SwoReader, SwoStatistics};
+pub use server::{SwvFraming, SwvServer};
+
 #[derive(Debug, Copy, Clone)]
 pub enum SwoMode {
     Uart,
@@ -129,6 +137,14 @@ pub trait SwoAccess {
     fn swo_buffer_size(&mut self) -> Option<usize> {
         None
     }
+
+    /// The number of times the SWO buffer of the probe overflowed since SWO was enabled,
+    /// or `None` if the probe does not report overflows.
+    ///
+    /// Data was lost at each overflow, see [`SwoReader`].
+    fn swo_overflows(&mut self) -> Option<u64> {
+        None
+    }
 }
 
 /// Helper function to compute a poll interval from a SwoConfig and SWO buffer size.
@@ -0,0 +1,269 @@
+use std::collections::VecDeque;
+
+use super::{SwoAccess, SwoDecode};
+use crate::Error;
+
+/// The default number of bytes a [`SwoReader`] buffers before it drops data.
+const DEFAULT_CAPACITY: usize = 64 * 1024;
+
+/// SWO data which was lost before the following items.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DataLoss {
+    /// Bytes which were received, but dropped because the buffer of the reader was full.
+    pub dropped_bytes: usize,
+    /// The SWO buffer of the probe overflowed, so an unknown number of bytes was lost.
+    pub probe_overflow: bool,
+}
+
+/// An item of [`SwoReader::decode`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum SwoEvent<T> {
+    /// A decoded item.
+    Item(T),
+    /// Data was lost here, so items may be missing.
+    ///
+    /// Items which could not be decoded are reported by the decoder itself, so this
+    /// distinguishes lost data from corrupted data.
+    DataLost(DataLoss),
+}
+
+/// Counters of a [`SwoReader`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SwoStatistics {
+    /// All bytes received from the probe.
+    pub received_bytes: u64,
+    /// Bytes which were dropped because the buffer of the reader was full.
+    pub dropped_bytes: u64,
+    /// The number of overflows of the SWO buffer of the probe.
+    pub probe_overflows: u64,
+}
+
+/// A chunk of received data, and the data lost before it.
+#[derive(Debug)]
+struct Chunk {
+    data: Vec<u8>,
+    loss: Option<DataLoss>,
+}
+
+/// Reads SWO data from a probe into a buffer, and decodes it on demand.
+///
+/// [`poll`](Self::poll) has to be called often enough to drain the buffer of the probe,
+/// while [`decode`](Self::decode) can be called whenever the consumer is ready. If the
+/// consumer falls behind, the oldest data is dropped once the buffer is full, so the
+/// memory use is bounded. Dropped data and overflows of the buffer of the probe are
+/// reported as [`SwoEvent::DataLost`] at the position of the loss, and counted in the
+/// [`statistics`](Self::statistics).
+///
+/// ```no_run
+/// use probe_rs::architecture::arm::{SwoDecoder, SwoEvent, SwoReader};
+/// # fn read(session: &mut probe_rs::Session) -> Result<(), probe_rs::Error> {
+/// let mut reader = SwoReader::new(SwoDecoder::new());
+///
+/// loop {
+///     reader.poll(session.get_arm_interface()?)?;
+///
+///     for event in reader.decode() {
+///         match event {
+///             SwoEvent::Item(packet) => println!("{:?}", packet),
+///             SwoEvent::DataLost(loss) => println!("Lost data: {:?}", loss),
+///         }
+///     }
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SwoReader<D: SwoDecode> {
+    decoder: D,
+    chunks: VecDeque<Chunk>,
+    buffered: usize,
+    capacity: usize,
+    statistics: SwoStatistics,
+    /// The last overflow count reported by the probe.
+    probe_overflows: Option<u64>,
+}
+
+impl<D: SwoDecode> SwoReader<D> {
+    /// Create a reader which decodes the data with `decoder`, and buffers up to 64 KiB.
+    pub fn new(decoder: D) -> Self {
+        Self {
+            decoder,
+            chunks: VecDeque::new(),
+            buffered: 0,
+            capacity: DEFAULT_CAPACITY,
+            statistics: SwoStatistics::default(),
+            probe_overflows: None,
+        }
+    }
+
+    /// Set the number of bytes which are buffered before the oldest data is dropped.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// The decoder of the reader.
+    pub fn decoder(&self) -> &D {
+        &self.decoder
+    }
+
+    /// The counters of the received and lost data.
+    pub fn statistics(&self) -> SwoStatistics {
+        self.statistics
+    }
+
+    /// The number of bytes which are buffered, but not decoded yet.
+    pub fn buffered(&self) -> usize {
+        self.buffered
+    }
+
+    /// Read the available data from `swo` into the buffer, and return the number of
+    /// received bytes.
+    ///
+    /// This is used with an ARM interface, e.g. from
+    /// [`Session::get_arm_interface`](crate::Session::get_arm_interface).
+    pub fn poll<S: SwoAccess + ?Sized>(&mut self, swo: &mut S) -> Result<usize, Error> {
+        let data = swo.read_swo()?;
+
+        let probe_overflow = match (swo.swo_overflows(), self.probe_overflows) {
+            (Some(overflows), Some(previous)) => {
+                self.probe_overflows = Some(overflows);
+                overflows.saturating_sub(previous)
+            }
+            (overflows, _) => {
+                self.probe_overflows = overflows;
+                0
+            }
+        };
+
+        let length = data.len();
+        self.push(data, probe_overflow);
+
+        Ok(length)
+    }
+
+    /// Add received data to the buffer, after `probe_overflows` overflows of the probe.
+    fn push(&mut self, data: Vec<u8>, probe_overflows: u64) {
+        self.statistics.received_bytes += data.len() as u64;
+        self.statistics.probe_overflows += probe_overflows;
+
+        if data.is_empty() && probe_overflows == 0 {
+            return;
+        }
+
+        self.buffered += data.len();
+        self.chunks.push_back(Chunk {
+            data,
+            loss: (probe_overflows > 0).then_some(DataLoss {
+                dropped_bytes: 0,
+                probe_overflow: true,
+            }),
+        });
+
+        self.drop_excess();
+    }
+
+    /// Drop the oldest data until the buffered data fits into the capacity.
+    fn drop_excess(&mut self) {
+        while self.buffered > self.capacity {
+            let excess = self.buffered - self.capacity;
+            let has_next = self.chunks.len() > 1;
+            let chunk = self.chunks.front_mut().unwrap();
+
+            let dropped = excess.min(chunk.data.len());
+            chunk.data.drain(..dropped);
+
+            let mut loss = chunk.loss.take().unwrap_or_default();
+            loss.dropped_bytes += dropped;
+
+            self.buffered -= dropped;
+            self.statistics.dropped_bytes += dropped as u64;
+
+            if chunk.data.is_empty() && has_next {
+                // Report the loss before the next chunk.
+                self.chunks.pop_front();
+
+                let next = self.chunks.front_mut().unwrap();
+                let next_loss = next.loss.get_or_insert_with(DataLoss::default);
+                next_loss.dropped_bytes += loss.dropped_bytes;
+                next_loss.probe_overflow |= loss.probe_overflow;
+            } else {
+                chunk.loss = Some(loss);
+            }
+        }
+    }
+
+    /// Decode all buffered data.
+    pub fn decode(&mut self) -> Vec<SwoEvent<D::Item>> {
+        let mut events = Vec::new();
+
+        for chunk in self.chunks.drain(..) {
+            if let Some(loss) = chunk.loss {
+                self.decoder.reset();
+                events.push(SwoEvent::DataLost(loss));
+            }
+
+            events.extend(
+                self.decoder
+                    .feed(&chunk.data)
+                    .into_iter()
+                    .map(SwoEvent::Item),
+            );
+        }
+
+        self.buffered = 0;
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DataLoss, SwoDecode, SwoEvent, SwoReader};
+
+    /// Decodes every byte as an item.
+    struct Bytes;
+
+    impl SwoDecode for Bytes {
+        type Item = u8;
+
+        fn feed(&mut self, data: &[u8]) -> Vec<u8> {
+            data.to_vec()
+        }
+
+        fn reset(&mut self) {}
+    }
+
+    #[test]
+    fn drop_oldest_data() {
+        let mut reader = SwoReader::new(Bytes).with_capacity(4);
+
+        reader.push(vec![1, 2, 3], 0);
+        reader.push(vec![4, 5, 6], 0);
+        reader.push(vec![], 1);
+        reader.push(vec![7], 0);
+
+        assert_eq!(reader.buffered(), 4);
+        assert_eq!(
+            reader.decode(),
+            vec![
+                SwoEvent::DataLost(DataLoss {
+                    dropped_bytes: 3,
+                    probe_overflow: false
+                }),
+                SwoEvent::Item(4),
+                SwoEvent::Item(5),
+                SwoEvent::Item(6),
+                SwoEvent::DataLost(DataLoss {
+                    dropped_bytes: 0,
+                    probe_overflow: true
+                }),
+                SwoEvent::Item(7),
+            ]
+        );
+
+        let statistics = reader.statistics();
+        assert_eq!(statistics.received_bytes, 7);
+        assert_eq!(statistics.dropped_bytes, 3);
+        assert_eq!(statistics.probe_overflows, 1);
+    }
+}
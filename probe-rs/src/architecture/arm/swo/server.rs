@@ -0,0 +1,339 @@
+//! A TCP server which streams the decoded SWV data to its clients.
+//!
+//! SWV viewers and dashboards can connect to the server to receive the ITM and DWT packets
+//! of the target, without linking probe-rs themselves. The server doesn't own a thread: the
+//! data is read with a [`SwoReader`](super::SwoReader), and the decoded events are passed
+//! to [`SwvServer::publish`].
+
+use std::io::{self, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+
+use serde::Serialize;
+
+use super::{DataAccess, DataLoss, SwoEvent, SwoPacket};
+
+/// The maximum number of bytes which are buffered for a client, before the client is
+/// disconnected because it can't keep up with the trace data.
+const MAX_PENDING: usize = 1024 * 1024;
+
+/// How the events are sent to the clients of a [`SwvServer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwvFraming {
+    /// One JSON object per line, with the kind of the event in the `type` field, e.g.
+    /// `{"type":"instrumentation","port":0,"payload":[104,105]}`.
+    ///
+    /// Lost data is sent as `{"type":"data_lost","dropped_bytes":..,"probe_overflow":..}`.
+    JsonLines,
+    /// One frame per event, made of the kind of the event as a byte, the length of the
+    /// payload as a little endian `u16`, and the payload.
+    ///
+    /// The kinds of the frames and their payloads are, with little endian numbers:
+    ///
+    /// | Kind | Event             | Payload                                                     |
+    /// |------|-------------------|-------------------------------------------------------------|
+    /// | 0    | `Instrumentation` | port (`u8`), data                                           |
+    /// | 1    | `LocalTimestamp`  | delta (`u32`)                                               |
+    /// | 2    | `DataValue`       | comparator (`u8`), write (`u8`), size (`u8`), value (`u32`) |
+    /// | 3    | `DataPc`          | comparator (`u8`), pc (`u32`)                               |
+    /// | 4    | `DataAddress`     | comparator (`u8`), offset (`u16`)                           |
+    /// | 5    | `Hardware`        | discriminator (`u8`), payload (`u32`)                       |
+    /// | 6    | `Overflow`        | none                                                        |
+    /// | 7    | `Other`           | header (`u8`)                                               |
+    /// | 8    | data lost         | dropped bytes (`u32`), probe overflow (`u8`)                |
+    Binary,
+}
+
+/// A client of a [`SwvServer`].
+#[derive(Debug)]
+struct Client {
+    stream: TcpStream,
+    address: SocketAddr,
+    /// The data which could not be sent yet.
+    pending: Vec<u8>,
+}
+
+impl Client {
+    /// Queue `data`, and send as much of it as possible without blocking.
+    ///
+    /// Returns `false` if the client is disconnected, or fell too far behind.
+    fn send(&mut self, data: &[u8]) -> bool {
+        self.pending.extend_from_slice(data);
+
+        if let Err(e) = self.flush() {
+            log::info!("SWV client {} disconnected: {}", self.address, e);
+            return false;
+        }
+
+        if self.pending.len() > MAX_PENDING {
+            log::warn!(
+                "Disconnecting SWV client {}, it can't keep up with the trace data",
+                self.address
+            );
+            return false;
+        }
+
+        true
+    }
+
+    /// Send as much of the pending data as possible without blocking.
+    fn flush(&mut self) -> io::Result<()> {
+        while !self.pending.is_empty() {
+            match self.stream.write(&self.pending) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => {
+                    self.pending.drain(..n);
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A TCP server which streams decoded SWV events to all connected clients.
+///
+/// ```no_run
+/// use probe_rs::architecture::arm::{SwoDecoder, SwoReader, SwvFraming, SwvServer};
+/// # fn serve(session: &mut probe_rs::Session) -> Result<(), Box<dyn std::error::Error>> {
+/// let mut server = SwvServer::bind("127.0.0.1:3443", SwvFraming::JsonLines)?;
+/// let mut reader = SwoReader::new(SwoDecoder::new());
+///
+/// loop {
+///     reader.poll(session.get_arm_interface()?)?;
+///     server.publish(&reader.decode())?;
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SwvServer {
+    listener: TcpListener,
+    framing: SwvFraming,
+    clients: Vec<Client>,
+}
+
+impl SwvServer {
+    /// Listen for clients on `address`.
+    pub fn bind(address: impl ToSocketAddrs, framing: SwvFraming) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Self {
+            listener,
+            framing,
+            clients: Vec::new(),
+        })
+    }
+
+    /// The address the server listens on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// The number of connected clients.
+    pub fn clients(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Accept new clients, and send `events` to all clients.
+    ///
+    /// This never blocks. Data which can't be sent right away is buffered, and clients which
+    /// are disconnected or fall too far behind are dropped. Sync packets are not sent.
+    pub fn publish(&mut self, events: &[SwoEvent<SwoPacket>]) -> io::Result<()> {
+        self.accept()?;
+
+        let mut data = Vec::new();
+        for event in events {
+            match self.framing {
+                SwvFraming::JsonLines => encode_json(event, &mut data),
+                SwvFraming::Binary => encode_binary(event, &mut data),
+            }
+        }
+
+        let mut index = 0;
+        while index < self.clients.len() {
+            if self.clients[index].send(&data) {
+                index += 1;
+            } else {
+                self.clients.swap_remove(index);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Accept all pending clients.
+    fn accept(&mut self) -> io::Result<()> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, address)) => {
+                    stream.set_nonblocking(true)?;
+                    log::info!("Accepted SWV client {}", address);
+
+                    self.clients.push(Client {
+                        stream,
+                        address,
+                        pending: Vec::new(),
+                    });
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// The lost data in the JSON format, see [`SwvFraming::JsonLines`].
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum JsonLoss {
+    DataLost {
+        dropped_bytes: usize,
+        probe_overflow: bool,
+    },
+}
+
+/// Append `event` as a line of JSON to `data`, see [`SwvFraming::JsonLines`].
+fn encode_json(event: &SwoEvent<SwoPacket>, data: &mut Vec<u8>) {
+    // note(unwrap): The events only contain numbers and byte arrays, which can always
+    // be serialized.
+    match event {
+        SwoEvent::Item(SwoPacket::Sync) => return,
+        SwoEvent::Item(packet) => serde_json::to_writer(&mut *data, packet).unwrap(),
+        SwoEvent::DataLost(DataLoss {
+            dropped_bytes,
+            probe_overflow,
+        }) => {
+            let loss = JsonLoss::DataLost {
+                dropped_bytes: *dropped_bytes,
+                probe_overflow: *probe_overflow,
+            };
+            serde_json::to_writer(&mut *data, &loss).unwrap()
+        }
+    }
+
+    data.push(b'\n');
+}
+
+/// Append `event` as a frame to `data`, see [`SwvFraming::Binary`].
+fn encode_binary(event: &SwoEvent<SwoPacket>, data: &mut Vec<u8>) {
+    let mut payload = Vec::new();
+
+    let kind = match event {
+        SwoEvent::Item(packet) => match packet {
+            SwoPacket::Sync => return,
+            SwoPacket::Instrumentation {
+                port,
+                payload: stimulus,
+            } => {
+                payload.push(*port);
+                payload.extend_from_slice(stimulus);
+                0
+            }
+            SwoPacket::LocalTimestamp { delta } => {
+                payload.extend_from_slice(&delta.to_le_bytes());
+                1
+            }
+            SwoPacket::DataValue {
+                comparator,
+                access,
+                value,
+                size,
+            } => {
+                payload.push(*comparator);
+                payload.push((*access == DataAccess::Write) as u8);
+                payload.push(*size);
+                payload.extend_from_slice(&value.to_le_bytes());
+                2
+            }
+            SwoPacket::DataPc { comparator, pc } => {
+                payload.push(*comparator);
+                payload.extend_from_slice(&pc.to_le_bytes());
+                3
+            }
+            SwoPacket::DataAddress { comparator, offset } => {
+                payload.push(*comparator);
+                payload.extend_from_slice(&offset.to_le_bytes());
+                4
+            }
+            SwoPacket::Hardware {
+                discriminator,
+                payload: value,
+            } => {
+                payload.push(*discriminator);
+                payload.extend_from_slice(&value.to_le_bytes());
+                5
+            }
+            SwoPacket::Overflow => 6,
+            SwoPacket::Other { header } => {
+                payload.push(*header);
+                7
+            }
+        },
+        SwoEvent::DataLost(loss) => {
+            let dropped_bytes = loss.dropped_bytes.min(u32::MAX as usize) as u32;
+            payload.extend_from_slice(&dropped_bytes.to_le_bytes());
+            payload.push(loss.probe_overflow as u8);
+            8
+        }
+    };
+
+    data.push(kind);
+    data.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+    data.extend_from_slice(&payload);
+}
+
+#[cfg(test)]
+mod test {
+    use super::{encode_binary, encode_json};
+    use crate::architecture::arm::swo::{DataAccess, DataLoss, SwoEvent, SwoPacket};
+
+    #[test]
+    fn json_lines() {
+        let mut data = Vec::new();
+
+        encode_json(&SwoEvent::Item(SwoPacket::Sync), &mut data);
+        encode_json(
+            &SwoEvent::Item(SwoPacket::Instrumentation {
+                port: 1,
+                payload: b"hi".to_vec(),
+            }),
+            &mut data,
+        );
+        encode_json(
+            &SwoEvent::DataLost(DataLoss {
+                dropped_bytes: 16,
+                probe_overflow: false,
+            }),
+            &mut data,
+        );
+
+        assert_eq!(
+            String::from_utf8(data).unwrap(),
+            "{\"type\":\"instrumentation\",\"port\":1,\"payload\":[104,105]}\n\
+             {\"type\":\"data_lost\",\"dropped_bytes\":16,\"probe_overflow\":false}\n"
+        );
+    }
+
+    #[test]
+    fn binary_frames() {
+        let mut data = Vec::new();
+
+        encode_binary(
+            &SwoEvent::Item(SwoPacket::DataValue {
+                comparator: 1,
+                access: DataAccess::Write,
+                value: 0x0800_2010,
+                size: 4,
+            }),
+            &mut data,
+        );
+        encode_binary(&SwoEvent::Item(SwoPacket::Overflow), &mut data);
+
+        assert_eq!(
+            data,
+            vec![2, 7, 0, 1, 1, 4, 0x10, 0x20, 0x00, 0x08, 6, 0, 0]
+        );
+    }
+}
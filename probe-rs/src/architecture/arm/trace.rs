@@ -0,0 +1,306 @@
+//! Decoding of instruction trace data.
+//!
+//! The Micro Trace Buffer (MTB) of ARMv6-M and ARMv8-M Baseline cores records a packet
+//! for every non-sequential change of the program counter into the RAM of the target.
+//! [`decode_mtb_buffer`] extracts these packets from a copy of the trace buffer, and
+//! [`reconstruct_flow`] turns them into the ranges of instructions which were executed,
+//! which shows how the core reached its current position.
+//!
+//! Trace data of an ETM captured in an ETB is wrapped in formatter frames, which can be
+//! removed with [`deformat`].
+
+/// A trace packet of the MTB, which records a non-sequential change of the program counter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MtbPacket {
+    /// The address of the branch instruction.
+    pub source: u32,
+    /// The address of the instruction which was executed after the branch.
+    pub destination: u32,
+    /// The branch was caused by an exception entry or return.
+    pub exception: bool,
+    /// This is the first packet after the trace was started.
+    pub start: bool,
+}
+
+impl MtbPacket {
+    /// Parse a packet from the two words it is stored as.
+    pub fn from_words(source: u32, destination: u32) -> Self {
+        Self {
+            source: source & !1,
+            destination: destination & !1,
+            exception: source & 1 != 0,
+            start: destination & 1 != 0,
+        }
+    }
+}
+
+/// Extract the packets from a copy of the MTB trace buffer, from the oldest to the newest.
+///
+/// `position` is the offset of the next packet the MTB writes into the buffer, and `wrapped`
+/// is set if the MTB already wrote beyond the end of the buffer, so that the oldest packets
+/// start at `position`.
+pub fn decode_mtb_buffer(buffer: &[u8], position: usize, wrapped: bool) -> Vec<MtbPacket> {
+    let packets: Vec<MtbPacket> = buffer
+        .chunks_exact(8)
+        .map(|packet| {
+            let source = u32::from_le_bytes([packet[0], packet[1], packet[2], packet[3]]);
+            let destination = u32::from_le_bytes([packet[4], packet[5], packet[6], packet[7]]);
+            MtbPacket::from_words(source, destination)
+        })
+        .collect();
+
+    let next = (position / 8).min(packets.len());
+
+    if wrapped {
+        packets[next..]
+            .iter()
+            .chain(&packets[..next])
+            .copied()
+            .collect()
+    } else {
+        packets[..next].to_vec()
+    }
+}
+
+/// How the execution of an [`ExecutedRange`] was started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RangeEntry {
+    /// A branch from the end of the previous range.
+    Branch,
+    /// An exception entry or return at the end of the previous range.
+    Exception,
+    /// The trace was started, the previous range is not related to this one.
+    TraceStart,
+}
+
+/// A range of instructions which were executed sequentially.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecutedRange {
+    /// The address of the first executed instruction.
+    pub start: u32,
+    /// The address of the last executed instruction, which is usually a branch.
+    pub end: u32,
+    /// How the execution of the range was started.
+    pub entry: RangeEntry,
+}
+
+/// Reconstruct the executed instructions from MTB packets, from the oldest to the newest.
+///
+/// Between two branches, the core executes the instructions from the destination of the
+/// first branch up to the source of the next one. The last range ends at `pc`, the current
+/// position of the core, if it is known.
+///
+/// The ranges before the first packet, and before a packet which starts the trace again,
+/// are not known, so they are not included.
+pub fn reconstruct_flow(packets: &[MtbPacket], pc: Option<u32>) -> Vec<ExecutedRange> {
+    let mut ranges = Vec::new();
+
+    // The start of the current range, and how it was entered.
+    let mut current: Option<(u32, RangeEntry)> = None;
+
+    for packet in packets {
+        let entry = if packet.start {
+            RangeEntry::TraceStart
+        } else {
+            if let Some((start, entry)) = current {
+                ranges.push(ExecutedRange {
+                    start,
+                    end: packet.source,
+                    entry,
+                });
+            }
+
+            if packet.exception {
+                RangeEntry::Exception
+            } else {
+                RangeEntry::Branch
+            }
+        };
+
+        current = Some((packet.destination, entry));
+    }
+
+    if let (Some((start, entry)), Some(pc)) = (current, pc) {
+        ranges.push(ExecutedRange {
+            start,
+            end: pc,
+            entry,
+        });
+    }
+
+    ranges
+}
+
+/// The trace source ID which marks unused bytes of a formatter frame.
+const NULL_TRACE_ID: u8 = 0x00;
+
+/// Extract the data of the trace source `id` from CoreSight formatter frames.
+///
+/// The formatter interleaves the data of several trace sources into frames of 16 bytes,
+/// which are e.g. stored by an ETB. An incomplete frame at the end is ignored.
+pub fn deformat(frames: &[u8], id: u8) -> Vec<u8> {
+    let mut data = Vec::new();
+
+    let mut current = NULL_TRACE_ID;
+
+    for frame in frames.chunks_exact(16) {
+        let auxiliary = frame[15];
+
+        for pair in 0..8 {
+            let even = frame[2 * pair];
+            let odd = frame.get(2 * pair + 1).filter(|_| pair < 7).copied();
+            let auxiliary_bit = (auxiliary >> pair) & 1;
+
+            let mut push = |source: u8, byte: u8| {
+                if source == id {
+                    data.push(byte);
+                }
+            };
+
+            if even & 1 != 0 {
+                // An ID change. The auxiliary bit tells if the following byte
+                // still belongs to the previous source.
+                let new_id = even >> 1;
+
+                if auxiliary_bit == 0 {
+                    current = new_id;
+                }
+                if let Some(odd) = odd {
+                    push(current, odd);
+                }
+                current = new_id;
+            } else {
+                // A data byte, where the lowest bit is stored in the auxiliary byte.
+                push(current, even | auxiliary_bit);
+                if let Some(odd) = odd {
+                    push(current, odd);
+                }
+            }
+        }
+    }
+
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        decode_mtb_buffer, deformat, reconstruct_flow, ExecutedRange, MtbPacket, RangeEntry,
+    };
+
+    fn buffer(packets: &[(u32, u32)]) -> Vec<u8> {
+        packets
+            .iter()
+            .flat_map(|(source, destination)| {
+                source
+                    .to_le_bytes()
+                    .iter()
+                    .chain(destination.to_le_bytes().iter())
+                    .copied()
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn decode_wrapped_buffer() {
+        let data = buffer(&[(0x300, 0x400), (0x100, 0x201), (0x220, 0x280)]);
+
+        // The next packet is written at the second slot, so the second packet is the oldest.
+        let packets = decode_mtb_buffer(&data, 8, true);
+
+        assert_eq!(
+            packets,
+            vec![
+                MtbPacket {
+                    source: 0x100,
+                    destination: 0x200,
+                    exception: false,
+                    start: true,
+                },
+                MtbPacket {
+                    source: 0x220,
+                    destination: 0x280,
+                    exception: false,
+                    start: false,
+                },
+                MtbPacket {
+                    source: 0x300,
+                    destination: 0x400,
+                    exception: false,
+                    start: false,
+                },
+            ]
+        );
+
+        assert_eq!(decode_mtb_buffer(&data, 16, false).len(), 2);
+    }
+
+    #[test]
+    fn reconstruct_execution() {
+        let packets = [
+            MtbPacket::from_words(0x100, 0x201),
+            MtbPacket::from_words(0x220, 0x280),
+            // An interrupt at 0x290.
+            MtbPacket::from_words(0x291, 0x500),
+        ];
+
+        assert_eq!(
+            reconstruct_flow(&packets, Some(0x510)),
+            vec![
+                ExecutedRange {
+                    start: 0x200,
+                    end: 0x220,
+                    entry: RangeEntry::TraceStart,
+                },
+                ExecutedRange {
+                    start: 0x280,
+                    end: 0x290,
+                    entry: RangeEntry::Branch,
+                },
+                ExecutedRange {
+                    start: 0x500,
+                    end: 0x510,
+                    entry: RangeEntry::Exception,
+                },
+            ]
+        );
+
+        assert_eq!(reconstruct_flow(&packets, None).len(), 2);
+    }
+
+    #[test]
+    fn deformat_frames() {
+        let frame = [
+            // ID change to 0x10, the following byte belongs to the new source.
+            0x21,
+            0xaa, //
+            // A data byte with the lowest bit in the auxiliary byte.
+            0x02,
+            0xbb, //
+            // ID change to 0x11, the following byte still belongs to 0x10.
+            0x23,
+            0xcc, //
+            0x04,
+            0xdd, //
+            0x06,
+            0xee, //
+            0x08,
+            0xff, //
+            // ID change back to 0x10.
+            0x21,
+            0x12, //
+            0x34, // The last data byte, without an odd byte.
+            0b0000_0110,
+        ];
+
+        assert_eq!(
+            deformat(&frame, 0x10),
+            vec![0xaa, 0x03, 0xbb, 0xcc, 0x12, 0x34]
+        );
+        assert_eq!(
+            deformat(&frame, 0x11),
+            vec![0x04, 0xdd, 0x06, 0xee, 0x08, 0xff]
+        );
+    }
+}
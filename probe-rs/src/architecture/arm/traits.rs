@@ -37,6 +37,45 @@ pub struct ApAddress {
     pub ap: u8,
 }
 
+/// A read or write of a DAP register, which is executed as part of a batch.
+///
+/// See [`RawDapAccess::raw_transfer_batch`] and [`DapAccess::transfer_raw_ap_batch`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DapTransfer {
+    /// Read the register at `addr`. The value is stored in `value` once the batch was executed.
+    Read { addr: u8, value: u32 },
+    /// Write `value` to the register at `addr`.
+    Write { addr: u8, value: u32 },
+}
+
+impl DapTransfer {
+    /// A read of the register at `addr`.
+    pub fn read(addr: u8) -> Self {
+        DapTransfer::Read { addr, value: 0 }
+    }
+
+    /// A write of `value` to the register at `addr`.
+    pub fn write(addr: u8, value: u32) -> Self {
+        DapTransfer::Write { addr, value }
+    }
+
+    /// The address of the register.
+    pub fn addr(&self) -> u8 {
+        match *self {
+            DapTransfer::Read { addr, .. } => addr,
+            DapTransfer::Write { addr, .. } => addr,
+        }
+    }
+
+    /// The value which was read, or the value which is written.
+    pub fn value(&self) -> u32 {
+        match *self {
+            DapTransfer::Read { value, .. } => value,
+            DapTransfer::Write { value, .. } => value,
+        }
+    }
+}
+
 /// Low-level DAP register access.
 ///
 /// Operations on this trait closely match the transactions on the wire. Implementors
@@ -100,6 +139,31 @@ pub trait RawDapAccess {
         Ok(())
     }
 
+    /// Execute a batch of reads and writes of registers of the same port.
+    ///
+    /// Probes which can queue transfers execute the whole batch in as few round trips
+    /// as possible, otherwise the transfers are executed one after another.
+    ///
+    /// Only the lowest 4 bits of the addresses are used. Bank switching is the caller's responsibility.
+    fn raw_transfer_batch(
+        &mut self,
+        port: PortType,
+        transfers: &mut [DapTransfer],
+    ) -> Result<(), DebugProbeError> {
+        for transfer in transfers {
+            match transfer {
+                DapTransfer::Read { addr, value } => {
+                    *value = self.raw_read_register(port, *addr)?;
+                }
+                DapTransfer::Write { addr, value } => {
+                    self.raw_write_register(port, *addr, *value)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     /// Flush any outstanding writes.
     ///
     /// By default, this does nothing -- but in probes that implement write
@@ -108,29 +172,6 @@ pub trait RawDapAccess {
         Ok(())
     }
 
-    /// Send a specific output sequence over JTAG or SWD.
-    ///
-    /// This can only be used for output, and should be used to generate
-    /// the initial reset sequence, for example.
-    fn swj_sequence(&mut self, bit_len: u8, bits: u64) -> Result<(), DebugProbeError>;
-
-    /// Set the state of debugger output pins directly.
-    ///
-    /// The bits have the following meaning:
-    ///
-    /// Bit 0: SWCLK/TCK
-    /// Bit 1: SWDIO/TMS
-    /// Bit 2: TDI
-    /// Bit 3: TDO
-    /// Bit 5: nTRST
-    /// Bit 7: nRESET
-    fn swj_pins(
-        &mut self,
-        pin_out: u32,
-        pin_select: u32,
-        pin_wait: u32,
-    ) -> Result<u32, DebugProbeError>;
-
     fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe>;
 }
 
@@ -211,4 +252,31 @@ pub trait DapAccess {
         }
         Ok(())
     }
+
+    /// Execute a batch of reads and writes of registers of the same Access Port.
+    ///
+    /// If possible, the whole batch is executed in as few round trips to the probe as
+    /// possible, otherwise it falls back to the `read_raw_ap_register` and
+    /// `write_raw_ap_register` functions.
+    ///
+    /// Highest 4 bits of the addresses are interpreted as the bank number, implementations
+    /// will do bank switching if necessary.
+    fn transfer_raw_ap_batch(
+        &mut self,
+        ap: ApAddress,
+        transfers: &mut [DapTransfer],
+    ) -> Result<(), DebugProbeError> {
+        for transfer in transfers {
+            match transfer {
+                DapTransfer::Read { addr, value } => {
+                    *value = self.read_raw_ap_register(ap, *addr)?;
+                }
+                DapTransfer::Write { addr, value } => {
+                    self.write_raw_ap_register(ap, *addr, *value)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
 }
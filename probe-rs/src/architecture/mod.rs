@@ -1,2 +1,3 @@
 pub mod arm;
 pub mod riscv;
+pub mod xtensa;
@@ -18,10 +18,8 @@ use crate::{MemoryInterface, Probe};
 use crate::{probe::JTAGAccess, CoreRegisterAddress, Error as ProbeRsError};
 
 use bitfield::bitfield;
-use std::{
-    collections::HashMap,
-    time::{Duration, Instant},
-};
+use std::{collections::HashMap, time::Duration};
+use web_time::Instant;
 
 #[derive(thiserror::Error, Debug)]
 pub enum RiscvError {
@@ -49,6 +47,12 @@ pub enum RiscvError {
     SystemBusAccess,
     #[error("Unexpected trigger type {0} for address breakpoint.")]
     UnexpectedTriggerType(u32),
+    #[error("The debug module does not support halting the hart after a reset.")]
+    ResetHaltRequestNotSupported,
+    #[error("The hart supports neither single stepping nor icount triggers.")]
+    StepNotSupported,
+    #[error("Hart {0} does not exist.")]
+    HartNotFound(u32),
 }
 
 impl From<RiscvError> for ProbeRsError {
@@ -120,6 +124,29 @@ impl From<u8> for DebugModuleVersion {
     }
 }
 
+/// The status of a single hart, as reported by the `dmstatus` register.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HartStatus {
+    /// The hart is halted.
+    Halted,
+    /// The hart is running.
+    Running,
+    /// The hart is not available, e.g. because it is powered down or held in reset.
+    Unavailable,
+}
+
+impl From<Dmstatus> for HartStatus {
+    fn from(status: Dmstatus) -> Self {
+        if status.allhalted() {
+            HartStatus::Halted
+        } else if status.allunavail() {
+            HartStatus::Unavailable
+        } else {
+            HartStatus::Running
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 struct CoreRegisterAbstractCmdSupport(u8);
 
@@ -168,11 +195,19 @@ pub struct RiscvCommunicationInterfaceState {
     /// Number of harts
     num_harts: u32,
 
+    /// The hart selected by `hartsel`, which is used for all
+    /// operations on a hart.
+    selected_hart: u32,
+
     memory_access_info: HashMap<RiscvBusAccess, MemoryAccessMethod>,
 
     /// describes, if the given register can be read / written with an
     /// abstract command
     abstract_cmd_register_info: HashMap<CoreRegisterAddress, CoreRegisterAbstractCmdSupport>,
+
+    /// The trigger types supported by each trigger of each hart, as a bit mask
+    /// in the format of `tinfo`. Determined on first use.
+    trigger_types: HashMap<u32, Option<Vec<u32>>>,
 }
 
 /// Timeout for RISCV operations.
@@ -205,9 +240,13 @@ impl RiscvCommunicationInterfaceState {
             // We assume only a singe hart exisits initially
             num_harts: 1,
 
+            selected_hart: 0,
+
             memory_access_info: HashMap::new(),
 
             abstract_cmd_register_info: HashMap::new(),
+
+            trigger_types: HashMap::new(),
         }
     }
 
@@ -219,6 +258,17 @@ impl RiscvCommunicationInterfaceState {
             .entry(access_width)
             .or_insert(MemoryAccessMethod::ProgramBuffer)
     }
+
+    /// Check if accesses with the specified width can be done using
+    /// wider reads on the system bus, if the system bus does not
+    /// support the width directly.
+    fn system_bus_can_widen(&self, access_width: RiscvBusAccess) -> bool {
+        access_width < RiscvBusAccess::A32
+            && matches!(
+                self.memory_access_info.get(&RiscvBusAccess::A32),
+                Some(MemoryAccessMethod::SystemBus)
+            )
+    }
 }
 
 impl Default for RiscvCommunicationInterfaceState {
@@ -255,6 +305,72 @@ impl<'probe> RiscvCommunicationInterface {
         Ok(s)
     }
 
+    /// The trigger types supported by each trigger of the selected hart, as a bit mask
+    /// in the format of `tinfo`, or `None` if not determined yet.
+    pub(super) fn trigger_types(&mut self) -> &mut Option<Vec<u32>> {
+        self.state
+            .trigger_types
+            .entry(self.state.selected_hart)
+            .or_default()
+    }
+
+    /// The number of harts connected to the debug module.
+    pub fn num_harts(&self) -> u32 {
+        self.state.num_harts
+    }
+
+    /// The hart which is currently selected.
+    pub fn selected_hart(&self) -> u32 {
+        self.state.selected_hart
+    }
+
+    /// Select the hart which is used for all following operations, e.g.
+    /// halting the core or accessing registers.
+    pub fn select_hart(&mut self, hart: u32) -> Result<(), RiscvError> {
+        if hart >= self.state.num_harts {
+            return Err(RiscvError::HartNotFound(hart));
+        }
+
+        if hart == self.state.selected_hart {
+            return Ok(());
+        }
+
+        log::debug!("Selecting hart {}", hart);
+
+        self.state.selected_hart = hart;
+
+        let dmcontrol = self.dmcontrol();
+        self.write_dm_register(dmcontrol)
+    }
+
+    /// Read the status of a hart, without changing the selected hart.
+    pub fn hart_status(&mut self, hart: u32) -> Result<HartStatus, RiscvError> {
+        let previous_hart = self.state.selected_hart;
+
+        self.select_hart(hart)?;
+        let status: Result<Dmstatus, RiscvError> = self.read_dm_register();
+        self.select_hart(previous_hart)?;
+
+        Ok(HartStatus::from(status?))
+    }
+
+    /// Read the status of all harts.
+    pub fn hart_statuses(&mut self) -> Result<Vec<HartStatus>, RiscvError> {
+        (0..self.state.num_harts)
+            .map(|hart| self.hart_status(hart))
+            .collect()
+    }
+
+    /// A `dmcontrol` value which keeps the debug module active, and selects the
+    /// current hart. All writes to `dmcontrol` have to start from this value,
+    /// otherwise a different hart is selected.
+    pub(crate) fn dmcontrol(&self) -> Dmcontrol {
+        let mut dmcontrol = Dmcontrol(0);
+        dmcontrol.set_dmactive(true);
+        dmcontrol.set_hartsel(self.state.selected_hart);
+        dmcontrol
+    }
+
     pub fn target_reset_deassert(&mut self) -> Result<(), DebugProbeError> {
         self.dtm.target_reset_deassert()
     }
@@ -350,10 +466,9 @@ impl<'probe> RiscvCommunicationInterface {
         self.state.num_harts = num_harts;
 
         // Select hart 0 again
-        let mut control = Dmcontrol(0);
-        control.set_hartsel(0);
-        control.set_dmactive(true);
+        self.state.selected_hart = 0;
 
+        let control = self.dmcontrol();
         self.write_dm_register(control)?;
 
         // determine size of the program buffer, and number of data
@@ -396,8 +511,9 @@ impl<'probe> RiscvCommunicationInterface {
 
         // Only version 1 is supported, this means that
         // the system bus access conforms to the debug
-        // specification 13.2.
-        if sbcs.sbversion() == 1 {
+        // specification 13.2. A width of zero for the
+        // address means that there is no system bus access.
+        if sbcs.sbversion() == 1 && sbcs.sbasize() != 0 {
             // When possible, we use system bus access for memory access
 
             if sbcs.sbaccess8() {
@@ -429,6 +545,8 @@ impl<'probe> RiscvCommunicationInterface {
                     .memory_access_info
                     .insert(RiscvBusAccess::A128, MemoryAccessMethod::SystemBus);
             }
+
+            log::debug!("System bus access is supported: {:x?}", sbcs);
         } else {
             log::debug!(
                 "System bus interface version {} is not supported.",
@@ -556,9 +674,8 @@ impl<'probe> RiscvCommunicationInterface {
 
     /// Perform a single read from a memory location, using system bus access.
     fn perform_memory_read_sysbus<V: RiscvValue>(&mut self, address: u32) -> Result<V, RiscvError> {
-        let mut sbcs = Sbcs(0);
+        let mut sbcs = Sbcs::for_access(V::WIDTH);
 
-        sbcs.set_sbaccess(V::WIDTH as u32);
         sbcs.set_sbreadonaddr(true);
 
         self.write_dm_register(sbcs)?;
@@ -569,7 +686,7 @@ impl<'probe> RiscvCommunicationInterface {
         // Check that the read was succesful
         let sbcs = self.read_dm_register::<Sbcs>()?;
 
-        if sbcs.sberror() != 0 {
+        if sbcs.has_error() {
             Err(RiscvError::SystemBusAccess)
         } else {
             Ok(data)
@@ -584,9 +701,7 @@ impl<'probe> RiscvCommunicationInterface {
         address: u32,
         data: &mut [V],
     ) -> Result<(), RiscvError> {
-        let mut sbcs = Sbcs(0);
-
-        sbcs.set_sbaccess(V::WIDTH as u32);
+        let mut sbcs = Sbcs::for_access(V::WIDTH);
 
         sbcs.set_sbreadonaddr(true);
 
@@ -605,7 +720,10 @@ impl<'probe> RiscvCommunicationInterface {
             read_results.push(idx);
         }
 
+        // Don't clear errors of the reads above, so they are detected below.
         sbcs.set_sbautoincrement(false);
+        sbcs.set_sberror(0);
+        sbcs.set_sbbusyerror(false);
         self.schedule_write_dm_register(sbcs)?;
 
         // Read last value
@@ -631,13 +749,43 @@ impl<'probe> RiscvCommunicationInterface {
 
         let sbcs = Sbcs(sbcs);
 
-        if sbcs.sberror() != 0 {
+        if sbcs.has_error() {
             Err(RiscvError::SystemBusAccess)
         } else {
             Ok(())
         }
     }
 
+    /// Perform reads from consecutive memory locations using 32 bit system bus accesses.
+    ///
+    /// This is used if the system bus does not support accesses of width `V`. As reads have
+    /// no side effects on memory, the words containing the requested values are read instead.
+    fn perform_memory_read_multiple_sysbus_widened<V: RiscvValue32>(
+        &mut self,
+        address: u32,
+        data: &mut [V],
+    ) -> Result<(), RiscvError> {
+        let width = V::WIDTH.byte_width();
+        let (start, word_count) = covering_words(address, data.len() * width);
+
+        let mut words = vec![0u32; word_count];
+        self.perform_memory_read_multiple_sysbus(start, &mut words)?;
+
+        let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+        let offset = (address - start) as usize;
+
+        for (index, value) in data.iter_mut().enumerate() {
+            let position = offset + index * width;
+
+            let mut raw = [0u8; 4];
+            raw[..width].copy_from_slice(&bytes[position..position + width]);
+
+            *value = V::from_register_value(u32::from_le_bytes(raw));
+        }
+
+        Ok(())
+    }
+
     /// Perform memory read from a single location using the program buffer.
     /// Only reads up to a width of 32 bits are currently supported.
     fn perform_memory_read_progbuf<V: RiscvValue32>(
@@ -768,10 +916,8 @@ impl<'probe> RiscvCommunicationInterface {
         address: u32,
         data: &[V],
     ) -> Result<(), RiscvError> {
-        let mut sbcs = Sbcs(0);
+        let mut sbcs = Sbcs::for_access(V::WIDTH);
 
-        // Set correct access width
-        sbcs.set_sbaccess(V::WIDTH as u32);
         sbcs.set_sbautoincrement(true);
 
         self.schedule_write_dm_register(sbcs)?;
@@ -795,7 +941,7 @@ impl<'probe> RiscvCommunicationInterface {
 
         let sbcs = Sbcs(sbcs);
 
-        if sbcs.sberror() != 0 {
+        if sbcs.has_error() {
             Err(RiscvError::SystemBusAccess)
         } else {
             Ok(())
@@ -941,11 +1087,10 @@ impl<'probe> RiscvCommunicationInterface {
         // resumereq    = 0
         // ackhavereset = 0
 
-        let mut dmcontrol = Dmcontrol(0);
+        let mut dmcontrol = self.dmcontrol();
         dmcontrol.set_haltreq(false);
         dmcontrol.set_resumereq(false);
         dmcontrol.set_ackhavereset(true);
-        dmcontrol.set_dmactive(true);
         self.write_dm_register(dmcontrol)?;
 
         // read abstractcs to see its state
@@ -1101,6 +1246,196 @@ impl<'probe> RiscvCommunicationInterface {
         }
     }
 
+    /// Read the core registers `regnos` with abstract commands, which are queued and
+    /// executed in a single batch.
+    ///
+    /// Registers which could not be read with an abstract command are `None`, and have
+    /// to be read another way.
+    pub(crate) fn abstract_cmd_register_read_batch(
+        &mut self,
+        regnos: &[CoreRegisterAddress],
+    ) -> Result<Vec<Option<u32>>, RiscvError> {
+        self.schedule_abstract_command_preconditions()?;
+
+        let mut indices = Vec::with_capacity(regnos.len());
+
+        for &regno in regnos {
+            if !self
+                .check_abstract_cmd_register_support(regno, CoreRegisterAbstractCmdSupport::READ)
+            {
+                indices.push(None);
+                continue;
+            }
+
+            let mut command = AccessRegisterCommand(0);
+            command.set_cmd_type(0);
+            command.set_transfer(true);
+            command.set_aarsize(RiscvBusAccess::A32);
+            command.set_regno(regno.0 as u32);
+
+            self.schedule_write_dm_register(Command(command.0))?;
+            let abstractcs = self.schedule_read_dm_register::<Abstractcs>()?;
+            let data0 = self.schedule_read_dm_register::<Data0>()?;
+
+            indices.push(Some((abstractcs, data0)));
+        }
+
+        let results = self.execute()?;
+
+        let mut statuses = Vec::with_capacity(regnos.len());
+        let mut values = Vec::with_capacity(regnos.len());
+
+        for index in indices {
+            match index {
+                Some((abstractcs, data0)) => {
+                    statuses.push(Some(Abstractcs(command_result_u32(&results[abstractcs]))));
+                    values.push(command_result_u32(&results[data0]));
+                }
+                None => {
+                    statuses.push(None);
+                    values.push(0);
+                }
+            }
+        }
+
+        let failed =
+            self.check_batch_statuses(regnos, &statuses, CoreRegisterAbstractCmdSupport::READ)?;
+
+        Ok(values
+            .into_iter()
+            .enumerate()
+            .map(|(index, value)| match statuses[index] {
+                Some(_) if index < failed => Some(value),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Write the core `registers` with abstract commands, which are queued and
+    /// executed in a single batch.
+    ///
+    /// Returns if each register was written. Registers which could not be written with
+    /// an abstract command have to be written another way.
+    pub(crate) fn abstract_cmd_register_write_batch(
+        &mut self,
+        registers: &[(CoreRegisterAddress, u32)],
+    ) -> Result<Vec<bool>, RiscvError> {
+        self.schedule_abstract_command_preconditions()?;
+
+        let mut indices = Vec::with_capacity(registers.len());
+
+        for &(regno, value) in registers {
+            if !self
+                .check_abstract_cmd_register_support(regno, CoreRegisterAbstractCmdSupport::WRITE)
+            {
+                indices.push(None);
+                continue;
+            }
+
+            let mut command = AccessRegisterCommand(0);
+            command.set_cmd_type(0);
+            command.set_transfer(true);
+            command.set_write(true);
+            command.set_aarsize(RiscvBusAccess::A32);
+            command.set_regno(regno.0 as u32);
+
+            self.schedule_write_dm_register(Data0(value))?;
+            self.schedule_write_dm_register(Command(command.0))?;
+            indices.push(Some(self.schedule_read_dm_register::<Abstractcs>()?));
+        }
+
+        let results = self.execute()?;
+
+        let statuses: Vec<_> = indices
+            .into_iter()
+            .map(|index| index.map(|index| Abstractcs(command_result_u32(&results[index]))))
+            .collect();
+
+        let regnos: Vec<_> = registers.iter().map(|(regno, _)| *regno).collect();
+
+        let failed =
+            self.check_batch_statuses(&regnos, &statuses, CoreRegisterAbstractCmdSupport::WRITE)?;
+
+        Ok(statuses
+            .iter()
+            .enumerate()
+            .map(|(index, status)| status.is_some() && index < failed)
+            .collect())
+    }
+
+    /// Schedule the writes which ensure the preconditions of abstract commands,
+    /// like [`Self::execute_abstract_command`] does.
+    fn schedule_abstract_command_preconditions(&mut self) -> Result<(), RiscvError> {
+        let mut dmcontrol = self.dmcontrol();
+        dmcontrol.set_haltreq(false);
+        dmcontrol.set_resumereq(false);
+        dmcontrol.set_ackhavereset(true);
+        self.schedule_write_dm_register(dmcontrol)?;
+
+        // Clear the error of a previous command, otherwise all commands are ignored.
+        let mut abstractcs_clear = Abstractcs(0);
+        abstractcs_clear.set_cmderr(0x7);
+        self.schedule_write_dm_register(abstractcs_clear)?;
+
+        Ok(())
+    }
+
+    /// Find the first failed command of a batch, from the `abstractcs` read after each
+    /// command. Commands after a failed one are ignored by the debug module.
+    ///
+    /// Returns the index of the failed command, or the length of the batch if all succeeded.
+    fn check_batch_statuses(
+        &mut self,
+        regnos: &[CoreRegisterAddress],
+        statuses: &[Option<Abstractcs>],
+        rw: CoreRegisterAbstractCmdSupport,
+    ) -> Result<usize, RiscvError> {
+        let failed = match statuses.iter().position(|status| match status {
+            Some(abstractcs) => abstractcs.busy() || abstractcs.cmderr() != 0,
+            None => false,
+        }) {
+            Some(failed) => failed,
+            None => return Ok(statuses.len()),
+        };
+
+        // NOTE(unwrap): `position` only matches statuses of executed commands.
+        let abstractcs = statuses[failed].as_ref().unwrap();
+
+        log::debug!(
+            "Abstract command for register {:#x} of batch failed: {:?}",
+            regnos[failed].0,
+            abstractcs
+        );
+
+        if matches!(
+            AbstractCommandErrorKind::parse(abstractcs.cmderr() as u8),
+            AbstractCommandErrorKind::NotSupported
+        ) {
+            self.set_abstract_cmd_register_unsupported(regnos[failed], rw);
+        }
+
+        // Wait for the command which is still running, and clear the error.
+        let start_time = Instant::now();
+
+        loop {
+            let abstractcs: Abstractcs = self.read_dm_register()?;
+
+            if !abstractcs.busy() {
+                break;
+            }
+
+            if start_time.elapsed() > RISCV_TIMEOUT {
+                return Err(RiscvError::Timeout);
+            }
+        }
+
+        let mut abstractcs_clear = Abstractcs(0);
+        abstractcs_clear.set_cmderr(0x7);
+        self.write_dm_register(abstractcs_clear)?;
+
+        Ok(failed)
+    }
+
     pub fn read_csr_progbuf(&mut self, address: u16) -> Result<u32, RiscvError> {
         log::debug!("Reading CSR {:#04x}", address);
 
@@ -1152,6 +1487,27 @@ impl<'probe> RiscvCommunicationInterface {
         Ok(())
     }
 
+    /// Whether all memory reads use the system bus, so that they can be done while the hart is running.
+    pub(crate) fn system_bus_can_read(&mut self) -> bool {
+        // Narrower reads are widened to 32 bit accesses on the system bus.
+        matches!(
+            self.state.memory_access_method(RiscvBusAccess::A32),
+            MemoryAccessMethod::SystemBus
+        )
+    }
+
+    /// Whether all memory writes use the system bus, so that they can be done while the hart is running.
+    pub(crate) fn system_bus_can_write(&mut self) -> bool {
+        [RiscvBusAccess::A8, RiscvBusAccess::A32]
+            .iter()
+            .all(|&width| {
+                matches!(
+                    self.state.memory_access_method(width),
+                    MemoryAccessMethod::SystemBus
+                )
+            })
+    }
+
     fn read_large_dtm_register<V, R>(&mut self) -> Result<V, RiscvError>
     where
         V: RiscvValue,
@@ -1170,6 +1526,11 @@ impl<'probe> RiscvCommunicationInterface {
 
     fn read_word<V: RiscvValue32>(&mut self, address: u32) -> Result<V, crate::Error> {
         let result = match self.state.memory_access_method(V::WIDTH) {
+            MemoryAccessMethod::ProgramBuffer if self.state.system_bus_can_widen(V::WIDTH) => {
+                let mut value = [V::from_register_value(0)];
+                self.perform_memory_read_multiple_sysbus_widened(address, &mut value)?;
+                value[0]
+            }
             MemoryAccessMethod::ProgramBuffer => self.perform_memory_read_progbuf(address)?,
             MemoryAccessMethod::SystemBus => self.perform_memory_read_sysbus(address)?,
             MemoryAccessMethod::AbstractCommand => {
@@ -1185,9 +1546,14 @@ impl<'probe> RiscvCommunicationInterface {
         address: u32,
         data: &mut [V],
     ) -> Result<(), crate::Error> {
-        log::debug!("read_32 from {:#08x}", address);
+        if data.is_empty() {
+            return Ok(());
+        }
 
-        match self.state.memory_access_method(RiscvBusAccess::A32) {
+        match self.state.memory_access_method(V::WIDTH) {
+            MemoryAccessMethod::ProgramBuffer if self.state.system_bus_can_widen(V::WIDTH) => {
+                self.perform_memory_read_multiple_sysbus_widened(address, data)?;
+            }
             MemoryAccessMethod::ProgramBuffer => {
                 self.perform_memory_read_multiple_progbuf(address, data)?;
             }
@@ -1221,6 +1587,10 @@ impl<'probe> RiscvCommunicationInterface {
         address: u32,
         data: &[V],
     ) -> Result<(), crate::Error> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
         match self.state.memory_access_method(V::WIDTH) {
             MemoryAccessMethod::SystemBus => self.perform_memory_write_sysbus(address, data)?,
             MemoryAccessMethod::ProgramBuffer => {
@@ -1738,6 +2108,15 @@ impl From<RiscvBusAccess> for u8 {
     }
 }
 
+/// The start address and the number of the aligned 32 bit words
+/// which contain the `length` bytes at `address`.
+fn covering_words(address: u32, length: usize) -> (u32, usize) {
+    let start = address & !0b11;
+    let end = u64::from(address) + length as u64;
+
+    (start, (end - u64::from(start)).div_ceil(4) as usize)
+}
+
 /// Different methods of memory access,
 /// which can be supported by a debug module.
 ///
@@ -1811,6 +2190,26 @@ bitfield! {
     sbaccess8, _: 0;
 }
 
+impl Sbcs {
+    /// Control value for a system bus access with the given width.
+    ///
+    /// The sticky error flags of previous accesses are cleared.
+    fn for_access(access_width: RiscvBusAccess) -> Self {
+        let mut sbcs = Sbcs(0);
+
+        sbcs.set_sbaccess(access_width as u32);
+        sbcs.set_sberror(0b111);
+        sbcs.set_sbbusyerror(true);
+
+        sbcs
+    }
+
+    /// Check if an access on the system bus has failed.
+    fn has_error(&self) -> bool {
+        self.sberror() != 0 || self.sbbusyerror()
+    }
+}
+
 impl DebugRegister for Sbcs {
     const ADDRESS: u8 = 0x38;
     const NAME: &'static str = "sbcs";
@@ -1901,3 +2300,38 @@ data_register! { Confstrptr0, 0x19, "confstrptr0" }
 data_register! { Confstrptr1, 0x1a, "confstrptr1" }
 data_register! { Confstrptr2, 0x1b, "confstrptr2" }
 data_register! { Confstrptr3, 0x1c, "confstrptr3" }
+
+fn command_result_u32(result: &CommandResult) -> u32 {
+    match result {
+        CommandResult::U32(value) => *value,
+        _ => panic!("Internal error occured."),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{covering_words, HartStatus};
+    use crate::architecture::riscv::Dmstatus;
+
+    #[test]
+    fn covering_words_for_unaligned_access() {
+        assert_eq!(covering_words(0x2000_0000, 4), (0x2000_0000, 1));
+        assert_eq!(covering_words(0x2000_0003, 1), (0x2000_0000, 1));
+        assert_eq!(covering_words(0x2000_0003, 2), (0x2000_0000, 2));
+        assert_eq!(covering_words(0x2000_0001, 8), (0x2000_0000, 3));
+        assert_eq!(covering_words(0xffff_fffe, 2), (0xffff_fffc, 1));
+    }
+
+    #[test]
+    fn hart_status_from_dmstatus() {
+        // allhalted, anyhalted
+        assert_eq!(HartStatus::from(Dmstatus(0x0000_0300)), HartStatus::Halted);
+        // allrunning, anyrunning
+        assert_eq!(HartStatus::from(Dmstatus(0x0000_0c00)), HartStatus::Running);
+        // allunavail, anyunavail
+        assert_eq!(
+            HartStatus::from(Dmstatus(0x0000_3000)),
+            HartStatus::Unavailable
+        );
+    }
+}
@@ -1,7 +1,5 @@
-use std::{
-    convert::TryInto,
-    time::{Duration, Instant},
-};
+use std::{convert::TryInto, time::Duration};
+use web_time::Instant;
 
 use bitfield::bitfield;
 
@@ -9,27 +9,42 @@ use communication_interface::{
     AbstractCommandErrorKind, DebugRegister, RiscvCommunicationInterface, RiscvError,
 };
 
-use crate::core::{CoreInformation, RegisterFile};
-use crate::{CoreRegisterAddress, CoreStatus, Error, HaltReason, MemoryInterface};
+use crate::core::{
+    BreakpointCause, CoreInformation, HaltDetails, RegisterFile, StepInterruptBehavior, Watchpoint,
+};
+use crate::{CoreRegisterAddress, CoreStatus, Error, HaltReason, MemoryInterface, ResetKind};
 use bitfield::bitfield;
-use register::RISCV_REGISTERS;
-use std::time::{Duration, Instant};
+pub(crate) use register::RISCV_REGISTERS;
+use sequences::RiscvDebugSequence;
+use std::sync::Arc;
+use std::time::Duration;
+use triggers::{TDATA1, TDATA2, TSELECT};
+use web_time::Instant;
 
 #[macro_use]
 mod register;
 pub(crate) mod assembly;
 mod dtm;
+mod triggers;
 
 pub mod communication_interface;
 pub mod sequences;
 
 pub struct Riscv32<'probe> {
     interface: &'probe mut RiscvCommunicationInterface,
+
+    sequence: Arc<dyn RiscvDebugSequence>,
 }
 
 impl<'probe> Riscv32<'probe> {
-    pub fn new(interface: &'probe mut RiscvCommunicationInterface) -> Self {
-        Self { interface }
+    pub fn new(
+        interface: &'probe mut RiscvCommunicationInterface,
+        sequence: Arc<dyn RiscvDebugSequence>,
+    ) -> Self {
+        Self {
+            interface,
+            sequence,
+        }
     }
 
     fn read_csr(&mut self, address: u16) -> Result<u32, RiscvError> {
@@ -63,6 +78,110 @@ impl<'probe> Riscv32<'probe> {
             other => other,
         }
     }
+
+    /// Reset the hart using the `hartreset` bit for [`ResetKind::Core`], or
+    /// the system reset of the debug sequence for [`ResetKind::System`].
+    ///
+    /// If `halt` is set, the hart is halted directly after the reset.
+    fn reset_hart(&mut self, kind: ResetKind, halt: bool) -> Result<(), crate::Error> {
+        if !matches!(kind, ResetKind::Core | ResetKind::System) {
+            return Err(crate::Error::UnsupportedResetKind(kind));
+        }
+
+        let reset_catch = halt && self.try_enable_reset_catch()?;
+
+        log::debug!("Resetting core using {:?} reset", kind);
+
+        if kind == ResetKind::Core {
+            let mut dmcontrol = self.interface.dmcontrol();
+            dmcontrol.set_haltreq(halt);
+            dmcontrol.set_hartreset(true);
+
+            self.interface.write_dm_register(dmcontrol)?;
+
+            // Read back register to verify reset is supported
+            let readback: Dmcontrol = self.interface.read_dm_register()?;
+
+            if !readback.hartreset() {
+                return Err(crate::Error::UnsupportedResetKind(kind));
+            }
+
+            // Reset is performed by setting the bit high, and then low again
+            let mut dmcontrol = self.interface.dmcontrol();
+            dmcontrol.set_haltreq(halt);
+            dmcontrol.set_hartreset(false);
+
+            self.interface.write_dm_register(dmcontrol)?;
+
+            acknowledge_reset(self.interface, halt)?;
+        } else {
+            self.sequence.reset_system(self.interface, halt)?;
+        }
+
+        if reset_catch {
+            self.disable_reset_catch()?;
+        }
+
+        Ok(())
+    }
+
+    /// Enable the reset catch, if the debug sequence supports it.
+    ///
+    /// Returns `false` if the reset catch is not supported, in which case the
+    /// hart has to be halted by a halt request during the reset.
+    fn try_enable_reset_catch(&mut self) -> Result<bool, crate::Error> {
+        match self.enable_reset_catch() {
+            Ok(()) => Ok(true),
+            Err(crate::Error::ArchitectureSpecific(error))
+                if matches!(
+                    error.downcast_ref::<RiscvError>(),
+                    Some(RiscvError::ResetHaltRequestNotSupported)
+                ) =>
+            {
+                log::debug!("resethaltreq not supported, using haltreq during the reset");
+                Ok(false)
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Reset the hart using the `hartreset` bit, or a system reset
+    /// if resetting a single hart is not supported.
+    fn reset_hart_with_fallback(&mut self, halt: bool) -> Result<(), crate::Error> {
+        match self.reset_hart(ResetKind::Core, halt) {
+            Err(crate::Error::UnsupportedResetKind(_)) => {
+                // Hartreset is not supported, whole core needs to be reset
+                //
+                // TODO: Cache this
+                log::debug!("Hartreset bit not supported, using a system reset");
+                self.reset_hart(ResetKind::System, halt)
+            }
+            result => result,
+        }
+    }
+}
+
+/// Check that the hart has been reset, and halted if `halt` is set, and acknowledge the reset.
+///
+/// This also clears any halt request which was set during the reset.
+fn acknowledge_reset(
+    interface: &mut RiscvCommunicationInterface,
+    halt: bool,
+) -> Result<(), crate::Error> {
+    let readback: Dmstatus = interface.read_dm_register()?;
+
+    if !readback.allhavereset() || (halt && !readback.allhalted()) {
+        log::warn!("Dmstatus: {:?}", readback);
+        return Err(RiscvError::RequestNotAcknowledged.into());
+    }
+
+    // acknowledge the reset, clear the halt request
+    let mut dmcontrol = interface.dmcontrol();
+    dmcontrol.set_ackhavereset(true);
+
+    interface.write_dm_register(dmcontrol)?;
+
+    Ok(())
 }
 
 impl<'probe> CoreInterface for Riscv32<'probe> {
@@ -96,20 +215,15 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
         let current_dmcontrol: Dmcontrol = self.interface.read_dm_register()?;
         log::debug!("{:?}", current_dmcontrol);
 
-        let mut dmcontrol = Dmcontrol(0);
-
+        let mut dmcontrol = self.interface.dmcontrol();
         dmcontrol.set_haltreq(true);
-        dmcontrol.set_dmactive(true);
 
         self.interface.write_dm_register(dmcontrol)?;
 
         self.wait_for_core_halted(timeout)?;
 
         // clear the halt request
-        let mut dmcontrol = Dmcontrol(0);
-
-        dmcontrol.set_dmactive(true);
-
+        let dmcontrol = self.interface.dmcontrol();
         self.interface.write_dm_register(dmcontrol)?;
 
         let pc = self.read_core_reg(register::RISCV_REGISTERS.program_counter.address)?;
@@ -121,8 +235,7 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
         // TODO: test if core halted?
 
         // set resume request
-        let mut dmcontrol = Dmcontrol(0);
-        dmcontrol.set_dmactive(true);
+        let mut dmcontrol = self.interface.dmcontrol();
         dmcontrol.set_resumereq(true);
 
         self.interface.write_dm_register(dmcontrol)?;
@@ -135,135 +248,63 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
         };
 
         // clear resume request
-        let mut dmcontrol = Dmcontrol(0);
-        dmcontrol.set_dmactive(true);
-
+        let dmcontrol = self.interface.dmcontrol();
         self.interface.write_dm_register(dmcontrol)?;
 
         Ok(())
     }
 
     fn reset(&mut self) -> Result<(), crate::Error> {
-        log::debug!("Resetting core, setting hartreset bit");
-
-        let mut dmcontrol = Dmcontrol(0);
-        dmcontrol.set_dmactive(true);
-        dmcontrol.set_hartreset(true);
-
-        self.interface.write_dm_register(dmcontrol)?;
-
-        // Read back register to verify reset is supported
-        let readback: Dmcontrol = self.interface.read_dm_register()?;
-
-        if readback.hartreset() {
-            log::debug!("Clearing hartreset bit");
-            // Reset is performed by setting the bit high, and then low again
-            let mut dmcontrol = Dmcontrol(0);
-            dmcontrol.set_dmactive(true);
-            dmcontrol.set_hartreset(false);
-
-            self.interface.write_dm_register(dmcontrol)?;
-        } else {
-            // Hartreset is not supported, whole core needs to be reset
-            //
-            // TODO: Cache this
-            log::debug!("Hartreset bit not supported, using ndmreset");
-            let mut dmcontrol = Dmcontrol(0);
-            dmcontrol.set_dmactive(true);
-            dmcontrol.set_ndmreset(true);
-
-            self.interface.write_dm_register(dmcontrol)?;
-
-            log::debug!("Clearing ndmreset bit");
-            let mut dmcontrol = Dmcontrol(0);
-            dmcontrol.set_dmactive(true);
-            dmcontrol.set_ndmreset(false);
-
-            self.interface.write_dm_register(dmcontrol)?;
-        }
-
-        // check that cores have reset
-
-        let readback: Dmstatus = self.interface.read_dm_register()?;
-
-        if !readback.allhavereset() {
-            log::warn!("Dmstatue: {:?}", readback);
-            return Err(RiscvError::RequestNotAcknowledged.into());
-        }
-
-        // acknowledge the reset
-        let mut dmcontrol = Dmcontrol(0);
-        dmcontrol.set_dmactive(true);
-        dmcontrol.set_ackhavereset(true);
-
-        self.interface.write_dm_register(dmcontrol)?;
-
-        Ok(())
+        self.reset_hart_with_fallback(false)
     }
 
     fn reset_and_halt(
         &mut self,
         _timeout: Duration,
     ) -> Result<crate::core::CoreInformation, crate::Error> {
-        log::debug!("Resetting core, setting hartreset bit");
-
-        let mut dmcontrol = Dmcontrol(0);
-        dmcontrol.set_dmactive(true);
-        dmcontrol.set_hartreset(true);
-        dmcontrol.set_haltreq(true);
-
-        self.interface.write_dm_register(dmcontrol)?;
+        self.reset_hart_with_fallback(true)?;
 
-        // Read back register to verify reset is supported
-        let readback: Dmcontrol = self.interface.read_dm_register()?;
+        let pc = self.read_core_reg(CoreRegisterAddress(0x7b1))?;
 
-        if readback.hartreset() {
-            log::debug!("Clearing hartreset bit");
-            // Reset is performed by setting the bit high, and then low again
-            let mut dmcontrol = Dmcontrol(0);
-            dmcontrol.set_dmactive(true);
-            dmcontrol.set_haltreq(true);
-            dmcontrol.set_hartreset(false);
+        Ok(CoreInformation { pc })
+    }
 
-            self.interface.write_dm_register(dmcontrol)?;
-        } else {
-            // Hartreset is not supported, whole core needs to be reset
-            //
-            // TODO: Cache this
-            log::debug!("Hartreset bit not supported, using ndmreset");
-            let mut dmcontrol = Dmcontrol(0);
-            dmcontrol.set_dmactive(true);
-            dmcontrol.set_ndmreset(true);
-            dmcontrol.set_haltreq(true);
+    fn reset_with(&mut self, kind: ResetKind) -> Result<(), crate::Error> {
+        self.reset_hart(kind, false)
+    }
 
-            self.interface.write_dm_register(dmcontrol)?;
+    fn reset_and_halt_with(
+        &mut self,
+        kind: ResetKind,
+        _timeout: Duration,
+    ) -> Result<crate::core::CoreInformation, crate::Error> {
+        self.reset_hart(kind, true)?;
 
-            log::debug!("Clearing ndmreset bit");
-            let mut dmcontrol = Dmcontrol(0);
-            dmcontrol.set_dmactive(true);
-            dmcontrol.set_ndmreset(false);
-            dmcontrol.set_haltreq(true);
+        let pc = self.read_core_reg(CoreRegisterAddress(0x7b1))?;
 
-            self.interface.write_dm_register(dmcontrol)?;
-        }
+        Ok(CoreInformation { pc })
+    }
 
-        // check that cores have reset
-        let readback: Dmstatus = self.interface.read_dm_register()?;
+    fn enable_reset_catch(&mut self) -> Result<(), crate::Error> {
+        self.sequence.reset_catch_set(self.interface)
+    }
 
-        if !(readback.allhavereset() && readback.allhalted()) {
-            return Err(RiscvError::RequestNotAcknowledged.into());
-        }
+    fn disable_reset_catch(&mut self) -> Result<(), crate::Error> {
+        self.sequence.reset_catch_clear(self.interface)
+    }
 
-        // acknowledge the reset, clear the halt request
-        let mut dmcontrol = Dmcontrol(0);
-        dmcontrol.set_dmactive(true);
-        dmcontrol.set_ackhavereset(true);
+    fn set_step_interrupt_behavior(
+        &mut self,
+        behavior: StepInterruptBehavior,
+    ) -> Result<(), crate::Error> {
+        let mut dcsr = Dcsr(self.read_core_reg(CoreRegisterAddress(0x7b0))?);
 
-        self.interface.write_dm_register(dmcontrol)?;
+        // Interrupts are enabled while stepping if `stepie` is set.
+        dcsr.set_stepie(behavior == StepInterruptBehavior::Enabled);
 
-        let pc = self.read_core_reg(CoreRegisterAddress(0x7b1))?;
+        self.write_csr(0x7b0, dcsr.0)?;
 
-        Ok(CoreInformation { pc })
+        Ok(())
     }
 
     fn step(&mut self) -> Result<crate::core::CoreInformation, crate::Error> {
@@ -273,18 +314,23 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
 
         self.write_csr(0x7b0, dcsr.0)?;
 
-        self.run()?;
+        // The step bit is hardwired to zero if the hart does not support stepping.
+        if Dcsr(self.read_csr(0x7b0)?).step() {
+            self.run()?;
 
-        self.wait_for_core_halted(Duration::from_millis(100))?;
+            self.wait_for_core_halted(Duration::from_millis(100))?;
 
-        let pc = self.read_core_reg(CoreRegisterAddress(0x7b1))?;
+            // clear step request
+            let mut dcsr = Dcsr(self.read_core_reg(CoreRegisterAddress(0x7b0))?);
 
-        // clear step request
-        let mut dcsr = Dcsr(self.read_core_reg(CoreRegisterAddress(0x7b0))?);
+            dcsr.set_step(false);
 
-        dcsr.set_step(false);
+            self.write_csr(0x7b0, dcsr.0)?;
+        } else {
+            self.step_with_icount()?;
+        }
 
-        self.write_csr(0x7b0, dcsr.0)?;
+        let pc = self.read_core_reg(CoreRegisterAddress(0x7b1))?;
 
         Ok(CoreInformation { pc })
     }
@@ -297,74 +343,45 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
         self.write_csr(address.0, value).map_err(|e| e.into())
     }
 
-    fn get_available_breakpoint_units(&mut self) -> Result<u32, crate::Error> {
-        // TODO: This should probably only be done once, when initialising
-
-        log::debug!("Determining number of HW breakpoints supported");
-
-        let tselect = 0x7a0;
-        let tdata1 = 0x7a1;
-        let tinfo = 0x7a4;
-
-        let mut tselect_index = 0;
-
-        // These steps follow the debug specification 0.13, section 5.1 Enumeration
-        loop {
-            log::debug!("Trying tselect={}", tselect_index);
-            if let Err(e) = self.write_csr(tselect, tselect_index) {
-                match e {
-                    RiscvError::AbstractCommand(AbstractCommandErrorKind::Exception) => break,
-                    other_error => return Err(other_error.into()),
-                }
-            }
+    fn read_core_regs(
+        &mut self,
+        addresses: &[crate::CoreRegisterAddress],
+    ) -> Result<Vec<u32>, crate::Error> {
+        let values = self.interface.abstract_cmd_register_read_batch(addresses)?;
 
-            let readback = self.read_csr(tselect)?;
+        addresses
+            .iter()
+            .zip(values)
+            .map(|(address, value)| match value {
+                Some(value) => Ok(value),
+                None => self.read_core_reg(*address),
+            })
+            .collect()
+    }
 
-            if readback != tselect_index {
-                break;
+    fn write_core_regs(
+        &mut self,
+        registers: &[(crate::CoreRegisterAddress, u32)],
+    ) -> Result<(), crate::Error> {
+        let written = self
+            .interface
+            .abstract_cmd_register_write_batch(registers)?;
+
+        for ((address, value), written) in registers.iter().zip(written) {
+            if !written {
+                self.write_core_reg(*address, *value)?;
             }
+        }
 
-            match self.read_csr(tinfo) {
-                Ok(tinfo_val) => {
-                    if tinfo_val & 0xffff == 1 {
-                        // Trigger doesn't exist, break the loop
-                        break;
-                    } else {
-                        log::info!(
-                            "Discovered trigger with index {} and type {}",
-                            tselect_index,
-                            tinfo_val & 0xffff
-                        );
-                    }
-                }
-                Err(RiscvError::AbstractCommand(AbstractCommandErrorKind::Exception)) => {
-                    // An exception means we have to read tdata1 to discover the type
-                    let tdata_val = self.read_csr(tdata1)?;
-
-                    // TODO: Proper handle xlen
-                    let xlen = 32;
-
-                    let trigger_type = tdata_val >> (xlen - 4);
-
-                    if trigger_type == 0 {
-                        break;
-                    }
-
-                    log::info!(
-                        "Discovered trigger with index {} and type {}",
-                        tselect_index,
-                        trigger_type,
-                    );
-                }
-                Err(other) => return Err(other.into()),
-            }
+        Ok(())
+    }
 
-            tselect_index += 1;
-        }
+    fn get_available_breakpoint_units(&mut self) -> Result<u32, crate::Error> {
+        let units = self.address_triggers()?.len() as u32;
 
-        log::debug!("Target supports {} breakpoints.", tselect_index);
+        log::debug!("Target supports {} breakpoints.", units);
 
-        Ok(tselect_index)
+        Ok(units)
     }
 
     fn enable_breakpoints(&mut self, _state: bool) -> Result<(), crate::Error> {
@@ -373,63 +390,59 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
     }
 
     fn set_hw_breakpoint(&mut self, bp_unit_index: usize, addr: u32) -> Result<(), crate::Error> {
-        // select requested trigger
-        let tselect = 0x7a0;
-        let tdata1 = 0x7a1;
-        let tdata2 = 0x7a2;
+        log::debug!("Setting breakpoint {}", bp_unit_index);
 
-        log::warn!("Setting breakpoint {}", bp_unit_index);
-
-        self.write_csr(tselect, bp_unit_index as u32)?;
-
-        // verify the trigger has the correct type
-
-        let tdata_value = Mcontrol(self.read_csr(tdata1)?);
-
-        // This should not happen
-        let trigger_type = tdata_value.type_();
-        if trigger_type != 0b10 {
-            return Err(RiscvError::UnexpectedTriggerType(trigger_type).into());
-        }
-
-        // Setup the trigger
-
-        let mut instruction_breakpoint = Mcontrol(0);
-
-        // Enter debug mode
-        instruction_breakpoint.set_action(1);
+        self.select_address_trigger(bp_unit_index)?;
 
         // Match exactly the value in tdata2
-        instruction_breakpoint.set_match(0);
-
-        instruction_breakpoint.set_m(true);
-        instruction_breakpoint.set_s(true);
-        instruction_breakpoint.set_u(true);
+        let mut instruction_breakpoint = Mcontrol::debug_trigger(0);
 
         // Trigger when instruction is executed
         instruction_breakpoint.set_execute(true);
 
-        instruction_breakpoint.set_dmode(true);
-
         // Match address
         instruction_breakpoint.set_select(false);
 
-        self.write_csr(tdata1, instruction_breakpoint.0)?;
-        self.write_csr(tdata2, addr)?;
+        self.write_address_trigger(instruction_breakpoint, addr)?;
 
         Ok(())
     }
 
     fn clear_hw_breakpoint(&mut self, unit_index: usize) -> Result<(), crate::Error> {
-        let tselect = 0x7a0;
-        let tdata1 = 0x7a1;
-        let tdata2 = 0x7a2;
+        self.clear_address_trigger(unit_index)
+    }
 
-        self.write_csr(tselect, unit_index as u32)?;
-        self.write_csr(tdata1, 0)?;
-        self.write_csr(tdata2, 0)?;
+    fn get_available_watchpoint_units(&mut self) -> Result<u32, crate::Error> {
+        Ok(self.address_triggers()?.len() as u32)
+    }
 
-        Ok(())
+    fn get_hw_watchpoints(&mut self) -> Result<Vec<Option<Watchpoint>>, crate::Error> {
+        self.read_watchpoints()
+    }
+
+    fn set_hw_watchpoint(
+        &mut self,
+        unit_index: usize,
+        watchpoint: Watchpoint,
+    ) -> Result<(), crate::Error> {
+        self.write_watchpoint(unit_index, watchpoint)
+    }
+
+    fn clear_hw_watchpoint(&mut self, unit_index: usize) -> Result<(), crate::Error> {
+        self.clear_address_trigger(unit_index)
+    }
+
+    fn hw_units_are_shared(&self) -> bool {
+        true
+    }
+
+    fn can_read_while_running(&mut self) -> bool {
+        // The program buffer can only be used while the hart is halted.
+        self.interface.system_bus_can_read()
+    }
+
+    fn can_write_while_running(&mut self) -> bool {
+        self.interface.system_bus_can_write()
     }
 
     fn registers(&self) -> &'static RegisterFile {
@@ -460,7 +473,7 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
                 // An ebreak instruction was hit
                 1 => HaltReason::Breakpoint,
                 // Trigger module caused halt
-                2 => HaltReason::Breakpoint,
+                2 => self.trigger_halt_reason()?,
                 // Debugger requested a halt
                 3 => HaltReason::Request,
                 // Core halted after single step
@@ -482,38 +495,65 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
         }
     }
 
+    fn reset_detected(&mut self) -> Result<bool, crate::Error> {
+        let status: Dmstatus = self.interface.read_dm_register()?;
+
+        if !status.anyhavereset() {
+            return Ok(false);
+        }
+
+        // Acknowledge the reset, so that the next reset can be detected.
+        let mut dmcontrol = self.interface.dmcontrol();
+        dmcontrol.set_ackhavereset(true);
+
+        self.interface.write_dm_register(dmcontrol)?;
+
+        Ok(true)
+    }
+
+    fn halt_details(&mut self) -> Result<HaltDetails, crate::Error> {
+        let reason = match self.status()? {
+            CoreStatus::Halted(reason) => reason,
+            _ => HaltReason::Unknown,
+        };
+
+        let mut details = HaltDetails::new(reason);
+
+        let dcsr = Dcsr(self.read_core_reg(CoreRegisterAddress::from(0x7b0))?);
+
+        match dcsr.cause() {
+            // An ebreak instruction was hit
+            1 => details.breakpoint = Some(BreakpointCause::Software),
+            // Trigger module caused halt
+            2 => {
+                let (breakpoint, watchpoint_address) = self.hit_trigger()?;
+                details.breakpoint = breakpoint;
+                details.watchpoint_address = watchpoint_address;
+            }
+            _ => {}
+        }
+
+        Ok(details)
+    }
+
     /// See docs on the [`CoreInterface::get_hw_breakpoints`] trait
-    /// NOTE: For riscv, this assumes that only execution breakpoints are used.
+    /// NOTE: For riscv, the triggers are shared with the watchpoints,
+    /// triggers used for watchpoints are reported as `None`.
     fn get_hw_breakpoints(&mut self) -> Result<Vec<Option<u32>>, Error> {
-        let tselect = 0x7a0;
-        let tdata1 = 0x7a1;
-        let tdata2 = 0x7a2;
-
         let mut breakpoints = vec![];
-        let num_hw_breakpoints = self.get_available_breakpoint_units()? as usize;
-        for bp_unit_index in 0..num_hw_breakpoints {
+
+        for trigger in self.address_triggers()? {
             // Select the trigger.
-            self.write_csr(tselect, bp_unit_index as u32)?;
+            self.write_csr(TSELECT, trigger)?;
 
             // Read the trigger "configuration" data.
-            let tdata_value = Mcontrol(self.read_csr(tdata1)?);
-
-            log::warn!("Breakpoint {}: {:?}", bp_unit_index, tdata_value);
+            let tdata_value = Mcontrol(self.read_csr(TDATA1)?);
 
-            // The trigger must be active in at least a single mode
-            let trigger_any_mode_active = tdata_value.m() || tdata_value.s() || tdata_value.u();
+            log::debug!("Trigger {}: {:?}", trigger, tdata_value);
 
-            let trigger_any_action_enabled =
-                tdata_value.execute() || tdata_value.store() || tdata_value.load();
-
-            // Only return if the trigger if it is for an execution debug action in all modes.
-            if tdata_value.type_() == 0b10
-                && tdata_value.action() == 1
-                && tdata_value.match_() == 0
-                && trigger_any_mode_active
-                && trigger_any_action_enabled
-            {
-                let breakpoint = self.read_csr(tdata2)?;
+            // Only return the trigger if it is for an execution debug action in any mode.
+            if tdata_value.is_breakpoint() {
+                let breakpoint = self.read_csr(TDATA2)?;
                 breakpoints.push(Some(breakpoint));
             } else {
                 breakpoints.push(None);
@@ -1,20 +1,42 @@
+//! Sequences for the ESP32-C3.
+//!
+//! The ESP32-C3 is usually debugged through its built-in USB-JTAG bridge. The `ndmreset` bit of
+//! the debug module resets the USB peripheral as well, which drops the connection to the probe,
+//! so the system is reset by the RTC controller instead, which keeps the bridge running.
+//!
+//! The flash is programmed by a flash algorithm, which calls the SPI flash functions of the ROM
+//! loader. These only work reliably while the watchdogs of the chip are disabled, which is done
+//! after connecting and after every reset which halts the core.
+
 use std::sync::Arc;
+use std::time::Duration;
+use web_time::Instant;
 
 use super::RiscvDebugSequence;
+use crate::architecture::riscv::communication_interface::{
+    RiscvCommunicationInterface, RiscvError,
+};
+use crate::architecture::riscv::Dmstatus;
 use crate::MemoryInterface;
 
+/// `RTC_CNTL_OPTIONS0_REG`
+const RTC_CNTL_OPTIONS0: u32 = 0x6000_8000;
+/// `RTC_CNTL_SW_SYS_RST`, resets the digital system, except the RTC and the USB-JTAG bridge.
+const RTC_CNTL_SW_SYS_RST: u32 = 1 << 31;
+
+/// Maximum time for the chip to come out of reset.
+const RESET_TIMEOUT: Duration = Duration::from_millis(500);
+
 pub struct ESP32C3(());
 
 impl ESP32C3 {
     pub fn create() -> Arc<dyn RiscvDebugSequence> {
         Arc::new(Self(()))
     }
-}
 
-impl RiscvDebugSequence for ESP32C3 {
-    fn on_connect(
+    fn disable_watchdogs(
         &self,
-        interface: &mut crate::architecture::riscv::communication_interface::RiscvCommunicationInterface,
+        interface: &mut RiscvCommunicationInterface,
     ) -> Result<(), crate::Error> {
         log::info!("Disabling esp32c3 watchdogs...");
         // disable super wdt
@@ -41,3 +63,63 @@ impl RiscvDebugSequence for ESP32C3 {
         Ok(())
     }
 }
+
+impl RiscvDebugSequence for ESP32C3 {
+    fn on_connect(&self, interface: &mut RiscvCommunicationInterface) -> Result<(), crate::Error> {
+        self.disable_watchdogs(interface)
+    }
+
+    fn reset_system(
+        &self,
+        interface: &mut RiscvCommunicationInterface,
+        halt: bool,
+    ) -> Result<(), crate::Error> {
+        let mut dmcontrol = interface.dmcontrol();
+        dmcontrol.set_haltreq(halt);
+
+        interface.write_dm_register(dmcontrol)?;
+
+        // The chip is reset while the write is still in progress, so the
+        // write is not always acknowledged.
+        let options = interface.read_word_32(RTC_CNTL_OPTIONS0)?;
+        if let Err(error) =
+            interface.write_word_32(RTC_CNTL_OPTIONS0, options | RTC_CNTL_SW_SYS_RST)
+        {
+            log::debug!(
+                "Ignoring error of the write which resets the chip: {}",
+                error
+            );
+        }
+
+        // The debug module is reset together with the rest of the system, so it
+        // has to be activated again, until the hart is back.
+        let start = Instant::now();
+        loop {
+            interface.write_dm_register(dmcontrol)?;
+
+            let dmstatus: Dmstatus = interface.read_dm_register()?;
+
+            if dmstatus.allhavereset() && (!halt || dmstatus.allhalted()) {
+                break;
+            }
+
+            if start.elapsed() > RESET_TIMEOUT {
+                log::warn!("Dmstatus: {:?}", dmstatus);
+                return Err(RiscvError::RequestNotAcknowledged.into());
+            }
+        }
+
+        // acknowledge the reset, clear the halt request
+        let mut dmcontrol = interface.dmcontrol();
+        dmcontrol.set_ackhavereset(true);
+
+        interface.write_dm_register(dmcontrol)?;
+
+        // The watchdogs are enabled again by the reset, and would reset the halted chip.
+        if halt {
+            self.disable_watchdogs(interface)?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
-use super::communication_interface::RiscvCommunicationInterface;
+use super::communication_interface::{RiscvCommunicationInterface, RiscvError};
+use super::Dmstatus;
 use std::sync::Arc;
 
 pub mod esp32c3;
@@ -7,6 +8,76 @@ pub trait RiscvDebugSequence: Send + Sync {
     fn on_connect(&self, _interface: &mut RiscvCommunicationInterface) -> Result<(), crate::Error> {
         Ok(())
     }
+
+    /// Halt the hart at the first instruction after any following reset.
+    ///
+    /// The default implementation uses the `resethaltreq` bit of the debug module, and returns
+    /// [`RiscvError::ResetHaltRequestNotSupported`] if the debug module does not support it.
+    fn reset_catch_set(
+        &self,
+        interface: &mut RiscvCommunicationInterface,
+    ) -> Result<(), crate::Error> {
+        let dmstatus: Dmstatus = interface.read_dm_register()?;
+
+        if !dmstatus.hasresethaltreq() {
+            return Err(RiscvError::ResetHaltRequestNotSupported.into());
+        }
+
+        let mut dmcontrol = interface.dmcontrol();
+        dmcontrol.set_resethaltreq(true);
+
+        interface.write_dm_register(dmcontrol)?;
+
+        Ok(())
+    }
+
+    /// Stop halting the hart after a reset, which was enabled with [`reset_catch_set`].
+    ///
+    /// [`reset_catch_set`]: RiscvDebugSequence::reset_catch_set
+    fn reset_catch_clear(
+        &self,
+        interface: &mut RiscvCommunicationInterface,
+    ) -> Result<(), crate::Error> {
+        let dmstatus: Dmstatus = interface.read_dm_register()?;
+
+        if !dmstatus.hasresethaltreq() {
+            return Ok(());
+        }
+
+        let mut dmcontrol = interface.dmcontrol();
+        dmcontrol.set_clrresethaltreq(true);
+
+        interface.write_dm_register(dmcontrol)?;
+
+        Ok(())
+    }
+
+    /// Reset the whole system, i.e. the hart and its peripherals.
+    ///
+    /// If `halt` is set, the hart has to be halted directly after the reset. The reset catch
+    /// is already enabled by the caller in this case, if it is supported.
+    ///
+    /// The default implementation pulses the `ndmreset` bit of the debug module.
+    fn reset_system(
+        &self,
+        interface: &mut RiscvCommunicationInterface,
+        halt: bool,
+    ) -> Result<(), crate::Error> {
+        let mut dmcontrol = interface.dmcontrol();
+        dmcontrol.set_haltreq(halt);
+        dmcontrol.set_ndmreset(true);
+
+        interface.write_dm_register(dmcontrol)?;
+
+        // Reset is performed by setting the bit high, and then low again
+        let mut dmcontrol = interface.dmcontrol();
+        dmcontrol.set_haltreq(halt);
+        dmcontrol.set_ndmreset(false);
+
+        interface.write_dm_register(dmcontrol)?;
+
+        super::acknowledge_reset(interface, halt)
+    }
 }
 
 pub struct DefaultRiscvSequence(pub(crate) ());
@@ -0,0 +1,450 @@
+//! Support for the trigger module, which is used for hardware breakpoints,
+//! watchpoints, and single stepping on harts without support for stepping.
+//!
+//! See chapter 5 of the RISC-V debug specification 0.13.
+
+use super::communication_interface::{AbstractCommandErrorKind, RiscvError};
+use super::{Mcontrol, Riscv32};
+use crate::core::{BreakpointCause, Watchpoint, WatchpointKind};
+use crate::{CoreInterface, HaltReason};
+use bitfield::bitfield;
+use std::time::Duration;
+
+pub(super) const TSELECT: u16 = 0x7a0;
+pub(super) const TDATA1: u16 = 0x7a1;
+pub(super) const TDATA2: u16 = 0x7a2;
+const TINFO: u16 = 0x7a4;
+
+/// Address of the `dpc` register.
+const DPC: u16 = 0x7b1;
+
+/// Trigger type of an address / data match trigger.
+const TRIGGER_TYPE_MCONTROL: u32 = 2;
+
+/// Trigger type of an instruction count trigger.
+const TRIGGER_TYPE_ICOUNT: u32 = 3;
+
+/// `match` value for an exact match of the address.
+const MATCH_EQUAL: u32 = 0;
+
+/// `match` value for a match of the upper bits of the address,
+/// for a naturally aligned power-of-two (NAPOT) range.
+const MATCH_NAPOT: u32 = 1;
+
+bitfield! {
+    struct Icount(u32);
+    impl Debug;
+
+    type_, set_type: 31, 28;
+    dmode, set_dmode: 27;
+    hit, set_hit: 24;
+    count, set_count: 23, 10;
+    m, set_m: 9;
+    s, set_s: 7;
+    u, set_u: 6;
+    action, set_action: 5, 0;
+}
+
+impl Mcontrol {
+    /// A trigger which enters debug mode in all privilege modes.
+    pub(super) fn debug_trigger(match_: u32) -> Self {
+        let mut mcontrol = Mcontrol(0);
+
+        mcontrol.set_type(TRIGGER_TYPE_MCONTROL);
+        mcontrol.set_dmode(true);
+
+        // Enter debug mode
+        mcontrol.set_action(1);
+        mcontrol.set_match(match_);
+
+        mcontrol.set_m(true);
+        mcontrol.set_s(true);
+        mcontrol.set_u(true);
+
+        mcontrol
+    }
+
+    /// Check if the trigger is configured to enter debug mode in any privilege mode.
+    pub(super) fn is_debug_trigger(&self) -> bool {
+        self.type_() == TRIGGER_TYPE_MCONTROL
+            && self.action() == 1
+            && (self.m() || self.s() || self.u())
+    }
+
+    /// Check if the trigger is a breakpoint, i.e. an exact match on an executed address.
+    pub(super) fn is_breakpoint(&self) -> bool {
+        self.is_debug_trigger() && self.execute() && self.match_() == MATCH_EQUAL
+    }
+
+    /// Check if the trigger is a watchpoint, i.e. a match on a load or store address.
+    fn is_watchpoint(&self) -> bool {
+        self.is_debug_trigger() && !self.execute() && (self.load() || self.store())
+    }
+}
+
+/// The `match` and `tdata2` values to watch the `length` bytes at `address`.
+///
+/// Returns `None` if the region cannot be matched by a single trigger.
+fn encode_watch_region(address: u32, length: u32) -> Option<(u32, u32)> {
+    match length {
+        1 => Some((MATCH_EQUAL, address)),
+        length if length.is_power_of_two() && address & (length - 1) == 0 => {
+            Some((MATCH_NAPOT, address | (length / 2 - 1)))
+        }
+        _ => None,
+    }
+}
+
+/// The address and length of the region matched by a trigger, see [`encode_watch_region`].
+fn decode_watch_region(match_: u32, tdata2: u32) -> Option<(u32, u32)> {
+    match match_ {
+        MATCH_EQUAL => Some((tdata2, 1)),
+        MATCH_NAPOT if tdata2 != u32::MAX => {
+            let length = 2u32 << tdata2.trailing_ones();
+            Some((tdata2 & !(length - 1), length))
+        }
+        _ => None,
+    }
+}
+
+impl<'probe> Riscv32<'probe> {
+    /// The trigger types supported by each trigger, as a bit mask in the format of `tinfo`.
+    ///
+    /// The triggers are enumerated on first use, following the debug
+    /// specification 0.13, section 5.1 Enumeration.
+    fn trigger_types(&mut self) -> Result<Vec<u32>, crate::Error> {
+        if let Some(types) = self.interface.trigger_types() {
+            return Ok(types.clone());
+        }
+
+        log::debug!("Enumerating triggers");
+
+        let mut types = Vec::new();
+
+        loop {
+            let tselect_index = types.len() as u32;
+
+            log::debug!("Trying tselect={}", tselect_index);
+            if let Err(e) = self.write_csr(TSELECT, tselect_index) {
+                match e {
+                    RiscvError::AbstractCommand(AbstractCommandErrorKind::Exception) => break,
+                    other_error => return Err(other_error.into()),
+                }
+            }
+
+            let readback = self.read_csr(TSELECT)?;
+
+            if readback != tselect_index {
+                break;
+            }
+
+            let supported_types = match self.read_csr(TINFO) {
+                // Trigger doesn't exist
+                Ok(1) => break,
+                Ok(tinfo_val) => tinfo_val & 0xffff,
+                Err(RiscvError::AbstractCommand(AbstractCommandErrorKind::Exception)) => {
+                    // Without tinfo, only the current type of the trigger is known.
+                    let trigger_type = Mcontrol(self.read_csr(TDATA1)?).type_();
+
+                    if trigger_type == 0 {
+                        break;
+                    }
+
+                    1 << trigger_type
+                }
+                Err(other) => return Err(other.into()),
+            };
+
+            log::info!(
+                "Discovered trigger with index {} and types {:#x}",
+                tselect_index,
+                supported_types
+            );
+
+            types.push(supported_types);
+        }
+
+        log::debug!("Target supports {} triggers.", types.len());
+
+        *self.interface.trigger_types() = Some(types.clone());
+
+        Ok(types)
+    }
+
+    /// The indices of the triggers which can match addresses. These are used
+    /// both for breakpoints and watchpoints, the breakpoint and watchpoint unit
+    /// indices are indices into this list.
+    pub(super) fn address_triggers(&mut self) -> Result<Vec<u32>, crate::Error> {
+        Ok(self
+            .trigger_types()?
+            .iter()
+            .enumerate()
+            .filter(|(_, types)| *types & (1 << TRIGGER_TYPE_MCONTROL) != 0)
+            .map(|(index, _)| index as u32)
+            .collect())
+    }
+
+    /// Select the trigger of the breakpoint or watchpoint unit `unit_index`.
+    pub(super) fn select_address_trigger(&mut self, unit_index: usize) -> Result<(), crate::Error> {
+        let trigger = *self
+            .address_triggers()?
+            .get(unit_index)
+            .ok_or(crate::Error::TriggerNotFound(unit_index))?;
+
+        self.write_csr(TSELECT, trigger)?;
+
+        Ok(())
+    }
+
+    /// Configure the selected trigger, and verify that the configuration is supported.
+    pub(super) fn write_address_trigger(
+        &mut self,
+        mcontrol: Mcontrol,
+        tdata2: u32,
+    ) -> Result<Mcontrol, crate::Error> {
+        self.write_csr(TDATA1, mcontrol.0)?;
+        self.write_csr(TDATA2, tdata2)?;
+
+        let readback = Mcontrol(self.read_csr(TDATA1)?);
+
+        if readback.type_() != TRIGGER_TYPE_MCONTROL {
+            return Err(RiscvError::UnexpectedTriggerType(readback.type_()).into());
+        }
+
+        Ok(readback)
+    }
+
+    /// Disable the trigger of the breakpoint or watchpoint unit `unit_index`.
+    pub(super) fn clear_address_trigger(&mut self, unit_index: usize) -> Result<(), crate::Error> {
+        self.select_address_trigger(unit_index)?;
+
+        self.write_csr(TDATA1, 0)?;
+        self.write_csr(TDATA2, 0)?;
+
+        Ok(())
+    }
+
+    pub(super) fn read_watchpoints(&mut self) -> Result<Vec<Option<Watchpoint>>, crate::Error> {
+        let mut watchpoints = vec![];
+
+        for trigger in self.address_triggers()? {
+            self.write_csr(TSELECT, trigger)?;
+
+            let mcontrol = Mcontrol(self.read_csr(TDATA1)?);
+
+            let watchpoint = if mcontrol.is_watchpoint() {
+                let tdata2 = self.read_csr(TDATA2)?;
+
+                let kind = match (mcontrol.load(), mcontrol.store()) {
+                    (true, false) => WatchpointKind::Read,
+                    (false, true) => WatchpointKind::Write,
+                    _ => WatchpointKind::ReadWrite,
+                };
+
+                decode_watch_region(mcontrol.match_(), tdata2).map(|(address, length)| Watchpoint {
+                    address,
+                    length,
+                    kind,
+                })
+            } else {
+                None
+            };
+
+            watchpoints.push(watchpoint);
+        }
+
+        Ok(watchpoints)
+    }
+
+    pub(super) fn write_watchpoint(
+        &mut self,
+        unit_index: usize,
+        watchpoint: Watchpoint,
+    ) -> Result<(), crate::Error> {
+        let unsupported = crate::Error::UnsupportedWatchpoint {
+            address: watchpoint.address,
+            length: watchpoint.length,
+        };
+
+        let (match_, tdata2) = match encode_watch_region(watchpoint.address, watchpoint.length) {
+            Some(encoded) => encoded,
+            None => return Err(unsupported),
+        };
+
+        let load = watchpoint.kind != WatchpointKind::Write;
+        let store = watchpoint.kind != WatchpointKind::Read;
+
+        let mut mcontrol = Mcontrol::debug_trigger(match_);
+        mcontrol.set_load(load);
+        mcontrol.set_store(store);
+
+        self.select_address_trigger(unit_index)?;
+        let readback = self.write_address_trigger(mcontrol, tdata2)?;
+
+        // Not all triggers support matching on data accesses, or all match types.
+        if readback.load() != load || readback.store() != store || readback.match_() != match_ {
+            self.clear_address_trigger(unit_index)?;
+            return Err(unsupported);
+        }
+
+        Ok(())
+    }
+
+    /// Determine which trigger caused a halt with the cause `trigger`.
+    ///
+    /// The `hit` bits of the triggers are optional, so a breakpoint is assumed
+    /// if the trigger cannot be determined.
+    pub(super) fn trigger_halt_reason(&mut self) -> Result<HaltReason, crate::Error> {
+        let dpc = self.read_csr(DPC)?;
+
+        let mut reason = HaltReason::Breakpoint;
+
+        for (trigger, types) in self.trigger_types()?.into_iter().enumerate() {
+            if types & ((1 << TRIGGER_TYPE_MCONTROL) | (1 << TRIGGER_TYPE_ICOUNT)) == 0 {
+                continue;
+            }
+
+            self.write_csr(TSELECT, trigger as u32)?;
+            let tdata1 = self.read_csr(TDATA1)?;
+
+            let mcontrol = Mcontrol(tdata1);
+
+            if mcontrol.is_breakpoint() && self.read_csr(TDATA2)? == dpc {
+                return Ok(HaltReason::Breakpoint);
+            } else if mcontrol.is_watchpoint() && mcontrol.hit() {
+                reason = HaltReason::Watchpoint;
+            } else if mcontrol.type_() == TRIGGER_TYPE_ICOUNT
+                && reason == HaltReason::Breakpoint
+                && Icount(tdata1).hit()
+            {
+                reason = HaltReason::Step;
+            }
+        }
+
+        Ok(reason)
+    }
+
+    /// Find the trigger which halted the hart, and return the kind of breakpoint if it is an
+    /// execution trigger, or the address if it is a watchpoint.
+    pub(super) fn hit_trigger(
+        &mut self,
+    ) -> Result<(Option<BreakpointCause>, Option<u32>), crate::Error> {
+        let dpc = self.read_csr(DPC)?;
+
+        for (trigger, types) in self.trigger_types()?.into_iter().enumerate() {
+            if types & (1 << TRIGGER_TYPE_MCONTROL) == 0 {
+                continue;
+            }
+
+            self.write_csr(TSELECT, trigger as u32)?;
+            let mcontrol = Mcontrol(self.read_csr(TDATA1)?);
+            let address = self.read_csr(TDATA2)?;
+
+            if mcontrol.is_breakpoint() && address == dpc {
+                return Ok((Some(BreakpointCause::Hardware), None));
+            } else if mcontrol.is_watchpoint() && mcontrol.hit() {
+                return Ok((None, Some(address)));
+            }
+        }
+
+        Ok((None, None))
+    }
+
+    /// Execute a single instruction using an `icount` trigger,
+    /// for harts which do not support stepping using `dcsr`.
+    pub(super) fn step_with_icount(&mut self) -> Result<(), crate::Error> {
+        let trigger = self
+            .free_icount_trigger()?
+            .ok_or(RiscvError::StepNotSupported)?;
+
+        log::debug!("Stepping using icount trigger {}", trigger);
+
+        let mut icount = Icount(0);
+        icount.set_type(TRIGGER_TYPE_ICOUNT);
+        icount.set_dmode(true);
+        icount.set_count(1);
+        icount.set_m(true);
+        icount.set_s(true);
+        icount.set_u(true);
+
+        // Enter debug mode
+        icount.set_action(1);
+
+        self.write_csr(TSELECT, trigger)?;
+        self.write_csr(TDATA1, icount.0)?;
+
+        let result = self
+            .run()
+            .and_then(|_| self.wait_for_core_halted(Duration::from_millis(100)));
+
+        // Disable the trigger again, even if the step failed. The hit bit
+        // is kept, so that the halt reason can be determined.
+        let disabled = self.write_csr(TSELECT, trigger).and_then(|_| {
+            let mut icount = Icount(self.read_csr(TDATA1)?);
+            icount.set_m(false);
+            icount.set_s(false);
+            icount.set_u(false);
+            self.write_csr(TDATA1, icount.0)
+        });
+
+        result?;
+        disabled?;
+
+        Ok(())
+    }
+
+    /// Find a trigger supporting `icount`, which is not used for a breakpoint or watchpoint.
+    fn free_icount_trigger(&mut self) -> Result<Option<u32>, crate::Error> {
+        let types = self.trigger_types()?;
+
+        // Use the last triggers first, as the breakpoints use the first ones.
+        for (trigger, types) in types.into_iter().enumerate().rev() {
+            if types & (1 << TRIGGER_TYPE_ICOUNT) == 0 {
+                continue;
+            }
+
+            self.write_csr(TSELECT, trigger as u32)?;
+
+            if !Mcontrol(self.read_csr(TDATA1)?).is_debug_trigger() {
+                return Ok(Some(trigger as u32));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode_watch_region, encode_watch_region, MATCH_EQUAL, MATCH_NAPOT};
+
+    #[test]
+    fn encode_napot_regions() {
+        assert_eq!(
+            encode_watch_region(0x2000_0003, 1),
+            Some((MATCH_EQUAL, 0x2000_0003))
+        );
+        assert_eq!(
+            encode_watch_region(0x2000_0000, 2),
+            Some((MATCH_NAPOT, 0x2000_0000))
+        );
+        assert_eq!(
+            encode_watch_region(0x2000_0010, 16),
+            Some((MATCH_NAPOT, 0x2000_0017))
+        );
+
+        // Unaligned or not a power of two
+        assert_eq!(encode_watch_region(0x2000_0002, 4), None);
+        assert_eq!(encode_watch_region(0x2000_0000, 12), None);
+        assert_eq!(encode_watch_region(0x2000_0000, 0), None);
+    }
+
+    #[test]
+    fn decode_napot_regions() {
+        for &(address, length) in &[(0x2000_0003, 1), (0x2000_0000, 2), (0x2000_0010, 16)] {
+            let (match_, tdata2) = encode_watch_region(address, length).unwrap();
+
+            assert_eq!(decode_watch_region(match_, tdata2), Some((address, length)));
+        }
+    }
+}
@@ -0,0 +1,572 @@
+//! Xtensa Debug Module Communication
+//!
+//! This module implements communication with the debug module of
+//! an Xtensa core, as described in the Xtensa Debug Guide. Registers
+//! and memory are accessed by executing instructions on the halted
+//! core, and transferring the data through the Debug Data Register (DDR).
+
+use std::time::Duration;
+use web_time::Instant;
+
+use super::{
+    instruction::{special_register, Instruction},
+    xdm::{DebugControl, DebugRegister, DebugStatus, PowerControl, PowerStatus, Xdm},
+    NUM_AREGS,
+};
+use crate::{probe::JTAGAccess, DebugProbeError, Error as ProbeRsError, MemoryInterface};
+
+#[derive(thiserror::Error, Debug)]
+pub enum XtensaError {
+    #[error("Debug Probe Error")]
+    DebugProbe(#[from] DebugProbeError),
+    #[error("Timeout during access to the debug module.")]
+    Timeout,
+    #[error("The debug module is busy.")]
+    XdmBusy,
+    #[error("An access to the debug module failed.")]
+    XdmAccessFailed,
+    #[error("The debug power domain could not be powered up.")]
+    DebugDomainOff,
+    #[error("An instruction executed by the debugger caused an exception.")]
+    ExecException,
+    #[error("The core has to be halted for this operation.")]
+    NotHalted,
+    #[error("Memory access at {0:#010x} is not aligned to 4 bytes.")]
+    UnalignedAccess(u32),
+    #[error("Address register ar{0} does not exist.")]
+    RegisterNotFound(u16),
+    #[error("Halting the core after any reset is not supported.")]
+    ResetCatchNotSupported,
+}
+
+impl From<XtensaError> for DebugProbeError {
+    fn from(err: XtensaError) -> Self {
+        match err {
+            XtensaError::DebugProbe(e) => e,
+            other => DebugProbeError::ArchitectureSpecific(Box::new(other)),
+        }
+    }
+}
+
+impl From<XtensaError> for ProbeRsError {
+    fn from(err: XtensaError) -> Self {
+        match err {
+            XtensaError::DebugProbe(e) => ProbeRsError::Probe(e),
+            other => ProbeRsError::ArchitectureSpecific(Box::new(other)),
+        }
+    }
+}
+
+/// Timeout for the execution of instructions and for halting the core.
+const XTENSA_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The address register used as scratch register to access special
+/// registers and memory. Its value is saved before the first use, and
+/// restored before the core is resumed.
+const SCRATCH_REGISTER: u8 = 3;
+
+#[derive(Debug, Default)]
+pub struct XtensaCommunicationInterfaceState {
+    /// The value of the scratch register, if it was modified since the core halted.
+    saved_scratch: Option<u32>,
+}
+
+#[derive(Debug)]
+pub struct XtensaCommunicationInterface {
+    /// The Xtensa Debug Module (XDM), accessed through JTAG.
+    xdm: Xdm,
+    state: XtensaCommunicationInterfaceState,
+}
+
+impl XtensaCommunicationInterface {
+    pub fn new(probe: Box<dyn JTAGAccess>) -> Result<Self, (Box<dyn JTAGAccess>, DebugProbeError)> {
+        let mut s = Self {
+            xdm: Xdm::new(probe),
+            state: XtensaCommunicationInterfaceState::default(),
+        };
+
+        if let Err(err) = s.enter_ocd_mode() {
+            return Err((s.xdm.probe, err.into()));
+        }
+
+        Ok(s)
+    }
+
+    pub fn read_idcode(&mut self) -> Result<u32, DebugProbeError> {
+        self.xdm.read_idcode()
+    }
+
+    /// Power up the debug domain, and enable the On-Chip Debug (OCD) mode.
+    fn enter_ocd_mode(&mut self) -> Result<(), XtensaError> {
+        log::debug!("Building Xtensa interface");
+
+        // The first write wakes up the power domains, the power
+        // control register is only reliably set after the second write.
+        self.xdm
+            .write_power_control(PowerControl::debug_domains_on())?;
+        self.xdm
+            .write_power_control(PowerControl::debug_domains_on())?;
+
+        // Clear the sticky reset bits from previous resets.
+        let mut clear = PowerStatus(0);
+        clear.set_core_was_reset(true);
+        clear.set_debug_was_reset(true);
+        let status = self.xdm.write_power_status(clear)?;
+
+        log::debug!("Power status: {:?}", status);
+
+        if !status.debug_domain_on() {
+            return Err(XtensaError::DebugDomainOff);
+        }
+
+        self.enable_ocd()?;
+
+        let ocd_id = self.xdm.read_register(DebugRegister::OcdId)?;
+        log::debug!("OCD ID: {:#010x}", ocd_id);
+
+        Ok(())
+    }
+
+    fn enable_ocd(&mut self) -> Result<(), XtensaError> {
+        let mut control = DebugControl(0);
+        control.set_enable_ocd(true);
+
+        self.xdm.write_register(DebugRegister::DcrSet, control.0)
+    }
+
+    fn read_debug_status(&mut self) -> Result<DebugStatus, XtensaError> {
+        Ok(DebugStatus(self.xdm.read_register(DebugRegister::Dsr)?))
+    }
+
+    /// Check if the core is halted, i.e. running in OCD mode.
+    pub fn is_halted(&mut self) -> Result<bool, XtensaError> {
+        Ok(self.read_debug_status()?.stopped())
+    }
+
+    /// Check if the core is powered down, e.g. in a light sleep mode.
+    pub fn is_powered_down(&mut self) -> Result<bool, XtensaError> {
+        Ok(!self.xdm.read_power_status()?.core_domain_on())
+    }
+
+    pub fn wait_for_halted(&mut self, timeout: Duration) -> Result<(), XtensaError> {
+        let start = Instant::now();
+
+        while start.elapsed() < timeout {
+            if self.is_halted()? {
+                return Ok(());
+            }
+        }
+
+        Err(XtensaError::Timeout)
+    }
+
+    /// Request the core to halt, using a debug interrupt.
+    pub fn halt(&mut self, timeout: Duration) -> Result<(), XtensaError> {
+        let mut control = DebugControl(0);
+        control.set_debug_interrupt(true);
+
+        self.xdm.write_register(DebugRegister::DcrSet, control.0)?;
+
+        self.wait_for_halted(timeout)?;
+
+        // Clear the request, otherwise the core halts again directly after resuming.
+        self.xdm.write_register(DebugRegister::DcrClr, control.0)
+    }
+
+    /// Restore the scratch register, and resume the core.
+    pub fn resume(&mut self) -> Result<(), XtensaError> {
+        self.restore_scratch()?;
+
+        let mut clear = DebugStatus::clear_exec_status();
+        clear.set_debug_int_break(true);
+        clear.set_debug_int_host(true);
+        self.xdm.write_register(DebugRegister::Dsr, clear.0)?;
+
+        // The core leaves OCD mode, so there is no need to wait for the execution.
+        self.xdm
+            .write_register(DebugRegister::Dir0Exec, Instruction::rfdo().0)
+    }
+
+    /// Reset the core, using the core reset bit of the power control register.
+    ///
+    /// If `halt` is set, a debug interrupt is requested during the reset, so that
+    /// the core halts at the first instruction.
+    pub fn reset(&mut self, halt: bool) -> Result<(), XtensaError> {
+        let mut control = DebugControl(0);
+        control.set_debug_interrupt(true);
+
+        if halt {
+            self.xdm.write_register(DebugRegister::DcrSet, control.0)?;
+        }
+
+        let mut reset = PowerControl::debug_domains_on();
+        reset.set_core_reset(true);
+
+        self.xdm.write_power_control(reset)?;
+        self.xdm
+            .write_power_control(PowerControl::debug_domains_on())?;
+
+        // The registers of the core have been reset, there is nothing to restore anymore.
+        self.state.saved_scratch = None;
+
+        let mut clear = PowerStatus(0);
+        clear.set_core_was_reset(true);
+        self.xdm.write_power_status(clear)?;
+
+        self.enable_ocd()?;
+
+        if halt {
+            self.wait_for_halted(XTENSA_TIMEOUT)?;
+            self.xdm.write_register(DebugRegister::DcrClr, control.0)?;
+        }
+
+        Ok(())
+    }
+
+    /// Execute an instruction on the halted core.
+    fn execute(&mut self, instruction: Instruction) -> Result<(), XtensaError> {
+        self.xdm
+            .write_register(DebugRegister::Dsr, DebugStatus::clear_exec_status().0)?;
+        self.xdm
+            .write_register(DebugRegister::Dir0Exec, instruction.0)?;
+
+        let start = Instant::now();
+
+        loop {
+            let status = self.read_debug_status()?;
+
+            if status.exec_exception() {
+                log::debug!("Executing {:#08x} failed: {:?}", instruction.0, status);
+
+                self.xdm
+                    .write_register(DebugRegister::Dsr, DebugStatus::clear_exec_status().0)?;
+
+                return Err(XtensaError::ExecException);
+            }
+
+            if !status.exec_busy() {
+                return Ok(());
+            }
+
+            if start.elapsed() > XTENSA_TIMEOUT {
+                return Err(XtensaError::Timeout);
+            }
+        }
+    }
+
+    fn read_ddr(&mut self) -> Result<u32, XtensaError> {
+        self.xdm.read_register(DebugRegister::Ddr)
+    }
+
+    fn write_ddr(&mut self, value: u32) -> Result<(), XtensaError> {
+        self.xdm.write_register(DebugRegister::Ddr, value)
+    }
+
+    /// Read an address register of the current window, ignoring the saved scratch value.
+    fn read_ar_raw(&mut self, register: u8) -> Result<u32, XtensaError> {
+        self.execute(Instruction::wsr(special_register::DDR, register))?;
+        self.read_ddr()
+    }
+
+    /// Write an address register of the current window, ignoring the saved scratch value.
+    fn write_ar_raw(&mut self, register: u8, value: u32) -> Result<(), XtensaError> {
+        self.write_ddr(value)?;
+        self.execute(Instruction::rsr(special_register::DDR, register))
+    }
+
+    /// Save the value of the scratch register, before it is modified.
+    fn save_scratch(&mut self) -> Result<(), XtensaError> {
+        if self.state.saved_scratch.is_none() {
+            self.state.saved_scratch = Some(self.read_ar_raw(SCRATCH_REGISTER)?);
+        }
+
+        Ok(())
+    }
+
+    fn restore_scratch(&mut self) -> Result<(), XtensaError> {
+        if let Some(value) = self.state.saved_scratch.take() {
+            self.write_ar_raw(SCRATCH_REGISTER, value)?;
+        }
+
+        Ok(())
+    }
+
+    /// Read the address register `a<register>` of the current window.
+    pub fn read_ar(&mut self, register: u8) -> Result<u32, XtensaError> {
+        match self.state.saved_scratch {
+            Some(value) if register == SCRATCH_REGISTER => Ok(value),
+            _ => self.read_ar_raw(register),
+        }
+    }
+
+    /// Write the address register `a<register>` of the current window.
+    pub fn write_ar(&mut self, register: u8, value: u32) -> Result<(), XtensaError> {
+        match &mut self.state.saved_scratch {
+            Some(saved) if register == SCRATCH_REGISTER => {
+                *saved = value;
+                Ok(())
+            }
+            _ => self.write_ar_raw(register, value),
+        }
+    }
+
+    /// Read a special register.
+    pub fn read_sr(&mut self, register: u8) -> Result<u32, XtensaError> {
+        self.save_scratch()?;
+
+        self.execute(Instruction::rsr(register, SCRATCH_REGISTER))?;
+        self.read_ar_raw(SCRATCH_REGISTER)
+    }
+
+    /// Write a special register.
+    pub fn write_sr(&mut self, register: u8, value: u32) -> Result<(), XtensaError> {
+        self.save_scratch()?;
+
+        self.write_ar_raw(SCRATCH_REGISTER, value)?;
+        self.execute(Instruction::wsr(register, SCRATCH_REGISTER))?;
+
+        // Changes of e.g. the break registers only take effect after an ISYNC.
+        self.execute(Instruction::isync())
+    }
+
+    /// Run `access` on a window which contains the physical address register `ar<register>`,
+    /// with the index of the register in this window. The last argument is set if the
+    /// window is the current window of the core.
+    fn with_physical_ar<T>(
+        &mut self,
+        register: u16,
+        access: impl FnOnce(&mut Self, u8, bool) -> Result<T, XtensaError>,
+    ) -> Result<T, XtensaError> {
+        if register >= NUM_AREGS {
+            return Err(XtensaError::RegisterNotFound(register));
+        }
+
+        let window_base = self.read_sr(special_register::WINDOWBASE)? as u16;
+        let offset = physical_to_window_offset(register, window_base);
+
+        if offset < 16 {
+            return access(self, offset as u8, true);
+        }
+
+        // Rotate the window, so that it starts at the register.
+        let rotation = (offset / 4) as i8;
+
+        self.execute(Instruction::rotw(rotation))?;
+
+        let result = access(self, (offset % 4) as u8, false);
+
+        self.execute(Instruction::rotw(-rotation))?;
+
+        result
+    }
+
+    /// Read the physical address register `ar<register>`, independent of the current window.
+    pub fn read_physical_ar(&mut self, register: u16) -> Result<u32, XtensaError> {
+        self.with_physical_ar(register, |interface, index, current_window| {
+            if current_window {
+                interface.read_ar(index)
+            } else {
+                // The saved scratch register belongs to the current window only.
+                interface.read_ar_raw(index)
+            }
+        })
+    }
+
+    /// Write the physical address register `ar<register>`, independent of the current window.
+    pub fn write_physical_ar(&mut self, register: u16, value: u32) -> Result<(), XtensaError> {
+        self.with_physical_ar(register, |interface, index, current_window| {
+            if current_window {
+                interface.write_ar(index, value)
+            } else {
+                interface.write_ar_raw(index, value)
+            }
+        })
+    }
+
+    fn ensure_halted(&mut self) -> Result<(), XtensaError> {
+        if self.is_halted()? {
+            Ok(())
+        } else {
+            Err(XtensaError::NotHalted)
+        }
+    }
+
+    fn read_words(&mut self, address: u32, data: &mut [u32]) -> Result<(), XtensaError> {
+        if address & 0x3 != 0 {
+            return Err(XtensaError::UnalignedAccess(address));
+        }
+
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        self.ensure_halted()?;
+        self.save_scratch()?;
+
+        self.write_ar_raw(SCRATCH_REGISTER, address)?;
+
+        for word in data.iter_mut() {
+            // The scratch register is incremented by each load.
+            self.execute(Instruction::lddr32_p(SCRATCH_REGISTER))?;
+            *word = self.read_ddr()?;
+        }
+
+        Ok(())
+    }
+
+    fn write_words(&mut self, address: u32, data: &[u32]) -> Result<(), XtensaError> {
+        if address & 0x3 != 0 {
+            return Err(XtensaError::UnalignedAccess(address));
+        }
+
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        self.ensure_halted()?;
+        self.save_scratch()?;
+
+        self.write_ar_raw(SCRATCH_REGISTER, address)?;
+
+        for word in data {
+            self.write_ddr(*word)?;
+            self.execute(Instruction::sddr32_p(SCRATCH_REGISTER))?;
+        }
+
+        Ok(())
+    }
+
+    /// Read the words covering `data.len()` bytes at `address`.
+    fn read_bytes(&mut self, address: u32, data: &mut [u8]) -> Result<(), XtensaError> {
+        let (start, word_count) = covering_words(address, data.len());
+
+        let mut words = vec![0u32; word_count];
+        self.read_words(start, &mut words)?;
+
+        let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+        let offset = (address - start) as usize;
+
+        data.copy_from_slice(&bytes[offset..offset + data.len()]);
+
+        Ok(())
+    }
+
+    /// Write `data` at `address`, using read-modify-write for partially written words.
+    fn write_bytes(&mut self, address: u32, data: &[u8]) -> Result<(), XtensaError> {
+        let (start, word_count) = covering_words(address, data.len());
+
+        let mut words = vec![0u32; word_count];
+        let offset = (address - start) as usize;
+
+        if offset != 0 || data.len() & 0x3 != 0 {
+            self.read_words(start, &mut words)?;
+        }
+
+        let mut bytes: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+        bytes[offset..offset + data.len()].copy_from_slice(data);
+
+        for (word, chunk) in words.iter_mut().zip(bytes.chunks_exact(4)) {
+            *word = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+
+        self.write_words(start, &words)
+    }
+}
+
+impl MemoryInterface for XtensaCommunicationInterface {
+    fn read_word_32(&mut self, address: u32) -> Result<u32, ProbeRsError> {
+        let mut data = [0u32];
+        self.read_words(address, &mut data)?;
+
+        Ok(data[0])
+    }
+
+    fn read_word_8(&mut self, address: u32) -> Result<u8, ProbeRsError> {
+        let mut data = [0u8];
+        self.read_bytes(address, &mut data)?;
+
+        Ok(data[0])
+    }
+
+    fn read_32(&mut self, address: u32, data: &mut [u32]) -> Result<(), ProbeRsError> {
+        self.read_words(address, data)?;
+
+        Ok(())
+    }
+
+    fn read_8(&mut self, address: u32, data: &mut [u8]) -> Result<(), ProbeRsError> {
+        self.read_bytes(address, data)?;
+
+        Ok(())
+    }
+
+    fn write_word_32(&mut self, address: u32, data: u32) -> Result<(), ProbeRsError> {
+        self.write_words(address, &[data])?;
+
+        Ok(())
+    }
+
+    fn write_word_8(&mut self, address: u32, data: u8) -> Result<(), ProbeRsError> {
+        self.write_bytes(address, &[data])?;
+
+        Ok(())
+    }
+
+    fn write_32(&mut self, address: u32, data: &[u32]) -> Result<(), ProbeRsError> {
+        self.write_words(address, data)?;
+
+        Ok(())
+    }
+
+    fn write_8(&mut self, address: u32, data: &[u8]) -> Result<(), ProbeRsError> {
+        self.write_bytes(address, data)?;
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), ProbeRsError> {
+        Ok(())
+    }
+}
+
+/// The offset of the physical address register `ar<register>` from the start
+/// of the current register window, which starts at `ar<4 * window_base>`.
+fn physical_to_window_offset(register: u16, window_base: u16) -> u16 {
+    (register + NUM_AREGS - (window_base * 4) % NUM_AREGS) % NUM_AREGS
+}
+
+/// The address of the first word, and the number of words covering `length` bytes at `address`.
+fn covering_words(address: u32, length: usize) -> (u32, usize) {
+    let start = address & !0x3;
+    let end = address as u64 + length as u64;
+
+    (start, ((end - start as u64) as usize).div_ceil(4))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{covering_words, physical_to_window_offset};
+
+    #[test]
+    fn physical_registers_in_window() {
+        // Window starting at ar0
+        assert_eq!(physical_to_window_offset(0, 0), 0);
+        assert_eq!(physical_to_window_offset(15, 0), 15);
+        assert_eq!(physical_to_window_offset(63, 0), 63);
+
+        // Window starting at ar8, the window wraps around
+        assert_eq!(physical_to_window_offset(8, 2), 0);
+        assert_eq!(physical_to_window_offset(4, 2), 60);
+
+        // Window starting at ar60
+        assert_eq!(physical_to_window_offset(60, 15), 0);
+        assert_eq!(physical_to_window_offset(2, 15), 6);
+    }
+
+    #[test]
+    fn covering_words_for_unaligned_access() {
+        assert_eq!(covering_words(0x3ffb_0000, 4), (0x3ffb_0000, 1));
+        assert_eq!(covering_words(0x3ffb_0003, 2), (0x3ffb_0000, 2));
+        assert_eq!(covering_words(0x3ffb_0001, 8), (0x3ffb_0000, 3));
+    }
+}
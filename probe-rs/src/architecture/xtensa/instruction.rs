@@ -0,0 +1,91 @@
+//! Encoding of the Xtensa instructions, which are executed
+//! by the debug module to access registers and memory.
+
+/// Special register numbers, as used by the `RSR` and `WSR` instructions.
+pub(super) mod special_register {
+    pub const LBEG: u8 = 0;
+    pub const LEND: u8 = 1;
+    pub const LCOUNT: u8 = 2;
+    pub const SAR: u8 = 3;
+    pub const WINDOWBASE: u8 = 72;
+    pub const WINDOWSTART: u8 = 73;
+    pub const IBREAKENABLE: u8 = 96;
+    /// The Debug Data Register, used to transfer data between the debugger and the core.
+    pub const DDR: u8 = 104;
+    pub const IBREAKA0: u8 = 128;
+    pub const DBREAKA0: u8 = 144;
+    pub const DBREAKC0: u8 = 160;
+    pub const EPC1: u8 = 177;
+    pub const EPS2: u8 = 194;
+    pub const EXCCAUSE: u8 = 232;
+    pub const DEBUGCAUSE: u8 = 233;
+    pub const ICOUNT: u8 = 236;
+    pub const ICOUNTLEVEL: u8 = 237;
+    pub const EXCVADDR: u8 = 238;
+}
+
+/// An Xtensa instruction, in the 24 bit encoding.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(super) struct Instruction(pub u32);
+
+impl Instruction {
+    /// `RSR at, sr`: Read special register `sr` into `at`.
+    pub fn rsr(special_register: u8, at: u8) -> Self {
+        Self(0x03_0000 | (special_register as u32) << 8 | (at as u32 & 0xf) << 4)
+    }
+
+    /// `WSR at, sr`: Write `at` to special register `sr`.
+    pub fn wsr(special_register: u8, at: u8) -> Self {
+        Self(0x13_0000 | (special_register as u32) << 8 | (at as u32 & 0xf) << 4)
+    }
+
+    /// `RFDO 0`: Return from debug mode.
+    pub fn rfdo() -> Self {
+        Self(0xf1_e000)
+    }
+
+    /// `ISYNC`: Wait until preceding instruction fetches are done.
+    pub fn isync() -> Self {
+        Self(0x00_2000)
+    }
+
+    /// `ROTW n`: Rotate the register window by `n` * 4 registers.
+    pub fn rotw(n: i8) -> Self {
+        Self(0x40_8000 | (n as u32 & 0xf) << 4)
+    }
+
+    /// `LDDR32.P as`: Load the word at the address in `as` into DDR, and increment `as` by 4.
+    pub fn lddr32_p(address_register: u8) -> Self {
+        Self(0x00_70e0 | (address_register as u32 & 0xf) << 8)
+    }
+
+    /// `SDDR32.P as`: Store DDR to the word at the address in `as`, and increment `as` by 4.
+    pub fn sddr32_p(address_register: u8) -> Self {
+        Self(0x00_70f0 | (address_register as u32 & 0xf) << 8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{special_register, Instruction};
+
+    #[test]
+    fn encode_instructions() {
+        // wsr a3, ddr
+        assert_eq!(
+            Instruction::wsr(special_register::DDR, 3),
+            Instruction(0x13_6830)
+        );
+        // rsr a3, ddr
+        assert_eq!(
+            Instruction::rsr(special_register::DDR, 3),
+            Instruction(0x03_6830)
+        );
+        // rotw -1
+        assert_eq!(Instruction::rotw(-1), Instruction(0x40_80f0));
+        // lddr32.p a3
+        assert_eq!(Instruction::lddr32_p(3), Instruction(0x00_73e0));
+        // sddr32.p a3
+        assert_eq!(Instruction::sddr32_p(3), Instruction(0x00_73f0));
+    }
+}
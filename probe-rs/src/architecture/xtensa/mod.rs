@@ -0,0 +1,461 @@
+//! Xtensa Support
+//!
+//! The Xtensa cores are debugged through the On-Chip Debug module, which is accessed through JTAG.
+//! The supported configuration is the one of the LX6 and LX7 cores used in the ESP32 and ESP32-S3.
+
+use crate::core::{
+    Architecture, BreakpointCause, CoreInformation, HaltDetails, RegisterFile, Watchpoint,
+    WatchpointKind,
+};
+use crate::{
+    CoreInterface, CoreRegisterAddress, CoreStatus, Error, HaltReason, MemoryInterface, ResetKind,
+};
+use anyhow::Result;
+use communication_interface::XtensaCommunicationInterface;
+use instruction::special_register;
+pub(crate) use register::XTENSA_REGISTERS;
+use register::{PHYSICAL_AR_BASE, SPECIAL_REGISTER_BASE};
+use sequences::XtensaDebugSequence;
+use std::sync::Arc;
+use std::time::Duration;
+
+mod instruction;
+mod register;
+mod xdm;
+
+pub mod communication_interface;
+pub mod sequences;
+
+/// The interrupt level of the debug exception.
+const DEBUG_LEVEL: u8 = 6;
+
+/// The number of physical address registers.
+const NUM_AREGS: u16 = 64;
+
+/// The number of instruction breakpoints.
+const NUM_IBREAK: u32 = 2;
+
+/// The number of data breakpoints.
+const NUM_DBREAK: u32 = 2;
+
+/// Bits of the `DEBUGCAUSE` register
+mod debug_cause {
+    pub const ICOUNT: u32 = 1 << 0;
+    pub const IBREAK: u32 = 1 << 1;
+    pub const DBREAK: u32 = 1 << 2;
+    pub const BREAK: u32 = 1 << 3;
+    pub const BREAKN: u32 = 1 << 4;
+    pub const DEBUGINT: u32 = 1 << 5;
+    /// The number of the `DBREAK` unit which matched is stored in bits 11:8.
+    pub const DBNUM_SHIFT: u32 = 8;
+}
+
+/// Bits of the `DBREAKC` registers
+mod dbreakc {
+    /// The mask of the address bits which are compared.
+    pub const MASK: u32 = 0x3f;
+    pub const LOAD: u32 = 1 << 30;
+    pub const STORE: u32 = 1 << 31;
+}
+
+pub struct Xtensa<'probe> {
+    interface: &'probe mut XtensaCommunicationInterface,
+
+    sequence: Arc<dyn XtensaDebugSequence>,
+}
+
+impl<'probe> Xtensa<'probe> {
+    pub fn new(
+        interface: &'probe mut XtensaCommunicationInterface,
+        sequence: Arc<dyn XtensaDebugSequence>,
+    ) -> Self {
+        Self {
+            interface,
+            sequence,
+        }
+    }
+
+    fn read_pc(&mut self) -> Result<u32, Error> {
+        self.read_core_reg(XTENSA_REGISTERS.program_counter.address)
+    }
+}
+
+impl<'probe> CoreInterface for Xtensa<'probe> {
+    fn wait_for_core_halted(&mut self, timeout: Duration) -> Result<(), Error> {
+        self.interface.wait_for_halted(timeout)?;
+
+        Ok(())
+    }
+
+    fn core_halted(&mut self) -> Result<bool, Error> {
+        Ok(self.interface.is_halted()?)
+    }
+
+    fn status(&mut self) -> Result<CoreStatus, Error> {
+        if self.interface.is_halted()? {
+            let cause = self.interface.read_sr(special_register::DEBUGCAUSE)?;
+
+            Ok(CoreStatus::Halted(halt_reason(cause)))
+        } else if self.interface.is_powered_down()? {
+            Ok(CoreStatus::Sleeping)
+        } else {
+            Ok(CoreStatus::Running)
+        }
+    }
+
+    fn halt_details(&mut self) -> Result<HaltDetails, Error> {
+        if !self.interface.is_halted()? {
+            return Ok(HaltDetails::new(HaltReason::Unknown));
+        }
+
+        let cause = self.interface.read_sr(special_register::DEBUGCAUSE)?;
+        let mut details = HaltDetails::new(halt_reason(cause));
+
+        if cause & debug_cause::IBREAK != 0 {
+            details.breakpoint = Some(BreakpointCause::Hardware);
+        } else if cause & (debug_cause::BREAK | debug_cause::BREAKN) != 0 {
+            details.breakpoint = Some(BreakpointCause::Software);
+        }
+
+        if cause & debug_cause::DBREAK != 0 {
+            let unit = ((cause >> debug_cause::DBNUM_SHIFT) & 0xf) as u8;
+            details.watchpoint_address =
+                Some(self.interface.read_sr(special_register::DBREAKA0 + unit)?);
+        }
+
+        Ok(details)
+    }
+
+    fn halt(&mut self, timeout: Duration) -> Result<CoreInformation, Error> {
+        self.interface.halt(timeout)?;
+
+        let pc = self.read_pc()?;
+
+        Ok(CoreInformation { pc })
+    }
+
+    fn run(&mut self) -> Result<(), Error> {
+        self.interface.resume()?;
+
+        Ok(())
+    }
+
+    fn reset(&mut self) -> Result<(), Error> {
+        self.reset_with(ResetKind::Core)
+    }
+
+    fn reset_and_halt(&mut self, timeout: Duration) -> Result<CoreInformation, Error> {
+        self.reset_and_halt_with(ResetKind::Core, timeout)
+    }
+
+    fn reset_with(&mut self, kind: ResetKind) -> Result<(), Error> {
+        match kind {
+            ResetKind::Core => Ok(self.interface.reset(false)?),
+            _ => Err(Error::UnsupportedResetKind(kind)),
+        }
+    }
+
+    fn reset_and_halt_with(
+        &mut self,
+        kind: ResetKind,
+        _timeout: Duration,
+    ) -> Result<CoreInformation, Error> {
+        match kind {
+            ResetKind::Core => self.interface.reset(true)?,
+            _ => return Err(Error::UnsupportedResetKind(kind)),
+        }
+
+        let pc = self.read_pc()?;
+
+        Ok(CoreInformation { pc })
+    }
+
+    fn enable_reset_catch(&mut self) -> Result<(), Error> {
+        self.sequence.reset_catch_set(self.interface)
+    }
+
+    fn disable_reset_catch(&mut self) -> Result<(), Error> {
+        self.sequence.reset_catch_clear(self.interface)
+    }
+
+    fn step(&mut self) -> Result<CoreInformation, Error> {
+        // The icount register is incremented for every instruction executed with an
+        // interrupt level below ICOUNTLEVEL, and halts the core when it overflows.
+        self.interface
+            .write_sr(special_register::ICOUNTLEVEL, DEBUG_LEVEL as u32)?;
+        self.interface
+            .write_sr(special_register::ICOUNT, -2i32 as u32)?;
+
+        self.run()?;
+        self.wait_for_core_halted(Duration::from_millis(100))?;
+
+        self.interface.write_sr(special_register::ICOUNTLEVEL, 0)?;
+
+        let pc = self.read_pc()?;
+
+        Ok(CoreInformation { pc })
+    }
+
+    fn read_core_reg(&mut self, address: CoreRegisterAddress) -> Result<u32, Error> {
+        let value = match address.0 {
+            register @ 0..=0xf => self.interface.read_ar(register as u8)?,
+            register @ SPECIAL_REGISTER_BASE..=0x1ff => self
+                .interface
+                .read_sr((register - SPECIAL_REGISTER_BASE) as u8)?,
+            register @ PHYSICAL_AR_BASE..=0x2ff => self
+                .interface
+                .read_physical_ar(register - PHYSICAL_AR_BASE)?,
+            _ => return Err(Error::UnknownRegister(address.0)),
+        };
+
+        Ok(value)
+    }
+
+    fn write_core_reg(&mut self, address: CoreRegisterAddress, value: u32) -> Result<()> {
+        match address.0 {
+            register @ 0..=0xf => self.interface.write_ar(register as u8, value)?,
+            register @ SPECIAL_REGISTER_BASE..=0x1ff => self
+                .interface
+                .write_sr((register - SPECIAL_REGISTER_BASE) as u8, value)?,
+            register @ PHYSICAL_AR_BASE..=0x2ff => self
+                .interface
+                .write_physical_ar(register - PHYSICAL_AR_BASE, value)?,
+            _ => return Err(Error::UnknownRegister(address.0).into()),
+        }
+
+        Ok(())
+    }
+
+    fn get_available_breakpoint_units(&mut self) -> Result<u32, Error> {
+        Ok(NUM_IBREAK)
+    }
+
+    fn get_hw_breakpoints(&mut self) -> Result<Vec<Option<u32>>, Error> {
+        let enabled = self.interface.read_sr(special_register::IBREAKENABLE)?;
+
+        let mut breakpoints = Vec::new();
+
+        for unit in 0..NUM_IBREAK {
+            if enabled & (1 << unit) != 0 {
+                let address = self
+                    .interface
+                    .read_sr(special_register::IBREAKA0 + unit as u8)?;
+                breakpoints.push(Some(address));
+            } else {
+                breakpoints.push(None);
+            }
+        }
+
+        Ok(breakpoints)
+    }
+
+    fn enable_breakpoints(&mut self, _state: bool) -> Result<(), Error> {
+        // Each breakpoint is enabled separately in IBREAKENABLE.
+        Ok(())
+    }
+
+    fn set_hw_breakpoint(&mut self, bp_unit_index: usize, addr: u32) -> Result<(), Error> {
+        log::debug!("Setting breakpoint {}", bp_unit_index);
+
+        self.interface
+            .write_sr(special_register::IBREAKA0 + bp_unit_index as u8, addr)?;
+
+        let enabled = self.interface.read_sr(special_register::IBREAKENABLE)?;
+        self.interface.write_sr(
+            special_register::IBREAKENABLE,
+            enabled | (1 << bp_unit_index),
+        )?;
+
+        Ok(())
+    }
+
+    fn clear_hw_breakpoint(&mut self, unit_index: usize) -> Result<(), Error> {
+        let enabled = self.interface.read_sr(special_register::IBREAKENABLE)?;
+        self.interface
+            .write_sr(special_register::IBREAKENABLE, enabled & !(1 << unit_index))?;
+
+        Ok(())
+    }
+
+    fn get_available_watchpoint_units(&mut self) -> Result<u32, Error> {
+        Ok(NUM_DBREAK)
+    }
+
+    fn get_hw_watchpoints(&mut self) -> Result<Vec<Option<Watchpoint>>, Error> {
+        let mut watchpoints = Vec::new();
+
+        for unit in 0..NUM_DBREAK as u8 {
+            let control = self.interface.read_sr(special_register::DBREAKC0 + unit)?;
+
+            let watchpoint = match decode_dbreakc(control) {
+                Some((length, kind)) => Some(Watchpoint {
+                    address: self.interface.read_sr(special_register::DBREAKA0 + unit)?,
+                    length,
+                    kind,
+                }),
+                None => None,
+            };
+
+            watchpoints.push(watchpoint);
+        }
+
+        Ok(watchpoints)
+    }
+
+    fn set_hw_watchpoint(
+        &mut self,
+        unit_index: usize,
+        watchpoint: Watchpoint,
+    ) -> Result<(), Error> {
+        let control = encode_dbreakc(watchpoint.address, watchpoint.length, watchpoint.kind)
+            .ok_or(Error::UnsupportedWatchpoint {
+                address: watchpoint.address,
+                length: watchpoint.length,
+            })?;
+
+        let unit = unit_index as u8;
+
+        self.interface
+            .write_sr(special_register::DBREAKA0 + unit, watchpoint.address)?;
+        self.interface
+            .write_sr(special_register::DBREAKC0 + unit, control)?;
+
+        Ok(())
+    }
+
+    fn clear_hw_watchpoint(&mut self, unit_index: usize) -> Result<(), Error> {
+        self.interface
+            .write_sr(special_register::DBREAKC0 + unit_index as u8, 0)?;
+
+        Ok(())
+    }
+
+    fn registers(&self) -> &'static RegisterFile {
+        &XTENSA_REGISTERS
+    }
+
+    fn hw_breakpoints_enabled(&self) -> bool {
+        true
+    }
+
+    fn architecture(&self) -> Architecture {
+        Architecture::Xtensa
+    }
+}
+
+impl<'probe> MemoryInterface for Xtensa<'probe> {
+    fn read_word_32(&mut self, address: u32) -> Result<u32, Error> {
+        self.interface.read_word_32(address)
+    }
+    fn read_word_8(&mut self, address: u32) -> Result<u8, Error> {
+        self.interface.read_word_8(address)
+    }
+    fn read_32(&mut self, address: u32, data: &mut [u32]) -> Result<(), Error> {
+        self.interface.read_32(address, data)
+    }
+    fn read_8(&mut self, address: u32, data: &mut [u8]) -> Result<(), Error> {
+        self.interface.read_8(address, data)
+    }
+    fn write_word_32(&mut self, address: u32, data: u32) -> Result<(), Error> {
+        self.interface.write_word_32(address, data)
+    }
+    fn write_word_8(&mut self, address: u32, data: u8) -> Result<(), Error> {
+        self.interface.write_word_8(address, data)
+    }
+    fn write_32(&mut self, address: u32, data: &[u32]) -> Result<(), Error> {
+        self.interface.write_32(address, data)
+    }
+    fn write_8(&mut self, address: u32, data: &[u8]) -> Result<(), Error> {
+        self.interface.write_8(address, data)
+    }
+    fn flush(&mut self) -> Result<(), Error> {
+        self.interface.flush()
+    }
+}
+
+/// Determine the reason for a halt from the `DEBUGCAUSE` register.
+fn halt_reason(cause: u32) -> HaltReason {
+    let reasons = [
+        (debug_cause::ICOUNT, HaltReason::Step),
+        (debug_cause::IBREAK, HaltReason::Breakpoint),
+        (debug_cause::DBREAK, HaltReason::Watchpoint),
+        (debug_cause::BREAK, HaltReason::Breakpoint),
+        (debug_cause::BREAKN, HaltReason::Breakpoint),
+        (debug_cause::DEBUGINT, HaltReason::Request),
+    ];
+
+    let mut matching = reasons.iter().filter(|(bit, _)| cause & bit != 0);
+
+    match (matching.next(), matching.next()) {
+        (Some((_, reason)), None) => *reason,
+        (Some(_), Some(_)) => HaltReason::Multiple,
+        (None, _) => HaltReason::Unknown,
+    }
+}
+
+/// Encode the `DBREAKC` value for a watchpoint.
+///
+/// The region has to be a naturally aligned power of two, of at most 64 bytes.
+fn encode_dbreakc(address: u32, length: u32, kind: WatchpointKind) -> Option<u32> {
+    if !length.is_power_of_two() || length > 64 || address & (length - 1) != 0 {
+        return None;
+    }
+
+    let mask = dbreakc::MASK & !(length - 1);
+
+    let access = match kind {
+        WatchpointKind::Read => dbreakc::LOAD,
+        WatchpointKind::Write => dbreakc::STORE,
+        WatchpointKind::ReadWrite => dbreakc::LOAD | dbreakc::STORE,
+    };
+
+    Some(mask | access)
+}
+
+/// Decode the length and kind of a watchpoint from a `DBREAKC` value,
+/// or `None` if the watchpoint is disabled.
+fn decode_dbreakc(control: u32) -> Option<(u32, WatchpointKind)> {
+    let kind = match (control & dbreakc::LOAD != 0, control & dbreakc::STORE != 0) {
+        (true, true) => WatchpointKind::ReadWrite,
+        (true, false) => WatchpointKind::Read,
+        (false, true) => WatchpointKind::Write,
+        (false, false) => return None,
+    };
+
+    let ignored_bits = 6 - (control & dbreakc::MASK).count_ones();
+
+    Some((1 << ignored_bits, kind))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_dbreakc, encode_dbreakc, halt_reason};
+    use crate::{core::WatchpointKind, HaltReason};
+
+    #[test]
+    fn halt_reason_from_debug_cause() {
+        assert_eq!(halt_reason(0x01), HaltReason::Step);
+        assert_eq!(halt_reason(0x02), HaltReason::Breakpoint);
+        assert_eq!(halt_reason(0x104), HaltReason::Watchpoint);
+        assert_eq!(halt_reason(0x20), HaltReason::Request);
+        assert_eq!(halt_reason(0x03), HaltReason::Multiple);
+        assert_eq!(halt_reason(0), HaltReason::Unknown);
+    }
+
+    #[test]
+    fn dbreakc_round_trip() {
+        let control = encode_dbreakc(0x3ffb_0010, 16, WatchpointKind::Write).unwrap();
+        assert_eq!(control, 0x8000_0030);
+        assert_eq!(decode_dbreakc(control), Some((16, WatchpointKind::Write)));
+
+        let control = encode_dbreakc(0x3ffb_0003, 1, WatchpointKind::ReadWrite).unwrap();
+        assert_eq!(
+            decode_dbreakc(control),
+            Some((1, WatchpointKind::ReadWrite))
+        );
+
+        assert_eq!(encode_dbreakc(0x3ffb_0004, 8, WatchpointKind::Read), None);
+        assert_eq!(encode_dbreakc(0x3ffb_0000, 128, WatchpointKind::Read), None);
+        assert_eq!(decode_dbreakc(0x3f), None);
+    }
+}
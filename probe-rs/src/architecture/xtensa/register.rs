@@ -0,0 +1,239 @@
+//! Registers of the Xtensa cores.
+//!
+//! The register addresses used by probe-rs encode the kind of the register:
+//!
+//! - `0x000..=0x00f`: the address registers `a0` to `a15` of the current window
+//! - `0x100..=0x1ff`: the special registers, with the special register number in the lower byte
+//! - `0x200..=0x23f`: the physical address registers `ar0` to `ar63`, independent of the window
+
+use super::{instruction::special_register, DEBUG_LEVEL};
+use crate::{
+    core::{RegisterDescription, RegisterFile, RegisterKind},
+    CoreRegisterAddress,
+};
+
+/// The first address of the special registers.
+pub(super) const SPECIAL_REGISTER_BASE: u16 = 0x100;
+
+/// The first address of the physical address registers.
+pub(super) const PHYSICAL_AR_BASE: u16 = 0x200;
+
+/// The `EPC` register of the debug level, which holds the program counter while the core is halted.
+const EPC_DEBUG: u16 =
+    SPECIAL_REGISTER_BASE + special_register::EPC1 as u16 + DEBUG_LEVEL as u16 - 1;
+
+/// The `EPS` register of the debug level, which holds the `PS` register of the halted program.
+const PS_DEBUG: u16 =
+    SPECIAL_REGISTER_BASE + special_register::EPS2 as u16 + DEBUG_LEVEL as u16 - 2;
+
+const fn special(register: u8) -> CoreRegisterAddress {
+    CoreRegisterAddress(SPECIAL_REGISTER_BASE + register as u16)
+}
+
+static PC: RegisterDescription = RegisterDescription {
+    name: "pc",
+    kind: RegisterKind::PC,
+    address: CoreRegisterAddress(EPC_DEBUG),
+};
+
+static RA: RegisterDescription = RegisterDescription {
+    name: "a0",
+    kind: RegisterKind::General,
+    address: CoreRegisterAddress(0x0),
+};
+
+static SP: RegisterDescription = RegisterDescription {
+    name: "a1",
+    kind: RegisterKind::General,
+    address: CoreRegisterAddress(0x1),
+};
+
+pub(crate) static XTENSA_REGISTERS: RegisterFile = RegisterFile {
+    platform_registers: &[
+        RegisterDescription {
+            name: "a0",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x0),
+        },
+        RegisterDescription {
+            name: "a1",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x1),
+        },
+        RegisterDescription {
+            name: "a2",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x2),
+        },
+        RegisterDescription {
+            name: "a3",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x3),
+        },
+        RegisterDescription {
+            name: "a4",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x4),
+        },
+        RegisterDescription {
+            name: "a5",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x5),
+        },
+        RegisterDescription {
+            name: "a6",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x6),
+        },
+        RegisterDescription {
+            name: "a7",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x7),
+        },
+        RegisterDescription {
+            name: "a8",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x8),
+        },
+        RegisterDescription {
+            name: "a9",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x9),
+        },
+        RegisterDescription {
+            name: "a10",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0xa),
+        },
+        RegisterDescription {
+            name: "a11",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0xb),
+        },
+        RegisterDescription {
+            name: "a12",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0xc),
+        },
+        RegisterDescription {
+            name: "a13",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0xd),
+        },
+        RegisterDescription {
+            name: "a14",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0xe),
+        },
+        RegisterDescription {
+            name: "a15",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0xf),
+        },
+        RegisterDescription {
+            name: "sar",
+            kind: RegisterKind::General,
+            address: special(special_register::SAR),
+        },
+        RegisterDescription {
+            name: "ps",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(PS_DEBUG),
+        },
+        RegisterDescription {
+            name: "windowbase",
+            kind: RegisterKind::General,
+            address: special(special_register::WINDOWBASE),
+        },
+        RegisterDescription {
+            name: "windowstart",
+            kind: RegisterKind::General,
+            address: special(special_register::WINDOWSTART),
+        },
+        RegisterDescription {
+            name: "lbeg",
+            kind: RegisterKind::General,
+            address: special(special_register::LBEG),
+        },
+        RegisterDescription {
+            name: "lend",
+            kind: RegisterKind::General,
+            address: special(special_register::LEND),
+        },
+        RegisterDescription {
+            name: "lcount",
+            kind: RegisterKind::General,
+            address: special(special_register::LCOUNT),
+        },
+        RegisterDescription {
+            name: "exccause",
+            kind: RegisterKind::General,
+            address: special(special_register::EXCCAUSE),
+        },
+        RegisterDescription {
+            name: "excvaddr",
+            kind: RegisterKind::General,
+            address: special(special_register::EXCVADDR),
+        },
+        RegisterDescription {
+            name: "debugcause",
+            kind: RegisterKind::General,
+            address: special(special_register::DEBUGCAUSE),
+        },
+    ],
+
+    program_counter: &PC,
+
+    return_address: &RA,
+
+    stack_pointer: &SP,
+
+    // The registers of the called function, when using the windowed ABI.
+    argument_registers: &[
+        RegisterDescription {
+            name: "a2",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x2),
+        },
+        RegisterDescription {
+            name: "a3",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x3),
+        },
+        RegisterDescription {
+            name: "a4",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x4),
+        },
+        RegisterDescription {
+            name: "a5",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x5),
+        },
+        RegisterDescription {
+            name: "a6",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x6),
+        },
+        RegisterDescription {
+            name: "a7",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x7),
+        },
+    ],
+
+    result_registers: &[
+        RegisterDescription {
+            name: "a2",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x2),
+        },
+        RegisterDescription {
+            name: "a3",
+            kind: RegisterKind::General,
+            address: CoreRegisterAddress(0x3),
+        },
+    ],
+
+    special_registers: &[],
+};
@@ -0,0 +1,44 @@
+use super::communication_interface::{XtensaCommunicationInterface, XtensaError};
+use std::sync::Arc;
+
+pub trait XtensaDebugSequence: Send + Sync {
+    fn on_connect(
+        &self,
+        _interface: &mut XtensaCommunicationInterface,
+    ) -> Result<(), crate::Error> {
+        Ok(())
+    }
+
+    /// Halt the core at the first instruction after any following reset.
+    ///
+    /// The debug module cannot halt the core after resets which are not triggered by the
+    /// debugger, so the default implementation returns [`XtensaError::ResetCatchNotSupported`].
+    /// Chips which can delay the reset, e.g. using a reset vector in the RTC memory, can
+    /// implement it in their sequence.
+    fn reset_catch_set(
+        &self,
+        _interface: &mut XtensaCommunicationInterface,
+    ) -> Result<(), crate::Error> {
+        Err(XtensaError::ResetCatchNotSupported.into())
+    }
+
+    /// Stop halting the core after a reset, which was enabled with [`reset_catch_set`].
+    ///
+    /// [`reset_catch_set`]: XtensaDebugSequence::reset_catch_set
+    fn reset_catch_clear(
+        &self,
+        _interface: &mut XtensaCommunicationInterface,
+    ) -> Result<(), crate::Error> {
+        Ok(())
+    }
+}
+
+pub struct DefaultXtensaSequence(pub(crate) ());
+
+impl DefaultXtensaSequence {
+    pub fn create() -> Arc<dyn XtensaDebugSequence> {
+        Arc::new(Self(()))
+    }
+}
+
+impl XtensaDebugSequence for DefaultXtensaSequence {}
@@ -0,0 +1,233 @@
+//! Xtensa Debug Module (XDM) handling
+//!
+//! The debug module is accessed through the On-Chip Debug (OCD) JTAG TAP. The power
+//! control registers are accessed directly through JTAG instructions, all other
+//! registers through the Nexus Address Register (NAR) and the Nexus Data Register (NDR).
+
+use std::convert::TryInto;
+
+use bitfield::bitfield;
+
+use super::communication_interface::XtensaError;
+use crate::{probe::JTAGAccess, DebugProbeError};
+
+/// JTAG instruction to access the power control register.
+const TAPINS_PWRCTL: u32 = 0x08;
+/// JTAG instruction to access the power status register.
+const TAPINS_PWRSTAT: u32 = 0x09;
+/// JTAG instruction to access the NAR, followed by the NDR.
+const TAPINS_NARSEL: u32 = 0x1c;
+/// JTAG instruction to read the IDCODE.
+const TAPINS_IDCODE: u32 = 0x1e;
+
+/// Width of the power control and status registers.
+const PWR_WIDTH: u32 = 8;
+/// Width of the Nexus Address Register.
+const NAR_WIDTH: u32 = 8;
+/// Width of the Nexus Data Register.
+const NDR_WIDTH: u32 = 32;
+
+/// The registers of the debug module, which are accessed through the NAR.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub(super) enum DebugRegister {
+    /// OCD ID register
+    OcdId = 0x40,
+    /// Clear bits in the Debug Control Register
+    DcrClr = 0x42,
+    /// Set bits in the Debug Control Register
+    DcrSet = 0x43,
+    /// Debug Status Register
+    Dsr = 0x44,
+    /// Debug Data Register
+    Ddr = 0x45,
+    /// Debug Instruction Register 0, writing it executes the instruction
+    Dir0Exec = 0x47,
+}
+
+bitfield! {
+    /// The power control register, accessed with [`TAPINS_PWRCTL`].
+    #[derive(Copy, Clone)]
+    pub struct PowerControl(u8);
+    impl Debug;
+
+    pub core_wakeup, set_core_wakeup: 0;
+    pub mem_wakeup, set_mem_wakeup: 1;
+    pub debug_wakeup, set_debug_wakeup: 2;
+    pub core_reset, set_core_reset: 4;
+    pub debug_reset, set_debug_reset: 6;
+    pub jtag_debug_use, set_jtag_debug_use: 7;
+}
+
+impl PowerControl {
+    /// Keep all power domains on, and take control of the debug module.
+    pub(super) fn debug_domains_on() -> Self {
+        let mut control = PowerControl(0);
+        control.set_core_wakeup(true);
+        control.set_mem_wakeup(true);
+        control.set_debug_wakeup(true);
+        control.set_jtag_debug_use(true);
+        control
+    }
+}
+
+bitfield! {
+    /// The power status register, accessed with [`TAPINS_PWRSTAT`].
+    ///
+    /// The `was_reset` bits are sticky, and cleared by writing a one.
+    #[derive(Copy, Clone)]
+    pub struct PowerStatus(u8);
+    impl Debug;
+
+    pub core_domain_on, _: 0;
+    pub mem_domain_on, _: 1;
+    pub debug_domain_on, _: 2;
+    pub core_still_needed, _: 3;
+    pub core_was_reset, set_core_was_reset: 4;
+    pub debug_was_reset, set_debug_was_reset: 6;
+}
+
+bitfield! {
+    /// The Debug Control Register (DCR), written through [`DebugRegister::DcrSet`]
+    /// and [`DebugRegister::DcrClr`].
+    #[derive(Copy, Clone)]
+    pub struct DebugControl(u32);
+    impl Debug;
+
+    pub enable_ocd, set_enable_ocd: 0;
+    pub debug_interrupt, set_debug_interrupt: 1;
+    pub interrupt_all_conds, set_interrupt_all_conds: 2;
+    pub break_in_enable, set_break_in_enable: 16;
+    pub break_out_enable, set_break_out_enable: 17;
+    pub debug_sw_active, set_debug_sw_active: 20;
+    pub run_stall_in_enable, set_run_stall_in_enable: 21;
+    pub debug_mode_out_enable, set_debug_mode_out_enable: 22;
+}
+
+bitfield! {
+    /// The Debug Status Register (DSR).
+    ///
+    /// The execution and break status bits are sticky, and cleared by writing a one.
+    #[derive(Copy, Clone)]
+    pub struct DebugStatus(u32);
+    impl Debug;
+
+    pub exec_done, set_exec_done: 0;
+    pub exec_exception, set_exec_exception: 1;
+    pub exec_busy, _: 2;
+    pub exec_overrun, set_exec_overrun: 3;
+    pub stopped, _: 4;
+    pub stop_cause, _: 8, 5;
+    pub core_wrote_ddr, set_core_wrote_ddr: 10;
+    pub core_read_ddr, set_core_read_ddr: 11;
+    pub host_wrote_ddr, set_host_wrote_ddr: 14;
+    pub host_read_ddr, set_host_read_ddr: 15;
+    pub debug_pend_break, set_debug_pend_break: 16;
+    pub debug_pend_host, set_debug_pend_host: 17;
+    pub debug_int_break, set_debug_int_break: 20;
+    pub debug_int_host, set_debug_int_host: 21;
+}
+
+impl DebugStatus {
+    /// A value which clears all sticky execution status bits when written to the DSR.
+    pub(super) fn clear_exec_status() -> Self {
+        let mut status = DebugStatus(0);
+        status.set_exec_done(true);
+        status.set_exec_exception(true);
+        status.set_exec_overrun(true);
+        status
+    }
+}
+
+/// Access to the Xtensa debug module through JTAG.
+#[derive(Debug)]
+pub struct Xdm {
+    pub probe: Box<dyn JTAGAccess>,
+}
+
+impl Xdm {
+    pub fn new(probe: Box<dyn JTAGAccess>) -> Self {
+        Self { probe }
+    }
+
+    pub fn read_idcode(&mut self) -> Result<u32, DebugProbeError> {
+        let value = self.probe.read_register(TAPINS_IDCODE, 32)?;
+
+        Ok(u32::from_le_bytes((&value[..]).try_into().unwrap()))
+    }
+
+    /// Write the power control register, and return the previous value.
+    pub(super) fn write_power_control(
+        &mut self,
+        control: PowerControl,
+    ) -> Result<PowerControl, XtensaError> {
+        let response = self
+            .probe
+            .write_register(TAPINS_PWRCTL, &[control.0], PWR_WIDTH)?;
+
+        Ok(PowerControl(response[0]))
+    }
+
+    /// Write the power status register, which clears the sticky bits set in `status`,
+    /// and return the current status.
+    pub(super) fn write_power_status(
+        &mut self,
+        status: PowerStatus,
+    ) -> Result<PowerStatus, XtensaError> {
+        let response = self
+            .probe
+            .write_register(TAPINS_PWRSTAT, &[status.0], PWR_WIDTH)?;
+
+        Ok(PowerStatus(response[0]))
+    }
+
+    /// Read the power status register, without clearing the sticky bits.
+    pub(super) fn read_power_status(&mut self) -> Result<PowerStatus, XtensaError> {
+        self.write_power_status(PowerStatus(0))
+    }
+
+    /// Read a register of the debug module.
+    pub(super) fn read_register(&mut self, register: DebugRegister) -> Result<u32, XtensaError> {
+        let response = self.access_register(register, false, 0)?;
+
+        Ok(u32::from_le_bytes((&response[..]).try_into().unwrap()))
+    }
+
+    /// Write a register of the debug module.
+    pub(super) fn write_register(
+        &mut self,
+        register: DebugRegister,
+        value: u32,
+    ) -> Result<(), XtensaError> {
+        self.access_register(register, true, value)?;
+
+        Ok(())
+    }
+
+    /// Write the address of the register to the NAR, and then access the NDR.
+    fn access_register(
+        &mut self,
+        register: DebugRegister,
+        write: bool,
+        value: u32,
+    ) -> Result<Vec<u8>, XtensaError> {
+        let nar = (register as u8) << 1 | write as u8;
+
+        let status = self
+            .probe
+            .write_register(TAPINS_NARSEL, &[nar], NAR_WIDTH)?;
+
+        // The NDR is always scanned, so that the next scan accesses the NAR again.
+        let response = self
+            .probe
+            .write_register(TAPINS_NARSEL, &value.to_le_bytes(), NDR_WIDTH)?;
+
+        // The NAR scan returns the status of the previous access: bit 0 is set while
+        // the debug module is busy, bit 1 if the previous access failed.
+        match status[0] & 0b11 {
+            0 => Ok(response),
+            0b01 => Err(XtensaError::XdmBusy),
+            _ => Err(XtensaError::XdmAccessFailed),
+        }
+    }
+}
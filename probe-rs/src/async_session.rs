@@ -0,0 +1,199 @@
+#![warn(missing_docs)]
+
+//! An async facade for [`Session`].
+//!
+//! Most operations of probe-rs block the calling thread until the probe has finished
+//! the operation. [`AsyncSession`] moves the session onto a dedicated worker thread,
+//! and exposes the operations as futures, which can be awaited on any executor.
+//!
+//! All operations are executed on the worker thread in the order in which they were started.
+
+use crate::flashing::{
+    download_file_with_options, DownloadOptions, FileDownloadError, FlashError, Format,
+};
+use crate::{CoreInformation, CoreStatus, Error, Session};
+use futures::channel::{mpsc, oneshot};
+use futures::Stream;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc as std_mpsc, Arc};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+type Job = Box<dyn FnOnce(&mut Session) + Send>;
+
+/// A [`Session`] running on a worker thread.
+///
+/// Dropping the `AsyncSession` stops the worker thread once all started operations have
+/// finished, and closes the session.
+pub struct AsyncSession {
+    jobs: Option<std_mpsc::Sender<Job>>,
+    worker: Option<JoinHandle<Session>>,
+    /// Set when the worker is stopped, to end all status streams.
+    stopped: Arc<AtomicBool>,
+}
+
+impl AsyncSession {
+    /// Move the session to a new worker thread.
+    pub fn new(mut session: Session) -> Self {
+        let (jobs, receiver) = std_mpsc::channel::<Job>();
+
+        let worker = thread::spawn(move || {
+            for job in receiver {
+                job(&mut session);
+            }
+
+            session
+        });
+
+        Self {
+            jobs: Some(jobs),
+            worker: Some(worker),
+            stopped: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Run `operation` with the session on the worker thread, and return its result.
+    ///
+    /// This can be used for all operations which have no dedicated async method.
+    pub async fn run<T: Send + 'static>(
+        &self,
+        operation: impl FnOnce(&mut Session) -> T + Send + 'static,
+    ) -> Result<T, Error> {
+        let (sender, receiver) = oneshot::channel();
+
+        self.submit(Box::new(move |session| {
+            // If the future was dropped, nobody is interested in the result anymore.
+            let _ = sender.send(operation(session));
+        }))?;
+
+        receiver.await.map_err(|_| worker_stopped())
+    }
+
+    /// Halt the core with the given id.
+    pub async fn halt(&self, core: usize, timeout: Duration) -> Result<CoreInformation, Error> {
+        self.run(move |session| session.core(core)?.halt(timeout))
+            .await?
+    }
+
+    /// Resume execution of the core with the given id.
+    pub async fn resume(&self, core: usize) -> Result<(), Error> {
+        self.run(move |session| session.core(core)?.run()).await?
+    }
+
+    /// Wait until the core with the given id is halted.
+    ///
+    /// Other operations are delayed until the core has halted or the timeout has expired.
+    /// Use [`AsyncSession::core_status`] to get notified about a halt without blocking the worker.
+    pub async fn wait_for_core_halted(&self, core: usize, timeout: Duration) -> Result<(), Error> {
+        self.run(move |session| session.core(core)?.wait_for_core_halted(timeout))
+            .await?
+    }
+
+    /// Download the file at `path` to the flash of the target.
+    ///
+    /// The progress of the download is reported to the observers of the session
+    /// as [`SessionEvent::Flash`](crate::SessionEvent::Flash) events.
+    pub async fn download(&self, path: PathBuf, format: Format) -> Result<(), FileDownloadError> {
+        self.run(move |session| {
+            let progress = session.flash_progress();
+
+            let mut options = DownloadOptions::new();
+            options.progress = Some(&progress);
+
+            download_file_with_options(session, path, format, options)
+        })
+        .await
+        .map_err(|error| FileDownloadError::Flash(FlashError::Core(error)))?
+    }
+
+    /// A stream of the status of the core with the given id.
+    ///
+    /// The status is polled every `interval`, and a new item is produced whenever it
+    /// changes, starting with the current status. If the status cannot be read, the error
+    /// is produced and the stream ends.
+    pub fn core_status(
+        &self,
+        core: usize,
+        interval: Duration,
+    ) -> impl Stream<Item = Result<CoreStatus, Error>> {
+        let (sender, receiver) = mpsc::unbounded();
+        let jobs = self.jobs.clone();
+        let stopped = self.stopped.clone();
+
+        thread::spawn(move || {
+            let jobs = match jobs {
+                Some(jobs) => jobs,
+                None => return,
+            };
+
+            let mut last_status = None;
+
+            // The sender for the jobs has to be dropped when the session is stopped,
+            // otherwise the worker would never finish.
+            while !sender.is_closed() && !stopped.load(Ordering::SeqCst) {
+                let (status_sender, status_receiver) = std_mpsc::channel();
+
+                let job: Job = Box::new(move |session| {
+                    let _ = status_sender.send(session.core(core).and_then(|mut c| c.status()));
+                });
+
+                if jobs.send(job).is_err() {
+                    return;
+                }
+
+                match status_receiver.recv() {
+                    Ok(Ok(status)) => {
+                        if last_status != Some(status) {
+                            last_status = Some(status);
+
+                            if sender.unbounded_send(Ok(status)).is_err() {
+                                return;
+                            }
+                        }
+                    }
+                    Ok(Err(error)) => {
+                        let _ = sender.unbounded_send(Err(error));
+                        return;
+                    }
+                    Err(_) => return,
+                }
+
+                thread::sleep(interval);
+            }
+        });
+
+        receiver
+    }
+
+    /// Stop the worker thread and return the session, once all started operations have finished.
+    pub fn into_session(mut self) -> Session {
+        self.stop().expect("The session worker thread panicked")
+    }
+
+    fn submit(&self, job: Job) -> Result<(), Error> {
+        self.jobs
+            .as_ref()
+            .ok_or_else(worker_stopped)?
+            .send(job)
+            .map_err(|_| worker_stopped())
+    }
+
+    fn stop(&mut self) -> Option<Session> {
+        // Closing the channel ends the loop of the worker.
+        self.stopped.store(true, Ordering::SeqCst);
+        self.jobs.take();
+
+        self.worker.take().and_then(|worker| worker.join().ok())
+    }
+}
+
+impl Drop for AsyncSession {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn worker_stopped() -> Error {
+    Error::SessionWorkerStopped
+}
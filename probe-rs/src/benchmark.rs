@@ -0,0 +1,185 @@
+#![warn(missing_docs)]
+
+//! Throughput benchmarks for a probe and target combination.
+//!
+//! The benchmark measures how fast memory can be written to and read from the target
+//! at the configured protocol speed, and optionally how fast the flash can be programmed.
+//! The results are returned as a [`BenchmarkResult`], which can be serialized with `serde`
+//! to track the performance of a setup over time.
+//!
+//! To compare different protocol speeds, run the benchmark once per speed, with a
+//! freshly opened [`Probe`] each time.
+
+use crate::config::TargetSelector;
+use crate::flashing::DownloadOptions;
+use crate::{Error, MemoryInterface, Probe, Session};
+use std::time::Duration;
+use web_time::Instant;
+
+/// Options for a benchmark run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchmarkOptions {
+    /// Address of the RAM used for the memory benchmark.
+    pub address: u32,
+    /// Number of 32 bit words which are written and read back.
+    pub word_count: usize,
+    /// If set, `4 * word_count` bytes of the flash at this address are programmed
+    /// to measure the flash programming speed.
+    ///
+    /// This overwrites the existing contents of the flash.
+    pub flash_address: Option<u32>,
+}
+
+/// The measured duration of a single operation.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Throughput {
+    /// The number of bytes transferred.
+    pub bytes: usize,
+    /// The time it took to transfer the bytes.
+    pub duration: Duration,
+}
+
+impl Throughput {
+    fn measure(bytes: usize, operation: impl FnOnce() -> Result<(), Error>) -> Result<Self, Error> {
+        let start = Instant::now();
+        operation()?;
+
+        Ok(Self {
+            bytes,
+            duration: start.elapsed(),
+        })
+    }
+
+    /// The achieved throughput in bytes per second.
+    pub fn bytes_per_second(&self) -> f64 {
+        self.bytes as f64 / self.duration.as_secs_f64()
+    }
+}
+
+/// The results of a benchmark run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchmarkResult {
+    /// The name of the probe.
+    pub probe: String,
+    /// The name of the target.
+    pub chip: String,
+    /// The protocol speed of the probe in kHz.
+    pub speed_khz: u32,
+    /// Writing to the target RAM.
+    pub write: Throughput,
+    /// Reading from the target RAM.
+    pub read: Throughput,
+    /// Whether the data which was read back matches the written data.
+    pub verified: bool,
+    /// Programming the flash, if requested.
+    pub flash: Option<Throughput>,
+}
+
+/// Attach to the target with the given probe and run the benchmark.
+///
+/// The speed and protocol of the probe have to be configured before calling this function.
+pub fn run(
+    probe: Probe,
+    target: impl Into<TargetSelector>,
+    options: &BenchmarkOptions,
+) -> Result<BenchmarkResult, Error> {
+    let probe_name = probe.get_name();
+    let speed_khz = probe.speed_khz();
+
+    let mut session = probe.attach(target)?;
+
+    run_on_session(&mut session, probe_name, speed_khz, options)
+}
+
+fn run_on_session(
+    session: &mut Session,
+    probe: String,
+    speed_khz: u32,
+    options: &BenchmarkOptions,
+) -> Result<BenchmarkResult, Error> {
+    let chip = session.target().name.clone();
+    let sample_data = sample_data(options.word_count);
+    let bytes = 4 * sample_data.len();
+
+    let (write, read, readback_data) = {
+        let mut core = session.core(0)?;
+        core.halt(Duration::from_millis(100))?;
+
+        let write = Throughput::measure(bytes, || {
+            core.write_32(options.address, &sample_data)?;
+            core.flush()
+        })?;
+
+        let mut readback_data = vec![0u32; sample_data.len()];
+        let read =
+            Throughput::measure(bytes, || core.read_32(options.address, &mut readback_data))?;
+
+        (write, read, readback_data)
+    };
+
+    let flash = match options.flash_address {
+        Some(address) => {
+            let data: Vec<u8> = sample_data.iter().flat_map(|w| w.to_le_bytes()).collect();
+
+            let mut loader = session.target().flash_loader();
+            loader.add_data(address, &data)?;
+
+            let flash = Throughput::measure(bytes, || {
+                Ok(loader.commit(session, DownloadOptions::default())?)
+            })?;
+
+            Some(flash)
+        }
+        None => None,
+    };
+
+    Ok(BenchmarkResult {
+        probe,
+        chip,
+        speed_khz,
+        write,
+        read,
+        verified: readback_data == sample_data,
+        flash,
+    })
+}
+
+/// Generate pseudo random data, so that the results do not depend on
+/// the contents of the memory or compression done by the probe.
+fn sample_data(word_count: usize) -> Vec<u32> {
+    // xorshift32
+    let mut state: u32 = 0x1234_5678;
+
+    (0..word_count)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sample_data, Throughput};
+    use std::time::Duration;
+
+    #[test]
+    fn throughput_bytes_per_second() {
+        let throughput = Throughput {
+            bytes: 4096,
+            duration: Duration::from_millis(500),
+        };
+
+        assert!((throughput.bytes_per_second() - 8192.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn sample_data_is_not_constant() {
+        let data = sample_data(16);
+
+        assert_eq!(data.len(), 16);
+        assert!(data.windows(2).all(|w| w[0] != w[1]));
+    }
+}
@@ -0,0 +1,1270 @@
+#![warn(missing_docs)]
+
+//! Breakpoints managed by a [`Session`].
+//!
+//! Breakpoints added with [`Session::add_breakpoint`] are owned by the session, which
+//! keeps track of their location, type and enabled state, and sets the required hardware
+//! breakpoints and watchpoints. They are set again automatically after a reset of the core,
+//! and [`Session::reload_breakpoints`] moves breakpoints at source locations to the new
+//! addresses after a different program was flashed.
+//!
+//! # Conditions
+//!
+//! The debug hardware can only halt the core when a breakpoint address is reached, but
+//! debuggers like GDB and DAP clients also support breakpoints which only stop if a
+//! condition is true, or after they have been hit a number of times. When a managed
+//! breakpoint halts the core, [`Session::handle_breakpoint`] evaluates its conditions, and resumes
+//! the core if they are not met.
+//!
+//! [`Session::poll_events`] does this automatically, so that the core only appears as
+//! halted if a breakpoint really stops it.
+
+use crate::config::MemoryRegion;
+use crate::debug::{DebugInfo, Symbol};
+use crate::{
+    Core, CoreRegisterAddress, CoreStatus, Error, HaltReason, MemoryInterface, Session, Watchpoint,
+    WatchpointKind,
+};
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Identifies a breakpoint added with [`Session::add_breakpoint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ManagedBreakpointId(usize);
+
+/// A condition on the number of times a breakpoint was hit, which has to be met
+/// for the breakpoint to stop the core.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HitCondition {
+    /// Stop only when the breakpoint is hit for the given time.
+    Equal(u32),
+    /// Stop every time the breakpoint is hit, starting with the given hit.
+    AtLeast(u32),
+    /// Stop every time the hit count is a multiple of the given number.
+    Multiple(u32),
+}
+
+impl HitCondition {
+    /// Check if the condition is met after the breakpoint was hit `hit_count` times.
+    pub fn is_met(&self, hit_count: u32) -> bool {
+        match *self {
+            HitCondition::Equal(count) => hit_count == count,
+            HitCondition::AtLeast(count) => hit_count >= count,
+            HitCondition::Multiple(count) => hit_count.checked_rem(count) == Some(0),
+        }
+    }
+}
+
+impl FromStr for HitCondition {
+    type Err = ConditionError;
+
+    /// Parse a hit condition as used by DAP clients: `==N`, `>=N` or `%N`.
+    ///
+    /// A plain number `N` is the same as `>=N`, i.e. the first `N - 1` hits are ignored.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        let (constructor, count): (fn(u32) -> HitCondition, &str) =
+            if let Some(count) = s.strip_prefix("==") {
+                (HitCondition::Equal, count)
+            } else if let Some(count) = s.strip_prefix(">=") {
+                (HitCondition::AtLeast, count)
+            } else if let Some(count) = s.strip_prefix('%') {
+                (HitCondition::Multiple, count)
+            } else {
+                (HitCondition::AtLeast, s)
+            };
+
+        let count = count.trim().parse().map_err(|_| ConditionError::Parse {
+            condition: s.to_owned(),
+            message: "expected a hit count".to_owned(),
+        })?;
+
+        Ok(constructor(count))
+    }
+}
+
+/// An error while parsing or evaluating the condition of a breakpoint.
+#[derive(Debug, thiserror::Error)]
+pub enum ConditionError {
+    /// The condition is not a valid expression.
+    #[error("Invalid condition '{condition}': {message}")]
+    Parse {
+        /// The condition.
+        condition: String,
+        /// What is wrong with the condition.
+        message: String,
+    },
+    /// An identifier is neither a register of the core, nor a symbol of the program.
+    #[error("Unknown register or variable '{0}'")]
+    UnknownIdentifier(String),
+    /// The condition divides by zero.
+    #[error("Division by zero")]
+    DivisionByZero,
+    /// Reading a register or the memory of the target failed.
+    #[error("Failed to access the target")]
+    Probe(#[from] Error),
+}
+
+/// A binary operator of a condition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOperator {
+    Or,
+    And,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    ShiftLeft,
+    ShiftRight,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+}
+
+impl BinaryOperator {
+    /// The operators of each precedence level, from the lowest to the highest precedence.
+    const PRECEDENCE: &'static [&'static [(&'static str, BinaryOperator)]] = &[
+        &[("||", BinaryOperator::Or)],
+        &[("&&", BinaryOperator::And)],
+        &[("|", BinaryOperator::BitOr)],
+        &[("^", BinaryOperator::BitXor)],
+        &[("&", BinaryOperator::BitAnd)],
+        &[
+            ("==", BinaryOperator::Equal),
+            ("!=", BinaryOperator::NotEqual),
+        ],
+        &[
+            ("<", BinaryOperator::Less),
+            ("<=", BinaryOperator::LessEqual),
+            (">", BinaryOperator::Greater),
+            (">=", BinaryOperator::GreaterEqual),
+        ],
+        &[
+            ("<<", BinaryOperator::ShiftLeft),
+            (">>", BinaryOperator::ShiftRight),
+        ],
+        &[("+", BinaryOperator::Add), ("-", BinaryOperator::Subtract)],
+        &[
+            ("*", BinaryOperator::Multiply),
+            ("/", BinaryOperator::Divide),
+            ("%", BinaryOperator::Remainder),
+        ],
+    ];
+
+    fn apply(self, lhs: u64, rhs: u64) -> Result<u64, ConditionError> {
+        Ok(match self {
+            BinaryOperator::Or => (lhs != 0 || rhs != 0) as u64,
+            BinaryOperator::And => (lhs != 0 && rhs != 0) as u64,
+            BinaryOperator::BitOr => lhs | rhs,
+            BinaryOperator::BitXor => lhs ^ rhs,
+            BinaryOperator::BitAnd => lhs & rhs,
+            BinaryOperator::Equal => (lhs == rhs) as u64,
+            BinaryOperator::NotEqual => (lhs != rhs) as u64,
+            BinaryOperator::Less => (lhs < rhs) as u64,
+            BinaryOperator::LessEqual => (lhs <= rhs) as u64,
+            BinaryOperator::Greater => (lhs > rhs) as u64,
+            BinaryOperator::GreaterEqual => (lhs >= rhs) as u64,
+            BinaryOperator::ShiftLeft => lhs.checked_shl(rhs as u32).unwrap_or(0),
+            BinaryOperator::ShiftRight => lhs.checked_shr(rhs as u32).unwrap_or(0),
+            BinaryOperator::Add => lhs.wrapping_add(rhs),
+            BinaryOperator::Subtract => lhs.wrapping_sub(rhs),
+            BinaryOperator::Multiply => lhs.wrapping_mul(rhs),
+            BinaryOperator::Divide => lhs.checked_div(rhs).ok_or(ConditionError::DivisionByZero)?,
+            BinaryOperator::Remainder => {
+                lhs.checked_rem(rhs).ok_or(ConditionError::DivisionByZero)?
+            }
+        })
+    }
+}
+
+/// An expression of a condition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expression {
+    Number(u64),
+    /// A register or a variable.
+    Identifier(String),
+    /// The address of a variable.
+    AddressOf(String),
+    /// Read a 32 bit word from memory.
+    Dereference(Box<Expression>),
+    Not(Box<Expression>),
+    Negate(Box<Expression>),
+    Complement(Box<Expression>),
+    Binary(BinaryOperator, Box<Expression>, Box<Expression>),
+}
+
+/// Access to the registers and variables of the target while evaluating a condition.
+trait Environment {
+    /// The value of a register or a variable.
+    fn value(&mut self, identifier: &str) -> Result<u64, ConditionError>;
+
+    /// The address of a variable.
+    fn address(&mut self, identifier: &str) -> Result<u64, ConditionError>;
+
+    /// Read a 32 bit word from memory.
+    fn read_word(&mut self, address: u64) -> Result<u64, ConditionError>;
+}
+
+impl Expression {
+    fn evaluate(&self, environment: &mut dyn Environment) -> Result<u64, ConditionError> {
+        Ok(match self {
+            Expression::Number(value) => *value,
+            Expression::Identifier(name) => environment.value(name)?,
+            Expression::AddressOf(name) => environment.address(name)?,
+            Expression::Dereference(address) => {
+                let address = address.evaluate(environment)?;
+                environment.read_word(address)?
+            }
+            Expression::Not(value) => (value.evaluate(environment)? == 0) as u64,
+            Expression::Negate(value) => value.evaluate(environment)?.wrapping_neg(),
+            Expression::Complement(value) => !value.evaluate(environment)?,
+            // The logical operators only evaluate the right hand side if required,
+            // so that e.g. `ptr != 0 && *ptr == 1` does not read from address zero.
+            Expression::Binary(BinaryOperator::And, lhs, rhs) => {
+                (lhs.evaluate(environment)? != 0 && rhs.evaluate(environment)? != 0) as u64
+            }
+            Expression::Binary(BinaryOperator::Or, lhs, rhs) => {
+                (lhs.evaluate(environment)? != 0 || rhs.evaluate(environment)? != 0) as u64
+            }
+            Expression::Binary(operator, lhs, rhs) => {
+                let lhs = lhs.evaluate(environment)?;
+                let rhs = rhs.evaluate(environment)?;
+                operator.apply(lhs, rhs)?
+            }
+        })
+    }
+
+    /// Collect all identifiers used in the expression.
+    fn identifiers<'a>(&'a self, identifiers: &mut Vec<&'a str>) {
+        match self {
+            Expression::Number(_) => {}
+            Expression::Identifier(name) | Expression::AddressOf(name) => identifiers.push(name),
+            Expression::Dereference(value)
+            | Expression::Not(value)
+            | Expression::Negate(value)
+            | Expression::Complement(value) => value.identifiers(identifiers),
+            Expression::Binary(_, lhs, rhs) => {
+                lhs.identifiers(identifiers);
+                rhs.identifiers(identifiers);
+            }
+        }
+    }
+}
+
+/// A recursive descent parser for conditions, with the operators and precedences of C.
+struct Parser<'a> {
+    condition: &'a str,
+    rest: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn parse(condition: &'a str) -> Result<Expression, ConditionError> {
+        let mut parser = Parser {
+            condition,
+            rest: condition,
+        };
+
+        let expression = parser.binary(0)?;
+
+        parser.skip_whitespace();
+        if !parser.rest.is_empty() {
+            return Err(parser.error(format!("unexpected '{}'", parser.rest)));
+        }
+
+        Ok(expression)
+    }
+
+    fn error(&self, message: String) -> ConditionError {
+        ConditionError::Parse {
+            condition: self.condition.to_owned(),
+            message,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.rest = self.rest.trim_start();
+    }
+
+    /// Consume `token` if the remaining input starts with it.
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+
+        match self.rest.strip_prefix(token) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Parse the operators of the precedence `level`, and all higher ones.
+    fn binary(&mut self, level: usize) -> Result<Expression, ConditionError> {
+        let operators = match BinaryOperator::PRECEDENCE.get(level) {
+            Some(operators) => operators,
+            None => return self.unary(),
+        };
+
+        let mut lhs = self.binary(level + 1)?;
+
+        'outer: loop {
+            self.skip_whitespace();
+
+            // Try longer operators first, so that e.g. `<=` is not parsed as `<`.
+            let mut candidates: Vec<_> = operators.iter().collect();
+            candidates.sort_by_key(|(token, _)| std::cmp::Reverse(token.len()));
+
+            for (token, operator) in candidates {
+                if !self.rest.starts_with(token) || self.is_longer_operator(token) {
+                    continue;
+                }
+
+                self.rest = &self.rest[token.len()..];
+
+                let rhs = self.binary(level + 1)?;
+                lhs = Expression::Binary(*operator, Box::new(lhs), Box::new(rhs));
+
+                continue 'outer;
+            }
+
+            return Ok(lhs);
+        }
+    }
+
+    /// Check if `token` at the start of the input is only the prefix of another operator,
+    /// e.g. `&` of `&&`, or `<` of `<<`.
+    fn is_longer_operator(&self, token: &str) -> bool {
+        BinaryOperator::PRECEDENCE
+            .iter()
+            .flat_map(|operators| operators.iter())
+            .any(|(other, _)| {
+                other.len() > token.len()
+                    && other.starts_with(token)
+                    && self.rest.starts_with(other)
+            })
+    }
+
+    fn unary(&mut self) -> Result<Expression, ConditionError> {
+        if self.eat("!") {
+            Ok(Expression::Not(Box::new(self.unary()?)))
+        } else if self.eat("-") {
+            Ok(Expression::Negate(Box::new(self.unary()?)))
+        } else if self.eat("~") {
+            Ok(Expression::Complement(Box::new(self.unary()?)))
+        } else if self.eat("*") {
+            Ok(Expression::Dereference(Box::new(self.unary()?)))
+        } else if self.eat("&") {
+            match self.primary()? {
+                Expression::Identifier(name) => Ok(Expression::AddressOf(name)),
+                _ => Err(self.error("'&' can only be applied to a variable".to_owned())),
+            }
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expression, ConditionError> {
+        if self.eat("(") {
+            let expression = self.binary(0)?;
+
+            if !self.eat(")") {
+                return Err(self.error("expected ')'".to_owned()));
+            }
+
+            return Ok(expression);
+        }
+
+        self.skip_whitespace();
+
+        let length = self
+            .rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '$' || c == ':'))
+            .unwrap_or(self.rest.len());
+
+        let (token, rest) = self.rest.split_at(length);
+
+        if token.is_empty() {
+            return Err(match self.rest.chars().next() {
+                Some(c) => self.error(format!("unexpected '{}'", c)),
+                None => self.error("unexpected end of the condition".to_owned()),
+            });
+        }
+
+        self.rest = rest;
+
+        if token.starts_with(|c: char| c.is_ascii_digit()) {
+            parse_number(token)
+                .map(Expression::Number)
+                .ok_or_else(|| self.error(format!("invalid number '{}'", token)))
+        } else {
+            Ok(Expression::Identifier(token.to_owned()))
+        }
+    }
+}
+
+/// Parse a decimal, hexadecimal (`0x`) or binary (`0b`) number, which can contain underscores.
+fn parse_number(token: &str) -> Option<u64> {
+    let token = token.replace('_', "");
+
+    if let Some(hex) = token
+        .strip_prefix("0x")
+        .or_else(|| token.strip_prefix("0X"))
+    {
+        u64::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = token.strip_prefix("0b") {
+        u64::from_str_radix(binary, 2).ok()
+    } else {
+        token.parse().ok()
+    }
+}
+
+/// A condition of a breakpoint, which is an expression over the registers and variables
+/// of the target.
+///
+/// Conditions use the syntax and operators of C, e.g. `r0 == 3 && COUNTER > 10`.
+/// Identifiers are the names of registers, like `r0`, `pc` or `sp`, or the names of
+/// variables in the symbols attached to the session with [`Session::load_symbols`].
+/// The value of a variable is read from memory according to its size, `&VARIABLE` is its
+/// address, and `*address` reads a 32 bit word from memory. All values are unsigned
+/// 64 bit integers, and the condition is true if its value is not zero.
+#[derive(Clone, PartialEq, Eq)]
+pub struct BreakpointCondition {
+    source: String,
+    expression: Expression,
+}
+
+impl BreakpointCondition {
+    /// The condition as it was parsed.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+}
+
+impl FromStr for BreakpointCondition {
+    type Err = ConditionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self {
+            source: s.to_owned(),
+            expression: Parser::parse(s)?,
+        })
+    }
+}
+
+impl fmt::Debug for BreakpointCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("BreakpointCondition")
+            .field(&self.source)
+            .finish()
+    }
+}
+
+impl fmt::Display for BreakpointCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// The location of a breakpoint managed by a [`Session`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BreakpointLocation {
+    /// An address in memory.
+    Address(u32),
+    /// A line of a source file, e.g. `main.rs` and `42`.
+    ///
+    /// The file is matched against the end of the source paths in the debug information.
+    /// If the code of the line is contained at several addresses, a hardware breakpoint
+    /// is set at each of them.
+    Source {
+        /// The source file.
+        file: PathBuf,
+        /// The line in the source file.
+        line: u64,
+    },
+}
+
+/// The type of a breakpoint managed by a [`Session`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakpointType {
+    /// A hardware breakpoint, which halts the core before an instruction is executed.
+    Breakpoint,
+    /// A hardware watchpoint, which halts the core when memory is accessed.
+    Watchpoint {
+        /// The number of bytes to watch.
+        length: u32,
+        /// The kind of access which triggers the watchpoint.
+        kind: WatchpointKind,
+    },
+}
+
+/// The settings of a breakpoint managed by a [`Session`].
+#[derive(Debug, Clone)]
+pub struct BreakpointOptions {
+    /// The type of the breakpoint.
+    pub kind: BreakpointType,
+    /// Disabled breakpoints are kept by the session, but not set on the core.
+    pub enabled: bool,
+    /// The condition which has to be true for the breakpoint to stop the core.
+    ///
+    /// Conditions are only evaluated for breakpoints, watchpoints always stop the core.
+    pub condition: Option<BreakpointCondition>,
+    /// The condition on the hit count which has to be met for the breakpoint to stop the core.
+    pub hit_condition: Option<HitCondition>,
+}
+
+impl Default for BreakpointOptions {
+    fn default() -> Self {
+        Self {
+            kind: BreakpointType::Breakpoint,
+            enabled: true,
+            condition: None,
+            hit_condition: None,
+        }
+    }
+}
+
+/// A breakpoint managed by a [`Session`].
+#[derive(Debug, Clone)]
+pub struct ManagedBreakpoint {
+    core: usize,
+    location: BreakpointLocation,
+    options: BreakpointOptions,
+    /// The addresses the location resolved to.
+    addresses: Vec<u32>,
+    hit_count: u32,
+}
+
+impl ManagedBreakpoint {
+    /// The id of the core the breakpoint is set on.
+    pub fn core(&self) -> usize {
+        self.core
+    }
+
+    /// The location of the breakpoint.
+    pub fn location(&self) -> &BreakpointLocation {
+        &self.location
+    }
+
+    /// The type of the breakpoint.
+    pub fn kind(&self) -> BreakpointType {
+        self.options.kind
+    }
+
+    /// Check if the breakpoint is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.options.enabled
+    }
+
+    /// The addresses the location of the breakpoint resolved to.
+    ///
+    /// This is empty for source locations, as long as no debug information was given to
+    /// resolve them, or if no code was found for the location. Addresses in memory
+    /// regions with an alias are followed by the same addresses in the alias.
+    pub fn addresses(&self) -> &[u32] {
+        &self.addresses
+    }
+
+    /// The condition which has to be true for the breakpoint to stop the core.
+    pub fn condition(&self) -> Option<&BreakpointCondition> {
+        self.options.condition.as_ref()
+    }
+
+    /// The condition on the hit count which has to be met for the breakpoint to stop the core.
+    pub fn hit_condition(&self) -> Option<HitCondition> {
+        self.options.hit_condition
+    }
+
+    /// The number of times the breakpoint was hit with a true condition, including
+    /// the hits which did not stop the core because of the hit condition.
+    pub fn hit_count(&self) -> u32 {
+        self.hit_count
+    }
+
+    /// Resolve the location to addresses, using `debug_info` for source locations.
+    ///
+    /// Addresses in memory regions with an alias, like the secure and non-secure views of the
+    /// memory of ARMv8-M chips, are also set in the alias, so the breakpoint is hit no matter
+    /// which view the core executes from.
+    fn resolve(&mut self, debug_info: Option<&DebugInfo>, memory_map: &[MemoryRegion]) {
+        let addresses = match (&self.location, debug_info) {
+            (BreakpointLocation::Address(address), _) => vec![*address],
+            (BreakpointLocation::Source { file, line }, Some(debug_info)) => {
+                match debug_info.get_breakpoint_locations(file, *line) {
+                    Ok(addresses) => addresses.into_iter().map(|a| a as u32).collect(),
+                    Err(error) => {
+                        log::warn!(
+                            "Failed to resolve breakpoint location {}:{}: {}",
+                            file.display(),
+                            line,
+                            error
+                        );
+                        Vec::new()
+                    }
+                }
+            }
+            (BreakpointLocation::Source { .. }, None) => Vec::new(),
+        };
+
+        let aliases: Vec<u32> = addresses
+            .iter()
+            .filter_map(|&address| {
+                memory_map.iter().find_map(|region| {
+                    region
+                        .address_to_alias(address)
+                        .or_else(|| region.address_from_alias(address))
+                })
+            })
+            .collect();
+
+        self.addresses = addresses;
+        self.addresses.extend(aliases);
+    }
+}
+
+/// The result of [`Session::handle_breakpoint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakpointHit {
+    /// The conditions of the breakpoint are met, and the core stays halted.
+    Stopped(ManagedBreakpointId),
+    /// The conditions of all breakpoints at the address are not met, and the core was resumed.
+    Resumed,
+    /// The core is not halted at a breakpoint managed by the session.
+    NotManaged,
+}
+
+/// The breakpoints managed by a [`Session`].
+#[derive(Debug, Default)]
+pub(crate) struct BreakpointManager {
+    breakpoints: Vec<(ManagedBreakpointId, ManagedBreakpoint)>,
+    next_id: usize,
+}
+
+impl BreakpointManager {
+    /// The enabled breakpoints at `address` on the core `core`, without watchpoints.
+    fn at(&self, core: usize, address: u32) -> impl Iterator<Item = ManagedBreakpointId> + '_ {
+        self.breakpoints
+            .iter()
+            .filter(move |(_, breakpoint)| {
+                breakpoint.core == core
+                    && breakpoint.options.enabled
+                    && breakpoint.options.kind == BreakpointType::Breakpoint
+                    && breakpoint.addresses.contains(&address)
+            })
+            .map(|(id, _)| *id)
+    }
+
+    fn get(&self, id: ManagedBreakpointId) -> Option<&ManagedBreakpoint> {
+        self.breakpoints
+            .iter()
+            .find(|(breakpoint_id, _)| *breakpoint_id == id)
+            .map(|(_, breakpoint)| breakpoint)
+    }
+
+    fn get_mut(&mut self, id: ManagedBreakpointId) -> Option<&mut ManagedBreakpoint> {
+        self.breakpoints
+            .iter_mut()
+            .find(|(breakpoint_id, _)| *breakpoint_id == id)
+            .map(|(_, breakpoint)| breakpoint)
+    }
+
+    /// The hardware breakpoints and watchpoints required by the enabled breakpoints of `core`.
+    fn active(&self, core: usize) -> (Vec<u32>, Vec<Watchpoint>) {
+        let mut breakpoints = Vec::new();
+        let mut watchpoints = Vec::new();
+
+        for (_, breakpoint) in &self.breakpoints {
+            if breakpoint.core != core || !breakpoint.options.enabled {
+                continue;
+            }
+
+            for &address in &breakpoint.addresses {
+                match breakpoint.options.kind {
+                    BreakpointType::Breakpoint => breakpoints.push(address),
+                    BreakpointType::Watchpoint { length, kind } => watchpoints.push(Watchpoint {
+                        address,
+                        length,
+                        kind,
+                    }),
+                }
+            }
+        }
+
+        breakpoints.sort_unstable();
+        breakpoints.dedup();
+        watchpoints.dedup();
+
+        (breakpoints, watchpoints)
+    }
+
+    fn cores(&self) -> Vec<usize> {
+        let mut cores: Vec<usize> = self
+            .breakpoints
+            .iter()
+            .map(|(_, breakpoint)| breakpoint.core)
+            .collect();
+
+        cores.sort_unstable();
+        cores.dedup();
+        cores
+    }
+}
+
+/// Evaluates conditions on a halted core.
+struct TargetEnvironment<'a, 'probe> {
+    core: &'a mut Core<'probe>,
+    /// The symbols used by the condition.
+    symbols: &'a [(String, Symbol)],
+}
+
+impl TargetEnvironment<'_, '_> {
+    fn symbol(&self, identifier: &str) -> Option<&Symbol> {
+        self.symbols
+            .iter()
+            .find(|(name, _)| name == identifier)
+            .map(|(_, symbol)| symbol)
+    }
+
+    fn register(&self, identifier: &str) -> Option<CoreRegisterAddress> {
+        let registers = self.core.registers();
+
+        let register = match identifier.to_ascii_lowercase().as_str() {
+            "pc" => registers.program_counter(),
+            "sp" => registers.stack_pointer(),
+            "lr" | "ra" => registers.return_address(),
+            _ => registers
+                .registers()
+                .find(|register| register.name().eq_ignore_ascii_case(identifier))?,
+        };
+
+        Some(register.into())
+    }
+}
+
+impl Environment for TargetEnvironment<'_, '_> {
+    fn value(&mut self, identifier: &str) -> Result<u64, ConditionError> {
+        if let Some(register) = self.register(identifier) {
+            let value: u32 = self.core.read_core_reg(register)?;
+            return Ok(value as u64);
+        }
+
+        let symbol = self
+            .symbol(identifier)
+            .ok_or_else(|| ConditionError::UnknownIdentifier(identifier.to_owned()))?
+            .clone();
+
+        let value = match symbol.size {
+            1 => self.core.read_word_8(symbol.address)? as u64,
+            2 | 8 => {
+                let mut data = [0u8; 8];
+                self.core
+                    .read_8(symbol.address, &mut data[..symbol.size as usize])?;
+                u64::from_le_bytes(data)
+            }
+            _ => self.core.read_word_32(symbol.address)? as u64,
+        };
+
+        Ok(value)
+    }
+
+    fn address(&mut self, identifier: &str) -> Result<u64, ConditionError> {
+        self.symbol(identifier)
+            .map(|symbol| symbol.address as u64)
+            .ok_or_else(|| ConditionError::UnknownIdentifier(identifier.to_owned()))
+    }
+
+    fn read_word(&mut self, address: u64) -> Result<u64, ConditionError> {
+        Ok(self.core.read_word_32(address as u32)? as u64)
+    }
+}
+
+impl Session {
+    /// Add a breakpoint on the core `core`, which is managed by the session.
+    ///
+    /// The breakpoint is set on the core as long as it is enabled, and set again
+    /// automatically after a reset of the core. Source locations are resolved with
+    /// `debug_info`. Without debug information, the breakpoint is kept, but only set
+    /// once [`Session::reload_breakpoints`] is called with debug information.
+    ///
+    /// If the hardware breakpoint cannot be set, the breakpoint is not added.
+    pub fn add_breakpoint(
+        &mut self,
+        core: usize,
+        location: BreakpointLocation,
+        options: BreakpointOptions,
+        debug_info: Option<&DebugInfo>,
+    ) -> Result<ManagedBreakpointId, Error> {
+        let id = ManagedBreakpointId(self.breakpoints.next_id);
+        self.breakpoints.next_id += 1;
+
+        let mut breakpoint = ManagedBreakpoint {
+            core,
+            location,
+            options,
+            addresses: Vec::new(),
+            hit_count: 0,
+        };
+        breakpoint.resolve(debug_info, &self.target().memory_map);
+
+        self.breakpoints.breakpoints.push((id, breakpoint));
+
+        if let Err(error) = self.apply_breakpoints(core) {
+            self.breakpoints.breakpoints.pop();
+
+            if let Err(error) = self.apply_breakpoints(core) {
+                log::warn!(
+                    "Failed to restore the breakpoints of core {}: {}",
+                    core,
+                    error
+                );
+            }
+
+            return Err(error);
+        }
+
+        Ok(id)
+    }
+
+    /// Remove a breakpoint added with [`Session::add_breakpoint`].
+    ///
+    /// The hardware breakpoint is cleared when no other breakpoint uses its address.
+    pub fn remove_breakpoint(&mut self, id: ManagedBreakpointId) -> Result<(), Error> {
+        let index = match self
+            .breakpoints
+            .breakpoints
+            .iter()
+            .position(|(breakpoint_id, _)| *breakpoint_id == id)
+        {
+            Some(index) => index,
+            None => return Ok(()),
+        };
+
+        let (_, breakpoint) = self.breakpoints.breakpoints.remove(index);
+
+        self.apply_breakpoints(breakpoint.core)
+    }
+
+    /// Enable or disable a breakpoint added with [`Session::add_breakpoint`].
+    pub fn enable_breakpoint(
+        &mut self,
+        id: ManagedBreakpointId,
+        enabled: bool,
+    ) -> Result<(), Error> {
+        let breakpoint = match self.breakpoints.get_mut(id) {
+            Some(breakpoint) => breakpoint,
+            None => return Ok(()),
+        };
+
+        if breakpoint.options.enabled == enabled {
+            return Ok(());
+        }
+
+        breakpoint.options.enabled = enabled;
+        let core = breakpoint.core;
+
+        if let Err(error) = self.apply_breakpoints(core) {
+            // Can't fail, the breakpoint was just looked up.
+            self.breakpoints.get_mut(id).unwrap().options.enabled = !enabled;
+
+            return Err(error);
+        }
+
+        Ok(())
+    }
+
+    /// The breakpoint with the given id.
+    pub fn breakpoint(&self, id: ManagedBreakpointId) -> Option<&ManagedBreakpoint> {
+        self.breakpoints.get(id)
+    }
+
+    /// All breakpoints added with [`Session::add_breakpoint`].
+    pub fn breakpoints(&self) -> impl Iterator<Item = (ManagedBreakpointId, &ManagedBreakpoint)> {
+        self.breakpoints
+            .breakpoints
+            .iter()
+            .map(|(id, breakpoint)| (*id, breakpoint))
+    }
+
+    /// Resolve the source locations of all breakpoints again, and set all breakpoints on the cores.
+    ///
+    /// This has to be called after a new program was flashed, with its debug information,
+    /// so that the breakpoints at source locations are moved to the new addresses.
+    pub fn reload_breakpoints(&mut self, debug_info: Option<&DebugInfo>) -> Result<(), Error> {
+        let memory_map = self.target().memory_map.clone();
+
+        for (_, breakpoint) in &mut self.breakpoints.breakpoints {
+            breakpoint.resolve(debug_info, &memory_map);
+        }
+
+        for core in self.breakpoints.cores() {
+            self.apply_breakpoints(core)?;
+        }
+
+        Ok(())
+    }
+
+    /// Set the hardware breakpoints and watchpoints of the enabled breakpoints of `core`,
+    /// and clear the ones which are not used anymore.
+    fn apply_breakpoints(&mut self, core: usize) -> Result<(), Error> {
+        let (breakpoints, watchpoints) = self.breakpoints.active(core);
+
+        self.core(core)?
+            .set_persistent_breakpoints(breakpoints, watchpoints)
+    }
+
+    /// Handle a halt of the core `core` at a breakpoint.
+    ///
+    /// If the core is halted at the address of breakpoints added with [`Session::add_breakpoint`],
+    /// their conditions are evaluated, and the hit counts of the breakpoints with a true condition
+    /// are increased. If no breakpoint stops the core, it steps over the breakpoint and is resumed.
+    ///
+    /// If a condition cannot be evaluated, the breakpoint stops the core.
+    pub fn handle_breakpoint(&mut self, core: usize) -> Result<BreakpointHit, Error> {
+        let pc = {
+            let mut core = self.core(core)?;
+            let pc_register = core.registers().program_counter();
+            core.read_core_reg(pc_register)?
+        };
+
+        let ids: Vec<_> = self.breakpoints.at(core, pc).collect();
+
+        if ids.is_empty() {
+            return Ok(BreakpointHit::NotManaged);
+        }
+
+        let mut stopped = None;
+
+        for id in ids {
+            // Can't fail, the id was just looked up.
+            let breakpoint = self.breakpoints.get_mut(id).unwrap().clone();
+
+            let condition_met = match &breakpoint.options.condition {
+                Some(condition) => match self.evaluate_condition(core, condition) {
+                    Ok(value) => value != 0,
+                    Err(error) => {
+                        log::warn!(
+                            "Failed to evaluate breakpoint condition '{}': {}",
+                            condition,
+                            error
+                        );
+                        true
+                    }
+                },
+                None => true,
+            };
+
+            if !condition_met {
+                continue;
+            }
+
+            let breakpoint = self.breakpoints.get_mut(id).unwrap();
+            breakpoint.hit_count += 1;
+
+            let hit_condition_met = breakpoint
+                .options
+                .hit_condition
+                .map(|hit_condition| hit_condition.is_met(breakpoint.hit_count))
+                .unwrap_or(true);
+
+            if hit_condition_met && stopped.is_none() {
+                stopped = Some(id);
+            }
+        }
+
+        if let Some(id) = stopped {
+            return Ok(BreakpointHit::Stopped(id));
+        }
+
+        log::debug!("Resuming core {} at breakpoint {:#010x}", core, pc);
+
+        let mut core = self.core(core)?;
+
+        // The hardware breakpoint has to be removed to step over it.
+        core.clear_hw_breakpoint(pc)?;
+        let step_result = core.step();
+        core.set_hw_breakpoint(pc)?;
+        step_result?;
+
+        core.run()?;
+
+        Ok(BreakpointHit::Resumed)
+    }
+
+    fn evaluate_condition(
+        &mut self,
+        core: usize,
+        condition: &BreakpointCondition,
+    ) -> Result<u64, ConditionError> {
+        let mut identifiers = Vec::new();
+        condition.expression.identifiers(&mut identifiers);
+
+        let symbols: Vec<(String, Symbol)> = match self.symbols() {
+            Some(table) => identifiers
+                .into_iter()
+                .filter_map(|name| Some((name.to_owned(), table.symbol_by_path(name)?.clone())))
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let mut core = self.core(core)?;
+
+        let mut environment = TargetEnvironment {
+            core: &mut core,
+            symbols: &symbols,
+        };
+
+        condition.expression.evaluate(&mut environment)
+    }
+
+    /// Check if the core `core` halted at a managed breakpoint whose conditions are not met,
+    /// and resume it in that case. Returns `true` if the core was resumed.
+    pub(crate) fn resume_unmet_breakpoint(
+        &mut self,
+        core: usize,
+        status: CoreStatus,
+    ) -> Result<bool, Error> {
+        if status != CoreStatus::Halted(HaltReason::Breakpoint)
+            || self.breakpoints.breakpoints.is_empty()
+        {
+            return Ok(false);
+        }
+
+        Ok(self.handle_breakpoint(core)? == BreakpointHit::Resumed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        BreakpointCondition, BreakpointLocation, BreakpointManager, BreakpointOptions,
+        BreakpointType, ConditionError, Environment, HitCondition, ManagedBreakpoint,
+        ManagedBreakpointId,
+    };
+    use crate::config::{MemoryRegion, NvmRegion};
+    use crate::debug::DebugInfo;
+    use crate::{Watchpoint, WatchpointKind};
+    use std::collections::HashMap;
+
+    struct TestEnvironment {
+        values: HashMap<&'static str, u64>,
+        memory: HashMap<u64, u64>,
+    }
+
+    impl Environment for TestEnvironment {
+        fn value(&mut self, identifier: &str) -> Result<u64, ConditionError> {
+            self.values
+                .get(identifier)
+                .copied()
+                .ok_or_else(|| ConditionError::UnknownIdentifier(identifier.to_owned()))
+        }
+
+        fn address(&mut self, _identifier: &str) -> Result<u64, ConditionError> {
+            Ok(0x2000_0000)
+        }
+
+        fn read_word(&mut self, address: u64) -> Result<u64, ConditionError> {
+            Ok(self.memory.get(&address).copied().unwrap_or(0))
+        }
+    }
+
+    fn evaluate(condition: &str) -> Result<u64, ConditionError> {
+        let mut environment = TestEnvironment {
+            values: vec![("r0", 3), ("COUNTER", 12), ("ptr", 0)]
+                .into_iter()
+                .collect(),
+            memory: vec![(0x2000_0000, 12)].into_iter().collect(),
+        };
+
+        condition
+            .parse::<BreakpointCondition>()?
+            .expression
+            .evaluate(&mut environment)
+    }
+
+    #[test]
+    fn evaluate_conditions() {
+        assert_eq!(evaluate("r0 == 3").unwrap(), 1);
+        assert_eq!(evaluate("r0 == 3 && COUNTER > 10").unwrap(), 1);
+        assert_eq!(evaluate("r0 != 3 || COUNTER <= 10").unwrap(), 0);
+        assert_eq!(evaluate("1 + 2 * 3 == 7").unwrap(), 1);
+        assert_eq!(evaluate("(1 + 2) * 3").unwrap(), 9);
+        assert_eq!(evaluate("0x10 | 0b1 << 2").unwrap(), 0x14);
+        assert_eq!(evaluate("COUNTER & 0x4 && !(r0 % 2 == 0)").unwrap(), 1);
+        assert_eq!(evaluate("*&COUNTER == COUNTER").unwrap(), 1);
+        assert_eq!(evaluate("-1 == ~0").unwrap(), 1);
+        assert_eq!(evaluate("r0 >= 3 && r0 < 3").unwrap(), 0);
+
+        // The right hand side is not evaluated if the left hand side decides the result.
+        assert_eq!(evaluate("ptr != 0 && missing == 1").unwrap(), 0);
+
+        assert!(matches!(
+            evaluate("missing == 1"),
+            Err(ConditionError::UnknownIdentifier(name)) if name == "missing"
+        ));
+        assert!(matches!(
+            evaluate("r0 / ptr"),
+            Err(ConditionError::DivisionByZero)
+        ));
+    }
+
+    #[test]
+    fn invalid_conditions() {
+        for condition in &["", "r0 ==", "(r0 == 1", "r0 == 1)", "0xzz", "&1", "r0 = 1"] {
+            assert!(
+                matches!(
+                    condition.parse::<BreakpointCondition>(),
+                    Err(ConditionError::Parse { .. })
+                ),
+                "{:?} should not be a valid condition",
+                condition
+            );
+        }
+    }
+
+    #[test]
+    fn hit_conditions() {
+        assert_eq!(
+            "==5".parse::<HitCondition>().unwrap(),
+            HitCondition::Equal(5)
+        );
+        assert_eq!(
+            "5".parse::<HitCondition>().unwrap(),
+            HitCondition::AtLeast(5)
+        );
+        assert_eq!(
+            "% 2".parse::<HitCondition>().unwrap(),
+            HitCondition::Multiple(2)
+        );
+        assert!("five".parse::<HitCondition>().is_err());
+
+        assert!(!HitCondition::Equal(2).is_met(1));
+        assert!(HitCondition::Equal(2).is_met(2));
+        assert!(!HitCondition::Equal(2).is_met(3));
+        assert!(HitCondition::AtLeast(2).is_met(3));
+        assert!(HitCondition::Multiple(2).is_met(4));
+        assert!(!HitCondition::Multiple(2).is_met(3));
+        assert!(!HitCondition::Multiple(0).is_met(0));
+    }
+
+    fn manager(
+        breakpoints: Vec<(usize, BreakpointLocation, BreakpointOptions)>,
+    ) -> BreakpointManager {
+        let debug_info = DebugInfo::from_file("tests/inlined-function").unwrap();
+
+        let breakpoints = breakpoints
+            .into_iter()
+            .enumerate()
+            .map(|(id, (core, location, options))| {
+                let mut breakpoint = ManagedBreakpoint {
+                    core,
+                    location,
+                    options,
+                    addresses: Vec::new(),
+                    hit_count: 0,
+                };
+                breakpoint.resolve(Some(&debug_info), &[]);
+
+                (ManagedBreakpointId(id), breakpoint)
+            })
+            .collect();
+
+        BreakpointManager {
+            breakpoints,
+            next_id: 0,
+        }
+    }
+
+    #[test]
+    fn active_breakpoints() {
+        let watchpoint = BreakpointOptions {
+            kind: BreakpointType::Watchpoint {
+                length: 4,
+                kind: WatchpointKind::Write,
+            },
+            ..Default::default()
+        };
+        let disabled = BreakpointOptions {
+            enabled: false,
+            ..Default::default()
+        };
+
+        let manager = manager(vec![
+            (0, BreakpointLocation::Address(0x100), Default::default()),
+            // The line is contained twice in the program.
+            (
+                0,
+                BreakpointLocation::Source {
+                    file: "inlined-function/src/main.rs".into(),
+                    line: 41,
+                },
+                Default::default(),
+            ),
+            (0, BreakpointLocation::Address(0x100), Default::default()),
+            (0, BreakpointLocation::Address(0x200), disabled),
+            (0, BreakpointLocation::Address(0x2000_0000), watchpoint),
+            (1, BreakpointLocation::Address(0x300), Default::default()),
+        ]);
+
+        assert_eq!(
+            manager.active(0),
+            (
+                vec![0x100, 0x128, 0x186],
+                vec![Watchpoint {
+                    address: 0x2000_0000,
+                    length: 4,
+                    kind: WatchpointKind::Write
+                }]
+            )
+        );
+        assert_eq!(manager.active(1), (vec![0x300], vec![]));
+
+        assert_eq!(
+            manager.at(0, 0x100).collect::<Vec<_>>(),
+            vec![ManagedBreakpointId(0), ManagedBreakpointId(2)]
+        );
+        assert_eq!(
+            manager.at(0, 0x186).collect::<Vec<_>>(),
+            vec![ManagedBreakpointId(1)]
+        );
+        assert_eq!(manager.at(0, 0x200).count(), 0);
+        assert_eq!(manager.at(0, 0x2000_0000).count(), 0);
+    }
+
+    #[test]
+    fn breakpoints_in_alias() {
+        let memory_map = vec![MemoryRegion::Nvm(NvmRegion {
+            range: 0x0000_0000..0x0001_0000,
+            is_boot_memory: true,
+            cores: vec!["main".into()],
+            access_port: None,
+            alias: Some(0x1000_0000),
+        })];
+
+        let mut breakpoint = ManagedBreakpoint {
+            core: 0,
+            location: BreakpointLocation::Address(0x100),
+            options: Default::default(),
+            addresses: Vec::new(),
+            hit_count: 0,
+        };
+
+        breakpoint.resolve(None, &memory_map);
+        assert_eq!(breakpoint.addresses(), &[0x100, 0x1000_0100]);
+
+        breakpoint.location = BreakpointLocation::Address(0x1000_0200);
+        breakpoint.resolve(None, &memory_map);
+        assert_eq!(breakpoint.addresses(), &[0x1000_0200, 0x200]);
+
+        breakpoint.location = BreakpointLocation::Address(0x2000_0000);
+        breakpoint.resolve(None, &memory_map);
+        assert_eq!(breakpoint.addresses(), &[0x2000_0000]);
+    }
+}
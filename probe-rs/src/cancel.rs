@@ -0,0 +1,54 @@
+//! Cancellation of long-running operations.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::Error;
+
+/// A token to cancel long-running operations, e.g. flashing or waiting for a core to halt,
+/// from another thread.
+///
+/// All clones of a token share the same state, so one clone can be passed to the operation,
+/// and another one kept to cancel it, e.g. when the user presses a button in a GUI.
+/// Operations check the token between steps which leave the target in a usable state,
+/// and return [`Error::Cancelled`] if it was cancelled, or
+/// [`FlashError::Cancelled`](crate::flashing::FlashError::Cancelled) for flashing.
+///
+/// ```
+/// use probe_rs::CancellationToken;
+///
+/// let token = CancellationToken::new();
+/// let operation_token = token.clone();
+///
+/// token.cancel();
+///
+/// assert!(operation_token.is_cancelled());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Create a token which is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel all operations which use this token, or one of its clones.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns `true` if the token was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Returns [`Error::Cancelled`] if the token was cancelled.
+    pub fn check(&self) -> Result<(), Error> {
+        if self.is_cancelled() {
+            Err(Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -25,14 +25,18 @@
 mod chip_info;
 mod registry;
 mod target;
+#[cfg(feature = "target-gen")]
+pub mod target_gen;
 
 pub use probe_rs_target::{
-    Chip, ChipFamily, Core, CoreType, FlashProperties, MemoryRange, MemoryRegion, NvmRegion,
-    PageInfo, RamRegion, RawFlashAlgorithm, SectorDescription, SectorInfo, TargetDescriptionSource,
+    Chip, ChipFamily, Core, CoreType, Diagnostic, DiagnosticKind, FlashProperties, MemoryRange,
+    MemoryRegion, NvmRegion, PageInfo, RamRegion, RawFlashAlgorithm, ResetKind, SectorDescription,
+    SectorInfo, Severity, TargetDescriptionSource,
 };
 
 pub use registry::{
-    add_target_from_yaml, families, get_target_by_name, search_chips, RegistryError,
+    add_target_family, add_target_from_yaml, families, get_target_by_name, lint_target_from_yaml,
+    search, search_chips, ChipSearchResult, RegistryError,
 };
 pub use target::{DebugSequence, Target, TargetParseError, TargetSelector};
 
@@ -2,9 +2,13 @@
 
 use super::{Chip, ChipFamily, ChipInfo, Core, Target, TargetDescriptionSource};
 use crate::config::CoreType;
-use once_cell::sync::Lazy;
-use probe_rs_target::{ArmCoreAccessOptions, CoreAccessOptions, RiscvCoreAccessOptions};
+use once_cell::sync::{Lazy, OnceCell};
+use probe_rs_target::{
+    ArmCoreAccessOptions, CoreAccessOptions, Diagnostic, MemoryRegion, RiscvCoreAccessOptions,
+};
 use std::fs::File;
+#[cfg(feature = "builtin-targets")]
+use std::ops::Range;
 use std::path::Path;
 use std::sync::{Arc, Mutex, TryLockError};
 
@@ -67,6 +71,8 @@ fn add_generic_targets(vec: &mut Vec<ChipFamily>) {
                 }],
                 memory_map: vec![],
                 flash_algorithms: vec![],
+                default_reset: None,
+                partitions: vec![],
             }],
             flash_algorithms: vec![],
             source: TargetDescriptionSource::Generic,
@@ -87,6 +93,8 @@ fn add_generic_targets(vec: &mut Vec<ChipFamily>) {
                 }],
                 memory_map: vec![],
                 flash_algorithms: vec![],
+                default_reset: None,
+                partitions: vec![],
             }],
             flash_algorithms: vec![],
             source: TargetDescriptionSource::Generic,
@@ -107,6 +115,8 @@ fn add_generic_targets(vec: &mut Vec<ChipFamily>) {
                 }],
                 memory_map: vec![],
                 flash_algorithms: vec![],
+                default_reset: None,
+                partitions: vec![],
             }],
             flash_algorithms: vec![],
             source: TargetDescriptionSource::Generic,
@@ -120,10 +130,14 @@ fn add_generic_targets(vec: &mut Vec<ChipFamily>) {
                 cores: vec![Core {
                     name: "core".to_owned(),
                     core_type: CoreType::Riscv,
-                    core_access_options: CoreAccessOptions::Riscv(RiscvCoreAccessOptions {}),
+                    core_access_options: CoreAccessOptions::Riscv(RiscvCoreAccessOptions {
+                        hart_id: 0,
+                    }),
                 }],
                 memory_map: vec![],
                 flash_algorithms: vec![],
+                default_reset: None,
+                partitions: vec![],
             }],
             flash_algorithms: vec![],
             source: TargetDescriptionSource::Generic,
@@ -132,20 +146,163 @@ fn add_generic_targets(vec: &mut Vec<ChipFamily>) {
 }
 
 /// Registry of all available targets.
+/// A chip of the registry which matches the query of [`search`].
+#[derive(Debug, Clone)]
+pub struct ChipSearchResult {
+    /// The name of the family of the chip.
+    pub family: String,
+    /// The name of the chip.
+    pub name: String,
+    /// The cores of the chip.
+    pub cores: Vec<Core>,
+    /// The total size of the flash of the chip in bytes.
+    pub flash_size: u64,
+    /// The total size of the RAM of the chip in bytes.
+    pub ram_size: u64,
+    /// How well the name of the chip matches the query, a higher score is a better match.
+    pub score: u32,
+}
+
+impl ChipSearchResult {
+    fn new(family: &ChipFamily, chip: &Chip, score: u32) -> Self {
+        let mut flash_size = 0;
+        let mut ram_size = 0;
+
+        for region in &chip.memory_map {
+            match region {
+                MemoryRegion::Nvm(region) => {
+                    flash_size += u64::from(region.range.end - region.range.start)
+                }
+                MemoryRegion::Ram(region) => {
+                    ram_size += u64::from(region.range.end - region.range.start)
+                }
+                MemoryRegion::Generic(_) => {}
+            }
+        }
+
+        Self {
+            family: family.name.clone(),
+            name: chip.name.clone(),
+            cores: chip.cores.clone(),
+            flash_size,
+            ram_size,
+            score,
+        }
+    }
+}
+
+/// Match the name of a chip against a search `query`, ignoring the case and all characters
+/// which are not letters or digits.
+///
+/// Returns `None` if the name doesn't match. Otherwise, the score is higher for an exact
+/// match than for a prefix of the name, than for a part of the name, and lowest if the
+/// characters of the query only appear in the same order in the name, e.g. `s32f407` in
+/// `STM32F407VGTx`.
+fn match_score(name: &str, query: &str) -> Option<u32> {
+    fn normalize(text: &str) -> String {
+        text.chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .map(|c| c.to_ascii_lowercase())
+            .collect()
+    }
+
+    let name = normalize(name);
+    let query = normalize(query);
+
+    if name == query {
+        Some(400)
+    } else if name.starts_with(&query) {
+        Some(300)
+    } else if name.contains(&query) {
+        Some(200)
+    } else {
+        let mut name_chars = name.chars();
+        let is_subsequence = query.chars().all(|c| name_chars.any(|n| n == c));
+
+        if is_subsequence {
+            Some(100)
+        } else {
+            None
+        }
+    }
+}
+
+/// A family of the registry.
+///
+/// The built-in families are stored compressed, and are only decompressed and deserialized
+/// when they are used, so looking up a chip doesn't cost the time and memory to load all
+/// families.
+struct RegistryFamily {
+    /// The family without its cores, memory maps and flash algorithms. It contains everything
+    /// which is needed to find a chip, so the family isn't loaded for that.
+    summary: ChipFamily,
+    /// The complete family, once it is loaded.
+    family: OnceCell<ChipFamily>,
+    /// The compressed bincode of a built-in family, which is not loaded yet.
+    compressed: &'static [u8],
+}
+
+impl RegistryFamily {
+    /// The complete family, which is loaded on the first use.
+    fn family(&self) -> &ChipFamily {
+        self.family.get_or_init(|| self.load())
+    }
+
+    #[cfg(feature = "builtin-targets")]
+    fn load(&self) -> ChipFamily {
+        let family_bin = miniz_oxide::inflate::decompress_to_vec(self.compressed)
+            .expect("Failed to decompress builtin target. This is a bug.");
+
+        bincode::deserialize(&family_bin)
+            .expect("Failed to deserialize builtin target. This is a bug.")
+    }
+
+    #[cfg(not(feature = "builtin-targets"))]
+    fn load(&self) -> ChipFamily {
+        unreachable!("Only the built-in families are loaded lazily.")
+    }
+}
+
+impl From<ChipFamily> for RegistryFamily {
+    fn from(family: ChipFamily) -> Self {
+        Self {
+            summary: family.clone(),
+            family: OnceCell::from(family),
+            compressed: &[],
+        }
+    }
+}
+
 struct Registry {
     /// All the available chips.
-    families: Vec<ChipFamily>,
+    families: Vec<RegistryFamily>,
 }
 
 impl Registry {
     #[cfg(feature = "builtin-targets")]
     fn from_builtin_families() -> Self {
-        const BUILTIN_TARGETS: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/targets.bincode"));
-
-        let mut families: Vec<ChipFamily> = bincode::deserialize(BUILTIN_TARGETS)
+        const BUILTIN_INDEX: &[u8] =
+            include_bytes!(concat!(env!("OUT_DIR"), "/targets.index.bincode"));
+        const BUILTIN_TARGETS: &[u8] =
+            include_bytes!(concat!(env!("OUT_DIR"), "/targets.bincode.deflate"));
+
+        // The index contains the summaries of the families, and the ranges of the compressed
+        // families in `BUILTIN_TARGETS`, see `build.rs`.
+        let index: Vec<(ChipFamily, Range<usize>)> = bincode::deserialize(BUILTIN_INDEX)
             .expect("Failed to deserialize builtin targets. This is a bug.");
 
-        add_generic_targets(&mut families);
+        let mut families: Vec<RegistryFamily> = index
+            .into_iter()
+            .map(|(summary, range)| RegistryFamily {
+                summary,
+                family: OnceCell::new(),
+                compressed: &BUILTIN_TARGETS[range],
+            })
+            .collect();
+
+        let mut generic_families = vec![];
+        add_generic_targets(&mut generic_families);
+        families.extend(generic_families.into_iter().map(RegistryFamily::from));
 
         // We skip validating the targets here as this is done at a later stage in `get_target`.
         // Additionally, validation for existing targets is done in the tests `validate_generic_targets` and
@@ -156,8 +313,12 @@ impl Registry {
 
     #[cfg(not(feature = "builtin-targets"))]
     fn from_builtin_families() -> Self {
-        let mut families = vec![];
-        add_generic_targets(&mut families);
+        let mut generic_families = vec![];
+        add_generic_targets(&mut generic_families);
+        let families = generic_families
+            .into_iter()
+            .map(RegistryFamily::from)
+            .collect();
 
         // We skip validating the targets here as this is done at a later stage in `get_target`.
         // Additionally, validation for existing targets is done in the tests `validate_generic_targets` and
@@ -166,8 +327,11 @@ impl Registry {
         Self { families }
     }
 
-    fn families(&self) -> &Vec<ChipFamily> {
-        &self.families
+    fn families(&self) -> Vec<ChipFamily> {
+        self.families
+            .iter()
+            .map(|family| family.family().clone())
+            .collect()
     }
 
     fn get_target_by_name(&self, name: impl AsRef<str>) -> Result<Target, RegistryError> {
@@ -181,13 +345,13 @@ impl Registry {
             let mut exact_matches = 0;
             let mut partial_matches = 0;
             for family in &self.families {
-                for variant in family.variants.iter() {
+                for variant in family.summary.variants.iter() {
                     if variant
                         .name
                         .to_ascii_lowercase()
                         .starts_with(&name.to_ascii_lowercase())
                     {
-                        if variant.name.to_ascii_lowercase() != name.to_ascii_lowercase() {
+                        if !variant.name.eq_ignore_ascii_case(name) {
                             log::debug!("Partial match for chip name: {}", variant.name);
                             partial_matches += 1;
                             if exact_matches > 0 {
@@ -221,7 +385,7 @@ impl Registry {
             // Try get the correspnding flash algorithm.
             (family, chip)
         };
-        self.get_target(family, chip)
+        self.get_target(family.family(), chip)
     }
 
     fn search_chips(&self, name: &str) -> Vec<String> {
@@ -230,7 +394,7 @@ impl Registry {
         let mut targets = Vec::new();
 
         for family in &self.families {
-            for variant in family.variants.iter() {
+            for variant in family.summary.variants.iter() {
                 if variant
                     .name
                     .to_ascii_lowercase()
@@ -244,6 +408,41 @@ impl Registry {
         targets
     }
 
+    fn search(&self, query: &str) -> Vec<ChipSearchResult> {
+        let mut results = Vec::new();
+
+        for family in &self.families {
+            let scores: Vec<(usize, u32)> = family
+                .summary
+                .variants
+                .iter()
+                .enumerate()
+                .filter_map(|(index, chip)| Some((index, match_score(&chip.name, query)?)))
+                .collect();
+
+            // The summary has no memory map, so only the families with matching chips are
+            // loaded. The chips are in the same order in the summary and the family.
+            if !scores.is_empty() {
+                let family = family.family();
+
+                results.extend(scores.into_iter().map(|(index, score)| {
+                    ChipSearchResult::new(family, &family.variants[index], score)
+                }));
+            }
+        }
+
+        // The best matches first, and the shortest names first among equal matches, as
+        // they are closest to the query.
+        results.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then(a.name.len().cmp(&b.name.len()))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+
+        results
+    }
+
     fn get_target_by_chip_info(&self, chip_info: ChipInfo) -> Result<Target, RegistryError> {
         let (family, chip) = {
             match chip_info {
@@ -251,7 +450,8 @@ impl Registry {
                     // Try get the corresponding chip.
 
                     let families = self.families.iter().filter(|f| {
-                        f.manufacturer
+                        f.summary
+                            .manufacturer
                             .map(|m| m == chip_info.manufacturer)
                             .unwrap_or(false)
                     });
@@ -259,9 +459,10 @@ impl Registry {
                     let mut identified_chips = Vec::new();
 
                     for family in families {
-                        log::debug!("Checking family {}", family.name);
+                        log::debug!("Checking family {}", family.summary.name);
 
                         let chips = family
+                            .summary
                             .variants()
                             .iter()
                             .filter(|v| v.part.map(|p| p == chip_info.part).unwrap_or(false))
@@ -283,7 +484,7 @@ impl Registry {
                 }
             }
         };
-        self.get_target(family, chip)
+        self.get_target(family.family(), chip)
     }
 
     fn get_target(&self, family: &ChipFamily, chip: &Chip) -> Result<Target, RegistryError> {
@@ -295,6 +496,10 @@ impl Registry {
         let file = File::open(path_to_yaml)?;
         let family: ChipFamily = serde_yaml::from_reader(file)?;
 
+        self.add_target_family(family)
+    }
+
+    fn add_target_family(&mut self, family: ChipFamily) -> Result<(), RegistryError> {
         family
             .validate()
             .map_err(|e| RegistryError::InvalidChipFamilyDefinition(family.clone(), e))?;
@@ -302,16 +507,27 @@ impl Registry {
         let index = self
             .families
             .iter()
-            .position(|old_family| old_family.name == family.name);
+            .position(|old_family| old_family.summary.name == family.name);
         if let Some(index) = index {
             self.families.remove(index);
         }
-        self.families.push(family);
+        self.families.push(RegistryFamily::from(family));
 
         Ok(())
     }
 }
 
+/// Check the target description file at `path_to_yaml` for problems, without adding it
+/// to the registry.
+///
+/// See [`ChipFamily::lint`] for the checks.
+pub fn lint_target_from_yaml(path_to_yaml: &Path) -> Result<Vec<Diagnostic>, RegistryError> {
+    let file = File::open(path_to_yaml)?;
+    let family: ChipFamily = serde_yaml::from_reader(file)?;
+
+    Ok(family.lint())
+}
+
 /// Get a target from the internal registry based on its name.
 pub fn get_target_by_name(name: impl AsRef<str>) -> Result<Target, RegistryError> {
     REGISTRY.try_lock()?.get_target_by_name(name)
@@ -322,6 +538,14 @@ pub fn search_chips(name: impl AsRef<str>) -> Result<Vec<String>, RegistryError>
     Ok(REGISTRY.try_lock()?.search_chips(name.as_ref()))
 }
 
+/// Search the chips of the internal registry, with a fuzzy match of their names.
+///
+/// The results are sorted with the best match first, and contain a summary of each chip,
+/// e.g. for a chip picker in a front-end. An empty query matches all chips.
+pub fn search(query: impl AsRef<str>) -> Result<Vec<ChipSearchResult>, RegistryError> {
+    Ok(REGISTRY.try_lock()?.search(query.as_ref()))
+}
+
 /// Try to retrieve a target based on [ChipInfo] read from a target.
 pub(crate) fn get_target_by_chip_info(chip_info: ChipInfo) -> Result<Target, RegistryError> {
     REGISTRY.try_lock()?.get_target_by_chip_info(chip_info)
@@ -333,10 +557,17 @@ pub fn add_target_from_yaml(path_to_yaml: &Path) -> Result<(), RegistryError> {
     REGISTRY.try_lock()?.add_target_from_yaml(path_to_yaml)
 }
 
+/// Add the targets of a chip family to the internal target registry.
+///
+/// A family with the same name is replaced.
+pub fn add_target_family(family: ChipFamily) -> Result<(), RegistryError> {
+    REGISTRY.try_lock()?.add_target_family(family)
+}
+
 /// Get a list of all families which are contained in the internal
 /// registry.
 pub fn families() -> Result<Vec<ChipFamily>, RegistryError> {
-    Ok(REGISTRY.try_lock()?.families().clone())
+    Ok(REGISTRY.try_lock()?.families())
 }
 
 #[cfg(test)]
@@ -344,6 +575,7 @@ mod tests {
     use super::*;
 
     #[test]
+    #[cfg(feature = "builtin-targets")]
     fn try_fetch_not_unique() {
         let registry = Registry::from_builtin_families();
         // ambiguous: partially matches STM32G081KBUx and STM32G081KBUxN
@@ -363,6 +595,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "builtin-targets")]
     fn try_fetch2() {
         let registry = Registry::from_builtin_families();
         // ok: matches both STM32G081KBUx and STM32G081KBUxN, but the first one is an exact match
@@ -370,6 +603,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "builtin-targets")]
     fn try_fetch3() {
         let registry = Registry::from_builtin_families();
         // ok: unique substring match
@@ -377,12 +611,55 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "builtin-targets")]
     fn try_fetch4() {
         let registry = Registry::from_builtin_families();
         // ok: unique exact match
         assert!(registry.get_target_by_name("nrf51822_Xxaa").is_ok());
     }
 
+    #[test]
+    #[cfg(feature = "builtin-targets")]
+    fn load_families_lazily() {
+        let registry = Registry::from_builtin_families();
+        assert!(registry.get_target_by_name("nrf51822_Xxaa").is_ok());
+
+        // Only the family of the chip is loaded, the other families are only searched by
+        // their summaries.
+        let loaded: Vec<&str> = registry
+            .families
+            .iter()
+            .filter(|family| !family.compressed.is_empty())
+            .filter_map(|family| family.family.get())
+            .map(|family| family.name.as_str())
+            .collect();
+        assert_eq!(loaded, vec!["nRF51 Series"]);
+    }
+
+    #[test]
+    fn fuzzy_match_score() {
+        assert_eq!(match_score("nRF52832_xxAA", "nrf52832-xxaa"), Some(400));
+        assert_eq!(match_score("nRF52832_xxAA", "nrf52832"), Some(300));
+        assert_eq!(match_score("STM32F407VGTx", "f407"), Some(200));
+        assert_eq!(match_score("STM32F407VGTx", "s32f407"), Some(100));
+        assert_eq!(match_score("STM32F407VGTx", "f704"), None);
+    }
+
+    #[test]
+    #[cfg(feature = "builtin-targets")]
+    fn search_chips_by_fuzzy_name() {
+        let registry = Registry::from_builtin_families();
+        let results = registry.search("nrf51822");
+
+        assert!(!results.is_empty());
+        assert!(results[0].name.to_ascii_lowercase().starts_with("nrf51822"));
+        assert!(results[0].flash_size > 0);
+        assert!(results[0].ram_size > 0);
+        assert!(results
+            .windows(2)
+            .all(|pair| pair[0].score >= pair[1].score));
+    }
+
     #[test]
     fn validate_generic_targets() {
         let mut families = vec![];
@@ -396,6 +673,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "builtin-targets")]
     fn validate_builtin() {
         let registry = Registry::from_builtin_families();
         registry
@@ -405,4 +683,18 @@ mod tests {
             .collect::<Result<Vec<_>, _>>()
             .unwrap();
     }
+
+    #[test]
+    fn parse_default_reset() {
+        let chip: Chip = serde_yaml::from_str(
+            "name: test\ncores: []\nmemory_map: []\nflash_algorithms: []\ndefault_reset: vector\n",
+        )
+        .unwrap();
+        assert_eq!(chip.default_reset, Some(crate::ResetKind::Vector));
+
+        let chip: Chip =
+            serde_yaml::from_str("name: test\ncores: []\nmemory_map: []\nflash_algorithms: []\n")
+                .unwrap();
+        assert_eq!(chip.default_reset, None);
+    }
 }
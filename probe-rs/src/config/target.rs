@@ -1,16 +1,26 @@
-use probe_rs_target::{Architecture, ChipFamily};
+use probe_rs_target::{Architecture, ChipFamily, FlashPartition, ResetKind};
 
 use super::{Core, MemoryRegion, RawFlashAlgorithm, RegistryError, TargetDescriptionSource};
 
+use crate::architecture::arm::sequences::nordic::{Nrf, Nrf53, Nrf91};
 use crate::architecture::arm::sequences::nxp::LPC55S69;
+use crate::architecture::arm::sequences::raspberrypi::Rp2040;
+use crate::architecture::arm::sequences::stm32::{Stm32, Stm32h7};
 use crate::architecture::arm::sequences::ArmDebugSequence;
 use crate::architecture::riscv::sequences::esp32c3::ESP32C3;
 use crate::architecture::riscv::sequences::{DefaultRiscvSequence, RiscvDebugSequence};
+use crate::architecture::xtensa::sequences::{DefaultXtensaSequence, XtensaDebugSequence};
 use crate::flashing::FlashLoader;
 use std::sync::Arc;
 
 use crate::architecture::arm::sequences::DefaultArmSequence;
 
+/// STM32 families which use the [`Stm32`] sequence to keep the debug clocks enabled in low power modes.
+const STM32_DBGMCU_FAMILIES: &[&str] = &[
+    "STM32F1", "STM32F2", "STM32F3", "STM32F4", "STM32F7", "STM32G4", "STM32L1", "STM32L4",
+    "STM32WB", "STM32WL",
+];
+
 /// This describes a complete target with a fixed chip model and variant.
 #[derive(Clone)]
 pub struct Target {
@@ -22,12 +32,20 @@ pub struct Target {
     pub flash_algorithms: Vec<RawFlashAlgorithm>,
     /// The memory map of the target.
     pub memory_map: Vec<MemoryRegion>,
+    /// Named partitions of the non-volatile memory of the target.
+    pub partitions: Vec<FlashPartition>,
 
     /// Source of the target description. Used for diagnostics.
     pub(crate) source: TargetDescriptionSource,
 
     /// Debug sequences for the given target.
     pub debug_sequence: DebugSequence,
+
+    /// The kind of reset used by [`Core::reset`](crate::Core::reset) and
+    /// [`Core::reset_and_halt`](crate::Core::reset_and_halt).
+    ///
+    /// If this is not set, the default reset of the architecture is used.
+    pub default_reset: Option<ResetKind>,
 }
 
 impl std::fmt::Debug for Target {
@@ -87,6 +105,7 @@ impl Target {
         let mut debug_sequence = match chip.cores[0].core_type.architecture() {
             Architecture::Arm => DebugSequence::Arm(DefaultArmSequence::create()),
             Architecture::Riscv => DebugSequence::Riscv(DefaultRiscvSequence::create()),
+            Architecture::Xtensa => DebugSequence::Xtensa(DefaultXtensaSequence::create()),
         };
 
         if chip.name.starts_with("LPC55S69") {
@@ -95,6 +114,27 @@ impl Target {
         } else if chip.name.starts_with("esp32c3") {
             log::warn!("Using custom sequence for ESP32c3");
             debug_sequence = DebugSequence::Riscv(ESP32C3::create());
+        } else if chip.name.starts_with("nRF51") || chip.name.starts_with("nRF52") {
+            log::debug!("Using custom sequence for nRF51/nRF52");
+            debug_sequence = DebugSequence::Arm(Nrf::create());
+        } else if chip.name.starts_with("nRF5340") {
+            log::debug!("Using custom sequence for nRF5340");
+            debug_sequence = DebugSequence::Arm(Nrf53::create());
+        } else if chip.name.starts_with("nRF9160") {
+            log::debug!("Using custom sequence for nRF9160");
+            debug_sequence = DebugSequence::Arm(Nrf91::create());
+        } else if chip.name.starts_with("RP2040") {
+            log::debug!("Using custom sequence for RP2040");
+            debug_sequence = DebugSequence::Arm(Rp2040::create());
+        } else if chip.name.starts_with("STM32H7") {
+            log::debug!("Using custom sequence for STM32H7");
+            debug_sequence = DebugSequence::Arm(Stm32h7::create());
+        } else if STM32_DBGMCU_FAMILIES
+            .iter()
+            .any(|family| chip.name.starts_with(family))
+        {
+            log::debug!("Using custom sequence for STM32");
+            debug_sequence = DebugSequence::Arm(Stm32::create(&chip.name));
         }
 
         Ok(Target {
@@ -103,7 +143,9 @@ impl Target {
             flash_algorithms,
             source: family.source.clone(),
             memory_map: chip.memory_map.clone(),
+            partitions: chip.partitions.clone(),
             debug_sequence,
+            default_reset: chip.default_reset,
         })
     }
 
@@ -131,7 +173,20 @@ impl Target {
     /// Create a [FlashLoader] for this target, which can be used
     /// to program its non-volatile memory.
     pub fn flash_loader(&self) -> FlashLoader {
-        FlashLoader::new(self.memory_map.clone(), self.source.clone())
+        let mut loader = FlashLoader::new(self.memory_map.clone(), self.source.clone());
+
+        for partition in &self.partitions {
+            loader.add_partition(partition.clone());
+        }
+
+        loader
+    }
+
+    /// Gets a partition of the non-volatile memory by its name.
+    pub fn partition(&self, name: &str) -> Option<&FlashPartition> {
+        self.partitions
+            .iter()
+            .find(|partition| partition.name == name)
     }
 
     /// Gets a [RawFlashAlgorithm] by name.
@@ -201,7 +256,7 @@ impl From<Target> for TargetSelector {
 }
 
 /// This is the type to denote a general debug sequence.  
-/// It can differentiate between ARM and RISC-V for now.  
+/// It can differentiate between ARM, RISC-V and Xtensa for now.  
 /// Currently, only the ARM variant does something sensible;  
 /// RISC-V will be ignored when encountered.
 #[derive(Clone)]
@@ -210,4 +265,6 @@ pub enum DebugSequence {
     Arm(Arc<dyn ArmDebugSequence>),
     /// A RISC-V debug sequence.
     Riscv(Arc<dyn RiscvDebugSequence>),
+    /// An Xtensa debug sequence.
+    Xtensa(Arc<dyn XtensaDebugSequence>),
 }
@@ -0,0 +1,619 @@
+//! Generating target descriptions from CMSIS packs.
+//!
+//! CMSIS packs describe the devices of a vendor in a `.pdsc` file, and contain the flash
+//! algorithms for them as `.FLM` files. This module converts the devices of a pack into
+//! [`ChipFamily`]s, which can be added to the registry directly with
+//! [`add_targets_from_pack_directory`], or written to a target description file:
+//!
+//! ```no_run
+//! use probe_rs::config::target_gen;
+//! use std::path::Path;
+//!
+//! // The pack is a zip archive, which has to be extracted first.
+//! let families = target_gen::families_from_pack_directory(Path::new("Keil.STM32F4xx_DFP"))?;
+//!
+//! for family in &families {
+//!     std::fs::write(format!("{}.yaml", family.name), serde_yaml::to_string(family)?)?;
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use object::{Object, ObjectSection, ObjectSymbol};
+use probe_rs_target::{ArmCoreAccessOptions, CoreAccessOptions, SectorDescription};
+use roxmltree::Node;
+
+use super::{
+    registry, Chip, ChipFamily, Core, CoreType, FlashProperties, MemoryRegion, NvmRegion,
+    RamRegion, RawFlashAlgorithm, RegistryError, TargetDescriptionSource,
+};
+
+/// The name of the core of the generated targets.
+const CORE_NAME: &str = "main";
+
+/// The offset of the sector descriptions in the `FlashDevice` struct of a flash algorithm.
+const FLASH_DEVICE_SECTORS_OFFSET: usize = 160;
+
+/// An error while generating targets from a CMSIS pack.
+#[derive(Debug, thiserror::Error)]
+pub enum TargetGenError {
+    /// The `.pdsc` file is not valid XML.
+    #[error("The pack description could not be parsed.")]
+    Xml(#[from] roxmltree::Error),
+    /// A file of the pack could not be read.
+    #[error("The file '{path}' of the pack could not be read.")]
+    Io {
+        /// The path of the file.
+        path: PathBuf,
+        /// The error while reading it.
+        #[source]
+        source: io::Error,
+    },
+    /// The pack directory contains no `.pdsc` file.
+    #[error("No pack description was found in '{0}'.")]
+    NoPackDescription(PathBuf),
+    /// A flash algorithm is not a valid ELF file.
+    #[error("The flash algorithm could not be parsed.")]
+    Elf(#[from] object::Error),
+    /// A section required by the CMSIS flash algorithm format is missing.
+    #[error("The flash algorithm has no '{0}' section.")]
+    MissingSection(&'static str),
+    /// A symbol required by the CMSIS flash algorithm format is missing.
+    #[error("The flash algorithm has no '{0}' symbol.")]
+    MissingSymbol(&'static str),
+    /// The `FlashDevice` description of a flash algorithm is truncated.
+    #[error("The flash device description of the flash algorithm is invalid.")]
+    InvalidFlashDevice,
+    /// An attribute in the `.pdsc` file has an invalid value.
+    #[error("The attribute '{name}' has the invalid value '{value}'.")]
+    InvalidAttribute {
+        /// The name of the attribute.
+        name: String,
+        /// The value of the attribute.
+        value: String,
+    },
+    /// The core of a device is not supported by probe-rs.
+    #[error("The core '{0}' is not supported.")]
+    UnsupportedCore(String),
+    /// The generated targets could not be added to the registry.
+    #[error("The generated targets could not be added to the registry.")]
+    Registry(#[from] RegistryError),
+}
+
+/// The core type of a `Dcore` attribute in a `.pdsc` file, e.g. `Cortex-M4`.
+pub fn core_type_from_name(core: &str) -> Result<CoreType, TargetGenError> {
+    match core {
+        "Cortex-M0" | "Cortex-M0+" | "Cortex-M1" => Ok(CoreType::Armv6m),
+        "Cortex-M3" | "SC300" => Ok(CoreType::Armv7m),
+        "Cortex-M4" | "Cortex-M7" => Ok(CoreType::Armv7em),
+        "Cortex-M23" | "Cortex-M33" | "Cortex-M35P" | "Cortex-M55" => Ok(CoreType::Armv8m),
+        other => Err(TargetGenError::UnsupportedCore(other.to_owned())),
+    }
+}
+
+/// The name of the flash algorithm in the file at `path`, e.g. `stm32f4xx_1024` for
+/// `Flash/STM32F4xx_1024.FLM`.
+pub fn algorithm_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// Extract a flash algorithm from a CMSIS flash algorithm (`.FLM`), which is an ELF file.
+///
+/// The code and data of the algorithm are taken from the `PrgCode` and `PrgData`
+/// sections, the entry points from the symbols of the CMSIS flash API, and the flash
+/// properties from the `FlashDevice` description.
+pub fn extract_flash_algorithm(
+    elf: &[u8],
+    name: impl Into<String>,
+    default: bool,
+) -> Result<RawFlashAlgorithm, TargetGenError> {
+    let file = object::File::parse(elf)?;
+
+    let section = |name| {
+        file.section_by_name(name)
+            .ok_or(TargetGenError::MissingSection(name))
+    };
+    let code = section("PrgCode")?;
+    let data = section("PrgData")?;
+    let device = section("DevDscr")?;
+
+    let symbol = |name: &str| {
+        file.symbols()
+            .find(|symbol| symbol.name().map_or(false, |symbol| symbol == name))
+            .map(|symbol| symbol.address())
+    };
+    let entry_point = |name| symbol(name).map(|address| (address - code.address()) as u32);
+
+    // The data follows the code, and is zero if it's not initialized.
+    let data_section_offset = (data.address() - code.address()) as u32;
+
+    let mut instructions = code.data()?.to_vec();
+    instructions.resize(data_section_offset as usize, 0);
+    instructions.extend_from_slice(data.data()?);
+    instructions.resize((data_section_offset as u64 + data.size()) as usize, 0);
+
+    // The instructions are loaded as words.
+    while instructions.len() % 4 != 0 {
+        instructions.push(0);
+    }
+
+    let flash_device = symbol("FlashDevice").ok_or(TargetGenError::MissingSymbol("FlashDevice"))?;
+    let flash_device = device
+        .data()?
+        .get((flash_device - device.address()) as usize..)
+        .ok_or(TargetGenError::InvalidFlashDevice)?;
+    let (description, flash_properties) =
+        parse_flash_device(flash_device).ok_or(TargetGenError::InvalidFlashDevice)?;
+
+    Ok(RawFlashAlgorithm {
+        name: name.into(),
+        description,
+        default,
+        instructions,
+        load_address: None,
+        pc_init: entry_point("Init"),
+        pc_uninit: entry_point("UnInit"),
+        pc_program_page: entry_point("ProgramPage")
+            .ok_or(TargetGenError::MissingSymbol("ProgramPage"))?,
+        pc_erase_sector: entry_point("EraseSector")
+            .ok_or(TargetGenError::MissingSymbol("EraseSector"))?,
+        pc_erase_all: entry_point("EraseChip"),
+        pc_blank_check: entry_point("BlankCheck"),
+        data_section_offset,
+        flash_properties,
+        cores: vec![CORE_NAME.to_owned()],
+    })
+}
+
+/// Parse the `FlashDevice` struct of a flash algorithm, and return the name of the device
+/// and its flash properties.
+fn parse_flash_device(data: &[u8]) -> Option<(String, FlashProperties)> {
+    let u32_at = |offset: usize| {
+        data.get(offset..offset + 4)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    };
+
+    let name = data.get(2..130)?;
+    let name = String::from_utf8_lossy(name.split(|byte| *byte == 0).next()?).into_owned();
+
+    let address = u32_at(132)?;
+    let size = u32_at(136)?;
+
+    // The sectors are terminated by an entry with all bits set.
+    let mut sectors = Vec::new();
+    let mut offset = FLASH_DEVICE_SECTORS_OFFSET;
+    loop {
+        let (size, address) = (u32_at(offset)?, u32_at(offset + 4)?);
+
+        if size == u32::MAX && address == u32::MAX {
+            break;
+        }
+
+        sectors.push(SectorDescription { size, address });
+        offset += 8;
+    }
+
+    let properties = FlashProperties {
+        address_range: address..address.checked_add(size)?,
+        page_size: u32_at(140)?,
+        erased_byte_value: *data.get(148)?,
+        program_page_timeout: u32_at(152)?,
+        erase_sector_timeout: u32_at(156)?,
+        sectors,
+    };
+
+    Some((name, properties))
+}
+
+/// A `memory` element of a device.
+#[derive(Debug, Clone)]
+struct PackMemory {
+    name: String,
+    start: u32,
+    size: u32,
+    writable: bool,
+    startup: bool,
+}
+
+/// An `algorithm` element of a device.
+#[derive(Debug, Clone)]
+struct PackAlgorithm {
+    path: PathBuf,
+    default: bool,
+}
+
+/// The elements of a device, including the ones inherited from its family and sub-family.
+#[derive(Debug, Clone, Default)]
+struct DeviceProperties {
+    core: Option<String>,
+    memories: Vec<PackMemory>,
+    algorithms: Vec<PackAlgorithm>,
+}
+
+impl DeviceProperties {
+    /// The properties of `node`, which inherits the properties of its parent.
+    fn inherit(&self, node: Node) -> Result<Self, TargetGenError> {
+        let mut properties = self.clone();
+
+        for child in node.children().filter(Node::is_element) {
+            match child.tag_name().name() {
+                "processor" => {
+                    if let Some(core) = child.attribute("Dcore") {
+                        if properties.core.is_some() && child.has_attribute("Pname") {
+                            log::warn!(
+                                "Only the first core of multi-core devices is supported, ignoring {}.",
+                                core
+                            );
+                            continue;
+                        }
+
+                        properties.core = Some(core.to_owned());
+                    }
+                }
+                "memory" => {
+                    let name = child
+                        .attribute("name")
+                        .or_else(|| child.attribute("id"))
+                        .unwrap_or_default()
+                        .to_owned();
+
+                    let writable = match child.attribute("access") {
+                        Some(access) => access.contains('w'),
+                        None => name.starts_with("IRAM"),
+                    };
+
+                    let memory = PackMemory {
+                        start: number_attribute(child, "start")?,
+                        size: number_attribute(child, "size")?,
+                        writable,
+                        startup: child.attribute("startup") == Some("1"),
+                        name,
+                    };
+
+                    // A memory of a device replaces the one with the same name of its family.
+                    properties
+                        .memories
+                        .retain(|existing| memory.name.is_empty() || existing.name != memory.name);
+                    properties.memories.push(memory);
+                }
+                "algorithm" => {
+                    if let Some(path) = child.attribute("name") {
+                        // Paths in packs may use either separator.
+                        let path = PathBuf::from(path.replace('\\', "/"));
+
+                        properties
+                            .algorithms
+                            .retain(|existing| existing.path != path);
+                        properties.algorithms.push(PackAlgorithm {
+                            path,
+                            default: child.attribute("default") == Some("1"),
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(properties)
+    }
+}
+
+/// Parse a numeric attribute, which is decimal or hexadecimal with a `0x` prefix.
+fn number_attribute(node: Node, name: &str) -> Result<u32, TargetGenError> {
+    let value = node.attribute(name).unwrap_or_default();
+
+    let number = match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+
+    number.map_err(|_| TargetGenError::InvalidAttribute {
+        name: name.to_owned(),
+        value: value.to_owned(),
+    })
+}
+
+/// Collect the devices and variants below `node`, with their properties.
+fn collect_devices(
+    node: Node,
+    inherited: &DeviceProperties,
+    devices: &mut Vec<(String, DeviceProperties)>,
+) -> Result<(), TargetGenError> {
+    for child in node.children().filter(Node::is_element) {
+        let name = match child.tag_name().name() {
+            "subFamily" => None,
+            "device" => child.attribute("Dname"),
+            "variant" => child.attribute("Dvariant"),
+            _ => continue,
+        };
+
+        let properties = inherited.inherit(child)?;
+
+        let variants = devices.len();
+        collect_devices(child, &properties, devices)?;
+
+        // A device with variants is only described by its variants.
+        if let Some(name) = name {
+            if devices.len() == variants {
+                devices.push((name.to_owned(), properties));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Generate the chip families described in the `.pdsc` file of a pack.
+///
+/// `load` reads a file of the pack, e.g. a flash algorithm, from its path relative to the
+/// root of the pack. Devices with a core which is not supported by probe-rs are skipped.
+pub fn families_from_pdsc(
+    pdsc: &str,
+    mut load: impl FnMut(&Path) -> io::Result<Vec<u8>>,
+) -> Result<Vec<ChipFamily>, TargetGenError> {
+    let document = roxmltree::Document::parse(pdsc)?;
+
+    let families = document
+        .descendants()
+        .filter(|node| node.has_tag_name("family"));
+
+    let mut result = Vec::new();
+
+    for family in families {
+        let name = family.attribute("Dfamily").unwrap_or_default().to_owned();
+
+        let mut devices = Vec::new();
+        collect_devices(
+            family,
+            &DeviceProperties::default().inherit(family)?,
+            &mut devices,
+        )?;
+
+        let mut algorithms: BTreeMap<String, RawFlashAlgorithm> = BTreeMap::new();
+        let mut variants = Vec::new();
+
+        for (device, properties) in devices {
+            let core_type = match properties.core.as_deref().map(core_type_from_name) {
+                Some(Ok(core_type)) => core_type,
+                Some(Err(error)) => {
+                    log::warn!("Skipping the device {}: {}", device, error);
+                    continue;
+                }
+                None => {
+                    log::warn!("Skipping the device {}, which has no core.", device);
+                    continue;
+                }
+            };
+
+            let mut algorithm_names = Vec::new();
+
+            for algorithm in &properties.algorithms {
+                let name = algorithm_name(&algorithm.path);
+
+                if !algorithms.contains_key(&name) {
+                    let elf = load(&algorithm.path).map_err(|source| TargetGenError::Io {
+                        path: algorithm.path.clone(),
+                        source,
+                    })?;
+
+                    algorithms.insert(
+                        name.clone(),
+                        extract_flash_algorithm(&elf, name.clone(), algorithm.default)?,
+                    );
+                }
+
+                algorithm_names.push(name);
+            }
+
+            let memory_map = properties
+                .memories
+                .iter()
+                .map(|memory| {
+                    let range = memory.start..memory.start.saturating_add(memory.size);
+                    let cores = vec![CORE_NAME.to_owned()];
+
+                    if memory.writable {
+                        MemoryRegion::Ram(RamRegion {
+                            range,
+                            is_boot_memory: memory.startup,
+                            cores,
+                            access_port: None,
+                            alias: None,
+                        })
+                    } else {
+                        MemoryRegion::Nvm(NvmRegion {
+                            range,
+                            is_boot_memory: memory.startup,
+                            cores,
+                            access_port: None,
+                            alias: None,
+                        })
+                    }
+                })
+                .collect();
+
+            variants.push(Chip {
+                name: device,
+                part: None,
+                cores: vec![Core {
+                    name: CORE_NAME.to_owned(),
+                    core_type,
+                    core_access_options: CoreAccessOptions::Arm(ArmCoreAccessOptions {
+                        ap: 0,
+                        psel: 0,
+                    }),
+                }],
+                memory_map,
+                flash_algorithms: algorithm_names,
+                default_reset: None,
+                partitions: vec![],
+            });
+        }
+
+        if variants.is_empty() {
+            continue;
+        }
+
+        result.push(ChipFamily {
+            name,
+            manufacturer: None,
+            variants,
+            flash_algorithms: algorithms.into_values().collect(),
+            source: TargetDescriptionSource::External,
+        });
+    }
+
+    Ok(result)
+}
+
+/// Generate the chip families of an extracted pack in the directory at `path`.
+pub fn families_from_pack_directory(path: &Path) -> Result<Vec<ChipFamily>, TargetGenError> {
+    let read = |path: &Path| {
+        fs::read(path).map_err(|source| TargetGenError::Io {
+            path: path.to_owned(),
+            source,
+        })
+    };
+
+    let entries = fs::read_dir(path).map_err(|source| TargetGenError::Io {
+        path: path.to_owned(),
+        source,
+    })?;
+
+    let pdsc = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| {
+            path.extension()
+                .map_or(false, |extension| extension == "pdsc")
+        })
+        .ok_or_else(|| TargetGenError::NoPackDescription(path.to_owned()))?;
+
+    let pdsc = String::from_utf8_lossy(&read(&pdsc)?).into_owned();
+
+    families_from_pdsc(&pdsc, |file| fs::read(path.join(file)))
+}
+
+/// Generate the chip families of an extracted pack in the directory at `path`, and add
+/// them to the registry.
+///
+/// Returns the names of the added families.
+pub fn add_targets_from_pack_directory(path: &Path) -> Result<Vec<String>, TargetGenError> {
+    let families = families_from_pack_directory(path)?;
+    let names = families.iter().map(|family| family.name.clone()).collect();
+
+    for family in families {
+        registry::add_target_family(family)?;
+    }
+
+    Ok(names)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PDSC: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<package schemaVersion="1.4">
+  <devices>
+    <family Dfamily="STM32F4 Series" Dvendor="STMicroelectronics:13">
+      <processor Dcore="Cortex-M4" Dfpu="SP_FPU"/>
+      <subFamily DsubFamily="STM32F401">
+        <memory id="IRAM1" start="0x20000000" size="0x10000" init="0" default="1"/>
+        <device Dname="STM32F401CB">
+          <memory id="IROM1" start="0x08000000" size="0x20000" startup="1" default="1"/>
+        </device>
+        <device Dname="STM32F401CC">
+          <memory id="IROM1" start="0x08000000" size="0x40000" startup="1" default="1"/>
+          <memory name="SRAM2" access="rwx" start="0x20010000" size="0x1000"/>
+        </device>
+      </subFamily>
+      <device Dname="Unsupported">
+        <processor Dcore="Cortex-A9"/>
+      </device>
+    </family>
+  </devices>
+</package>
+"#;
+
+    #[test]
+    fn parse_pdsc() {
+        let families = families_from_pdsc(PDSC, |_| unreachable!()).unwrap();
+
+        assert_eq!(families.len(), 1);
+
+        let family = &families[0];
+        assert_eq!(family.name, "STM32F4 Series");
+
+        let names: Vec<_> = family.variants.iter().map(|chip| &chip.name).collect();
+        assert_eq!(names, ["STM32F401CB", "STM32F401CC"]);
+
+        let chip = &family.variants[1];
+        assert_eq!(chip.cores[0].core_type, CoreType::Armv7em);
+        assert_eq!(
+            chip.memory_map
+                .iter()
+                .map(|region| region.range().clone())
+                .collect::<Vec<_>>(),
+            vec![
+                0x2000_0000..0x2001_0000,
+                0x0800_0000..0x0804_0000,
+                0x2001_0000..0x2001_1000,
+            ]
+        );
+        assert!(matches!(&chip.memory_map[0], MemoryRegion::Ram(_)));
+        assert!(matches!(&chip.memory_map[1], MemoryRegion::Nvm(region) if region.is_boot_memory));
+        assert!(matches!(&chip.memory_map[2], MemoryRegion::Ram(_)));
+    }
+
+    #[test]
+    fn parse_flash_device_description() {
+        let mut data = vec![0; FLASH_DEVICE_SECTORS_OFFSET];
+        data[2..9].copy_from_slice(b"Example");
+        data[132..136].copy_from_slice(&0x0800_0000u32.to_le_bytes());
+        data[136..140].copy_from_slice(&0x0002_0000u32.to_le_bytes());
+        data[140..144].copy_from_slice(&0x400u32.to_le_bytes());
+        data[148] = 0xff;
+        data[152..156].copy_from_slice(&100u32.to_le_bytes());
+        data[156..160].copy_from_slice(&3000u32.to_le_bytes());
+
+        for (size, address) in &[(0x4000u32, 0u32), (0x10000, 0x10000), (u32::MAX, u32::MAX)] {
+            data.extend_from_slice(&size.to_le_bytes());
+            data.extend_from_slice(&address.to_le_bytes());
+        }
+
+        let (name, properties) = parse_flash_device(&data).unwrap();
+
+        assert_eq!(name, "Example");
+        assert_eq!(properties.address_range, 0x0800_0000..0x0802_0000);
+        assert_eq!(properties.page_size, 0x400);
+        assert_eq!(properties.erased_byte_value, 0xff);
+        assert_eq!(properties.program_page_timeout, 100);
+        assert_eq!(properties.erase_sector_timeout, 3000);
+        assert_eq!(
+            properties.sectors,
+            vec![
+                SectorDescription {
+                    size: 0x4000,
+                    address: 0
+                },
+                SectorDescription {
+                    size: 0x10000,
+                    address: 0x10000
+                },
+            ]
+        );
+
+        // The sectors are not terminated.
+        assert!(parse_flash_device(&data[..data.len() - 8]).is_none());
+    }
+}
@@ -0,0 +1,156 @@
+//! A cache for reads of memory regions which don't change while the target is debugged.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::Error;
+
+/// The size of the blocks which are read from the target at once.
+const BLOCK_SIZE: u32 = 256;
+
+/// Serves repeated reads of read-only regions, like flash or ROM tables, from host memory.
+///
+/// Memory is read from the target in aligned blocks of [`BLOCK_SIZE`] bytes, which are kept
+/// until they are invalidated. Reads which are not completely inside a cached region
+/// are not cached.
+#[derive(Debug, Default)]
+pub(crate) struct MemoryCache {
+    enabled: bool,
+    regions: Vec<Range<u32>>,
+    blocks: HashMap<u32, Vec<u8>>,
+}
+
+impl MemoryCache {
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Enable or disable the cache. The cached blocks are discarded in both cases.
+    pub(crate) fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.invalidate();
+    }
+
+    /// Cache reads of `region`.
+    pub(crate) fn add_region(&mut self, region: Range<u32>) {
+        if !self.regions.contains(&region) {
+            self.regions.push(region);
+        }
+    }
+
+    /// Discard all cached blocks, e.g. after the memory was changed by a reset or by flashing.
+    pub(crate) fn invalidate(&mut self) {
+        self.blocks.clear();
+    }
+
+    /// Discard the cached blocks which contain any of the `length` bytes at `address`.
+    pub(crate) fn invalidate_range(&mut self, address: u32, length: usize) {
+        if self.blocks.is_empty() || length == 0 {
+            return;
+        }
+
+        let end = address as u64 + length as u64;
+        self.blocks.retain(|&block, _| {
+            let block = block as u64;
+            block + BLOCK_SIZE as u64 <= address as u64 || block >= end
+        });
+    }
+
+    /// Read `data` at `address` from the cache, and read all blocks which are
+    /// not cached yet with `fetch`.
+    ///
+    /// Returns `false` without reading anything if the cache is disabled, or the
+    /// read is not completely inside a cached region.
+    pub(crate) fn read(
+        &mut self,
+        address: u32,
+        data: &mut [u8],
+        mut fetch: impl FnMut(u32, &mut [u8]) -> Result<(), Error>,
+    ) -> Result<bool, Error> {
+        if !self.enabled || data.is_empty() {
+            return Ok(false);
+        }
+
+        let end = address as u64 + data.len() as u64;
+        let first_block = address - address % BLOCK_SIZE;
+        let last_block = ((end - 1) as u32) - ((end - 1) as u32) % BLOCK_SIZE;
+
+        // The blocks have to be inside the region as well, so that nothing
+        // outside of it is read.
+        let cacheable = self.regions.iter().any(|region| {
+            region.start <= first_block
+                && last_block as u64 + BLOCK_SIZE as u64 <= region.end as u64
+        });
+
+        if !cacheable {
+            return Ok(false);
+        }
+
+        let mut block = first_block;
+        let mut position = 0;
+
+        while position < data.len() {
+            let contents = match self.blocks.get(&block) {
+                Some(contents) => contents,
+                None => {
+                    let mut contents = vec![0u8; BLOCK_SIZE as usize];
+                    fetch(block, &mut contents)?;
+                    self.blocks.entry(block).or_insert(contents)
+                }
+            };
+
+            let offset = (address + position as u32 - block) as usize;
+            let length = (BLOCK_SIZE as usize - offset).min(data.len() - position);
+            data[position..position + length].copy_from_slice(&contents[offset..offset + length]);
+
+            position += length;
+            block = block.wrapping_add(BLOCK_SIZE);
+        }
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MemoryCache, BLOCK_SIZE};
+
+    fn memory(address: u32, data: &mut [u8]) {
+        for (index, byte) in data.iter_mut().enumerate() {
+            *byte = (address as usize + index) as u8;
+        }
+    }
+
+    #[test]
+    fn cached_reads() {
+        let mut cache = MemoryCache::default();
+        cache.add_region(0x1000..0x2000);
+
+        let mut fetched = Vec::new();
+        let mut fetch = |address, data: &mut [u8]| {
+            fetched.push(address);
+            memory(address, data);
+            Ok(())
+        };
+
+        let mut data = [0u8; 8];
+        assert!(!cache.read(0x1000, &mut data, &mut fetch).unwrap());
+
+        cache.set_enabled(true);
+
+        // A read which crosses a block boundary.
+        assert!(cache.read(0x10fc, &mut data, &mut fetch).unwrap());
+        assert_eq!(data, [0xfc, 0xfd, 0xfe, 0xff, 0x00, 0x01, 0x02, 0x03]);
+
+        assert!(cache.read(0x1100, &mut data, &mut fetch).unwrap());
+        assert_eq!(data[0], 0x00);
+
+        // Outside of the region.
+        assert!(!cache.read(0x1ffc, &mut data, &mut fetch).unwrap());
+
+        cache.invalidate_range(0x1000 + BLOCK_SIZE, 1);
+        assert!(cache.read(0x10fc, &mut data, &mut fetch).unwrap());
+
+        assert_eq!(fetched, vec![0x1000, 0x1100, 0x1100]);
+    }
+}
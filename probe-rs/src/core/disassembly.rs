@@ -0,0 +1,171 @@
+//! Disassembly of the code of a core, using Capstone.
+
+use std::fmt;
+
+use capstone::prelude::*;
+
+use super::Core;
+use crate::{Architecture, Error, MemoryInterface};
+
+/// The maximum size of an instruction of the supported instruction sets.
+const MAX_INSTRUCTION_SIZE: usize = 4;
+
+/// A disassembled instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    /// The address of the instruction.
+    pub address: u32,
+    /// The encoding of the instruction.
+    pub bytes: Vec<u8>,
+    /// The mnemonic, e.g. `ldr`.
+    ///
+    /// Data which is not a valid instruction is shown as `.short`.
+    pub mnemonic: String,
+    /// The operands, e.g. `r0, [r1, #4]`.
+    pub operands: String,
+}
+
+impl Instruction {
+    /// The size of the instruction in bytes.
+    pub fn size(&self) -> u32 {
+        self.bytes.len() as u32
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#010x}: {}", self.address, self.mnemonic)?;
+
+        if !self.operands.is_empty() {
+            write!(f, " {}", self.operands)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'probe> Core<'probe> {
+    /// Disassemble `count` instructions starting at `address`.
+    ///
+    /// ARM cores are disassembled as Thumb code, RISC-V cores as RV32 code with compressed
+    /// instructions. Halfwords which are not a valid instruction, e.g. literal pools, are
+    /// returned as `.short` and the disassembly continues after them.
+    ///
+    /// The core should be halted, so that the code can be read.
+    pub fn disassemble(&mut self, address: u32, count: usize) -> Result<Vec<Instruction>, Error> {
+        let mut code = vec![0u8; count * MAX_INSTRUCTION_SIZE];
+        self.read_8(address, &mut code)?;
+
+        disassemble(self.architecture(), &code, address, count)
+    }
+}
+
+/// Disassemble up to `count` instructions of `code`, which is located at `address`.
+fn disassemble(
+    architecture: Architecture,
+    code: &[u8],
+    address: u32,
+    count: usize,
+) -> Result<Vec<Instruction>, Error> {
+    let capstone = match architecture {
+        Architecture::Arm => Capstone::new()
+            .arm()
+            .mode(arch::arm::ArchMode::Thumb)
+            .extra_mode(std::iter::once(arch::arm::ArchExtraMode::MClass))
+            .endian(capstone::Endian::Little)
+            .build(),
+        Architecture::Riscv => Capstone::new()
+            .riscv()
+            .mode(arch::riscv::ArchMode::RiscV32)
+            .extra_mode(std::iter::once(arch::riscv::ArchExtraMode::RiscVC))
+            .endian(capstone::Endian::Little)
+            .build(),
+        Architecture::Xtensa => return Err(Error::ArchitectureRequired(&["ARMv7", "Riscv"])),
+    }
+    .map_err(|error| Error::Disassembly {
+        address: None,
+        message: error.to_string(),
+    })?;
+
+    let mut instructions = Vec::with_capacity(count);
+    let mut offset = 0;
+
+    while instructions.len() < count && offset + 1 < code.len() {
+        let current = address + offset as u32;
+        let decoded = capstone
+            .disasm_count(&code[offset..], current as u64, 1)
+            .map_err(|error| Error::Disassembly {
+                address: Some(current),
+                message: error.to_string(),
+            })?;
+
+        let instruction = match decoded.iter().next() {
+            Some(decoded) => Instruction {
+                address: current,
+                bytes: decoded.bytes().to_vec(),
+                mnemonic: decoded.mnemonic().unwrap_or_default().to_string(),
+                operands: decoded.op_str().unwrap_or_default().to_string(),
+            },
+            None => {
+                let bytes = code[offset..offset + 2].to_vec();
+                let value = u16::from_le_bytes([bytes[0], bytes[1]]);
+
+                Instruction {
+                    address: current,
+                    bytes,
+                    mnemonic: ".short".to_string(),
+                    operands: format!("{:#06x}", value),
+                }
+            }
+        };
+
+        offset += instruction.bytes.len();
+        instructions.push(instruction);
+    }
+
+    Ok(instructions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::disassemble;
+    use crate::Architecture;
+
+    #[test]
+    fn disassemble_thumb() {
+        let code = [
+            0x01, 0x20, // movs r0, #1
+            0x00, 0xf0, 0x02, 0xf8, // bl #0x1000000a
+            0x70, 0x47, // bx lr
+            0xff, 0xff, // the first half of a 32 bit instruction
+        ];
+
+        let instructions = disassemble(Architecture::Arm, &code, 0x1000_0000, 4).unwrap();
+
+        let text: Vec<String> = instructions.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            text,
+            vec![
+                "0x10000000: movs r0, #1",
+                "0x10000002: bl #0x1000000a",
+                "0x10000006: bx lr",
+                "0x10000008: .short 0xffff",
+            ]
+        );
+        assert_eq!(instructions[1].size(), 4);
+    }
+
+    #[test]
+    fn disassemble_riscv() {
+        let code = [
+            0x13, 0x05, 0x10, 0x00, // addi a0, zero, 1
+            0x82, 0x80, // c.jr ra
+        ];
+
+        let instructions = disassemble(Architecture::Riscv, &code, 0x2000_0000, 2).unwrap();
+
+        assert_eq!(instructions.len(), 2);
+        assert_eq!(instructions[0].mnemonic, "addi");
+        assert_eq!(instructions[1].size(), 2);
+    }
+}
@@ -0,0 +1,317 @@
+//! Post-mortem core dumps.
+//!
+//! A [`CoreDump`] contains the register contents of a halted core and the contents of
+//! selected memory regions. It can be serialized into a standard ELF core file, which
+//! can be loaded together with the firmware ELF file into GDB for offline debugging:
+//!
+//! ```text
+//! arm-none-eabi-gdb firmware.elf core.elf
+//! ```
+
+use crate::{Architecture, Core, CoreRegisterAddress, Error, MemoryInterface};
+use object::elf::{
+    ELFCLASS32, ELFDATA2LSB, ELFOSABI_NONE, EM_ARM, EM_RISCV, EM_XTENSA, ET_CORE, EV_CURRENT,
+    NT_PRSTATUS, PF_R, PF_W, PF_X, PT_LOAD, PT_NOTE,
+};
+use std::io::Write;
+use std::ops::Range;
+
+/// Size of the ELF header of a 32 bit ELF file.
+const ELF32_HEADER_SIZE: usize = 52;
+
+/// Size of a single program header entry of a 32 bit ELF file.
+const ELF32_PROGRAM_HEADER_SIZE: usize = 32;
+
+/// Offset of the register set inside the `elf_prstatus` structure.
+///
+/// This is the same for all 32 bit Linux targets, which is the layout GDB expects.
+const PRSTATUS_REGISTER_OFFSET: usize = 72;
+
+/// The signal reported in the dump, `SIGTRAP`.
+const SIGTRAP: u16 = 5;
+
+/// Register contents and memory of a halted core.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoreDump {
+    /// The architecture of the dumped core.
+    pub architecture: Architecture,
+    /// The id of the dumped core.
+    pub core_id: usize,
+    /// The register values, in the order expected in the `elf_prstatus` structure.
+    ///
+    /// For ARM, these are R0-R15 followed by XPSR.
+    /// For RISC-V, these are the PC followed by x1-x31.
+    pub registers: Vec<u32>,
+    /// The dumped memory regions, each consisting of the start address and the data.
+    pub memory: Vec<(u32, Vec<u8>)>,
+}
+
+impl CoreDump {
+    /// Create a core dump from the given core, which has to be halted.
+    ///
+    /// The memory contents of all `regions` are included in the dump.
+    pub(crate) fn new(core: &mut Core, regions: &[Range<u32>]) -> Result<Self, Error> {
+        let architecture = core.architecture();
+
+        // GDB expects the special registers of the Xtensa register set, e.g. LBEG, LEND
+        // and WINDOWBASE, before all physical address registers, which is not supported yet.
+        if architecture == Architecture::Xtensa {
+            return Err(Error::ArchitectureRequired(&["ARM", "RISC-V"]));
+        }
+
+        if !core.core_halted()? {
+            return Err(Error::CoreNotHalted(core.id()));
+        }
+
+        let addresses: Vec<CoreRegisterAddress> = match architecture {
+            // R0-R15, XPSR
+            Architecture::Arm => (0..=16).map(CoreRegisterAddress).collect(),
+            // PC, x1-x31
+            Architecture::Riscv => std::iter::once(core.registers().program_counter().into())
+                .chain((1..32).map(|i| CoreRegisterAddress(0x1000 + i)))
+                .collect(),
+            Architecture::Xtensa => unreachable!("core dumps are not supported on Xtensa"),
+        };
+
+        let mut registers = Vec::with_capacity(addresses.len());
+
+        for address in addresses {
+            registers.push(core.read_core_reg(address)?);
+        }
+
+        let mut memory = Vec::with_capacity(regions.len());
+
+        for region in regions {
+            let mut data = vec![0u8; region.len()];
+            core.read_8(region.start, &mut data)?;
+
+            memory.push((region.start, data));
+        }
+
+        Ok(Self {
+            architecture,
+            core_id: core.id(),
+            registers,
+            memory,
+        })
+    }
+
+    /// Serialize the dump as an ELF core file.
+    pub fn write_elf(&self, mut writer: impl Write) -> std::io::Result<()> {
+        writer.write_all(&self.to_elf())
+    }
+
+    /// Serialize the dump as an ELF core file into a new buffer.
+    pub fn to_elf(&self) -> Vec<u8> {
+        let machine = match self.architecture {
+            Architecture::Arm => EM_ARM,
+            Architecture::Riscv => EM_RISCV,
+            Architecture::Xtensa => EM_XTENSA,
+        };
+
+        let note = self.prstatus_note();
+
+        let program_header_count = 1 + self.memory.len();
+        let note_offset = ELF32_HEADER_SIZE + ELF32_PROGRAM_HEADER_SIZE * program_header_count;
+
+        let mut elf = Vec::new();
+
+        // ELF header
+        elf.extend_from_slice(&[0x7f, b'E', b'L', b'F']);
+        elf.extend_from_slice(&[ELFCLASS32, ELFDATA2LSB, EV_CURRENT, ELFOSABI_NONE]);
+        elf.extend_from_slice(&[0u8; 8]);
+        push_u16(&mut elf, ET_CORE);
+        push_u16(&mut elf, machine);
+        push_u32(&mut elf, u32::from(EV_CURRENT));
+        // e_entry
+        push_u32(&mut elf, 0);
+        // e_phoff
+        push_u32(&mut elf, ELF32_HEADER_SIZE as u32);
+        // e_shoff
+        push_u32(&mut elf, 0);
+        // e_flags
+        push_u32(&mut elf, 0);
+        push_u16(&mut elf, ELF32_HEADER_SIZE as u16);
+        push_u16(&mut elf, ELF32_PROGRAM_HEADER_SIZE as u16);
+        push_u16(&mut elf, program_header_count as u16);
+        // e_shentsize, e_shnum, e_shstrndx
+        push_u16(&mut elf, 0);
+        push_u16(&mut elf, 0);
+        push_u16(&mut elf, 0);
+
+        // Program header for the note segment
+        push_program_header(&mut elf, PT_NOTE, note_offset, 0, note.len(), 0, 4);
+
+        // Program headers for all memory regions
+        let mut data_offset = note_offset + note.len();
+
+        for (address, data) in &self.memory {
+            push_program_header(
+                &mut elf,
+                PT_LOAD,
+                data_offset,
+                *address,
+                data.len(),
+                PF_R | PF_W | PF_X,
+                1,
+            );
+
+            data_offset += data.len();
+        }
+
+        elf.extend_from_slice(&note);
+
+        for (_, data) in &self.memory {
+            elf.extend_from_slice(data);
+        }
+
+        elf
+    }
+
+    /// Build the `NT_PRSTATUS` note, containing the register values.
+    fn prstatus_note(&self) -> Vec<u8> {
+        let mut prstatus = vec![0u8; PRSTATUS_REGISTER_OFFSET];
+
+        // pr_cursig
+        prstatus[12..14].copy_from_slice(&SIGTRAP.to_le_bytes());
+        // pr_pid, used by GDB to identify the thread
+        prstatus[24..28].copy_from_slice(&(self.core_id as u32 + 1).to_le_bytes());
+
+        for register in &self.registers {
+            push_u32(&mut prstatus, *register);
+        }
+
+        // The ARM register set contains an additional ORIG_R0 entry.
+        if self.architecture == Architecture::Arm {
+            push_u32(&mut prstatus, 0);
+        }
+
+        // pr_fpvalid
+        push_u32(&mut prstatus, 0);
+
+        let name = b"CORE\0";
+
+        let mut note = Vec::new();
+        push_u32(&mut note, name.len() as u32);
+        push_u32(&mut note, prstatus.len() as u32);
+        push_u32(&mut note, NT_PRSTATUS);
+        note.extend_from_slice(name);
+        pad_to_word(&mut note);
+        note.extend_from_slice(&prstatus);
+        pad_to_word(&mut note);
+
+        note
+    }
+}
+
+impl<'probe> Core<'probe> {
+    /// Create a [`CoreDump`] of this core, containing all registers and the given memory regions.
+    ///
+    /// The core has to be halted. Only ARM and RISC-V cores are supported, for other
+    /// architectures [`Error::ArchitectureRequired`] is returned.
+    pub fn dump(&mut self, regions: &[Range<u32>]) -> Result<CoreDump, Error> {
+        CoreDump::new(self, regions)
+    }
+}
+
+fn push_program_header(
+    elf: &mut Vec<u8>,
+    p_type: u32,
+    offset: usize,
+    address: u32,
+    size: usize,
+    flags: u32,
+    align: u32,
+) {
+    push_u32(elf, p_type);
+    push_u32(elf, offset as u32);
+    // p_vaddr, p_paddr
+    push_u32(elf, address);
+    push_u32(elf, address);
+    // p_filesz, p_memsz
+    push_u32(elf, size as u32);
+    push_u32(elf, if p_type == PT_LOAD { size as u32 } else { 0 });
+    push_u32(elf, flags);
+    push_u32(elf, align);
+}
+
+fn push_u16(buffer: &mut Vec<u8>, value: u16) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn push_u32(buffer: &mut Vec<u8>, value: u32) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn pad_to_word(buffer: &mut Vec<u8>) {
+    while buffer.len() % 4 != 0 {
+        buffer.push(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CoreDump;
+    use crate::{Architecture, Core, Error, MockCore};
+    use object::elf::{FileHeader32, PT_LOAD, PT_NOTE};
+    use object::read::elf::{FileHeader, ProgramHeader};
+    use object::Endianness;
+
+    #[test]
+    fn arm_core_dump_is_valid_elf() {
+        let dump = CoreDump {
+            architecture: Architecture::Arm,
+            core_id: 0,
+            registers: (0..17).collect(),
+            memory: vec![
+                (0x2000_0000, vec![1, 2, 3, 4]),
+                (0x2000_1000, vec![5, 6, 7]),
+            ],
+        };
+
+        let elf = dump.to_elf();
+
+        let header = FileHeader32::<Endianness>::parse(&elf[..]).unwrap();
+        let endian = header.endian().unwrap();
+
+        assert_eq!(header.e_type(endian), object::elf::ET_CORE);
+        assert_eq!(header.e_machine(endian), object::elf::EM_ARM);
+
+        let segments = header.program_headers(endian, &elf[..]).unwrap();
+        assert_eq!(segments.len(), 3);
+
+        assert_eq!(segments[0].p_type(endian), PT_NOTE);
+        // 20 bytes note header and name, 148 bytes elf_prstatus
+        assert_eq!(segments[0].p_filesz(endian), 20 + 148);
+
+        for (segment, (address, data)) in segments[1..].iter().zip(dump.memory.iter()) {
+            assert_eq!(segment.p_type(endian), PT_LOAD);
+            assert_eq!(segment.p_vaddr(endian), *address);
+            assert_eq!(segment.data(endian, &elf[..]).unwrap(), &data[..]);
+        }
+    }
+
+    #[test]
+    fn xtensa_core_dump_is_not_supported() {
+        let mut state = Core::create_state(0);
+        let mut core = Core::new(MockCore::new(Architecture::Xtensa), &mut state);
+
+        assert!(matches!(
+            core.dump(&[]),
+            Err(Error::ArchitectureRequired(_))
+        ));
+    }
+
+    #[test]
+    fn riscv_prstatus_size() {
+        let dump = CoreDump {
+            architecture: Architecture::Riscv,
+            core_id: 0,
+            registers: vec![0; 32],
+            memory: vec![],
+        };
+
+        // 20 bytes note header and name, 204 bytes elf_prstatus
+        assert_eq!(dump.prstatus_note().len(), 20 + 204);
+    }
+}
@@ -0,0 +1,190 @@
+//! The history of the halts, resumes and resets of a core.
+
+use std::collections::VecDeque;
+use web_time::SystemTime;
+
+use probe_rs_target::ResetKind;
+
+use super::HaltReason;
+
+/// The number of events which are kept in the history of a core.
+const DEFAULT_CAPACITY: usize = 256;
+
+/// A change of the execution of a core, recorded in [`Core::event_history`].
+///
+/// [`Core::event_history`]: crate::Core::event_history
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CoreEvent {
+    /// The core halted, e.g. on a breakpoint or because it was requested.
+    Halted {
+        /// The reason for the halt.
+        reason: HaltReason,
+        /// The program counter, if it could be read.
+        pc: Option<u32>,
+    },
+    /// The core resumed execution.
+    Resumed,
+    /// The core was reset by the debugger.
+    Reset {
+        /// The kind of the reset, `None` for the default reset of the architecture.
+        kind: Option<ResetKind>,
+    },
+    /// A reset which was not requested by the debugger was detected, e.g. a reset by
+    /// a watchdog.
+    ResetDetected,
+}
+
+/// A [`CoreEvent`] with the time it was recorded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CoreEventRecord {
+    /// The time the event was recorded.
+    ///
+    /// Halts and resumes which are not caused by the debugger are only recorded when the
+    /// status of the core is read, e.g. by [`Session::poll_events`], so they may have
+    /// happened earlier.
+    ///
+    /// [`Session::poll_events`]: crate::Session::poll_events
+    pub time: SystemTime,
+    /// The event.
+    pub event: CoreEvent,
+}
+
+/// A ring buffer of the latest events of a core.
+#[derive(Debug)]
+pub(crate) struct EventHistory {
+    records: VecDeque<CoreEventRecord>,
+    capacity: usize,
+    /// Whether the core is halted according to the recorded events, `None` before the
+    /// first event. Halts and resumes are only recorded when this changes.
+    halted: Option<bool>,
+}
+
+impl Default for EventHistory {
+    fn default() -> Self {
+        Self {
+            records: VecDeque::new(),
+            capacity: DEFAULT_CAPACITY,
+            halted: None,
+        }
+    }
+}
+
+impl EventHistory {
+    /// The recorded events, the oldest first.
+    pub(crate) fn records(&self) -> &VecDeque<CoreEventRecord> {
+        &self.records
+    }
+
+    /// Whether the core is known to be halted. Reading the program counter for
+    /// [`CoreEvent::Halted`] is skipped if this returns `true`.
+    pub(crate) fn is_halted(&self) -> bool {
+        self.halted == Some(true)
+    }
+
+    /// Change the number of events which are kept. The oldest events are dropped if
+    /// there are more.
+    pub(crate) fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.truncate();
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.records.clear();
+    }
+
+    /// Record `event`, unless it is a halt or resume which doesn't change whether the core
+    /// is halted.
+    pub(crate) fn record(&mut self, event: CoreEvent) {
+        match event {
+            CoreEvent::Halted { .. } => {
+                if self.is_halted() {
+                    return;
+                }
+                self.halted = Some(true);
+            }
+            CoreEvent::Resumed => {
+                if self.halted == Some(false) {
+                    return;
+                }
+                self.halted = Some(false);
+            }
+            // The core may be halted or running after a reset, which is recorded by the
+            // following event.
+            CoreEvent::Reset { .. } | CoreEvent::ResetDetected => self.halted = None,
+        }
+
+        self.records.push_back(CoreEventRecord {
+            time: SystemTime::now(),
+            event,
+        });
+        self.truncate();
+    }
+
+    fn truncate(&mut self) {
+        while self.records.len() > self.capacity {
+            self.records.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CoreEvent, EventHistory};
+    use crate::HaltReason;
+
+    fn events(history: &EventHistory) -> Vec<CoreEvent> {
+        history
+            .records()
+            .iter()
+            .map(|record| record.event)
+            .collect()
+    }
+
+    #[test]
+    fn only_changes_are_recorded() {
+        let mut history = EventHistory::default();
+
+        let halt = CoreEvent::Halted {
+            reason: HaltReason::Breakpoint,
+            pc: Some(0x0800_0100),
+        };
+
+        history.record(CoreEvent::Resumed);
+        history.record(CoreEvent::Resumed);
+        history.record(halt);
+        history.record(CoreEvent::Halted {
+            reason: HaltReason::Step,
+            pc: Some(0x0800_0102),
+        });
+        history.record(CoreEvent::ResetDetected);
+        history.record(CoreEvent::Resumed);
+
+        assert_eq!(
+            events(&history),
+            vec![
+                CoreEvent::Resumed,
+                halt,
+                CoreEvent::ResetDetected,
+                CoreEvent::Resumed
+            ]
+        );
+    }
+
+    #[test]
+    fn oldest_events_are_dropped() {
+        let mut history = EventHistory::default();
+        history.set_capacity(2);
+
+        history.record(CoreEvent::Reset { kind: None });
+        history.record(CoreEvent::Resumed);
+        history.record(CoreEvent::ResetDetected);
+
+        assert_eq!(
+            events(&history),
+            vec![CoreEvent::Resumed, CoreEvent::ResetDetected]
+        );
+
+        history.set_capacity(1);
+        assert_eq!(events(&history), vec![CoreEvent::ResetDetected]);
+    }
+}
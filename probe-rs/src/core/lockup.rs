@@ -0,0 +1,88 @@
+//! Recovery of cores which are locked up.
+
+use std::time::Duration;
+
+use super::{Core, CoreInformation, CoreStatus};
+use crate::Error;
+
+/// The fault status registers of an ARMv7-M or ARMv8-M core, which describe the fault
+/// which caused a lockup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FaultStatus {
+    /// The Configurable Fault Status Register, which combines the MemManage, BusFault
+    /// and UsageFault status registers.
+    pub cfsr: u32,
+    /// The HardFault Status Register.
+    pub hfsr: u32,
+    /// The address of the access which caused a MemManage fault, if it is valid.
+    pub mmfar: Option<u32>,
+    /// The address of the access which caused a BusFault, if it is valid.
+    pub bfar: Option<u32>,
+}
+
+impl FaultStatus {
+    /// The HardFault was caused by another fault, which could not be handled.
+    pub fn forced(&self) -> bool {
+        self.hfsr & (1 << 30) != 0
+    }
+
+    /// The HardFault was caused by a bus error while reading the vector table.
+    pub fn vector_table_read(&self) -> bool {
+        self.hfsr & (1 << 1) != 0
+    }
+}
+
+/// The result of [`Core::recover_from_lockup`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockupRecovery {
+    /// The core could not be halted, and was reset instead. The registers do not
+    /// describe the lockup anymore in this case.
+    pub reset: bool,
+    /// The program counter of the halted core.
+    pub pc: u32,
+    /// The fault status of the core, if it is an ARMv7-M or ARMv8-M core and was not reset.
+    pub fault_status: Option<FaultStatus>,
+}
+
+impl<'probe> Core<'probe> {
+    /// Halt a core which is locked up, e.g. because of a fault in the HardFault handler,
+    /// so that the cause of the lockup can be diagnosed.
+    ///
+    /// A halt request exits the lockup of ARM cores and keeps the registers, so they can
+    /// be read afterwards. If the core does not halt within `timeout`, it is reset and
+    /// halted instead, using the default reset of the core.
+    pub fn recover_from_lockup(&mut self, timeout: Duration) -> Result<LockupRecovery, Error> {
+        let halted = match self.inner.halt(timeout) {
+            Ok(information) => match self.inner.status()? {
+                CoreStatus::LockedUp => None,
+                _ => Some(information),
+            },
+            Err(error) => {
+                log::warn!("Failed to halt locked up core: {}", error);
+                None
+            }
+        };
+
+        let (reset, information) = match halted {
+            Some(information) => (false, information),
+            None => {
+                log::warn!("Resetting locked up core {}", self.id());
+                (true, self.reset_and_halt(timeout)?)
+            }
+        };
+
+        let fault_status = if reset {
+            None
+        } else {
+            self.inner.fault_status()?
+        };
+
+        let CoreInformation { pc } = information;
+
+        Ok(LockupRecovery {
+            reset,
+            pc,
+            fault_status,
+        })
+    }
+}
@@ -0,0 +1,478 @@
+//! A core which is simulated on the host, for tests which don't need a target.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use probe_rs_target::ResetKind;
+
+use super::{
+    Architecture, CoreInformation, CoreInterface, CoreRegisterAddress, CoreStatus, HaltReason,
+    RegisterFile,
+};
+use crate::architecture::{
+    arm::core::ARM_REGISTER_FILE, riscv::RISCV_REGISTERS, xtensa::XTENSA_REGISTERS,
+};
+use crate::{DebugProbeError, Error, MemoryInterface};
+
+/// The number of hardware breakpoints of a [`MockCore`].
+const BREAKPOINT_UNITS: usize = 4;
+
+/// A core whose memory, registers and halt behavior are scripted, for tests of code which
+/// uses a [`Core`](crate::Core) without a target.
+///
+/// Memory which was not set reads as zero, and so do registers. When the core is run, it
+/// halts at the first enabled hardware breakpoint, as if the program reached it, and keeps
+/// running if there is none.
+///
+/// ```
+/// use probe_rs::{Architecture, Core, MemoryInterface, MockCore};
+///
+/// let mut mock = MockCore::new(Architecture::Arm);
+/// mock.set_memory(0x2000_0000, &[1, 2, 3, 4]);
+///
+/// let mut state = Core::create_state(0);
+/// let mut core = Core::new(mock, &mut state);
+///
+/// assert_eq!(core.read_word_32(0x2000_0000)?, 0x0403_0201);
+/// # Ok::<(), probe_rs::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct MockCore {
+    architecture: Architecture,
+    memory: HashMap<u32, u8>,
+    registers: HashMap<u16, u32>,
+    status: CoreStatus,
+    reset_address: u32,
+    reset_catch: bool,
+    ignore_halt_requests: bool,
+    breakpoints: Vec<Option<u32>>,
+    breakpoints_enabled: bool,
+}
+
+impl MockCore {
+    /// Create a halted core of `architecture`.
+    pub fn new(architecture: Architecture) -> Self {
+        Self {
+            architecture,
+            memory: HashMap::new(),
+            registers: HashMap::new(),
+            status: CoreStatus::Halted(HaltReason::Request),
+            reset_address: 0,
+            reset_catch: false,
+            ignore_halt_requests: false,
+            breakpoints: vec![None; BREAKPOINT_UNITS],
+            breakpoints_enabled: false,
+        }
+    }
+
+    /// Set the memory at `address` to `data`.
+    pub fn set_memory(&mut self, address: u32, data: &[u8]) {
+        for (offset, byte) in data.iter().enumerate() {
+            self.memory
+                .insert(address.wrapping_add(offset as u32), *byte);
+        }
+    }
+
+    /// The `length` bytes of memory at `address`.
+    pub fn memory(&self, address: u32, length: usize) -> Vec<u8> {
+        (0..length)
+            .map(|offset| self.read_byte(address.wrapping_add(offset as u32)))
+            .collect()
+    }
+
+    /// Set the register at `address` to `value`.
+    pub fn set_register(&mut self, address: impl Into<CoreRegisterAddress>, value: u32) {
+        self.registers.insert(address.into().0, value);
+    }
+
+    /// The value of the register at `address`.
+    pub fn register(&self, address: impl Into<CoreRegisterAddress>) -> u32 {
+        self.registers
+            .get(&address.into().0)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Set the status of the core, e.g. to [`CoreStatus::LockedUp`].
+    pub fn set_status(&mut self, status: CoreStatus) {
+        self.status = status;
+    }
+
+    /// Set the address of the program counter after a reset.
+    pub fn set_reset_address(&mut self, address: u32) {
+        self.reset_address = address;
+    }
+
+    /// Ignore requests to halt the core, so that halting it times out.
+    pub fn ignore_halt_requests(&mut self, ignore: bool) {
+        self.ignore_halt_requests = ignore;
+    }
+
+    fn read_byte(&self, address: u32) -> u8 {
+        self.memory.get(&address).copied().unwrap_or_default()
+    }
+
+    fn pc(&self) -> CoreRegisterAddress {
+        self.registers().program_counter().into()
+    }
+
+    fn halted(&mut self, reason: HaltReason) -> CoreInformation {
+        self.status = CoreStatus::Halted(reason);
+
+        CoreInformation {
+            pc: self.register(self.pc()),
+        }
+    }
+
+    /// The size of the instructions which are stepped over.
+    fn instruction_size(&self) -> u32 {
+        match self.architecture {
+            Architecture::Arm => 2,
+            Architecture::Riscv => 4,
+            Architecture::Xtensa => 3,
+        }
+    }
+}
+
+impl MemoryInterface for MockCore {
+    fn read_word_32(&mut self, address: u32) -> Result<u32, Error> {
+        let mut bytes = [0u8; 4];
+        self.read_8(address, &mut bytes)?;
+
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn read_word_8(&mut self, address: u32) -> Result<u8, Error> {
+        Ok(self.read_byte(address))
+    }
+
+    fn read_32(&mut self, address: u32, data: &mut [u32]) -> Result<(), Error> {
+        for (index, word) in data.iter_mut().enumerate() {
+            *word = self.read_word_32(address.wrapping_add(index as u32 * 4))?;
+        }
+
+        Ok(())
+    }
+
+    fn read_8(&mut self, address: u32, data: &mut [u8]) -> Result<(), Error> {
+        data.copy_from_slice(&self.memory(address, data.len()));
+
+        Ok(())
+    }
+
+    fn write_word_32(&mut self, address: u32, data: u32) -> Result<(), Error> {
+        self.set_memory(address, &data.to_le_bytes());
+
+        Ok(())
+    }
+
+    fn write_word_8(&mut self, address: u32, data: u8) -> Result<(), Error> {
+        self.set_memory(address, &[data]);
+
+        Ok(())
+    }
+
+    fn write_32(&mut self, address: u32, data: &[u32]) -> Result<(), Error> {
+        for (index, word) in data.iter().enumerate() {
+            self.write_word_32(address.wrapping_add(index as u32 * 4), *word)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_8(&mut self, address: u32, data: &[u8]) -> Result<(), Error> {
+        self.set_memory(address, data);
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl CoreInterface for MockCore {
+    fn wait_for_core_halted(&mut self, _timeout: Duration) -> Result<(), Error> {
+        if self.status.is_halted() {
+            Ok(())
+        } else {
+            Err(DebugProbeError::Timeout.into())
+        }
+    }
+
+    fn core_halted(&mut self) -> Result<bool, Error> {
+        Ok(self.status.is_halted())
+    }
+
+    fn status(&mut self) -> Result<CoreStatus, Error> {
+        Ok(self.status)
+    }
+
+    fn halt(&mut self, _timeout: Duration) -> Result<CoreInformation, Error> {
+        if self.ignore_halt_requests {
+            return Err(DebugProbeError::Timeout.into());
+        }
+
+        Ok(self.halted(HaltReason::Request))
+    }
+
+    fn run(&mut self) -> Result<(), Error> {
+        let breakpoint = match self.breakpoints_enabled {
+            true => self.breakpoints.iter().flatten().next().copied(),
+            false => None,
+        };
+
+        match breakpoint {
+            Some(address) => {
+                self.set_register(self.pc(), address);
+                self.halted(HaltReason::Breakpoint);
+            }
+            None => self.status = CoreStatus::Running,
+        }
+
+        Ok(())
+    }
+
+    fn reset(&mut self) -> Result<(), Error> {
+        self.reset_with(ResetKind::System)
+    }
+
+    fn reset_and_halt(&mut self, timeout: Duration) -> Result<CoreInformation, Error> {
+        self.reset_and_halt_with(ResetKind::System, timeout)
+    }
+
+    fn reset_with(&mut self, _kind: ResetKind) -> Result<(), Error> {
+        self.set_register(self.pc(), self.reset_address);
+
+        if self.reset_catch {
+            self.halted(HaltReason::Request);
+        } else {
+            self.status = CoreStatus::Running;
+        }
+
+        Ok(())
+    }
+
+    fn reset_and_halt_with(
+        &mut self,
+        _kind: ResetKind,
+        _timeout: Duration,
+    ) -> Result<CoreInformation, Error> {
+        self.set_register(self.pc(), self.reset_address);
+
+        Ok(self.halted(HaltReason::Request))
+    }
+
+    fn enable_reset_catch(&mut self) -> Result<(), Error> {
+        self.reset_catch = true;
+
+        Ok(())
+    }
+
+    fn disable_reset_catch(&mut self) -> Result<(), Error> {
+        self.reset_catch = false;
+
+        Ok(())
+    }
+
+    fn step(&mut self) -> Result<CoreInformation, Error> {
+        let pc = self.register(self.pc());
+        self.set_register(self.pc(), pc.wrapping_add(self.instruction_size()));
+
+        Ok(self.halted(HaltReason::Step))
+    }
+
+    fn read_core_reg(&mut self, address: CoreRegisterAddress) -> Result<u32, Error> {
+        Ok(self.register(address))
+    }
+
+    fn write_core_reg(&mut self, address: CoreRegisterAddress, value: u32) -> anyhow::Result<()> {
+        self.set_register(address, value);
+
+        Ok(())
+    }
+
+    fn get_available_breakpoint_units(&mut self) -> Result<u32, Error> {
+        Ok(self.breakpoints.len() as u32)
+    }
+
+    fn get_hw_breakpoints(&mut self) -> Result<Vec<Option<u32>>, Error> {
+        Ok(self.breakpoints.clone())
+    }
+
+    fn enable_breakpoints(&mut self, state: bool) -> Result<(), Error> {
+        self.breakpoints_enabled = state;
+
+        Ok(())
+    }
+
+    fn set_hw_breakpoint(&mut self, bp_unit_index: usize, addr: u32) -> Result<(), Error> {
+        match self.breakpoints.get_mut(bp_unit_index) {
+            Some(breakpoint) => {
+                *breakpoint = Some(addr);
+                Ok(())
+            }
+            None => Err(Error::NoFreeBreakpoint),
+        }
+    }
+
+    fn clear_hw_breakpoint(&mut self, unit_index: usize) -> Result<(), Error> {
+        if let Some(breakpoint) = self.breakpoints.get_mut(unit_index) {
+            *breakpoint = None;
+        }
+
+        Ok(())
+    }
+
+    fn registers(&self) -> &'static RegisterFile {
+        match self.architecture {
+            Architecture::Arm => &ARM_REGISTER_FILE,
+            Architecture::Riscv => &RISCV_REGISTERS,
+            Architecture::Xtensa => &XTENSA_REGISTERS,
+        }
+    }
+
+    fn hw_breakpoints_enabled(&self) -> bool {
+        self.breakpoints_enabled
+    }
+
+    fn architecture(&self) -> Architecture {
+        self.architecture
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::MockCore;
+    use crate::{
+        Architecture, CancellationToken, Core, CoreEvent, CoreRegisterAddress, CoreStatus, Error,
+        HaltReason,
+    };
+
+    #[test]
+    fn run_to_breakpoint() {
+        let mut state = Core::create_state(0);
+        let mut core = Core::new(MockCore::new(Architecture::Arm), &mut state);
+        let pc = CoreRegisterAddress::from(core.registers().program_counter());
+
+        core.write_core_reg(pc, 0x100).unwrap();
+        assert_eq!(core.step().unwrap().pc, 0x102);
+
+        core.set_hw_breakpoint(0x200).unwrap();
+        core.run().unwrap();
+        assert_eq!(
+            core.status().unwrap(),
+            CoreStatus::Halted(HaltReason::Breakpoint)
+        );
+        assert_eq!(core.read_core_reg(pc).unwrap(), 0x200);
+
+        core.clear_hw_breakpoint(0x200).unwrap();
+        core.run().unwrap();
+        assert!(core.wait_for_core_halted(Duration::ZERO).is_err());
+    }
+
+    #[test]
+    fn cancel_wait_for_halt() {
+        let mut state = Core::create_state(0);
+        let mut core = Core::new(MockCore::new(Architecture::Arm), &mut state);
+        let cancellation = CancellationToken::new();
+
+        core.run().unwrap();
+        cancellation.cancel();
+
+        assert!(matches!(
+            core.wait_for_core_halted_cancellable(Duration::from_secs(60), &cancellation),
+            Err(Error::Cancelled)
+        ));
+    }
+
+    #[test]
+    fn halt_routine_after_timeout() {
+        let mut state = Core::create_state(0);
+        let mut core = Core::new(MockCore::new(Architecture::Arm), &mut state);
+        let pc = CoreRegisterAddress::from(core.registers().program_counter());
+        let sp = CoreRegisterAddress::from(core.registers().stack_pointer());
+
+        core.write_core_reg(pc, 0x100).unwrap();
+        core.write_core_reg(sp, 0x2000_1000).unwrap();
+
+        // Without a breakpoint the core keeps running, like a routine which doesn't return.
+        assert!(matches!(
+            core.run_routine(0x0800_0001, &[1, 2], Duration::ZERO),
+            Err(Error::RoutineTimeout {
+                address: 0x0800_0001,
+                ..
+            })
+        ));
+
+        // The core is halted, and its registers are restored.
+        assert!(core.core_halted().unwrap());
+        assert_eq!(core.read_core_reg(pc).unwrap(), 0x100);
+        assert_eq!(core.read_core_reg(sp).unwrap(), 0x2000_1000);
+    }
+
+    #[test]
+    fn routine_without_stack_space() {
+        let mut state = Core::create_state(0);
+        let mut core = Core::new(MockCore::new(Architecture::Riscv), &mut state);
+        let pc = CoreRegisterAddress::from(core.registers().program_counter());
+        let sp = CoreRegisterAddress::from(core.registers().stack_pointer());
+
+        // The stack pointer of a RISC-V core is 0 after a reset.
+        core.write_core_reg(pc, 0x100).unwrap();
+        core.write_core_reg(sp, 0).unwrap();
+
+        assert!(matches!(
+            core.run_routine(0x4000_0000, &[1, 2], Duration::ZERO),
+            Err(Error::AddressOutOfRange { address: 0, .. })
+        ));
+
+        // Stack arguments which don't fit below the stack pointer are rejected as well.
+        core.write_core_reg(sp, 0x10).unwrap();
+        assert!(matches!(
+            core.run_routine(0x4000_0000, &[0; 12], Duration::ZERO),
+            Err(Error::AddressOutOfRange {
+                address: 0x10,
+                length: 20
+            })
+        ));
+
+        // The core was not started, and its registers are restored.
+        assert!(core.core_halted().unwrap());
+        assert_eq!(core.read_core_reg(pc).unwrap(), 0x100);
+        assert_eq!(core.read_core_reg(sp).unwrap(), 0x10);
+    }
+
+    #[test]
+    fn record_event_history() {
+        let mut state = Core::create_state(0);
+        let mut core = Core::new(MockCore::new(Architecture::Arm), &mut state);
+
+        core.set_hw_breakpoint(0x200).unwrap();
+        core.run().unwrap();
+        // The halt is only recorded once.
+        core.status().unwrap();
+        core.status().unwrap();
+        core.clear_hw_breakpoint(0x200).unwrap();
+        core.reset().unwrap();
+
+        let events: Vec<CoreEvent> = core
+            .event_history()
+            .iter()
+            .map(|record| record.event)
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                CoreEvent::Resumed,
+                CoreEvent::Halted {
+                    reason: HaltReason::Breakpoint,
+                    pc: Some(0x200)
+                },
+                CoreEvent::Reset { kind: None },
+            ]
+        );
+    }
+}
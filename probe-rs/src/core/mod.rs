@@ -1,24 +1,46 @@
+mod cache;
 pub(crate) mod communication_interface;
-
+#[cfg(feature = "disassembly")]
+mod disassembly;
+mod dump;
+mod history;
+mod lockup;
+mod mock;
+mod poller;
+pub(crate) mod routine;
+mod stepping;
+
+pub(crate) use cache::MemoryCache;
 pub use communication_interface::CommunicationInterface;
+#[cfg(feature = "disassembly")]
+pub use disassembly::Instruction;
+pub use dump::CoreDump;
+pub use history::{CoreEvent, CoreEventRecord};
+pub use lockup::{FaultStatus, LockupRecovery};
+pub use mock::MockCore;
+pub use poller::{StatusChange, StatusPoller};
 pub use probe_rs_target::Architecture;
-use probe_rs_target::CoreType;
+use probe_rs_target::{CoreType, ResetKind};
 
 use crate::architecture::{
     arm::core::State, riscv::communication_interface::RiscvCommunicationInterface,
+    xtensa::communication_interface::XtensaCommunicationInterface,
 };
 use crate::error;
 use crate::Target;
-use crate::{Error, Memory, MemoryInterface};
-use anyhow::{anyhow, Result};
+use crate::{CancellationToken, DebugProbeError, Error, Memory, MemoryInterface};
+use anyhow::Result;
+use history::EventHistory;
+use std::collections::VecDeque;
 use std::time::Duration;
+use web_time::Instant;
 
 pub trait CoreRegister: Clone + From<u32> + Into<u32> + Sized + std::fmt::Debug {
     const ADDRESS: u32;
     const NAME: &'static str;
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct CoreRegisterAddress(pub u16);
 
 impl From<CoreRegisterAddress> for u32 {
@@ -82,6 +104,10 @@ pub struct RegisterFile {
 
     pub(crate) argument_registers: &'static [RegisterDescription],
     pub(crate) result_registers: &'static [RegisterDescription],
+
+    /// Registers which are not part of the platform registers, e.g. the banked
+    /// stack pointers and the exception mask registers on ARM.
+    pub(crate) special_registers: &'static [RegisterDescription],
 }
 
 impl RegisterFile {
@@ -124,6 +150,19 @@ impl RegisterFile {
     pub fn get_platform_register(&self, index: usize) -> Option<&RegisterDescription> {
         self.platform_registers.get(index)
     }
+
+    /// The special registers of the core, which are accessible in addition to the
+    /// platform registers, e.g. `MSP`, `PSP` and `CONTROL` on ARM.
+    pub fn special_registers(&self) -> impl Iterator<Item = &RegisterDescription> {
+        self.special_registers.iter()
+    }
+
+    /// Find a special register by its name.
+    pub fn get_special_register(&self, name: &str) -> Option<&RegisterDescription> {
+        self.special_registers
+            .iter()
+            .find(|register| register.name.eq_ignore_ascii_case(name))
+    }
 }
 
 pub trait CoreInterface: MemoryInterface {
@@ -155,6 +194,63 @@ pub trait CoreInterface: MemoryInterface {
     /// [`reset`]: Core::reset
     fn reset_and_halt(&mut self, timeout: Duration) -> Result<CoreInformation, error::Error>;
 
+    /// Reset the core using the given kind of reset, and then continue to execute instructions.
+    ///
+    /// Returns [`Error::UnsupportedResetKind`] if the core does not support the kind of reset.
+    fn reset_with(&mut self, kind: ResetKind) -> Result<(), error::Error>;
+
+    /// Reset the core using the given kind of reset, and then immediately halt.
+    ///
+    /// Returns [`Error::UnsupportedResetKind`] if the core does not support the kind of reset.
+    fn reset_and_halt_with(
+        &mut self,
+        kind: ResetKind,
+        timeout: Duration,
+    ) -> Result<CoreInformation, error::Error>;
+
+    /// Halt the core at the first instruction after any following reset, e.g. one
+    /// triggered externally through the reset pin or by a watchdog.
+    fn enable_reset_catch(&mut self) -> Result<(), error::Error>;
+
+    /// Stop halting the core after a reset, which was enabled with [`enable_reset_catch`].
+    ///
+    /// [`enable_reset_catch`]: Core::enable_reset_catch
+    fn disable_reset_catch(&mut self) -> Result<(), error::Error>;
+
+    /// Check if the core was reset since the last call, e.g. by a watchdog.
+    ///
+    /// The reset is acknowledged, so that the next call only returns `true` after
+    /// another reset. Cores which can't detect resets always return `false`.
+    fn reset_detected(&mut self) -> Result<bool, error::Error> {
+        Ok(false)
+    }
+
+    /// Enable debugging again after an unexpected reset of the core, detected with
+    /// [`CoreInterface::reset_detected`], and restore the debug configuration which
+    /// was lost by the reset.
+    ///
+    /// Breakpoints are set again by the caller.
+    fn reinitialize_debug(&mut self) -> Result<(), error::Error> {
+        Ok(())
+    }
+
+    /// Stop the watchdogs of the chip while the core is halted, or let them run again.
+    ///
+    /// Cores which don't know how to stop the watchdogs of the chip ignore this.
+    fn set_watchdog_freeze(&mut self, _freeze: bool) -> Result<(), error::Error> {
+        Ok(())
+    }
+
+    /// Change how interrupts are handled by following calls of [`CoreInterface::step`].
+    ///
+    /// Cores which can't mask interrupts while stepping ignore this.
+    fn set_step_interrupt_behavior(
+        &mut self,
+        _behavior: StepInterruptBehavior,
+    ) -> Result<(), error::Error> {
+        Ok(())
+    }
+
     /// Steps one instruction and then enters halted state again.
     fn step(&mut self) -> Result<CoreInformation, error::Error>;
 
@@ -162,6 +258,35 @@ pub trait CoreInterface: MemoryInterface {
 
     fn write_core_reg(&mut self, address: CoreRegisterAddress, value: u32) -> Result<()>;
 
+    /// Read the core registers at `addresses`.
+    ///
+    /// Cores which can queue the register transfers read all registers in as few round
+    /// trips to the probe as possible. The default implementation reads them one after another.
+    fn read_core_regs(
+        &mut self,
+        addresses: &[CoreRegisterAddress],
+    ) -> Result<Vec<u32>, error::Error> {
+        addresses
+            .iter()
+            .map(|address| self.read_core_reg(*address))
+            .collect()
+    }
+
+    /// Write the values of the core `registers`.
+    ///
+    /// Cores which can queue the register transfers write all registers in as few round
+    /// trips to the probe as possible. The default implementation writes them one after another.
+    fn write_core_regs(
+        &mut self,
+        registers: &[(CoreRegisterAddress, u32)],
+    ) -> Result<(), error::Error> {
+        for (address, value) in registers {
+            self.write_core_reg(*address, *value)?;
+        }
+
+        Ok(())
+    }
+
     fn get_available_breakpoint_units(&mut self) -> Result<u32, error::Error>;
 
     /// Read the hardware breakpoints from FpComp registers, and adds them to the Result Vector.
@@ -175,47 +300,152 @@ pub trait CoreInterface: MemoryInterface {
 
     fn clear_hw_breakpoint(&mut self, unit_index: usize) -> Result<(), error::Error>;
 
+    /// The number of hardware watchpoint units of the core.
+    fn get_available_watchpoint_units(&mut self) -> Result<u32, error::Error> {
+        Ok(0)
+    }
+
+    /// Read the configured hardware watchpoints, indexed by watchpoint unit.
+    /// A value of None in any position of the Vector indicates that the unit is available.
+    fn get_hw_watchpoints(&mut self) -> Result<Vec<Option<Watchpoint>>, error::Error> {
+        Ok(Vec::new())
+    }
+
+    fn set_hw_watchpoint(
+        &mut self,
+        _unit_index: usize,
+        _watchpoint: Watchpoint,
+    ) -> Result<(), error::Error> {
+        Err(Error::NoFreeWatchpoint)
+    }
+
+    fn clear_hw_watchpoint(&mut self, _unit_index: usize) -> Result<(), error::Error> {
+        Ok(())
+    }
+
+    /// Whether breakpoints and watchpoints are set using the same hardware units,
+    /// like the triggers of RISC-V. In this case, the unit indices of breakpoints
+    /// and watchpoints refer to the same units.
+    fn hw_units_are_shared(&self) -> bool {
+        false
+    }
+
+    /// Details about why the halted core halted.
+    ///
+    /// The default implementation only returns the reason of [`CoreInterface::status`].
+    fn halt_details(&mut self) -> Result<HaltDetails, error::Error> {
+        let reason = match self.status()? {
+            CoreStatus::Halted(reason) => reason,
+            _ => HaltReason::Unknown,
+        };
+
+        Ok(HaltDetails::new(reason))
+    }
+
+    /// The fault status registers of the halted core, which describe the cause of a lockup.
+    ///
+    /// Cores without fault status registers return `None`.
+    fn fault_status(&mut self) -> Result<Option<FaultStatus>, error::Error> {
+        Ok(None)
+    }
+
+    /// Whether memory can be read without halting the core.
+    fn can_read_while_running(&mut self) -> bool {
+        false
+    }
+
+    /// Whether memory can be written without halting the core.
+    fn can_write_while_running(&mut self) -> bool {
+        false
+    }
+
     fn registers(&self) -> &'static RegisterFile;
 
     fn hw_breakpoints_enabled(&self) -> bool;
 
     /// Get the `Architecture` of the Core.
     fn architecture(&self) -> Architecture;
+
+    /// The security state of the halted core, or `None` if the core does not
+    /// implement a security extension.
+    fn security_state(&mut self) -> Result<Option<SecurityState>, error::Error> {
+        Ok(None)
+    }
+
+    /// Change the security state of the halted core.
+    fn set_security_state(&mut self, _state: SecurityState) -> Result<(), error::Error> {
+        Err(Error::NoSecurityExtension)
+    }
 }
 
 impl<'probe> MemoryInterface for Core<'probe> {
     fn read_word_32(&mut self, address: u32) -> Result<u32, Error> {
+        let mut bytes = [0u8; 4];
+        if self.read_cached(address, &mut bytes)? {
+            return Ok(u32::from_le_bytes(bytes));
+        }
+
         self.inner.read_word_32(address)
     }
 
     fn read_word_8(&mut self, address: u32) -> Result<u8, Error> {
+        let mut bytes = [0u8; 1];
+        if self.read_cached(address, &mut bytes)? {
+            return Ok(bytes[0]);
+        }
+
         self.inner.read_word_8(address)
     }
 
     fn read_32(&mut self, address: u32, data: &mut [u32]) -> Result<(), Error> {
+        if self.state.memory_cache.is_enabled() {
+            let mut bytes = vec![0u8; data.len() * 4];
+            if self.read_cached(address, &mut bytes)? {
+                for (word, bytes) in data.iter_mut().zip(bytes.chunks_exact(4)) {
+                    *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                }
+                return Ok(());
+            }
+        }
+
         self.inner.read_32(address, data)
     }
 
     fn read_8(&mut self, address: u32, data: &mut [u8]) -> Result<(), Error> {
+        if self.read_cached(address, data)? {
+            return Ok(());
+        }
+
         self.inner.read_8(address, data)
     }
 
     fn write_word_32(&mut self, addr: u32, data: u32) -> Result<(), Error> {
+        self.state.memory_cache.invalidate_range(addr, 4);
         self.inner.write_word_32(addr, data)
     }
 
     fn write_word_8(&mut self, addr: u32, data: u8) -> Result<(), Error> {
+        self.state.memory_cache.invalidate_range(addr, 1);
         self.inner.write_word_8(addr, data)
     }
 
     fn write_32(&mut self, addr: u32, data: &[u32]) -> Result<(), Error> {
+        self.state
+            .memory_cache
+            .invalidate_range(addr, data.len() * 4);
         self.inner.write_32(addr, data)
     }
 
     fn write_8(&mut self, addr: u32, data: &[u8]) -> Result<(), Error> {
+        self.state.memory_cache.invalidate_range(addr, data.len());
         self.inner.write_8(addr, data)
     }
 
+    fn fill_32(&mut self, addr: u32, value: u32, count: usize) -> Result<(), Error> {
+        self.state.memory_cache.invalidate_range(addr, count * 4);
+        self.inner.fill_32(addr, value, count)
+    }
+
     fn flush(&mut self) -> Result<(), Error> {
         self.inner.flush()
     }
@@ -224,16 +454,52 @@ impl<'probe> MemoryInterface for Core<'probe> {
 #[derive(Debug)]
 pub struct CoreState {
     id: usize,
+    default_reset: Option<ResetKind>,
+    /// The breakpoints which are set again after a reset.
+    persistent_breakpoints: Vec<u32>,
+    /// The watchpoints which are set again after a reset.
+    persistent_watchpoints: Vec<Watchpoint>,
+    /// The cache for reads of read-only memory regions.
+    memory_cache: MemoryCache,
+    /// How interrupts are handled by [`Core::step`].
+    step_interrupt_behavior: StepInterruptBehavior,
+    /// The latest halts, resumes and resets of the core.
+    event_history: EventHistory,
 }
 
 impl CoreState {
     pub fn new(id: usize) -> Self {
-        Self { id }
+        Self {
+            id,
+            default_reset: None,
+            persistent_breakpoints: Vec::new(),
+            persistent_watchpoints: Vec::new(),
+            memory_cache: MemoryCache::default(),
+            step_interrupt_behavior: StepInterruptBehavior::default(),
+            event_history: EventHistory::default(),
+        }
     }
 
     pub fn id(&self) -> usize {
         self.id
     }
+
+    /// Set the kind of reset used by [`Core::reset`] and [`Core::reset_and_halt`].
+    ///
+    /// If `None` is given, the default reset of the architecture is used.
+    pub fn set_default_reset(&mut self, kind: Option<ResetKind>) {
+        self.default_reset = kind;
+    }
+
+    /// The cache for reads of read-only memory regions of this core.
+    pub(crate) fn memory_cache(&self) -> &MemoryCache {
+        &self.memory_cache
+    }
+
+    /// The cache for reads of read-only memory regions of this core.
+    pub(crate) fn memory_cache_mut(&mut self) -> &mut MemoryCache {
+        &mut self.memory_cache
+    }
 }
 
 #[derive(Debug)]
@@ -243,6 +509,7 @@ pub enum SpecificCoreState {
     Armv7em(State),
     Armv8m(State),
     Riscv,
+    Xtensa,
 }
 
 impl SpecificCoreState {
@@ -253,6 +520,7 @@ impl SpecificCoreState {
             CoreType::Armv7em => SpecificCoreState::Armv7m(State::new()),
             CoreType::Armv8m => SpecificCoreState::Armv8m(State::new()),
             CoreType::Riscv => SpecificCoreState::Riscv,
+            CoreType::Xtensa => SpecificCoreState::Xtensa,
         }
     }
 
@@ -263,6 +531,7 @@ impl SpecificCoreState {
             SpecificCoreState::Armv7em(_) => CoreType::Armv7em,
             SpecificCoreState::Armv8m(_) => CoreType::Armv8m,
             SpecificCoreState::Riscv => CoreType::Riscv,
+            SpecificCoreState::Xtensa => CoreType::Xtensa,
         }
     }
 
@@ -274,7 +543,7 @@ impl SpecificCoreState {
     ) -> Result<Core<'probe>, Error> {
         let debug_sequence = match &target.debug_sequence {
             crate::config::DebugSequence::Arm(sequence) => sequence.clone(),
-            crate::config::DebugSequence::Riscv(_) => {
+            _ => {
                 return Err(Error::UnableToOpenProbe(
                     "Core architecture and Probe mismatch.",
                 ))
@@ -306,11 +575,50 @@ impl SpecificCoreState {
         &self,
         state: &'probe mut CoreState,
         interface: &'probe mut RiscvCommunicationInterface,
+        target: &Target,
     ) -> Result<Core<'probe>, Error> {
+        let debug_sequence = match &target.debug_sequence {
+            crate::config::DebugSequence::Riscv(sequence) => sequence.clone(),
+            _ => {
+                return Err(Error::UnableToOpenProbe(
+                    "Core architecture and Probe mismatch.",
+                ))
+            }
+        };
+
         Ok(match self {
-            SpecificCoreState::Riscv => {
-                Core::new(crate::architecture::riscv::Riscv32::new(interface), state)
+            SpecificCoreState::Riscv => Core::new(
+                crate::architecture::riscv::Riscv32::new(interface, debug_sequence),
+                state,
+            ),
+            _ => {
+                return Err(Error::UnableToOpenProbe(
+                    "Core architecture and Probe mismatch.",
+                ))
             }
+        })
+    }
+
+    pub fn attach_xtensa<'probe>(
+        &self,
+        state: &'probe mut CoreState,
+        interface: &'probe mut XtensaCommunicationInterface,
+        target: &Target,
+    ) -> Result<Core<'probe>, Error> {
+        let debug_sequence = match &target.debug_sequence {
+            crate::config::DebugSequence::Xtensa(sequence) => sequence.clone(),
+            _ => {
+                return Err(Error::UnableToOpenProbe(
+                    "Core architecture and Probe mismatch.",
+                ))
+            }
+        };
+
+        Ok(match self {
+            SpecificCoreState::Xtensa => Core::new(
+                crate::architecture::xtensa::Xtensa::new(interface, debug_sequence),
+                state,
+            ),
             _ => {
                 return Err(Error::UnableToOpenProbe(
                     "Core architecture and Probe mismatch.",
@@ -344,7 +652,37 @@ impl<'probe> Core<'probe> {
     /// Wait until the core is halted. If the core does not halt on its own,
     /// a [`DebugProbeError::Timeout`](crate::DebugProbeError::Timeout) error will be returned.
     pub fn wait_for_core_halted(&mut self, timeout: Duration) -> Result<(), error::Error> {
-        self.inner.wait_for_core_halted(timeout)
+        self.inner.wait_for_core_halted(timeout)?;
+
+        // Record the halt in the event history.
+        self.status()?;
+
+        Ok(())
+    }
+
+    /// Wait until the core is halted, like [`Core::wait_for_core_halted`], but stop
+    /// waiting with an [`Error::Cancelled`] error as soon as `cancellation` is cancelled.
+    pub fn wait_for_core_halted_cancellable(
+        &mut self,
+        timeout: Duration,
+        cancellation: &CancellationToken,
+    ) -> Result<(), error::Error> {
+        let start = Instant::now();
+
+        while start.elapsed() < timeout {
+            cancellation.check()?;
+
+            if self.inner.core_halted()? {
+                // Update the halted state.
+                self.status()?;
+
+                return Ok(());
+            }
+
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        Err(Error::Probe(DebugProbeError::Timeout))
     }
 
     /// Check if the core is halted. If the core does not halt on its own,
@@ -356,36 +694,364 @@ impl<'probe> Core<'probe> {
     /// Try to halt the core. This function ensures the core is actually halted, and
     /// returns a [`DebugProbeError::Timeout`](crate::DebugProbeError::Timeout) otherwise.
     pub fn halt(&mut self, timeout: Duration) -> Result<CoreInformation, error::Error> {
-        self.inner.halt(timeout)
+        let information = self.inner.halt(timeout)?;
+
+        self.state.event_history.record(CoreEvent::Halted {
+            reason: HaltReason::Request,
+            pc: Some(information.pc),
+        });
+
+        Ok(information)
     }
 
     pub fn run(&mut self) -> Result<(), error::Error> {
-        self.inner.run()
+        self.inner.run()?;
+        self.state.event_history.record(CoreEvent::Resumed);
+
+        Ok(())
     }
 
     /// Reset the core, and then continue to execute instructions. If the core
     /// should be halted after reset, use the [`reset_and_halt`] function.
     ///
+    /// The kind of reset is taken from [`Target::default_reset`]. If the target
+    /// has no default, the default reset of the architecture is used.
+    ///
+    /// Breakpoints managed by the [`Session`](crate::Session) are set again after the reset.
+    ///
     /// [`reset_and_halt`]: Core::reset_and_halt
     pub fn reset(&mut self) -> Result<(), error::Error> {
-        self.inner.reset()
+        self.reset_restoring_breakpoints(self.state.default_reset)
     }
 
     /// Reset the core, and then immediately halt. To continue execution after
     /// reset, use the [`reset`] function.
     ///
+    /// The kind of reset is taken from [`Target::default_reset`]. If the target
+    /// has no default, the default reset of the architecture is used.
+    ///
+    /// Breakpoints managed by the [`Session`](crate::Session) are set again after the reset.
+    ///
     /// [`reset`]: Core::reset
     pub fn reset_and_halt(&mut self, timeout: Duration) -> Result<CoreInformation, error::Error> {
-        self.inner.reset_and_halt(timeout)
+        self.reset_and_halt_restoring_breakpoints(self.state.default_reset, timeout)
+    }
+
+    /// Reset the core using the given kind of reset, and then continue to execute instructions.
+    ///
+    /// Returns [`Error::UnsupportedResetKind`] if the core does not support the kind of reset.
+    pub fn reset_with(&mut self, kind: ResetKind) -> Result<(), error::Error> {
+        self.reset_restoring_breakpoints(Some(kind))
+    }
+
+    /// Reset the core using the given kind of reset, and then immediately halt.
+    ///
+    /// Returns [`Error::UnsupportedResetKind`] if the core does not support the kind of reset.
+    pub fn reset_and_halt_with(
+        &mut self,
+        kind: ResetKind,
+        timeout: Duration,
+    ) -> Result<CoreInformation, error::Error> {
+        self.reset_and_halt_restoring_breakpoints(Some(kind), timeout)
+    }
+
+    /// Reset the core using `kind`, or the default reset of the architecture, and set the
+    /// persistent breakpoints again.
+    ///
+    /// If there are persistent breakpoints, the core is halted after the reset to set them
+    /// before any instruction is executed, and then resumed.
+    fn reset_restoring_breakpoints(&mut self, kind: Option<ResetKind>) -> Result<(), error::Error> {
+        // A reset can change memory, e.g. when a bootloader remaps it.
+        self.state.memory_cache.invalidate();
+
+        if self.state.persistent_breakpoints.is_empty()
+            && self.state.persistent_watchpoints.is_empty()
+        {
+            match kind {
+                Some(kind) => self.inner.reset_with(kind)?,
+                None => self.inner.reset()?,
+            };
+            self.state.event_history.record(CoreEvent::Reset { kind });
+
+            return Ok(());
+        }
+
+        self.reset_and_halt_restoring_breakpoints(kind, Duration::from_millis(500))?;
+        self.run()
+    }
+
+    fn reset_and_halt_restoring_breakpoints(
+        &mut self,
+        kind: Option<ResetKind>,
+        timeout: Duration,
+    ) -> Result<CoreInformation, error::Error> {
+        self.state.memory_cache.invalidate();
+
+        let information = match kind {
+            Some(kind) => self.inner.reset_and_halt_with(kind, timeout)?,
+            None => self.inner.reset_and_halt(timeout)?,
+        };
+
+        self.state.event_history.record(CoreEvent::Reset { kind });
+        self.state.event_history.record(CoreEvent::Halted {
+            reason: HaltReason::Request,
+            pc: Some(information.pc),
+        });
+
+        self.restore_persistent_breakpoints()?;
+
+        Ok(information)
+    }
+
+    /// Replace the persistent breakpoints and watchpoints, which are set again after every
+    /// reset. Breakpoints and watchpoints which are not persistent anymore are cleared.
+    pub(crate) fn set_persistent_breakpoints(
+        &mut self,
+        breakpoints: Vec<u32>,
+        watchpoints: Vec<Watchpoint>,
+    ) -> Result<(), error::Error> {
+        // The state is updated after every change, so that it stays correct if one fails.
+        while let Some(index) = self
+            .state
+            .persistent_breakpoints
+            .iter()
+            .position(|address| !breakpoints.contains(address))
+        {
+            let address = self.state.persistent_breakpoints.remove(index);
+            match self.clear_hw_breakpoint(address) {
+                // The breakpoint may have been cleared directly on the core.
+                Err(error::Error::BreakpointNotFound(_)) => {}
+                result => result?,
+            }
+        }
+
+        while let Some(index) = self
+            .state
+            .persistent_watchpoints
+            .iter()
+            .position(|watchpoint| !watchpoints.contains(watchpoint))
+        {
+            let watchpoint = self.state.persistent_watchpoints.remove(index);
+            match self.clear_hw_watchpoint(watchpoint.address) {
+                Err(error::Error::WatchpointNotFound(_)) => {}
+                result => result?,
+            }
+        }
+
+        for address in breakpoints {
+            if !self.state.persistent_breakpoints.contains(&address) {
+                self.set_hw_breakpoint(address)?;
+                self.state.persistent_breakpoints.push(address);
+            }
+        }
+
+        for watchpoint in watchpoints {
+            if !self.state.persistent_watchpoints.contains(&watchpoint) {
+                self.set_hw_watchpoint(watchpoint.address, watchpoint.length, watchpoint.kind)?;
+                self.state.persistent_watchpoints.push(watchpoint);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Set the persistent breakpoints and watchpoints again, e.g. after a reset.
+    pub(crate) fn restore_persistent_breakpoints(&mut self) -> Result<(), error::Error> {
+        if !self.state.persistent_breakpoints.is_empty() {
+            // The enable bit may have been cleared by the reset.
+            self.enable_breakpoints(true)?;
+        }
+
+        for address in self.state.persistent_breakpoints.clone() {
+            self.set_hw_breakpoint(address)?;
+        }
+
+        for watchpoint in self.state.persistent_watchpoints.clone() {
+            self.set_hw_watchpoint(watchpoint.address, watchpoint.length, watchpoint.kind)?;
+        }
+
+        Ok(())
+    }
+
+    /// Halt the core at the first instruction after any following reset, e.g. one
+    /// triggered externally through the reset pin or by a watchdog.
+    ///
+    /// [`reset_and_halt`](Core::reset_and_halt) uses the reset catch automatically, so this is
+    /// only needed for resets which are not triggered by probe-rs. The reset catch stays
+    /// enabled until [`disable_reset_catch`](Core::disable_reset_catch) is called.
+    pub fn enable_reset_catch(&mut self) -> Result<(), error::Error> {
+        self.inner.enable_reset_catch()
+    }
+
+    /// Stop halting the core after a reset, which was enabled with [`enable_reset_catch`].
+    ///
+    /// [`enable_reset_catch`]: Core::enable_reset_catch
+    pub fn disable_reset_catch(&mut self) -> Result<(), error::Error> {
+        self.inner.disable_reset_catch()
+    }
+
+    /// Stop the watchdogs of the chip while the core is halted, so that stepping through
+    /// the code is not interrupted by a watchdog reset, or let them run again.
+    ///
+    /// This is currently supported for STM32 and nRF51/nRF52 chips, and is ignored for
+    /// other chips. The watchdogs are stopped again after an unexpected reset.
+    pub fn set_watchdog_freeze(&mut self, freeze: bool) -> Result<(), error::Error> {
+        self.inner.set_watchdog_freeze(freeze)
+    }
+
+    /// Check if the core was reset since the last call, see [`CoreInterface::reset_detected`].
+    pub(crate) fn reset_detected(&mut self) -> Result<bool, error::Error> {
+        let reset = self.inner.reset_detected()?;
+
+        if reset {
+            self.state.event_history.record(CoreEvent::ResetDetected);
+        }
+
+        Ok(reset)
+    }
+
+    /// Enable debugging again after an unexpected reset, and set the persistent
+    /// breakpoints again.
+    pub(crate) fn reinitialize_after_reset(&mut self) -> Result<(), error::Error> {
+        // Memory may have changed, e.g. when a bootloader remaps it.
+        self.state.memory_cache.invalidate();
+
+        self.inner.reinitialize_debug()?;
+        self.restore_persistent_breakpoints()
     }
 
     /// Steps one instruction and then enters halted state again.
+    ///
+    /// Interrupts are handled as configured with [`Core::set_step_interrupt_behavior`].
     pub fn step(&mut self) -> Result<CoreInformation, error::Error> {
         self.inner.step()
     }
 
+    /// Steps one instruction, handling interrupts according to `behavior` instead of the
+    /// behavior configured with [`Core::set_step_interrupt_behavior`].
+    pub fn step_with(
+        &mut self,
+        behavior: StepInterruptBehavior,
+    ) -> Result<CoreInformation, error::Error> {
+        self.inner.set_step_interrupt_behavior(behavior)?;
+
+        let result = self.inner.step();
+
+        self.inner
+            .set_step_interrupt_behavior(self.state.step_interrupt_behavior)?;
+
+        result
+    }
+
+    /// Change how interrupts are handled when the core is stepped.
+    ///
+    /// By default, interrupts are masked, so that stepping doesn't continue in interrupt
+    /// handlers. Interrupts are not masked when the core runs.
+    pub fn set_step_interrupt_behavior(
+        &mut self,
+        behavior: StepInterruptBehavior,
+    ) -> Result<(), error::Error> {
+        self.inner.set_step_interrupt_behavior(behavior)?;
+        self.state.step_interrupt_behavior = behavior;
+
+        Ok(())
+    }
+
+    /// How interrupts are handled when the core is stepped.
+    pub fn step_interrupt_behavior(&self) -> StepInterruptBehavior {
+        self.state.step_interrupt_behavior
+    }
+
     pub fn status(&mut self) -> Result<CoreStatus, error::Error> {
-        self.inner.status()
+        let status = self.inner.status()?;
+
+        match status {
+            CoreStatus::Halted(reason) => {
+                // The program counter is only read for a new halt.
+                if !self.state.event_history.is_halted() {
+                    let pc_address = self.inner.registers().program_counter().into();
+                    let pc = self.inner.read_core_reg(pc_address).ok();
+
+                    self.state
+                        .event_history
+                        .record(CoreEvent::Halted { reason, pc });
+                }
+            }
+            CoreStatus::Running | CoreStatus::Sleeping => {
+                self.state.event_history.record(CoreEvent::Resumed)
+            }
+            CoreStatus::LockedUp | CoreStatus::Unknown => {}
+        }
+
+        Ok(status)
+    }
+
+    /// The latest halts, resumes and resets of the core, the oldest first, with the time
+    /// they were recorded.
+    ///
+    /// This helps to find out what happened to a core which was left running, e.g. when
+    /// it halted at some point during the night. The history is kept for the whole
+    /// session, and holds the latest 256 events by default, see
+    /// [`Core::set_event_history_capacity`].
+    ///
+    /// Halts and resumes which are not caused by the debugger, e.g. breakpoint hits, are
+    /// recorded when the status of the core is read, e.g. by [`Core::status`] or
+    /// [`Session::poll_events`](crate::Session::poll_events). Single steps are not
+    /// recorded.
+    pub fn event_history(&self) -> &VecDeque<CoreEventRecord> {
+        self.state.event_history.records()
+    }
+
+    /// Change the number of events which are kept in the [event history](Core::event_history).
+    /// The oldest events are dropped if there are more.
+    pub fn set_event_history_capacity(&mut self, capacity: usize) {
+        self.state.event_history.set_capacity(capacity);
+    }
+
+    /// Remove all events from the [event history](Core::event_history).
+    pub fn clear_event_history(&mut self) {
+        self.state.event_history.clear();
+    }
+
+    /// Read memory without halting the core.
+    ///
+    /// On ARM cores, memory is accessed through the MEM-AP, which does not interfere with the
+    /// core. RISC-V harts can only be accessed while running if the debug module supports system
+    /// bus access. If the memory can not be accessed while the core is running, and the core is
+    /// not halted, [`Error::MemoryAccessWhileRunningNotSupported`] is returned.
+    pub fn read_while_running(
+        &mut self,
+        address: u32,
+        data: &mut [u8],
+    ) -> Result<(), error::Error> {
+        if !self.inner.can_read_while_running() && !self.core_halted()? {
+            return Err(Error::MemoryAccessWhileRunningNotSupported(self.id()));
+        }
+
+        self.read_8(address, data)
+    }
+
+    /// Write memory without halting the core.
+    ///
+    /// See [`Core::read_while_running`] for the cores which support this.
+    pub fn write_while_running(&mut self, address: u32, data: &[u8]) -> Result<(), error::Error> {
+        if !self.inner.can_write_while_running() && !self.core_halted()? {
+            return Err(Error::MemoryAccessWhileRunningNotSupported(self.id()));
+        }
+
+        self.write_8(address, data)
+    }
+
+    /// Read `data` from the memory cache, if the cache is enabled and the
+    /// data is in one of the cached regions.
+    ///
+    /// Blocks which are not cached yet are read from the target.
+    fn read_cached(&mut self, address: u32, data: &mut [u8]) -> Result<bool, error::Error> {
+        let inner = &mut self.inner;
+
+        self.state
+            .memory_cache
+            .read(address, data, |address, block| inner.read_8(address, block))
     }
 
     pub fn read_core_reg(
@@ -403,10 +1069,35 @@ impl<'probe> Core<'probe> {
         Ok(self.inner.write_core_reg(address, value)?)
     }
 
+    /// Read the core registers at `addresses`, e.g. all general purpose registers.
+    ///
+    /// This is much faster than reading the registers one by one with [`Core::read_core_reg`],
+    /// because the register transfers are queued and sent to the probe together, where the
+    /// architecture and the probe support it.
+    pub fn read_core_regs(
+        &mut self,
+        addresses: &[CoreRegisterAddress],
+    ) -> Result<Vec<u32>, error::Error> {
+        self.inner.read_core_regs(addresses)
+    }
+
+    /// Write the values of the core `registers`, like [`Core::read_core_regs`] reads them.
+    pub fn write_core_regs(
+        &mut self,
+        registers: &[(CoreRegisterAddress, u32)],
+    ) -> Result<(), error::Error> {
+        self.inner.write_core_regs(registers)
+    }
+
     pub fn get_available_breakpoint_units(&mut self) -> Result<u32, error::Error> {
         self.inner.get_available_breakpoint_units()
     }
 
+    /// The addresses of the hardware breakpoints, by unit, `None` for unused units.
+    pub fn hw_breakpoints(&mut self) -> Result<Vec<Option<u32>>, error::Error> {
+        self.inner.get_hw_breakpoints()
+    }
+
     fn enable_breakpoints(&mut self, state: bool) -> Result<(), error::Error> {
         self.inner.enable_breakpoints(state)
     }
@@ -417,17 +1108,29 @@ impl<'probe> Core<'probe> {
 
     /// Find the index of the next available HW breakpoint comparator.
     fn find_free_breakpoint_comparator_index(&mut self) -> Result<usize, error::Error> {
+        let watchpoints = self.shared_watchpoints()?;
+
         let mut next_available_hw_breakpoint = 0;
         for breakpoint in self.inner.get_hw_breakpoints()? {
-            if breakpoint.is_none() {
+            let used_by_watchpoint =
+                matches!(watchpoints.get(next_available_hw_breakpoint), Some(Some(_)));
+
+            if breakpoint.is_none() && !used_by_watchpoint {
                 return Ok(next_available_hw_breakpoint);
             } else {
                 next_available_hw_breakpoint += 1;
             }
         }
-        Err(error::Error::Other(anyhow!(
-            "No available hardware breakpoints"
-        )))
+        Err(error::Error::NoFreeBreakpoint)
+    }
+
+    /// The watchpoints which occupy units that can also be used for breakpoints.
+    fn shared_watchpoints(&mut self) -> Result<Vec<Option<Watchpoint>>, error::Error> {
+        if self.inner.hw_units_are_shared() {
+            self.inner.get_hw_watchpoints()
+        } else {
+            Ok(Vec::new())
+        }
     }
 
     /// Set a hardware breakpoint
@@ -481,10 +1184,7 @@ impl<'probe> Core<'probe> {
                 self.inner.clear_hw_breakpoint(bp_position)?;
                 Ok(())
             }
-            None => Err(error::Error::Other(anyhow!(
-                "No breakpoint found at address {}",
-                address
-            ))),
+            None => Err(error::Error::BreakpointNotFound(address)),
         }
     }
 
@@ -500,9 +1200,132 @@ impl<'probe> Core<'probe> {
         Ok(())
     }
 
+    /// The number of hardware watchpoints which can be set.
+    ///
+    /// On cores where breakpoints and watchpoints use the same hardware units,
+    /// every watchpoint reduces the number of available breakpoints and vice versa.
+    pub fn get_available_watchpoint_units(&mut self) -> Result<u32, error::Error> {
+        self.inner.get_available_watchpoint_units()
+    }
+
+    /// The hardware watchpoints set on the core.
+    pub fn get_hw_watchpoints(&mut self) -> Result<Vec<Watchpoint>, error::Error> {
+        Ok(self
+            .inner
+            .get_hw_watchpoints()?
+            .into_iter()
+            .flatten()
+            .collect())
+    }
+
+    /// Set a hardware watchpoint, which halts the core when the `length` bytes at `address`
+    /// are accessed.
+    ///
+    /// The supported lengths depend on the core, usually only powers of two with
+    /// an address aligned to the length are supported.
+    pub fn set_hw_watchpoint(
+        &mut self,
+        address: u32,
+        length: u32,
+        kind: WatchpointKind,
+    ) -> Result<(), error::Error> {
+        let watchpoint = Watchpoint {
+            address,
+            length,
+            kind,
+        };
+
+        let watchpoints = self.inner.get_hw_watchpoints()?;
+
+        // Reuse the unit if the watchpoint is already set, to update its kind.
+        let index = match watchpoints
+            .iter()
+            .position(|wp| matches!(wp, Some(wp) if wp.address == address && wp.length == length))
+        {
+            Some(index) => index,
+            None => {
+                let breakpoints = if self.inner.hw_units_are_shared() {
+                    self.inner.get_hw_breakpoints()?
+                } else {
+                    Vec::new()
+                };
+
+                watchpoints
+                    .iter()
+                    .enumerate()
+                    .position(|(index, wp)| {
+                        wp.is_none() && !matches!(breakpoints.get(index), Some(Some(_)))
+                    })
+                    .ok_or(Error::NoFreeWatchpoint)?
+            }
+        };
+
+        log::debug!("Setting HW watchpoint #{}: {:x?}", index, watchpoint);
+
+        self.inner.set_hw_watchpoint(index, watchpoint)
+    }
+
+    /// Clear the hardware watchpoint at `address`.
+    pub fn clear_hw_watchpoint(&mut self, address: u32) -> Result<(), error::Error> {
+        let index = self
+            .inner
+            .get_hw_watchpoints()?
+            .iter()
+            .position(|wp| matches!(wp, Some(wp) if wp.address == address))
+            .ok_or(Error::WatchpointNotFound(address))?;
+
+        self.inner.clear_hw_watchpoint(index)
+    }
+
+    /// Clear all hardware watchpoints.
+    pub fn clear_all_hw_watchpoints(&mut self) -> Result<(), error::Error> {
+        for (index, watchpoint) in self.inner.get_hw_watchpoints()?.iter().enumerate() {
+            if watchpoint.is_some() {
+                self.inner.clear_hw_watchpoint(index)?;
+            }
+        }
+        Ok(())
+    }
+
     pub fn architecture(&self) -> Architecture {
         self.inner.architecture()
     }
+
+    /// Details about why the core halted, like the kind of breakpoint, the address of the
+    /// matched watchpoint and the active exception.
+    ///
+    /// The core has to be halted.
+    pub fn halt_details(&mut self) -> Result<HaltDetails, error::Error> {
+        if !self.core_halted()? {
+            return Err(Error::CoreNotHalted(self.id()));
+        }
+
+        self.inner.halt_details()
+    }
+
+    /// The security state of the core, e.g. for ARMv8-M cores with the TrustZone-M security
+    /// extension. Returns `None` if the core does not implement a security extension.
+    ///
+    /// The core has to be halted.
+    pub fn security_state(&mut self) -> Result<Option<SecurityState>, error::Error> {
+        if !self.core_halted()? {
+            return Err(Error::CoreNotHalted(self.id()));
+        }
+
+        self.inner.security_state()
+    }
+
+    /// Change the security state of the core, which is used when execution is resumed.
+    ///
+    /// The core has to be halted. Switching to [`SecurityState::Secure`] is only possible
+    /// if secure debug is enabled, otherwise [`Error::SecureDebugDisabled`] is returned.
+    pub fn set_security_state(&mut self, state: SecurityState) -> Result<(), error::Error> {
+        if !self.core_halted()? {
+            return Err(Error::CoreNotHalted(self.id()));
+        }
+
+        self.inner.set_security_state(state)
+    }
 }
 
 pub struct CoreList<'probe>(&'probe [CoreType]);
@@ -551,6 +1374,37 @@ impl CoreStatus {
     }
 }
 
+/// The security state of a core with a security extension, e.g. ARM TrustZone-M.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum SecurityState {
+    /// The core is executing in secure state.
+    Secure,
+    /// The core is executing in non-secure state.
+    NonSecure,
+}
+
+/// The kind of memory access which triggers a watchpoint.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum WatchpointKind {
+    /// Trigger on reads.
+    Read,
+    /// Trigger on writes.
+    Write,
+    /// Trigger on reads and writes.
+    ReadWrite,
+}
+
+/// A hardware watchpoint.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Watchpoint {
+    /// The first address of the watched region.
+    pub address: u32,
+    /// The length of the watched region in bytes.
+    pub length: u32,
+    /// The kind of access which triggers the watchpoint.
+    pub kind: WatchpointKind,
+}
+
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum HaltReason {
     /// Multiple reasons for a halt.
@@ -578,3 +1432,52 @@ pub enum HaltReason {
     /// This can happen for example when the core is already halted when we connect.
     Unknown,
 }
+
+/// How interrupts are handled while a core executes a single instruction.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
+pub enum StepInterruptBehavior {
+    /// Pending interrupts are not taken during the step, so that stepping does not
+    /// continue in an interrupt handler. This is the default.
+    ///
+    /// This uses `C_MASKINTS` of `DHCSR` on ARM cores, which does not change `PRIMASK`, and
+    /// `stepie` of `dcsr` on RISC-V harts.
+    #[default]
+    Masked,
+    /// Pending interrupts are taken during the step.
+    Enabled,
+}
+
+/// The kind of breakpoint which halted a core.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum BreakpointCause {
+    /// A breakpoint set in a hardware unit of the core.
+    Hardware,
+    /// A breakpoint instruction in the code, like `BKPT` or `ebreak`.
+    Software,
+}
+
+/// Details about why a core halted, see [`Core::halt_details`].
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct HaltDetails {
+    /// The reason for the halt.
+    pub reason: HaltReason,
+    /// The kind of breakpoint which halted the core, if it halted at a breakpoint.
+    pub breakpoint: Option<BreakpointCause>,
+    /// The address of the watchpoint which matched, if the core halted at a watchpoint.
+    pub watchpoint_address: Option<u32>,
+    /// The number of the exception which was active when the core halted, e.g. the
+    /// `IPSR` of ARM cores. This is `None` in thread mode, and on cores where it is not known.
+    pub exception: Option<u32>,
+}
+
+impl HaltDetails {
+    /// Details which contain only the reason for the halt.
+    pub fn new(reason: HaltReason) -> Self {
+        Self {
+            reason,
+            breakpoint: None,
+            watchpoint_address: None,
+            exception: None,
+        }
+    }
+}
@@ -0,0 +1,270 @@
+//! Polling of the status of a core.
+
+use std::time::Duration;
+use web_time::Instant;
+
+use super::{Core, CoreStatus};
+use crate::{DebugProbeError, Error};
+
+/// The default time between two reads of the status.
+const DEFAULT_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A change of the status of a core, reported by [`StatusPoller::poll`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StatusChange {
+    /// The previously reported status, `None` for the first poll.
+    pub previous: Option<CoreStatus>,
+    /// The new status.
+    pub status: CoreStatus,
+    /// The core was reset since the previous poll, e.g. by a watchdog.
+    ///
+    /// Debugging is enabled again and the breakpoints are set again before this is
+    /// reported. A reset is reported even if the status did not change.
+    pub reset: bool,
+}
+
+/// Watches the status of a core for changes, e.g. halts, resets, or the core entering
+/// a low power mode.
+///
+/// The status is read with [`Core::status`], i.e. from `DHCSR` on ARM cores and from
+/// `dmstatus` on RISC-V cores. [`poll`](Self::poll) reads it at most once per interval,
+/// so it can be called in every iteration of an event loop.
+///
+/// A new status is only reported after it was read a number of times in a row, which
+/// hides short transitions, e.g. a core in a `WFI` loop, which is only awake for a few
+/// cycles for every interrupt. Resets are always reported immediately, because they are
+/// only detected once.
+///
+/// Resets are detected with the same sticky bits as
+/// [`Session::poll_events`](crate::Session::poll_events), so only one of them should be
+/// used for a core.
+#[derive(Debug, Clone)]
+pub struct StatusPoller {
+    interval: Duration,
+    debounce: usize,
+    status: Option<CoreStatus>,
+    /// A new status, and how often it was read in a row.
+    pending: Option<(CoreStatus, usize)>,
+    last_poll: Option<Instant>,
+}
+
+impl Default for StatusPoller {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StatusPoller {
+    /// Create a poller which reads the status every 10 ms, and reports every change.
+    pub fn new() -> Self {
+        Self {
+            interval: DEFAULT_INTERVAL,
+            debounce: 1,
+            status: None,
+            pending: None,
+            last_poll: None,
+        }
+    }
+
+    /// Set the minimum time between two reads of the status.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Only report a new status after it was read `polls` times in a row.
+    ///
+    /// A value of `0` is treated like `1`, which reports every change.
+    pub fn with_debounce(mut self, polls: usize) -> Self {
+        self.debounce = polls.max(1);
+        self
+    }
+
+    /// The last reported status, `None` before the first poll.
+    pub fn status(&self) -> Option<CoreStatus> {
+        self.status
+    }
+
+    /// Read the status of the core, if the interval has passed since the last read.
+    ///
+    /// Returns the change if the status is different from the last reported one, or
+    /// if the core was reset. The first reported change has no previous status.
+    pub fn poll(&mut self, core: &mut Core) -> Result<Option<StatusChange>, Error> {
+        if self.time_to_next_poll() > Duration::ZERO {
+            return Ok(None);
+        }
+
+        self.last_poll = Some(Instant::now());
+
+        // The reset status has to be read before the status, because
+        // reading the status clears the sticky reset bit of ARM cores.
+        let reset = core.reset_detected()?;
+        let status = core.status()?;
+
+        if reset {
+            // The reset may have disabled debugging and cleared the breakpoints.
+            core.reinitialize_after_reset()?;
+        }
+
+        Ok(self.update(status, reset))
+    }
+
+    /// Poll the status until it changes, or `timeout` has passed.
+    ///
+    /// If the timeout expires, a [`DebugProbeError::Timeout`] error is returned.
+    pub fn wait_for_change(
+        &mut self,
+        core: &mut Core,
+        timeout: Duration,
+    ) -> Result<StatusChange, Error> {
+        let start = Instant::now();
+
+        loop {
+            if let Some(change) = self.poll(core)? {
+                return Ok(change);
+            }
+
+            let remaining = timeout
+                .checked_sub(start.elapsed())
+                .ok_or(Error::Probe(DebugProbeError::Timeout))?;
+
+            std::thread::sleep(self.time_to_next_poll().min(remaining));
+        }
+    }
+
+    fn time_to_next_poll(&self) -> Duration {
+        match self.last_poll {
+            Some(last_poll) => self.interval.saturating_sub(last_poll.elapsed()),
+            None => Duration::ZERO,
+        }
+    }
+
+    /// Update the state with a newly read status, and return the change to report.
+    fn update(&mut self, status: CoreStatus, reset: bool) -> Option<StatusChange> {
+        if self.status == Some(status) && !reset {
+            self.pending = None;
+            return None;
+        }
+
+        let count = match self.pending {
+            Some((pending, count)) if pending == status => count + 1,
+            _ => 1,
+        };
+
+        if count < self.debounce && !reset {
+            self.pending = Some((status, count));
+            return None;
+        }
+
+        self.pending = None;
+
+        Some(StatusChange {
+            previous: self.status.replace(status),
+            status,
+            reset,
+        })
+    }
+}
+
+impl<'probe> Core<'probe> {
+    /// Wait until the status of the core changes, or the core is reset, and return the
+    /// new status.
+    ///
+    /// The status is read every 10 ms. If it does not change within `timeout`, a
+    /// [`DebugProbeError::Timeout`] error is returned. Use a [`StatusPoller`] to watch
+    /// the status continuously, or with a different interval.
+    pub fn wait_for_status_change(&mut self, timeout: Duration) -> Result<CoreStatus, Error> {
+        let mut poller = StatusPoller::new();
+
+        // The first poll only determines the current status.
+        poller.poll(self)?;
+
+        Ok(poller.wait_for_change(self, timeout)?.status)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::{StatusChange, StatusPoller};
+    use crate::{Architecture, Core, CoreStatus, DebugProbeError, Error, HaltReason, MockCore};
+
+    const HALTED: CoreStatus = CoreStatus::Halted(HaltReason::Request);
+
+    #[test]
+    fn debounced_status() {
+        let mut poller = StatusPoller::new().with_debounce(2);
+
+        assert_eq!(poller.update(CoreStatus::Running, false), None);
+        assert_eq!(
+            poller.update(CoreStatus::Running, false),
+            Some(StatusChange {
+                previous: None,
+                status: CoreStatus::Running,
+                reset: false
+            })
+        );
+
+        // A short transition is not reported.
+        assert_eq!(poller.update(CoreStatus::Sleeping, false), None);
+        assert_eq!(poller.update(CoreStatus::Running, false), None);
+        assert_eq!(poller.update(CoreStatus::Sleeping, false), None);
+        assert_eq!(poller.status(), Some(CoreStatus::Running));
+
+        assert_eq!(
+            poller.update(CoreStatus::Sleeping, false),
+            Some(StatusChange {
+                previous: Some(CoreStatus::Running),
+                status: CoreStatus::Sleeping,
+                reset: false
+            })
+        );
+
+        // Resets are reported immediately.
+        assert_eq!(
+            poller.update(CoreStatus::Sleeping, true),
+            Some(StatusChange {
+                previous: Some(CoreStatus::Sleeping),
+                status: CoreStatus::Sleeping,
+                reset: true
+            })
+        );
+    }
+
+    #[test]
+    fn poll_interval() {
+        let mut state = Core::create_state(0);
+        let mut core = Core::new(MockCore::new(Architecture::Arm), &mut state);
+        let mut poller = StatusPoller::new().with_interval(Duration::from_secs(60));
+
+        core.halt(Duration::ZERO).unwrap();
+        assert_eq!(poller.poll(&mut core).unwrap().unwrap().status, HALTED);
+
+        // The status is not read again before the interval has passed.
+        core.run().unwrap();
+        assert_eq!(poller.poll(&mut core).unwrap(), None);
+    }
+
+    #[test]
+    fn wait_for_status_change() {
+        let mut state = Core::create_state(0);
+        let mut core = Core::new(MockCore::new(Architecture::Arm), &mut state);
+        let mut poller = StatusPoller::new().with_interval(Duration::ZERO);
+
+        core.run().unwrap();
+        poller.poll(&mut core).unwrap();
+
+        core.halt(Duration::ZERO).unwrap();
+        let change = poller
+            .wait_for_change(&mut core, Duration::from_millis(100))
+            .unwrap();
+        assert_eq!(change.previous, Some(CoreStatus::Running));
+        assert_eq!(change.status, HALTED);
+
+        assert!(matches!(
+            core.wait_for_status_change(Duration::from_millis(20)),
+            Err(Error::Probe(DebugProbeError::Timeout))
+        ));
+    }
+}
@@ -0,0 +1,273 @@
+//! Execution of routines on the target.
+//!
+//! This allows calling functions which are present in the memory of the target,
+//! e.g. a CRC routine in a ROM, or a helper loaded into RAM by the user.
+
+use crate::{Architecture, Core, CoreRegisterAddress, Error, MemoryInterface};
+use std::convert::TryFrom;
+use std::time::Duration;
+
+/// Address of the RISC-V `dcsr` register.
+const RISCV_DCSR: CoreRegisterAddress = CoreRegisterAddress(0x7b0);
+
+/// Address of the ARM `XPSR` register.
+const ARM_XPSR: CoreRegisterAddress = CoreRegisterAddress(0b1_0000);
+
+/// `BKPT #0`, twice, to fill a complete word.
+const ARM_TRAP_INSTRUCTION: u32 = 0xbe00_be00;
+
+/// `EBREAK`
+const RISCV_TRAP_INSTRUCTION: u32 = 0x0010_0073;
+
+/// Timeout for halting a core whose routine did not return in time.
+const ROUTINE_HALT_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Alignment of the stack pointer when calling a routine.
+///
+/// The ARM AAPCS requires an alignment of 8 bytes, the RISC-V calling convention
+/// an alignment of 16 bytes.
+const STACK_ALIGNMENT: u32 = 16;
+
+/// Configure a RISC-V core so that `ebreak` instructions enter debug mode,
+/// which is necessary for soft breakpoints to work.
+///
+/// Returns the previous value of the `dcsr` register.
+pub(crate) fn riscv_enable_ebreak_halt(core: &mut Core) -> Result<u32, Error> {
+    let dcsr = core.read_core_reg(RISCV_DCSR)?;
+
+    // ebreakm, ebreaks, ebreaku
+    core.write_core_reg(RISCV_DCSR, dcsr | (1 << 15) | (1 << 13) | (1 << 12))?;
+
+    Ok(dcsr)
+}
+
+/// The registers for calling a routine with [`Core::start_routine`].
+pub(crate) struct RoutineCall<'a> {
+    /// The address of the routine. For ARM, the Thumb bit is ignored.
+    pub(crate) address: u32,
+    /// The arguments which are passed in the argument registers.
+    pub(crate) args: &'a [u32],
+    /// The address the routine returns to, which has to halt the core, e.g. with a
+    /// breakpoint instruction.
+    pub(crate) return_address: u32,
+    /// The stack pointer of the routine, or `None` to keep the current one.
+    pub(crate) stack_pointer: Option<u32>,
+    /// The static base register `R9` of the routine, or `None` to keep the current one.
+    pub(crate) static_base: Option<u32>,
+}
+
+impl<'probe> Core<'probe> {
+    /// Call the routine at `address` on the target, and return its result.
+    ///
+    /// The core has to be halted. The arguments are passed according to the calling
+    /// convention of the architecture, i.e. in `R0`-`R3` for ARM (AAPCS) and in `a0`-`a7`
+    /// for RISC-V, with any remaining arguments passed on the stack.
+    ///
+    /// The routine runs on the current stack of the core. A breakpoint instruction is
+    /// placed below the current stack pointer and used as the return address, so the core
+    /// halts when the routine returns. The routine has to return within `timeout`,
+    /// otherwise the core is halted and [`Error::RoutineTimeout`] is returned.
+    ///
+    /// All registers are restored after the routine has returned, so that execution of
+    /// the interrupted program can continue afterwards.
+    ///
+    /// For ARM targets, `address` has to be the address of a Thumb function. The
+    /// Thumb bit in the address is ignored.
+    pub fn run_routine(
+        &mut self,
+        address: u32,
+        args: &[u32],
+        timeout: Duration,
+    ) -> Result<u32, Error> {
+        if !self.core_halted()? {
+            return Err(Error::CoreNotHalted(self.id()));
+        }
+
+        // The windowed calling convention of Xtensa needs the return address to encode
+        // the window increment, which is not supported yet.
+        if self.architecture() == Architecture::Xtensa {
+            return Err(Error::ArchitectureRequired(&["ARM", "RISC-V"]));
+        }
+
+        let architecture = self.architecture();
+        let regs = self.registers();
+
+        // Save the state of the core, to be able to restore it afterwards.
+        let mut saved_registers: Vec<CoreRegisterAddress> = regs
+            .registers()
+            .map(|r| r.address)
+            // x0 is hardwired to zero on RISC-V.
+            .filter(|address| *address != CoreRegisterAddress(0x1000))
+            .collect();
+
+        match architecture {
+            Architecture::Arm => saved_registers.push(ARM_XPSR),
+            Architecture::Riscv => {
+                saved_registers.push(regs.program_counter().address);
+                saved_registers.push(RISCV_DCSR);
+            }
+            Architecture::Xtensa => unreachable!("routines are not supported on Xtensa"),
+        }
+
+        let mut saved_values = Vec::with_capacity(saved_registers.len());
+        for register in &saved_registers {
+            saved_values.push((*register, self.read_core_reg(*register)?));
+        }
+
+        let result = self.call_routine(address, args, timeout);
+
+        // Restore the state of the core, even if the call failed.
+        let restored = saved_values
+            .iter()
+            .try_for_each(|(register, value)| self.write_core_reg(*register, *value));
+
+        let result = result?;
+        restored?;
+
+        Ok(result)
+    }
+
+    fn call_routine(
+        &mut self,
+        address: u32,
+        args: &[u32],
+        timeout: Duration,
+    ) -> Result<u32, Error> {
+        let architecture = self.architecture();
+        let regs = self.registers();
+
+        let trap_instruction = match architecture {
+            Architecture::Arm => ARM_TRAP_INSTRUCTION,
+            Architecture::Riscv => RISCV_TRAP_INSTRUCTION,
+            Architecture::Xtensa => unreachable!("routines are not supported on Xtensa"),
+        };
+
+        let register_arg_count = args.len().min(regs.argument_registers.len());
+        let (register_args, stack_args) = args.split_at(register_arg_count);
+
+        // Place the trap instruction below the current stack, and the stack arguments below that.
+        let current_stack_pointer = self.read_core_reg(regs.stack_pointer())?;
+        let stack_length = 4 * (stack_args.len() as u64 + 1);
+
+        // The stack pointer is often 0 after a reset of a RISC-V core, so there is no room below it.
+        let trap_address = current_stack_pointer
+            .checked_sub(4)
+            .map(|address| address & !(STACK_ALIGNMENT - 1));
+        let stack_pointer = trap_address
+            .and_then(|address| address.checked_sub(u32::try_from(4 * stack_args.len()).ok()?))
+            .map(|address| address & !(STACK_ALIGNMENT - 1));
+
+        let (trap_address, stack_pointer) = match (trap_address, stack_pointer) {
+            (Some(trap_address), Some(stack_pointer)) => (trap_address, stack_pointer),
+            _ => {
+                return Err(Error::AddressOutOfRange {
+                    address: current_stack_pointer,
+                    length: stack_length,
+                })
+            }
+        };
+
+        self.write_word_32(trap_address, trap_instruction)?;
+
+        if !stack_args.is_empty() {
+            self.write_32(stack_pointer, stack_args)?;
+        }
+        self.flush()?;
+
+        log::debug!(
+            "Calling routine at {:#010x} with {} arguments, SP = {:#010x}",
+            address,
+            args.len(),
+            stack_pointer
+        );
+
+        self.start_routine(&RoutineCall {
+            address,
+            args: register_args,
+            return_address: trap_address,
+            stack_pointer: Some(stack_pointer),
+            static_base: None,
+        })?;
+
+        let result = self
+            .wait_for_routine(timeout)?
+            .ok_or(Error::RoutineTimeout { address, timeout })?;
+
+        let pc = self.read_core_reg(regs.program_counter())?;
+
+        if pc != trap_address {
+            return Err(Error::RoutineDidNotReturn { address, pc });
+        }
+
+        Ok(result)
+    }
+
+    /// Start the routine described by `call`, without waiting for it to return.
+    ///
+    /// Only the registers of `call` are written, the stack has to be prepared by the
+    /// caller. Use [`Core::wait_for_routine`] to wait for the routine to return.
+    pub(crate) fn start_routine(&mut self, call: &RoutineCall) -> Result<(), Error> {
+        let architecture = self.architecture();
+        let regs = self.registers();
+
+        let routine_address = match architecture {
+            Architecture::Arm => call.address & !1,
+            Architecture::Riscv => call.address,
+            Architecture::Xtensa => return Err(Error::ArchitectureRequired(&["ARM", "RISC-V"])),
+        };
+
+        for (index, value) in call.args.iter().enumerate() {
+            self.write_core_reg(regs.argument_register(index).address, *value)?;
+        }
+
+        if let Some(static_base) = call.static_base {
+            self.write_core_reg(regs.platform_register(9).address, static_base)?;
+        }
+
+        if let Some(stack_pointer) = call.stack_pointer {
+            self.write_core_reg(regs.stack_pointer().address, stack_pointer)?;
+        }
+
+        match architecture {
+            Architecture::Arm => {
+                // Stay in Thumb mode when returning.
+                self.write_core_reg(regs.return_address().address, call.return_address | 1)?;
+
+                // Ensure the Thumb bit is set in the XPSR.
+                let xpsr = self.read_core_reg(ARM_XPSR)?;
+                self.write_core_reg(ARM_XPSR, xpsr | (1 << 24))?;
+            }
+            Architecture::Riscv => {
+                self.write_core_reg(regs.return_address().address, call.return_address)?;
+
+                // Ensure ebreak enters debug mode, this is necessary for soft breakpoints to work.
+                riscv_enable_ebreak_halt(self)?;
+            }
+            Architecture::Xtensa => unreachable!("routines are not supported on Xtensa"),
+        }
+
+        self.write_core_reg(regs.program_counter().address, routine_address)?;
+
+        self.run()
+    }
+
+    /// Wait for a routine started with [`Core::start_routine`] to return, and read its result.
+    ///
+    /// Returns `None` if the routine is still running after `timeout`. The core is halted
+    /// in that case, so that its registers can be restored.
+    pub(crate) fn wait_for_routine(&mut self, timeout: Duration) -> Result<Option<u32>, Error> {
+        if let Err(error) = self.wait_for_core_halted(timeout) {
+            // Distinguish a routine which is still running from a failure to access the core.
+            return match self.core_halted() {
+                Ok(false) => {
+                    self.halt(ROUTINE_HALT_TIMEOUT)?;
+                    Ok(None)
+                }
+                _ => Err(error),
+            };
+        }
+
+        let regs = self.registers();
+        self.read_core_reg(regs.result_register(0)).map(Some)
+    }
+}
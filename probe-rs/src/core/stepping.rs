@@ -0,0 +1,186 @@
+//! Stepping over function calls and running to an address.
+
+use std::time::Duration;
+
+use super::{Core, CoreInformation, CoreRegisterAddress};
+use crate::{Architecture, Error, MemoryInterface};
+
+impl<'probe> Core<'probe> {
+    /// Execute the instruction at the program counter, and halt at the next instruction.
+    ///
+    /// If the instruction is a function call, like `BL` and `BLX` on ARM or `JAL` and `JALR`
+    /// on RISC-V, a temporary breakpoint is set at the return address and the core runs
+    /// until the function returns. Otherwise this is the same as [`Core::step`].
+    ///
+    /// If the core halts somewhere else first, e.g. at a breakpoint in the called function,
+    /// it stays halted there. If the function does not return within `timeout`, the core is
+    /// halted and [`DebugProbeError::Timeout`](crate::DebugProbeError::Timeout) is returned.
+    pub fn step_over(&mut self, timeout: Duration) -> Result<CoreInformation, Error> {
+        let pc_register = CoreRegisterAddress::from(self.registers().program_counter());
+        let pc: u32 = self.read_core_reg(pc_register)?;
+
+        let mut code = [0u8; 4];
+        self.read_8(pc, &mut code)?;
+
+        let size = match call_size(self.architecture(), &code) {
+            Some(size) => size,
+            None => return self.step(),
+        };
+        let return_address = pc + size;
+
+        // The call is stepped first, in case there is a breakpoint on it.
+        let information = self.step()?;
+
+        if information.pc == return_address {
+            return Ok(information);
+        }
+
+        self.run_to(return_address, timeout)
+    }
+
+    /// Run until the core reaches `address`, e.g. for "run to cursor" in a debugger.
+    ///
+    /// A temporary hardware breakpoint is set at `address`, and removed again when the
+    /// core halts. A breakpoint which is already set at `address` is kept. If the core
+    /// is halted at `address`, it is stepped first, so that it runs until it reaches
+    /// `address` again.
+    ///
+    /// If the core halts somewhere else first, e.g. at another breakpoint, it stays halted
+    /// there. If the core does not halt within `timeout`, it is halted and
+    /// [`DebugProbeError::Timeout`](crate::DebugProbeError::Timeout) is returned.
+    pub fn run_to(&mut self, address: u32, timeout: Duration) -> Result<CoreInformation, Error> {
+        let pc_register = CoreRegisterAddress::from(self.registers().program_counter());
+
+        if self.core_halted()? {
+            let pc: u32 = self.read_core_reg(pc_register)?;
+
+            if pc == address {
+                let information = self.step()?;
+
+                // The instruction jumped to itself.
+                if information.pc == address {
+                    return Ok(information);
+                }
+            }
+        }
+
+        let temporary = !self.inner.get_hw_breakpoints()?.contains(&Some(address));
+
+        self.set_hw_breakpoint(address)?;
+
+        let result = self.run().and_then(|_| self.wait_for_core_halted(timeout));
+        if result.is_err() && !self.core_halted()? {
+            self.halt(timeout)?;
+        }
+
+        if temporary {
+            self.clear_hw_breakpoint(address)?;
+        }
+        result?;
+
+        Ok(CoreInformation {
+            pc: self.read_core_reg(pc_register)?,
+        })
+    }
+}
+
+/// Returns the size of the instruction at the start of `code`, if it is a function call.
+fn call_size(architecture: Architecture, code: &[u8; 4]) -> Option<u32> {
+    let first = u16::from_le_bytes([code[0], code[1]]);
+    let second = u16::from_le_bytes([code[2], code[3]]);
+
+    match architecture {
+        Architecture::Arm => {
+            if first & 0xff87 == 0x4780 {
+                // BLX <Rm>
+                Some(2)
+            } else if first & 0xf800 == 0xf000 && second & 0xd000 == 0xd000 {
+                // BL <label>
+                Some(4)
+            } else {
+                None
+            }
+        }
+        Architecture::Riscv => {
+            if first & 0b11 == 0b11 {
+                let instruction = u32::from_le_bytes(*code);
+                let opcode = instruction & 0x7f;
+                let rd = (instruction >> 7) & 0x1f;
+
+                // JAL and JALR which store the return address.
+                ((opcode == 0x6f || opcode == 0x67) && rd != 0).then_some(4)
+            } else {
+                let is_jal = first & 0xe003 == 0x2001;
+                let is_jalr = first & 0xf07f == 0x9002 && (first >> 7) & 0x1f != 0;
+
+                // C.JAL (RV32 only) and C.JALR always store the return address in `ra`.
+                (is_jal || is_jalr).then_some(2)
+            }
+        }
+        Architecture::Xtensa => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::call_size;
+    use crate::Architecture;
+
+    #[test]
+    fn arm_calls() {
+        // bl #0x1000000a
+        assert_eq!(
+            call_size(Architecture::Arm, &[0x00, 0xf0, 0x02, 0xf8]),
+            Some(4)
+        );
+        // blx r3
+        assert_eq!(
+            call_size(Architecture::Arm, &[0x98, 0x47, 0x00, 0x00]),
+            Some(2)
+        );
+        // bx lr
+        assert_eq!(
+            call_size(Architecture::Arm, &[0x70, 0x47, 0x00, 0x00]),
+            None
+        );
+        // b.w
+        assert_eq!(
+            call_size(Architecture::Arm, &[0x00, 0xf0, 0x02, 0xb8]),
+            None
+        );
+    }
+
+    #[test]
+    fn riscv_calls() {
+        // jal ra, 16
+        assert_eq!(
+            call_size(Architecture::Riscv, &[0xef, 0x00, 0x00, 0x01]),
+            Some(4)
+        );
+        // jalr ra, 0(a5)
+        assert_eq!(
+            call_size(Architecture::Riscv, &[0xe7, 0x80, 0x07, 0x00]),
+            Some(4)
+        );
+        // j 16, which does not return
+        assert_eq!(
+            call_size(Architecture::Riscv, &[0x6f, 0x00, 0x00, 0x01]),
+            None
+        );
+        // c.jalr a5
+        assert_eq!(
+            call_size(Architecture::Riscv, &[0x82, 0x97, 0x00, 0x00]),
+            Some(2)
+        );
+        // c.jr ra
+        assert_eq!(
+            call_size(Architecture::Riscv, &[0x82, 0x80, 0x00, 0x00]),
+            None
+        );
+        // c.jal 16
+        assert_eq!(
+            call_size(Architecture::Riscv, &[0x01, 0x20, 0x00, 0x00]),
+            Some(2)
+        );
+    }
+}
@@ -0,0 +1,206 @@
+//! Breakpoints at source locations and functions.
+
+use std::path::Path;
+
+use super::{DebugError, DebugInfo};
+use crate::Core;
+
+impl<'probe> Core<'probe> {
+    /// Set hardware breakpoints at a source location, given as `file:line`, e.g. `main.rs:42`.
+    ///
+    /// The file name is matched against the end of the source paths of the debug information.
+    /// If the code of the line is contained at several addresses, for example because it was
+    /// inlined, a breakpoint is set at each of them. The addresses of the breakpoints are returned.
+    ///
+    /// If not all breakpoints can be set, the ones which were already set are cleared again.
+    pub fn set_breakpoint_at(
+        &mut self,
+        debug_info: &DebugInfo,
+        location: &str,
+    ) -> Result<Vec<u32>, DebugError> {
+        let (file, line) = parse_source_location(location)
+            .ok_or_else(|| DebugError::InvalidSourceLocation(location.to_owned()))?;
+
+        let addresses = debug_info.get_breakpoint_locations(Path::new(file), line)?;
+
+        self.set_hw_breakpoints(&addresses, location)
+    }
+
+    /// Set hardware breakpoints at the start of the function `name`, e.g. `App::init`.
+    ///
+    /// The name is matched against the end of the path of the functions, and a breakpoint
+    /// is set at every place the function was inlined at, in addition to the function itself.
+    /// The addresses of the breakpoints are returned.
+    ///
+    /// If not all breakpoints can be set, the ones which were already set are cleared again.
+    pub fn set_breakpoint_at_function(
+        &mut self,
+        debug_info: &DebugInfo,
+        name: &str,
+    ) -> Result<Vec<u32>, DebugError> {
+        let addresses = debug_info.get_function_addresses(name)?;
+
+        self.set_hw_breakpoints(&addresses, name)
+    }
+
+    fn set_hw_breakpoints(
+        &mut self,
+        addresses: &[u64],
+        location: &str,
+    ) -> Result<Vec<u32>, DebugError> {
+        if addresses.is_empty() {
+            return Err(DebugError::BreakpointLocationNotFound(location.to_owned()));
+        }
+
+        let mut set = Vec::with_capacity(addresses.len());
+
+        for &address in addresses {
+            let address = address as u32;
+
+            if let Err(error) = self.set_hw_breakpoint(address) {
+                for &address in &set {
+                    if let Err(clear_error) = self.clear_hw_breakpoint(address) {
+                        log::warn!(
+                            "Failed to clear breakpoint at {:#010x}: {}",
+                            address,
+                            clear_error
+                        );
+                    }
+                }
+
+                return Err(error.into());
+            }
+
+            set.push(address);
+        }
+
+        Ok(set)
+    }
+}
+
+/// Split a source location of the form `file:line`.
+fn parse_source_location(location: &str) -> Option<(&str, u64)> {
+    let (file, line) = location.rsplit_once(':')?;
+
+    if file.is_empty() {
+        return None;
+    }
+
+    Some((file, line.trim().parse().ok()?))
+}
+
+/// Check if the function path `path` ends with `name`, comparing whole path segments.
+pub(super) fn path_matches(path: &str, name: &str) -> bool {
+    path == name
+        || path
+            .strip_suffix(name)
+            .map(|prefix| prefix.ends_with("::"))
+            .unwrap_or(false)
+}
+
+/// Demangle a Rust symbol name in the legacy mangling scheme, e.g.
+/// `_ZN8my_crate3App4init17h0123456789abcdefE` to `my_crate::App::init`.
+///
+/// Returns `None` if the name is not mangled in this scheme.
+pub(super) fn demangle(mangled: &str) -> Option<String> {
+    let mut rest = mangled
+        .strip_prefix("_ZN")
+        .or_else(|| mangled.strip_prefix("__ZN"))?;
+
+    let mut segments = Vec::new();
+
+    while !rest.starts_with('E') {
+        let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+        let length: usize = rest[..digits].parse().ok()?;
+        let segment = rest.get(digits..digits + length)?;
+        rest = &rest[digits + length..];
+
+        segments.push(segment);
+    }
+
+    // Drop the hash, which is always the last segment.
+    if let Some(hash) = segments.last() {
+        if hash.len() == 17 && hash.starts_with('h') {
+            segments.pop();
+        }
+    }
+
+    if segments.is_empty() {
+        return None;
+    }
+
+    let segments: Vec<String> = segments.iter().map(|s| unescape(s)).collect();
+
+    Some(segments.join("::"))
+}
+
+/// Replace the escape sequences of the legacy mangling scheme.
+fn unescape(segment: &str) -> String {
+    const ESCAPES: &[(&str, &str)] = &[
+        ("$SP$", "@"),
+        ("$BP$", "*"),
+        ("$RF$", "&"),
+        ("$LT$", "<"),
+        ("$GT$", ">"),
+        ("$LP$", "("),
+        ("$RP$", ")"),
+        ("$C$", ","),
+        ("$u20$", " "),
+        ("$u27$", "'"),
+        ("$u5b$", "["),
+        ("$u5d$", "]"),
+        ("$u7b$", "{"),
+        ("$u7d$", "}"),
+        ("$u7e$", "~"),
+    ];
+
+    let mut segment = segment
+        .strip_prefix("_$")
+        .map_or(segment.to_owned(), |s| format!("${}", s));
+
+    for (escaped, character) in ESCAPES {
+        segment = segment.replace(escaped, character);
+    }
+
+    segment.replace("..", "::")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{demangle, parse_source_location, path_matches};
+
+    #[test]
+    fn demangle_legacy_names() {
+        assert_eq!(
+            demangle("_ZN8my_crate3App4init17h0123456789abcdefE").as_deref(),
+            Some("my_crate::App::init")
+        );
+        assert_eq!(
+            demangle("_ZN66_$LT$my_crate..Led$u20$as$u20$embedded_hal..digital..OutputPin$GT$7set_low17h0123456789abcdefE")
+                .as_deref(),
+            Some("<my_crate::Led as embedded_hal::digital::OutputPin>::set_low")
+        );
+        assert_eq!(demangle("main"), None);
+        assert_eq!(demangle("_ZN3foo"), None);
+    }
+
+    #[test]
+    fn match_function_paths() {
+        assert!(path_matches("my_crate::App::init", "App::init"));
+        assert!(path_matches("my_crate::App::init", "my_crate::App::init"));
+        assert!(!path_matches("my_crate::MyApp::init", "App::init"));
+        assert!(!path_matches("my_crate::App::init", "App"));
+    }
+
+    #[test]
+    fn parse_source_locations() {
+        assert_eq!(parse_source_location("main.rs:42"), Some(("main.rs", 42)));
+        assert_eq!(
+            parse_source_location("C:\\src\\main.rs:7"),
+            Some(("C:\\src\\main.rs", 7))
+        );
+        assert_eq!(parse_source_location("main.rs"), None);
+        assert_eq!(parse_source_location(":42"), None);
+        assert_eq!(parse_source_location("main.rs:x"), None);
+    }
+}
@@ -3,10 +3,13 @@
 //! The `debug` module contains various debug functionality, which can be
 //! used to implement a debugger based on `probe-rs`.
 
+mod breakpoint;
+mod symbols;
 mod variable;
 
 use crate::{core::Core, MemoryInterface};
 use num_traits::Zero;
+pub use symbols::{Symbol, SymbolTable};
 pub use variable::{Variable, VariableInclusion, VariableKind, VariantRole};
 
 // use std::{borrow, intrinsics::variant_count, io, path::{Path, PathBuf}, rc::Rc, str::{from_utf8, Utf8Error}};
@@ -40,6 +43,10 @@ pub enum DebugError {
     CharConversion(#[from] std::char::CharTryFromError),
     #[error(transparent)]
     IntConversion(#[from] std::num::TryFromIntError),
+    #[error("Invalid source location '{0}', expected 'file:line'")]
+    InvalidSourceLocation(String),
+    #[error("No code found for breakpoint location '{0}'")]
+    BreakpointLocationNotFound(String),
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -855,6 +862,169 @@ impl DebugInfo {
         }
     }
 
+    /// Find all addresses where a breakpoint should be set to stop at `line` of the
+    /// source file `path`.
+    ///
+    /// `path` is matched against the end of the paths in the debug information, so
+    /// `main.rs` matches `/project/src/main.rs`. A line can have several locations, for
+    /// example if its code was inlined or duplicated by the compiler. For each of them, the
+    /// first statement of a consecutive sequence of instructions of the line is returned.
+    pub fn get_breakpoint_locations(&self, path: &Path, line: u64) -> Result<Vec<u64>, DebugError> {
+        let mut unit_iter = self.dwarf.units();
+
+        let mut locations = Vec::new();
+
+        while let Some(unit_header) = unit_iter.next()? {
+            let unit = self.dwarf.unit(unit_header)?;
+
+            let line_program = match unit.line_program {
+                Some(ref line_program) => line_program.clone(),
+                None => continue,
+            };
+
+            let mut rows = line_program.rows();
+
+            // Whether the previous row of the sequence belongs to the line.
+            let mut previous_matched = false;
+
+            while let Some((header, row)) = rows.next_row()? {
+                if row.end_sequence() {
+                    previous_matched = false;
+                    continue;
+                }
+
+                let matches = row.line().map(|l| l.get()) == Some(line)
+                    && row
+                        .file(header)
+                        .and_then(|file_entry| self.get_path(&unit, header, file_entry))
+                        .map(|p| p.ends_with(path))
+                        .unwrap_or(false);
+
+                if matches && !previous_matched && row.is_stmt() {
+                    locations.push(row.address());
+                }
+
+                // Rows which are not statements do not start a new sequence of the line.
+                previous_matched = matches && (previous_matched || row.is_stmt());
+            }
+        }
+
+        locations.sort_unstable();
+        locations.dedup();
+
+        log::debug!(
+            "Found {} breakpoint locations for {}:{}",
+            locations.len(),
+            path.display(),
+            line
+        );
+
+        Ok(locations)
+    }
+
+    /// Find the entry addresses of all instances of the function `name`, i.e. the address
+    /// of the function itself and the start of every place it was inlined at.
+    ///
+    /// `name` is matched against the end of the path of the function, so `App::init` matches
+    /// `my_crate::App::init`.
+    pub fn get_function_addresses(&self, name: &str) -> Result<Vec<u64>, DebugError> {
+        let mut unit_iter = self.dwarf.units();
+
+        let mut addresses = Vec::new();
+
+        while let Some(unit_header) = unit_iter.next()? {
+            let unit = self.dwarf.unit(unit_header)?;
+
+            let mut entries = unit.entries();
+
+            while let Some((_, entry)) = entries.next_dfs()? {
+                if entry.tag() != gimli::DW_TAG_subprogram
+                    && entry.tag() != gimli::DW_TAG_inlined_subroutine
+                {
+                    continue;
+                }
+
+                // Abstract instances of inlined functions do not have any code.
+                let mut ranges = self.dwarf.die_ranges(&unit, entry)?;
+                let mut entry_address = None;
+                while let Some(range) = ranges.next()? {
+                    entry_address =
+                        Some(entry_address.map_or(range.begin, |a: u64| a.min(range.begin)));
+                }
+
+                // Functions removed by the linker have their address set to zero.
+                let entry_address = match entry_address {
+                    Some(address) if address != 0 => address,
+                    _ => continue,
+                };
+
+                let path = match self.function_path(&unit, entry, 0)? {
+                    Some(path) => path,
+                    None => continue,
+                };
+
+                if breakpoint::path_matches(&path, name) {
+                    addresses.push(entry_address);
+                }
+            }
+        }
+
+        addresses.sort_unstable();
+        addresses.dedup();
+
+        log::debug!(
+            "Found {} breakpoint locations for function {}",
+            addresses.len(),
+            name
+        );
+
+        Ok(addresses)
+    }
+
+    /// The path of a function, e.g. `my_crate::App::init`.
+    ///
+    /// The path is taken from the linkage name if possible, and otherwise from the name of the
+    /// function. Concrete instances of a function are resolved to the abstract instance or
+    /// declaration they refer to.
+    fn function_path(
+        &self,
+        unit: &gimli::Unit<DwarfReader>,
+        entry: &DebuggingInformationEntry<DwarfReader>,
+        depth: usize,
+    ) -> Result<Option<String>, DebugError> {
+        for attribute in &[gimli::DW_AT_linkage_name, gimli::DW_AT_MIPS_linkage_name] {
+            if let Some(value) = entry.attr_value(*attribute)? {
+                let linkage_name = self.dwarf.attr_string(unit, value)?;
+                let linkage_name = String::from_utf8_lossy(&linkage_name);
+
+                return Ok(Some(
+                    breakpoint::demangle(&linkage_name).unwrap_or_else(|| linkage_name.to_string()),
+                ));
+            }
+        }
+
+        if let Some(value) = entry.attr_value(gimli::DW_AT_name)? {
+            let name = self.dwarf.attr_string(unit, value)?;
+
+            return Ok(Some(String::from_utf8_lossy(&name).to_string()));
+        }
+
+        // Limit the recursion, in case of a malformed reference cycle.
+        if depth > 4 {
+            return Ok(None);
+        }
+
+        for attribute in &[DW_AT_abstract_origin, gimli::DW_AT_specification] {
+            if let Some(gimli::AttributeValue::UnitRef(offset)) = entry.attr_value(*attribute)? {
+                let origin = unit.entry(offset)?;
+
+                return self.function_path(unit, &origin, depth + 1);
+            }
+        }
+
+        Ok(None)
+    }
+
     /// Get the absolute path for an entry in a line program header
     fn get_path(
         &self,
@@ -0,0 +1,218 @@
+//! Lookup of the symbols of an ELF file.
+
+use std::path::Path;
+
+use object::{Object, ObjectSymbol, SymbolKind};
+
+use super::breakpoint::{demangle, path_matches};
+use super::DebugError;
+use crate::Session;
+
+/// A symbol of an ELF file, i.e. a function or a variable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    /// The (mangled) name of the symbol.
+    pub name: String,
+    /// The address of the symbol. For Thumb functions, the Thumb bit is cleared.
+    pub address: u32,
+    /// The size of the symbol in bytes, which is zero if it is unknown.
+    pub size: u32,
+}
+
+impl Symbol {
+    /// Check if `address` is part of the symbol.
+    ///
+    /// Symbols with an unknown size only contain their own address.
+    fn contains(&self, address: u32) -> bool {
+        address >= self.address && address - self.address < self.size.max(1)
+    }
+}
+
+/// The function and data symbols of an ELF file, which allows looking up the
+/// address of a symbol by its name, and the symbol at an address.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+    /// All symbols, sorted by their address.
+    symbols: Vec<Symbol>,
+}
+
+impl SymbolTable {
+    /// Read the symbols of the ELF file at `path`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, DebugError> {
+        let data = std::fs::read(path)?;
+
+        Self::from_raw(&data)
+    }
+
+    /// Read the symbols of an ELF file from a buffer.
+    pub fn from_raw(data: &[u8]) -> Result<Self, DebugError> {
+        let object = object::File::parse(data)?;
+
+        let thumb = object.architecture() == object::Architecture::Arm;
+
+        let symbols: Vec<Symbol> = object
+            .symbols()
+            .filter(|symbol| {
+                symbol.is_definition()
+                    && matches!(symbol.kind(), SymbolKind::Text | SymbolKind::Data)
+            })
+            .filter_map(|symbol| {
+                let name = symbol.name().ok().filter(|name| !name.is_empty())?;
+
+                let mut address = symbol.address() as u32;
+                if thumb && symbol.kind() == SymbolKind::Text {
+                    address &= !1;
+                }
+
+                Some(Symbol {
+                    name: name.to_owned(),
+                    address,
+                    size: symbol.size() as u32,
+                })
+            })
+            .collect();
+
+        Ok(Self::new(symbols))
+    }
+
+    /// Create a symbol table from a list of symbols.
+    pub fn new(mut symbols: Vec<Symbol>) -> Self {
+        symbols.sort_by_key(|symbol| symbol.address);
+
+        Self { symbols }
+    }
+
+    /// All symbols, sorted by their address.
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    /// Find the symbol with the given name.
+    pub fn symbol(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.iter().find(|symbol| symbol.name == name)
+    }
+
+    /// Find the symbol with the given name, or with a demangled path which ends with it.
+    ///
+    /// This allows to find the symbol of e.g. the static variable `COUNTER` of a Rust
+    /// program, which has a mangled name like `_ZN8my_crate7COUNTER17h0123456789abcdefE`.
+    pub fn symbol_by_path(&self, path: &str) -> Option<&Symbol> {
+        self.symbol(path).or_else(|| {
+            self.symbols.iter().find(|symbol| {
+                demangle(&symbol.name)
+                    .map(|demangled| path_matches(&demangled, path))
+                    .unwrap_or(false)
+            })
+        })
+    }
+
+    /// Find the address of the symbol with the given name.
+    pub fn symbol_address(&self, name: &str) -> Option<u32> {
+        self.symbol(name).map(|symbol| symbol.address)
+    }
+
+    /// Find the symbol which contains `address`, and return it together
+    /// with the offset of the address into the symbol.
+    ///
+    /// If several symbols contain the address, the one which starts last is returned.
+    pub fn symbolize(&self, address: u32) -> Option<(&Symbol, u32)> {
+        // Index of the first symbol after the address.
+        let end = self
+            .symbols
+            .partition_point(|symbol| symbol.address <= address);
+
+        self.symbols[..end]
+            .iter()
+            .rev()
+            .find(|symbol| symbol.contains(address))
+            .map(|symbol| (symbol, address - symbol.address))
+    }
+}
+
+impl Session {
+    /// Attach the symbols of the ELF file at `path` to the session, which replaces any
+    /// previously attached symbols.
+    ///
+    /// The symbols are used by [`Session::symbol_address`] and [`Session::symbolize`].
+    pub fn load_symbols<P: AsRef<Path>>(&mut self, path: P) -> Result<(), DebugError> {
+        self.set_symbols(SymbolTable::from_file(path)?);
+
+        Ok(())
+    }
+
+    /// Attach a symbol table to the session, which replaces any previously attached symbols.
+    pub fn set_symbols(&mut self, symbols: SymbolTable) {
+        self.symbols = Some(symbols);
+    }
+
+    /// The symbols attached to the session, if any.
+    pub fn symbols(&self) -> Option<&SymbolTable> {
+        self.symbols.as_ref()
+    }
+
+    /// Find the address of the symbol with the given name, e.g. `_SEGGER_RTT`.
+    ///
+    /// Returns `None` if no symbols are attached to the session, or the symbol does not exist.
+    pub fn symbol_address(&self, name: &str) -> Option<u32> {
+        self.symbols()?.symbol_address(name)
+    }
+
+    /// Find the symbol which contains `address`, together with the offset of the address into it.
+    ///
+    /// Returns `None` if no symbols are attached to the session, or no symbol contains the address.
+    pub fn symbolize(&self, address: u32) -> Option<(&Symbol, u32)> {
+        self.symbols()?.symbolize(address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Symbol, SymbolTable};
+
+    fn symbol(name: &str, address: u32, size: u32) -> Symbol {
+        Symbol {
+            name: name.to_owned(),
+            address,
+            size,
+        }
+    }
+
+    #[test]
+    fn lookup_symbols() {
+        let table = SymbolTable::new(vec![
+            symbol("main", 0x100, 0x40),
+            symbol("_SEGGER_RTT", 0x2000_0000, 0x78),
+            symbol("reset", 0x80, 0x20),
+            symbol("label", 0x120, 0),
+            symbol("_ZN8my_crate7COUNTER17h0123456789abcdefE", 0x2000_0100, 4),
+        ]);
+
+        assert_eq!(table.symbol_address("_SEGGER_RTT"), Some(0x2000_0000));
+        assert_eq!(table.symbol_address("missing"), None);
+
+        assert_eq!(
+            table.symbol_by_path("COUNTER"),
+            Some(&symbol(
+                "_ZN8my_crate7COUNTER17h0123456789abcdefE",
+                0x2000_0100,
+                4
+            ))
+        );
+
+        assert_eq!(
+            table.symbolize(0x84),
+            Some((&symbol("reset", 0x80, 0x20), 4))
+        );
+        assert_eq!(
+            table.symbolize(0x13c),
+            Some((&symbol("main", 0x100, 0x40), 0x3c))
+        );
+        // The innermost symbol is preferred.
+        assert_eq!(
+            table.symbolize(0x120),
+            Some((&symbol("label", 0x120, 0), 0))
+        );
+        assert_eq!(table.symbolize(0xa0), None);
+        assert_eq!(table.symbolize(0x140), None);
+    }
+}
@@ -1,10 +1,13 @@
+use crate::architecture::arm::{ap::AccessPortError, ApAddress};
+use crate::config::{RegistryError, ResetKind};
+use crate::flashing::FlashError;
 use crate::DebugProbeError;
-use crate::{architecture::arm::ap::AccessPortError, config::RegistryError};
+use std::time::Duration;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("An error with the usage of the probe occured")]
-    Probe(#[from] DebugProbeError),
+    Probe(#[source] DebugProbeError),
     #[error("A core architecture specific error occured")]
     ArchitectureSpecific(#[from] Box<dyn std::error::Error + Send + Sync>),
     #[error("Probe could not be opened: {0}")]
@@ -15,6 +18,63 @@ pub enum Error {
     ChipNotFound(#[from] RegistryError),
     #[error("This feature requires one of the following architectures: {0:?}")]
     ArchitectureRequired(&'static [&'static str]),
+    #[error("Core {0} has to be halted for this operation")]
+    CoreNotHalted(usize),
+    #[error("No free hardware breakpoint is available")]
+    NoFreeBreakpoint,
+    #[error("No hardware breakpoint is set at address {0:#010x}")]
+    BreakpointNotFound(u32),
+    #[error("A hardware breakpoint cannot be set at address {0:#010x}")]
+    UnsupportedBreakpointAddress(u32),
+    #[error("The breakpoint comparator value {0:#010x} is not supported")]
+    UnsupportedBreakpointComparator(u32),
+    #[error("The FPB revision {0} is not supported, hardware breakpoints are not available")]
+    UnsupportedFpbRevision(u32),
+    #[error("The data length {0} is not a multiple of the access size")]
+    InvalidDataLength(usize),
+    #[error("The region at {address:#010x} with a length of {length} bytes exceeds the 32 bit address space")]
+    AddressOutOfRange { address: u32, length: u64 },
+    #[error("The routine at {address:#010x} halted at {pc:#010x} instead of returning")]
+    RoutineDidNotReturn { address: u32, pc: u32 },
+    #[error("The routine at {address:#010x} did not return within {timeout:?}")]
+    RoutineTimeout { address: u32, timeout: Duration },
+    #[error("The access port {0:x?} is not a memory access port")]
+    NotAMemoryAp(ApAddress),
+    #[error("The access port {0:x?} has no debug base address")]
+    MissingDebugBaseAddress(ApAddress),
+    #[error("An error occured while programming the flash")]
+    Flash(#[source] Box<FlashError>),
+    #[error("A reset of kind {0:?} is not supported by this core")]
+    UnsupportedResetKind(ResetKind),
+    #[error("The core does not implement the security extension")]
+    NoSecurityExtension,
+    #[error("Secure debug is disabled, the core can only be debugged in non-secure state")]
+    SecureDebugDisabled,
+    #[error("No free hardware watchpoint is available")]
+    NoFreeWatchpoint,
+    #[error("No hardware watchpoint is set at address {0:#010x}")]
+    WatchpointNotFound(u32),
+    #[error("A watchpoint for {length} bytes at address {address:#010x} is not supported")]
+    UnsupportedWatchpoint { address: u32, length: u32 },
+    #[error("The memory of core {0} can not be accessed while it is running")]
+    MemoryAccessWhileRunningNotSupported(usize),
+    #[error("The operation was cancelled")]
+    Cancelled,
+    #[error("The probe is in use by another process{}", pid.map(|pid| format!(" with PID {}", pid)).unwrap_or_default())]
+    ProbeInUse { pid: Option<u32> },
+    #[error("The worker thread of the session has stopped")]
+    SessionWorkerStopped,
+    #[error("Trigger unit {0} does not exist")]
+    TriggerNotFound(usize),
+    #[error("Register {0:#x} does not exist")]
+    UnknownRegister(u16),
+    #[error("Disassembling failed{}: {message}", address.map(|address| format!(" at {:#010x}", address)).unwrap_or_default())]
+    Disassembly {
+        address: Option<u32>,
+        message: String,
+    },
+    #[error("The operation requires the permission {0}, which was not granted")]
+    MissingPermissions(String),
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -23,6 +83,65 @@ impl Error {
     pub fn architecture_specific(e: impl std::error::Error + Send + Sync + 'static) -> Self {
         Self::ArchitectureSpecific(Box::new(e))
     }
+
+    /// A numeric code identifying the kind of the error.
+    ///
+    /// The codes are stable, and are not reused when variants are removed, so they can
+    /// be used to identify errors across the boundaries of the library, e.g. in tools
+    /// communicating with probe-rs over a protocol.
+    pub fn code(&self) -> u32 {
+        match self {
+            Error::Probe(_) => 1,
+            Error::ArchitectureSpecific(_) => 2,
+            Error::UnableToOpenProbe(_) => 3,
+            Error::CoreNotFound(_) => 4,
+            Error::ChipNotFound(_) => 5,
+            Error::ArchitectureRequired(_) => 6,
+            Error::CoreNotHalted(_) => 7,
+            Error::NoFreeBreakpoint => 8,
+            Error::BreakpointNotFound(_) => 9,
+            Error::UnsupportedBreakpointAddress(_) => 10,
+            Error::UnsupportedBreakpointComparator(_) => 11,
+            Error::UnsupportedFpbRevision(_) => 12,
+            Error::InvalidDataLength(_) => 13,
+            Error::AddressOutOfRange { .. } => 14,
+            Error::RoutineDidNotReturn { .. } => 15,
+            Error::NotAMemoryAp(_) => 16,
+            Error::MissingDebugBaseAddress(_) => 17,
+            Error::Flash(_) => 18,
+            Error::UnsupportedResetKind(_) => 19,
+            Error::NoSecurityExtension => 20,
+            Error::SecureDebugDisabled => 21,
+            Error::NoFreeWatchpoint => 22,
+            Error::WatchpointNotFound(_) => 23,
+            Error::UnsupportedWatchpoint { .. } => 24,
+            Error::MemoryAccessWhileRunningNotSupported(_) => 25,
+            Error::Cancelled => 26,
+            Error::ProbeInUse { .. } => 27,
+            Error::RoutineTimeout { .. } => 28,
+            Error::SessionWorkerStopped => 29,
+            Error::TriggerNotFound(_) => 30,
+            Error::UnknownRegister(_) => 31,
+            Error::Disassembly { .. } => 32,
+            Error::MissingPermissions(_) => 33,
+            Error::Other(_) => 0xffff,
+        }
+    }
+}
+
+impl From<DebugProbeError> for Error {
+    fn from(err: DebugProbeError) -> Self {
+        match err {
+            DebugProbeError::ProbeInUse { pid } => Error::ProbeInUse { pid },
+            err => Error::Probe(err),
+        }
+    }
+}
+
+impl From<FlashError> for Error {
+    fn from(err: FlashError) -> Self {
+        Error::Flash(Box::new(err))
+    }
 }
 
 impl From<AccessPortError> for Error {
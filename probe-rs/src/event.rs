@@ -0,0 +1,287 @@
+#![warn(missing_docs)]
+
+//! Events emitted by a [`Session`].
+//!
+//! Observers registered with [`Session::add_observer`] are called for every event,
+//! which allows e.g. a GUI debugger to react to changes of the target without polling
+//! every part of the API separately.
+//!
+//! Changes of the core status, including a core entering or leaving a low power mode,
+//! are detected by [`Session::poll_events`], which has to be called periodically.
+//! Flash progress is reported when the [`FlashProgress`] returned
+//! by [`Session::flash_progress`] is used for flashing. Other components, like an RTT
+//! reader, can publish their events using [`Session::emit`].
+
+use crate::flashing::{FlashProgress, ProgressEvent};
+use crate::{CoreStatus, Error, HaltReason, Session};
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// An event emitted by a [`Session`].
+#[derive(Debug)]
+pub enum SessionEvent {
+    /// A core has halted.
+    CoreHalted {
+        /// The id of the core.
+        core: usize,
+        /// The reason for the halt.
+        reason: HaltReason,
+    },
+    /// A core has resumed execution.
+    CoreResumed {
+        /// The id of the core.
+        core: usize,
+    },
+    /// A core has entered a low power mode, e.g. by executing `WFI`.
+    ///
+    /// This is currently only detected for ARM cores.
+    CoreSleeping {
+        /// The id of the core.
+        core: usize,
+    },
+    /// A core has woken up from a low power mode.
+    CoreWokeUp {
+        /// The id of the core.
+        core: usize,
+    },
+    /// A reset of a core has been detected, e.g. a reset by a watchdog.
+    ///
+    /// Debugging is enabled again and the breakpoints are set again before this is
+    /// emitted. Resets are currently only detected for ARM and RISC-V cores.
+    ResetDetected {
+        /// The id of the core.
+        core: usize,
+    },
+    /// Progress of a flash operation.
+    Flash(ProgressEvent),
+    /// The probe does not respond anymore.
+    ProbeDisconnected,
+    /// Data was received on an RTT up channel.
+    RttData {
+        /// The number of the channel.
+        channel: usize,
+        /// The received data.
+        data: Vec<u8>,
+    },
+}
+
+/// Identifies an observer registered with [`Session::add_observer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObserverId(usize);
+
+type Observer = Box<dyn Fn(&SessionEvent) + Send>;
+
+/// The registered observers, shared with the handlers created by [`Session::flash_progress`].
+#[derive(Clone, Default)]
+struct Observers(Arc<Mutex<Vec<(ObserverId, Observer)>>>);
+
+impl Observers {
+    fn lock(&self) -> MutexGuard<'_, Vec<(ObserverId, Observer)>> {
+        // A panicking observer must not disable all other observers.
+        match self.0.lock() {
+            Ok(observers) => observers,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn emit(&self, event: &SessionEvent) {
+        for (_, observer) in self.lock().iter() {
+            observer(event);
+        }
+    }
+}
+
+/// The state used to emit events of a [`Session`].
+#[derive(Default)]
+pub(crate) struct SessionEvents {
+    observers: Observers,
+    next_id: usize,
+    /// The last known status of each core.
+    core_status: Vec<Option<CoreStatus>>,
+}
+
+impl fmt::Debug for SessionEvents {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionEvents")
+            .field("next_id", &self.next_id)
+            .field("core_status", &self.core_status)
+            .finish()
+    }
+}
+
+impl Session {
+    /// Register an observer, which is called for every event of the session.
+    ///
+    /// The observer is called on the thread which caused the event, and must not
+    /// call back into the session.
+    pub fn add_observer(
+        &mut self,
+        observer: impl Fn(&SessionEvent) + Send + 'static,
+    ) -> ObserverId {
+        let id = ObserverId(self.events.next_id);
+        self.events.next_id += 1;
+
+        self.events.observers.lock().push((id, Box::new(observer)));
+
+        id
+    }
+
+    /// Remove an observer registered with [`Session::add_observer`].
+    pub fn remove_observer(&mut self, id: ObserverId) {
+        self.events
+            .observers
+            .lock()
+            .retain(|(observer_id, _)| *observer_id != id);
+    }
+
+    /// Emit an event to all observers.
+    pub fn emit(&self, event: SessionEvent) {
+        self.events.observers.emit(&event);
+    }
+
+    /// Create a [`FlashProgress`] which emits all flash progress as [`SessionEvent::Flash`].
+    pub fn flash_progress(&self) -> FlashProgress {
+        let observers = self.events.observers.clone();
+
+        FlashProgress::new(move |event| observers.emit(&SessionEvent::Flash(event)))
+    }
+
+    /// Check the status of all cores, and emit events for all changes since the last call.
+    ///
+    /// If the probe does not respond, [`SessionEvent::ProbeDisconnected`] is emitted
+    /// and the error is returned.
+    pub fn poll_events(&mut self) -> Result<(), Error> {
+        let core_count = self.list_cores().len();
+        self.events.core_status.resize(core_count, None);
+
+        for id in 0..core_count {
+            match self.poll_core(id) {
+                Err(Error::Probe(error)) => {
+                    self.emit(SessionEvent::ProbeDisconnected);
+                    return Err(Error::Probe(error));
+                }
+                result => result?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn poll_core(&mut self, id: usize) -> Result<(), Error> {
+        let (reset, status) = {
+            let mut core = self.core(id)?;
+
+            // Reading the status clears the sticky reset bit of ARM cores, which
+            // is latched by the core until it is checked here.
+            let reset = core.reset_detected()?;
+
+            (reset, core.status()?)
+        };
+
+        // A breakpoint whose conditions are not met resumes the core, without any events.
+        let previous_halted = matches!(self.events.core_status[id], Some(CoreStatus::Halted(_)));
+        let status = if !previous_halted && self.resume_unmet_breakpoint(id, status)? {
+            CoreStatus::Running
+        } else {
+            status
+        };
+
+        let previous = self.events.core_status[id].replace(status);
+
+        if reset {
+            // The reset may have disabled debugging and cleared the breakpoints.
+            self.core(id)?.reinitialize_after_reset()?;
+
+            self.emit(SessionEvent::ResetDetected { core: id });
+        }
+
+        for event in status_events(id, previous, status) {
+            self.emit(event);
+        }
+
+        Ok(())
+    }
+}
+
+/// The events caused by a change of the status of a core from `previous` to `status`.
+fn status_events(
+    core: usize,
+    previous: Option<CoreStatus>,
+    status: CoreStatus,
+) -> Vec<SessionEvent> {
+    let mut events = Vec::new();
+
+    match (previous, status) {
+        (Some(CoreStatus::Halted(_)), CoreStatus::Halted(_)) => {}
+        (_, CoreStatus::Halted(reason)) => events.push(SessionEvent::CoreHalted { core, reason }),
+        (Some(CoreStatus::Halted(_)), CoreStatus::Running | CoreStatus::Sleeping) => {
+            events.push(SessionEvent::CoreResumed { core })
+        }
+        _ => {}
+    }
+
+    match (previous, status) {
+        (Some(CoreStatus::Sleeping), CoreStatus::Sleeping) => {}
+        (_, CoreStatus::Sleeping) => events.push(SessionEvent::CoreSleeping { core }),
+        (Some(CoreStatus::Sleeping), _) => events.push(SessionEvent::CoreWokeUp { core }),
+        _ => {}
+    }
+
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{status_events, ObserverId, Observers, SessionEvent};
+    use crate::{CoreStatus, HaltReason};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn events_are_emitted_to_all_observers() {
+        let observers = Observers::default();
+        let count = Arc::new(AtomicUsize::new(0));
+
+        for id in 0..2 {
+            let count = count.clone();
+            observers.lock().push((
+                ObserverId(id),
+                Box::new(move |event| {
+                    assert!(matches!(event, SessionEvent::ProbeDisconnected));
+                    count.fetch_add(1, Ordering::SeqCst);
+                }),
+            ));
+        }
+
+        observers.emit(&SessionEvent::ProbeDisconnected);
+
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn sleep_transitions_are_detected() {
+        assert!(matches!(
+            status_events(0, Some(CoreStatus::Running), CoreStatus::Sleeping)[..],
+            [SessionEvent::CoreSleeping { core: 0 }]
+        ));
+
+        assert!(matches!(
+            status_events(0, Some(CoreStatus::Sleeping), CoreStatus::Running)[..],
+            [SessionEvent::CoreWokeUp { core: 0 }]
+        ));
+
+        assert!(matches!(
+            status_events(
+                0,
+                Some(CoreStatus::Sleeping),
+                CoreStatus::Halted(HaltReason::Request)
+            )[..],
+            [
+                SessionEvent::CoreHalted { core: 0, .. },
+                SessionEvent::CoreWokeUp { core: 0 }
+            ]
+        ));
+
+        assert!(status_events(0, Some(CoreStatus::Sleeping), CoreStatus::Sleeping).is_empty());
+    }
+}
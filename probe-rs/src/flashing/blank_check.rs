@@ -0,0 +1,156 @@
+use std::ops::Range;
+
+use probe_rs_target::{MemoryRegion, SectorInfo};
+
+use crate::flashing::{flasher::Flasher, FlashError, FlashLoader, FlashTimeouts};
+use crate::memory::MemoryInterface;
+use crate::Session;
+
+/// The size of the blocks which are read to find bytes which are not erased.
+const READ_BLOCK_SIZE: u32 = 0x1_0000;
+
+/// Check if the flash in `range` is erased, and return the address of the first byte
+/// which is not erased, or `None` if the whole range is erased.
+///
+/// If the flash algorithm of a region has a `BlankCheck()` routine, it is used to find the
+/// first sector which is not erased, which is much faster than reading all sectors with
+/// most probes. Only that sector is read to find the address. Otherwise, the flash is read
+/// in large blocks.
+///
+/// The flash algorithm overwrites the RAM it is loaded to. Returns
+/// [`FlashError::NoSuitableNvm`] if a part of `range` is not flash.
+pub fn blank_check(session: &mut Session, range: Range<u32>) -> Result<Option<u32>, FlashError> {
+    let mut address = range.start;
+
+    while address < range.end {
+        let region = session
+            .target()
+            .memory_map
+            .iter()
+            .find_map(|region| match region {
+                MemoryRegion::Nvm(region) if region.range.contains(&address) => {
+                    Some(region.clone())
+                }
+                _ => None,
+            })
+            .ok_or_else(|| FlashError::NoSuitableNvm {
+                start: address,
+                end: range.end,
+                description_source: session.target().source().clone(),
+            })?;
+
+        let end = range.end.min(region.range.end);
+
+        let algo = FlashLoader::get_flash_algorithm_for_region(&region, session.target())?.clone();
+
+        // Get the first core that can access the region
+        let core_name = region
+            .cores
+            .first()
+            .ok_or_else(|| FlashError::NoNvmCoreAccess(region.clone()))?;
+        let core_index = session.target().core_index_by_name(core_name).unwrap();
+
+        let candidate = if algo.pc_blank_check.is_some() {
+            let mut flasher =
+                Flasher::new(session, core_index, &algo, FlashTimeouts::default(), false)?;
+
+            let chunks = sector_chunks(flasher.flash_algorithm().iter_sectors(), address..end);
+
+            flasher.run_verify(|active| {
+                for chunk in chunks {
+                    if !active.blank_check(chunk.start, chunk.end - chunk.start)? {
+                        return Ok(Some(chunk));
+                    }
+                }
+
+                Ok(None)
+            })?
+        } else {
+            Some(address..end)
+        };
+
+        if let Some(chunk) = candidate {
+            let erased_byte_value = algo.flash_properties.erased_byte_value;
+
+            if let Some(address) =
+                first_non_erased(session, core_index, chunk.clone(), erased_byte_value)?
+            {
+                return Ok(Some(address));
+            }
+
+            if algo.pc_blank_check.is_some() {
+                // The routine may check more than the contents, e.g. the ECC of the flash.
+                log::debug!(
+                    "The sector at {:#010x} is not blank, but reads as erased",
+                    chunk.start
+                );
+                return Ok(Some(chunk.start));
+            }
+        }
+
+        address = end;
+    }
+
+    Ok(None)
+}
+
+/// The parts of the `sectors` which are in `range`.
+fn sector_chunks(sectors: impl Iterator<Item = SectorInfo>, range: Range<u32>) -> Vec<Range<u32>> {
+    sectors
+        .map(|sector| sector.base_address..sector.base_address + sector.size)
+        .filter(|sector| sector.start < range.end && range.start < sector.end)
+        .map(|sector| sector.start.max(range.start)..sector.end.min(range.end))
+        .collect()
+}
+
+/// Read the flash in `range` in blocks, and return the address of the first byte which
+/// is not `erased_byte_value`.
+fn first_non_erased(
+    session: &mut Session,
+    core_index: usize,
+    range: Range<u32>,
+    erased_byte_value: u8,
+) -> Result<Option<u32>, FlashError> {
+    let mut core = session.core(core_index).map_err(FlashError::Core)?;
+
+    let mut address = range.start;
+    let mut data = vec![0; READ_BLOCK_SIZE as usize];
+
+    while address < range.end {
+        let size = READ_BLOCK_SIZE.min(range.end - address);
+        let block = &mut data[..size as usize];
+
+        core.read(address, block).map_err(FlashError::Core)?;
+
+        if let Some(offset) = block.iter().position(|&byte| byte != erased_byte_value) {
+            return Ok(Some(address + offset as u32));
+        }
+
+        address += size;
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod test {
+    use super::sector_chunks;
+    use probe_rs_target::SectorInfo;
+
+    #[test]
+    fn chunks_of_partial_sectors() {
+        let sectors = (0..4).map(|index| SectorInfo {
+            base_address: 0x800_0000 + index * 0x1000,
+            size: 0x1000,
+        });
+
+        assert_eq!(
+            sector_chunks(sectors, 0x800_0800..0x800_2400),
+            vec![
+                0x800_0800..0x800_1000,
+                0x800_1000..0x800_2000,
+                0x800_2000..0x800_2400,
+            ]
+        );
+    }
+}
@@ -3,6 +3,8 @@ use std::fmt::{Debug, Formatter};
 use std::ops::Range;
 
 use probe_rs_target::{MemoryRange, NvmRegion, PageInfo};
+use serde::ser::{SerializeStruct, Serializer};
+use serde::Serialize;
 
 use super::{FlashAlgorithm, FlashError, FlashVisualizer};
 
@@ -22,6 +24,15 @@ impl Debug for FlashPage {
     }
 }
 
+impl Serialize for FlashPage {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut page = serializer.serialize_struct("FlashPage", 2)?;
+        page.serialize_field("address", &self.address())?;
+        page.serialize_field("size", &self.size())?;
+        page.end()
+    }
+}
+
 impl FlashPage {
     /// Creates a new empty flash page from a `PageInfo`.
     fn new(page_info: &PageInfo, default_value: u8) -> Self {
@@ -53,7 +64,7 @@ impl FlashPage {
 }
 
 /// The description of a sector in flash.
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize)]
 pub struct FlashSector {
     address: u32,
     size: u32,
@@ -73,7 +84,7 @@ impl FlashSector {
 
 /// A struct to hold all the information about one region
 /// in the flash that is erased during flashing and has to be restored to its original value afterwards.
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize)]
 pub struct FlashFill {
     address: u32,
     size: u32,
@@ -98,7 +109,10 @@ impl FlashFill {
 }
 
 /// The built layout of the data in flash.
-#[derive(Debug, Clone, PartialEq, Eq)]
+///
+/// The layout can be serialized, e.g. to render it in another tool. The pages are
+/// serialized with their address and size, without their data.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct FlashLayout {
     sectors: Vec<FlashSector>,
     pages: Vec<FlashPage>,
@@ -135,13 +149,127 @@ impl FlashLayout {
     pub fn visualize(&self) -> FlashVisualizer {
         FlashVisualizer::new(self)
     }
+
+    /// Read the data which will be written at `address` from the pages of the layout.
+    ///
+    /// Returns an error if any byte of the range is not part of a page.
+    pub fn read(&self, address: u32, data: &mut [u8]) -> Result<(), FlashError> {
+        let mut offset = 0;
+
+        while offset < data.len() {
+            let (page, page_offset) = self.page_at(address, offset, data.len())?;
+            let page = &self.pages[page];
+
+            let length = (page.data.len() - page_offset).min(data.len() - offset);
+            data[offset..offset + length]
+                .copy_from_slice(&page.data[page_offset..page_offset + length]);
+            offset += length;
+        }
+
+        Ok(())
+    }
+
+    /// Change the data which will be written at `address` in the pages of the layout.
+    ///
+    /// Returns an error if any byte of the range is not part of a page, in which case
+    /// nothing is changed.
+    pub fn write(&mut self, address: u32, data: &[u8]) -> Result<(), FlashError> {
+        // Check the whole range first, so the pages aren't partially changed.
+        self.read(address, &mut vec![0; data.len()])?;
+
+        let mut offset = 0;
+
+        while offset < data.len() {
+            let (page, page_offset) = self.page_at(address, offset, data.len())?;
+            let page = &mut self.pages[page];
+
+            let length = (page.data.len() - page_offset).min(data.len() - offset);
+            page.data[page_offset..page_offset + length]
+                .copy_from_slice(&data[offset..offset + length]);
+            offset += length;
+        }
+
+        self.remove_fills(address, data.len());
+
+        Ok(())
+    }
+
+    /// Remove a range which is now covered by data from the fills, splitting the fills
+    /// which overlap it.
+    fn remove_fills(&mut self, address: u32, length: usize) {
+        let start = address as u64;
+        let end = start + length as u64;
+
+        self.fills = self
+            .fills
+            .drain(..)
+            .flat_map(|fill| {
+                let fill_start = fill.address as u64;
+                let fill_end = fill_start + fill.size as u64;
+
+                vec![
+                    fill_start..fill_end.min(start),
+                    fill_start.max(end)..fill_end,
+                ]
+                .into_iter()
+                .filter(|range| !range.is_empty())
+                .map(move |range| FlashFill {
+                    address: range.start as u32,
+                    size: (range.end - range.start) as u32,
+                    page_index: fill.page_index,
+                })
+            })
+            .collect();
+    }
+
+    /// Set all bytes of the pages which are not covered by the flashed data to `value`.
+    ///
+    /// These are the bytes of the fills, so if unwritten bytes are restored, this replaces
+    /// the previous contents of the flash.
+    pub fn fill_gaps(&mut self, value: u8) {
+        for fill in &self.fills {
+            let page = &mut self.pages[fill.page_index];
+            let start = (fill.address - page.address) as usize;
+
+            page.data[start..start + fill.size as usize].fill(value);
+        }
+    }
+
+    /// Find the page which contains the byte at `offset` of the range starting at `address`,
+    /// and the offset of the byte in the page.
+    fn page_at(
+        &self,
+        address: u32,
+        offset: usize,
+        length: usize,
+    ) -> Result<(usize, usize), FlashError> {
+        let byte_address = address as u64 + offset as u64;
+
+        self.pages
+            .iter()
+            .position(|page| {
+                let start = page.address as u64;
+                (start..start + page.data.len() as u64).contains(&byte_address)
+            })
+            .map(|page| {
+                (
+                    page,
+                    (byte_address - self.pages[page].address as u64) as usize,
+                )
+            })
+            .ok_or(FlashError::NotInFlashLayout {
+                start: address,
+                end: address.saturating_add(length as u32),
+            })
+    }
 }
 
 /// A block of data that is to be written to flash.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
 pub struct FlashDataBlockSpan {
     address: u32,
     size: u32,
+    sources: Vec<String>,
 }
 
 impl FlashDataBlockSpan {
@@ -154,12 +282,22 @@ impl FlashDataBlockSpan {
     pub fn size(&self) -> u32 {
         self.size
     }
+
+    /// Returns the names of the sources of the data in the block, e.g. the sections of an
+    /// ELF file or a partition.
+    ///
+    /// This is empty if the data was added without a source.
+    pub fn sources(&self) -> &[String] {
+        &self.sources
+    }
 }
 
 /// A helper structure to build a flash layout from a set of data blocks.
 #[derive(Default)]
 pub(super) struct FlashBuilder {
     pub(super) data: BTreeMap<u32, Vec<u8>>,
+    /// The names of the sources of the data, by address range.
+    sources: Vec<(Range<u32>, String)>,
 }
 
 impl FlashBuilder {
@@ -167,7 +305,26 @@ impl FlashBuilder {
     pub(super) fn new() -> Self {
         Self {
             data: BTreeMap::new(),
+            sources: Vec::new(),
+        }
+    }
+
+    /// Records that the data in `range` comes from `source`, e.g. a section of an ELF file.
+    pub(super) fn add_source(&mut self, range: Range<u32>, source: &str) {
+        self.sources.push((range, source.to_owned()));
+    }
+
+    /// The names of the sources of the data in `range`, without duplicates.
+    fn sources_in_range(&self, range: &Range<u32>) -> Vec<String> {
+        let mut sources: Vec<String> = Vec::new();
+
+        for (source_range, source) in &self.sources {
+            if source_range.intersects_range(range) && !sources.contains(source) {
+                sources.push(source.clone());
+            }
         }
+
+        sources
     }
 
     /// Stages a chunk of data to be programmed.
@@ -348,9 +505,12 @@ impl FlashBuilder {
         }
 
         for (address, data) in self.data_in_range(&region.range) {
+            let range = address..address + data.len() as u32;
+
             data_blocks.push(FlashDataBlockSpan {
                 address,
                 size: data.len() as _,
+                sources: self.sources_in_range(&range),
             });
         }
 
@@ -390,6 +550,8 @@ mod tests {
             is_boot_memory: true,
             range: 0..1 << 16,
             cores: vec!["main".into()],
+            access_port: None,
+            alias: None,
         };
 
         (region, flash_algorithm)
@@ -415,6 +577,8 @@ mod tests {
             is_boot_memory: true,
             range: 0..1 << 16,
             cores: vec!["main".into()],
+            access_port: None,
+            alias: None,
         };
 
         (region, flash_algorithm)
@@ -485,6 +649,7 @@ mod tests {
                 data_blocks: vec![FlashDataBlockSpan {
                     address: 0,
                     size: 1,
+                    sources: vec![],
                 }],
             }
         )
@@ -546,6 +711,7 @@ mod tests {
                 data_blocks: vec![FlashDataBlockSpan {
                     address: 0,
                     size: 1024,
+                    sources: vec![],
                 }],
             }
         )
@@ -611,6 +777,7 @@ mod tests {
                 data_blocks: vec![FlashDataBlockSpan {
                     address: 0,
                     size: 1025,
+                    sources: vec![],
                 }],
             }
         )
@@ -656,6 +823,7 @@ mod tests {
                 data_blocks: vec![FlashDataBlockSpan {
                     address: 0,
                     size: 1025,
+                    sources: vec![],
                 }],
             }
         )
@@ -734,6 +902,7 @@ mod tests {
                 data_blocks: vec![FlashDataBlockSpan {
                     address: 42,
                     size: 1024,
+                    sources: vec![],
                 },],
             }
         )
@@ -828,6 +997,7 @@ mod tests {
                 data_blocks: vec![FlashDataBlockSpan {
                     address: 0,
                     size: 5024,
+                    sources: vec![],
                 },],
             }
         )
@@ -996,10 +1166,12 @@ mod tests {
                     FlashDataBlockSpan {
                         address: 0,
                         size: 5024,
+                        sources: vec![],
                     },
                     FlashDataBlockSpan {
                         address: 7860,
                         size: 5024,
+                        sources: vec![],
                     },
                 ],
             }
@@ -1124,13 +1296,89 @@ mod tests {
                     FlashDataBlockSpan {
                         address: 0,
                         size: 5024,
+                        sources: vec![],
                     },
                     FlashDataBlockSpan {
                         address: 7860,
                         size: 5024,
+                        sources: vec![],
                     },
                 ],
             }
         )
     }
+
+    #[test]
+    fn change_layout_data() {
+        let (region, flash_algorithm) = assemble_demo_flash1();
+        let mut flash_builder = FlashBuilder::new();
+        flash_builder.add_data(0, &[42; 1025]).unwrap();
+        let mut flash_layout = flash_builder
+            .build_sectors_and_pages(&region, &flash_algorithm, false)
+            .unwrap();
+
+        // Writes can span multiple pages.
+        flash_layout.write(0x03fe, &[1, 2, 3, 4]).unwrap();
+
+        let mut data = [0; 6];
+        flash_layout.read(0x03fd, &mut data).unwrap();
+        assert_eq!(data, [42, 1, 2, 3, 4, 255]);
+
+        // The written bytes are no longer part of the fills.
+        assert_eq!(flash_layout.fills()[0].address(), 0x0402);
+
+        flash_layout.fill_gaps(0);
+        flash_layout.read(0x03fd, &mut data).unwrap();
+        assert_eq!(data, [42, 1, 2, 3, 4, 0]);
+
+        // Nothing is changed if the range is not completely part of the pages.
+        assert!(matches!(
+            flash_layout.write(0x07ff, &[5, 6]),
+            Err(FlashError::NotInFlashLayout {
+                start: 0x07ff,
+                end: 0x0801
+            })
+        ));
+        assert_eq!(flash_layout.pages()[1].data()[0x3ff], 0);
+    }
+
+    #[test]
+    fn data_block_sources() {
+        let (region, flash_algorithm) = assemble_demo_flash1();
+        let mut flash_builder = FlashBuilder::new();
+
+        // Adjacent data is merged into one block with both sources.
+        flash_builder.add_data(0, &[1; 16]).unwrap();
+        flash_builder.add_source(0..16, ".vector_table");
+        flash_builder.add_data(16, &[2; 16]).unwrap();
+        flash_builder.add_source(16..32, ".text");
+        flash_builder.add_data(0x2000, &[3; 4]).unwrap();
+
+        let flash_layout = flash_builder
+            .build_sectors_and_pages(&region, &flash_algorithm, false)
+            .unwrap();
+
+        assert_eq!(
+            flash_layout.data_blocks(),
+            &[
+                FlashDataBlockSpan {
+                    address: 0,
+                    size: 32,
+                    sources: vec![".vector_table".to_owned(), ".text".to_owned()],
+                },
+                FlashDataBlockSpan {
+                    address: 0x2000,
+                    size: 4,
+                    sources: vec![],
+                },
+            ]
+        );
+
+        let json = serde_json::to_value(&flash_layout).unwrap();
+        assert_eq!(
+            json["pages"][0],
+            serde_json::json!({ "address": 0, "size": 1024 })
+        );
+        assert_eq!(json["data_blocks"][0]["sources"][1], ".text");
+    }
 }
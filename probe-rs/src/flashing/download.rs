@@ -2,12 +2,13 @@ use object::{
     elf::FileHeader32, elf::PT_LOAD, read::elf::FileHeader, read::elf::ProgramHeader, Endianness,
     Object, ObjectSection,
 };
-use probe_rs_target::MemoryRange;
+use probe_rs_target::{FlashProperties, MemoryRange};
 
-use std::{fs::File, path::Path, str::FromStr};
+use std::{fs::File, path::Path, str::FromStr, time::Duration};
 
 use super::*;
 use crate::session::Session;
+use crate::CancellationToken;
 
 /// Extended options for flashing a binary file.
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
@@ -101,6 +102,8 @@ pub struct DownloadOptions<'progress> {
     /// This is necessary because the flash can only be erased in sectors. If only parts of the erased sector are written thereafter,
     /// instead of the full sector, the excessively erased bytes wont match the contents before the erase which might not be intuitive
     /// to the user or even worse, result in unexpected behavior if those contents contain important data.
+    ///
+    /// Sectors without any data are not erased at all, so `do_chip_erase` is ignored if this is set.
     pub keep_unwritten_bytes: bool,
     /// Perform a dry run. This prepares everything for flashing, but does not write anything to flash.
     pub dry_run: bool,
@@ -113,6 +116,26 @@ pub struct DownloadOptions<'progress> {
     pub skip_erase: bool,
     /// After flashing, read back all the flashed data to verify it has been written correctly.
     pub verify: bool,
+    /// Program the pages one after the other, instead of loading the next page into a second
+    /// buffer while the flash algorithm programs the current one.
+    ///
+    /// Double buffering is only used if the RAM fits two page buffers next to the flash algorithm.
+    pub disable_double_buffering: bool,
+    /// Save the contents of the RAM which is used by the flash algorithm before loading it,
+    /// and restore them after flashing, so that the RAM contents of the application are kept.
+    ///
+    /// The RAM regions of the loaded file are still written after flashing.
+    pub preserve_ram: bool,
+    /// Timeouts for the routines of the flash algorithm, which override the timeouts
+    /// of the target description.
+    pub timeouts: FlashTimeouts,
+    /// A token to cancel the download from another thread.
+    ///
+    /// The cancellation is checked before each sector is erased and each page is programmed,
+    /// a running routine of the flash algorithm, e.g. a chip erase, is always finished. The
+    /// flash algorithm is uninitialized and the RAM is restored before
+    /// [`FlashError::Cancelled`] is returned, so the target can be flashed again afterwards.
+    pub cancellation: Option<CancellationToken>,
 }
 
 impl<'progress> DownloadOptions<'progress> {
@@ -122,6 +145,74 @@ impl<'progress> DownloadOptions<'progress> {
     }
 }
 
+/// Timeouts for the routines of a flash algorithm.
+///
+/// Each timeout which is `None` uses the default: the erase sector and program page timeouts
+/// are taken from the flash properties of the target description, which are extracted from
+/// the `FlashDevice` description of CMSIS packs. These can be too short for slow external
+/// flashes, in which case they can be overridden here.
+///
+/// ```
+/// use probe_rs::flashing::DownloadOptions;
+/// use std::time::Duration;
+///
+/// let mut options = DownloadOptions::default();
+///
+/// options.timeouts.erase_sector = Some(Duration::from_secs(5));
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FlashTimeouts {
+    /// Timeout for the `Init()` routine.
+    pub init: Option<Duration>,
+    /// Timeout for the `UnInit()` routine.
+    pub uninit: Option<Duration>,
+    /// Timeout for the `EraseSector()` routine.
+    pub erase_sector: Option<Duration>,
+    /// Timeout for the `EraseAll()` routine.
+    pub erase_all: Option<Duration>,
+    /// Timeout for the `ProgramPage()` routine.
+    pub program_page: Option<Duration>,
+}
+
+impl FlashTimeouts {
+    /// Timeout of the routines without a timeout in the target description.
+    const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+
+    /// Default timeout of the `EraseAll()` routine, which takes much longer than the other routines.
+    const DEFAULT_ERASE_ALL_TIMEOUT: Duration = Duration::from_secs(30);
+
+    pub(super) fn init_timeout(&self) -> Duration {
+        self.init.unwrap_or(Self::DEFAULT_TIMEOUT)
+    }
+
+    pub(super) fn uninit_timeout(&self) -> Duration {
+        self.uninit.unwrap_or(Self::DEFAULT_TIMEOUT)
+    }
+
+    pub(super) fn erase_all_timeout(&self) -> Duration {
+        self.erase_all.unwrap_or(Self::DEFAULT_ERASE_ALL_TIMEOUT)
+    }
+
+    pub(super) fn erase_sector_timeout(&self, properties: &FlashProperties) -> Duration {
+        self.erase_sector
+            .unwrap_or_else(|| Self::from_properties(properties.erase_sector_timeout))
+    }
+
+    pub(super) fn program_page_timeout(&self, properties: &FlashProperties) -> Duration {
+        self.program_page
+            .unwrap_or_else(|| Self::from_properties(properties.program_page_timeout))
+    }
+
+    /// Convert a timeout of the target description in milliseconds,
+    /// where zero means that no timeout is specified.
+    fn from_properties(timeout: u32) -> Duration {
+        match timeout {
+            0 => Self::DEFAULT_TIMEOUT,
+            timeout => Duration::from_millis(timeout as u64),
+        }
+    }
+}
+
 /// Downloads a file of given `format` at `path` to the flash of the target given in `session`.
 ///
 /// This will ensure that memory bounderies are honored and does unlocking, erasing and programming of the flash for you.
@@ -164,6 +255,22 @@ pub fn download_file_with_options<P: AsRef<Path>>(
         .map_err(FileDownloadError::Flash)
 }
 
+/// Downloads `data` to the start of the partition `name` of the target given in `session`.
+///
+/// This also writes protected partitions, see [`FlashLoader::add_data_to_partition`].
+pub fn download_to_partition(
+    session: &mut Session,
+    name: &str,
+    data: &[u8],
+    options: DownloadOptions<'_>,
+) -> Result<(), FlashError> {
+    let mut loader = session.target().flash_loader();
+
+    loader.add_data_to_partition(name, 0, data)?;
+
+    loader.commit(session, options)
+}
+
 /// Flash data which was extraced from an ELF file.
 pub(super) struct ExtractedFlashData<'data> {
     pub(super) section_names: Vec<String>,
@@ -281,9 +388,11 @@ pub(super) fn extract_from_elf<'data>(
 
 #[cfg(test)]
 mod tests {
-    use std::str::FromStr;
+    use std::{str::FromStr, time::Duration};
+
+    use probe_rs_target::FlashProperties;
 
-    use super::{BinOptions, Format};
+    use super::{BinOptions, FlashTimeouts, Format};
 
     #[test]
     fn parse_format() {
@@ -339,4 +448,35 @@ mod tests {
             Err("Format 'asdasdf' is unknown.".to_string())
         );
     }
+
+    #[test]
+    fn flash_timeouts_from_properties() {
+        let properties = FlashProperties {
+            program_page_timeout: 500,
+            erase_sector_timeout: 0,
+            ..Default::default()
+        };
+
+        let timeouts = FlashTimeouts::default();
+
+        assert_eq!(
+            timeouts.program_page_timeout(&properties),
+            Duration::from_millis(500)
+        );
+        // A timeout of zero is not specified in the target description.
+        assert_eq!(
+            timeouts.erase_sector_timeout(&properties),
+            FlashTimeouts::DEFAULT_TIMEOUT
+        );
+
+        let timeouts = FlashTimeouts {
+            program_page: Some(Duration::from_secs(3)),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            timeouts.program_page_timeout(&properties),
+            Duration::from_secs(3)
+        );
+    }
 }
@@ -2,7 +2,7 @@ use std::collections::HashMap;
 
 use probe_rs_target::{MemoryRange, MemoryRegion, NvmRegion};
 
-use crate::flashing::{flasher::Flasher, FlashError, FlashLoader};
+use crate::flashing::{flasher::Flasher, FlashError, FlashLoader, FlashTimeouts};
 use crate::Session;
 
 /// Mass-erase all nonvolatile memory.
@@ -45,7 +45,8 @@ pub fn erase_all(session: &mut Session) -> Result<(), FlashError> {
         let algo = algo.unwrap().clone();
 
         let core_index = session.target().core_index_by_name(&core_name).unwrap();
-        let mut flasher = Flasher::new(session, core_index, &algo)?;
+        let mut flasher =
+            Flasher::new(session, core_index, &algo, FlashTimeouts::default(), false)?;
 
         if flasher.is_chip_erase_supported() {
             log::debug!("     -- chip erase supported, doing it.");
@@ -82,3 +83,79 @@ pub fn erase_all(session: &mut Session) -> Result<(), FlashError> {
 
     Ok(())
 }
+
+/// Erase all sectors of the partition `name` of the target.
+///
+/// This also erases protected partitions. The partition has to start and end at sector
+/// boundaries, so no data outside of it is erased.
+pub fn erase_partition(session: &mut Session, name: &str) -> Result<(), FlashError> {
+    let partition = session
+        .target()
+        .partition(name)
+        .ok_or_else(|| FlashError::PartitionNotFound(name.to_owned()))?
+        .clone();
+
+    log::debug!(
+        "Erasing partition {}: {:08x}-{:08x}",
+        partition.name,
+        partition.range.start,
+        partition.range.end
+    );
+
+    let region = session
+        .target()
+        .memory_map
+        .iter()
+        .find_map(|region| match region {
+            MemoryRegion::Nvm(region) if region.range.contains_range(&partition.range) => {
+                Some(region.clone())
+            }
+            _ => None,
+        })
+        .ok_or_else(|| FlashError::NoSuitableNvm {
+            start: partition.range.start,
+            end: partition.range.end,
+            description_source: session.target().source().clone(),
+        })?;
+
+    let algo = FlashLoader::get_flash_algorithm_for_region(&region, session.target())?.clone();
+
+    // Get the first core that can access the region
+    let core_name = region
+        .cores
+        .first()
+        .ok_or_else(|| FlashError::NoNvmCoreAccess(region.clone()))?;
+    let core_index = session.target().core_index_by_name(core_name).unwrap();
+
+    let mut flasher = Flasher::new(session, core_index, &algo, FlashTimeouts::default(), false)?;
+
+    let sectors = flasher
+        .flash_algorithm()
+        .iter_sectors()
+        .filter(|info| {
+            let range = info.base_address..info.base_address + info.size;
+            range.intersects_range(&partition.range)
+        })
+        .collect::<Vec<_>>();
+
+    if sectors.iter().any(|info| {
+        let range = info.base_address..info.base_address + info.size;
+        !partition.range.contains_range(&range)
+    }) {
+        return Err(FlashError::PartitionNotSectorAligned(partition.name));
+    }
+
+    flasher.run_erase(|active| {
+        for info in sectors {
+            log::debug!(
+                "    sector: {:08x}-{:08x} ({} bytes)",
+                info.base_address,
+                info.base_address + info.size,
+                info.size
+            );
+
+            active.erase_sector(info.base_address)?;
+        }
+        Ok(())
+    })
+}
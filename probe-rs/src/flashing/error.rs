@@ -3,6 +3,7 @@
 use crate::config::{NvmRegion, RamRegion, TargetDescriptionSource};
 use crate::error;
 use std::ops::Range;
+use std::time::Duration;
 
 /// Describes any error that happened during the or in preparation for the flashing procedure.
 #[derive(thiserror::Error, Debug)]
@@ -15,6 +16,8 @@ pub enum FlashError {
         end: u32,
         description_source: TargetDescriptionSource,
     },
+    #[error("No RAM contains the entire requested memory range {start:#010x}..{end:#10x}.")]
+    NoSuitableRam { start: u32, end: u32 },
     #[error("Failed to erase the whole chip.")]
     ChipEraseFailed {
         source: Box<dyn std::error::Error + 'static + Send + Sync>,
@@ -35,8 +38,29 @@ pub enum FlashError {
     Uninit(#[source] Box<dyn std::error::Error + 'static + Send + Sync>),
     #[error("The chip erase routine is not supported with the given flash algorithm.")]
     ChipEraseNotSupported,
-    #[error("The execution of '{name}' failed with code {error_code}. This might indicate a problem with the flash algorithm.")]
-    RoutineCallFailed { name: &'static str, error_code: u32 },
+    #[error("The blank check routine is not supported with the given flash algorithm.")]
+    BlankCheckNotSupported,
+    #[error(
+        "The execution of '{name}'{} failed with code {error_code:#x}. This might indicate a problem with the flash algorithm.",
+        .address.map(|address| format!(" at address {:#010x}", address)).unwrap_or_default()
+    )]
+    RoutineCallFailed {
+        name: &'static str,
+        /// The address of the flash the routine operated on, if any.
+        address: Option<u32>,
+        /// The raw value returned by the routine.
+        error_code: u32,
+    },
+    #[error(
+        "The execution of '{name}'{} did not finish within {timeout:?}.",
+        .address.map(|address| format!(" at address {:#010x}", address)).unwrap_or_default()
+    )]
+    RoutineTimeout {
+        name: &'static str,
+        /// The address of the flash the routine operated on, if any.
+        address: Option<u32>,
+        timeout: Duration,
+    },
     #[error("{address:#010x} is not contained in {region:?}")]
     AddressNotInRegion { address: u32, region: NvmRegion },
     #[error("Something during the interaction with the core went wrong")]
@@ -86,4 +110,32 @@ pub enum FlashError {
     NoNvmCoreAccess(NvmRegion),
     #[error("No core can access the ram region {0:?}.")]
     NoRamCoreAccess(RamRegion),
+    #[error("Flashing was cancelled.")]
+    Cancelled,
+    #[error("The target has no partition named '{0}'.")]
+    PartitionNotFound(String),
+    #[error("{length} bytes at offset {offset:#x} do not fit into the partition '{name}'.")]
+    DataOutsidePartition {
+        name: String,
+        offset: u32,
+        length: usize,
+    },
+    #[error("The data at {address:#010x} would overwrite the protected partition '{name}'.")]
+    PartitionProtected { name: String, address: u32 },
+    #[error(
+        "The partition '{0}' does not start and end at sector boundaries, and can't be erased."
+    )]
+    PartitionNotSectorAligned(String),
+    #[error("The range {start:#010x}..{end:#010x} is not part of the flashed pages.")]
+    NotInFlashLayout { start: u32, end: u32 },
+    #[error("The pre-program hook failed.")]
+    PreProgramHook(#[source] Box<dyn std::error::Error + Send + Sync>),
+    #[error(
+        "The OTP word at {address:#010x} for '{name}' is already programmed with different data."
+    )]
+    OtpAlreadyProgrammed { name: String, address: u32 },
+    #[error(
+        "The data of '{name}' at {address:#010x} does not match the memory after provisioning."
+    )]
+    ProvisioningVerify { name: String, address: u32 },
 }
@@ -4,6 +4,7 @@ use super::FlashError;
 use crate::core::Architecture;
 use crate::{architecture::riscv, Target};
 use std::convert::TryInto;
+use std::ops::Range;
 
 /// A flash algorithm, which has been assembled for a specific
 /// chip.
@@ -31,6 +32,8 @@ pub struct FlashAlgorithm {
     pub pc_erase_sector: u32,
     /// Address of the `EraseAll()` entry point. Optional.
     pub pc_erase_all: Option<u32>,
+    /// Address of the `BlankCheck()` entry point. Optional.
+    pub pc_blank_check: Option<u32>,
     /// Initial value of the R9 register for calling flash algo entry points, which
     /// determines where the position-independent data resides.
     pub static_base: u32,
@@ -79,6 +82,23 @@ impl FlashAlgorithm {
         })
     }
 
+    /// The range of RAM which is used by the flash algorithm, i.e. its code and data,
+    /// its stack and the page buffers.
+    pub fn ram_range(&self) -> Range<u32> {
+        let page_buffers_end = self
+            .page_buffers
+            .iter()
+            .map(|buffer| buffer + self.flash_properties.page_size)
+            .max()
+            .unwrap_or(0);
+
+        let end = page_buffers_end
+            .max(self.begin_stack)
+            .max(self.load_address + (self.instructions.len() * std::mem::size_of::<u32>()) as u32);
+
+        self.load_address..end
+    }
+
     /// Returns the necessary information about the page which `address` resides in
     /// if the address is inside the flash region.
     pub fn page_info(&self, address: u32) -> Option<PageInfo> {
@@ -175,6 +195,8 @@ impl FlashAlgorithm {
         match architecture {
             Architecture::Arm => &Self::ARM_FLASH_BLOB_HEADER,
             Architecture::Riscv => &Self::RISCV_FLASH_BLOB_HEADER,
+            // Flash algorithms can not be run on Xtensa cores yet, so there is no header.
+            Architecture::Xtensa => &[],
         }
     }
 
@@ -271,6 +293,7 @@ impl FlashAlgorithm {
             pc_program_page: code_start + raw.pc_program_page,
             pc_erase_sector: code_start + raw.pc_erase_sector,
             pc_erase_all: raw.pc_erase_all.map(|v| code_start + v),
+            pc_blank_check: raw.pc_blank_check.map(|v| code_start + v),
             static_base: code_start + raw.data_section_offset,
             begin_stack: addr_stack,
             begin_data: page_buffers[0],
@@ -452,4 +475,22 @@ mod test {
         ];
         assert_eq!(&got, expected);
     }
+
+    #[test]
+    fn ram_range_includes_page_buffers() {
+        let config = FlashAlgorithm {
+            load_address: 0x2000_0000,
+            instructions: vec![0; 0x40],
+            begin_stack: 0x2000_0300,
+            begin_data: 0x2000_0300,
+            page_buffers: vec![0x2000_0300, 0x2000_0400],
+            flash_properties: FlashProperties {
+                page_size: 0x100,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        assert_eq!(config.ram_range(), 0x2000_0000..0x2000_0500);
+    }
 }
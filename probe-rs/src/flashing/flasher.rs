@@ -2,14 +2,11 @@ use probe_rs_target::{MemoryRegion, RawFlashAlgorithm};
 
 use super::{
     FlashAlgorithm, FlashBuilder, FlashError, FlashFill, FlashLayout, FlashPage, FlashProgress,
+    FlashTimeouts, PreProgramHook,
 };
 use crate::config::NvmRegion;
 use crate::memory::MemoryInterface;
-use crate::{
-    core::{Architecture, RegisterFile},
-    session::Session,
-    Core, CoreRegisterAddress,
-};
+use crate::{core::routine::RoutineCall, session::Session, CancellationToken, Core, Target};
 use std::{fmt::Debug, time::Duration};
 
 pub(super) trait Operation {
@@ -55,44 +52,66 @@ pub(super) struct Flasher<'session> {
     session: &'session mut Session,
     core_index: usize,
     flash_algorithm: FlashAlgorithm,
+    timeouts: FlashTimeouts,
+    /// The previous contents of the RAM used by the flash algorithm, if it has to be restored.
+    saved_ram: Option<Vec<u8>>,
+    cancellation: Option<CancellationToken>,
+}
+
+/// Assemble the flash algorithm for the RAM of the core with `core_index`.
+pub(super) fn assemble_algorithm(
+    target: &Target,
+    core_index: usize,
+    raw_flash_algorithm: &RawFlashAlgorithm,
+) -> Result<FlashAlgorithm, FlashError> {
+    // Find a RAM region from which we can run the algo.
+    let mm = &target.memory_map;
+    let core_name = &target.cores[core_index].name;
+    let ram = mm
+        .iter()
+        .filter_map(|mm| match mm {
+            MemoryRegion::Ram(ram) => Some(ram),
+            _ => None,
+        })
+        .find(|ram| {
+            // The RAM must be accessible from the core we're going to run the algo on.
+            ram.cores.contains(core_name)
+        })
+        .ok_or(FlashError::NoRamDefined {
+            chip: target.name.clone(),
+        })?;
+
+    log::info!("chosen RAM to run the algo: {:x?}", ram);
+
+    FlashAlgorithm::assemble_from_raw(raw_flash_algorithm, ram, target)
 }
 
 impl<'session> Flasher<'session> {
+    /// Load the flash algorithm into the RAM of the core.
+    ///
+    /// If `preserve_ram` is `true`, the contents of the RAM used by the algorithm are read
+    /// before it is loaded, so that they can be written back with [`Flasher::restore_ram`].
     pub(super) fn new(
         session: &'session mut Session,
         core_index: usize,
         raw_flash_algorithm: &RawFlashAlgorithm,
+        timeouts: FlashTimeouts,
+        preserve_ram: bool,
     ) -> Result<Self, FlashError> {
         let target = session.target();
 
-        // Find a RAM region from which we can run the algo.
-        let mm = &target.memory_map;
-        let core_name = &target.cores[core_index].name;
-        let ram = mm
-            .iter()
-            .filter_map(|mm| match mm {
-                MemoryRegion::Ram(ram) => Some(ram),
-                _ => None,
-            })
-            .find(|ram| {
-                // The RAM must be accessible from the core we're going to run the algo on.
-                ram.cores.contains(core_name)
-            })
-            .ok_or(FlashError::NoRamDefined {
-                chip: session.target().name.clone(),
-            })?;
-
-        log::info!("chosen RAM to run the algo: {:x?}", ram);
-
-        let flash_algorithm = FlashAlgorithm::assemble_from_raw(raw_flash_algorithm, ram, target)?;
+        let flash_algorithm = assemble_algorithm(target, core_index, raw_flash_algorithm)?;
 
         let mut this = Self {
             session,
             core_index,
             flash_algorithm,
+            timeouts,
+            saved_ram: None,
+            cancellation: None,
         };
 
-        this.load()?;
+        this.load(preserve_ram)?;
 
         Ok(this)
     }
@@ -105,7 +124,38 @@ impl<'session> Flasher<'session> {
         self.flash_algorithm.page_buffers.len() > 1
     }
 
-    fn load(&mut self) -> Result<(), FlashError> {
+    /// Check `cancellation` before each sector is erased and each page is programmed.
+    pub(super) fn set_cancellation(&mut self, cancellation: Option<CancellationToken>) {
+        self.cancellation = cancellation;
+    }
+
+    /// Write the RAM contents which were saved before the flash algorithm was loaded back.
+    ///
+    /// This does nothing if the RAM was not preserved.
+    pub(super) fn restore_ram(&mut self) -> Result<(), FlashError> {
+        if let Some(saved_ram) = self.saved_ram.take() {
+            let range = self.flash_algorithm.ram_range();
+
+            log::debug!(
+                "Restoring RAM used by the flash algorithm: {:#010x}..{:#010x}",
+                range.start,
+                range.end
+            );
+
+            let mut core = self
+                .session
+                .core(self.core_index)
+                .map_err(FlashError::Core)?;
+
+            core.write_8(range.start, &saved_ram)
+                .map_err(FlashError::Core)?;
+            core.flush().map_err(FlashError::Core)?;
+        }
+
+        Ok(())
+    }
+
+    fn load(&mut self, preserve_ram: bool) -> Result<(), FlashError> {
         log::debug!("Initializing the flash algorithm.");
         let algo = &mut self.flash_algorithm;
 
@@ -121,6 +171,22 @@ impl<'session> Flasher<'session> {
             .halt(Duration::from_millis(100))
             .map_err(FlashError::Core)?;
         log::debug!("PC = 0x{:08x}", cpu_info.pc);
+
+        if preserve_ram {
+            let range = algo.ram_range();
+
+            log::debug!(
+                "Saving RAM used by the flash algorithm: {:#010x}..{:#010x}",
+                range.start,
+                range.end
+            );
+
+            let mut saved_ram = vec![0; (range.end - range.start) as usize];
+            core.read(range.start, &mut saved_ram)
+                .map_err(FlashError::Core)?;
+            self.saved_ram = Some(saved_ram);
+        }
+
         log::debug!("Reset and halt");
         core.reset_and_halt(Duration::from_millis(500))
             .map_err(FlashError::Core)?;
@@ -176,6 +242,8 @@ impl<'session> Flasher<'session> {
         let mut flasher = ActiveFlasher::<O> {
             core,
             flash_algorithm: self.flash_algorithm.clone(),
+            timeouts: self.timeouts,
+            cancellation: self.cancellation.clone(),
             _operation: core::marker::PhantomData,
         };
 
@@ -189,10 +257,16 @@ impl<'session> Flasher<'session> {
         F: FnOnce(&mut ActiveFlasher<'_, Erase>) -> Result<T, FlashError> + Sized,
     {
         // TODO: Fix those values (None, None).
-        let mut active = self.init(None)?;
-        let r = f(&mut active)?;
-        active.uninit()?;
-        Ok(r)
+        let result = self.init(None).and_then(|mut active| {
+            let r = f(&mut active).or_else(|error| active.uninit_after_cancellation(error))?;
+            active.uninit()?;
+            Ok(r)
+        });
+
+        // The flash was changed, even if the operation failed.
+        self.session.invalidate_memory_cache();
+
+        result
     }
 
     pub(super) fn run_program<T, F>(&mut self, f: F) -> Result<T, FlashError>
@@ -200,10 +274,16 @@ impl<'session> Flasher<'session> {
         F: FnOnce(&mut ActiveFlasher<'_, Program>) -> Result<T, FlashError> + Sized,
     {
         // TODO: Fix those values (None, None).
-        let mut active = self.init(None)?;
-        let r = f(&mut active)?;
-        active.uninit()?;
-        Ok(r)
+        let result = self.init(None).and_then(|mut active| {
+            let r = f(&mut active).or_else(|error| active.uninit_after_cancellation(error))?;
+            active.uninit()?;
+            Ok(r)
+        });
+
+        // The flash was changed, even if the operation failed.
+        self.session.invalidate_memory_cache();
+
+        result
     }
 
     pub(super) fn run_verify<T, F>(&mut self, f: F) -> Result<T, FlashError>
@@ -212,7 +292,7 @@ impl<'session> Flasher<'session> {
     {
         // TODO: Fix those values (None, None).
         let mut active = self.init(None)?;
-        let r = f(&mut active)?;
+        let r = f(&mut active).or_else(|error| active.uninit_after_cancellation(error))?;
         active.uninit()?;
         Ok(r)
     }
@@ -233,6 +313,7 @@ impl<'session> Flasher<'session> {
         restore_unwritten_bytes: bool,
         enable_double_buffering: bool,
         skip_erasing: bool,
+        pre_program_hook: Option<&PreProgramHook>,
         progress: &FlashProgress,
     ) -> Result<(), FlashError> {
         log::debug!("Starting program procedure.");
@@ -255,25 +336,23 @@ impl<'session> Flasher<'session> {
         progress.started_filling();
 
         if restore_unwritten_bytes {
-            let fills = flash_layout.fills().to_vec();
-            for fill in fills {
-                let t = std::time::Instant::now();
-                let page = &mut flash_layout.pages_mut()[fill.page_index()];
-                let result = self.fill_page(page, &fill);
+            let result = self.fill_pages(&mut flash_layout, progress);
 
-                // If we encounter an error, catch it, gracefully report the failure and return the error.
-                if result.is_err() {
-                    progress.failed_filling();
-                    return result;
-                } else {
-                    progress.page_filled(fill.size(), t.elapsed());
-                }
+            // If we encounter an error, catch it, gracefully report the failure and return the error.
+            if result.is_err() {
+                progress.failed_filling();
+                return result;
             }
         }
 
         // We successfully finished filling.
         progress.finished_filling();
 
+        // Let the caller change the data before anything is erased.
+        if let Some(hook) = pre_program_hook {
+            hook(&mut flash_layout)?;
+        }
+
         // Skip erase if necessary
         if !skip_erasing {
             // Erase all necessary sectors
@@ -290,23 +369,29 @@ impl<'session> Flasher<'session> {
         Ok(())
     }
 
-    /// Fills all the bytes of `current_page`.
+    /// Reads the current contents of the flash into all fills of `flash_layout`.
     ///
-    /// If `restore_unwritten_bytes` is `true`, all bytes of the page,
-    /// that are not to be written during flashing will be read from the flash first
-    /// and written again once the page is programmed.
-    pub(super) fn fill_page(
+    /// This is used if `restore_unwritten_bytes` is `true`, so that all bytes of the
+    /// erased sectors which are not written during flashing are written again with
+    /// their previous value. All fills are read with a single initialization of the
+    /// flash algorithm.
+    fn fill_pages(
         &mut self,
-        page: &mut FlashPage,
-        fill: &FlashFill,
+        flash_layout: &mut FlashLayout,
+        progress: &FlashProgress,
     ) -> Result<(), FlashError> {
-        let page_offset = (fill.address() - page.address()) as usize;
-        let page_slice = &mut page.data_mut()[page_offset..page_offset + fill.size() as usize];
+        let fills = flash_layout.fills().to_vec();
+
         self.run_verify(|active| {
-            active
-                .core
-                .read(fill.address(), page_slice)
-                .map_err(FlashError::Core)
+            for fill in &fills {
+                active.check_cancelled()?;
+
+                let t = web_time::Instant::now();
+                let page = &mut flash_layout.pages_mut()[fill.page_index()];
+                active.fill_page(page, fill)?;
+                progress.page_filled(fill.size(), t.elapsed());
+            }
+            Ok(())
         })
     }
 
@@ -318,9 +403,11 @@ impl<'session> Flasher<'session> {
     ) -> Result<(), FlashError> {
         progress.started_programming();
 
-        let mut t = std::time::Instant::now();
+        let mut t = web_time::Instant::now();
         let result = self.run_program(|active| {
             for page in flash_layout.pages() {
+                active.check_cancelled()?;
+
                 active
                     .program_page(page.address(), page.data())
                     .map_err(|error| FlashError::PageWrite {
@@ -328,7 +415,7 @@ impl<'session> Flasher<'session> {
                         source: Box::new(error),
                     })?;
                 progress.page_programmed(page.size(), t.elapsed());
-                t = std::time::Instant::now();
+                t = web_time::Instant::now();
             }
             Ok(())
         });
@@ -350,9 +437,11 @@ impl<'session> Flasher<'session> {
     ) -> Result<(), FlashError> {
         progress.started_erasing();
 
-        let mut t = std::time::Instant::now();
+        let mut t = web_time::Instant::now();
         let result = self.run_erase(|active| {
             for sector in flash_layout.sectors() {
+                active.check_cancelled()?;
+
                 active
                     .erase_sector(sector.address())
                     .map_err(|e| FlashError::EraseFailed {
@@ -361,7 +450,7 @@ impl<'session> Flasher<'session> {
                     })?;
 
                 progress.sector_erased(sector.size(), t.elapsed());
-                t = std::time::Instant::now();
+                t = web_time::Instant::now();
             }
             Ok(())
         });
@@ -393,35 +482,32 @@ impl<'session> Flasher<'session> {
 
         progress.started_programming();
 
-        let mut t = std::time::Instant::now();
+        let mut t = web_time::Instant::now();
         let result = self.run_program(|active| {
-            let mut last_page_address = 0;
+            let mut last_page: Option<&FlashPage> = None;
+            let mut cancelled = false;
             for page in flash_layout.pages() {
+                // The page which is being programmed is finished before returning.
+                if active.is_cancelled() {
+                    cancelled = true;
+                    break;
+                }
+
                 // At the start of each loop cycle load the next page buffer into RAM.
                 active.load_page_buffer(page.address(), page.data(), current_buf)?;
 
                 // Then wait for the active RAM -> Flash copy process to finish.
                 // Also check if it finished properly. If it didn't, return an error.
-                let result =
-                    active
-                        .wait_for_completion(Duration::from_secs(2))
-                        .map_err(|error| FlashError::PageWrite {
-                            page_address: last_page_address,
-                            source: Box::new(error),
-                        })?;
-
-                last_page_address = page.address();
-                progress.page_programmed(page.size(), t.elapsed());
-                t = std::time::Instant::now();
-                if result != 0 {
-                    return Err(FlashError::RoutineCallFailed {
-                        name: "program_page",
-                        error_code: result,
-                    });
+                if let Some(last_page) = last_page {
+                    active.wait_for_page_programmed(last_page.address())?;
+
+                    progress.page_programmed(last_page.size(), t.elapsed());
+                    t = web_time::Instant::now();
                 }
 
                 // Start the next copy process.
-                active.start_program_page_with_buffer(page.address(), current_buf)?;
+                active.start_program_page_with_buffer(page.address(), page.size(), current_buf)?;
+                last_page = Some(page);
 
                 // Swap the buffers
                 if current_buf == 1 {
@@ -431,21 +517,17 @@ impl<'session> Flasher<'session> {
                 }
             }
 
-            let result = active
-                .wait_for_completion(Duration::from_secs(2))
-                .map_err(|error| FlashError::PageWrite {
-                    page_address: last_page_address,
-                    source: Box::new(error),
-                })?;
+            if let Some(last_page) = last_page {
+                active.wait_for_page_programmed(last_page.address())?;
 
-            if result != 0 {
-                Err(FlashError::RoutineCallFailed {
-                    name: "wait_for_completion",
-                    error_code: result,
-                })
-            } else {
-                Ok(0)
+                progress.page_programmed(last_page.size(), t.elapsed());
             }
+
+            if cancelled {
+                return Err(FlashError::Cancelled);
+            }
+
+            Ok(())
         });
 
         if result.is_ok() {
@@ -480,6 +562,8 @@ impl Debug for Registers {
 pub(super) struct ActiveFlasher<'probe, O: Operation> {
     core: Core<'probe>,
     flash_algorithm: FlashAlgorithm,
+    timeouts: FlashTimeouts,
+    cancellation: Option<CancellationToken>,
     _operation: core::marker::PhantomData<O>,
 }
 
@@ -494,6 +578,8 @@ impl<'probe, O: Operation> ActiveFlasher<'probe, O> {
         if let Some(pc_init) = algo.pc_init {
             let result = self
                 .call_function_and_wait(
+                    "init",
+                    None,
                     &Registers {
                         pc: pc_init,
                         r0: Some(address),
@@ -502,13 +588,14 @@ impl<'probe, O: Operation> ActiveFlasher<'probe, O> {
                         r3: None,
                     },
                     true,
-                    Duration::from_secs(2),
+                    self.timeouts.init_timeout(),
                 )
                 .map_err(|error| FlashError::Init(Box::new(error)))?;
 
             if result != 0 {
                 return Err(FlashError::RoutineCallFailed {
                     name: "init",
+                    address: None,
                     error_code: result,
                 });
             }
@@ -528,6 +615,8 @@ impl<'probe, O: Operation> ActiveFlasher<'probe, O> {
         if let Some(pc_uninit) = algo.pc_uninit {
             let result = self
                 .call_function_and_wait(
+                    "uninit",
+                    None,
                     &Registers {
                         pc: pc_uninit,
                         r0: Some(O::operation()),
@@ -536,13 +625,14 @@ impl<'probe, O: Operation> ActiveFlasher<'probe, O> {
                         r3: None,
                     },
                     false,
-                    Duration::from_secs(2),
+                    self.timeouts.uninit_timeout(),
                 )
                 .map_err(|error| FlashError::Uninit(Box::new(error)))?;
 
             if result != 0 {
                 return Err(FlashError::RoutineCallFailed {
                     name: "uninit",
+                    address: None,
                     error_code: result,
                 });
             }
@@ -550,85 +640,135 @@ impl<'probe, O: Operation> ActiveFlasher<'probe, O> {
         Ok(())
     }
 
+    /// Returns `true` if the download was cancelled with its [`CancellationToken`].
+    pub(super) fn is_cancelled(&self) -> bool {
+        matches!(&self.cancellation, Some(cancellation) if cancellation.is_cancelled())
+    }
+
+    /// Returns [`FlashError::Cancelled`] if the download was cancelled.
+    pub(super) fn check_cancelled(&self) -> Result<(), FlashError> {
+        if self.is_cancelled() {
+            Err(FlashError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Run the uninit routine if `error` is a cancellation, so that the flash is usable
+    /// afterwards, and return `error`.
+    fn uninit_after_cancellation<T>(&mut self, error: FlashError) -> Result<T, FlashError> {
+        if let FlashError::Cancelled = error {
+            self.uninit()?;
+        }
+
+        Err(error)
+    }
+
     fn call_function_and_wait(
         &mut self,
+        name: &'static str,
+        address: Option<u32>,
         registers: &Registers,
         init: bool,
-        duration: Duration,
-    ) -> Result<u32, crate::Error> {
-        self.call_function(registers, init)?;
-        self.wait_for_completion(duration)
+        timeout: Duration,
+    ) -> Result<u32, FlashError> {
+        self.call_function(registers, init)
+            .map_err(FlashError::Core)?;
+        self.wait_for_completion(name, address, timeout)
     }
 
     fn call_function(&mut self, registers: &Registers, init: bool) -> Result<(), crate::Error> {
         log::debug!("Calling routine {:?}, init={})", &registers, init);
 
         let algo = &self.flash_algorithm;
-        let regs: &'static RegisterFile = self.core.registers();
-
-        let registers = [
-            (regs.program_counter(), Some(registers.pc)),
-            (regs.argument_register(0), registers.r0),
-            (regs.argument_register(1), registers.r1),
-            (regs.argument_register(2), registers.r2),
-            (regs.argument_register(3), registers.r3),
-            (
-                regs.platform_register(9),
-                if init { Some(algo.static_base) } else { None },
-            ),
-            (
-                regs.stack_pointer(),
-                if init { Some(algo.begin_stack) } else { None },
-            ),
-            (
-                regs.return_address(),
-                // For ARM Cortex-M cores, we have to add 1 to the return address,
-                // to ensure that we stay in Thumb mode.
-                if self.core.architecture() == Architecture::Arm {
-                    Some(algo.load_address + 1)
-                } else {
-                    Some(algo.load_address)
-                },
-            ),
-        ];
-
-        for (description, value) in &registers {
-            if let Some(v) = value {
-                self.core.write_core_reg(description.address, *v)?;
-                log::debug!(
-                    "content of {} {:#x}: 0x{:08x} should be: 0x{:08x}",
-                    description.name,
-                    description.address.0,
-                    self.core.read_core_reg(description.address)?,
-                    *v
-                );
-            }
-        }
 
-        if self.core.architecture() == Architecture::Riscv {
-            // Ensure ebreak enters debug mode, this is necessary for soft breakpoints to work.
-            let dcsr = self.core.read_core_reg(CoreRegisterAddress::from(0x7b0))?;
+        // The arguments of the routines of the algorithm are always the first arguments.
+        let args: Vec<u32> = [registers.r0, registers.r1, registers.r2, registers.r3]
+            .iter()
+            .map_while(|arg| *arg)
+            .collect();
 
-            self.core.write_core_reg(
-                CoreRegisterAddress::from(0x7b0),
-                dcsr | (1 << 15) | (1 << 13) | (1 << 12),
-            )?;
-        }
+        // The routines return to the breakpoint instruction at the start of the algorithm.
+        self.core.start_routine(&RoutineCall {
+            address: registers.pc,
+            args: &args,
+            return_address: algo.load_address,
+            stack_pointer: if init { Some(algo.begin_stack) } else { None },
+            static_base: if init { Some(algo.static_base) } else { None },
+        })
+    }
 
-        // Resume target operation.
-        self.core.run()?;
+    /// Wait for the routine `name`, which operates on `address`, to return, and read its result.
+    ///
+    /// If the routine is still running after `timeout`, the core is halted and
+    /// [`FlashError::RoutineTimeout`] is returned.
+    pub(super) fn wait_for_completion(
+        &mut self,
+        name: &'static str,
+        address: Option<u32>,
+        timeout: Duration,
+    ) -> Result<u32, FlashError> {
+        log::debug!("Waiting for routine call completion.");
 
-        Ok(())
+        self.core
+            .wait_for_routine(timeout)
+            .map_err(FlashError::Core)?
+            .ok_or(FlashError::RoutineTimeout {
+                name,
+                address,
+                timeout,
+            })
     }
+}
 
-    pub(super) fn wait_for_completion(&mut self, timeout: Duration) -> Result<u32, crate::Error> {
-        log::debug!("Waiting for routine call completion.");
-        let regs = self.core.registers();
+impl<'probe> ActiveFlasher<'probe, Verify> {
+    /// Reads the current flash contents of the area described by `fill` into `page`.
+    pub(super) fn fill_page(
+        &mut self,
+        page: &mut FlashPage,
+        fill: &FlashFill,
+    ) -> Result<(), FlashError> {
+        let page_offset = (fill.address() - page.address()) as usize;
+        let page_slice = &mut page.data_mut()[page_offset..page_offset + fill.size() as usize];
 
-        self.core.wait_for_core_halted(timeout)?;
+        self.core
+            .read(fill.address(), page_slice)
+            .map_err(FlashError::Core)
+    }
 
-        let r = self.core.read_core_reg(regs.result_register(0).address)?;
-        Ok(r)
+    /// Check with the `BlankCheck()` routine of the algorithm if the `size` bytes at
+    /// `address` are erased.
+    ///
+    /// Returns [`FlashError::BlankCheckNotSupported`] if the algorithm has no such routine.
+    pub(super) fn blank_check(&mut self, address: u32, size: u32) -> Result<bool, FlashError> {
+        let pc_blank_check = self
+            .flash_algorithm
+            .pc_blank_check
+            .ok_or(FlashError::BlankCheckNotSupported)?;
+
+        log::debug!(
+            "Checking if {} bytes at address {:#010x} are blank",
+            size,
+            address
+        );
+
+        // Reading a sector is faster than erasing it, so its timeout is long enough.
+        let result = self.call_function_and_wait(
+            "blank_check",
+            Some(address),
+            &Registers {
+                pc: pc_blank_check,
+                r0: Some(address),
+                r1: Some(size),
+                r2: Some(self.flash_algorithm.flash_properties.erased_byte_value as u32),
+                r3: None,
+            },
+            false,
+            self.timeouts
+                .erase_sector_timeout(&self.flash_algorithm.flash_properties),
+        )?;
+
+        Ok(result == 0)
     }
 }
 
@@ -641,6 +781,8 @@ impl<'probe> ActiveFlasher<'probe, Erase> {
         if let Some(pc_erase_all) = algo.pc_erase_all {
             let result = flasher
                 .call_function_and_wait(
+                    "chip_erase",
+                    None,
                     &Registers {
                         pc: pc_erase_all,
                         r0: None,
@@ -649,7 +791,7 @@ impl<'probe> ActiveFlasher<'probe, Erase> {
                         r3: None,
                     },
                     false,
-                    Duration::from_secs(30),
+                    flasher.timeouts.erase_all_timeout(),
                 )
                 .map_err(|error| FlashError::ChipEraseFailed {
                     source: Box::new(error),
@@ -659,6 +801,7 @@ impl<'probe> ActiveFlasher<'probe, Erase> {
                 Err(FlashError::ChipEraseFailed {
                     source: Box::new(FlashError::RoutineCallFailed {
                         name: "chip_erase",
+                        address: None,
                         error_code: result,
                     }),
                 })
@@ -672,10 +815,12 @@ impl<'probe> ActiveFlasher<'probe, Erase> {
 
     pub(super) fn erase_sector(&mut self, address: u32) -> Result<(), FlashError> {
         log::info!("Erasing sector at address 0x{:08x}", address);
-        let t1 = std::time::Instant::now();
+        let t1 = web_time::Instant::now();
 
         let result = self
             .call_function_and_wait(
+                "erase_sector",
+                Some(address),
                 &Registers {
                     pc: self.flash_algorithm.pc_erase_sector,
                     r0: Some(address),
@@ -684,9 +829,8 @@ impl<'probe> ActiveFlasher<'probe, Erase> {
                     r3: None,
                 },
                 false,
-                Duration::from_millis(
-                    self.flash_algorithm.flash_properties.erase_sector_timeout as u64,
-                ),
+                self.timeouts
+                    .erase_sector_timeout(&self.flash_algorithm.flash_properties),
             )
             .map_err(|error| FlashError::EraseFailed {
                 sector_address: address,
@@ -701,6 +845,7 @@ impl<'probe> ActiveFlasher<'probe, Erase> {
         if result != 0 {
             Err(FlashError::RoutineCallFailed {
                 name: "erase_sector",
+                address: Some(address),
                 error_code: result,
             })
         } else {
@@ -711,7 +856,7 @@ impl<'probe> ActiveFlasher<'probe, Erase> {
 
 impl<'p> ActiveFlasher<'p, Program> {
     pub(super) fn program_page(&mut self, address: u32, bytes: &[u8]) -> Result<(), FlashError> {
-        let t1 = std::time::Instant::now();
+        let t1 = web_time::Instant::now();
 
         log::info!(
             "Flashing page at address {:#08x} with size: {}",
@@ -724,31 +869,47 @@ impl<'p> ActiveFlasher<'p, Program> {
             .write_8(self.flash_algorithm.begin_data, bytes)
             .map_err(FlashError::Core)?;
 
+        self.call_function(
+            &Registers {
+                pc: self.flash_algorithm.pc_program_page,
+                r0: Some(address),
+                r1: Some(bytes.len() as u32),
+                r2: Some(self.flash_algorithm.begin_data),
+                r3: None,
+            },
+            false,
+        )
+        .map_err(|error| FlashError::PageWrite {
+            page_address: address,
+            source: Box::new(error),
+        })?;
+
+        self.wait_for_page_programmed(address)?;
+        log::info!("Flashing took: {:?}", t1.elapsed());
+
+        Ok(())
+    }
+
+    /// Wait until the `ProgramPage()` routine for the page at `address` has returned,
+    /// and check its result.
+    pub(super) fn wait_for_page_programmed(&mut self, address: u32) -> Result<(), FlashError> {
+        let timeout = self
+            .timeouts
+            .program_page_timeout(&self.flash_algorithm.flash_properties);
+
         let result = self
-            .call_function_and_wait(
-                &Registers {
-                    pc: self.flash_algorithm.pc_program_page,
-                    r0: Some(address),
-                    r1: Some(bytes.len() as u32),
-                    r2: Some(self.flash_algorithm.begin_data),
-                    r3: None,
-                },
-                false,
-                Duration::from_millis(
-                    self.flash_algorithm.flash_properties.program_page_timeout as u64,
-                ),
-            )
+            .wait_for_completion("program_page", Some(address), timeout)
             .map_err(|error| FlashError::PageWrite {
                 page_address: address,
                 source: Box::new(error),
             })?;
-        log::info!("Flashing took: {:?}", t1.elapsed());
 
         if result != 0 {
             Err(FlashError::PageWrite {
                 page_address: address,
                 source: Box::new(FlashError::RoutineCallFailed {
                     name: "program_page",
+                    address: Some(address),
                     error_code: result,
                 }),
             })
@@ -757,9 +918,12 @@ impl<'p> ActiveFlasher<'p, Program> {
         }
     }
 
+    /// Start programming the `size` bytes in page buffer `buffer_number` to `address`,
+    /// without waiting for the flash algorithm to finish.
     pub(super) fn start_program_page_with_buffer(
         &mut self,
         address: u32,
+        size: u32,
         buffer_number: usize,
     ) -> Result<(), FlashError> {
         // Ensure the buffer number is valid, otherwise there is a bug somewhere
@@ -774,8 +938,8 @@ impl<'p> ActiveFlasher<'p, Program> {
             &Registers {
                 pc: self.flash_algorithm.pc_program_page,
                 r0: Some(address),
-                r1: Some(self.flash_algorithm.flash_properties.page_size),
-                r2: Some(self.flash_algorithm.page_buffers[buffer_number as usize]),
+                r1: Some(size),
+                r2: Some(self.flash_algorithm.page_buffers[buffer_number]),
                 r3: None,
             },
             false,
@@ -811,7 +975,7 @@ impl<'p> ActiveFlasher<'p, Program> {
             .map(|a| u32::from_le_bytes([a[0], a[1], a[2], a[3]]))
             .collect();
 
-        let t1 = std::time::Instant::now();
+        let t1 = web_time::Instant::now();
         self.core
             .write_32(algo.page_buffers[buffer_number], &words)
             .map_err(FlashError::Core)?;
@@ -1,6 +1,7 @@
 use ihex::Record;
 use probe_rs_target::{
-    MemoryRange, MemoryRegion, NvmRegion, RawFlashAlgorithm, TargetDescriptionSource,
+    FlashPartition, MemoryRange, MemoryRegion, NvmRegion, RawFlashAlgorithm,
+    TargetDescriptionSource,
 };
 use std::collections::HashMap;
 use std::io::{Read, Seek, SeekFrom};
@@ -8,13 +9,18 @@ use std::ops::Range;
 
 use super::builder::FlashBuilder;
 use super::{
-    extract_from_elf, BinOptions, DownloadOptions, FileDownloadError, FlashError, FlashProgress,
-    Flasher,
+    assemble_algorithm, extract_from_elf, BinOptions, DownloadOptions, FileDownloadError,
+    FlashError, FlashLayout, FlashPlan, FlashProgress, Flasher, RegionPlan,
 };
+use crate::architecture::arm::sequences::raspberrypi::rp2040_boot2_valid;
 use crate::memory::MemoryInterface;
 use crate::session::Session;
 use crate::Target;
 
+/// A function which can change the data of the pages before they are programmed,
+/// see [`FlashLoader::set_pre_program_hook`].
+pub type PreProgramHook = dyn Fn(&mut FlashLayout) -> Result<(), FlashError> + Send;
+
 /// `FlashLoader` is a struct which manages the flashing of any chunks of data onto any sections of flash.
 ///
 /// Use [add_data()](FlashLoader::add_data) to add a chunk of data.
@@ -24,6 +30,8 @@ use crate::Target;
 pub struct FlashLoader {
     memory_map: Vec<MemoryRegion>,
     builder: FlashBuilder,
+    partitions: Vec<FlashPartition>,
+    pre_program_hook: Option<Box<PreProgramHook>>,
 
     /// Source of the flash description,
     /// used for diagnostics.
@@ -36,10 +44,58 @@ impl FlashLoader {
         Self {
             memory_map,
             builder: FlashBuilder::new(),
+            partitions: Vec::new(),
+            pre_program_hook: None,
             source,
         }
     }
 
+    /// Set a function which is called with the layout of each flashed NVM region, after
+    /// the unwritten bytes were read and before the sectors are erased.
+    ///
+    /// The function can change the data of the pages with [`FlashLayout::write`], e.g. to
+    /// patch the checksum of an image into its header, or insert a serial number, and fill
+    /// the gaps between the data with [`FlashLayout::fill_gaps`]. If it returns an error,
+    /// nothing is erased or programmed. Errors of the caller can be returned as
+    /// [`FlashError::PreProgramHook`].
+    ///
+    /// The verification after flashing compares the flash with the data added to the
+    /// loader, so it fails if the hook changed that data.
+    pub fn set_pre_program_hook(
+        &mut self,
+        hook: impl Fn(&mut FlashLayout) -> Result<(), FlashError> + Send + 'static,
+    ) {
+        self.pre_program_hook = Some(Box::new(hook));
+    }
+
+    /// Add a named partition, which can be written with [`FlashLoader::add_data_to_partition`].
+    ///
+    /// If the partition is protected, [`FlashLoader::add_data`] rejects data which overlaps it.
+    pub fn add_partition(&mut self, partition: FlashPartition) {
+        self.partitions.push(partition);
+    }
+
+    /// Check that `range` does not overlap a protected partition, except the partition `allowed`.
+    fn check_not_protected(
+        &self,
+        range: Range<u32>,
+        allowed: Option<&str>,
+    ) -> Result<(), FlashError> {
+        let protected = self.partitions.iter().find(|partition| {
+            partition.protected
+                && Some(partition.name.as_str()) != allowed
+                && partition.range.intersects_range(&range)
+        });
+
+        match protected {
+            Some(partition) => Err(FlashError::PartitionProtected {
+                name: partition.name.clone(),
+                address: range.start.max(partition.range.start),
+            }),
+            None => Ok(()),
+        }
+    }
+
     /// Check the given address range is completely covered by the memory map,
     /// possibly by multiple memory regions.
     fn check_data_in_memory_map(&mut self, range: Range<u32>) -> Result<(), FlashError> {
@@ -63,15 +119,118 @@ impl FlashLoader {
     /// Stages a chunk of data to be programmed.
     ///
     /// The chunk can cross flash boundaries as long as one flash region connects to another flash region.
+    ///
+    /// Data in the alias of a memory region, e.g. the secure alias of the flash on ARMv8-M chips,
+    /// is programmed at the same location in the region itself.
+    ///
+    /// Data which overlaps a protected partition is rejected, use
+    /// [`FlashLoader::add_data_to_partition`] to write it.
     pub fn add_data(&mut self, address: u32, data: &[u8]) -> Result<(), FlashError> {
+        self.stage_data(address, data, None, None)
+    }
+
+    /// Stages a chunk of data like [`FlashLoader::add_data`], and records the name of its
+    /// source, e.g. a section of an ELF file.
+    ///
+    /// The sources are reported by [`FlashDataBlockSpan::sources`](super::FlashDataBlockSpan::sources)
+    /// of the blocks of the flash layout.
+    pub fn add_data_with_source(
+        &mut self,
+        address: u32,
+        data: &[u8],
+        source: &str,
+    ) -> Result<(), FlashError> {
+        self.stage_data(address, data, Some(source), None)
+    }
+
+    /// Stages a chunk of data to be programmed at `offset` in the partition `name`.
+    ///
+    /// The data has to fit into the partition. Protected partitions can only be written
+    /// with this function.
+    pub fn add_data_to_partition(
+        &mut self,
+        name: &str,
+        offset: u32,
+        data: &[u8],
+    ) -> Result<(), FlashError> {
+        let partition = self
+            .partitions
+            .iter()
+            .find(|partition| partition.name == name)
+            .ok_or_else(|| FlashError::PartitionNotFound(name.to_owned()))?;
+
+        let size = u64::from(partition.range.end - partition.range.start);
+
+        if u64::from(offset) + data.len() as u64 > size {
+            return Err(FlashError::DataOutsidePartition {
+                name: name.to_owned(),
+                offset,
+                length: data.len(),
+            });
+        }
+
+        let address = partition.range.start + offset;
+
+        self.stage_data(address, data, Some(name), Some(name))
+    }
+
+    /// Stages a chunk of data from `source`, which may overlap the protected partition `allowed`.
+    fn stage_data(
+        &mut self,
+        mut address: u32,
+        mut data: &[u8],
+        source: Option<&str>,
+        allowed: Option<&str>,
+    ) -> Result<(), FlashError> {
         log::trace!(
             "Adding data at address {:#010x} with size {} bytes",
             address,
             data.len()
         );
 
-        self.check_data_in_memory_map(address..address + data.len() as u32)?;
-        self.builder.add_data(address, data)
+        while !data.is_empty() {
+            let (length, target_address) =
+                match Self::get_region_for_alias(&self.memory_map, address) {
+                    Some(region) => {
+                        // Can't fail, the address is in the alias of the region.
+                        let alias = region.alias_range().unwrap();
+                        let translated = region.address_from_alias(address).unwrap();
+
+                        log::debug!("Translating {:#010x} to {:#010x}", address, translated);
+
+                        (data.len().min((alias.end - address) as usize), translated)
+                    }
+                    None => {
+                        // Stop at the next alias, which is translated separately.
+                        let next_alias = self
+                            .memory_map
+                            .iter()
+                            .filter_map(|region| region.alias())
+                            .filter(|&alias| alias > address)
+                            .min()
+                            .map_or(data.len(), |alias| (alias - address) as usize);
+
+                        (data.len().min(next_alias), address)
+                    }
+                };
+
+            let (chunk, rest) = data.split_at(length);
+
+            let range = target_address..target_address + length as u32;
+
+            self.check_data_in_memory_map(range.clone())?;
+            self.check_not_protected(range.clone(), allowed)?;
+            self.builder.add_data(target_address, chunk)?;
+
+            if let Some(source) = source {
+                self.builder.add_source(range, source);
+            }
+
+            address += length as u32;
+            data = rest;
+        }
+
+        Ok(())
     }
 
     pub(super) fn get_region_for_address(
@@ -91,6 +250,13 @@ impl FlashLoader {
         None
     }
 
+    /// The memory region with an alias which contains `address`.
+    fn get_region_for_alias(memory_map: &[MemoryRegion], address: u32) -> Option<&MemoryRegion> {
+        memory_map
+            .iter()
+            .find(|region| region.address_from_alias(address).is_some())
+    }
+
     /// Reads the data from the binary file and adds it to the loader without splitting it into flash instructions yet.
     pub fn load_bin_data<T: Read + Seek>(
         &mut self,
@@ -183,7 +349,12 @@ impl FlashLoader {
         }
 
         for data in extracted_data {
-            self.add_data(data.address, data.data)?;
+            if data.section_names.is_empty() {
+                self.add_data(data.address, data.data)?;
+            } else {
+                let source = data.section_names.join(", ");
+                self.add_data_with_source(data.address, data.data, &source)?;
+            }
         }
 
         Ok(())
@@ -211,6 +382,19 @@ impl FlashLoader {
             );
         }
 
+        if session.target().name.starts_with("RP2040") {
+            if let Some(false) = self
+                .builder
+                .data
+                .iter()
+                .find_map(|(&address, data)| rp2040_boot2_valid(address, data))
+            {
+                log::warn!(
+                    "The CRC of the boot2 at the start of the flash is invalid, the boot ROM of the RP2040 will not run the program."
+                );
+            }
+        }
+
         log::debug!("Flash algorithms:");
         for algorithm in &session.target().flash_algorithms {
             let Range { start, end } = algorithm.flash_properties.address_range;
@@ -230,54 +414,12 @@ impl FlashLoader {
             log::warn!("Memory map of flash loader does not match memory map of target!");
         }
 
-        let mut algos: HashMap<(String, String), Vec<NvmRegion>> = HashMap::new();
-
         // Commit NVM first
-
-        // Iterate all NvmRegions and group them by flash algorithm.
-        // This avoids loading the same algorithm twice if it's used for two regions.
-        //
-        // This also ensures correct operation when chip erase is used. We assume doing a chip erase
-        // using a given algorithm erases all regions controlled by it. Therefore, we must do
-        // chip erase once per algorithm, not once per region. Otherwise subsequent chip erases will
-        // erase previous regions' flashed contents.
-        log::debug!("Regions:");
-        for region in &self.memory_map {
-            if let MemoryRegion::Nvm(region) = region {
-                log::debug!(
-                    "    region: {:08x}-{:08x} ({} bytes)",
-                    region.range.start,
-                    region.range.end,
-                    region.range.end - region.range.start
-                );
-
-                // If we have no data in this region, ignore it.
-                // This avoids uselessly initializing and deinitializing its flash algorithm.
-                if !self.builder.has_data_in_range(&region.range) {
-                    log::debug!("     -- empty, ignoring!");
-                    continue;
-                }
-
-                let algo = Self::get_flash_algorithm_for_region(region, session.target())?;
-
-                let entry = algos
-                    .entry((
-                        algo.name.clone(),
-                        region
-                            .cores
-                            .first()
-                            .ok_or_else(|| FlashError::NoNvmCoreAccess(region.clone()))?
-                            .clone(),
-                    ))
-                    .or_default();
-                entry.push(region.clone());
-
-                log::debug!("     -- using algorithm: {}", algo.name);
-            }
-        }
+        let algos = self.regions_by_algorithm(session.target())?;
 
         if options.dry_run {
-            log::info!("Skipping programming, dry run!");
+            let plan = self.plan(session.target(), &options)?;
+            log::info!("Skipping programming, dry run!\n{}", plan);
 
             if let Some(progress) = options.progress {
                 progress.failed_filling();
@@ -302,7 +444,9 @@ impl FlashLoader {
                 .iter()
                 .position(|c| c.name == core_name)
                 .unwrap();
-            let mut flasher = Flasher::new(session, core, &algo)?;
+            let mut flasher =
+                Flasher::new(session, core, &algo, options.timeouts, options.preserve_ram)?;
+            flasher.set_cancellation(options.cancellation.clone());
 
             let mut do_chip_erase = options.do_chip_erase;
 
@@ -313,29 +457,29 @@ impl FlashLoader {
                 log::warn!("A manual sector erase will be performed.");
             }
 
-            if do_chip_erase {
-                log::debug!("    Doing chip erase...");
-                flasher.run_erase(|active| active.erase_all())?;
+            // A chip erase would also erase all sectors without any data, whose contents
+            // can't be restored, and the fills are only read after it.
+            if do_chip_erase && options.keep_unwritten_bytes {
+                do_chip_erase = false;
+                log::warn!("Chip erase was the selected method to erase the sectors, but unwritten bytes have to be restored.");
+                log::warn!("A manual sector erase will be performed.");
             }
 
-            for region in regions {
-                log::debug!(
-                    "    programming region: {:08x}-{:08x} ({} bytes)",
-                    region.range.start,
-                    region.range.end,
-                    region.range.end - region.range.start
-                );
+            let result = Self::program_regions(
+                &mut flasher,
+                &self.builder,
+                &regions,
+                do_chip_erase,
+                self.pre_program_hook.as_deref(),
+                &options,
+            );
 
-                // Program the data.
-                flasher.program(
-                    &region,
-                    &self.builder,
-                    options.keep_unwritten_bytes,
-                    true,
-                    options.skip_erase || do_chip_erase,
-                    options.progress.unwrap_or(&FlashProgress::new(|_| {})),
-                )?;
+            // The RAM of the application is restored after a cancellation as well.
+            if matches!(result, Ok(()) | Err(FlashError::Cancelled)) {
+                flasher.restore_ram()?;
             }
+
+            result?;
         }
 
         log::debug!("committing RAM!");
@@ -418,6 +562,177 @@ impl FlashLoader {
         Ok(())
     }
 
+    /// Group the NVM regions with data by flash algorithm and core.
+    fn regions_by_algorithm(
+        &self,
+        target: &Target,
+    ) -> Result<HashMap<(String, String), Vec<NvmRegion>>, FlashError> {
+        let mut algos: HashMap<(String, String), Vec<NvmRegion>> = HashMap::new();
+
+        // Iterate all NvmRegions and group them by flash algorithm.
+        // This avoids loading the same algorithm twice if it's used for two regions.
+        //
+        // This also ensures correct operation when chip erase is used. We assume doing a chip erase
+        // using a given algorithm erases all regions controlled by it. Therefore, we must do
+        // chip erase once per algorithm, not once per region. Otherwise subsequent chip erases will
+        // erase previous regions' flashed contents.
+        log::debug!("Regions:");
+        for region in &self.memory_map {
+            if let MemoryRegion::Nvm(region) = region {
+                log::debug!(
+                    "    region: {:08x}-{:08x} ({} bytes)",
+                    region.range.start,
+                    region.range.end,
+                    region.range.end - region.range.start
+                );
+
+                // If we have no data in this region, ignore it.
+                // This avoids uselessly initializing and deinitializing its flash algorithm.
+                if !self.builder.has_data_in_range(&region.range) {
+                    log::debug!("     -- empty, ignoring!");
+                    continue;
+                }
+
+                let algo = Self::get_flash_algorithm_for_region(region, target)?;
+
+                let entry = algos
+                    .entry((
+                        algo.name.clone(),
+                        region
+                            .cores
+                            .first()
+                            .ok_or_else(|| FlashError::NoNvmCoreAccess(region.clone()))?
+                            .clone(),
+                    ))
+                    .or_default();
+                entry.push(region.clone());
+
+                log::debug!("     -- using algorithm: {}", algo.name);
+            }
+        }
+
+        Ok(algos)
+    }
+
+    /// Plan what [`commit`](Self::commit) would erase and program with `options`, without
+    /// accessing the target.
+    ///
+    /// This lays out the sectors and pages of all NVM regions like a download, so it can be
+    /// used to validate linker scripts and partition layouts. The pre-program hook is not
+    /// called, because the fills of the pages are not read from the target.
+    pub fn plan(
+        &self,
+        target: &Target,
+        options: &DownloadOptions<'_>,
+    ) -> Result<FlashPlan, FlashError> {
+        let mut plan = FlashPlan::default();
+
+        let mut algos = self
+            .regions_by_algorithm(target)?
+            .into_iter()
+            .collect::<Vec<_>>();
+        algos.sort_by_key(|(_, regions)| regions[0].range.start);
+
+        for ((algo_name, core_name), regions) in algos {
+            // This can't fail, algo_name comes from the target.
+            let raw_algo = target.flash_algorithm_by_name(&algo_name).unwrap();
+            let core = target.core_index_by_name(&core_name).unwrap();
+
+            let algo = assemble_algorithm(target, core, raw_algo)?;
+            let properties = &algo.flash_properties;
+            let timeouts = &options.timeouts;
+
+            // A chip erase is replaced by sector erases like in `commit`.
+            let chip_erase = options.do_chip_erase
+                && algo.pc_erase_all.is_some()
+                && !options.keep_unwritten_bytes
+                && !options.skip_erase;
+
+            plan.max_duration += timeouts.init_timeout() + timeouts.uninit_timeout();
+
+            if chip_erase {
+                plan.max_duration += timeouts.erase_all_timeout();
+            }
+
+            for region in regions {
+                let layout = self.builder.build_sectors_and_pages(
+                    &region,
+                    &algo,
+                    options.keep_unwritten_bytes,
+                )?;
+
+                if !chip_erase && !options.skip_erase {
+                    plan.max_duration +=
+                        timeouts.erase_sector_timeout(properties) * layout.sectors().len() as u32;
+                }
+                plan.max_duration +=
+                    timeouts.program_page_timeout(properties) * layout.pages().len() as u32;
+
+                plan.regions.push(RegionPlan {
+                    algorithm: algo_name.clone(),
+                    range: region.range.clone(),
+                    layout,
+                    chip_erase,
+                    skip_erase: options.skip_erase,
+                });
+            }
+        }
+
+        for region in &self.memory_map {
+            if let MemoryRegion::Ram(region) = region {
+                for (address, data) in self.builder.data_in_range(&region.range) {
+                    plan.ram_blocks.push(address..address + data.len() as u32);
+                }
+            }
+        }
+
+        Ok(plan)
+    }
+
+    /// Erase the chip if `do_chip_erase` is set, and program the data of all `regions`
+    /// which use the flash algorithm of `flasher`.
+    fn program_regions(
+        flasher: &mut Flasher<'_>,
+        builder: &FlashBuilder,
+        regions: &[NvmRegion],
+        do_chip_erase: bool,
+        pre_program_hook: Option<&PreProgramHook>,
+        options: &DownloadOptions<'_>,
+    ) -> Result<(), FlashError> {
+        if do_chip_erase {
+            if let Some(cancellation) = &options.cancellation {
+                if cancellation.is_cancelled() {
+                    return Err(FlashError::Cancelled);
+                }
+            }
+
+            log::debug!("    Doing chip erase...");
+            flasher.run_erase(|active| active.erase_all())?;
+        }
+
+        for region in regions {
+            log::debug!(
+                "    programming region: {:08x}-{:08x} ({} bytes)",
+                region.range.start,
+                region.range.end,
+                region.range.end - region.range.start
+            );
+
+            // Program the data.
+            flasher.program(
+                region,
+                builder,
+                options.keep_unwritten_bytes,
+                !options.disable_double_buffering,
+                options.skip_erase || do_chip_erase,
+                pre_program_hook,
+                options.progress.unwrap_or(&FlashProgress::new(|_| {})),
+            )?;
+        }
+
+        Ok(())
+    }
+
     /// Try to find a flash algorithm for the given NvmRegion.
     /// Errors when:
     /// - there's no algo for the region.
@@ -463,3 +778,150 @@ impl FlashLoader {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use probe_rs_target::{
+        FlashPartition, MemoryRegion, NvmRegion, RamRegion, TargetDescriptionSource,
+    };
+
+    use std::time::Duration;
+
+    use super::FlashLoader;
+    use crate::config::get_target_by_name;
+    use crate::flashing::{DownloadOptions, FlashError};
+
+    #[test]
+    fn data_in_alias_is_translated() {
+        let memory_map = vec![
+            MemoryRegion::Nvm(NvmRegion {
+                range: 0x0000_0000..0x0001_0000,
+                is_boot_memory: true,
+                cores: vec!["main".into()],
+                access_port: None,
+                alias: Some(0x1000_0000),
+            }),
+            MemoryRegion::Ram(RamRegion {
+                range: 0x2000_0000..0x2001_0000,
+                is_boot_memory: false,
+                cores: vec!["main".into()],
+                access_port: None,
+                alias: Some(0x3000_0000),
+            }),
+        ];
+
+        let mut loader = FlashLoader::new(memory_map, TargetDescriptionSource::BuiltIn);
+
+        loader.add_data(0x1000_0100, &[1, 2, 3, 4]).unwrap();
+        loader.add_data(0x3000_0000, &[5, 6]).unwrap();
+        loader.add_data(0x0000_0200, &[7, 8]).unwrap();
+
+        let data: Vec<_> = loader
+            .builder
+            .data
+            .iter()
+            .map(|(address, data)| (*address, data.clone()))
+            .collect();
+
+        assert_eq!(
+            data,
+            vec![
+                (0x0000_0100, vec![1, 2, 3, 4]),
+                (0x0000_0200, vec![7, 8]),
+                (0x2000_0000, vec![5, 6]),
+            ]
+        );
+
+        // The end of the alias is not in the memory map.
+        assert!(loader.add_data(0x1000_fffe, &[0; 4]).is_err());
+    }
+
+    #[test]
+    fn partitions() {
+        let memory_map = vec![MemoryRegion::Nvm(NvmRegion {
+            range: 0x0000_0000..0x0001_0000,
+            is_boot_memory: true,
+            cores: vec!["main".into()],
+            access_port: None,
+            alias: None,
+        })];
+
+        let mut loader = FlashLoader::new(memory_map, TargetDescriptionSource::BuiltIn);
+
+        loader.add_partition(FlashPartition {
+            name: "bootloader".into(),
+            range: 0x0000_0000..0x0000_4000,
+            protected: true,
+        });
+        loader.add_partition(FlashPartition {
+            name: "app".into(),
+            range: 0x0000_4000..0x0001_0000,
+            protected: false,
+        });
+
+        loader.add_data_to_partition("app", 0x100, &[1, 2]).unwrap();
+        loader
+            .add_data_to_partition("bootloader", 0, &[3, 4])
+            .unwrap();
+
+        assert!(matches!(
+            loader.add_data(0x0000_3ffe, &[0; 4]),
+            Err(FlashError::PartitionProtected {
+                address: 0x0000_3ffe,
+                ..
+            })
+        ));
+        assert!(matches!(
+            loader.add_data_to_partition("app", 0xbffe, &[0; 4]),
+            Err(FlashError::DataOutsidePartition { .. })
+        ));
+        assert!(matches!(
+            loader.add_data_to_partition("settings", 0, &[0]),
+            Err(FlashError::PartitionNotFound(_))
+        ));
+
+        let data: Vec<_> = loader
+            .builder
+            .data
+            .iter()
+            .map(|(address, data)| (*address, data.clone()))
+            .collect();
+
+        assert_eq!(
+            data,
+            vec![(0x0000_0000, vec![3, 4]), (0x0000_4100, vec![1, 2])]
+        );
+    }
+
+    #[test]
+    fn dry_run_plan() {
+        let target = get_target_by_name("nRF52840_xxAA").unwrap();
+        let mut loader = target.flash_loader();
+
+        // Two sectors of the flash, the UICR and RAM.
+        loader.add_data(0x0000_0ff0, &[0; 0x20]).unwrap();
+        loader.add_data(0x1000_1080, &[1, 2, 3, 4]).unwrap();
+        loader.add_data(0x2000_0000, &[5, 6, 7, 8]).unwrap();
+
+        let plan = loader.plan(&target, &DownloadOptions::new()).unwrap();
+
+        assert_eq!(plan.regions.len(), 2);
+        assert_eq!(plan.regions[0].range, 0x0000_0000..0x0010_0000);
+        assert_eq!(plan.regions[0].layout.sectors().len(), 2);
+        assert_eq!(plan.regions[1].range, 0x1000_1000..0x1000_2000);
+        assert_eq!(plan.erased_bytes(), 0x3000);
+        assert_eq!(plan.programmed_bytes(), 0x3000);
+        assert_eq!(plan.ram_blocks, vec![0x2000_0000..0x2000_0004]);
+
+        // Init and uninit, three sector erases and three pages.
+        assert_eq!(plan.max_duration, Duration::from_secs(2 + 2 + 3 * 3 + 3));
+
+        let mut options = DownloadOptions::new();
+        options.do_chip_erase = true;
+
+        let plan = loader.plan(&target, &options).unwrap();
+
+        assert!(plan.regions.iter().all(|region| region.chip_erase));
+        assert_eq!(plan.max_duration, Duration::from_secs(2 + 2 + 30 + 3));
+    }
+}
@@ -45,6 +45,7 @@
 //!
 //!
 
+mod blank_check;
 mod builder;
 mod download;
 mod erase;
@@ -52,16 +53,25 @@ mod error;
 mod flash_algorithm;
 mod flasher;
 mod loader;
+mod plan;
 mod progress;
+mod provisioning;
+mod ram_image;
 mod visualizer;
 
 use builder::*;
 use flasher::*;
 
+pub use blank_check::*;
+pub use builder::{FlashDataBlockSpan, FlashFill, FlashLayout, FlashPage, FlashSector};
+
 pub use download::*;
 pub use erase::*;
 pub use error::*;
 pub use flash_algorithm::*;
 pub use loader::*;
+pub use plan::*;
 pub use progress::*;
+pub use provisioning::*;
+pub use ram_image::*;
 pub use visualizer::*;
@@ -0,0 +1,132 @@
+use std::fmt;
+use std::ops::Range;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use super::FlashLayout;
+
+/// The operations of a flash algorithm on one NVM region, see [`FlashPlan`].
+#[derive(Debug, Clone, Serialize)]
+pub struct RegionPlan {
+    /// The name of the flash algorithm which programs the region.
+    pub algorithm: String,
+    /// The address range of the region.
+    pub range: Range<u32>,
+    /// The sectors and pages which are erased and programmed.
+    pub layout: FlashLayout,
+    /// The sectors are erased by a chip erase of the algorithm, instead of one by one.
+    pub chip_erase: bool,
+    /// The sectors are not erased, see [`DownloadOptions::skip_erase`](super::DownloadOptions::skip_erase).
+    pub skip_erase: bool,
+}
+
+impl RegionPlan {
+    /// The number of bytes in the sectors which are erased.
+    ///
+    /// With a chip erase, the whole flash of the algorithm is erased, not only these sectors.
+    pub fn erased_bytes(&self) -> u32 {
+        if self.skip_erase {
+            return 0;
+        }
+
+        self.layout
+            .sectors()
+            .iter()
+            .map(|sector| sector.size())
+            .sum()
+    }
+
+    /// The number of bytes in the pages which are programmed.
+    pub fn programmed_bytes(&self) -> u32 {
+        self.layout.pages().iter().map(|page| page.size()).sum()
+    }
+}
+
+/// What a download would erase and program, without touching the target.
+///
+/// This is returned by [`FlashLoader::plan`](super::FlashLoader::plan), and logged by
+/// [`FlashLoader::commit`](super::FlashLoader::commit) for a
+/// [dry run](super::DownloadOptions::dry_run).
+///
+/// The plan can be serialized, so tools can render the flash layout of each region.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FlashPlan {
+    /// The NVM regions which are programmed, grouped by flash algorithm.
+    pub regions: Vec<RegionPlan>,
+    /// The blocks of data which are written to RAM directly.
+    pub ram_blocks: Vec<Range<u32>>,
+    /// The longest time the flash algorithms may take, from the timeouts of the routines.
+    ///
+    /// This is an upper bound of the time spent on the target, the actual download is
+    /// usually a lot faster.
+    pub max_duration: Duration,
+}
+
+impl FlashPlan {
+    /// The number of bytes in the sectors which are erased.
+    pub fn erased_bytes(&self) -> u32 {
+        self.regions.iter().map(RegionPlan::erased_bytes).sum()
+    }
+
+    /// The number of bytes in the pages which are programmed.
+    pub fn programmed_bytes(&self) -> u32 {
+        self.regions.iter().map(RegionPlan::programmed_bytes).sum()
+    }
+}
+
+impl fmt::Display for FlashPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for region in &self.regions {
+            writeln!(
+                f,
+                "{:08x}-{:08x} with {}:",
+                region.range.start, region.range.end, region.algorithm
+            )?;
+
+            if region.skip_erase {
+                writeln!(f, "    no erase")?;
+            } else if region.chip_erase {
+                writeln!(f, "    chip erase")?;
+            } else {
+                for sector in region.layout.sectors() {
+                    writeln!(
+                        f,
+                        "    erase   {:08x}-{:08x} ({} bytes)",
+                        sector.address(),
+                        sector.address() + sector.size(),
+                        sector.size()
+                    )?;
+                }
+            }
+
+            for page in region.layout.pages() {
+                writeln!(
+                    f,
+                    "    program {:08x}-{:08x} ({} bytes)",
+                    page.address(),
+                    page.address() + page.size(),
+                    page.size()
+                )?;
+            }
+        }
+
+        for block in &self.ram_blocks {
+            writeln!(
+                f,
+                "write to RAM {:08x}-{:08x} ({} bytes)",
+                block.start,
+                block.end,
+                block.end - block.start
+            )?;
+        }
+
+        write!(
+            f,
+            "{} bytes erased, {} bytes programmed, at most {:.1} s",
+            self.erased_bytes(),
+            self.programmed_bytes(),
+            self.max_duration.as_secs_f32()
+        )
+    }
+}
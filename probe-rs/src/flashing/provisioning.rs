@@ -0,0 +1,289 @@
+//! Writing device-unique data, e.g. serial numbers or calibration values, to the
+//! user rows, `UICR` or OTP memory of a device.
+
+use probe_rs_target::MemoryRegion;
+use serde::{Deserialize, Serialize};
+
+use super::{DownloadOptions, FlashError, FlashLoader};
+use crate::memory::MemoryInterface;
+use crate::Session;
+
+/// The size of the words of OTP memory, which can only be programmed once.
+const OTP_WORD_SIZE: u32 = 4;
+
+/// A chunk of device-unique data, see [`Provisioning`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProvisioningItem {
+    /// The name of the item, used in errors, e.g. `serial_number`.
+    pub name: String,
+    /// The address the data is written to.
+    pub address: u32,
+    /// The data.
+    pub data: Vec<u8>,
+    /// The data is written to one-time programmable memory.
+    ///
+    /// Words which are already programmed are never written again. If they contain the
+    /// data already, the item is skipped, otherwise provisioning fails.
+    #[serde(default)]
+    pub one_time_programmable: bool,
+}
+
+/// A specification of the device-unique data of a device.
+///
+/// The specification can be built in code, or deserialized, e.g. from a YAML file:
+///
+/// ```yaml
+/// items:
+///   - name: serial_number
+///     address: 0x10001080
+///     data: [0x12, 0x34, 0x56, 0x78]
+///     one_time_programmable: true
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Provisioning {
+    /// The items which are written.
+    pub items: Vec<ProvisioningItem>,
+}
+
+impl Provisioning {
+    /// Create an empty specification.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an item, which is written to non-volatile memory or RAM.
+    pub fn add(&mut self, name: impl Into<String>, address: u32, data: &[u8]) -> &mut Self {
+        self.push(name.into(), address, data, false)
+    }
+
+    /// Add an item, which is written to one-time programmable memory.
+    pub fn add_otp(&mut self, name: impl Into<String>, address: u32, data: &[u8]) -> &mut Self {
+        self.push(name.into(), address, data, true)
+    }
+
+    fn push(
+        &mut self,
+        name: String,
+        address: u32,
+        data: &[u8],
+        one_time_programmable: bool,
+    ) -> &mut Self {
+        self.items.push(ProvisioningItem {
+            name,
+            address,
+            data: data.to_vec(),
+            one_time_programmable,
+        });
+        self
+    }
+}
+
+/// The state of an OTP item in the memory of the device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OtpState {
+    /// The data of the item is written already.
+    Programmed,
+    /// All words which have to be written are still erased.
+    Writable,
+    /// The word at the address is already programmed with different data.
+    Conflict(u32),
+}
+
+/// Determine the state of the OTP item at `address`, whose memory contains `current`.
+fn otp_state(address: u32, current: &[u8], data: &[u8], erased_byte_value: u8) -> OtpState {
+    if current == data {
+        return OtpState::Programmed;
+    }
+
+    let mut offset = 0;
+
+    while offset < data.len() {
+        let word_address = address + offset as u32;
+        let length = (OTP_WORD_SIZE - word_address % OTP_WORD_SIZE) as usize;
+        let end = (offset + length).min(data.len());
+
+        let word = &current[offset..end];
+
+        if word != &data[offset..end] && word.iter().any(|&byte| byte != erased_byte_value) {
+            return OtpState::Conflict(word_address - word_address % OTP_WORD_SIZE);
+        }
+
+        offset = end;
+    }
+
+    OtpState::Writable
+}
+
+/// Read memory for provisioning, with the first core which can access it.
+fn read_memory(session: &mut Session, address: u32, data: &mut [u8]) -> Result<(), FlashError> {
+    let region = session
+        .target()
+        .get_memory_region_by_address(address)
+        .ok_or(FlashError::NoSuitableNvm {
+            start: address,
+            end: address + data.len() as u32,
+            description_source: session.target().source().clone(),
+        })?;
+
+    let core_name = match region {
+        MemoryRegion::Ram(region) => region.cores.first(),
+        MemoryRegion::Generic(region) => region.cores.first(),
+        MemoryRegion::Nvm(region) => region.cores.first(),
+    }
+    .cloned();
+
+    let core_index = core_name
+        .and_then(|name| session.target().core_index_by_name(&name))
+        .unwrap_or(0);
+
+    let mut core = session.core(core_index).map_err(FlashError::Core)?;
+    core.read(address, data).map_err(FlashError::Core)
+}
+
+/// The erased value of the NVM at `address`.
+fn erased_byte_value(session: &Session, address: u32) -> Result<u8, FlashError> {
+    let region = session
+        .target()
+        .memory_map
+        .iter()
+        .find_map(|region| match region {
+            MemoryRegion::Nvm(region) if region.range.contains(&address) => Some(region),
+            _ => None,
+        })
+        .ok_or(FlashError::NoSuitableNvm {
+            start: address,
+            end: address + 1,
+            description_source: session.target().source().clone(),
+        })?;
+
+    let algorithm = FlashLoader::get_flash_algorithm_for_region(region, session.target())?;
+
+    Ok(algorithm.flash_properties.erased_byte_value)
+}
+
+/// Write the device-unique data of `provisioning` to the device, and verify it.
+///
+/// Items in non-volatile memory are written without changing the other contents of the
+/// erased sectors. Items in one-time programmable memory are written without erasing,
+/// and only if all words they change are still erased, otherwise
+/// [`FlashError::OtpAlreadyProgrammed`] is returned before anything is written. OTP items
+/// which are already programmed with their data are skipped.
+///
+/// After writing, all items are read back, and [`FlashError::ProvisioningVerify`] is
+/// returned if one of them differs.
+pub fn provision(session: &mut Session, provisioning: &Provisioning) -> Result<(), FlashError> {
+    let mut loader = session.target().flash_loader();
+    let mut otp_loader = session.target().flash_loader();
+
+    let mut has_data = false;
+    let mut has_otp_data = false;
+
+    for item in &provisioning.items {
+        if !item.one_time_programmable {
+            loader.add_data(item.address, &item.data)?;
+            has_data = true;
+            continue;
+        }
+
+        let erased_byte_value = erased_byte_value(session, item.address)?;
+
+        let mut current = vec![0; item.data.len()];
+        read_memory(session, item.address, &mut current)?;
+
+        match otp_state(item.address, &current, &item.data, erased_byte_value) {
+            OtpState::Programmed => {
+                log::info!("{} is already programmed, skipping it.", item.name);
+            }
+            OtpState::Writable => {
+                otp_loader.add_data(item.address, &item.data)?;
+                has_otp_data = true;
+            }
+            OtpState::Conflict(address) => {
+                return Err(FlashError::OtpAlreadyProgrammed {
+                    name: item.name.clone(),
+                    address,
+                })
+            }
+        }
+    }
+
+    if has_data {
+        let mut options = DownloadOptions::new();
+        options.keep_unwritten_bytes = true;
+
+        loader.commit(session, options)?;
+    }
+
+    if has_otp_data {
+        let mut options = DownloadOptions::new();
+        options.skip_erase = true;
+
+        otp_loader.commit(session, options)?;
+    }
+
+    for item in &provisioning.items {
+        let mut written = vec![0; item.data.len()];
+        read_memory(session, item.address, &mut written)?;
+
+        if written != item.data {
+            return Err(FlashError::ProvisioningVerify {
+                name: item.name.clone(),
+                address: item.address,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{otp_state, OtpState, Provisioning};
+
+    #[test]
+    fn otp_words() {
+        let data = [1, 2, 3, 4, 5, 6];
+
+        assert_eq!(
+            otp_state(0x1002, &[0xff; 6], &data, 0xff),
+            OtpState::Writable
+        );
+        assert_eq!(otp_state(0x1002, &data, &data, 0xff), OtpState::Programmed);
+
+        // A partially programmed word with the same data can be written again.
+        assert_eq!(
+            otp_state(0x1002, &[1, 2, 0xff, 0xff, 0xff, 0xff], &data, 0xff),
+            OtpState::Writable
+        );
+
+        // The second word is programmed with different data.
+        assert_eq!(
+            otp_state(0x1002, &[1, 2, 3, 0, 0xff, 0xff], &data, 0xff),
+            OtpState::Conflict(0x1004)
+        );
+    }
+
+    #[test]
+    fn deserialize_provisioning() {
+        let yaml = "
+items:
+  - name: serial_number
+    address: 0x10001080
+    data: [0x12, 0x34]
+    one_time_programmable: true
+  - name: calibration
+    address: 0x0007f000
+    data: [1]
+";
+
+        let mut expected = Provisioning::new();
+        expected
+            .add_otp("serial_number", 0x1000_1080, &[0x12, 0x34])
+            .add("calibration", 0x0007_f000, &[1]);
+
+        assert_eq!(
+            serde_yaml::from_str::<Provisioning>(yaml).unwrap(),
+            expected
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use std::{path::Path, time::Duration};
+
+use object::Object;
+use probe_rs_target::{MemoryRange, MemoryRegion};
+
+use super::{extract_from_elf, FileDownloadError, FlashError};
+use crate::{Architecture, CoreRegisterAddress, MemoryInterface, Session};
+
+/// Address of the ARM `VTOR` register.
+const ARM_VTOR: u32 = 0xE000_ED08;
+
+/// The initial register values of a program loaded into RAM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct StartState {
+    pc: u32,
+    stack_pointer: Option<u32>,
+    vector_table: Option<u32>,
+}
+
+/// Loads the ELF file at `path` into the RAM of the target, and starts executing it.
+///
+/// All loadable segments have to be contained in the RAM regions of the target, and
+/// are written after the core has been reset and halted.
+///
+/// On ARM, if the segment with the lowest address starts with a vector table whose
+/// reset vector points into the loaded program, the stack pointer and program counter
+/// are taken from it, and `VTOR` is set to its address. Otherwise, and on all other
+/// architectures, execution starts at the entry point of the ELF file, and the program
+/// has to set up the stack pointer itself.
+pub fn load_ram_image<P: AsRef<Path>>(
+    session: &mut Session,
+    path: P,
+) -> Result<(), FileDownloadError> {
+    let elf_data = std::fs::read(path.as_ref())?;
+
+    let entry = object::File::parse(&elf_data[..])?.entry() as u32;
+
+    let mut segments = Vec::new();
+    extract_from_elf(&mut segments, &elf_data)?;
+    segments.sort_by_key(|segment| segment.address);
+
+    if segments.is_empty() {
+        return Err(FileDownloadError::NoLoadableSegments);
+    }
+
+    // Find the RAM region of each segment, and the core to load the program with.
+    let mut core_name = None;
+
+    for segment in &segments {
+        let range = segment.address..segment.address + segment.data.len() as u32;
+
+        let region = session
+            .target()
+            .memory_map
+            .iter()
+            .find_map(|region| match region {
+                MemoryRegion::Ram(ram) if ram.range.contains_range(&range) => Some(ram),
+                _ => None,
+            })
+            .ok_or(FlashError::NoSuitableRam {
+                start: range.start,
+                end: range.end,
+            })?;
+
+        if core_name.is_none() {
+            core_name = Some(
+                region
+                    .cores
+                    .first()
+                    .ok_or_else(|| FlashError::NoRamCoreAccess(region.clone()))?
+                    .clone(),
+            );
+        }
+    }
+
+    // This can't fail, the core name comes from the target.
+    let core_index = session
+        .target()
+        .core_index_by_name(&core_name.unwrap())
+        .unwrap();
+
+    let mut core = session.core(core_index).map_err(FlashError::Core)?;
+
+    core.reset_and_halt(Duration::from_millis(500))
+        .map_err(FlashError::Core)?;
+
+    for segment in &segments {
+        log::debug!(
+            "Loading segment {:#010x}..{:#010x} ({:?}) into RAM",
+            segment.address,
+            segment.address + segment.data.len() as u32,
+            segment.section_names
+        );
+
+        core.write_8(segment.address, segment.data)
+            .map_err(FlashError::Core)?;
+    }
+
+    let images: Vec<(u32, &[u8])> = segments
+        .iter()
+        .map(|segment| (segment.address, segment.data))
+        .collect();
+
+    let start = start_state(core.architecture(), &images, entry);
+
+    log::info!("Starting RAM image at {:#010x}", start.pc);
+
+    let regs = core.registers();
+
+    if let Some(vector_table) = start.vector_table {
+        core.write_word_32(ARM_VTOR, vector_table)
+            .map_err(FlashError::Core)?;
+    }
+
+    if let Some(stack_pointer) = start.stack_pointer {
+        core.write_core_reg(regs.stack_pointer().address, stack_pointer)
+            .map_err(FlashError::Core)?;
+    }
+
+    core.write_core_reg(CoreRegisterAddress::from(regs.program_counter()), start.pc)
+        .map_err(FlashError::Core)?;
+
+    core.run().map_err(FlashError::Core)?;
+
+    Ok(())
+}
+
+/// Determine the initial register values of a program which consists of `images`,
+/// sorted by their address, and has the ELF entry point `entry`.
+fn start_state(architecture: Architecture, images: &[(u32, &[u8])], entry: u32) -> StartState {
+    let from_entry = StartState {
+        // The entry point of a Thumb function has the Thumb bit set.
+        pc: match architecture {
+            Architecture::Arm => entry & !1,
+            _ => entry,
+        },
+        stack_pointer: None,
+        vector_table: None,
+    };
+
+    if architecture != Architecture::Arm {
+        return from_entry;
+    }
+
+    let (address, data) = match images.first() {
+        Some(&(address, data)) if data.len() >= 8 => (address, data),
+        _ => return from_entry,
+    };
+
+    let stack_pointer = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+    let reset_vector = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+
+    // The reset vector has to be a Thumb address inside the loaded program.
+    let pc = reset_vector & !1;
+    let in_image = images
+        .iter()
+        .any(|(start, data)| (*start..*start + data.len() as u32).contains(&pc));
+
+    if reset_vector & 1 == 0 || !in_image {
+        return from_entry;
+    }
+
+    StartState {
+        pc,
+        stack_pointer: Some(stack_pointer),
+        vector_table: Some(address),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{start_state, StartState};
+    use crate::Architecture;
+
+    #[test]
+    fn start_from_vector_table() {
+        let mut vector_table = vec![0u8; 0x40];
+        vector_table[0..4].copy_from_slice(&0x2000_8000u32.to_le_bytes());
+        vector_table[4..8].copy_from_slice(&0x2000_0021u32.to_le_bytes());
+
+        let images: [(u32, &[u8]); 1] = [(0x2000_0000, &vector_table)];
+
+        assert_eq!(
+            start_state(Architecture::Arm, &images, 0x2000_0030),
+            StartState {
+                pc: 0x2000_0020,
+                stack_pointer: Some(0x2000_8000),
+                vector_table: Some(0x2000_0000),
+            }
+        );
+
+        // RISC-V programs always start at the entry point.
+        assert_eq!(
+            start_state(Architecture::Riscv, &images, 0x2000_0030),
+            StartState {
+                pc: 0x2000_0030,
+                stack_pointer: None,
+                vector_table: None,
+            }
+        );
+    }
+
+    #[test]
+    fn start_from_entry_without_vector_table() {
+        // The reset vector points outside of the loaded program.
+        let mut data = vec![0u8; 0x40];
+        data[0..4].copy_from_slice(&0x2000_8000u32.to_le_bytes());
+        data[4..8].copy_from_slice(&0x0800_0101u32.to_le_bytes());
+
+        let images: [(u32, &[u8]); 1] = [(0x2000_0000, &data)];
+
+        assert_eq!(
+            start_state(Architecture::Arm, &images, 0x2000_0011),
+            StartState {
+                pc: 0x2000_0010,
+                stack_pointer: None,
+                vector_table: None,
+            }
+        );
+    }
+}
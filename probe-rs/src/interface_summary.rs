@@ -0,0 +1,239 @@
+#![warn(missing_docs)]
+
+//! A summary of how the probe is connected to the target.
+//!
+//! The summary is collected once when the session attaches, and can be queried with
+//! [`Session::interface_summary`] for the rest of the session, so front-ends can show the
+//! debug ports, access ports and cores of the chip without discovering them themselves.
+
+use probe_rs_target::CoreAccessOptions;
+
+use crate::architecture::arm::{
+    ap::{GenericAp, MemoryAp},
+    communication_interface::ArmProbeInterface,
+    dp::{DebugPortVersion, DPIDR},
+    ApAddress, ApInformation, DpAddress,
+};
+use crate::{CoreType, Error, ScanChainElement, Session};
+
+/// The address of the `CPUID` register of Cortex-M cores.
+const CPUID: u32 = 0xE000_ED00;
+
+/// The `CPUID` register of a Cortex-M core, which identifies the core and its revision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuId {
+    /// The implementer of the core, `0x41` for ARM.
+    pub implementer: u8,
+    /// The major revision of the core, the `n` in `rnpm`.
+    pub variant: u8,
+    /// The part number of the core, assigned by the implementer.
+    pub part_number: u16,
+    /// The minor revision of the core, the `m` in `rnpm`.
+    pub revision: u8,
+}
+
+impl From<u32> for CpuId {
+    fn from(value: u32) -> Self {
+        Self {
+            implementer: (value >> 24) as u8,
+            variant: ((value >> 20) & 0xf) as u8,
+            part_number: ((value >> 4) & 0xfff) as u16,
+            revision: (value & 0xf) as u8,
+        }
+    }
+}
+
+impl CpuId {
+    /// The name of the core, if it is a known core designed by ARM.
+    pub fn part_name(&self) -> Option<&'static str> {
+        if self.implementer != 0x41 {
+            return None;
+        }
+
+        match self.part_number {
+            0xc20 => Some("Cortex-M0"),
+            0xc21 => Some("Cortex-M1"),
+            0xc23 => Some("Cortex-M3"),
+            0xc24 => Some("Cortex-M4"),
+            0xc27 => Some("Cortex-M7"),
+            0xc60 => Some("Cortex-M0+"),
+            0xd20 => Some("Cortex-M23"),
+            0xd21 => Some("Cortex-M33"),
+            0xd22 => Some("Cortex-M55"),
+            0xd23 => Some("Cortex-M85"),
+            0xd31 => Some("Cortex-M35P"),
+            _ => None,
+        }
+    }
+}
+
+/// A debug port of an ARM chip, which is used by one of the cores of the target.
+#[derive(Debug, Clone)]
+pub struct DebugPortSummary {
+    /// The address of the debug port.
+    pub dp: DpAddress,
+    /// The value of the `DPIDR` register, the IDCODE of the debug port.
+    ///
+    /// `None` for DPv0 debug ports, and for probes which don't give access to the
+    /// registers of the debug port, like the ST-Link.
+    pub idcode: Option<u32>,
+    /// The version of the debug port, from `DPIDR`.
+    pub version: Option<DebugPortVersion>,
+    /// The access ports of the debug port.
+    pub access_ports: Vec<ApInformation>,
+}
+
+/// A core of the target.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoreSummary {
+    /// The name of the core in the target description.
+    pub name: String,
+    /// The type of the core in the target description.
+    pub core_type: CoreType,
+    /// The `CPUID` register of the core, for ARM cores, if it could be read.
+    pub cpuid: Option<CpuId>,
+}
+
+/// How the probe is connected to the target, see [`Session::interface_summary`].
+#[derive(Debug, Clone, Default)]
+pub struct InterfaceSummary {
+    /// The TAPs in the JTAG scan chain, starting with the TAP closest to TDO.
+    ///
+    /// This is empty for SWD, and for probes which don't report the scan chain.
+    pub scan_chain: Vec<ScanChainElement>,
+    /// The debug ports used by the cores of an ARM target, empty for other targets.
+    pub debug_ports: Vec<DebugPortSummary>,
+    /// The cores of the target, in the order of the target description.
+    pub cores: Vec<CoreSummary>,
+}
+
+impl InterfaceSummary {
+    /// Collect the summary of the attached `session`.
+    ///
+    /// Failures to read parts of the summary are logged, and leave these parts out, so
+    /// they don't fail the attach.
+    pub(crate) fn collect(session: &mut Session, scan_chain: Vec<ScanChainElement>) -> Self {
+        let core_aps: Vec<Option<ApAddress>> = session
+            .target()
+            .cores
+            .iter()
+            .map(|core| match &core.core_access_options {
+                CoreAccessOptions::Arm(options) => Some(ApAddress {
+                    dp: match options.psel {
+                        0 => DpAddress::Default,
+                        x => DpAddress::Multidrop(x),
+                    },
+                    ap: options.ap,
+                }),
+                _ => None,
+            })
+            .collect();
+
+        let mut cores: Vec<CoreSummary> = session
+            .target()
+            .cores
+            .iter()
+            .map(|core| CoreSummary {
+                name: core.name.clone(),
+                core_type: core.core_type,
+                cpuid: None,
+            })
+            .collect();
+
+        let mut debug_ports = Vec::new();
+
+        // Only ARM targets have debug ports.
+        if let Ok(interface) = session.get_arm_interface() {
+            for ap in core_aps.iter().flatten() {
+                if debug_ports
+                    .iter()
+                    .any(|port: &DebugPortSummary| port.dp == ap.dp)
+                {
+                    continue;
+                }
+
+                match debug_port_summary(interface, ap.dp) {
+                    Ok(summary) => debug_ports.push(summary),
+                    Err(error) => {
+                        log::warn!("Failed to read the summary of DP {:x?}: {}", ap.dp, error)
+                    }
+                }
+            }
+
+            for (core, ap) in cores.iter_mut().zip(&core_aps) {
+                if let Some(ap) = ap {
+                    match read_cpuid(interface, *ap) {
+                        Ok(cpuid) => core.cpuid = Some(cpuid),
+                        Err(error) => {
+                            log::warn!("Failed to read the CPUID of {}: {}", core.name, error)
+                        }
+                    }
+                }
+            }
+        }
+
+        Self {
+            scan_chain,
+            debug_ports,
+            cores,
+        }
+    }
+}
+
+fn debug_port_summary(
+    interface: &mut dyn ArmProbeInterface,
+    dp: DpAddress,
+) -> Result<DebugPortSummary, Error> {
+    let idcode = interface.debug_port_id(dp)?;
+    let version = idcode.map(|idcode| DebugPortVersion::from(DPIDR::from(idcode).version()));
+
+    let mut access_ports = Vec::new();
+    for ap in 0..(interface.num_access_ports(dp)? as u8) {
+        let information = interface.ap_information(GenericAp::new(ApAddress { dp, ap }))?;
+        access_ports.push(information.clone());
+    }
+
+    Ok(DebugPortSummary {
+        dp,
+        idcode,
+        version,
+        access_ports,
+    })
+}
+
+fn read_cpuid(interface: &mut dyn ArmProbeInterface, ap: ApAddress) -> Result<CpuId, Error> {
+    let mut memory = interface.memory_interface(MemoryAp::new(ap))?;
+
+    Ok(CpuId::from(memory.read_word_32(CPUID)?))
+}
+
+impl Session {
+    /// How the probe is connected to the target: the JTAG scan chain, the debug ports and
+    /// access ports of ARM targets, and the `CPUID` of the cores.
+    ///
+    /// The summary is collected when the session attaches, so it is cheap to query.
+    pub fn interface_summary(&self) -> &InterfaceSummary {
+        &self.interface_summary
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CpuId;
+
+    #[test]
+    fn cortex_m4_cpuid() {
+        let cpuid = CpuId::from(0x410f_c241);
+
+        assert_eq!(
+            cpuid,
+            CpuId {
+                implementer: 0x41,
+                variant: 0,
+                part_number: 0xc24,
+                revision: 1,
+            }
+        );
+        assert_eq!(cpuid.part_name(), Some("Cortex-M4"));
+    }
+}
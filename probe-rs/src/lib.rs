@@ -66,28 +66,76 @@
 extern crate serde;
 
 pub mod architecture;
+#[cfg(feature = "async")]
+mod async_session;
+pub mod benchmark;
+mod breakpoints;
+mod cancel;
 pub mod config;
 mod core;
 pub mod debug;
 mod error;
+mod event;
 pub mod flashing;
+mod interface_summary;
+mod live_watch;
 mod memory;
+mod memory_map;
+mod multi_session;
+mod permissions;
 mod probe;
 mod session;
+mod session_config;
+mod shared_session;
+mod snapshot;
+#[cfg(feature = "svd")]
+pub mod svd;
 
-pub use crate::config::{CoreType, Target};
+#[cfg(feature = "async")]
+pub use crate::async_session::AsyncSession;
+pub use crate::breakpoints::{
+    BreakpointCondition, BreakpointHit, BreakpointLocation, BreakpointOptions, BreakpointType,
+    ConditionError, HitCondition, ManagedBreakpoint, ManagedBreakpointId,
+};
+pub use crate::cancel::CancellationToken;
+pub use crate::config::{CoreType, ResetKind, Target};
+#[cfg(feature = "disassembly")]
+pub use crate::core::Instruction;
 pub use crate::core::{
-    Architecture, Breakpoint, BreakpointId, CommunicationInterface, Core, CoreInformation,
-    CoreInterface, CoreList, CoreRegister, CoreRegisterAddress, CoreState, CoreStatus, HaltReason,
-    SpecificCoreState,
+    Architecture, Breakpoint, BreakpointCause, BreakpointId, CommunicationInterface, Core,
+    CoreDump, CoreEvent, CoreEventRecord, CoreInformation, CoreInterface, CoreList, CoreRegister,
+    CoreRegisterAddress, CoreState, CoreStatus, FaultStatus, HaltDetails, HaltReason,
+    LockupRecovery, MockCore, RegisterDescription, RegisterFile, SecurityState, SpecificCoreState,
+    StatusChange, StatusPoller, StepInterruptBehavior, Watchpoint, WatchpointKind,
 };
 pub use crate::error::Error;
+pub use crate::event::{ObserverId, SessionEvent};
+pub use crate::interface_summary::{CoreSummary, CpuId, DebugPortSummary, InterfaceSummary};
+pub use crate::live_watch::{LiveWatch, WatchSample, WatchedValue};
 pub use crate::memory::{Memory, MemoryInterface};
+pub use crate::memory_map::{MemoryKind, MemoryMapRegion};
+pub use crate::multi_session::{MultiDownloadOptions, MultiSession};
+pub use crate::permissions::Permissions;
+#[cfg(any(feature = "cmsisdap", feature = "webusb"))]
+pub use crate::probe::cmsisdap::{commands::SendError as CmsisDapSendError, CmsisDapTransport};
+#[cfg(feature = "webusb")]
+pub use crate::probe::cmsisdap::{WebUsbError, WebUsbTransport};
 pub use crate::probe::{
-    AttachMethod, DebugProbe, DebugProbeError, DebugProbeInfo, DebugProbeSelector, DebugProbeType,
-    Probe, ProbeCreationError, WireProtocol,
+    AttachMethod, BridgeInterfaces, DebugProbe, DebugProbeError, DebugProbeInfo,
+    DebugProbeSelector, DebugProbeType, Probe, ProbeBridge, ProbeCreationError, ProbeEvent,
+    ProbeWatcher, ProtocolTracer, ReconnectPolicy, ScanChainElement, SpiConfig, SpiMode,
+    SupportedSpeeds, TraceEvent, TraceRecord, UartConfig, UsbAccessHint, UsbOperation,
+    WireProtocol,
 };
 pub use crate::session::Session;
+pub use crate::session_config::{
+    GdbConfig, GdbResetBehavior, ResetConfig, RttChannelConfig, RttConfig, SessionConfig,
+    SessionConfigError,
+};
+pub use crate::shared_session::{Priority, SessionGuard, SharedSession};
+pub use crate::snapshot::{CoreSnapshot, SessionSnapshot};
 
 // TODO: Hide behind feature
-pub use crate::probe::fake_probe::FakeProbe;
+pub use crate::probe::fake_probe::{FakeProbe, ReplayError};
+#[cfg(feature = "simulator")]
+pub use crate::probe::simulator::{SimulatedTarget, SimulatorProbe};
@@ -0,0 +1,134 @@
+//! Sampling of variables while the core is running.
+
+use std::thread;
+use std::time::Duration;
+use web_time::Instant;
+
+use crate::debug::SymbolTable;
+use crate::{Core, Error};
+
+/// A memory region watched by a [`LiveWatch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchedValue {
+    /// The name of the value, e.g. the name of the variable.
+    pub name: String,
+    /// The address of the value.
+    pub address: u32,
+    /// The size of the value in bytes.
+    pub size: u32,
+}
+
+/// A sample of a [`WatchedValue`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchSample {
+    /// The index of the value in the [`LiveWatch`].
+    pub index: usize,
+    /// When the value was read.
+    pub timestamp: Instant,
+    /// The bytes of the value.
+    pub data: Vec<u8>,
+}
+
+impl WatchSample {
+    /// The first 8 bytes of the value as a little endian integer.
+    pub fn value(&self) -> u64 {
+        let mut bytes = [0u8; 8];
+        let length = self.data.len().min(8);
+        bytes[..length].copy_from_slice(&self.data[..length]);
+
+        u64::from_le_bytes(bytes)
+    }
+}
+
+/// Periodically reads a list of values without halting the core, e.g. to show them in a
+/// dashboard while tuning parameters of the program.
+///
+/// The values are read with [`Core::read_while_running`], so this only works on cores
+/// which support memory accesses while running.
+#[derive(Debug)]
+pub struct LiveWatch {
+    values: Vec<WatchedValue>,
+    interval: Duration,
+    last_sample: Option<Instant>,
+}
+
+impl LiveWatch {
+    /// Creates a watch, which samples the values at most once per `interval`.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            values: Vec::new(),
+            interval,
+            last_sample: None,
+        }
+    }
+
+    /// Watch `size` bytes at `address`, and return the index of the value.
+    pub fn add(&mut self, name: impl Into<String>, address: u32, size: u32) -> usize {
+        self.values.push(WatchedValue {
+            name: name.into(),
+            address,
+            size,
+        });
+
+        self.values.len() - 1
+    }
+
+    /// Watch the variable `path`, e.g. `my_crate::COUNTER`, and return the index of the value.
+    ///
+    /// Returns `None` if the symbol table contains no symbol of this name with a known size.
+    pub fn add_symbol(&mut self, symbols: &SymbolTable, path: &str) -> Option<usize> {
+        let symbol = symbols
+            .symbol_by_path(path)
+            .filter(|symbol| symbol.size > 0)?;
+        let (address, size) = (symbol.address, symbol.size);
+
+        Some(self.add(path, address, size))
+    }
+
+    /// Stop watching all values.
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+
+    /// The watched values, indexed by the indices returned when they were added.
+    pub fn values(&self) -> &[WatchedValue] {
+        &self.values
+    }
+
+    /// Returns the sampling interval.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Changes the sampling interval.
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /// Waits until the sampling interval has elapsed since the last sample, and then reads all
+    /// watched values.
+    pub fn sample(&mut self, core: &mut Core) -> Result<Vec<WatchSample>, Error> {
+        if let Some(last_sample) = self.last_sample {
+            if let Some(remaining) = self.interval.checked_sub(last_sample.elapsed()) {
+                thread::sleep(remaining);
+            }
+        }
+
+        self.last_sample = Some(Instant::now());
+
+        self.values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| {
+                let mut data = vec![0u8; value.size as usize];
+                core.read_while_running(value.address, &mut data)?;
+
+                Ok(WatchSample {
+                    index,
+                    timestamp: Instant::now(),
+                    data,
+                })
+            })
+            .collect()
+    }
+}
@@ -11,8 +11,8 @@ use crate::{
     error,
 };
 
-use anyhow::anyhow;
 use anyhow::Result;
+use std::ops::Range;
 
 pub trait MemoryInterface {
     /// Read a 32bit word of at `address`.
@@ -42,9 +42,7 @@ pub trait MemoryInterface {
         // provide an implementation that avoids heap allocation and endian
         // conversions. Must be overridden for big endian targets.
         if data.len() % 4 != 0 {
-            return Err(error::Error::Other(anyhow!(
-                "Call to read_mem_32bit with data.len() not a multiple of 4"
-            )));
+            return Err(error::Error::InvalidDataLength(data.len()));
         }
         let mut buffer = vec![0u32; data.len() / 4];
         self.read_32(address, &mut buffer)?;
@@ -89,6 +87,39 @@ pub trait MemoryInterface {
     /// Write a block of 8bit words at `address`.
     fn write_8(&mut self, address: u32, data: &[u8]) -> Result<(), error::Error>;
 
+    /// Fill `count` 32bit words starting at `address` with `value`.
+    ///
+    /// The address where the write should be performed at has to be word aligned.
+    /// Returns `AccessPortError::MemoryNotAligned` if this does not hold true.
+    fn fill_32(&mut self, address: u32, value: u32, count: usize) -> Result<(), error::Error> {
+        // Default implementation writes the pattern in fixed size chunks, so
+        // that no buffer the size of the whole region has to be allocated.
+        // May be overridden to use a more efficient mechanism, e.g. the
+        // auto-increment feature of an ARM memory AP.
+        const CHUNK_SIZE_WORDS: usize = 256;
+
+        if u64::from(address) + count as u64 * 4 > 1 << 32 {
+            return Err(error::Error::AddressOutOfRange {
+                address,
+                length: count as u64 * 4,
+            });
+        }
+
+        let buffer = [value; CHUNK_SIZE_WORDS];
+        let mut address = address;
+        let mut remaining = count;
+
+        while remaining > 0 {
+            let chunk_size = remaining.min(CHUNK_SIZE_WORDS);
+            self.write_32(address, &buffer[..chunk_size])?;
+
+            remaining -= chunk_size;
+            address = address.wrapping_add((chunk_size * 4) as u32);
+        }
+
+        Ok(())
+    }
+
     /// Flush any outstanding operations.
     ///
     /// For performance, debug probe implementations may choose to batch writes;
@@ -134,6 +165,10 @@ where
         (*self).write_8(addr, data)
     }
 
+    fn fill_32(&mut self, addr: u32, value: u32, count: usize) -> Result<(), error::Error> {
+        (*self).fill_32(addr, value, count)
+    }
+
     fn flush(&mut self) -> Result<(), error::Error> {
         (*self).flush()
     }
@@ -142,6 +177,8 @@ where
 pub struct Memory<'probe> {
     inner: Box<dyn ArmProbe + 'probe>,
     ap_sel: MemoryAp,
+    /// Memory regions which are accessed through another AP than `ap_sel`.
+    routes: Vec<(Range<u32>, MemoryAp)>,
 }
 
 impl<'probe> Memory<'probe> {
@@ -149,45 +186,69 @@ impl<'probe> Memory<'probe> {
         Self {
             inner: Box::new(memory),
             ap_sel,
+            routes: Vec::new(),
         }
     }
 
+    /// Access the memory in `range` through the AP `ap` instead of the default AP.
+    ///
+    /// Accesses are routed by their start address, so an access must not cross
+    /// the boundary of a routed range.
+    pub fn route(&mut self, range: Range<u32>, ap: MemoryAp) {
+        self.routes.push((range, ap));
+    }
+
+    /// The AP through which the memory at `address` is accessed.
+    fn ap_for(&self, address: u32) -> MemoryAp {
+        self.routes
+            .iter()
+            .find(|(range, _)| range.contains(&address))
+            .map(|(_, ap)| *ap)
+            .unwrap_or(self.ap_sel)
+    }
+
     pub fn read_word_32(&mut self, address: u32) -> Result<u32, error::Error> {
         let mut buff = [0];
-        self.inner.read_32(self.ap_sel, address, &mut buff)?;
+        self.inner
+            .read_32(self.ap_for(address), address, &mut buff)?;
 
         Ok(buff[0])
     }
 
     pub fn read_word_8(&mut self, address: u32) -> Result<u8, error::Error> {
         let mut buff = [0];
-        self.inner.read_8(self.ap_sel, address, &mut buff)?;
+        self.inner
+            .read_8(self.ap_for(address), address, &mut buff)?;
 
         Ok(buff[0])
     }
 
     pub fn read_32(&mut self, address: u32, data: &mut [u32]) -> Result<(), error::Error> {
-        self.inner.read_32(self.ap_sel, address, data)
+        self.inner.read_32(self.ap_for(address), address, data)
     }
 
     pub fn read_8(&mut self, address: u32, data: &mut [u8]) -> Result<(), error::Error> {
-        self.inner.read_8(self.ap_sel, address, data)
+        self.inner.read_8(self.ap_for(address), address, data)
     }
 
     pub fn write_word_32(&mut self, addr: u32, data: u32) -> Result<(), error::Error> {
-        self.inner.write_32(self.ap_sel, addr, &[data])
+        self.inner.write_32(self.ap_for(addr), addr, &[data])
     }
 
     pub fn write_word_8(&mut self, addr: u32, data: u8) -> Result<(), error::Error> {
-        self.inner.write_8(self.ap_sel, addr, &[data])
+        self.inner.write_8(self.ap_for(addr), addr, &[data])
     }
 
     pub fn write_32(&mut self, addr: u32, data: &[u32]) -> Result<(), error::Error> {
-        self.inner.write_32(self.ap_sel, addr, data)
+        self.inner.write_32(self.ap_for(addr), addr, data)
     }
 
     pub fn write_8(&mut self, addr: u32, data: &[u8]) -> Result<(), error::Error> {
-        self.inner.write_8(self.ap_sel, addr, data)
+        self.inner.write_8(self.ap_for(addr), addr, data)
+    }
+
+    pub fn fill_32(&mut self, addr: u32, value: u32, count: usize) -> Result<(), error::Error> {
+        self.inner.fill_32(self.ap_for(addr), addr, value, count)
     }
 
     pub fn flush(&mut self) -> Result<(), error::Error> {
@@ -206,6 +267,21 @@ impl<'probe> Memory<'probe> {
         self.inner.write_core_reg(self.ap_sel, addr, value)
     }
 
+    pub fn read_core_regs(
+        &mut self,
+        addresses: &[CoreRegisterAddress],
+        values: &mut [u32],
+    ) -> Result<(), error::Error> {
+        self.inner.read_core_regs(self.ap_sel, addresses, values)
+    }
+
+    pub fn write_core_regs(
+        &mut self,
+        registers: &[(CoreRegisterAddress, u32)],
+    ) -> Result<(), error::Error> {
+        self.inner.write_core_regs(self.ap_sel, registers)
+    }
+
     pub fn get_arm_interface(
         &mut self,
     ) -> Result<&mut ArmCommunicationInterface<Initialized>, error::Error> {
@@ -216,3 +292,45 @@ impl<'probe> Memory<'probe> {
         self.ap_sel.ap_address()
     }
 }
+
+impl MemoryInterface for Memory<'_> {
+    fn read_word_32(&mut self, address: u32) -> Result<u32, error::Error> {
+        Memory::read_word_32(self, address)
+    }
+
+    fn read_word_8(&mut self, address: u32) -> Result<u8, error::Error> {
+        Memory::read_word_8(self, address)
+    }
+
+    fn read_32(&mut self, address: u32, data: &mut [u32]) -> Result<(), error::Error> {
+        Memory::read_32(self, address, data)
+    }
+
+    fn read_8(&mut self, address: u32, data: &mut [u8]) -> Result<(), error::Error> {
+        Memory::read_8(self, address, data)
+    }
+
+    fn write_word_32(&mut self, address: u32, data: u32) -> Result<(), error::Error> {
+        Memory::write_word_32(self, address, data)
+    }
+
+    fn write_word_8(&mut self, address: u32, data: u8) -> Result<(), error::Error> {
+        Memory::write_word_8(self, address, data)
+    }
+
+    fn write_32(&mut self, address: u32, data: &[u32]) -> Result<(), error::Error> {
+        Memory::write_32(self, address, data)
+    }
+
+    fn write_8(&mut self, address: u32, data: &[u8]) -> Result<(), error::Error> {
+        Memory::write_8(self, address, data)
+    }
+
+    fn fill_32(&mut self, address: u32, value: u32, count: usize) -> Result<(), error::Error> {
+        Memory::fill_32(self, address, value, count)
+    }
+
+    fn flush(&mut self) -> Result<(), error::Error> {
+        Memory::flush(self)
+    }
+}
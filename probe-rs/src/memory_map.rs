@@ -0,0 +1,180 @@
+#![warn(missing_docs)]
+
+//! The memory map of a target, as seen by the debugger.
+//!
+//! [`Session::memory_map`] resolves the memory regions of the target description, i.e.
+//! which cores can access them, through which access port, and which flash algorithm
+//! programs them, so tools don't have to interpret the raw target description themselves.
+
+use std::ops::Range;
+
+use probe_rs_target::{CoreAccessOptions, MemoryRange, MemoryRegion};
+
+use crate::architecture::arm::{ApAddress, DpAddress};
+use crate::flashing::FlashLoader;
+use crate::{Session, Target};
+
+/// The kind of memory of a [`MemoryMapRegion`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryKind {
+    /// RAM, which can be read and written directly.
+    Ram,
+    /// Non-volatile memory, which can be read directly, but has to be written with a
+    /// flash algorithm.
+    Nvm,
+    /// Other memory, e.g. peripherals or a ROM.
+    Generic,
+}
+
+/// A region of the memory map of a target.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryMapRegion {
+    /// The kind of memory.
+    pub kind: MemoryKind,
+    /// The address range of the region.
+    pub range: Range<u32>,
+    /// The address range of the alias of the region, e.g. the secure alias on ARMv8-M chips.
+    pub alias: Option<Range<u32>>,
+    /// The region contains the boot memory of the target.
+    pub is_boot_memory: bool,
+    /// The indices of the cores which can access the region.
+    pub cores: Vec<usize>,
+    /// The access port through which the region is accessed, for ARM targets.
+    ///
+    /// This is the access port of the region if the target description specifies one,
+    /// and the access port of the first core of the region otherwise.
+    pub access_port: Option<ApAddress>,
+    /// The name of the flash algorithm which programs the region, for NVM regions.
+    ///
+    /// `None` if there is no flash algorithm for the region, or the default algorithm
+    /// is ambiguous.
+    pub flash_algorithm: Option<String>,
+}
+
+impl MemoryMapRegion {
+    /// Resolve `region` of the memory map of `target`.
+    pub(crate) fn new(region: &MemoryRegion, target: &Target) -> Self {
+        let (kind, is_boot_memory, core_names) = match region {
+            MemoryRegion::Ram(region) => (MemoryKind::Ram, region.is_boot_memory, &region.cores),
+            MemoryRegion::Nvm(region) => (MemoryKind::Nvm, region.is_boot_memory, &region.cores),
+            MemoryRegion::Generic(region) => (MemoryKind::Generic, false, &region.cores),
+        };
+
+        let cores: Vec<usize> = core_names
+            .iter()
+            .filter_map(|name| target.core_index_by_name(name))
+            .collect();
+
+        let access_options = cores
+            .first()
+            .map(|&core| &target.cores[core].core_access_options);
+
+        let access_port = match access_options {
+            Some(CoreAccessOptions::Arm(options)) => Some(ApAddress {
+                dp: match options.psel {
+                    0 => DpAddress::Default,
+                    x => DpAddress::Multidrop(x),
+                },
+                ap: region.access_port().unwrap_or(options.ap),
+            }),
+            _ => None,
+        };
+
+        let flash_algorithm = match region {
+            MemoryRegion::Nvm(region) => {
+                FlashLoader::get_flash_algorithm_for_region(region, target)
+                    .ok()
+                    .map(|algorithm| algorithm.name.clone())
+            }
+            _ => None,
+        };
+
+        Self {
+            kind,
+            range: region.range().clone(),
+            alias: region.alias_range(),
+            is_boot_memory,
+            cores,
+            access_port,
+            flash_algorithm,
+        }
+    }
+
+    /// Returns `true` if the region can be written directly, without a flash algorithm.
+    pub fn is_writable(&self) -> bool {
+        self.kind != MemoryKind::Nvm
+    }
+
+    /// Returns `true` if `address` is in the region, or in its alias.
+    pub fn contains(&self, address: u32) -> bool {
+        self.range.contains(&address)
+            || matches!(&self.alias, Some(alias) if alias.contains(&address))
+    }
+
+    /// Returns `true` if the region and `range` overlap.
+    pub fn intersects(&self, range: &Range<u32>) -> bool {
+        self.range.intersects_range(range)
+    }
+}
+
+/// The memory map of `target`, see [`Session::memory_map`].
+pub(crate) fn memory_map(target: &Target) -> Vec<MemoryMapRegion> {
+    target
+        .memory_map
+        .iter()
+        .map(|region| MemoryMapRegion::new(region, target))
+        .collect()
+}
+
+impl Session {
+    /// The memory map of the target, in the order of the target description.
+    ///
+    /// This is the same memory map which is used for flashing and for routing memory
+    /// accesses to the access ports, so tools like the GDB server should use it instead
+    /// of interpreting the target description themselves.
+    pub fn memory_map(&self) -> Vec<MemoryMapRegion> {
+        memory_map(self.target())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{memory_map, MemoryKind};
+    use crate::architecture::arm::{ApAddress, DpAddress};
+    use crate::config::get_target_by_name;
+
+    #[test]
+    fn lpc55s69_memory_map() {
+        let target = get_target_by_name("LPC55S69JBD100").unwrap();
+        let map = memory_map(&target);
+
+        let flash = map
+            .iter()
+            .find(|region| region.kind == MemoryKind::Nvm)
+            .unwrap();
+
+        assert!(flash.is_boot_memory);
+        assert!(!flash.is_writable());
+        assert_eq!(flash.cores, vec![0]);
+        assert_eq!(
+            flash.access_port,
+            Some(ApAddress {
+                dp: DpAddress::Default,
+                ap: 0
+            })
+        );
+        assert!(flash.flash_algorithm.is_some());
+
+        // The secure alias of the flash.
+        assert!(flash.contains(0x1000_0000));
+        assert!(!flash.contains(0x2000_0000));
+
+        let ram = map
+            .iter()
+            .find(|region| region.kind == MemoryKind::Ram)
+            .unwrap();
+
+        assert!(ram.is_writable());
+        assert_eq!(ram.flash_algorithm, None);
+    }
+}
@@ -0,0 +1,222 @@
+#![warn(missing_docs)]
+
+//! Flashing several targets at once.
+//!
+//! [`MultiSession`] holds the sessions of several probes, e.g. of the boards in a
+//! programming fixture, and flashes them concurrently, with one thread per session.
+
+use std::sync::Arc;
+use std::thread;
+
+use crate::config::TargetSelector;
+use crate::flashing::{
+    DownloadOptions, FlashError, FlashLoader, FlashProgress, FlashTimeouts, ProgressEvent,
+};
+use crate::{CancellationToken, DebugProbeSelector, Error, Probe, Session, Target};
+
+/// Options of [`MultiSession::download`], which are used for all sessions.
+///
+/// These are the options of [`DownloadOptions`] which can be shared between threads, see
+/// there for their meaning. The progress of all sessions is reported to the handler which
+/// is passed to [`MultiSession::download`].
+#[derive(Debug, Clone, Default)]
+pub struct MultiDownloadOptions {
+    /// See [`DownloadOptions::keep_unwritten_bytes`].
+    pub keep_unwritten_bytes: bool,
+    /// See [`DownloadOptions::do_chip_erase`].
+    pub do_chip_erase: bool,
+    /// See [`DownloadOptions::skip_erase`].
+    pub skip_erase: bool,
+    /// See [`DownloadOptions::verify`].
+    pub verify: bool,
+    /// See [`DownloadOptions::disable_double_buffering`].
+    pub disable_double_buffering: bool,
+    /// See [`DownloadOptions::preserve_ram`].
+    pub preserve_ram: bool,
+    /// See [`DownloadOptions::timeouts`].
+    pub timeouts: FlashTimeouts,
+    /// A token to cancel the downloads of all sessions, see [`DownloadOptions::cancellation`].
+    pub cancellation: Option<CancellationToken>,
+}
+
+impl MultiDownloadOptions {
+    /// The [`DownloadOptions`] of one session, which report to `progress`.
+    fn download_options<'progress>(
+        &self,
+        progress: &'progress FlashProgress,
+    ) -> DownloadOptions<'progress> {
+        let mut options = DownloadOptions::new();
+
+        options.progress = Some(progress);
+        options.keep_unwritten_bytes = self.keep_unwritten_bytes;
+        options.do_chip_erase = self.do_chip_erase;
+        options.skip_erase = self.skip_erase;
+        options.verify = self.verify;
+        options.disable_double_buffering = self.disable_double_buffering;
+        options.preserve_ram = self.preserve_ram;
+        options.timeouts = self.timeouts;
+        options.cancellation = self.cancellation.clone();
+
+        options
+    }
+}
+
+/// The sessions of several probes, which are flashed concurrently.
+///
+/// The sessions are identified by their index, in the order they were added.
+///
+/// ```no_run
+/// use probe_rs::flashing::FlashLoader;
+/// use probe_rs::{DebugProbeSelector, MultiDownloadOptions, MultiSession};
+/// use std::convert::TryInto;
+///
+/// let selectors: Vec<DebugProbeSelector> = vec![
+///     "0483:374b:0001".try_into()?,
+///     "0483:374b:0002".try_into()?,
+/// ];
+///
+/// let mut sessions = MultiSession::open(&selectors, "nrf52840_xxAA")?;
+///
+/// let results = sessions.download(
+///     |_index, target| {
+///         let mut loader = target.flash_loader();
+///         loader.add_data(0x0, &[0x1, 0x2, 0x3])?;
+///         Ok(loader)
+///     },
+///     &MultiDownloadOptions::default(),
+///     |index, event| println!("{}: {:?}", index, event),
+/// );
+///
+/// for (index, result) in results.iter().enumerate() {
+///     if let Err(error) = result {
+///         println!("Flashing board {} failed: {}", index, error);
+///     }
+/// }
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct MultiSession {
+    sessions: Vec<Session>,
+}
+
+impl MultiSession {
+    /// Create a multi-session of sessions which are already attached.
+    pub fn new(sessions: Vec<Session>) -> Self {
+        Self { sessions }
+    }
+
+    /// Open the probes of `selectors` and attach to the `target` with each of them.
+    pub fn open(
+        selectors: &[DebugProbeSelector],
+        target: impl Into<TargetSelector>,
+    ) -> Result<Self, Error> {
+        let target = target.into();
+        let mut sessions = Vec::with_capacity(selectors.len());
+
+        for selector in selectors {
+            let probe = Probe::open(selector.clone())?;
+            sessions.push(probe.attach(target.clone())?);
+        }
+
+        Ok(Self::new(sessions))
+    }
+
+    /// Add an attached session, and return its index.
+    pub fn push(&mut self, session: Session) -> usize {
+        self.sessions.push(session);
+        self.sessions.len() - 1
+    }
+
+    /// The number of sessions.
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Returns `true` if there are no sessions.
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    /// The sessions, in the order of their indices.
+    pub fn sessions(&self) -> &[Session] {
+        &self.sessions
+    }
+
+    /// The sessions as mutable, in the order of their indices.
+    pub fn sessions_mut(&mut self) -> &mut [Session] {
+        &mut self.sessions
+    }
+
+    /// Return the sessions, in the order of their indices.
+    pub fn into_sessions(self) -> Vec<Session> {
+        self.sessions
+    }
+
+    /// Flash all sessions concurrently, and return the result of each session.
+    ///
+    /// `load` creates the flash loader of each session from its index and target, so the
+    /// same image or a different one, e.g. with a unique serial number, can be flashed to
+    /// each board. The events of all sessions are reported to `progress`, with the index of
+    /// the session. An error of one session, including an error of `load`, doesn't stop the
+    /// others.
+    ///
+    /// # Panics
+    ///
+    /// Panics if flashing one of the sessions panics, after all other sessions are finished.
+    pub fn download(
+        &mut self,
+        mut load: impl FnMut(usize, &Target) -> Result<FlashLoader, FlashError>,
+        options: &MultiDownloadOptions,
+        progress: impl Fn(usize, ProgressEvent) + Send + Sync + 'static,
+    ) -> Vec<Result<(), FlashError>> {
+        let progress = Arc::new(progress);
+
+        // The loaders are created on this thread, so `load` doesn't have to be `Send`.
+        let loaders = self
+            .sessions
+            .iter()
+            .enumerate()
+            .map(|(index, session)| load(index, session.target()))
+            .collect::<Vec<_>>();
+
+        let handles = self
+            .sessions
+            .drain(..)
+            .zip(loaders)
+            .enumerate()
+            .map(|(index, (mut session, loader))| {
+                let progress = progress.clone();
+                let options = options.clone();
+
+                thread::spawn(move || {
+                    let result = loader.and_then(|loader| {
+                        let progress = FlashProgress::new(move |event| (*progress)(index, event));
+
+                        loader.commit(&mut session, options.download_options(&progress))
+                    });
+
+                    (session, result)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut results = Vec::with_capacity(handles.len());
+        let mut panic = None;
+
+        for handle in handles {
+            match handle.join() {
+                Ok((session, result)) => {
+                    self.sessions.push(session);
+                    results.push(result);
+                }
+                Err(payload) => panic = Some(payload),
+            }
+        }
+
+        if let Some(payload) = panic {
+            std::panic::resume_unwind(payload);
+        }
+
+        results
+    }
+}
@@ -0,0 +1,40 @@
+//! Permissions for operations which destroy data on the target.
+
+use crate::Error;
+
+/// The operations which probe-rs is allowed to do without asking, when it attaches to a target.
+///
+/// Some chips can only be unlocked by erasing them completely, e.g. the nRF5340 and nRF9160
+/// when their APPROTECT is enabled. By default, attaching to such a locked chip fails with
+/// [`Error::MissingPermissions`], and the erase has to be allowed explicitly.
+///
+/// ```
+/// use probe_rs::Permissions;
+///
+/// let permissions = Permissions::new().allow_erase_all();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Permissions {
+    erase_all: bool,
+}
+
+impl Permissions {
+    /// Permissions which don't allow any destructive operation.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow erasing the whole chip, including the data which is protected, to unlock it.
+    pub fn allow_erase_all(self) -> Self {
+        Self { erase_all: true }
+    }
+
+    /// Check if erasing the whole chip is allowed, and return [`Error::MissingPermissions`] if not.
+    pub(crate) fn erase_all(&self) -> Result<(), Error> {
+        if self.erase_all {
+            Ok(())
+        } else {
+            Err(Error::MissingPermissions("erase_all".into()))
+        }
+    }
+}
@@ -1,9 +1,24 @@
+mod bridge;
+#[cfg(any(feature = "cmsisdap", feature = "webusb"))]
 pub(crate) mod cmsisdap;
 pub(crate) mod fake_probe;
 #[cfg(feature = "ftdi")]
 pub(crate) mod ftdi;
+#[cfg(feature = "jlink")]
 pub(crate) mod jlink;
+mod lock;
+#[cfg(feature = "simulator")]
+pub(crate) mod simulator;
+#[cfg(feature = "stlink")]
 pub(crate) mod stlink;
+mod trace;
+mod watch;
+
+pub use bridge::{BridgeInterfaces, ProbeBridge, SpiConfig, SpiMode, UartConfig};
+pub use trace::{ProtocolTracer, TraceEvent, TraceRecord};
+pub use watch::{ProbeEvent, ProbeWatcher};
+
+pub(crate) use lock::ProbeLock;
 
 use crate::architecture::{
     arm::{
@@ -12,15 +27,19 @@ use crate::architecture::{
         PortType, SwoAccess,
     },
     riscv::communication_interface::RiscvCommunicationInterface,
+    xtensa::communication_interface::XtensaCommunicationInterface,
 };
 use crate::error::Error;
-use crate::Session;
 use crate::{
     architecture::arm::communication_interface::UninitializedArmProbe,
     config::{RegistryError, TargetSelector},
 };
-use jlink::list_jlink_devices;
-use std::{convert::TryFrom, fmt};
+use crate::{Permissions, Session};
+use std::{
+    convert::{Infallible, TryFrom, TryInto},
+    fmt,
+    time::Duration,
+};
 
 /// Used to log warnings when the measured target voltage is
 /// lower than 1.4V, if at all measureable.
@@ -56,6 +75,77 @@ impl std::str::FromStr for WireProtocol {
     }
 }
 
+/// A TAP in the JTAG scan chain, as found by the probe when it attached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanChainElement {
+    /// The IDCODE of the TAP.
+    pub idcode: u32,
+    /// The length of the instruction register of the TAP.
+    pub ir_len: usize,
+}
+
+/// How a probe reconnects when it drops off USB, e.g. because of a firmware quirk or a
+/// power cycle of the target which also powers the probe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    /// How often to try to open the probe again. No reconnect is tried if this is zero.
+    pub attempts: u32,
+    /// How long to wait before each attempt.
+    pub delay: Duration,
+}
+
+impl ReconnectPolicy {
+    /// Don't reconnect, and fail the operation which lost the probe.
+    pub fn disabled() -> Self {
+        Self {
+            attempts: 0,
+            delay: Duration::ZERO,
+        }
+    }
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 5,
+            delay: Duration::from_millis(500),
+        }
+    }
+}
+
+/// The protocol speeds supported by a probe.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SupportedSpeeds {
+    /// Only the listed speeds, in kHz, are supported.
+    Discrete(Vec<u32>),
+    /// All speeds between `min_khz` and `max_khz`, inclusive, are supported.
+    Range { min_khz: u32, max_khz: u32 },
+    /// The probe cannot report which speeds it supports.
+    Unknown,
+}
+
+impl SupportedSpeeds {
+    /// The next speed below `speed_khz` which should be tried, if a probe
+    /// rejected `speed_khz`.
+    ///
+    /// If the supported speeds are unknown, the speed is halved.
+    pub fn next_lower(&self, speed_khz: u32) -> Option<u32> {
+        match self {
+            SupportedSpeeds::Discrete(speeds) => {
+                speeds.iter().copied().filter(|s| *s < speed_khz).max()
+            }
+            SupportedSpeeds::Range { min_khz, max_khz } => {
+                if speed_khz > *max_khz {
+                    Some(*max_khz)
+                } else {
+                    Some(speed_khz / 2).filter(|s| s >= min_khz)
+                }
+            }
+            SupportedSpeeds::Unknown => Some(speed_khz / 2).filter(|s| *s > 0),
+        }
+    }
+}
+
 /// A command queued in a batch for later execution
 ///
 /// Mostly used internally but returned in DebugProbeError to indicate
@@ -120,24 +210,134 @@ pub enum DebugProbeError {
     CommandNotSupportedByProbe(&'static str),
     #[error("Unable to set hardware breakpoint, all available breakpoint units are in use.")]
     BreakpointUnitsExceeded,
+    #[error("The probe selector is invalid")]
+    InvalidSelector(#[from] DebugProbeSelectorParseError),
+    #[error("The probe is in use by another process{}", pid.map(|pid| format!(" with PID {}", pid)).unwrap_or_default())]
+    ProbeInUse { pid: Option<u32> },
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
 
+impl From<Infallible> for DebugProbeError {
+    fn from(never: Infallible) -> Self {
+        match never {}
+    }
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum ProbeCreationError {
     #[error("Probe was not found.")]
     NotFound,
     #[error("USB device could not be opened. Please check the permissions.")]
     CouldNotOpen,
+    #[cfg(feature = "cmsisdap")]
     #[error("{0}")]
     HidApi(#[from] hidapi::HidError),
+    #[cfg(feature = "libusb")]
     #[error("{0}")]
     Rusb(#[from] rusb::Error),
     #[error("An error specific to a probe type occured: {0}")]
     ProbeSpecific(#[source] Box<dyn std::error::Error + Send + Sync>),
     #[error("{0}")]
     Other(&'static str),
+    #[cfg(feature = "libusb")]
+    #[error("Accessing the USB device {vendor_id:04x}:{product_id:04x} failed while trying to {operation}: {}", hint.guidance())]
+    UsbAccess {
+        vendor_id: u16,
+        product_id: u16,
+        /// The operation which failed.
+        operation: UsbOperation,
+        /// The likely cause, to show guidance to the user.
+        hint: UsbAccessHint,
+        #[source]
+        source: rusb::Error,
+    },
+}
+
+#[cfg(feature = "libusb")]
+impl ProbeCreationError {
+    /// The error of `operation` on the USB device `vendor_id:product_id`.
+    ///
+    /// Errors caused by permissions, the driver or other programs are returned as
+    /// [`ProbeCreationError::UsbAccess`], so front-ends can show guidance to the user.
+    pub(crate) fn from_usb(
+        vendor_id: u16,
+        product_id: u16,
+        operation: UsbOperation,
+        source: rusb::Error,
+    ) -> Self {
+        match UsbAccessHint::from_rusb(&source) {
+            Some(hint) => ProbeCreationError::UsbAccess {
+                vendor_id,
+                product_id,
+                operation,
+                hint,
+                source,
+            },
+            None => ProbeCreationError::Rusb(source),
+        }
+    }
+}
+
+/// An operation on a USB device, see [`ProbeCreationError::UsbAccess`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsbOperation {
+    /// Opening the device.
+    Open,
+    /// Claiming an interface of the device.
+    ClaimInterface(u8),
+}
+
+impl fmt::Display for UsbOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UsbOperation::Open => write!(f, "open"),
+            UsbOperation::ClaimInterface(interface) => write!(f, "claim interface {}", interface),
+        }
+    }
+}
+
+/// The likely cause of a failed access to a USB device, see [`ProbeCreationError::UsbAccess`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsbAccessHint {
+    /// The user may not access the device, e.g. because of missing udev rules on Linux.
+    Permissions,
+    /// The device is not bound to a driver which can be used, e.g. WinUSB on Windows.
+    Driver,
+    /// Another program uses the device.
+    Busy,
+}
+
+impl UsbAccessHint {
+    /// The likely cause of `error`, or `None` if it is not caused by the access to the device.
+    #[cfg(feature = "libusb")]
+    pub fn from_rusb(error: &rusb::Error) -> Option<Self> {
+        match error {
+            rusb::Error::Access => Some(UsbAccessHint::Permissions),
+            rusb::Error::NotSupported => Some(UsbAccessHint::Driver),
+            rusb::Error::Busy => Some(UsbAccessHint::Busy),
+            _ => None,
+        }
+    }
+
+    /// Guidance for the user on the current platform.
+    pub fn guidance(&self) -> &'static str {
+        match self {
+            UsbAccessHint::Permissions if cfg!(target_os = "linux") => {
+                "the user has no permission to access it. Add a udev rule for the probe, reload the rules and plug the probe in again."
+            }
+            UsbAccessHint::Permissions => "the user has no permission to access it.",
+            UsbAccessHint::Driver if cfg!(windows) => {
+                "no usable driver is bound to it. Bind the WinUSB driver to the probe, e.g. with Zadig."
+            }
+            UsbAccessHint::Driver => "no usable driver is bound to it.",
+            UsbAccessHint::Busy => {
+                "it is used by another program. Close other programs which use the probe, e.g. another debugger."
+            }
+        }
+    }
 }
 
 /// The Probe struct is a generic wrapper over the different
@@ -160,6 +360,8 @@ pub enum ProbeCreationError {
 pub struct Probe {
     inner: Box<dyn DebugProbe>,
     attached: bool,
+    /// The lock of the probe, if it was opened with [`Probe::open`].
+    lock: Option<ProbeLock>,
 }
 
 impl Probe {
@@ -167,6 +369,7 @@ impl Probe {
         Self {
             inner: Box::new(probe),
             attached: false,
+            lock: None,
         }
     }
 
@@ -174,6 +377,7 @@ impl Probe {
         Self {
             inner: probe,
             attached: true,
+            lock: None,
         }
     }
 
@@ -181,32 +385,202 @@ impl Probe {
         Probe {
             inner: probe,
             attached: false,
+            lock: None,
+        }
+    }
+
+    /// Create a `Probe` for a CMSIS-DAP probe, which is connected with `transport`.
+    ///
+    /// This allows to use CMSIS-DAP probes over transports which probe-rs doesn't
+    /// implement itself, e.g. `WebUsbTransport` with the `webusb`
+    /// feature. The probe is not locked.
+    #[cfg(any(feature = "cmsisdap", feature = "webusb"))]
+    pub fn from_cmsisdap_transport(
+        transport: Box<dyn cmsisdap::CmsisDapTransport>,
+    ) -> Result<Self, DebugProbeError> {
+        let device = cmsisdap::commands::CmsisDapDevice::new(transport);
+
+        Ok(Probe::from_specific_probe(Box::new(
+            cmsisdap::CmsisDap::new_from_device(device)?,
+        )))
+    }
+
+    /// Open the probe with the file descriptor `fd` of its USB device, which was opened
+    /// outside of probe-rs, e.g. with the USB host API of Android.
+    ///
+    /// The USB devices are not enumerated, so this works where libusb can't access them
+    /// itself. ST-Link and CMSIS-DAP v2 probes are supported. The probe is not locked, and
+    /// can't be opened again if it is lost from USB.
+    ///
+    /// # Safety
+    ///
+    /// `fd` has to be the open file descriptor of a USB device, and has to stay open until
+    /// the probe is dropped. probe-rs doesn't close it.
+    #[cfg(all(unix, feature = "libusb"))]
+    #[allow(unused_variables)]
+    pub unsafe fn open_usb_fd(fd: std::os::unix::io::RawFd) -> Result<Self, DebugProbeError> {
+        use rusb::UsbContext;
+
+        let context = rusb::Context::new().map_err(ProbeCreationError::from)?;
+
+        /// The rusb error for a libusb error code.
+        fn usb_error(code: i32) -> rusb::Error {
+            use libusb1_sys::constants::*;
+
+            match code {
+                LIBUSB_ERROR_IO => rusb::Error::Io,
+                LIBUSB_ERROR_INVALID_PARAM => rusb::Error::InvalidParam,
+                LIBUSB_ERROR_ACCESS => rusb::Error::Access,
+                LIBUSB_ERROR_NO_DEVICE => rusb::Error::NoDevice,
+                LIBUSB_ERROR_NOT_FOUND => rusb::Error::NotFound,
+                LIBUSB_ERROR_BUSY => rusb::Error::Busy,
+                LIBUSB_ERROR_NO_MEM => rusb::Error::NoMem,
+                LIBUSB_ERROR_NOT_SUPPORTED => rusb::Error::NotSupported,
+                _ => rusb::Error::Other,
+            }
+        }
+
+        // rusb 0.8 can't wrap a file descriptor itself, and jaylink still depends on it.
+        let mut raw_handle = std::ptr::null_mut();
+        let result = libusb1_sys::libusb_wrap_sys_device(
+            context.as_raw(),
+            fd as isize as _,
+            &mut raw_handle,
+        );
+        if result != 0 {
+            return Err(ProbeCreationError::from(usb_error(result)).into());
+        }
+
+        // SAFETY: libusb returned a valid handle, which is owned by the `DeviceHandle` now.
+        let handle = match std::ptr::NonNull::new(raw_handle) {
+            Some(raw_handle) => rusb::DeviceHandle::from_libusb(context, raw_handle),
+            None => return Err(ProbeCreationError::from(rusb::Error::Other).into()),
+        };
+
+        #[cfg(feature = "stlink")]
+        if stlink::tools::is_stlink_device(&handle.device()) {
+            return Ok(Probe::from_specific_probe(stlink::StLink::new_from_handle(
+                handle,
+            )?));
         }
+
+        #[cfg(feature = "cmsisdap")]
+        if let Some(device) = cmsisdap::tools::open_v2_handle(handle) {
+            return Ok(Probe::from_specific_probe(Box::new(
+                cmsisdap::CmsisDap::new_from_device(device)?,
+            )));
+        }
+
+        Err(DebugProbeError::ProbeCouldNotBeCreated(
+            ProbeCreationError::NotFound,
+        ))
+    }
+
+    /// Take the lock of the probe, so it can be held after the probe is consumed.
+    pub(crate) fn take_lock(&mut self) -> Option<ProbeLock> {
+        self.lock.take()
     }
 
     /// Get a list of all debug probes found.
     /// This can be used to select the debug probe which
     /// should be used.
+    ///
+    /// Only the probes with a driver enabled by the features of the crate are listed.
+    #[allow(unused_mut)]
     pub fn list_all() -> Vec<DebugProbeInfo> {
-        let mut list = cmsisdap::tools::list_cmsisdap_devices();
+        let mut list = Vec::new();
+        #[cfg(feature = "cmsisdap")]
+        {
+            list.extend(cmsisdap::tools::list_cmsisdap_devices());
+        }
         #[cfg(feature = "ftdi")]
         {
             list.extend(ftdi::list_ftdi_devices());
         }
-        list.extend(stlink::tools::list_stlink_devices());
-
-        list.extend(list_jlink_devices());
+        #[cfg(feature = "stlink")]
+        {
+            list.extend(stlink::tools::list_stlink_devices());
+        }
+        #[cfg(feature = "jlink")]
+        {
+            list.extend(jlink::list_jlink_devices());
+        }
 
         list
     }
 
+    /// Watch for debug probes which are attached or detached.
+    ///
+    /// This allows long running tools to recover when a probe is unplugged and plugged in
+    /// again. Changes are reported by libusb hotplug events where they are supported.
+    /// Otherwise, the probes are listed every `interval`.
+    pub fn watch(interval: Duration) -> ProbeWatcher {
+        ProbeWatcher::new(interval)
+    }
+
     /// Create a `Probe` from `DebugProbeInfo`. Use the
     /// `Probe::list_all()` function to get the information
     /// about all probes available.
-    pub fn open(selector: impl Into<DebugProbeSelector> + Clone) -> Result<Self, DebugProbeError> {
+    ///
+    /// The probe can also be selected by a string in the form `VID:PID:<Serial>`.
+    /// With the `simulator` feature, `"simulator"` opens a [`SimulatorProbe`], which is
+    /// connected to a Cortex-M core simulated on the host.
+    ///
+    /// [`SimulatorProbe`]: crate::SimulatorProbe
+    pub fn open<S>(selector: S) -> Result<Self, DebugProbeError>
+    where
+        S: TryInto<DebugProbeSelector>,
+        DebugProbeError: From<S::Error>,
+    {
+        let selector = selector.try_into()?;
+
+        #[cfg(feature = "simulator")]
+        if selector.is_simulator() {
+            return Ok(Probe::from_specific_probe(
+                simulator::SimulatorProbe::new_from_selector(selector)?,
+            ));
+        }
+
+        // Another process using the probe would corrupt the transfers.
+        let lock = ProbeLock::acquire(&Self::lock_selector(selector.clone()))?;
+
+        let mut probe = Self::open_selector(selector)?;
+        probe.lock = lock;
+
+        Ok(probe)
+    }
+
+    /// The selector of the probe which is locked, with the serial number of the probe
+    /// which is opened if `selector` has none.
+    fn lock_selector(selector: DebugProbeSelector) -> DebugProbeSelector {
+        if selector.serial_number.is_some() {
+            return selector;
+        }
+
+        Self::list_all()
+            .iter()
+            .find(|probe| {
+                probe.vendor_id == selector.vendor_id && probe.product_id == selector.product_id
+            })
+            .map_or(selector, DebugProbeSelector::from)
+    }
+
+    /// Open the first probe which matches `selector`.
+    ///
+    /// Only the drivers enabled by the features of the crate are tried.
+    #[allow(unused_mut, unused_variables)]
+    fn open_selector(selector: DebugProbeSelector) -> Result<Self, DebugProbeError> {
+        // The CMSIS-DAP driver can't tell if a device it can't access is a CMSIS-DAP probe,
+        // so the other drivers are tried, and the error is returned if none of them finds it.
+        let mut access_error = None;
+
+        #[cfg(feature = "cmsisdap")]
         match cmsisdap::CmsisDap::new_from_selector(selector.clone()) {
             Ok(link) => return Ok(Probe::from_specific_probe(link)),
             Err(DebugProbeError::ProbeCouldNotBeCreated(ProbeCreationError::NotFound)) => {}
+            Err(DebugProbeError::ProbeCouldNotBeCreated(
+                error @ ProbeCreationError::UsbAccess { .. },
+            )) => access_error = Some(error),
             Err(e) => return Err(e),
         };
         #[cfg(feature = "ftdi")]
@@ -215,11 +589,13 @@ impl Probe {
             Err(DebugProbeError::ProbeCouldNotBeCreated(ProbeCreationError::NotFound)) => {}
             Err(e) => return Err(e),
         };
+        #[cfg(feature = "stlink")]
         match stlink::StLink::new_from_selector(selector.clone()) {
             Ok(link) => return Ok(Probe::from_specific_probe(link)),
             Err(DebugProbeError::ProbeCouldNotBeCreated(ProbeCreationError::NotFound)) => {}
             Err(e) => return Err(e),
         };
+        #[cfg(feature = "jlink")]
         match jlink::JLink::new_from_selector(selector) {
             Ok(link) => return Ok(Probe::from_specific_probe(link)),
             Err(DebugProbeError::ProbeCouldNotBeCreated(ProbeCreationError::NotFound)) => {}
@@ -227,7 +603,7 @@ impl Probe {
         };
 
         Err(DebugProbeError::ProbeCouldNotBeCreated(
-            ProbeCreationError::NotFound,
+            access_error.unwrap_or(ProbeCreationError::NotFound),
         ))
     }
 
@@ -241,10 +617,20 @@ impl Probe {
     /// This runs all the necessary protocol init routines.
     ///
     /// If this doesn't work, you might want to try `attach_under_reset`
-    pub fn attach(mut self, target: impl Into<TargetSelector>) -> Result<Session, Error> {
+    pub fn attach(self, target: impl Into<TargetSelector>) -> Result<Session, Error> {
+        self.attach_with_permissions(target, Permissions::default())
+    }
+
+    /// Attach to the chip, with `permissions` for destructive operations, which are
+    /// needed to attach to some locked chips, see [`Permissions`].
+    pub fn attach_with_permissions(
+        mut self,
+        target: impl Into<TargetSelector>,
+        permissions: Permissions,
+    ) -> Result<Session, Error> {
         self.attached = true;
 
-        Session::new(self, target.into(), AttachMethod::Normal)
+        Session::new(self, target.into(), AttachMethod::Normal, permissions)
     }
 
     pub fn attach_to_unspecified(&mut self) -> Result<(), Error> {
@@ -274,20 +660,33 @@ impl Probe {
     /// This asserts the reset pin via the probe, plays the protocol init routines and deasserts the pin.
     /// This is necessary if the chip is not responding to the SWD reset sequence.
     /// For example this can happen if the chip has the SWDIO pin remapped.
-    pub fn attach_under_reset(
+    pub fn attach_under_reset(self, target: impl Into<TargetSelector>) -> Result<Session, Error> {
+        self.attach_under_reset_with_permissions(target, Permissions::default())
+    }
+
+    /// Attach to the chip under hard-reset, with `permissions` for destructive operations,
+    /// see [`attach_under_reset`](Self::attach_under_reset) and [`Permissions`].
+    pub fn attach_under_reset_with_permissions(
         mut self,
         target: impl Into<TargetSelector>,
+        permissions: Permissions,
     ) -> Result<Session, Error> {
         self.attached = true;
 
         // The session will de-assert reset after connecting to the debug interface.
-        Session::new(self, target.into(), AttachMethod::UnderReset)
+        Session::new(self, target.into(), AttachMethod::UnderReset, permissions)
     }
 
     pub(crate) fn inner_attach(&mut self) -> Result<(), DebugProbeError> {
         self.inner.attach()
     }
 
+    /// The TAPs in the JTAG scan chain, starting with the TAP closest to TDO, see
+    /// [`DebugProbe::scan_chain`].
+    pub fn scan_chain(&self) -> &[ScanChainElement] {
+        self.inner.scan_chain()
+    }
+
     /// Selects the transport protocol to be used by the debug probe.
     pub fn select_protocol(&mut self, protocol: WireProtocol) -> Result<(), DebugProbeError> {
         if !self.attached {
@@ -319,12 +718,66 @@ impl Probe {
         self.inner.target_reset_deassert()
     }
 
-    /// Configure protocol speed to use in kHz
+    /// Set how the probe reconnects when it drops off USB.
+    ///
+    /// After reconnecting, the probe is attached again with the same protocol and speed, so
+    /// that a running session can continue. Probes which can't reconnect ignore this.
+    pub fn set_reconnect_policy(&mut self, policy: ReconnectPolicy) {
+        self.inner.set_reconnect_policy(policy)
+    }
+
+    /// Record the operations of the probe in `tracer`, or stop recording them if it is `None`.
+    ///
+    /// This is useful to report problems with the communication between the probe and the
+    /// target. Probes which don't support tracing ignore this.
+    pub fn set_protocol_tracer(&mut self, tracer: Option<ProtocolTracer>) {
+        self.inner.set_protocol_tracer(tracer)
+    }
+
+    /// Configure protocol speed to use in kHz.
+    ///
+    /// If the probe rejects the requested speed, the next lower supported speed
+    /// is tried, until the probe accepts one. The actually configured speed is returned.
     pub fn set_speed(&mut self, speed_khz: u32) -> Result<u32, DebugProbeError> {
-        if !self.attached {
-            self.inner.set_speed(speed_khz)
-        } else {
-            Err(DebugProbeError::Attached)
+        if self.attached {
+            return Err(DebugProbeError::Attached);
+        }
+
+        let mut requested_khz = speed_khz;
+        let mut supported = None;
+
+        loop {
+            match self.inner.set_speed(requested_khz) {
+                Err(DebugProbeError::UnsupportedSpeed(_)) => {
+                    if supported.is_none() {
+                        supported = Some(self.inner.supported_speeds()?);
+                    }
+
+                    let rejected_khz = requested_khz;
+                    requested_khz = supported
+                        .as_ref()
+                        .and_then(|supported| supported.next_lower(rejected_khz))
+                        .ok_or(DebugProbeError::UnsupportedSpeed(speed_khz))?;
+
+                    log::debug!(
+                        "Speed {} kHz rejected by probe, trying {} kHz",
+                        rejected_khz,
+                        requested_khz
+                    );
+                }
+                Ok(actual_khz) => {
+                    if actual_khz != speed_khz {
+                        log::warn!(
+                            "Requested speed of {} kHz is not supported, using {} kHz",
+                            speed_khz,
+                            actual_khz
+                        );
+                    }
+
+                    return Ok(actual_khz);
+                }
+                Err(error) => return Err(error),
+            }
         }
     }
 
@@ -333,6 +786,11 @@ impl Probe {
         self.inner.speed()
     }
 
+    /// The protocol speeds supported by the probe, for the selected protocol.
+    pub fn supported_speeds(&mut self) -> Result<SupportedSpeeds, DebugProbeError> {
+        self.inner.supported_speeds()
+    }
+
     /// Check if the probe has an interface to
     /// debug ARM chips.
     pub fn has_arm_interface(&self) -> bool {
@@ -378,6 +836,29 @@ impl Probe {
         }
     }
 
+    /// Check if the probe has an interface to
+    /// debug Xtensa chips.
+    pub fn has_xtensa_interface(&self) -> bool {
+        self.inner.has_xtensa_interface()
+    }
+
+    /// Try to get a [`XtensaCommunicationInterface`], which can
+    /// can be used to communicate with chips using the Xtensa
+    /// architecture.
+    ///
+    /// If an error occurs while trying to connect, the probe is returned.
+    pub fn try_into_xtensa_interface(
+        self,
+    ) -> Result<XtensaCommunicationInterface, (Self, DebugProbeError)> {
+        if !self.attached {
+            Err((self, DebugProbeError::NotAttached))
+        } else {
+            self.inner
+                .try_get_xtensa_interface()
+                .map_err(|(probe, err)| (Probe::from_attached_probe(probe), err))
+        }
+    }
+
     pub fn get_swo_interface(&self) -> Option<&dyn SwoAccess> {
         self.inner.get_swo_interface()
     }
@@ -389,6 +870,27 @@ impl Probe {
     pub fn try_as_dap_probe(&mut self) -> Option<&mut dyn DapProbe> {
         self.inner.try_as_dap_probe()
     }
+
+    /// The bridge of the probe to the SPI, I2C and UART interfaces of the target board,
+    /// if the probe has one.
+    pub fn get_bridge_interface_mut(&mut self) -> Option<&mut dyn ProbeBridge> {
+        self.inner.get_bridge_interface_mut()
+    }
+
+    /// Send a raw SWJ sequence, see [`DebugProbe::swj_sequence`].
+    pub fn swj_sequence(&mut self, bit_len: u8, bits: u64) -> Result<(), DebugProbeError> {
+        self.inner.swj_sequence(bit_len, bits)
+    }
+
+    /// Set and read the debug pins, see [`DebugProbe::swj_pins`].
+    pub fn swj_pins(
+        &mut self,
+        pin_out: u32,
+        pin_select: u32,
+        pin_wait: u32,
+    ) -> Result<u32, DebugProbeError> {
+        self.inner.swj_pins(pin_out, pin_select, pin_wait)
+    }
 }
 
 pub trait DebugProbe: Send + fmt::Debug {
@@ -421,11 +923,39 @@ pub trait DebugProbe: Send + fmt::Debug {
     ///
     fn set_speed(&mut self, speed_khz: u32) -> Result<u32, DebugProbeError>;
 
+    /// Get the speeds supported by the probe for the selected protocol.
+    ///
+    /// This is used to select a lower speed when the probe rejects a requested speed.
+    fn supported_speeds(&mut self) -> Result<SupportedSpeeds, DebugProbeError> {
+        Ok(SupportedSpeeds::Unknown)
+    }
+
+    /// Set how the probe reconnects when it drops off USB.
+    ///
+    /// Probes which can't reconnect ignore this.
+    fn set_reconnect_policy(&mut self, _policy: ReconnectPolicy) {}
+
+    /// Record the operations of the probe in `tracer`, see [`Probe::set_protocol_tracer`].
+    fn set_protocol_tracer(&mut self, _tracer: Option<ProtocolTracer>) {}
+
+    /// The tracer which was set with [`DebugProbe::set_protocol_tracer`], if the probe
+    /// supports tracing.
+    fn protocol_tracer(&self) -> Option<&ProtocolTracer> {
+        None
+    }
+
     /// Attach to the chip.
     ///
     /// This should run all the necessary protocol init routines.
     fn attach(&mut self) -> Result<(), DebugProbeError>;
 
+    /// The TAPs in the JTAG scan chain, starting with the TAP closest to TDO.
+    ///
+    /// This is empty for SWD, and for probes which don't scan the chain when they attach.
+    fn scan_chain(&self) -> &[ScanChainElement] {
+        &[]
+    }
+
     /// Detach from the chip.
     ///
     /// This should run all the necessary protocol deinit routines.
@@ -443,6 +973,38 @@ pub trait DebugProbe: Send + fmt::Debug {
     /// Selects the transport protocol to be used by the debug probe.
     fn select_protocol(&mut self, protocol: WireProtocol) -> Result<(), DebugProbeError>;
 
+    /// Send the lowest `bit_len` bits of `bits` on SWDIO/TMS, starting with the least
+    /// significant bit, like `DAP_SWJ_Sequence` of CMSIS-DAP.
+    ///
+    /// This can only be used for output, e.g. for the initial reset sequence or to wake up
+    /// a target from the dormant state.
+    fn swj_sequence(&mut self, _bit_len: u8, _bits: u64) -> Result<(), DebugProbeError> {
+        Err(DebugProbeError::CommandNotSupportedByProbe("swj_sequence"))
+    }
+
+    /// Set the debug pins in `pin_select` to their value in `pin_out`, wait up to
+    /// `pin_wait` microseconds for them to settle, and return the state of all pins, like
+    /// `DAP_SWJ_Pins` of CMSIS-DAP.
+    ///
+    /// The bits have the following meaning, see [`Pins`](crate::architecture::arm::Pins):
+    ///
+    /// Bit 0: SWCLK/TCK
+    /// Bit 1: SWDIO/TMS
+    /// Bit 2: TDI
+    /// Bit 3: TDO
+    /// Bit 5: nTRST
+    /// Bit 7: nRESET
+    ///
+    /// Probes which can't read the pins return `0xFFFF_FFFF`.
+    fn swj_pins(
+        &mut self,
+        _pin_out: u32,
+        _pin_select: u32,
+        _pin_wait: u32,
+    ) -> Result<u32, DebugProbeError> {
+        Err(DebugProbeError::CommandNotSupportedByProbe("swj_pins"))
+    }
+
     /// Check if the proble offers an interface to debug ARM chips.
     fn has_arm_interface(&self) -> bool {
         false
@@ -476,6 +1038,22 @@ pub trait DebugProbe: Send + fmt::Debug {
         false
     }
 
+    /// Get the dedicated interface to debug Xtensa chips. Ensure that the
+    /// probe actually supports this by calling [DebugProbe::has_xtensa_interface] first.
+    fn try_get_xtensa_interface(
+        self: Box<Self>,
+    ) -> Result<XtensaCommunicationInterface, (Box<dyn DebugProbe>, DebugProbeError)> {
+        Err((
+            self.into_probe(),
+            DebugProbeError::InterfaceNotAvailable("Xtensa"),
+        ))
+    }
+
+    /// Check if the probe offers an interface to debug Xtensa chips.
+    fn has_xtensa_interface(&self) -> bool {
+        false
+    }
+
     fn get_swo_interface(&self) -> Option<&dyn SwoAccess> {
         None
     }
@@ -484,6 +1062,12 @@ pub trait DebugProbe: Send + fmt::Debug {
         None
     }
 
+    /// The bridge of the probe to the SPI, I2C and UART interfaces of the target board,
+    /// if the probe has one.
+    fn get_bridge_interface_mut(&mut self) -> Option<&mut dyn ProbeBridge> {
+        None
+    }
+
     fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe>;
 
     fn try_as_dap_probe(&mut self) -> Option<&mut dyn DapProbe> {
@@ -570,7 +1154,8 @@ pub enum DebugProbeSelectorParseError {
 
 /// A struct to describe the way a probe should be selected.
 ///
-/// Construct this from a set of info or from a string.
+/// Construct this from a set of info or from a string. The string `simulator`
+/// selects the simulated probe, see [`DebugProbeSelector::simulator`].
 ///
 /// Example:
 /// ```
@@ -579,16 +1164,43 @@ pub enum DebugProbeSelectorParseError {
 /// ```
 #[derive(Debug, Clone, Serialize, Deserialize)]
 // We need this so that serde will first converst from the string `PID:VID:<Serial>` to a struct before deserializing.
-#[serde(try_from = "String")]
+#[serde(try_from = "String", into = "String")]
 pub struct DebugProbeSelector {
     pub vendor_id: u16,
     pub product_id: u16,
     pub serial_number: Option<String>,
 }
 
+/// The name of the simulated probe, which is used as its serial number.
+const SIMULATOR_NAME: &str = "simulator";
+
+impl DebugProbeSelector {
+    /// The selector of the simulated probe, which is only available with the `simulator` feature.
+    ///
+    /// The simulator does not use a USB device, so it uses an invalid VID and PID of 0.
+    pub fn simulator() -> Self {
+        DebugProbeSelector {
+            vendor_id: 0,
+            product_id: 0,
+            serial_number: Some(SIMULATOR_NAME.to_owned()),
+        }
+    }
+
+    /// Check if this selects the simulated probe.
+    pub fn is_simulator(&self) -> bool {
+        self.vendor_id == 0
+            && self.product_id == 0
+            && self.serial_number.as_deref() == Some(SIMULATOR_NAME)
+    }
+}
+
 impl TryFrom<&str> for DebugProbeSelector {
     type Error = DebugProbeSelectorParseError;
     fn try_from(value: &str) -> Result<Self, Self::Error> {
+        if value.eq_ignore_ascii_case(SIMULATOR_NAME) {
+            return Ok(DebugProbeSelector::simulator());
+        }
+
         let split = value.split(':').collect::<Vec<_>>();
         let mut selector = if split.len() > 1 {
             DebugProbeSelector {
@@ -642,8 +1254,18 @@ impl From<&DebugProbeInfo> for DebugProbeSelector {
     }
 }
 
+impl From<DebugProbeSelector> for String {
+    fn from(selector: DebugProbeSelector) -> Self {
+        selector.to_string()
+    }
+}
+
 impl fmt::Display for DebugProbeSelector {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_simulator() {
+            return write!(f, "{}", SIMULATOR_NAME);
+        }
+
         write!(f, "{:04x}:{:04x}", self.vendor_id, self.product_id)?;
         if let Some(ref sn) = self.serial_number {
             write!(f, ":{}", sn)?;
@@ -749,3 +1371,65 @@ pub enum AttachMethod {
     Normal,
     UnderReset,
 }
+
+#[cfg(test)]
+mod test {
+    use super::SupportedSpeeds;
+
+    #[test]
+    fn next_lower_discrete_speed() {
+        let speeds = SupportedSpeeds::Discrete(vec![100, 1800, 4600, 950]);
+
+        assert_eq!(speeds.next_lower(4000), Some(1800));
+        assert_eq!(speeds.next_lower(1800), Some(950));
+        assert_eq!(speeds.next_lower(100), None);
+    }
+
+    #[test]
+    fn next_lower_speed_in_range() {
+        let speeds = SupportedSpeeds::Range {
+            min_khz: 100,
+            max_khz: 12_000,
+        };
+
+        assert_eq!(speeds.next_lower(50_000), Some(12_000));
+        assert_eq!(speeds.next_lower(4_000), Some(2_000));
+        assert_eq!(speeds.next_lower(150), None);
+    }
+
+    #[test]
+    fn next_lower_unknown_speed() {
+        assert_eq!(SupportedSpeeds::Unknown.next_lower(4_000), Some(2_000));
+        assert_eq!(SupportedSpeeds::Unknown.next_lower(1), None);
+    }
+
+    #[test]
+    #[cfg(feature = "libusb")]
+    fn usb_access_error() {
+        use super::{ProbeCreationError, UsbAccessHint, UsbOperation};
+
+        let error =
+            ProbeCreationError::from_usb(0x1366, 0x1015, UsbOperation::Open, rusb::Error::Access);
+
+        assert!(matches!(
+            error,
+            ProbeCreationError::UsbAccess {
+                vendor_id: 0x1366,
+                product_id: 0x1015,
+                operation: UsbOperation::Open,
+                hint: UsbAccessHint::Permissions,
+                ..
+            }
+        ));
+
+        // Errors which are not caused by the access to the device are kept.
+        let error = ProbeCreationError::from_usb(
+            0x1366,
+            0x1015,
+            UsbOperation::ClaimInterface(0),
+            rusb::Error::Io,
+        );
+
+        assert!(matches!(error, ProbeCreationError::Rusb(rusb::Error::Io)));
+    }
+}
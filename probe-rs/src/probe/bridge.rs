@@ -0,0 +1,109 @@
+//! Bridges of debug probes to the SPI, I2C and UART interfaces of the target board.
+//!
+//! Some probes can drive other interfaces than the debug port, e.g. the UART of the
+//! target. This can be used in tests to stimulate the peripherals of the target while
+//! it is debugged over the same probe. The bridge of a probe is optional, see
+//! [`Probe::get_bridge_interface_mut`](crate::Probe::get_bridge_interface_mut) and
+//! [`Session::probe_bridge`](crate::Session::probe_bridge).
+
+use super::DebugProbeError;
+
+/// The interfaces which are bridged by a [`ProbeBridge`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BridgeInterfaces {
+    /// The probe is an SPI master.
+    pub spi: bool,
+    /// The probe is an I2C master.
+    pub i2c: bool,
+    /// The probe has a UART.
+    pub uart: bool,
+}
+
+/// The clock polarity and phase of the SPI bridge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpiMode {
+    /// CPOL = 0, CPHA = 0.
+    Mode0,
+    /// CPOL = 0, CPHA = 1.
+    Mode1,
+    /// CPOL = 1, CPHA = 0.
+    Mode2,
+    /// CPOL = 1, CPHA = 1.
+    Mode3,
+}
+
+/// The configuration of the SPI bridge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpiConfig {
+    /// The requested clock frequency in Hz.
+    pub frequency_hz: u32,
+    /// The clock polarity and phase.
+    pub mode: SpiMode,
+    /// Send the least significant bit of each byte first.
+    pub lsb_first: bool,
+}
+
+/// The configuration of the UART bridge. The UART always uses 8 data bits, no parity and
+/// one stop bit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UartConfig {
+    /// The requested baud rate.
+    pub baud_rate: u32,
+}
+
+/// The bridge of a probe to the SPI, I2C and UART interfaces of the target board.
+///
+/// The interfaces which are not bridged by the probe return
+/// [`DebugProbeError::CommandNotSupportedByProbe`], see [`ProbeBridge::bridge_interfaces`].
+pub trait ProbeBridge {
+    /// The interfaces which are bridged by the probe.
+    fn bridge_interfaces(&self) -> BridgeInterfaces;
+
+    /// Configure the SPI bridge, and return the clock frequency which is used.
+    fn spi_configure(&mut self, _config: &SpiConfig) -> Result<u32, DebugProbeError> {
+        Err(DebugProbeError::CommandNotSupportedByProbe("spi_configure"))
+    }
+
+    /// Send `write` on MOSI while chip select is asserted, and receive the same number of
+    /// bytes from MISO into `read`.
+    ///
+    /// `read` has to be as long as `write`.
+    fn spi_transfer(&mut self, _write: &[u8], _read: &mut [u8]) -> Result<(), DebugProbeError> {
+        Err(DebugProbeError::CommandNotSupportedByProbe("spi_transfer"))
+    }
+
+    /// Configure the clock frequency of the I2C bridge in kHz.
+    fn i2c_configure(&mut self, _speed_khz: u32) -> Result<(), DebugProbeError> {
+        Err(DebugProbeError::CommandNotSupportedByProbe("i2c_configure"))
+    }
+
+    /// Write `data` to the I2C device with the 7 bit `address`.
+    fn i2c_write(&mut self, _address: u8, _data: &[u8]) -> Result<(), DebugProbeError> {
+        Err(DebugProbeError::CommandNotSupportedByProbe("i2c_write"))
+    }
+
+    /// Read `data.len()` bytes from the I2C device with the 7 bit `address`.
+    fn i2c_read(&mut self, _address: u8, _data: &mut [u8]) -> Result<(), DebugProbeError> {
+        Err(DebugProbeError::CommandNotSupportedByProbe("i2c_read"))
+    }
+
+    /// Configure and enable the UART bridge, and return the baud rate which is used.
+    fn uart_configure(&mut self, _config: &UartConfig) -> Result<u32, DebugProbeError> {
+        Err(DebugProbeError::CommandNotSupportedByProbe(
+            "uart_configure",
+        ))
+    }
+
+    /// Send `data` on the UART, and return the number of bytes which were sent.
+    ///
+    /// Fewer bytes than `data.len()` are sent if the transmit buffer of the probe is full.
+    fn uart_write(&mut self, _data: &[u8]) -> Result<usize, DebugProbeError> {
+        Err(DebugProbeError::CommandNotSupportedByProbe("uart_write"))
+    }
+
+    /// Read the bytes which were received on the UART into `data`, without waiting, and
+    /// return the number of bytes which were read.
+    fn uart_read(&mut self, _data: &mut [u8]) -> Result<usize, DebugProbeError> {
+        Err(DebugProbeError::CommandNotSupportedByProbe("uart_read"))
+    }
+}
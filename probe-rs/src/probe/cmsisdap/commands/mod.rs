@@ -3,8 +3,10 @@ pub mod swd;
 pub mod swj;
 pub mod swo;
 pub mod transfer;
+pub mod uart;
 
 use crate::probe::cmsisdap::commands::general::info::PacketSizeCommand;
+use crate::probe::cmsisdap::transport::CmsisDapTransport;
 use crate::DebugProbeError;
 use std::str::Utf8Error;
 use std::time::Duration;
@@ -29,15 +31,19 @@ pub enum CmsisDapError {
     #[error("Requested SWO mode is not available on this probe")]
     SwoModeNotAvailable,
     #[error("USB Error reading SWO data.")]
-    SwoReadError(#[source] rusb::Error),
+    SwoReadError(#[source] SendError),
     #[error("Could not determine a suitable packet size for this probe")]
     NoPacketSize,
+    #[error("Requested UART settings could not be configured")]
+    UartNotConfigured,
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum SendError {
+    #[cfg(feature = "cmsisdap")]
     #[error("Error in the USB HID access")]
     HidApi(#[from] hidapi::HidError),
+    #[cfg(feature = "cmsisdap")]
     #[error("Error in the USB access")]
     UsbError(rusb::Error),
     #[error("Not enough data in response from probe")]
@@ -58,8 +64,12 @@ pub enum SendError {
     UnexpectedAnswer,
     #[error("Timeout in USB communication.")]
     Timeout,
+    /// An error of a transport which is implemented outside of probe-rs.
+    #[error("Error in the transport")]
+    Transport(#[source] Box<dyn std::error::Error + Send + Sync>),
 }
 
+#[cfg(feature = "cmsisdap")]
 impl From<rusb::Error> for SendError {
     fn from(error: rusb::Error) -> Self {
         match error {
@@ -75,111 +85,47 @@ impl From<CmsisDapError> for DebugProbeError {
     }
 }
 
-pub enum CmsisDapDevice {
-    /// CMSIS-DAP v1 over HID.
-    /// Stores a HID device handle and maximum HID report size.
-    V1 {
-        handle: hidapi::HidDevice,
-        report_size: usize,
-    },
-
-    /// CMSIS-DAP v2 over WinUSB/Bulk.
-    /// Stores an rusb device handle, out/in EP addresses, maximum DAP packet size,
-    /// and an optional SWO streaming EP address and SWO maximum packet size.
-    V2 {
-        handle: rusb::DeviceHandle<rusb::Context>,
-        out_ep: u8,
-        in_ep: u8,
-        max_packet_size: usize,
-        swo_ep: Option<(u8, usize)>,
-    },
+/// A CMSIS-DAP probe, connected with a [`CmsisDapTransport`].
+pub struct CmsisDapDevice {
+    transport: Box<dyn CmsisDapTransport>,
+    /// The size of the packets, the HID report size for v1 devices, or the maximum
+    /// bulk transfer size for v2 devices.
+    packet_size: usize,
 }
 
 impl CmsisDapDevice {
-    /// Read from the probe into `buf`, returning the number of bytes read on success.
-    fn read(&self, buf: &mut [u8]) -> Result<usize, SendError> {
-        match self {
-            CmsisDapDevice::V1 { handle, .. } => match handle.read_timeout(buf, 100)? {
-                // Timeout is not indicated by error, but by returning 0 read bytes
-                0 => Err(SendError::Timeout),
-                n => Ok(n),
-            },
-            CmsisDapDevice::V2 { handle, in_ep, .. } => {
-                let timeout = Duration::from_millis(100);
-                Ok(handle.read_bulk(*in_ep, buf, timeout)?)
-            }
-        }
-    }
-
-    /// Write `buf` to the probe, returning the number of bytes written on success.
-    fn write(&self, buf: &[u8]) -> Result<usize, SendError> {
-        match self {
-            CmsisDapDevice::V1 { handle, .. } => Ok(handle.write(buf)?),
-            CmsisDapDevice::V2 { handle, out_ep, .. } => {
-                let timeout = Duration::from_millis(100);
-                // Skip first byte as it's set to 0 for HID transfers
-                Ok(handle.write_bulk(*out_ep, &buf[1..], timeout)?)
-            }
+    /// The timeout of the transfers of the commands.
+    const TIMEOUT: Duration = Duration::from_millis(100);
+
+    /// A probe which is connected with `transport`, using its initial packet size until
+    /// the packet size is read from the probe.
+    pub fn new(transport: Box<dyn CmsisDapTransport>) -> Self {
+        Self {
+            packet_size: transport.initial_packet_size(),
+            transport,
         }
     }
 
     /// Drain any pending data from the probe, ensuring future responses are
     /// synchronised to requests. Swallows any errors, which are expected if
     /// there is no pending data to read.
-    pub(super) fn drain(&self) {
+    pub(super) fn drain(&mut self) {
         log::debug!("Draining probe of any pending data.");
 
-        match self {
-            CmsisDapDevice::V1 {
-                handle,
-                report_size,
-                ..
-            } => loop {
-                let mut discard = vec![0u8; report_size + 1];
-                match handle.read_timeout(&mut discard, 1) {
-                    Ok(n) if n != 0 => continue,
-                    _ => break,
-                }
-            },
-
-            CmsisDapDevice::V2 {
-                handle,
-                in_ep,
-                max_packet_size,
-                ..
-            } => {
-                let timeout = Duration::from_millis(1);
-                let mut discard = vec![0u8; *max_packet_size];
-                loop {
-                    match handle.read_bulk(*in_ep, &mut discard, timeout) {
-                        Ok(n) if n != 0 => continue,
-                        _ => break,
-                    }
-                }
+        let timeout = Duration::from_millis(1);
+        let mut discard = vec![0u8; self.packet_size];
+        loop {
+            match self.transport.read(&mut discard, timeout) {
+                Ok(n) if n != 0 => continue,
+                _ => break,
             }
         }
     }
 
     /// Set the packet size to use for this device.
-    ///
-    /// Sets either the HID report size for V1 devices,
-    /// or the maximum bulk transfer size for V2 devices.
     pub(super) fn set_packet_size(&mut self, packet_size: usize) {
         log::debug!("Configuring probe to use packet size {}", packet_size);
-        match self {
-            CmsisDapDevice::V1 {
-                ref mut report_size,
-                ..
-            } => {
-                *report_size = packet_size;
-            }
-            CmsisDapDevice::V2 {
-                ref mut max_packet_size,
-                ..
-            } => {
-                *max_packet_size = packet_size;
-            }
-        }
+        self.packet_size = packet_size;
     }
 
     /// Attempt to determine the correct packet size for this device.
@@ -217,10 +163,7 @@ impl CmsisDapDevice {
 
     /// Check if SWO streaming is supported by this device.
     pub(super) fn swo_streaming_supported(&self) -> bool {
-        match self {
-            CmsisDapDevice::V1 { .. } => false,
-            CmsisDapDevice::V2 { swo_ep, .. } => swo_ep.is_some(),
-        }
+        self.transport.swo_packet_size().is_some()
     }
 
     /// Read from the SWO streaming endpoint.
@@ -228,26 +171,18 @@ impl CmsisDapDevice {
     /// Returns SWOModeNotAvailable if this device does not support SWO streaming.
     ///
     /// On timeout, returns a zero-length buffer.
-    pub(super) fn read_swo_stream(&self, timeout: Duration) -> Result<Vec<u8>, CmsisDapError> {
-        match self {
-            CmsisDapDevice::V1 { .. } => Err(CmsisDapError::SwoModeNotAvailable),
-            CmsisDapDevice::V2 { handle, swo_ep, .. } => match swo_ep {
-                Some((ep, len)) => {
-                    let mut buf = vec![0u8; *len];
-                    match handle.read_bulk(*ep, &mut buf, timeout) {
-                        Ok(n) => {
-                            buf.truncate(n);
-                            Ok(buf)
-                        }
-                        Err(rusb::Error::Timeout) => {
-                            buf.truncate(0);
-                            Ok(buf)
-                        }
-                        Err(e) => Err(CmsisDapError::SwoReadError(e)),
-                    }
-                }
-                None => Err(CmsisDapError::SwoModeNotAvailable),
-            },
+    pub(super) fn read_swo_stream(&mut self, timeout: Duration) -> Result<Vec<u8>, CmsisDapError> {
+        match self.transport.swo_packet_size() {
+            Some(len) => {
+                let mut buf = vec![0u8; len];
+                let n = self
+                    .transport
+                    .read_swo(&mut buf, timeout)
+                    .map_err(CmsisDapError::SwoReadError)?;
+                buf.truncate(n);
+                Ok(buf)
+            }
+            None => Err(CmsisDapError::SwoModeNotAvailable),
         }
     }
 }
@@ -335,36 +270,32 @@ fn send_command_inner<Req: Request>(
     device: &mut CmsisDapDevice,
     request: Req,
 ) -> Result<Req::Response, SendError> {
-    // Size the buffer for the maximum packet size.
-    // On v1, we always send this full-sized report, while
-    // on v2 we can truncate to just the required data.
-    // Add one byte for HID report ID.
-    let buffer_len: usize = match device {
-        CmsisDapDevice::V1 { report_size, .. } => *report_size + 1,
-        CmsisDapDevice::V2 {
-            max_packet_size, ..
-        } => *max_packet_size + 1,
-    };
-    let mut buffer = vec![0; buffer_len];
-
-    // Leave byte 0 as the HID report, and write the command and request to the buffer.
-    buffer[1] = Req::COMMAND_ID as u8;
-    let mut size = request.to_bytes(&mut buffer[2..])? + 2;
+    // Size the buffer for the packet size. On v1, we always send
+    // this full-sized report, while on v2 we can truncate to just
+    // the required data.
+    let mut buffer = vec![0; device.packet_size];
+
+    // Write the command and request to the buffer.
+    buffer[0] = Req::COMMAND_ID as u8;
+    let mut size = request.to_bytes(&mut buffer[1..])? + 1;
 
     // For HID devices we must write a full report every time,
-    // so set the transfer size to the report size, plus one
-    // byte for the HID report ID. On v2 devices, we just
-    // write the exact required size every time.
-    if let CmsisDapDevice::V1 { report_size, .. } = device {
-        size = *report_size + 1;
+    // so set the transfer size to the report size. On v2 devices,
+    // we just write the exact required size every time.
+    if device.transport.pads_packets() {
+        size = device.packet_size;
     }
 
     // Send buffer to the device.
-    let _ = device.write(&buffer[..size])?;
+    let _ = device
+        .transport
+        .write(&buffer[..size], CmsisDapDevice::TIMEOUT)?;
     trace_buffer("Transmit buffer", &buffer[..size]);
 
     // Read back response.
-    let bytes_read = device.read(&mut buffer)?;
+    let bytes_read = device
+        .transport
+        .read(&mut buffer, CmsisDapDevice::TIMEOUT)?;
     let response_data = &buffer[..bytes_read];
     trace_buffer("Receive buffer", response_data);
 
@@ -236,6 +236,10 @@ impl Request for DataRequest {
     }
 
     fn from_bytes(&self, buffer: &[u8]) -> Result<Self::Response, SendError> {
+        if buffer.len() < 3 {
+            return Err(SendError::NotEnoughData);
+        }
+
         let status = TraceStatus::from(buffer[0]);
         let count = u16::from_le_bytes(
             buffer[1..3]
@@ -0,0 +1,158 @@
+use scroll::{Pread, LE};
+
+use super::{CommandId, Request, SendError, Status};
+
+#[repr(u8)]
+#[allow(unused)]
+#[derive(Copy, Clone, Debug)]
+pub enum TransportRequest {
+    NoTransport = 0,
+    UsbComPort = 1,
+    DapCommand = 2,
+}
+
+impl Request for TransportRequest {
+    const COMMAND_ID: CommandId = CommandId::UartTransport;
+
+    type Response = TransportResponse;
+
+    fn to_bytes(&self, buffer: &mut [u8]) -> Result<usize, SendError> {
+        buffer[0] = *self as u8;
+        Ok(1)
+    }
+
+    fn from_bytes(&self, buffer: &[u8]) -> Result<Self::Response, SendError> {
+        Ok(TransportResponse(Status::from_byte(buffer[0])?))
+    }
+}
+
+#[derive(Debug)]
+pub struct TransportResponse(pub(crate) Status);
+
+/// Configure the UART. A `control` value of zero selects 8 data bits, no parity and
+/// one stop bit.
+#[derive(Copy, Clone, Debug)]
+pub struct ConfigureRequest {
+    pub(crate) control: u8,
+    pub(crate) baud_rate: u32,
+}
+
+impl Request for ConfigureRequest {
+    const COMMAND_ID: CommandId = CommandId::UartConfigure;
+
+    type Response = ConfigureResponse;
+
+    fn to_bytes(&self, buffer: &mut [u8]) -> Result<usize, SendError> {
+        buffer[0] = self.control;
+        buffer[1..5].copy_from_slice(&self.baud_rate.to_le_bytes());
+        Ok(5)
+    }
+
+    fn from_bytes(&self, buffer: &[u8]) -> Result<Self::Response, SendError> {
+        if buffer.len() < 5 {
+            return Err(SendError::NotEnoughData);
+        }
+
+        Ok(ConfigureResponse {
+            errors: buffer[0],
+            baud_rate: buffer
+                .pread_with(1, LE)
+                .map_err(|_| SendError::NotEnoughData)?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct ConfigureResponse {
+    /// The settings which could not be configured: bit 0 for the data bits, bit 1 for the
+    /// parity and bit 2 for the stop bits.
+    pub(crate) errors: u8,
+    /// The baud rate which is used.
+    pub(crate) baud_rate: u32,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct ControlRequest(pub(crate) u8);
+
+impl ControlRequest {
+    pub(crate) const RX_ENABLE: u8 = 1 << 0;
+    pub(crate) const RX_FLUSH: u8 = 1 << 2;
+    pub(crate) const TX_ENABLE: u8 = 1 << 4;
+    pub(crate) const TX_FLUSH: u8 = 1 << 6;
+}
+
+impl Request for ControlRequest {
+    const COMMAND_ID: CommandId = CommandId::UartControl;
+
+    type Response = ControlResponse;
+
+    fn to_bytes(&self, buffer: &mut [u8]) -> Result<usize, SendError> {
+        buffer[0] = self.0;
+        Ok(1)
+    }
+
+    fn from_bytes(&self, buffer: &[u8]) -> Result<Self::Response, SendError> {
+        Ok(ControlResponse(Status::from_byte(buffer[0])?))
+    }
+}
+
+#[derive(Debug)]
+pub struct ControlResponse(pub(crate) Status);
+
+/// Send `data` on the UART, and receive the data which the probe has buffered.
+#[derive(Clone, Debug)]
+pub struct TransferRequest<'a> {
+    pub(crate) data: &'a [u8],
+}
+
+impl<'a> Request for TransferRequest<'a> {
+    const COMMAND_ID: CommandId = CommandId::UartTransfer;
+
+    type Response = TransferResponse;
+
+    fn to_bytes(&self, buffer: &mut [u8]) -> Result<usize, SendError> {
+        // The caller limits the data to the packet size.
+        assert!(
+            buffer.len() >= 2 + self.data.len(),
+            "Buffer for CMSIS-DAP command is too small. This is a bug, please report it."
+        );
+
+        buffer[0..2].copy_from_slice(&(self.data.len() as u16).to_le_bytes());
+        buffer[2..2 + self.data.len()].copy_from_slice(self.data);
+        Ok(2 + self.data.len())
+    }
+
+    fn from_bytes(&self, buffer: &[u8]) -> Result<Self::Response, SendError> {
+        if buffer.len() < 5 {
+            return Err(SendError::NotEnoughData);
+        }
+
+        let tx_count: u16 = buffer
+            .pread_with(1, LE)
+            .map_err(|_| SendError::NotEnoughData)?;
+        let rx_count: u16 = buffer
+            .pread_with(3, LE)
+            .map_err(|_| SendError::NotEnoughData)?;
+
+        let data = buffer
+            .get(5..5 + rx_count as usize)
+            .ok_or(SendError::NotEnoughData)?;
+
+        Ok(TransferResponse {
+            status: buffer[0],
+            tx_count: tx_count as usize,
+            data: data.to_vec(),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct TransferResponse {
+    /// The error flags of the UART: bit 0 if received data was lost, bit 1 for a framing
+    /// error and bit 2 for a parity error.
+    pub(crate) status: u8,
+    /// The number of bytes which were sent.
+    pub(crate) tx_count: usize,
+    /// The received data.
+    pub(crate) data: Vec<u8>,
+}
@@ -1,5 +1,13 @@
 pub mod commands;
+#[cfg(feature = "cmsisdap")]
 pub mod tools;
+mod transport;
+#[cfg(feature = "webusb")]
+mod webusb;
+
+pub use transport::CmsisDapTransport;
+#[cfg(feature = "webusb")]
+pub use webusb::{WebUsbError, WebUsbTransport};
 
 use crate::{
     architecture::arm::{
@@ -7,17 +15,18 @@ use crate::{
         communication_interface::UninitializedArmProbe,
         dp::{Abort, Ctrl},
         swo::poll_interval_from_buf_size,
-        ArmCommunicationInterface, DapError, DpAddress, Pins, PortType, RawDapAccess, Register,
-        SwoAccess, SwoConfig, SwoMode,
+        ArmCommunicationInterface, DapError, DapTransfer, DpAddress, Pins, PortType, RawDapAccess,
+        Register, SwoAccess, SwoConfig, SwoMode,
     },
     probe::{
         cmsisdap::commands::{
             general::info::{CapabilitiesCommand, PacketCountCommand, SWOTraceBufferSizeCommand},
             CmsisDapError,
         },
-        BatchCommand,
+        BatchCommand, BridgeInterfaces, ProbeBridge, UartConfig,
     },
-    DebugProbe, DebugProbeError, DebugProbeSelector, Error as ProbeRsError, WireProtocol,
+    DebugProbe, DebugProbeError, DebugProbeSelector, Error as ProbeRsError, ProtocolTracer,
+    WireProtocol,
 };
 
 use commands::{
@@ -40,9 +49,10 @@ use commands::{
         Ack, InnerTransferRequest, TransferBlockRequest, TransferBlockResponse, TransferRequest,
         RW,
     },
-    CmsisDapDevice, Status,
+    uart, CmsisDapDevice, Status,
 };
 
+use std::collections::VecDeque;
 use std::time::Duration;
 
 pub struct CmsisDap {
@@ -57,11 +67,17 @@ pub struct CmsisDap {
     swo_buffer_size: Option<usize>,
     swo_active: bool,
     swo_streaming: bool,
+    /// Overflows of the SWO buffer since SWO was enabled.
+    swo_overflows: u64,
+    /// Data received on the UART, which was not read yet.
+    uart_received: VecDeque<u8>,
 
     /// Speed in kHz
     speed_khz: u32,
 
     batch: Vec<BatchCommand>,
+
+    tracer: Option<ProtocolTracer>,
 }
 
 impl std::fmt::Debug for CmsisDap {
@@ -74,6 +90,7 @@ impl std::fmt::Debug for CmsisDap {
             .field("swo_buffer_size", &self.swo_buffer_size)
             .field("swo_active", &self.swo_active)
             .field("swo_streaming", &self.swo_streaming)
+            .field("swo_overflows", &self.swo_overflows)
             .field("speed_khz", &self.speed_khz)
             .finish()
     }
@@ -111,8 +128,11 @@ impl CmsisDap {
             swo_buffer_size,
             swo_active: false,
             swo_streaming: false,
+            swo_overflows: 0,
             speed_khz: 1_000,
             batch: Vec::new(),
+            tracer: None,
+            uart_received: VecDeque::new(),
         })
     }
 
@@ -373,6 +393,11 @@ impl CmsisDap {
                 if response.status.error {
                     Err(CmsisDapError::SwoTraceStreamError.into())
                 } else {
+                    if response.status.overrun {
+                        self.swo_overflows += 1;
+                        log::warn!("The SWO buffer of the probe overflowed, trace data was lost.");
+                    }
+
                     Ok(response.data)
                 }
             }
@@ -388,9 +413,22 @@ impl DebugProbe for CmsisDap {
     where
         Self: Sized,
     {
-        Ok(Box::new(Self::new_from_device(
-            tools::open_device_from_selector(selector)?,
-        )?))
+        #[cfg(feature = "cmsisdap")]
+        {
+            Ok(Box::new(Self::new_from_device(
+                tools::open_device_from_selector(selector)?,
+            )?))
+        }
+
+        // Without libusb and hidapi, the probes are only connected with
+        // `Probe::from_cmsisdap_transport`.
+        #[cfg(not(feature = "cmsisdap"))]
+        {
+            let _ = selector.into();
+            Err(DebugProbeError::ProbeCouldNotBeCreated(
+                crate::ProbeCreationError::NotFound,
+            ))
+        }
     }
 
     fn get_name(&self) -> &str {
@@ -408,13 +446,29 @@ impl DebugProbe for CmsisDap {
     /// used by the probe cannot be determined, but it will not be
     /// higher than this value.
     fn set_speed(&mut self, speed_khz: u32) -> Result<u32, DebugProbeError> {
-        self.set_swj_clock(speed_khz * 1_000)?;
+        let speed_hz = speed_khz
+            .checked_mul(1_000)
+            .ok_or(DebugProbeError::UnsupportedSpeed(speed_khz))?;
+
+        // The probe reports an error if it cannot generate the requested clock.
+        self.set_swj_clock(speed_hz).map_err(|error| match error {
+            CmsisDapError::ErrorResponse => DebugProbeError::UnsupportedSpeed(speed_khz),
+            other => other.into(),
+        })?;
         self.speed_khz = speed_khz;
 
         Ok(speed_khz)
     }
 
     /// Enters debug mode.
+    fn set_protocol_tracer(&mut self, tracer: Option<ProtocolTracer>) {
+        self.tracer = tracer;
+    }
+
+    fn protocol_tracer(&self) -> Option<&ProtocolTracer> {
+        self.tracer.as_ref()
+    }
+
     fn attach(&mut self) -> Result<(), DebugProbeError> {
         log::debug!("Attaching to target system (clock = {}kHz)", self.speed_khz);
 
@@ -515,6 +569,27 @@ impl DebugProbe for CmsisDap {
         Ok(())
     }
 
+    fn swj_sequence(&mut self, bit_len: u8, bits: u64) -> Result<(), DebugProbeError> {
+        let data = bits.to_le_bytes();
+
+        self.send_swj_sequences(SequenceRequest::new(&data, bit_len)?)?;
+
+        Ok(())
+    }
+
+    fn swj_pins(
+        &mut self,
+        pin_out: u32,
+        pin_select: u32,
+        pin_wait: u32,
+    ) -> Result<u32, DebugProbeError> {
+        let request = SWJPinsRequest::from_raw_values(pin_out as u8, pin_select as u8, pin_wait);
+
+        let Pins(response) = commands::send_command(&mut self.device, request)?;
+
+        Ok(response as u32)
+    }
+
     fn get_swo_interface(&self) -> Option<&dyn SwoAccess> {
         Some(self as _)
     }
@@ -523,6 +598,14 @@ impl DebugProbe for CmsisDap {
         Some(self as _)
     }
 
+    fn get_bridge_interface_mut(&mut self) -> Option<&mut dyn ProbeBridge> {
+        if self.capabilities.uart_communication_port_implemented {
+            Some(self as _)
+        } else {
+            None
+        }
+    }
+
     fn try_get_arm_interface<'probe>(
         self: Box<Self>,
     ) -> Result<Box<dyn UninitializedArmProbe + 'probe>, (Box<dyn DebugProbe>, DebugProbeError)>
@@ -608,6 +691,85 @@ impl RawDapAccess for CmsisDap {
             .map(|_| ())
     }
 
+    fn raw_transfer_batch(
+        &mut self,
+        port: PortType,
+        transfers: &mut [DapTransfer],
+    ) -> Result<(), DebugProbeError> {
+        self.process_batch()?;
+
+        // Every transfer needs one byte in the request, writes 4 more for their value,
+        // and reads need 4 bytes in the response. Both have a header of 3 bytes.
+        let max_size = self.packet_size as usize - 3;
+
+        let mut start = 0;
+        while start < transfers.len() {
+            let mut request_size = 0;
+            let mut response_size = 0;
+            let mut end = start;
+
+            while end < transfers.len() && end - start < 255 {
+                let (request, response) = match transfers[end] {
+                    DapTransfer::Read { .. } => (1, 4),
+                    DapTransfer::Write { .. } => (5, 0),
+                };
+
+                if request_size + request > max_size || response_size + response > max_size {
+                    break;
+                }
+
+                request_size += request;
+                response_size += response;
+                end += 1;
+            }
+
+            let requests: Vec<InnerTransferRequest> = transfers[start..end]
+                .iter()
+                .map(|transfer| match *transfer {
+                    DapTransfer::Read { addr, .. } => {
+                        InnerTransferRequest::new(port, RW::R, addr, None)
+                    }
+                    DapTransfer::Write { addr, value } => {
+                        InnerTransferRequest::new(port, RW::W, addr, Some(value))
+                    }
+                })
+                .collect();
+
+            let response = commands::send_command::<TransferRequest>(
+                &mut self.device,
+                TransferRequest::new(&requests),
+            )
+            .map_err(CmsisDapError::from)?;
+
+            log::debug!(
+                "{} of batch of {} transfers succeeded",
+                response.transfer_count,
+                requests.len()
+            );
+
+            if response.last_transfer_response.protocol_error {
+                return Err(DapError::SwdProtocol.into());
+            }
+
+            match response.last_transfer_response.ack {
+                Ack::Ok => (),
+                Ack::NoAck => return Err(DapError::NoAcknowledge.into()),
+                Ack::Fault => return Err(DapError::FaultResponse.into()),
+                Ack::Wait => return Err(DapError::WaitResponse.into()),
+            }
+
+            for (transfer, response) in transfers[start..end].iter_mut().zip(&response.transfers) {
+                if let (DapTransfer::Read { value, .. }, Some(data)) = (transfer, response.data) {
+                    *value = data;
+                }
+            }
+
+            start = end;
+        }
+
+        Ok(())
+    }
+
     fn raw_write_block(
         &mut self,
         port: PortType,
@@ -699,30 +861,97 @@ impl RawDapAccess for CmsisDap {
     fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
         self
     }
+}
 
-    fn swj_sequence(&mut self, bit_len: u8, bits: u64) -> Result<(), DebugProbeError> {
-        let data = bits.to_le_bytes();
+impl DapProbe for CmsisDap {}
 
-        self.send_swj_sequences(SequenceRequest::new(&data, bit_len)?)?;
+impl CmsisDap {
+    /// Send `data` on the UART, buffer the received data, and return the number of bytes
+    /// which were sent.
+    fn uart_transfer(&mut self, data: &[u8]) -> Result<usize, DebugProbeError> {
+        // The request has a header of 3 bytes, the response of 6 bytes.
+        let max_len = self.packet_size as usize - 6;
+        let data = &data[..data.len().min(max_len)];
 
-        Ok(())
-    }
+        let response = commands::send_command(&mut self.device, uart::TransferRequest { data })?;
 
-    fn swj_pins(
-        &mut self,
-        pin_out: u32,
-        pin_select: u32,
-        pin_wait: u32,
-    ) -> Result<u32, DebugProbeError> {
-        let request = SWJPinsRequest::from_raw_values(pin_out as u8, pin_select as u8, pin_wait);
+        if response.status != 0 {
+            log::warn!(
+                "The UART of the probe reported errors: {:#x}",
+                response.status
+            );
+        }
 
-        let Pins(response) = commands::send_command(&mut self.device, request)?;
+        self.uart_received.extend(response.data);
 
-        Ok(response as u32)
+        Ok(response.tx_count)
     }
 }
 
-impl DapProbe for CmsisDap {}
+impl ProbeBridge for CmsisDap {
+    fn bridge_interfaces(&self) -> BridgeInterfaces {
+        BridgeInterfaces {
+            uart: self.capabilities.uart_communication_port_implemented,
+            ..Default::default()
+        }
+    }
+
+    fn uart_configure(&mut self, config: &UartConfig) -> Result<u32, DebugProbeError> {
+        if !self.capabilities.uart_communication_port_implemented {
+            return Err(DebugProbeError::CommandNotSupportedByProbe(
+                "uart_configure",
+            ));
+        }
+
+        let response =
+            commands::send_command(&mut self.device, uart::TransportRequest::DapCommand)?;
+        if let uart::TransportResponse(Status::DAPError) = response {
+            return Err(CmsisDapError::ErrorResponse.into());
+        }
+
+        let response = commands::send_command(
+            &mut self.device,
+            uart::ConfigureRequest {
+                control: 0,
+                baud_rate: config.baud_rate,
+            },
+        )?;
+        if response.errors != 0 {
+            return Err(CmsisDapError::UartNotConfigured.into());
+        }
+        let baud_rate = response.baud_rate;
+
+        let control = uart::ControlRequest::RX_ENABLE
+            | uart::ControlRequest::RX_FLUSH
+            | uart::ControlRequest::TX_ENABLE
+            | uart::ControlRequest::TX_FLUSH;
+        let response = commands::send_command(&mut self.device, uart::ControlRequest(control))?;
+        if let uart::ControlResponse(Status::DAPError) = response {
+            return Err(CmsisDapError::ErrorResponse.into());
+        }
+
+        self.uart_received.clear();
+
+        Ok(baud_rate)
+    }
+
+    fn uart_write(&mut self, data: &[u8]) -> Result<usize, DebugProbeError> {
+        self.uart_transfer(data)
+    }
+
+    fn uart_read(&mut self, data: &mut [u8]) -> Result<usize, DebugProbeError> {
+        if self.uart_received.is_empty() {
+            self.uart_transfer(&[])?;
+        }
+
+        let len = data.len().min(self.uart_received.len());
+        for (byte, received) in data.iter_mut().zip(self.uart_received.drain(..len)) {
+            *byte = received;
+        }
+
+        Ok(len)
+    }
+}
 
 impl SwoAccess for CmsisDap {
     fn enable_swo(&mut self, config: &SwoConfig) -> Result<(), ProbeRsError> {
@@ -780,6 +1009,7 @@ impl SwoAccess for CmsisDap {
         self.start_swo_capture()?;
 
         self.swo_active = true;
+        self.swo_overflows = 0;
         Ok(())
     }
 
@@ -800,7 +1030,21 @@ impl SwoAccess for CmsisDap {
                 log::trace!("SWO streaming buffer: {:?}", buffer);
                 Ok(buffer)
             } else {
-                let data = self.get_swo_data()?;
+                // Poll until there is data, or the timeout expired.
+                let start = web_time::Instant::now();
+
+                let data = loop {
+                    let data = self.get_swo_data()?;
+
+                    if !data.is_empty() || start.elapsed() >= timeout {
+                        break data;
+                    }
+
+                    std::thread::sleep(
+                        Duration::from_millis(1).min(timeout.saturating_sub(start.elapsed())),
+                    );
+                };
+
                 log::trace!("SWO polled data: {:?}", data);
                 Ok(data)
             }
@@ -828,6 +1072,15 @@ impl SwoAccess for CmsisDap {
     fn swo_buffer_size(&mut self) -> Option<usize> {
         self.swo_buffer_size
     }
+
+    fn swo_overflows(&mut self) -> Option<u64> {
+        // Only the polled transport reports the status of the trace.
+        if self.swo_streaming {
+            None
+        } else {
+            Some(self.swo_overflows)
+        }
+    }
 }
 
 impl Drop for CmsisDap {
@@ -1,10 +1,11 @@
+use super::transport::{BulkTransport, HidTransport};
 use super::CmsisDapDevice;
 use crate::{
-    probe::{DebugProbeInfo, DebugProbeType, ProbeCreationError},
+    probe::{DebugProbeInfo, DebugProbeType, ProbeCreationError, UsbAccessHint, UsbOperation},
     DebugProbeSelector,
 };
 use hidapi::HidApi;
-use rusb::{constants::LIBUSB_CLASS_HID, Device, DeviceDescriptor, UsbContext};
+use rusb::{constants::LIBUSB_CLASS_HID, Device, DeviceDescriptor, DeviceHandle, UsbContext};
 use std::time::Duration;
 
 /// Finds all CMSIS-DAP devices, either v1 (HID) or v2 (WinUSB Bulk).
@@ -147,12 +148,19 @@ fn get_cmsisdap_hid_info(device: &hidapi::DeviceInfo) -> Option<DebugProbeInfo>
 
 /// Attempt to open the given device in CMSIS-DAP v2 mode
 pub fn open_v2_device(device: Device<rusb::Context>) -> Option<CmsisDapDevice> {
-    // Open device handle and read basic information
+    let handle = device.open().ok()?;
+
+    open_v2_handle(handle)
+}
+
+/// Attempt to use the opened device `handle` in CMSIS-DAP v2 mode
+pub fn open_v2_handle(mut handle: DeviceHandle<rusb::Context>) -> Option<CmsisDapDevice> {
+    // Read basic information
     let timeout = Duration::from_millis(100);
+    let device = handle.device();
     let d_desc = device.device_descriptor().ok()?;
     let vid = d_desc.vendor_id();
     let pid = d_desc.product_id();
-    let mut handle = device.open().ok()?;
     let language = handle.read_languages(timeout).ok()?.get(0).cloned()?;
 
     // Go through interfaces to try and find a v2 interface.
@@ -207,13 +215,13 @@ pub fn open_v2_device(device: Device<rusb::Context>) -> Option<CmsisDapDevice> {
             match handle.claim_interface(interface.number()) {
                 Ok(()) => {
                     log::debug!("Opening {:04x}:{:04x} in CMSIS-DAPv2 mode", vid, pid);
-                    return Some(CmsisDapDevice::V2 {
+                    return Some(CmsisDapDevice::new(Box::new(BulkTransport {
                         handle,
                         out_ep: eps[0].address(),
                         in_ep: eps[1].address(),
                         swo_ep,
                         max_packet_size: eps[1].max_packet_size() as usize,
-                    });
+                    })));
                 }
                 Err(_) => continue,
             }
@@ -265,6 +273,10 @@ pub fn open_device_from_selector(
     // try to open that.
     let mut hid_device_info: Option<DebugProbeInfo> = None;
 
+    // A device with the VID and PID of the selector which could not be opened with rusb,
+    // e.g. because of missing permissions. This is returned if hidapi can't open it either.
+    let mut access_error: Option<ProbeCreationError> = None;
+
     // Try using rusb to open a v2 device. This might fail if
     // the device does not support v2 operation or due to driver
     // or permission issues with opening bulk devices.
@@ -284,6 +296,21 @@ pub fn open_device_from_selector(
                 Ok(handle) => handle,
                 Err(err) => {
                     log::trace!("Error opening: {:?}", err);
+
+                    if d_desc.vendor_id() == selector.vendor_id
+                        && d_desc.product_id() == selector.product_id
+                        && UsbAccessHint::from_rusb(&err).is_some()
+                    {
+                        access_error.get_or_insert_with(|| {
+                            ProbeCreationError::from_usb(
+                                selector.vendor_id,
+                                selector.product_id,
+                                UsbOperation::Open,
+                                err,
+                            )
+                        });
+                    }
+
                     continue;
                 }
             };
@@ -358,18 +385,17 @@ pub fn open_device_from_selector(
 
             device_match
         })
-        .ok_or(ProbeCreationError::NotFound)?;
+        .ok_or_else(|| access_error.take().unwrap_or(ProbeCreationError::NotFound))?;
 
-    let device = device_info.open_device(&hid_api)?;
+    let device = match device_info.open_device(&hid_api) {
+        Ok(device) => device,
+        Err(error) => return Err(access_error.unwrap_or_else(|| error.into())),
+    };
 
     match device.get_product_string() {
-        Ok(Some(s)) if s.contains("CMSIS-DAP") => Ok(CmsisDapDevice::V1 {
-            handle: device,
-            // Start with a default 64-byte report size, which is the most
-            // common size for CMSIS-DAPv1 HID devices. We'll request the
-            // actual size to use from the probe later.
-            report_size: 64,
-        }),
+        Ok(Some(s)) if s.contains("CMSIS-DAP") => {
+            Ok(CmsisDapDevice::new(Box::new(HidTransport { device })))
+        }
         _ => {
             // Return NotFound if this VID:PID was not a valid CMSIS-DAP probe,
             // or if it couldn't be opened, so that other probe modules can
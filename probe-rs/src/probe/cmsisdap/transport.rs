@@ -0,0 +1,138 @@
+//! The transports of the CMSIS-DAP packets between the host and the probe.
+//!
+//! CMSIS-DAP v1 probes exchange the packets as HID reports, and CMSIS-DAP v2 probes over a
+//! pair of bulk endpoints. Apart from that, the protocol is the same, so the driver only
+//! uses the [`CmsisDapTransport`] trait. It can also be implemented outside of probe-rs, and
+//! be used with [`Probe::from_cmsisdap_transport`].
+//!
+//! The transfers are blocking, so transports over asynchronous APIs have to wait for their
+//! completion, like the WebUSB transport of the `webusb` feature.
+//!
+//! [`Probe::from_cmsisdap_transport`]: crate::Probe::from_cmsisdap_transport
+
+use super::commands::SendError;
+use std::time::Duration;
+
+/// The transport of the CMSIS-DAP packets between the host and the probe.
+///
+/// The packets which are passed to the transport start with the command ID, a HID report
+/// ID is added by the transport if it needs one.
+pub trait CmsisDapTransport: Send {
+    /// Whether every packet has to be padded to the packet size, like the HID reports of
+    /// CMSIS-DAP v1. Otherwise, the packets are sent with the length of their data.
+    fn pads_packets(&self) -> bool;
+
+    /// The size of the packets which are used until the probe reported its packet size,
+    /// usually the maximum packet size of the endpoint.
+    fn initial_packet_size(&self) -> usize;
+
+    /// Send `packet` to the probe, and return the number of bytes which were sent.
+    fn write(&mut self, packet: &[u8], timeout: Duration) -> Result<usize, SendError>;
+
+    /// Receive a packet from the probe into `buffer`, and return its length.
+    ///
+    /// Returns [`SendError::Timeout`] if no packet is received within `timeout`.
+    fn read(&mut self, buffer: &mut [u8], timeout: Duration) -> Result<usize, SendError>;
+
+    /// The maximum packet size of the SWO streaming endpoint, if the probe has one.
+    fn swo_packet_size(&self) -> Option<usize> {
+        None
+    }
+
+    /// Receive data from the SWO streaming endpoint into `buffer`, and return its length.
+    ///
+    /// Returns 0 if no data is received within `timeout`. This is only called if the
+    /// transport has a [`swo_packet_size`](Self::swo_packet_size).
+    fn read_swo(&mut self, _buffer: &mut [u8], _timeout: Duration) -> Result<usize, SendError> {
+        Ok(0)
+    }
+}
+
+/// CMSIS-DAP v1 over HID.
+#[cfg(feature = "cmsisdap")]
+pub(crate) struct HidTransport {
+    pub(crate) device: hidapi::HidDevice,
+}
+
+#[cfg(feature = "cmsisdap")]
+impl CmsisDapTransport for HidTransport {
+    fn pads_packets(&self) -> bool {
+        true
+    }
+
+    fn initial_packet_size(&self) -> usize {
+        // Start with a default 64-byte report size, which is the most
+        // common size for CMSIS-DAPv1 HID devices. We'll request the
+        // actual size to use from the probe later.
+        64
+    }
+
+    fn write(&mut self, packet: &[u8], _timeout: Duration) -> Result<usize, SendError> {
+        // The reports are sent with report ID 0.
+        let mut report = Vec::with_capacity(packet.len() + 1);
+        report.push(0);
+        report.extend_from_slice(packet);
+
+        Ok(self.device.write(&report)?.saturating_sub(1))
+    }
+
+    fn read(&mut self, buffer: &mut [u8], timeout: Duration) -> Result<usize, SendError> {
+        match self
+            .device
+            .read_timeout(buffer, timeout.as_millis() as i32)?
+        {
+            // Timeout is not indicated by error, but by returning 0 read bytes
+            0 => Err(SendError::Timeout),
+            n => Ok(n),
+        }
+    }
+}
+
+/// CMSIS-DAP v2 over WinUSB/Bulk.
+///
+/// Stores an rusb device handle, out/in EP addresses, maximum DAP packet size,
+/// and an optional SWO streaming EP address and SWO maximum packet size.
+#[cfg(feature = "cmsisdap")]
+pub(crate) struct BulkTransport {
+    pub(crate) handle: rusb::DeviceHandle<rusb::Context>,
+    pub(crate) out_ep: u8,
+    pub(crate) in_ep: u8,
+    pub(crate) max_packet_size: usize,
+    pub(crate) swo_ep: Option<(u8, usize)>,
+}
+
+#[cfg(feature = "cmsisdap")]
+impl CmsisDapTransport for BulkTransport {
+    fn pads_packets(&self) -> bool {
+        false
+    }
+
+    fn initial_packet_size(&self) -> usize {
+        self.max_packet_size
+    }
+
+    fn write(&mut self, packet: &[u8], timeout: Duration) -> Result<usize, SendError> {
+        Ok(self.handle.write_bulk(self.out_ep, packet, timeout)?)
+    }
+
+    fn read(&mut self, buffer: &mut [u8], timeout: Duration) -> Result<usize, SendError> {
+        Ok(self.handle.read_bulk(self.in_ep, buffer, timeout)?)
+    }
+
+    fn swo_packet_size(&self) -> Option<usize> {
+        self.swo_ep.map(|(_, size)| size)
+    }
+
+    fn read_swo(&mut self, buffer: &mut [u8], timeout: Duration) -> Result<usize, SendError> {
+        let ep = match self.swo_ep {
+            Some((ep, _)) => ep,
+            None => return Ok(0),
+        };
+
+        match self.handle.read_bulk(ep, buffer, timeout) {
+            Ok(n) => Ok(n),
+            Err(rusb::Error::Timeout) => Ok(0),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
@@ -0,0 +1,424 @@
+//! CMSIS-DAP v2 over WebUSB, to use probe-rs in a browser.
+//!
+//! The WebUSB API is asynchronous, while the driver blocks until a transfer is done. The
+//! transfers are run by a task on the thread which opened the device, and the
+//! [`WebUsbTransport`] sends them to this task and blocks until they are done. Because of
+//! that, the transport has to be used on another thread than the one which opened it, e.g.
+//! in a Web Worker which shares the memory of the WebAssembly module. This needs the
+//! `atomics` target feature, which is also needed to wait in the driver.
+//!
+//! CMSIS-DAP v1 probes can't be used, because browsers don't allow access to HID interfaces
+//! over WebUSB.
+
+use super::commands::SendError;
+use super::transport::CmsisDapTransport;
+use futures::channel::{mpsc, oneshot};
+use futures::future::{self, Either};
+use futures::StreamExt;
+use js_sys::{Array, DataView, Promise, Uint8Array};
+use std::collections::HashMap;
+use std::time::Duration;
+use wasm_bindgen::{prelude::*, JsCast};
+use wasm_bindgen_futures::JsFuture;
+
+#[wasm_bindgen]
+extern "C" {
+    /// A `USBDevice` of the WebUSB API.
+    type UsbDevice;
+
+    #[wasm_bindgen(method, getter)]
+    fn configuration(this: &UsbDevice) -> Option<UsbConfiguration>;
+
+    #[wasm_bindgen(method)]
+    fn open(this: &UsbDevice) -> Promise;
+
+    #[wasm_bindgen(method)]
+    fn close(this: &UsbDevice) -> Promise;
+
+    #[wasm_bindgen(method, js_name = selectConfiguration)]
+    fn select_configuration(this: &UsbDevice, configuration_value: u8) -> Promise;
+
+    #[wasm_bindgen(method, js_name = claimInterface)]
+    fn claim_interface(this: &UsbDevice, interface_number: u8) -> Promise;
+
+    #[wasm_bindgen(method, js_name = transferOut)]
+    fn transfer_out(this: &UsbDevice, endpoint_number: u8, data: &Uint8Array) -> Promise;
+
+    #[wasm_bindgen(method, js_name = transferIn)]
+    fn transfer_in(this: &UsbDevice, endpoint_number: u8, length: u32) -> Promise;
+
+    type UsbConfiguration;
+
+    #[wasm_bindgen(method, getter)]
+    fn interfaces(this: &UsbConfiguration) -> Array;
+
+    type UsbInterface;
+
+    #[wasm_bindgen(method, getter, js_name = interfaceNumber)]
+    fn interface_number(this: &UsbInterface) -> u8;
+
+    #[wasm_bindgen(method, getter)]
+    fn alternate(this: &UsbInterface) -> UsbAlternateInterface;
+
+    type UsbAlternateInterface;
+
+    #[wasm_bindgen(method, getter, js_name = interfaceName)]
+    fn interface_name(this: &UsbAlternateInterface) -> Option<String>;
+
+    #[wasm_bindgen(method, getter)]
+    fn endpoints(this: &UsbAlternateInterface) -> Array;
+
+    type UsbEndpoint;
+
+    #[wasm_bindgen(method, getter, js_name = endpointNumber)]
+    fn endpoint_number(this: &UsbEndpoint) -> u8;
+
+    #[wasm_bindgen(method, getter)]
+    fn direction(this: &UsbEndpoint) -> String;
+
+    #[wasm_bindgen(method, getter, js_name = type)]
+    fn type_(this: &UsbEndpoint) -> String;
+
+    #[wasm_bindgen(method, getter, js_name = packetSize)]
+    fn packet_size(this: &UsbEndpoint) -> u32;
+
+    type UsbInTransferResult;
+
+    #[wasm_bindgen(method, getter)]
+    fn data(this: &UsbInTransferResult) -> Option<DataView>;
+
+    #[wasm_bindgen(method, getter)]
+    fn status(this: &UsbInTransferResult) -> String;
+
+    type UsbOutTransferResult;
+
+    #[wasm_bindgen(method, getter, js_name = bytesWritten)]
+    fn bytes_written(this: &UsbOutTransferResult) -> u32;
+
+    #[wasm_bindgen(method, getter, js_name = status)]
+    fn out_status(this: &UsbOutTransferResult) -> String;
+
+    #[wasm_bindgen(js_name = setTimeout)]
+    fn set_timeout(handler: &js_sys::Function, timeout: i32) -> JsValue;
+}
+
+/// An error of the WebUSB transport.
+#[derive(Debug, thiserror::Error)]
+pub enum WebUsbError {
+    /// The device has no interface which can be used for CMSIS-DAP v2.
+    #[error("The device has no CMSIS-DAP v2 interface")]
+    NoInterface,
+    /// A call of the WebUSB API failed.
+    #[error("The WebUSB API failed: {0}")]
+    Js(String),
+    /// A transfer was not completed successfully.
+    #[error("The USB transfer failed with the status '{0}'")]
+    Status(String),
+    /// The task which runs the transfers has stopped.
+    #[error("The WebUSB transfers are not run anymore")]
+    Closed,
+}
+
+impl From<JsValue> for WebUsbError {
+    fn from(error: JsValue) -> Self {
+        // The WebUSB API rejects its promises with `DOMException`s, which are errors.
+        match error.dyn_ref::<js_sys::Error>() {
+            Some(error) => WebUsbError::Js(error.message().into()),
+            None => WebUsbError::Js(format!("{:?}", error)),
+        }
+    }
+}
+
+impl From<WebUsbError> for SendError {
+    fn from(error: WebUsbError) -> Self {
+        SendError::Transport(Box::new(error))
+    }
+}
+
+/// The endpoints of the CMSIS-DAP v2 interface.
+#[derive(Debug, Clone, Copy)]
+struct Endpoints {
+    out_ep: u8,
+    in_ep: u8,
+    max_packet_size: usize,
+    swo_ep: Option<(u8, usize)>,
+}
+
+/// A transfer, which is sent to the task which runs the transfers.
+///
+/// The result is `None` if the transfer timed out.
+enum Request {
+    Write {
+        endpoint: u8,
+        data: Vec<u8>,
+        timeout: Duration,
+        done: oneshot::Sender<Result<Option<usize>, WebUsbError>>,
+    },
+    Read {
+        endpoint: u8,
+        length: usize,
+        timeout: Duration,
+        done: oneshot::Sender<Result<Option<Vec<u8>>, WebUsbError>>,
+    },
+}
+
+/// CMSIS-DAP v2 over WebUSB.
+///
+/// The transport is opened with [`WebUsbTransport::open`], and the probe is created with
+/// [`Probe::from_cmsisdap_transport`]. See the [module documentation](self) for the threads
+/// which are needed.
+///
+/// [`Probe::from_cmsisdap_transport`]: crate::Probe::from_cmsisdap_transport
+pub struct WebUsbTransport {
+    requests: mpsc::UnboundedSender<Request>,
+    endpoints: Endpoints,
+}
+
+impl WebUsbTransport {
+    /// Open `device`, a `USBDevice` of the WebUSB API, and claim its CMSIS-DAP v2 interface.
+    ///
+    /// The transfers are run on the current thread, so the transport has to be used on
+    /// another one. The device is closed when the transport is dropped.
+    pub async fn open(device: JsValue) -> Result<Self, WebUsbError> {
+        let device: UsbDevice = device.unchecked_into();
+
+        JsFuture::from(device.open()).await?;
+
+        if device.configuration().is_none() {
+            JsFuture::from(device.select_configuration(1)).await?;
+        }
+
+        let (interface, endpoints) = device
+            .configuration()
+            .and_then(|configuration| find_interface(&configuration))
+            .ok_or(WebUsbError::NoInterface)?;
+
+        JsFuture::from(device.claim_interface(interface)).await?;
+
+        log::debug!("Opened the WebUSB device in CMSIS-DAPv2 mode");
+
+        let (requests, receiver) = mpsc::unbounded();
+        wasm_bindgen_futures::spawn_local(run_transfers(device, receiver));
+
+        Ok(Self {
+            requests,
+            endpoints,
+        })
+    }
+
+    /// Send a transfer to the task which runs it, and wait for its result.
+    fn transfer<T>(
+        &self,
+        request: impl FnOnce(oneshot::Sender<Result<Option<T>, WebUsbError>>) -> Request,
+    ) -> Result<Option<T>, SendError> {
+        let (done, result) = oneshot::channel();
+
+        self.requests
+            .unbounded_send(request(done))
+            .map_err(|_| WebUsbError::Closed)?;
+
+        match futures::executor::block_on(result) {
+            Ok(result) => Ok(result?),
+            Err(oneshot::Canceled) => Err(WebUsbError::Closed.into()),
+        }
+    }
+
+    /// Read a packet from `endpoint` into `buffer`, or return `None` if it timed out.
+    fn read_endpoint(
+        &self,
+        endpoint: u8,
+        buffer: &mut [u8],
+        timeout: Duration,
+    ) -> Result<Option<usize>, SendError> {
+        let data = self.transfer(|done| Request::Read {
+            endpoint,
+            length: buffer.len(),
+            timeout,
+            done,
+        })?;
+
+        Ok(data.map(|data| {
+            let length = data.len().min(buffer.len());
+            buffer[..length].copy_from_slice(&data[..length]);
+            length
+        }))
+    }
+}
+
+impl CmsisDapTransport for WebUsbTransport {
+    fn pads_packets(&self) -> bool {
+        false
+    }
+
+    fn initial_packet_size(&self) -> usize {
+        self.endpoints.max_packet_size
+    }
+
+    fn write(&mut self, packet: &[u8], timeout: Duration) -> Result<usize, SendError> {
+        self.transfer(|done| Request::Write {
+            endpoint: self.endpoints.out_ep,
+            data: packet.to_vec(),
+            timeout,
+            done,
+        })?
+        .ok_or(SendError::Timeout)
+    }
+
+    fn read(&mut self, buffer: &mut [u8], timeout: Duration) -> Result<usize, SendError> {
+        self.read_endpoint(self.endpoints.in_ep, buffer, timeout)?
+            .ok_or(SendError::Timeout)
+    }
+
+    fn swo_packet_size(&self) -> Option<usize> {
+        self.endpoints.swo_ep.map(|(_, size)| size)
+    }
+
+    fn read_swo(&mut self, buffer: &mut [u8], timeout: Duration) -> Result<usize, SendError> {
+        match self.endpoints.swo_ep {
+            Some((endpoint, _)) => Ok(self.read_endpoint(endpoint, buffer, timeout)?.unwrap_or(0)),
+            None => Ok(0),
+        }
+    }
+}
+
+/// Find the CMSIS-DAP v2 interface of `configuration`, and return its number and endpoints.
+///
+/// Like with libusb, this is an interface whose name contains "CMSIS-DAP", and which has two
+/// or three bulk endpoints of the correct direction.
+fn find_interface(configuration: &UsbConfiguration) -> Option<(u8, Endpoints)> {
+    configuration.interfaces().iter().find_map(|interface| {
+        let interface: UsbInterface = interface.unchecked_into();
+        let alternate = interface.alternate();
+
+        match alternate.interface_name() {
+            Some(name) if name.contains("CMSIS-DAP") => (),
+            _ => return None,
+        }
+
+        let eps: Vec<UsbEndpoint> = alternate
+            .endpoints()
+            .iter()
+            .map(JsCast::unchecked_into)
+            .collect();
+
+        if !(2..=3).contains(&eps.len()) {
+            return None;
+        }
+
+        let is_bulk =
+            |ep: &UsbEndpoint, direction: &str| ep.type_() == "bulk" && ep.direction() == direction;
+
+        if !is_bulk(&eps[0], "out") || !is_bulk(&eps[1], "in") {
+            return None;
+        }
+
+        // A third bulk endpoint is for SWO streaming.
+        let swo_ep = eps
+            .get(2)
+            .filter(|ep| is_bulk(ep, "in"))
+            .map(|ep| (ep.endpoint_number(), ep.packet_size() as usize));
+
+        Some((
+            interface.interface_number(),
+            Endpoints {
+                out_ep: eps[0].endpoint_number(),
+                in_ep: eps[1].endpoint_number(),
+                max_packet_size: eps[1].packet_size() as usize,
+                swo_ep,
+            },
+        ))
+    })
+}
+
+/// Run the transfers of `requests` on `device`, until the transport is dropped.
+async fn run_transfers(device: UsbDevice, mut requests: mpsc::UnboundedReceiver<Request>) {
+    // The WebUSB API can't cancel a transfer. When a read times out, it is kept, and the next
+    // read of the endpoint waits for it, so no packet is lost.
+    let mut pending_reads: HashMap<u8, JsFuture> = HashMap::new();
+
+    while let Some(request) = requests.next().await {
+        match request {
+            Request::Write {
+                endpoint,
+                data,
+                timeout,
+                done,
+            } => {
+                // The data is copied, because the WebUSB API can't use the shared memory.
+                let transfer =
+                    JsFuture::from(device.transfer_out(endpoint, &Uint8Array::from(&data[..])));
+
+                // A write which timed out is not waited for, it fails if the probe is gone.
+                let result = match future::select(transfer, sleep(timeout)).await {
+                    Either::Left((result, _)) => out_result(result),
+                    Either::Right(_) => Ok(None),
+                };
+
+                let _ = done.send(result);
+            }
+            Request::Read {
+                endpoint,
+                length,
+                timeout,
+                done,
+            } => {
+                let mut transfer = pending_reads
+                    .remove(&endpoint)
+                    .unwrap_or_else(|| JsFuture::from(device.transfer_in(endpoint, length as u32)));
+
+                let result = match future::select(&mut transfer, sleep(timeout)).await {
+                    Either::Left((result, _)) => in_result(result),
+                    Either::Right(_) => {
+                        pending_reads.insert(endpoint, transfer);
+                        Ok(None)
+                    }
+                };
+
+                let _ = done.send(result);
+            }
+        }
+    }
+
+    if let Err(error) = JsFuture::from(device.close()).await {
+        log::warn!(
+            "Failed to close the WebUSB device: {}",
+            WebUsbError::from(error)
+        );
+    }
+}
+
+/// The number of bytes written by a `transferOut` call.
+fn out_result(result: Result<JsValue, JsValue>) -> Result<Option<usize>, WebUsbError> {
+    let result: UsbOutTransferResult = result?.unchecked_into();
+
+    match result.out_status().as_str() {
+        "ok" => Ok(Some(result.bytes_written() as usize)),
+        status => Err(WebUsbError::Status(status.to_owned())),
+    }
+}
+
+/// The data received by a `transferIn` call.
+fn in_result(result: Result<JsValue, JsValue>) -> Result<Option<Vec<u8>>, WebUsbError> {
+    let result: UsbInTransferResult = result?.unchecked_into();
+
+    match result.status().as_str() {
+        "ok" => Ok(Some(result.data().map_or_else(Vec::new, |data| {
+            Uint8Array::new_with_byte_offset_and_length(
+                &data.buffer(),
+                data.byte_offset() as u32,
+                data.byte_length() as u32,
+            )
+            .to_vec()
+        }))),
+        status => Err(WebUsbError::Status(status.to_owned())),
+    }
+}
+
+/// A future which completes after `duration`.
+fn sleep(duration: Duration) -> JsFuture {
+    let timeout = duration.as_millis().min(i32::MAX as u128) as i32;
+
+    JsFuture::from(Promise::new(&mut |resolve, _reject| {
+        set_timeout(&resolve, timeout);
+    }))
+}
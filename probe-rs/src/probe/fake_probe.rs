@@ -1,17 +1,19 @@
-use std::{fmt::Debug, sync::Arc};
+use std::{collections::VecDeque, fmt::Debug, path::Path, sync::Arc};
 
 use crate::{
     architecture::arm::{
         ap::{memory_ap::mock::MockMemoryAp, AccessPort, MemoryAp},
         communication_interface::{
-            ArmDebugState, Initialized, SwdSequence, Uninitialized, UninitializedArmProbe,
+            ArmDebugState, DapProbe, Initialized, SwdSequence, Uninitialized,
+            UninitializedArmProbe, DEFAULT_AUTO_INCREMENT_WRAP,
         },
         memory::adi_v5_memory_interface::ADIMemoryInterface,
         sequences::ArmDebugSequence,
-        ApAddress, ArmProbeInterface, DapAccess, DpAddress, MemoryApInformation, PortType,
-        RawDapAccess, SwoAccess,
+        ApAddress, ApInformation, ArmProbeInterface, DapAccess, DpAddress, MemoryApInformation,
+        PortType, RawDapAccess, SwoAccess,
     },
-    DebugProbe, DebugProbeError, DebugProbeSelector, Error, Memory, Probe, WireProtocol,
+    CommunicationInterface, DebugProbe, DebugProbeError, DebugProbeSelector, Error, Memory, Probe,
+    TraceEvent, TraceRecord, WireProtocol,
 };
 
 #[allow(clippy::type_complexity)]
@@ -24,6 +26,9 @@ pub struct FakeProbe {
 
     dap_register_write_handler:
         Option<Box<dyn Fn(PortType, u8, u32) -> Result<(), DebugProbeError> + Send>>,
+
+    /// The recorded DAP transfers which are replayed.
+    replay: Option<VecDeque<TraceEvent>>,
 }
 
 impl Debug for FakeProbe {
@@ -43,6 +48,8 @@ impl FakeProbe {
 
             dap_register_read_handler: None,
             dap_register_write_handler: None,
+
+            replay: None,
         }
     }
 
@@ -60,9 +67,216 @@ impl FakeProbe {
         self.dap_register_write_handler = Some(handler);
     }
 
+    /// Replay the DAP transfers of a protocol trace, recorded with a
+    /// [`ProtocolTracer`](crate::ProtocolTracer).
+    ///
+    /// The DP and AP registers, and the memory of the target, are accessed through the
+    /// recorded transfers. Reads return the recorded values, and transfers which differ
+    /// from the next recorded one fail. The packets of the trace are ignored.
+    pub fn replay(&mut self, events: impl IntoIterator<Item = TraceEvent>) {
+        let events = events
+            .into_iter()
+            .filter(|event| !matches!(event, TraceEvent::Command(_) | TraceEvent::Response(_)))
+            .collect();
+
+        self.replay = Some(events);
+    }
+
+    /// Replay a protocol trace file, written by
+    /// [`ProtocolTracer::to_file`](crate::ProtocolTracer::to_file). See [`FakeProbe::replay`].
+    pub fn replay_file(&mut self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let records = std::fs::read_to_string(path)?
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.parse::<TraceRecord>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))?;
+
+        self.replay(records.into_iter().map(|record| record.event));
+
+        Ok(())
+    }
+
     pub fn into_probe(self) -> Probe {
         Probe::from_specific_probe(Box::new(self))
     }
+
+    fn next_replayed(&mut self, access: &str) -> Result<TraceEvent, DebugProbeError> {
+        self.replay
+            .as_mut()
+            .ok_or(DebugProbeError::CommandNotSupportedByProbe("DAP access"))?
+            .pop_front()
+            .ok_or_else(|| ReplayError::TraceEnded(access.to_owned()).into())
+    }
+
+    fn replay_read(&mut self, ap: Option<u8>, address: u8) -> Result<u32, DebugProbeError> {
+        let access = describe_access(ap, "read", address);
+
+        let value = match self.next_replayed(&access)? {
+            TraceEvent::DpRead {
+                address: recorded,
+                value,
+            } if ap.is_none() && recorded == address => value,
+            TraceEvent::ApRead {
+                ap: recorded_ap,
+                address: recorded,
+                value,
+            } if ap == Some(recorded_ap) && recorded == address => value,
+            other => {
+                return Err(ReplayError::Mismatch {
+                    recorded: other,
+                    access,
+                }
+                .into())
+            }
+        };
+
+        value.ok_or_else(|| ReplayError::TransferFailed(access).into())
+    }
+
+    fn replay_write(
+        &mut self,
+        ap: Option<u8>,
+        address: u8,
+        value: u32,
+    ) -> Result<(), DebugProbeError> {
+        let access = format!("{}: {:#010x}", describe_access(ap, "write", address), value);
+
+        let expected = match ap {
+            Some(ap) => TraceEvent::ApWrite {
+                ap,
+                address,
+                value,
+                ok: true,
+            },
+            None => TraceEvent::DpWrite {
+                address,
+                value,
+                ok: true,
+            },
+        };
+
+        match self.next_replayed(&access)? {
+            TraceEvent::DpWrite { ok, .. } | TraceEvent::ApWrite { ok, .. } if !ok => {
+                Err(ReplayError::TransferFailed(access).into())
+            }
+            recorded if recorded == expected => Ok(()),
+            other => Err(ReplayError::Mismatch {
+                recorded: other,
+                access,
+            }
+            .into()),
+        }
+    }
+
+    fn replay_read_block(
+        &mut self,
+        ap: u8,
+        address: u8,
+        values: &mut [u32],
+    ) -> Result<(), DebugProbeError> {
+        let access = format!(
+            "{} x{}",
+            describe_access(Some(ap), "read", address),
+            values.len()
+        );
+
+        match self.next_replayed(&access)? {
+            TraceEvent::ApReadBlock {
+                ap: recorded_ap,
+                address: recorded,
+                values: recorded_values,
+                ok,
+            } if recorded_ap == ap
+                && recorded == address
+                && recorded_values.len() == values.len() =>
+            {
+                values.copy_from_slice(&recorded_values);
+
+                if ok {
+                    Ok(())
+                } else {
+                    Err(ReplayError::TransferFailed(access).into())
+                }
+            }
+            other => Err(ReplayError::Mismatch {
+                recorded: other,
+                access,
+            }
+            .into()),
+        }
+    }
+
+    fn replay_write_block(
+        &mut self,
+        ap: u8,
+        address: u8,
+        values: &[u32],
+    ) -> Result<(), DebugProbeError> {
+        let access = format!(
+            "{} x{}",
+            describe_access(Some(ap), "write", address),
+            values.len()
+        );
+
+        match self.next_replayed(&access)? {
+            TraceEvent::ApWriteBlock {
+                ap: recorded_ap,
+                address: recorded,
+                values: recorded_values,
+                ok,
+            } if recorded_ap == ap && recorded == address && recorded_values == values => {
+                if ok {
+                    Ok(())
+                } else {
+                    Err(ReplayError::TransferFailed(access).into())
+                }
+            }
+            other => Err(ReplayError::Mismatch {
+                recorded: other,
+                access,
+            }
+            .into()),
+        }
+    }
+}
+
+fn describe_access(ap: Option<u8>, kind: &str, address: u8) -> String {
+    match ap {
+        Some(ap) => format!("AP{} {} {:#04x}", ap, kind, address),
+        None => format!("DP {} {:#04x}", kind, address),
+    }
+}
+
+/// An error while replaying a protocol trace with [`FakeProbe::replay`].
+#[derive(thiserror::Error, Debug)]
+pub enum ReplayError {
+    #[error("The replayed trace ended before `{0}`")]
+    TraceEnded(String),
+    #[error("The replayed trace expected `{recorded}` instead of `{access}`")]
+    Mismatch {
+        recorded: TraceEvent,
+        access: String,
+    },
+    #[error("The replayed `{0}` failed")]
+    TransferFailed(String),
+}
+
+impl From<ReplayError> for DebugProbeError {
+    fn from(error: ReplayError) -> Self {
+        DebugProbeError::ProbeSpecific(Box::new(error))
+    }
+}
+
+/// The information of the memory APs of the fake target.
+fn memory_ap_information(address: ApAddress) -> MemoryApInformation {
+    MemoryApInformation {
+        address,
+        only_32bit_data_size: false,
+        debug_base_address: 0xf000_0000,
+        supports_hnonsec: false,
+        auto_increment_wrap: DEFAULT_AUTO_INCREMENT_WRAP,
+    }
 }
 
 impl Default for FakeProbe {
@@ -172,30 +386,22 @@ impl RawDapAccess for FakeProbe {
         }
     }
 
-    fn swj_sequence(&mut self, _bit_len: u8, _bits: u64) -> Result<(), DebugProbeError> {
-        todo!()
-    }
-
-    fn swj_pins(
-        &mut self,
-        _pin_out: u32,
-        _pin_select: u32,
-        _pin_wait: u32,
-    ) -> Result<u32, DebugProbeError> {
-        todo!()
-    }
-
     fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
         self
     }
 }
 
+impl DapProbe for FakeProbe {}
+
 #[derive(Debug)]
 struct FakeArmInterface<S: ArmDebugState> {
     probe: Box<FakeProbe>,
 
     memory_ap: MockMemoryAp,
 
+    /// The information about the last queried AP, which is always a memory AP.
+    ap_information: Option<ApInformation>,
+
     state: S,
 }
 
@@ -210,6 +416,7 @@ impl<'interface> FakeArmInterface<Uninitialized> {
             probe,
             state,
             memory_ap,
+            ap_information: None,
         }
     }
 
@@ -233,6 +440,7 @@ impl FakeArmInterface<Initialized> {
             probe: interface.probe,
             state: Initialized::new(sequence, false),
             memory_ap,
+            ap_information: None,
         }
     }
 }
@@ -273,12 +481,13 @@ impl UninitializedArmProbe for FakeArmInterface<Uninitialized> {
 
 impl ArmProbeInterface for FakeArmInterface<Initialized> {
     fn memory_interface(&mut self, access_port: MemoryAp) -> Result<Memory<'_>, Error> {
-        let ap_information = MemoryApInformation {
-            address: access_port.ap_address(),
-            only_32bit_data_size: false,
-            debug_base_address: 0xf000_0000,
-            supports_hnonsec: false,
-        };
+        let ap_information = memory_ap_information(access_port.ap_address());
+
+        if self.probe.replay.is_some() {
+            let memory = ADIMemoryInterface::new(self, &ap_information)?;
+
+            return Ok(Memory::new(memory, access_port));
+        }
 
         let memory = ADIMemoryInterface::new(&mut self.memory_ap, &ap_information)?;
 
@@ -287,9 +496,13 @@ impl ArmProbeInterface for FakeArmInterface<Initialized> {
 
     fn ap_information(
         &mut self,
-        _access_port: crate::architecture::arm::ap::GenericAp,
-    ) -> Result<&crate::architecture::arm::ApInformation, Error> {
-        todo!()
+        access_port: crate::architecture::arm::ap::GenericAp,
+    ) -> Result<&ApInformation, Error> {
+        let information = memory_ap_information(access_port.ap_address());
+
+        Ok(self
+            .ap_information
+            .insert(ApInformation::MemoryAp(information)))
     }
 
     fn num_access_ports(&mut self, _dp: DpAddress) -> Result<usize, Error> {
@@ -322,63 +535,77 @@ impl SwoAccess for FakeArmInterface<Initialized> {
     }
 }
 
+impl CommunicationInterface for FakeArmInterface<Initialized> {
+    fn flush(&mut self) -> Result<(), DebugProbeError> {
+        Ok(())
+    }
+
+    fn get_arm_communication_interface(
+        &mut self,
+    ) -> Result<&mut crate::architecture::arm::ArmCommunicationInterface<Initialized>, Error> {
+        Err(Error::Probe(DebugProbeError::NotImplemented(
+            "The fake probe has no ARM communication interface",
+        )))
+    }
+}
+
 impl DapAccess for FakeArmInterface<Initialized> {
     fn read_raw_dp_register(
         &mut self,
         _dp: DpAddress,
-        _address: u8,
+        address: u8,
     ) -> Result<u32, DebugProbeError> {
-        todo!()
+        self.probe.replay_read(None, address)
     }
 
     fn write_raw_dp_register(
         &mut self,
         _dp: DpAddress,
-        _address: u8,
-        _value: u32,
+        address: u8,
+        value: u32,
     ) -> Result<(), DebugProbeError> {
-        todo!()
+        self.probe.replay_write(None, address, value)
     }
 
-    fn read_raw_ap_register(
-        &mut self,
-        _ap: ApAddress,
-        _address: u8,
-    ) -> Result<u32, DebugProbeError> {
-        todo!()
+    fn read_raw_ap_register(&mut self, ap: ApAddress, address: u8) -> Result<u32, DebugProbeError> {
+        self.probe.replay_read(Some(ap.ap), address)
     }
 
     fn read_raw_ap_register_repeated(
         &mut self,
-        _ap: ApAddress,
-        _address: u8,
-        _values: &mut [u32],
+        ap: ApAddress,
+        address: u8,
+        values: &mut [u32],
     ) -> Result<(), DebugProbeError> {
-        todo!()
+        self.probe.replay_read_block(ap.ap, address, values)
     }
 
     fn write_raw_ap_register(
         &mut self,
-        _ap: ApAddress,
-        _address: u8,
-        _value: u32,
+        ap: ApAddress,
+        address: u8,
+        value: u32,
     ) -> Result<(), DebugProbeError> {
-        todo!()
+        self.probe.replay_write(Some(ap.ap), address, value)
     }
 
     fn write_raw_ap_register_repeated(
         &mut self,
-        _ap: ApAddress,
-        _address: u8,
-        _values: &[u32],
+        ap: ApAddress,
+        address: u8,
+        values: &[u32],
     ) -> Result<(), DebugProbeError> {
-        todo!()
+        self.probe.replay_write_block(ap.ap, address, values)
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::FakeProbe;
+    use super::{FakeArmInterface, FakeProbe};
+    use crate::architecture::arm::{
+        sequences::DefaultArmSequence, ApAddress, DapAccess, DpAddress,
+    };
+    use crate::TraceEvent;
 
     #[test]
     fn create_session_with_fake_probe() {
@@ -388,4 +615,50 @@ mod test {
 
         probe.attach("nrf51822_xxAC").unwrap();
     }
+
+    #[test]
+    fn replay_trace() {
+        let mut fake_probe = FakeProbe::new();
+        fake_probe.replay(vec![
+            TraceEvent::DpRead {
+                address: 0,
+                value: Some(0x2ba0_1477),
+            },
+            TraceEvent::Command(vec![0xf2, 0x45]),
+            TraceEvent::ApWrite {
+                ap: 0,
+                address: 4,
+                value: 0x2000_0000,
+                ok: true,
+            },
+            TraceEvent::ApRead {
+                ap: 0,
+                address: 0xc,
+                value: None,
+            },
+        ]);
+
+        let mut interface = FakeArmInterface::new(Box::new(fake_probe))
+            .into_initialized(DefaultArmSequence::create())
+            .unwrap_or_else(|_| panic!("Failed to initialize the interface"));
+        let ap = ApAddress {
+            dp: DpAddress::Default,
+            ap: 0,
+        };
+
+        assert_eq!(
+            interface
+                .read_raw_dp_register(DpAddress::Default, 0)
+                .unwrap(),
+            0x2ba0_1477
+        );
+        // A different value than the recorded one.
+        assert!(interface.write_raw_ap_register(ap, 4, 0x2000_0004).is_err());
+        // The recorded read failed.
+        assert!(interface.read_raw_ap_register(ap, 0xc).is_err());
+        // The trace ended.
+        assert!(interface
+            .read_raw_dp_register(DpAddress::Default, 0)
+            .is_err());
+    }
 }
@@ -1,8 +1,9 @@
 use crate::architecture::{
     arm::communication_interface::UninitializedArmProbe,
     riscv::communication_interface::RiscvCommunicationInterface,
+    xtensa::communication_interface::XtensaCommunicationInterface,
 };
-use crate::probe::{JTAGAccess, ProbeCreationError};
+use crate::probe::{JTAGAccess, ProbeCreationError, ScanChainElement};
 use crate::{
     DebugProbe, DebugProbeError, DebugProbeInfo, DebugProbeSelector, DebugProbeType, WireProtocol,
 };
@@ -424,6 +425,7 @@ pub struct FtdiProbe {
     adapter: JtagAdapter,
     speed_khz: u32,
     idle_cycles: u8,
+    scan_chain: Vec<ScanChainElement>,
 }
 
 impl DebugProbe for FtdiProbe {
@@ -449,6 +451,7 @@ impl DebugProbe for FtdiProbe {
             adapter,
             speed_khz: 0,
             idle_cycles: 0,
+            scan_chain: Vec::new(),
         };
         log::debug!("opened probe: {:?}", probe);
         Ok(Box::new(probe))
@@ -483,6 +486,14 @@ impl DebugProbe for FtdiProbe {
             log::warn!("no JTAG taps detected");
             return Err(DebugProbeError::TargetNotFound);
         }
+
+        self.scan_chain = taps
+            .iter()
+            .map(|tap| ScanChainElement {
+                idcode: tap.idcode,
+                ir_len: tap.irlen,
+            })
+            .collect();
         if taps.len() == 1 {
             self.adapter
                 .select_target(taps[0].idcode)
@@ -506,6 +517,10 @@ impl DebugProbe for FtdiProbe {
         Ok(())
     }
 
+    fn scan_chain(&self) -> &[ScanChainElement] {
+        &self.scan_chain
+    }
+
     fn detach(&mut self) -> Result<(), DebugProbeError> {
         Ok(())
     }
@@ -546,6 +561,19 @@ impl DebugProbe for FtdiProbe {
         true
     }
 
+    fn try_get_xtensa_interface(
+        self: Box<Self>,
+    ) -> Result<XtensaCommunicationInterface, (Box<dyn DebugProbe>, DebugProbeError)> {
+        match XtensaCommunicationInterface::new(self) {
+            Ok(interface) => Ok(interface),
+            Err((probe, err)) => Err((probe.into_probe(), err)),
+        }
+    }
+
+    fn has_xtensa_interface(&self) -> bool {
+        true
+    }
+
     fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
         self
     }
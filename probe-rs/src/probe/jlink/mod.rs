@@ -11,22 +11,53 @@ use crate::{
     architecture::{
         arm::{
             communication_interface::DapProbe, communication_interface::UninitializedArmProbe,
-            swo::SwoConfig, ArmCommunicationInterface, SwoAccess,
+            swo::SwoConfig, ArmCommunicationInterface, Pins, SwoAccess,
         },
         riscv::communication_interface::RiscvCommunicationInterface,
+        xtensa::communication_interface::XtensaCommunicationInterface,
     },
     probe::{
-        DebugProbe, DebugProbeError, DebugProbeInfo, DebugProbeType, JTAGAccess, WireProtocol,
+        DebugProbe, DebugProbeError, DebugProbeInfo, DebugProbeType, JTAGAccess, SupportedSpeeds,
+        WireProtocol,
     },
-    DebugProbeSelector, Error as ProbeRsError,
+    DebugProbeSelector, Error as ProbeRsError, ProtocolTracer,
 };
 
-use self::swd::{SwdSettings, SwdStatistics};
+use self::swd::{RawSwdIo, SwdSettings, SwdStatistics};
 
 mod swd;
 
 const SWO_BUFFER_SIZE: u16 = 128;
 
+/// Maximum deviation of the SWO baud rate from the requested one, in percent.
+///
+/// UART receivers tolerate a deviation of a few percent.
+const SWO_MAX_BAUD_DEVIATION: u32 = 3;
+
+/// Find the SWO baud rate closest to `baud` which the J-Link supports.
+///
+/// The J-Link derives the SWO baud rate from a base frequency with a divider, but only
+/// reports the maximum rate, `max_speed`. Dividing it by an integer gives a rate which is
+/// also reachable with a divider of the base frequency, so those rates are used.
+///
+/// Returns `None` if the closest baud rate deviates more than [`SWO_MAX_BAUD_DEVIATION`].
+fn negotiate_swo_baud(max_speed: u32, baud: u32) -> Option<u32> {
+    if baud == 0 || max_speed == 0 {
+        return None;
+    }
+
+    let div = ((max_speed + baud / 2) / baud).max(1);
+    let actual = max_speed / div;
+
+    let deviation = (actual as i64 - baud as i64).unsigned_abs() * 100;
+
+    if deviation > baud as u64 * SWO_MAX_BAUD_DEVIATION as u64 {
+        None
+    } else {
+        Some(actual)
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct JLink {
     handle: JayLink,
@@ -48,6 +79,8 @@ pub(crate) struct JLink {
 
     swd_statistics: SwdStatistics,
     swd_settings: SwdSettings,
+
+    tracer: Option<ProtocolTracer>,
 }
 
 impl JLink {
@@ -394,6 +427,7 @@ impl DebugProbe for JLink {
             speed_khz: 0,
             swd_settings: SwdSettings::default(),
             swd_statistics: SwdStatistics::default(),
+            tracer: None,
         }))
     }
 
@@ -445,6 +479,24 @@ impl DebugProbe for JLink {
         Ok(actual_speed_khz)
     }
 
+    fn supported_speeds(&mut self) -> Result<SupportedSpeeds, DebugProbeError> {
+        Ok(match self.handle.read_speeds() {
+            Ok(speeds) => SupportedSpeeds::Range {
+                min_khz: 1,
+                max_khz: (speeds.max_speed_hz() / 1000).min(0xfffe),
+            },
+            Err(_) => SupportedSpeeds::Unknown,
+        })
+    }
+
+    fn set_protocol_tracer(&mut self, tracer: Option<ProtocolTracer>) {
+        self.tracer = tracer;
+    }
+
+    fn protocol_tracer(&self) -> Option<&ProtocolTracer> {
+        self.tracer.as_ref()
+    }
+
     fn attach(&mut self) -> Result<(), super::DebugProbeError> {
         log::debug!("Attaching to J-Link");
 
@@ -556,6 +608,58 @@ impl DebugProbe for JLink {
         Ok(())
     }
 
+    fn swj_sequence(&mut self, bit_len: u8, bits: u64) -> Result<(), DebugProbeError> {
+        let sequence: Vec<bool> = (0..bit_len).map(|bit| (bits >> bit) & 1 == 1).collect();
+
+        if self.protocol == Some(WireProtocol::Jtag) {
+            // The sequence is sent on TMS, with TDI low.
+            let tdi = iter::repeat(false).take(sequence.len());
+            self.handle.jtag_io(sequence, tdi)?;
+        } else {
+            // All bits are output.
+            let direction = iter::repeat(true).take(sequence.len());
+            self.swd_io(direction, sequence)?;
+        }
+
+        Ok(())
+    }
+
+    fn swj_pins(
+        &mut self,
+        pin_out: u32,
+        pin_select: u32,
+        pin_wait: u32,
+    ) -> Result<u32, DebugProbeError> {
+        let (select, out) = (Pins(pin_select as u8), Pins(pin_out as u8));
+
+        // The J-Link can't drive the clock or read TDO directly.
+        if select.swclk_tck() || select.tdo() {
+            return Err(DebugProbeError::CommandNotSupportedByProbe("swj_pins"));
+        }
+
+        if select.swdio_tms() {
+            self.handle.set_tms(out.swdio_tms())?;
+        }
+
+        if select.tdi() {
+            self.handle.set_tdi(out.tdi())?;
+        }
+
+        if select.ntrst() {
+            self.handle.set_trst(out.ntrst())?;
+        }
+
+        if select.nreset() {
+            self.handle.set_reset(out.nreset())?;
+        }
+
+        // The J-Link can't wait for the pins to settle, so we wait on the host instead.
+        std::thread::sleep(Duration::from_micros(pin_wait as u64));
+
+        // We signal that we cannot read the pin state.
+        Ok(0xFFFF_FFFF)
+    }
+
     fn try_get_riscv_interface(
         self: Box<Self>,
     ) -> Result<RiscvCommunicationInterface, (Box<dyn DebugProbe>, DebugProbeError)> {
@@ -572,6 +676,22 @@ impl DebugProbe for JLink {
         }
     }
 
+    fn try_get_xtensa_interface(
+        self: Box<Self>,
+    ) -> Result<XtensaCommunicationInterface, (Box<dyn DebugProbe>, DebugProbeError)> {
+        if self.supported_protocols.contains(&WireProtocol::Jtag) {
+            match XtensaCommunicationInterface::new(self) {
+                Ok(interface) => Ok(interface),
+                Err((probe, err)) => Err((probe.into_probe(), err)),
+            }
+        } else {
+            Err((
+                RawDapAccess::into_probe(self),
+                DebugProbeError::InterfaceNotAvailable("JTAG"),
+            ))
+        }
+    }
+
     fn get_swo_interface(&self) -> Option<&dyn SwoAccess> {
         Some(self as _)
     }
@@ -588,6 +708,10 @@ impl DebugProbe for JLink {
         self.supported_protocols.contains(&WireProtocol::Jtag)
     }
 
+    fn has_xtensa_interface(&self) -> bool {
+        self.supported_protocols.contains(&WireProtocol::Jtag)
+    }
+
     fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
         self
     }
@@ -684,9 +808,41 @@ impl DapProbe for JLink {}
 
 impl SwoAccess for JLink {
     fn enable_swo(&mut self, config: &SwoConfig) -> Result<(), ProbeRsError> {
-        self.swo_config = Some(*config);
+        if matches!(config.mode(), crate::architecture::arm::SwoMode::Manchester) {
+            return Err(DebugProbeError::ProbeSpecific(
+                JlinkError::ManchesterSwoNotSupported.into(),
+            )
+            .into());
+        }
+
+        if !self.handle.capabilities().contains(Capability::Swo) {
+            return Err(DebugProbeError::ProbeSpecific(JlinkError::SwoNotSupported.into()).into());
+        }
+
+        let speeds = self
+            .handle
+            .read_swo_speeds(SwoMode::Uart)
+            .map_err(DebugProbeError::from)?;
+        let baud = negotiate_swo_baud(speeds.max_speed_hz(), config.baud()).ok_or_else(|| {
+            DebugProbeError::ProbeSpecific(
+                JlinkError::SwoBaudNotSupported {
+                    baud: config.baud(),
+                }
+                .into(),
+            )
+        })?;
+
+        if baud != config.baud() {
+            log::info!(
+                "Using SWO baud rate {} instead of {}, the closest rate the J-Link supports",
+                baud,
+                config.baud()
+            );
+        }
+
+        self.swo_config = Some(config.set_baud(baud));
         self.handle
-            .swo_start(SwoMode::Uart, config.baud(), SWO_BUFFER_SIZE.into())
+            .swo_start(SwoMode::Uart, baud, SWO_BUFFER_SIZE.into())
             .map_err(|e| ProbeRsError::Probe(DebugProbeError::ArchitectureSpecific(Box::new(e))))?;
         Ok(())
     }
@@ -704,12 +860,15 @@ impl SwoAccess for JLink {
     }
 
     fn read_swo_timeout(&mut self, timeout: std::time::Duration) -> Result<Vec<u8>, ProbeRsError> {
+        let config = match self.swo_config {
+            Some(config) => config,
+            None => return Ok(Vec::new()),
+        };
+
         let end = std::time::Instant::now() + timeout;
         let mut buf = vec![0; SWO_BUFFER_SIZE.into()];
 
-        let poll_interval = self
-            .swo_poll_interval_hint(&self.swo_config.unwrap())
-            .unwrap();
+        let poll_interval = self.swo_poll_interval_hint(&config).unwrap();
 
         let mut bytes = vec![];
         loop {
@@ -790,6 +949,12 @@ impl From<jaylink::Error> for DebugProbeError {
 pub enum JlinkError {
     #[error("Unknown interface reported by J-Link: {0:?}")]
     UnknownInterface(jaylink::Interface),
+    #[error("The J-Link does not support SWO.")]
+    SwoNotSupported,
+    #[error("Manchester-coded SWO mode not supported")]
+    ManchesterSwoNotSupported,
+    #[error("The J-Link can't receive SWO with a baud rate close to {baud}.")]
+    SwoBaudNotSupported { baud: u32 },
 }
 
 impl TryFrom<jaylink::Interface> for WireProtocol {
@@ -803,3 +968,19 @@ impl TryFrom<jaylink::Interface> for WireProtocol {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::negotiate_swo_baud;
+
+    #[test]
+    fn swo_baud() {
+        // The closest divider is used.
+        assert_eq!(negotiate_swo_baud(60_000_000, 2_000_000), Some(2_000_000));
+        assert_eq!(negotiate_swo_baud(60_000_000, 1_900_000), Some(1_875_000));
+
+        // Rates above the maximum are not supported.
+        assert_eq!(negotiate_swo_baud(15_000_000, 30_000_000), None);
+        assert_eq!(negotiate_swo_baud(60_000_000, 0), None);
+    }
+}
@@ -0,0 +1,178 @@
+//! Advisory locking of probes, so that two processes don't use the same probe.
+//!
+//! The lock is a file in the temporary directory for each probe, which is locked with the
+//! file locking of the OS while the probe is open, and contains the PID of the process
+//! which uses the probe. The OS releases the lock when the process exits, so a crashed
+//! process doesn't leave a stale lock behind.
+//!
+//! In WebAssembly, there is no file system, and the probes are not locked.
+
+use std::fs::File;
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use super::{DebugProbeError, DebugProbeSelector};
+
+/// The lock of an open probe, which is released when it is dropped.
+#[derive(Debug)]
+pub(crate) struct ProbeLock {
+    // The lock is held as long as the file is open.
+    _file: File,
+}
+
+impl ProbeLock {
+    /// Lock the probe of `selector`.
+    ///
+    /// Returns [`DebugProbeError::ProbeInUse`] if another process holds the lock, and `None`
+    /// if the lock file can't be used, e.g. because the temporary directory is not writable,
+    /// in which case the probe is used without a lock.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn acquire(selector: &DebugProbeSelector) -> Result<Option<Self>, DebugProbeError> {
+        let path = lock_path(selector);
+
+        match lock_file(&path) {
+            Ok(Some(mut file)) => {
+                let written = file
+                    .set_len(0)
+                    .and_then(|_| write!(file, "{}", std::process::id()));
+
+                if let Err(error) = written {
+                    log::warn!("Failed to write the lock file {:?}: {}", path, error);
+                }
+
+                log::debug!("Locked the probe {} with {:?}", selector, path);
+
+                Ok(Some(Self { _file: file }))
+            }
+            Ok(None) => {
+                // The PID is missing if the other process is just writing it.
+                let pid = fs::read_to_string(&path)
+                    .ok()
+                    .and_then(|pid| pid.trim().parse().ok());
+
+                Err(DebugProbeError::ProbeInUse { pid })
+            }
+            Err(error) => {
+                log::warn!(
+                    "Failed to lock the probe {} with {:?}, it is used without a lock: {}",
+                    selector,
+                    path,
+                    error
+                );
+
+                Ok(None)
+            }
+        }
+    }
+
+    /// The probes are not locked in WebAssembly.
+    #[cfg(target_arch = "wasm32")]
+    pub(crate) fn acquire(_selector: &DebugProbeSelector) -> Result<Option<Self>, DebugProbeError> {
+        Ok(None)
+    }
+}
+
+/// The path of the lock file of the probe of `selector`.
+#[cfg(not(target_arch = "wasm32"))]
+fn lock_path(selector: &DebugProbeSelector) -> PathBuf {
+    let serial: String = selector
+        .serial_number
+        .as_deref()
+        .unwrap_or_default()
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect();
+
+    std::env::temp_dir().join(format!(
+        "probe-rs-{:04x}-{:04x}-{}.lock",
+        selector.vendor_id, selector.product_id, serial
+    ))
+}
+
+/// Open and lock the file at `path`, or return `None` if another process holds the lock.
+#[cfg(unix)]
+fn lock_file(path: &std::path::Path) -> io::Result<Option<File>> {
+    use std::os::unix::io::AsRawFd;
+
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)?;
+
+    // SAFETY: The file descriptor is valid as long as `file` is open.
+    let result = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+
+    if result == 0 {
+        return Ok(Some(file));
+    }
+
+    match io::Error::last_os_error() {
+        error if error.kind() == io::ErrorKind::WouldBlock => Ok(None),
+        error => Err(error),
+    }
+}
+
+/// Open and lock the file at `path`, or return `None` if another process holds the lock.
+#[cfg(windows)]
+fn lock_file(path: &std::path::Path) -> io::Result<Option<File>> {
+    use std::os::windows::fs::OpenOptionsExt;
+
+    const FILE_SHARE_READ: u32 = 0x1;
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+
+    // Other processes can read the PID, but not open the file for writing.
+    let result = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .share_mode(FILE_SHARE_READ)
+        .open(path);
+
+    match result {
+        Ok(file) => Ok(Some(file)),
+        Err(error) if error.raw_os_error() == Some(ERROR_SHARING_VIOLATION) => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+/// Probes are not locked on other platforms.
+#[cfg(not(any(unix, windows, target_arch = "wasm32")))]
+fn lock_file(_path: &std::path::Path) -> io::Result<Option<File>> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "locking is not supported on this platform",
+    ))
+}
+
+#[cfg(all(test, any(unix, windows)))]
+mod test {
+    use super::ProbeLock;
+    use crate::{DebugProbeError, DebugProbeSelector};
+
+    #[test]
+    fn second_lock_fails() {
+        let selector = DebugProbeSelector {
+            vendor_id: 0xffff,
+            product_id: 0xfffe,
+            serial_number: Some(format!("test{}", std::process::id())),
+        };
+
+        let lock = ProbeLock::acquire(&selector).unwrap();
+        assert!(lock.is_some());
+
+        let pid = std::process::id();
+        assert!(matches!(
+            ProbeLock::acquire(&selector),
+            Err(DebugProbeError::ProbeInUse { pid: Some(p) }) if p == pid
+        ));
+
+        drop(lock);
+        assert!(ProbeLock::acquire(&selector).unwrap().is_some());
+    }
+}
@@ -0,0 +1,499 @@
+//! A virtual probe, connected to a Cortex-M core which is simulated on the host.
+//!
+//! The simulator implements the DAP of the target, with a single AHB-AP, so the whole
+//! ARM stack of probe-rs is exercised when it is used. This allows tests of flashing,
+//! breakpoints and the GDB server without physical boards, e.g. in CI pipelines.
+//!
+//! The simulator is selected with the `simulator` probe selector:
+//!
+//! ```no_run
+//! use probe_rs::Probe;
+//!
+//! let probe = Probe::open("simulator")?;
+//! let session = probe.attach("armv7m")?;
+//! # Ok::<(), probe_rs::Error>(())
+//! ```
+
+mod target;
+mod thumb;
+
+pub use target::SimulatedTarget;
+
+use crate::{
+    architecture::arm::{
+        communication_interface::{DapProbe, UninitializedArmProbe},
+        ArmCommunicationInterface, DpAddress, PortType, RawDapAccess,
+    },
+    DebugProbe, DebugProbeError, DebugProbeSelector, Probe, ProbeCreationError, WireProtocol,
+};
+
+/// The number of instructions the core executes for each DAP transfer, while it is running.
+const INSTRUCTIONS_PER_TRANSFER: usize = 64;
+
+/// The DPIDR of a SW-DP, version 1.
+const DPIDR: u32 = 0x2BA0_1477;
+/// The IDR of the AHB-AP of a Cortex-M4.
+const AHB_AP_IDR: u32 = 0x2477_0011;
+
+const CTRL_STAT_CDBGPWRUPREQ: u32 = 1 << 28;
+const CTRL_STAT_CSYSPWRUPREQ: u32 = 1 << 30;
+
+/// A probe connected to a [`SimulatedTarget`].
+#[derive(Debug)]
+pub struct SimulatorProbe {
+    target: SimulatedTarget,
+
+    protocol: WireProtocol,
+    speed_khz: u32,
+
+    ctrl_stat: u32,
+    select: u32,
+
+    csw: u32,
+    tar: u32,
+}
+
+impl SimulatorProbe {
+    /// Create a probe connected to a new [`SimulatedTarget`].
+    pub fn new() -> Self {
+        Self::with_target(SimulatedTarget::new())
+    }
+
+    /// Create a probe connected to `target`, e.g. a target with a program already in its memory.
+    pub fn with_target(target: SimulatedTarget) -> Self {
+        Self {
+            target,
+            protocol: WireProtocol::Swd,
+            speed_khz: 1000,
+            ctrl_stat: 0,
+            select: 0,
+            csw: 0,
+            tar: 0,
+        }
+    }
+
+    /// The simulated target.
+    pub fn target(&self) -> &SimulatedTarget {
+        &self.target
+    }
+
+    /// The simulated target, e.g. to load a program into its memory.
+    pub fn target_mut(&mut self) -> &mut SimulatedTarget {
+        &mut self.target
+    }
+
+    pub fn into_probe(self) -> Probe {
+        Probe::from_specific_probe(Box::new(self))
+    }
+
+    fn read_dp(&mut self, addr: u8) -> u32 {
+        match addr & 0xC {
+            0x0 => DPIDR,
+            0x4 if self.select & 0xF == 0 => {
+                // The power up requests are acknowledged immediately.
+                self.ctrl_stat
+                    | ((self.ctrl_stat & CTRL_STAT_CDBGPWRUPREQ) << 1)
+                    | ((self.ctrl_stat & CTRL_STAT_CSYSPWRUPREQ) << 1)
+            }
+            _ => 0,
+        }
+    }
+
+    fn write_dp(&mut self, addr: u8, value: u32) {
+        match addr & 0xC {
+            0x4 if self.select & 0xF == 0 => self.ctrl_stat = value & 0x5000_0F00,
+            0x8 => self.select = value,
+            // ABORT and TARGETSEL have no effect, there are no errors and only a single DP.
+            _ => {}
+        }
+    }
+
+    /// The address of the AP register selected by `SELECT` and `addr`, if the AP exists.
+    fn ap_register(&self, addr: u8) -> Option<u8> {
+        if self.select >> 24 != 0 {
+            return None;
+        }
+
+        Some((((self.select >> 4) & 0xF) as u8) << 4 | (addr & 0xC))
+    }
+
+    fn transfer_size(&self) -> u32 {
+        match self.csw & 0b111 {
+            0b000 => 1,
+            0b001 => 2,
+            _ => 4,
+        }
+    }
+
+    fn increment_tar(&mut self) {
+        // Only the lower 10 bits of TAR are incremented, like most MEM-APs do.
+        if (self.csw >> 4) & 0b11 != 0 {
+            let incremented = self.tar.wrapping_add(self.transfer_size());
+            self.tar = (self.tar & !0x3FF) | (incremented & 0x3FF);
+        }
+    }
+
+    fn read_ap(&mut self, addr: u8) -> u32 {
+        let register = match self.ap_register(addr) {
+            Some(register) => register,
+            None => return 0,
+        };
+
+        match register {
+            // CSW, with the DeviceEn bit always set.
+            0x00 => self.csw | (1 << 6),
+            0x04 => self.tar,
+            0x0C => {
+                let lane = (self.tar & 0b11) * 8;
+
+                let value = match self.transfer_size() {
+                    1 => u32::from(self.target.read_8(self.tar)) << lane,
+                    2 => u32::from(self.target.read_16(self.tar)) << (lane & 0b10000),
+                    _ => self.target.read_32(self.tar),
+                };
+
+                self.increment_tar();
+
+                value
+            }
+            0x10..=0x1C => self
+                .target
+                .read_32((self.tar & !0xF) | u32::from(register & 0xC)),
+            // BASE, in the ADIv5 format, with a debug entry present.
+            0xF8 => target::ROM_TABLE | 0b11,
+            0xFC => AHB_AP_IDR,
+            _ => 0,
+        }
+    }
+
+    fn write_ap(&mut self, addr: u8, value: u32) {
+        let register = match self.ap_register(addr) {
+            Some(register) => register,
+            None => return,
+        };
+
+        match register {
+            0x00 => self.csw = value & !((1 << 6) | (1 << 7)),
+            0x04 => self.tar = value,
+            0x0C => {
+                let lane = (self.tar & 0b11) * 8;
+
+                match self.transfer_size() {
+                    1 => self.target.write_8(self.tar, (value >> lane) as u8),
+                    2 => self
+                        .target
+                        .write_16(self.tar, (value >> (lane & 0b10000)) as u16),
+                    _ => self.target.write_32(self.tar, value),
+                }
+
+                self.increment_tar();
+            }
+            0x10..=0x1C => self
+                .target
+                .write_32((self.tar & !0xF) | u32::from(register & 0xC), value),
+            _ => {}
+        }
+    }
+}
+
+impl Default for SimulatorProbe {
+    fn default() -> Self {
+        SimulatorProbe::new()
+    }
+}
+
+impl DebugProbe for SimulatorProbe {
+    fn new_from_selector(
+        selector: impl Into<DebugProbeSelector>,
+    ) -> Result<Box<Self>, DebugProbeError>
+    where
+        Self: Sized,
+    {
+        if selector.into().is_simulator() {
+            Ok(Box::new(SimulatorProbe::new()))
+        } else {
+            Err(DebugProbeError::ProbeCouldNotBeCreated(
+                ProbeCreationError::NotFound,
+            ))
+        }
+    }
+
+    fn get_name(&self) -> &str {
+        "Simulator"
+    }
+
+    fn speed(&self) -> u32 {
+        self.speed_khz
+    }
+
+    fn set_speed(&mut self, speed_khz: u32) -> Result<u32, DebugProbeError> {
+        self.speed_khz = speed_khz;
+
+        Ok(speed_khz)
+    }
+
+    fn attach(&mut self) -> Result<(), DebugProbeError> {
+        Ok(())
+    }
+
+    fn detach(&mut self) -> Result<(), DebugProbeError> {
+        Ok(())
+    }
+
+    fn target_reset(&mut self) -> Result<(), DebugProbeError> {
+        self.target.reset();
+
+        Ok(())
+    }
+
+    fn target_reset_assert(&mut self) -> Result<(), DebugProbeError> {
+        self.target.set_reset_pin(true);
+
+        Ok(())
+    }
+
+    fn target_reset_deassert(&mut self) -> Result<(), DebugProbeError> {
+        self.target.set_reset_pin(false);
+
+        Ok(())
+    }
+
+    fn select_protocol(&mut self, protocol: WireProtocol) -> Result<(), DebugProbeError> {
+        self.protocol = protocol;
+
+        Ok(())
+    }
+
+    fn swj_sequence(&mut self, _bit_len: u8, _bits: u64) -> Result<(), DebugProbeError> {
+        Ok(())
+    }
+
+    fn swj_pins(
+        &mut self,
+        pin_out: u32,
+        pin_select: u32,
+        _pin_wait: u32,
+    ) -> Result<u32, DebugProbeError> {
+        const N_RESET: u32 = 1 << 7;
+
+        if pin_select & N_RESET != 0 {
+            self.target.set_reset_pin(pin_out & N_RESET == 0);
+        }
+
+        Ok(pin_out)
+    }
+
+    fn has_arm_interface(&self) -> bool {
+        true
+    }
+
+    fn try_get_arm_interface<'probe>(
+        self: Box<Self>,
+    ) -> Result<Box<dyn UninitializedArmProbe + 'probe>, (Box<dyn DebugProbe>, DebugProbeError)>
+    {
+        Ok(Box::new(ArmCommunicationInterface::new(self, false)))
+    }
+
+    fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
+        self
+    }
+
+    fn try_as_dap_probe(&mut self) -> Option<&mut dyn DapProbe> {
+        Some(self)
+    }
+}
+
+impl RawDapAccess for SimulatorProbe {
+    fn select_dp(&mut self, dp: DpAddress) -> Result<(), DebugProbeError> {
+        match dp {
+            DpAddress::Default => Ok(()),
+            DpAddress::Multidrop(_) => Err(DebugProbeError::TargetNotFound),
+        }
+    }
+
+    fn raw_read_register(&mut self, port: PortType, addr: u8) -> Result<u32, DebugProbeError> {
+        self.target.run(INSTRUCTIONS_PER_TRANSFER);
+
+        Ok(match port {
+            PortType::DebugPort => self.read_dp(addr),
+            PortType::AccessPort => self.read_ap(addr),
+        })
+    }
+
+    fn raw_write_register(
+        &mut self,
+        port: PortType,
+        addr: u8,
+        value: u32,
+    ) -> Result<(), DebugProbeError> {
+        self.target.run(INSTRUCTIONS_PER_TRANSFER);
+
+        match port {
+            PortType::DebugPort => self.write_dp(addr, value),
+            PortType::AccessPort => self.write_ap(addr, value),
+        }
+
+        Ok(())
+    }
+
+    fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
+        self
+    }
+}
+
+impl DapProbe for SimulatorProbe {}
+
+#[cfg(test)]
+mod test {
+    use super::SimulatorProbe;
+    use crate::architecture::arm::Cfbp;
+    use crate::{CoreRegisterAddress, MemoryInterface, Probe};
+
+    #[test]
+    fn open_simulator() {
+        let probe = Probe::open("simulator").unwrap();
+
+        assert_eq!(probe.get_name(), "Simulator");
+    }
+
+    #[test]
+    fn run_to_breakpoint() {
+        let mut probe = SimulatorProbe::new();
+
+        // Vector table, with the stack at the end of the first 4 KiB.
+        probe
+            .target_mut()
+            .write_memory(0x0, &0x1000u32.to_le_bytes());
+        probe
+            .target_mut()
+            .write_memory(0x4, &0x101u32.to_le_bytes());
+        probe.target_mut().write_memory(
+            0x100,
+            &[
+                0x00, 0x20, // movs r0, #0
+                0x0a, 0x21, // movs r1, #10
+                0x40, 0x18, // adds r0, r0, r1
+                0x01, 0x39, // subs r1, #1
+                0xfc, 0xd1, // bne.n 0x104
+                0x00, 0xbe, // bkpt 0x0000
+            ],
+        );
+
+        let mut session = probe.into_probe().attach("armv7m").unwrap();
+        let mut core = session.core(0).unwrap();
+
+        core.reset_and_halt(std::time::Duration::from_millis(100))
+            .unwrap();
+        core.set_hw_breakpoint(0x10a).unwrap();
+        core.run().unwrap();
+        core.wait_for_core_halted(std::time::Duration::from_millis(100))
+            .unwrap();
+
+        assert_eq!(
+            core.read_core_reg(core.registers().program_counter())
+                .unwrap(),
+            0x10a
+        );
+        assert_eq!(core.read_core_reg(CoreRegisterAddress(0)).unwrap(), 55u32);
+
+        core.write_word_32(0x2000, 0xdead_beef).unwrap();
+        assert_eq!(core.read_word_32(0x2000).unwrap(), 0xdead_beef);
+    }
+
+    #[test]
+    fn read_and_write_core_regs() {
+        let probe = SimulatorProbe::new();
+
+        let mut session = probe.into_probe().attach("armv7m").unwrap();
+        let mut core = session.core(0).unwrap();
+
+        core.halt(std::time::Duration::from_millis(100)).unwrap();
+
+        let registers: Vec<(CoreRegisterAddress, u32)> = (0u16..13)
+            .map(|register| (register.into(), 0x1000 + u32::from(register)))
+            .collect();
+
+        core.write_core_regs(&registers).unwrap();
+
+        let addresses: Vec<_> = registers.iter().map(|(address, _)| *address).collect();
+        let values = core.read_core_regs(&addresses).unwrap();
+
+        assert_eq!(values, (0x1000..0x100d).collect::<Vec<u32>>());
+        assert_eq!(core.read_core_reg(7u16).unwrap(), 0x1007);
+    }
+
+    #[test]
+    fn special_registers() {
+        let probe = SimulatorProbe::new();
+
+        let mut session = probe.into_probe().attach("armv7m").unwrap();
+        let mut core = session.core(0).unwrap();
+
+        core.halt(std::time::Duration::from_millis(100)).unwrap();
+
+        let registers = core.registers();
+        let msp = registers.get_special_register("MSP").unwrap();
+        let psp = registers.get_special_register("PSP").unwrap();
+        let cfbp = registers.get_special_register("CFBP").unwrap();
+
+        core.write_core_reg(msp.into(), 0x2000_1000).unwrap();
+        core.write_core_reg(psp.into(), 0x2000_0800).unwrap();
+
+        let sp = registers.stack_pointer();
+        assert_eq!(core.read_core_reg(sp).unwrap(), 0x2000_1000);
+
+        let mut value = Cfbp(core.read_core_reg(cfbp).unwrap());
+        assert!(!value.process_stack_active());
+
+        value.set_control(0b10);
+        value.set_primask(1);
+        core.write_core_reg(cfbp.into(), value.into()).unwrap();
+
+        let value = Cfbp(core.read_core_reg(cfbp).unwrap());
+        assert!(value.process_stack_active());
+        assert_eq!(value.primask(), 1);
+
+        assert_eq!(core.read_core_reg(sp).unwrap(), 0x2000_0800);
+        assert_eq!(core.read_core_reg(msp).unwrap(), 0x2000_1000);
+    }
+
+    #[test]
+    fn detect_reset_between_polls() {
+        let mut probe = SimulatorProbe::new();
+
+        // Vector table, with the stack at the end of the first 4 KiB.
+        probe
+            .target_mut()
+            .write_memory(0x0, &0x1000u32.to_le_bytes());
+        probe
+            .target_mut()
+            .write_memory(0x4, &0x101u32.to_le_bytes());
+        // A program which requests a system reset through AIRCR.
+        probe.target_mut().write_memory(
+            0x100,
+            &[
+                0x01, 0x48, // ldr r0, [pc, #4]
+                0x02, 0x49, // ldr r1, [pc, #8]
+                0x01, 0x60, // str r1, [r0]
+                0xfe, 0xe7, // b.n 0x106
+                0x0c, 0xed, 0x00, 0xe0, // AIRCR
+                0x04, 0x00, 0xfa, 0x05, // VECTKEY | SYSRESETREQ
+            ],
+        );
+
+        let mut session = probe.into_probe().attach("armv7m").unwrap();
+        let mut core = session.core(0).unwrap();
+
+        core.halt(std::time::Duration::from_millis(100)).unwrap();
+        core.reset_detected().unwrap();
+        assert!(!core.reset_detected().unwrap());
+
+        core.run().unwrap();
+        // Reading the status clears the sticky reset bit of the core.
+        core.status().unwrap();
+        core.halt(std::time::Duration::from_millis(100)).unwrap();
+
+        assert!(core.reset_detected().unwrap());
+        assert!(!core.reset_detected().unwrap());
+    }
+}
@@ -0,0 +1,652 @@
+//! The memory, system control space and debug logic of the simulated target.
+
+use std::collections::HashMap;
+
+use super::thumb::{Bus, Cpu, Fault, Step};
+
+const PAGE_SIZE: u32 = 0x400;
+
+/// The private peripheral bus, which contains the system control space and the debug components.
+const PPB_START: u32 = 0xE000_0000;
+const PPB_END: u32 = 0xE010_0000;
+
+const DWT: u32 = 0xE000_1000;
+const FPB: u32 = 0xE000_2000;
+const SCS: u32 = 0xE000_E000;
+/// The ROM table, which is also the debug base address of the MEM-AP.
+pub(super) const ROM_TABLE: u32 = 0xE00F_F000;
+
+const CPUID: u32 = 0xE000_ED00;
+const VTOR: u32 = 0xE000_ED08;
+const AIRCR: u32 = 0xE000_ED0C;
+const CFSR: u32 = 0xE000_ED28;
+const HFSR: u32 = 0xE000_ED2C;
+const DFSR: u32 = 0xE000_ED30;
+const DHCSR: u32 = 0xE000_EDF0;
+const DCRSR: u32 = 0xE000_EDF4;
+const DCRDR: u32 = 0xE000_EDF8;
+const DEMCR: u32 = 0xE000_EDFC;
+const FP_CTRL: u32 = FPB;
+const FP_COMP0: u32 = FPB + 0x8;
+
+/// A Cortex-M4, revision r0p1.
+const CPUID_CORTEX_M4: u32 = 0x410F_C241;
+
+const NUM_FP_COMPARATORS: u32 = 6;
+
+const DHCSR_DBGKEY: u32 = 0xA05F;
+const DHCSR_C_DEBUGEN: u32 = 1 << 0;
+const DHCSR_C_HALT: u32 = 1 << 1;
+const DHCSR_C_STEP: u32 = 1 << 2;
+const DHCSR_C_MASKINTS: u32 = 1 << 3;
+const DHCSR_S_REGRDY: u32 = 1 << 16;
+const DHCSR_S_HALT: u32 = 1 << 17;
+const DHCSR_S_SLEEP: u32 = 1 << 18;
+const DHCSR_S_LOCKUP: u32 = 1 << 19;
+const DHCSR_S_RETIRE_ST: u32 = 1 << 24;
+const DHCSR_S_RESET_ST: u32 = 1 << 25;
+
+const DFSR_HALTED: u32 = 1 << 0;
+const DFSR_BKPT: u32 = 1 << 1;
+const DFSR_VCATCH: u32 = 1 << 3;
+
+const DEMCR_VC_CORERESET: u32 = 1 << 0;
+
+const AIRCR_VECTKEY: u32 = 0x05FA;
+const AIRCR_VECTRESET: u32 = 1 << 0;
+const AIRCR_SYSRESETREQ: u32 = 1 << 2;
+
+const CFSR_IACCVIOL: u32 = 1 << 0;
+const CFSR_UNDEFINSTR: u32 = 1 << 16;
+const CFSR_INVSTATE: u32 = 1 << 17;
+const HFSR_FORCED: u32 = 1 << 30;
+
+/// The debug components listed in the ROM table, with their part numbers and component class.
+const COMPONENTS: [(u32, u32, u32); 4] = [
+    (SCS, 0x00C, 0xE),
+    (DWT, 0x002, 0xE),
+    (FPB, 0x003, 0xE),
+    (ROM_TABLE, 0x4C4, 0x1),
+];
+
+/// Everything the core can access, i.e. the memory and the registers of the
+/// private peripheral bus.
+///
+/// Side effects which change the state of the core, like resets, are requested here and
+/// performed by the [`SimulatedTarget`].
+#[derive(Debug, Default)]
+struct SystemBus {
+    memory: HashMap<u32, Box<[u8; PAGE_SIZE as usize]>>,
+
+    /// The vector table which is used after a reset.
+    reset_vector_table: u32,
+    vtor: u32,
+    cfsr: u32,
+    hfsr: u32,
+    dfsr: u32,
+    demcr: u32,
+    dcrdr: u32,
+    fpb_enabled: bool,
+    fp_comparators: [u32; NUM_FP_COMPARATORS as usize],
+    /// The registers without side effects, which simply store the written value.
+    registers: HashMap<u32, u32>,
+
+    debug_enabled: bool,
+    mask_interrupts: bool,
+    halted: bool,
+    sleeping: bool,
+    locked_up: bool,
+    /// Sticky flag, set when an instruction was retired since `DHCSR` was read.
+    retired: bool,
+    /// Sticky flag, set when the core was reset since `DHCSR` was read.
+    was_reset: bool,
+
+    step_requested: bool,
+    reset_requested: bool,
+    register_transfer: Option<u32>,
+}
+
+impl SystemBus {
+    fn page(&self, address: u32) -> Option<&[u8; PAGE_SIZE as usize]> {
+        self.memory
+            .get(&(address / PAGE_SIZE))
+            .map(|page| page.as_ref())
+    }
+
+    fn read_byte(&self, address: u32) -> u8 {
+        self.page(address)
+            .map(|page| page[(address % PAGE_SIZE) as usize])
+            .unwrap_or(0)
+    }
+
+    fn write_byte(&mut self, address: u32, value: u8) {
+        let page = self
+            .memory
+            .entry(address / PAGE_SIZE)
+            .or_insert_with(|| Box::new([0; PAGE_SIZE as usize]));
+
+        page[(address % PAGE_SIZE) as usize] = value;
+    }
+
+    fn is_ppb(address: u32) -> bool {
+        (PPB_START..PPB_END).contains(&address)
+    }
+
+    fn halt(&mut self, reason: u32) {
+        self.halted = true;
+        self.sleeping = false;
+        self.dfsr |= reason;
+    }
+
+    fn is_running(&self) -> bool {
+        !(self.halted || self.sleeping || self.locked_up)
+    }
+
+    fn breakpoint_at(&self, address: u32) -> bool {
+        self.fpb_enabled
+            && self
+                .fp_comparators
+                .iter()
+                .any(|comparator| comparator & 1 == 1 && comparator & !1 == address)
+    }
+
+    /// Read a register of the private peripheral bus.
+    fn read_register(&mut self, address: u32) -> u32 {
+        match address {
+            CPUID => CPUID_CORTEX_M4,
+            VTOR => self.vtor,
+            AIRCR => 0xFA05_0000,
+            CFSR => self.cfsr,
+            HFSR => self.hfsr,
+            DFSR => self.dfsr,
+            DHCSR => {
+                let mut dhcsr = DHCSR_S_REGRDY;
+
+                let flags = [
+                    (self.debug_enabled, DHCSR_C_DEBUGEN),
+                    (self.halted, DHCSR_C_HALT | DHCSR_S_HALT),
+                    (self.mask_interrupts, DHCSR_C_MASKINTS),
+                    (self.sleeping, DHCSR_S_SLEEP),
+                    (self.locked_up, DHCSR_S_LOCKUP),
+                    (self.retired, DHCSR_S_RETIRE_ST),
+                    (self.was_reset, DHCSR_S_RESET_ST),
+                ];
+
+                for (set, bits) in flags.iter() {
+                    if *set {
+                        dhcsr |= bits;
+                    }
+                }
+
+                self.retired = false;
+                self.was_reset = false;
+
+                dhcsr
+            }
+            DCRDR => self.dcrdr,
+            DEMCR => self.demcr,
+            // Revision 2 of the FPB, which can set breakpoints on the whole address space.
+            FP_CTRL => (1 << 28) | (NUM_FP_COMPARATORS << 4) | u32::from(self.fpb_enabled),
+            address if (FP_COMP0..FP_COMP0 + 4 * NUM_FP_COMPARATORS).contains(&address) => {
+                self.fp_comparators[((address - FP_COMP0) / 4) as usize]
+            }
+            address if (ROM_TABLE..ROM_TABLE + 0xFD0).contains(&address) => {
+                // The entries of the ROM table are the offsets of the components, followed by a zero entry.
+                let index = ((address - ROM_TABLE) / 4) as usize;
+
+                COMPONENTS[..COMPONENTS.len() - 1]
+                    .get(index)
+                    .map(|(base, _, _)| base.wrapping_sub(ROM_TABLE) | 0b11)
+                    .unwrap_or(0)
+            }
+            address => component_id(address)
+                .or_else(|| self.registers.get(&address).copied())
+                .unwrap_or(0),
+        }
+    }
+
+    /// Write a register of the private peripheral bus.
+    fn write_register(&mut self, address: u32, value: u32) {
+        match address {
+            CPUID => {}
+            VTOR => self.vtor = value & !0x7F,
+            AIRCR => {
+                if value >> 16 == AIRCR_VECTKEY
+                    && value & (AIRCR_SYSRESETREQ | AIRCR_VECTRESET) != 0
+                {
+                    self.reset_requested = true;
+                }
+            }
+            CFSR => self.cfsr &= !value,
+            HFSR => self.hfsr &= !value,
+            DFSR => self.dfsr &= !value,
+            DHCSR => {
+                if value >> 16 != DHCSR_DBGKEY {
+                    return;
+                }
+
+                self.debug_enabled = value & DHCSR_C_DEBUGEN != 0;
+                self.mask_interrupts = value & DHCSR_C_MASKINTS != 0;
+
+                if self.debug_enabled && value & DHCSR_C_HALT != 0 {
+                    if !self.halted {
+                        self.halt(DFSR_HALTED);
+                    }
+                } else if self.halted {
+                    self.halted = false;
+                    self.step_requested = self.debug_enabled && value & DHCSR_C_STEP != 0;
+                }
+            }
+            DCRSR => self.register_transfer = Some(value),
+            DCRDR => self.dcrdr = value,
+            DEMCR => self.demcr = value,
+            FP_CTRL => {
+                // The enable bit is only written if the key bit is set.
+                if value & 0b10 != 0 {
+                    self.fpb_enabled = value & 1 != 0;
+                }
+            }
+            address if (FP_COMP0..FP_COMP0 + 4 * NUM_FP_COMPARATORS).contains(&address) => {
+                self.fp_comparators[((address - FP_COMP0) / 4) as usize] = value;
+            }
+            address => {
+                self.registers.insert(address, value);
+            }
+        }
+    }
+}
+
+impl Bus for SystemBus {
+    fn read_8(&mut self, address: u32) -> u8 {
+        if Self::is_ppb(address) {
+            (self.read_32(address & !0b11) >> ((address & 0b11) * 8)) as u8
+        } else {
+            self.read_byte(address)
+        }
+    }
+
+    fn read_16(&mut self, address: u32) -> u16 {
+        if Self::is_ppb(address) {
+            (self.read_32(address & !0b11) >> ((address & 0b10) * 8)) as u16
+        } else {
+            u16::from_le_bytes([
+                self.read_byte(address),
+                self.read_byte(address.wrapping_add(1)),
+            ])
+        }
+    }
+
+    fn read_32(&mut self, address: u32) -> u32 {
+        if Self::is_ppb(address) {
+            self.read_register(address & !0b11)
+        } else {
+            u32::from_le_bytes([
+                self.read_byte(address),
+                self.read_byte(address.wrapping_add(1)),
+                self.read_byte(address.wrapping_add(2)),
+                self.read_byte(address.wrapping_add(3)),
+            ])
+        }
+    }
+
+    // Only word accesses to the private peripheral bus are supported, smaller writes are ignored.
+
+    fn write_8(&mut self, address: u32, value: u8) {
+        if !Self::is_ppb(address) {
+            self.write_byte(address, value);
+        }
+    }
+
+    fn write_16(&mut self, address: u32, value: u16) {
+        if !Self::is_ppb(address) {
+            for (offset, byte) in value.to_le_bytes().iter().enumerate() {
+                self.write_byte(address.wrapping_add(offset as u32), *byte);
+            }
+        }
+    }
+
+    fn write_32(&mut self, address: u32, value: u32) {
+        if Self::is_ppb(address) {
+            self.write_register(address & !0b11, value);
+        } else {
+            for (offset, byte) in value.to_le_bytes().iter().enumerate() {
+                self.write_byte(address.wrapping_add(offset as u32), *byte);
+            }
+        }
+    }
+}
+
+/// The peripheral and component ID registers of the debug components.
+fn component_id(address: u32) -> Option<u32> {
+    let base = address & !0xFFF;
+    let (_, part, class) = COMPONENTS.iter().find(|(b, _, _)| *b == base)?;
+
+    if address & 0xFFF < 0xFD0 {
+        return None;
+    }
+
+    // The JEP106 code of ARM, 0x4 continuation codes followed by 0x3B.
+    let id = match address & 0xFFF {
+        0xFD0 => 0x04,
+        0xFE0 => part & 0xFF,
+        0xFE4 => (part >> 8) | 0xB0,
+        0xFE8 => 0x0B,
+        0xFF0 => 0x0D,
+        0xFF4 => class << 4,
+        0xFF8 => 0x05,
+        0xFFC => 0xB1,
+        _ => 0,
+    };
+
+    Some(id)
+}
+
+/// A Cortex-M4 core with a flat memory, simulated on the host.
+///
+/// All addresses outside of the private peripheral bus are backed by RAM, which reads as zero
+/// until it is written. The core is reset when the target is created, so it starts to execute
+/// from the vector table at address 0, unless [`SimulatedTarget::set_vector_table`] is used.
+#[derive(Debug)]
+pub struct SimulatedTarget {
+    cpu: Cpu,
+    bus: SystemBus,
+    /// Registers which are accessible through the `DCRSR`, but not simulated, e.g. the FPU registers.
+    other_registers: HashMap<u8, u32>,
+    reset_asserted: bool,
+}
+
+impl SimulatedTarget {
+    pub fn new() -> Self {
+        let mut target = SimulatedTarget {
+            cpu: Cpu::default(),
+            bus: SystemBus::default(),
+            other_registers: HashMap::new(),
+            reset_asserted: false,
+        };
+
+        target.reset();
+
+        target
+    }
+
+    /// Set the address of the vector table which is used after a reset, i.e. the reset value of the `VTOR`.
+    pub fn set_vector_table(&mut self, address: u32) {
+        self.bus.reset_vector_table = address;
+    }
+
+    /// Write to the memory of the target, e.g. to load a program before the target is reset.
+    pub fn write_memory(&mut self, address: u32, data: &[u8]) {
+        for (offset, byte) in data.iter().enumerate() {
+            self.bus.write_8(address.wrapping_add(offset as u32), *byte);
+        }
+    }
+
+    /// Read from the memory of the target.
+    pub fn read_memory(&mut self, address: u32, data: &mut [u8]) {
+        for (offset, byte) in data.iter_mut().enumerate() {
+            *byte = self.bus.read_8(address.wrapping_add(offset as u32));
+        }
+    }
+
+    /// Reset the core and the system control space, and load the initial stack pointer and PC
+    /// from the vector table. The memory and the debug registers keep their values.
+    pub fn reset(&mut self) {
+        let bus = &mut self.bus;
+
+        bus.vtor = bus.reset_vector_table;
+        bus.cfsr = 0;
+        bus.hfsr = 0;
+        bus.fpb_enabled = false;
+        bus.fp_comparators = Default::default();
+        bus.registers.clear();
+        bus.halted = false;
+        bus.sleeping = false;
+        bus.locked_up = false;
+        bus.was_reset = true;
+        bus.reset_requested = false;
+        bus.step_requested = false;
+
+        let initial_sp = bus.read_32(bus.vtor);
+        let reset_vector = bus.read_32(bus.vtor.wrapping_add(4));
+
+        self.cpu = Cpu::reset(initial_sp, reset_vector);
+
+        if bus.debug_enabled && bus.demcr & DEMCR_VC_CORERESET != 0 {
+            bus.halt(DFSR_VCATCH);
+        }
+    }
+
+    /// Assert or deassert the reset pin. The core is reset when the pin is deasserted.
+    pub fn set_reset_pin(&mut self, asserted: bool) {
+        if self.reset_asserted && !asserted {
+            self.reset_asserted = false;
+            self.reset();
+        } else {
+            self.reset_asserted = asserted;
+            self.bus.was_reset |= asserted;
+        }
+    }
+
+    /// The address of the next instruction.
+    pub fn pc(&self) -> u32 {
+        self.cpu.pc()
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.bus.halted
+    }
+
+    /// Check if the core locked up, because it executed an unsupported instruction.
+    pub fn is_locked_up(&self) -> bool {
+        self.bus.locked_up
+    }
+
+    /// Execute up to `instructions` instructions, until the core halts or sleeps.
+    pub fn run(&mut self, instructions: usize) {
+        for _ in 0..instructions {
+            if self.reset_asserted || !self.bus.is_running() {
+                break;
+            }
+
+            if self.bus.breakpoint_at(self.cpu.pc()) {
+                self.bus.halt(DFSR_BKPT);
+                break;
+            }
+
+            self.execute_instruction();
+        }
+    }
+
+    fn execute_instruction(&mut self) {
+        // The private peripheral bus is never executable.
+        let result = if SystemBus::is_ppb(self.cpu.pc()) || self.cpu.pc() >= PPB_END {
+            Err(None)
+        } else {
+            self.cpu.step(&mut self.bus).map_err(Some)
+        };
+
+        match result {
+            Ok(Step::Executed) => {}
+            Ok(Step::Breakpoint) => {
+                if self.bus.debug_enabled {
+                    self.bus.halt(DFSR_BKPT);
+                } else {
+                    // Without a debugger, BKPT escalates to a HardFault.
+                    self.bus.hfsr |= HFSR_FORCED;
+                    self.bus.locked_up = true;
+                }
+            }
+            Ok(Step::Sleep) => self.bus.sleeping = true,
+            Err(fault) => {
+                self.bus.cfsr |= match fault {
+                    None => CFSR_IACCVIOL,
+                    Some(Fault::UndefinedInstruction(_)) => CFSR_UNDEFINSTR,
+                    Some(Fault::InvalidState(_)) => CFSR_INVSTATE,
+                };
+                self.bus.hfsr |= HFSR_FORCED;
+                self.bus.locked_up = true;
+            }
+        }
+
+        self.bus.retired = true;
+        self.handle_requests();
+    }
+
+    /// Perform the side effects of a register access.
+    fn handle_requests(&mut self) {
+        if let Some(dcrsr) = self.bus.register_transfer.take() {
+            let regsel = (dcrsr & 0x7F) as u8;
+
+            if dcrsr & (1 << 16) != 0 {
+                if !self.cpu.set_register(regsel, self.bus.dcrdr) {
+                    self.other_registers.insert(regsel, self.bus.dcrdr);
+                }
+            } else {
+                self.bus.dcrdr = self
+                    .cpu
+                    .register(regsel)
+                    .unwrap_or_else(|| self.other_registers.get(&regsel).copied().unwrap_or(0));
+            }
+        }
+
+        if self.bus.reset_requested {
+            self.reset();
+        }
+
+        if self.bus.step_requested {
+            self.bus.step_requested = false;
+
+            if self.bus.is_running() {
+                self.execute_instruction();
+
+                if !self.bus.halted {
+                    self.bus.halt(DFSR_HALTED);
+                }
+            }
+        }
+    }
+
+    pub(super) fn read_8(&mut self, address: u32) -> u8 {
+        self.bus.read_8(address)
+    }
+
+    pub(super) fn read_16(&mut self, address: u32) -> u16 {
+        self.bus.read_16(address)
+    }
+
+    pub(super) fn read_32(&mut self, address: u32) -> u32 {
+        self.bus.read_32(address)
+    }
+
+    pub(super) fn write_8(&mut self, address: u32, value: u8) {
+        self.bus.write_8(address, value);
+        self.handle_requests();
+    }
+
+    pub(super) fn write_16(&mut self, address: u32, value: u16) {
+        self.bus.write_16(address, value);
+        self.handle_requests();
+    }
+
+    pub(super) fn write_32(&mut self, address: u32, value: u32) {
+        self.bus.write_32(address, value);
+        self.handle_requests();
+    }
+}
+
+impl Default for SimulatedTarget {
+    fn default() -> Self {
+        SimulatedTarget::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        SimulatedTarget, DCRDR, DCRSR, DEMCR, DEMCR_VC_CORERESET, DFSR, DFSR_BKPT, DFSR_VCATCH,
+        DHCSR, DHCSR_C_DEBUGEN, DHCSR_C_HALT, DHCSR_C_STEP, DHCSR_DBGKEY, DHCSR_S_HALT, FP_COMP0,
+        FP_CTRL,
+    };
+
+    /// A target with the vector table at 0, and a counting loop at 0x100.
+    fn counting_target() -> SimulatedTarget {
+        let mut target = SimulatedTarget::new();
+
+        target.write_memory(0x0, &0x1000u32.to_le_bytes());
+        target.write_memory(0x4, &0x101u32.to_le_bytes());
+        target.write_memory(
+            0x100,
+            &[
+                0x01, 0x30, // adds r0, #1
+                0xfd, 0xe7, // b.n 0x100
+            ],
+        );
+        target.reset();
+
+        target
+    }
+
+    fn write_dhcsr(target: &mut SimulatedTarget, bits: u32) {
+        target.write_32(DHCSR, (DHCSR_DBGKEY << 16) | bits);
+    }
+
+    fn read_register(target: &mut SimulatedTarget, regsel: u32) -> u32 {
+        target.write_32(DCRSR, regsel);
+        target.read_32(DCRDR)
+    }
+
+    #[test]
+    fn halt_on_reset_and_step() {
+        let mut target = counting_target();
+
+        write_dhcsr(&mut target, DHCSR_C_DEBUGEN);
+        target.write_32(DEMCR, DEMCR_VC_CORERESET);
+        target.reset();
+
+        assert!(target.is_halted());
+        assert_eq!(target.read_32(DFSR) & DFSR_VCATCH, DFSR_VCATCH);
+        assert_eq!(read_register(&mut target, 15), 0x100);
+        assert_eq!(read_register(&mut target, 13), 0x1000);
+
+        write_dhcsr(&mut target, DHCSR_C_DEBUGEN | DHCSR_C_STEP);
+
+        assert!(target.is_halted());
+        assert_eq!(read_register(&mut target, 0), 1);
+        assert_eq!(read_register(&mut target, 15), 0x102);
+
+        write_dhcsr(&mut target, DHCSR_C_DEBUGEN);
+        target.run(100);
+        write_dhcsr(&mut target, DHCSR_C_DEBUGEN | DHCSR_C_HALT);
+
+        assert!(target.read_32(DHCSR) & DHCSR_S_HALT != 0);
+        assert_eq!(read_register(&mut target, 0), 51);
+    }
+
+    #[test]
+    fn hardware_breakpoint() {
+        let mut target = counting_target();
+
+        write_dhcsr(&mut target, DHCSR_C_DEBUGEN | DHCSR_C_HALT);
+        target.write_32(FP_COMP0, 0x102 | 1);
+        target.write_32(FP_CTRL, 0b11);
+        write_dhcsr(&mut target, DHCSR_C_DEBUGEN);
+        target.run(100);
+
+        assert!(target.is_halted());
+        assert_eq!(target.pc(), 0x102);
+        assert_eq!(target.read_32(DFSR) & DFSR_BKPT, DFSR_BKPT);
+    }
+
+    #[test]
+    fn unsupported_instruction_locks_up() {
+        let mut target = SimulatedTarget::new();
+
+        target.write_memory(0x4, &0x101u32.to_le_bytes());
+        target.write_memory(0x100, &[0x00, 0xde]);
+        target.reset();
+        target.run(10);
+
+        assert!(target.is_locked_up());
+        assert_eq!(target.pc(), 0x100);
+    }
+}
@@ -0,0 +1,1596 @@
+//! A basic interpreter for the Thumb instruction set of ARMv7-M cores.
+//!
+//! The 16-bit instructions of ARMv6-M and the 32-bit integer instructions which
+//! compilers commonly emit are supported. Exceptions are not simulated, so faults,
+//! `SVC` and exception returns lock the core up instead.
+
+/// The memory of the simulated target, as seen by the core.
+pub(super) trait Bus {
+    fn read_8(&mut self, address: u32) -> u8;
+    fn read_16(&mut self, address: u32) -> u16;
+    fn read_32(&mut self, address: u32) -> u32;
+    fn write_8(&mut self, address: u32, value: u8);
+    fn write_16(&mut self, address: u32, value: u16);
+    fn write_32(&mut self, address: u32, value: u32);
+}
+
+/// The reason why the core can't continue execution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Fault {
+    /// The instruction is undefined, or not supported by the interpreter.
+    UndefinedInstruction(u32),
+    /// A branch tried to switch to the ARM state, or to return from an exception.
+    InvalidState(u32),
+}
+
+/// The result of executing a single instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Step {
+    Executed,
+    /// A `BKPT` instruction was hit. The PC points to the `BKPT` instruction.
+    Breakpoint,
+    /// The core waits for an interrupt or event, i.e. `WFI` or `WFE`.
+    Sleep,
+}
+
+const FLAG_N: u32 = 1 << 31;
+const FLAG_Z: u32 = 1 << 30;
+const FLAG_C: u32 = 1 << 29;
+const FLAG_V: u32 = 1 << 28;
+
+const EPSR_T: u32 = 1 << 24;
+
+const CONTROL_SPSEL: u32 = 1 << 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shift {
+    Lsl,
+    Lsr,
+    Asr,
+    Ror,
+    Rrx,
+}
+
+/// The registers of an ARMv7-M core.
+#[derive(Debug, Clone, Default)]
+pub(super) struct Cpu {
+    /// R0 to R15. R13 is the currently selected stack pointer.
+    pub regs: [u32; 16],
+    /// The flags of the APSR.
+    apsr: u32,
+    /// The IT bits of the EPSR.
+    it_state: u8,
+    /// The stack pointer which is not selected by `CONTROL.SPSEL`.
+    banked_sp: u32,
+    control: u32,
+    primask: u32,
+    basepri: u32,
+    faultmask: u32,
+}
+
+impl Cpu {
+    /// The state of the core after a reset, using the given vector table entries.
+    pub fn reset(initial_sp: u32, reset_vector: u32) -> Self {
+        let mut cpu = Cpu::default();
+
+        cpu.regs[13] = initial_sp & !0b11;
+        cpu.regs[14] = 0xFFFF_FFFF;
+        cpu.regs[15] = reset_vector & !1;
+
+        cpu
+    }
+
+    pub fn pc(&self) -> u32 {
+        self.regs[15]
+    }
+
+    pub fn xpsr(&self) -> u32 {
+        let it = u32::from(self.it_state);
+
+        self.apsr | EPSR_T | ((it & 0b11) << 25) | ((it >> 2) << 10)
+    }
+
+    fn set_xpsr(&mut self, value: u32) {
+        self.apsr = value & 0xF800_0000;
+        self.it_state = (((value >> 25) & 0b11) | (((value >> 10) & 0x3F) << 2)) as u8;
+    }
+
+    fn msp(&self) -> u32 {
+        if self.control & CONTROL_SPSEL == 0 {
+            self.regs[13]
+        } else {
+            self.banked_sp
+        }
+    }
+
+    fn set_msp(&mut self, value: u32) {
+        if self.control & CONTROL_SPSEL == 0 {
+            self.regs[13] = value & !0b11;
+        } else {
+            self.banked_sp = value & !0b11;
+        }
+    }
+
+    fn psp(&self) -> u32 {
+        if self.control & CONTROL_SPSEL == 0 {
+            self.banked_sp
+        } else {
+            self.regs[13]
+        }
+    }
+
+    fn set_psp(&mut self, value: u32) {
+        if self.control & CONTROL_SPSEL == 0 {
+            self.banked_sp = value & !0b11;
+        } else {
+            self.regs[13] = value & !0b11;
+        }
+    }
+
+    fn set_control(&mut self, value: u32) {
+        let value = value & 0b111;
+
+        if (value ^ self.control) & CONTROL_SPSEL != 0 {
+            std::mem::swap(&mut self.regs[13], &mut self.banked_sp);
+        }
+
+        self.control = value;
+    }
+
+    /// Read a register, using the register numbers of the `DCRSR`.
+    pub fn register(&self, regsel: u8) -> Option<u32> {
+        match regsel {
+            0..=15 => Some(self.regs[regsel as usize]),
+            16 => Some(self.xpsr()),
+            17 => Some(self.msp()),
+            18 => Some(self.psp()),
+            20 => Some(
+                (self.control << 24) | (self.faultmask << 16) | (self.basepri << 8) | self.primask,
+            ),
+            _ => None,
+        }
+    }
+
+    /// Write a register, using the register numbers of the `DCRSR`.
+    ///
+    /// Returns `false` if the register is not simulated.
+    pub fn set_register(&mut self, regsel: u8, value: u32) -> bool {
+        match regsel {
+            0..=12 | 14 => self.regs[regsel as usize] = value,
+            13 => self.regs[13] = value & !0b11,
+            15 => self.regs[15] = value & !1,
+            16 => self.set_xpsr(value),
+            17 => self.set_msp(value),
+            18 => self.set_psp(value),
+            20 => {
+                self.primask = value & 1;
+                self.basepri = (value >> 8) & 0xFF;
+                self.faultmask = (value >> 16) & 1;
+                self.set_control(value >> 24);
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    /// Execute the instruction at the PC.
+    pub fn step(&mut self, bus: &mut impl Bus) -> Result<Step, Fault> {
+        let pc = self.regs[15];
+
+        let first = u32::from(bus.read_16(pc));
+        let (instruction, size) = if first >> 11 >= 0b11101 {
+            (
+                (first << 16) | u32::from(bus.read_16(pc.wrapping_add(2))),
+                4,
+            )
+        } else {
+            (first, 2)
+        };
+
+        let in_it_block = self.it_state & 0xF != 0;
+        let condition = u32::from(self.it_state >> 4);
+        if in_it_block {
+            self.advance_it();
+        }
+
+        self.regs[15] = pc.wrapping_add(size);
+
+        if in_it_block && !self.condition_passed(condition) {
+            return Ok(Step::Executed);
+        }
+
+        let result = if size == 2 {
+            self.execute_16(instruction, pc, bus, in_it_block)
+        } else {
+            self.execute_32(instruction, pc, bus)
+        };
+
+        match result {
+            Ok(Step::Breakpoint) | Err(_) => self.regs[15] = pc,
+            _ => {}
+        }
+
+        result
+    }
+
+    fn advance_it(&mut self) {
+        if self.it_state & 0b111 == 0 {
+            self.it_state = 0;
+        } else {
+            self.it_state = (self.it_state & 0xE0) | ((self.it_state << 1) & 0x1F);
+        }
+    }
+
+    fn condition_passed(&self, condition: u32) -> bool {
+        let n = self.apsr & FLAG_N != 0;
+        let z = self.apsr & FLAG_Z != 0;
+        let c = self.apsr & FLAG_C != 0;
+        let v = self.apsr & FLAG_V != 0;
+
+        let result = match condition >> 1 {
+            0 => z,
+            1 => c,
+            2 => n,
+            3 => v,
+            4 => c && !z,
+            5 => n == v,
+            6 => !z && n == v,
+            _ => true,
+        };
+
+        if condition & 1 == 1 && condition != 0xF {
+            !result
+        } else {
+            result
+        }
+    }
+
+    fn carry(&self) -> bool {
+        self.apsr & FLAG_C != 0
+    }
+
+    fn set_nz(&mut self, result: u32) {
+        self.apsr &= !(FLAG_N | FLAG_Z);
+        if result & (1 << 31) != 0 {
+            self.apsr |= FLAG_N;
+        }
+        if result == 0 {
+            self.apsr |= FLAG_Z;
+        }
+    }
+
+    fn set_nzc(&mut self, result: u32, carry: bool) {
+        self.set_nz(result);
+        self.apsr &= !FLAG_C;
+        if carry {
+            self.apsr |= FLAG_C;
+        }
+    }
+
+    fn set_nzcv(&mut self, result: u32, carry: bool, overflow: bool) {
+        self.set_nzc(result, carry);
+        self.apsr &= !FLAG_V;
+        if overflow {
+            self.apsr |= FLAG_V;
+        }
+    }
+
+    /// Read a register as an operand, where the PC reads as the address of the
+    /// current instruction plus 4.
+    fn reg(&self, n: u32, pc: u32) -> u32 {
+        if n == 15 {
+            pc.wrapping_add(4)
+        } else {
+            self.regs[n as usize]
+        }
+    }
+
+    /// Write a register, where writes to the PC are simple branches.
+    fn set_reg(&mut self, n: u32, value: u32) {
+        if n == 15 {
+            self.regs[15] = value & !1;
+        } else {
+            self.regs[n as usize] = value;
+        }
+    }
+
+    /// Branch to an address loaded from memory or a register, which has to stay in the Thumb state.
+    fn bx_write_pc(&mut self, address: u32) -> Result<(), Fault> {
+        if address & 1 == 0 || address >= 0xF000_0000 {
+            return Err(Fault::InvalidState(address));
+        }
+
+        self.regs[15] = address & !1;
+
+        Ok(())
+    }
+
+    fn execute_16(
+        &mut self,
+        hw: u32,
+        pc: u32,
+        bus: &mut impl Bus,
+        in_it_block: bool,
+    ) -> Result<Step, Fault> {
+        let setflags = !in_it_block;
+        let undefined = Err(Fault::UndefinedInstruction(hw));
+
+        match hw >> 11 {
+            // LSL, LSR, ASR (immediate)
+            0b00000..=0b00010 => {
+                let (shift, amount) = decode_imm_shift(hw >> 11, (hw >> 6) & 0x1F);
+                let (result, carry) = shift_c(
+                    self.regs[((hw >> 3) & 7) as usize],
+                    shift,
+                    amount,
+                    self.carry(),
+                );
+
+                self.regs[(hw & 7) as usize] = result;
+                if setflags {
+                    self.set_nzc(result, carry);
+                }
+            }
+            // ADD, SUB (register or 3 bit immediate)
+            0b00011 => {
+                let n = self.regs[((hw >> 3) & 7) as usize];
+                let operand = if hw & (1 << 10) != 0 {
+                    (hw >> 6) & 7
+                } else {
+                    self.regs[((hw >> 6) & 7) as usize]
+                };
+
+                let (result, carry, overflow) = if hw & (1 << 9) != 0 {
+                    add_with_carry(n, !operand, true)
+                } else {
+                    add_with_carry(n, operand, false)
+                };
+
+                self.regs[(hw & 7) as usize] = result;
+                if setflags {
+                    self.set_nzcv(result, carry, overflow);
+                }
+            }
+            // MOV, CMP, ADD, SUB (8 bit immediate)
+            0b00100..=0b00111 => {
+                let rdn = ((hw >> 8) & 7) as usize;
+                let imm = hw & 0xFF;
+
+                match (hw >> 11) & 0b11 {
+                    0b00 => {
+                        self.regs[rdn] = imm;
+                        if setflags {
+                            self.set_nz(imm);
+                        }
+                    }
+                    0b01 => {
+                        let (result, carry, overflow) = add_with_carry(self.regs[rdn], !imm, true);
+                        self.set_nzcv(result, carry, overflow);
+                    }
+                    op => {
+                        let (result, carry, overflow) = if op == 0b10 {
+                            add_with_carry(self.regs[rdn], imm, false)
+                        } else {
+                            add_with_carry(self.regs[rdn], !imm, true)
+                        };
+
+                        self.regs[rdn] = result;
+                        if setflags {
+                            self.set_nzcv(result, carry, overflow);
+                        }
+                    }
+                }
+            }
+            0b01000 => {
+                if hw & (1 << 10) == 0 {
+                    self.data_processing_16(hw, setflags);
+                } else {
+                    return self.special_data_16(hw, pc);
+                }
+            }
+            // LDR (literal)
+            0b01001 => {
+                let address = align(pc.wrapping_add(4)).wrapping_add((hw & 0xFF) << 2);
+                self.regs[((hw >> 8) & 7) as usize] = bus.read_32(address);
+            }
+            // Load and store (register offset)
+            0b01010 | 0b01011 => {
+                let rt = (hw & 7) as usize;
+                let address = self.regs[((hw >> 3) & 7) as usize]
+                    .wrapping_add(self.regs[((hw >> 6) & 7) as usize]);
+
+                match (hw >> 9) & 7 {
+                    0 => bus.write_32(address, self.regs[rt]),
+                    1 => bus.write_16(address, self.regs[rt] as u16),
+                    2 => bus.write_8(address, self.regs[rt] as u8),
+                    3 => self.regs[rt] = bus.read_8(address) as i8 as u32,
+                    4 => self.regs[rt] = bus.read_32(address),
+                    5 => self.regs[rt] = u32::from(bus.read_16(address)),
+                    6 => self.regs[rt] = u32::from(bus.read_8(address)),
+                    _ => self.regs[rt] = bus.read_16(address) as i16 as u32,
+                }
+            }
+            // Load and store (5 bit immediate offset)
+            0b01100..=0b10001 => {
+                let rt = (hw & 7) as usize;
+                let base = self.regs[((hw >> 3) & 7) as usize];
+                let imm = (hw >> 6) & 0x1F;
+
+                match hw >> 11 {
+                    0b01100 => bus.write_32(base.wrapping_add(imm << 2), self.regs[rt]),
+                    0b01101 => self.regs[rt] = bus.read_32(base.wrapping_add(imm << 2)),
+                    0b01110 => bus.write_8(base.wrapping_add(imm), self.regs[rt] as u8),
+                    0b01111 => self.regs[rt] = u32::from(bus.read_8(base.wrapping_add(imm))),
+                    0b10000 => bus.write_16(base.wrapping_add(imm << 1), self.regs[rt] as u16),
+                    _ => self.regs[rt] = u32::from(bus.read_16(base.wrapping_add(imm << 1))),
+                }
+            }
+            // STR, LDR (SP relative)
+            0b10010 | 0b10011 => {
+                let rt = ((hw >> 8) & 7) as usize;
+                let address = self.regs[13].wrapping_add((hw & 0xFF) << 2);
+
+                if hw & (1 << 11) == 0 {
+                    bus.write_32(address, self.regs[rt]);
+                } else {
+                    self.regs[rt] = bus.read_32(address);
+                }
+            }
+            // ADR
+            0b10100 => {
+                self.regs[((hw >> 8) & 7) as usize] =
+                    align(pc.wrapping_add(4)).wrapping_add((hw & 0xFF) << 2);
+            }
+            // ADD (SP plus immediate)
+            0b10101 => {
+                self.regs[((hw >> 8) & 7) as usize] = self.regs[13].wrapping_add((hw & 0xFF) << 2);
+            }
+            0b10110 | 0b10111 => return self.miscellaneous_16(hw, pc, bus, in_it_block),
+            // STM
+            0b11000 => {
+                let rn = ((hw >> 8) & 7) as usize;
+                let mut address = self.regs[rn];
+
+                for register in registers(hw & 0xFF) {
+                    bus.write_32(address, self.regs[register]);
+                    address = address.wrapping_add(4);
+                }
+
+                self.regs[rn] = address;
+            }
+            // LDM
+            0b11001 => {
+                let rn = ((hw >> 8) & 7) as usize;
+                let mut address = self.regs[rn];
+
+                for register in registers(hw & 0xFF) {
+                    self.regs[register] = bus.read_32(address);
+                    address = address.wrapping_add(4);
+                }
+
+                if hw & (1 << rn) == 0 {
+                    self.regs[rn] = address;
+                }
+            }
+            // B (conditional), UDF, SVC
+            0b11010 | 0b11011 => {
+                let condition = (hw >> 8) & 0xF;
+
+                if condition >= 0xE {
+                    return undefined;
+                }
+
+                if self.condition_passed(condition) {
+                    let offset = sign_extend((hw & 0xFF) << 1, 9);
+                    self.regs[15] = pc.wrapping_add(4).wrapping_add(offset);
+                }
+            }
+            // B (unconditional)
+            0b11100 => {
+                let offset = sign_extend((hw & 0x7FF) << 1, 12);
+                self.regs[15] = pc.wrapping_add(4).wrapping_add(offset);
+            }
+            _ => return undefined,
+        }
+
+        Ok(Step::Executed)
+    }
+
+    fn data_processing_16(&mut self, hw: u32, setflags: bool) {
+        let rdn = (hw & 7) as usize;
+        let rm = ((hw >> 3) & 7) as usize;
+
+        let n = self.regs[rdn];
+        let m = self.regs[rm];
+
+        match (hw >> 6) & 0xF {
+            // AND, EOR, ORR, BIC, MVN
+            op @ (0b0000 | 0b0001 | 0b1100 | 0b1110 | 0b1111) => {
+                let result = match op {
+                    0b0000 => n & m,
+                    0b0001 => n ^ m,
+                    0b1100 => n | m,
+                    0b1110 => n & !m,
+                    _ => !m,
+                };
+
+                self.regs[rdn] = result;
+                if setflags {
+                    self.set_nz(result);
+                }
+            }
+            // LSL, LSR, ASR, ROR (register)
+            op @ (0b0010 | 0b0011 | 0b0100 | 0b0111) => {
+                let shift = match op {
+                    0b0010 => Shift::Lsl,
+                    0b0011 => Shift::Lsr,
+                    0b0100 => Shift::Asr,
+                    _ => Shift::Ror,
+                };
+
+                let (result, carry) = shift_c(n, shift, m & 0xFF, self.carry());
+
+                self.regs[rdn] = result;
+                if setflags {
+                    self.set_nzc(result, carry);
+                }
+            }
+            // ADC, SBC, RSB
+            op @ (0b0101 | 0b0110 | 0b1001) => {
+                let (result, carry, overflow) = match op {
+                    0b0101 => add_with_carry(n, m, self.carry()),
+                    0b0110 => add_with_carry(n, !m, self.carry()),
+                    _ => add_with_carry(!m, 0, true),
+                };
+
+                self.regs[rdn] = result;
+                if setflags {
+                    self.set_nzcv(result, carry, overflow);
+                }
+            }
+            // TST
+            0b1000 => self.set_nz(n & m),
+            // CMP
+            0b1010 => {
+                let (result, carry, overflow) = add_with_carry(n, !m, true);
+                self.set_nzcv(result, carry, overflow);
+            }
+            // CMN
+            0b1011 => {
+                let (result, carry, overflow) = add_with_carry(n, m, false);
+                self.set_nzcv(result, carry, overflow);
+            }
+            // MUL
+            _ => {
+                let result = n.wrapping_mul(m);
+
+                self.regs[rdn] = result;
+                if setflags {
+                    self.set_nz(result);
+                }
+            }
+        }
+    }
+
+    fn special_data_16(&mut self, hw: u32, pc: u32) -> Result<Step, Fault> {
+        let rdn = ((hw >> 4) & 0b1000) | (hw & 7);
+        let rm = (hw >> 3) & 0xF;
+
+        match (hw >> 8) & 0b11 {
+            // ADD (high registers)
+            0b00 => {
+                let result = self.reg(rdn, pc).wrapping_add(self.reg(rm, pc));
+                self.set_reg(rdn, result);
+            }
+            // CMP (high registers)
+            0b01 => {
+                let (result, carry, overflow) =
+                    add_with_carry(self.reg(rdn, pc), !self.reg(rm, pc), true);
+                self.set_nzcv(result, carry, overflow);
+            }
+            // MOV (high registers)
+            0b10 => {
+                let value = self.reg(rm, pc);
+                self.set_reg(rdn, value);
+            }
+            // BX, BLX
+            _ => {
+                let target = self.reg(rm, pc);
+
+                if hw & (1 << 7) != 0 {
+                    self.regs[14] = pc.wrapping_add(2) | 1;
+                }
+
+                self.bx_write_pc(target)?;
+            }
+        }
+
+        Ok(Step::Executed)
+    }
+
+    fn miscellaneous_16(
+        &mut self,
+        hw: u32,
+        pc: u32,
+        bus: &mut impl Bus,
+        in_it_block: bool,
+    ) -> Result<Step, Fault> {
+        let undefined = Err(Fault::UndefinedInstruction(hw));
+
+        match (hw >> 8) & 0xF {
+            // ADD, SUB (SP plus immediate)
+            0b0000 => {
+                let imm = (hw & 0x7F) << 2;
+
+                self.regs[13] = if hw & (1 << 7) == 0 {
+                    self.regs[13].wrapping_add(imm)
+                } else {
+                    self.regs[13].wrapping_sub(imm)
+                };
+            }
+            // CBZ, CBNZ
+            0b0001 | 0b0011 | 0b1001 | 0b1011 => {
+                let offset = (((hw >> 9) & 1) << 6) | (((hw >> 3) & 0x1F) << 1);
+                let nonzero = hw & (1 << 11) != 0;
+
+                if (self.regs[(hw & 7) as usize] != 0) == nonzero {
+                    self.regs[15] = pc.wrapping_add(4).wrapping_add(offset);
+                }
+            }
+            // SXTH, SXTB, UXTH, UXTB
+            0b0010 => {
+                let m = self.regs[((hw >> 3) & 7) as usize];
+
+                self.regs[(hw & 7) as usize] = match (hw >> 6) & 0b11 {
+                    0b00 => m as i16 as u32,
+                    0b01 => m as i8 as u32,
+                    0b10 => m & 0xFFFF,
+                    _ => m & 0xFF,
+                };
+            }
+            // PUSH
+            0b0100 | 0b0101 => {
+                let list = (hw & 0xFF) | (((hw >> 8) & 1) << 14);
+                self.store_multiple_decrement_before(13, list, true, bus);
+            }
+            // CPS
+            0b0110 if hw & 0xFFEC == 0xB660 => {
+                let disable = (hw >> 4) & 1;
+
+                if hw & 0b10 != 0 {
+                    self.primask = disable;
+                }
+                if hw & 0b01 != 0 {
+                    self.faultmask = disable;
+                }
+            }
+            // REV, REV16, REVSH
+            0b1010 => {
+                let m = self.regs[((hw >> 3) & 7) as usize];
+
+                self.regs[(hw & 7) as usize] = match (hw >> 6) & 0b11 {
+                    0b00 => m.swap_bytes(),
+                    0b01 => rev16(m),
+                    0b11 => (m as u16).swap_bytes() as i16 as u32,
+                    _ => return undefined,
+                };
+            }
+            // POP
+            0b1100 | 0b1101 => {
+                let list = (hw & 0xFF) | (((hw >> 8) & 1) << 15);
+                self.load_multiple_increment_after(13, list, true, pc, bus)?;
+            }
+            // BKPT
+            0b1110 => return Ok(Step::Breakpoint),
+            // IT, and hints
+            0b1111 => {
+                if hw & 0xF != 0 {
+                    if in_it_block {
+                        return undefined;
+                    }
+
+                    self.it_state = (hw & 0xFF) as u8;
+                } else {
+                    return Ok(hint((hw >> 4) & 0xF));
+                }
+            }
+            _ => return undefined,
+        }
+
+        Ok(Step::Executed)
+    }
+
+    fn execute_32(&mut self, instruction: u32, pc: u32, bus: &mut impl Bus) -> Result<Step, Fault> {
+        let hw1 = instruction >> 16;
+        let hw2 = instruction & 0xFFFF;
+
+        let op2 = (hw1 >> 4) & 0x7F;
+
+        match (hw1 >> 11) & 0b11 {
+            0b01 => {
+                if op2 & 0b110_0100 == 0b000_0000 {
+                    self.load_store_multiple(instruction, pc, bus)
+                } else if op2 & 0b110_0100 == 0b000_0100 {
+                    self.load_store_dual(instruction, pc, bus)
+                } else if op2 & 0b110_0000 == 0b010_0000 {
+                    let imm5 = (((hw2 >> 12) & 0b111) << 2) | ((hw2 >> 6) & 0b11);
+                    let (shift, amount) = decode_imm_shift((hw2 >> 4) & 0b11, imm5);
+                    let (operand, carry) =
+                        shift_c(self.reg(hw2 & 0xF, pc), shift, amount, self.carry());
+
+                    self.data_processing_32(instruction, operand, carry, pc)
+                } else {
+                    Err(Fault::UndefinedInstruction(instruction))
+                }
+            }
+            0b10 => {
+                if hw2 & (1 << 15) != 0 {
+                    self.branch_and_miscellaneous(instruction, pc)
+                } else if hw1 & (1 << 9) == 0 {
+                    let imm12 =
+                        (((hw1 >> 10) & 1) << 11) | (((hw2 >> 12) & 0b111) << 8) | (hw2 & 0xFF);
+                    let (operand, carry) = thumb_expand_imm_c(imm12, self.carry());
+
+                    self.data_processing_32(instruction, operand, carry, pc)
+                } else {
+                    self.plain_binary_immediate(instruction, pc)
+                }
+            }
+            _ => {
+                if op2 & 0b110_0000 == 0 {
+                    self.load_store_single(instruction, pc, bus)
+                } else if op2 & 0b111_0000 == 0b010_0000 {
+                    self.data_processing_register(instruction)
+                } else if op2 & 0b111_1000 == 0b011_0000 {
+                    self.multiply(instruction)
+                } else if op2 & 0b111_1000 == 0b011_1000 {
+                    self.long_multiply_divide(instruction)
+                } else {
+                    Err(Fault::UndefinedInstruction(instruction))
+                }
+            }
+        }
+    }
+
+    fn store_multiple_decrement_before(
+        &mut self,
+        rn: usize,
+        list: u32,
+        writeback: bool,
+        bus: &mut impl Bus,
+    ) {
+        let start = self.regs[rn].wrapping_sub(4 * list.count_ones());
+        let mut address = start;
+
+        for register in registers(list) {
+            bus.write_32(address, self.regs[register]);
+            address = address.wrapping_add(4);
+        }
+
+        if writeback {
+            self.regs[rn] = start;
+        }
+    }
+
+    fn load_multiple_increment_after(
+        &mut self,
+        rn: usize,
+        list: u32,
+        writeback: bool,
+        pc: u32,
+        bus: &mut impl Bus,
+    ) -> Result<(), Fault> {
+        let mut address = self.regs[rn];
+        let mut new_pc = None;
+
+        for register in registers(list) {
+            let value = bus.read_32(address);
+            address = address.wrapping_add(4);
+
+            if register == 15 {
+                new_pc = Some(value);
+            } else {
+                self.regs[register] = value;
+            }
+        }
+
+        if writeback && list & (1 << rn) == 0 {
+            self.regs[rn] = address;
+        }
+
+        if let Some(new_pc) = new_pc {
+            self.bx_write_pc(new_pc).map_err(|fault| {
+                // Report the fault at the load instruction.
+                self.regs[15] = pc;
+                fault
+            })?;
+        }
+
+        Ok(())
+    }
+
+    fn load_store_multiple(
+        &mut self,
+        instruction: u32,
+        pc: u32,
+        bus: &mut impl Bus,
+    ) -> Result<Step, Fault> {
+        let hw1 = instruction >> 16;
+        let rn = (hw1 & 0xF) as usize;
+        let list = instruction & 0xFFFF;
+        let load = hw1 & (1 << 4) != 0;
+        let writeback = hw1 & (1 << 5) != 0;
+
+        match ((hw1 >> 7) & 0b11, load) {
+            // STMIA
+            (0b01, false) => {
+                let mut address = self.regs[rn];
+
+                for register in registers(list) {
+                    bus.write_32(address, self.regs[register]);
+                    address = address.wrapping_add(4);
+                }
+
+                if writeback {
+                    self.regs[rn] = address;
+                }
+            }
+            // LDMIA, POP
+            (0b01, true) => self.load_multiple_increment_after(rn, list, writeback, pc, bus)?,
+            // STMDB, PUSH
+            (0b10, false) => self.store_multiple_decrement_before(rn, list, writeback, bus),
+            // LDMDB
+            (0b10, true) => {
+                let start = self.regs[rn].wrapping_sub(4 * list.count_ones());
+                let end = self.regs[rn];
+
+                self.regs[rn] = start;
+                self.load_multiple_increment_after(rn, list, false, pc, bus)?;
+
+                if !writeback && list & (1 << rn) == 0 {
+                    self.regs[rn] = end;
+                }
+            }
+            _ => return Err(Fault::UndefinedInstruction(instruction)),
+        }
+
+        Ok(Step::Executed)
+    }
+
+    fn load_store_dual(
+        &mut self,
+        instruction: u32,
+        pc: u32,
+        bus: &mut impl Bus,
+    ) -> Result<Step, Fault> {
+        let hw1 = instruction >> 16;
+        let hw2 = instruction & 0xFFFF;
+        let rn = hw1 & 0xF;
+        let rt = (hw2 >> 12) as usize;
+
+        let pre_indexed = hw1 & (1 << 8) != 0;
+        let add = hw1 & (1 << 7) != 0;
+        let writeback = hw1 & (1 << 5) != 0;
+        let load = hw1 & (1 << 4) != 0;
+
+        if hw1 & 0xFFF0 == 0xE8D0 && hw2 & 0xFFE0 == 0xF000 {
+            // TBB, TBH
+            let base = self.reg(rn, pc);
+            let index = self.regs[(hw2 & 0xF) as usize];
+
+            let offset = if hw2 & (1 << 4) != 0 {
+                u32::from(bus.read_16(base.wrapping_add(index << 1)))
+            } else {
+                u32::from(bus.read_8(base.wrapping_add(index)))
+            };
+
+            self.regs[15] = pc.wrapping_add(4).wrapping_add(offset << 1);
+        } else if !pre_indexed && !writeback && (hw1 >> 7) & 0b11 == 0 {
+            // LDREX, STREX. There is only a single core, so the store always succeeds.
+            let address = self.regs[rn as usize].wrapping_add((hw2 & 0xFF) << 2);
+
+            if load {
+                self.regs[rt] = bus.read_32(address);
+            } else {
+                bus.write_32(address, self.regs[rt]);
+                self.regs[((hw2 >> 8) & 0xF) as usize] = 0;
+            }
+        } else if pre_indexed || writeback {
+            // LDRD, STRD
+            let rt2 = ((hw2 >> 8) & 0xF) as usize;
+            let imm = (hw2 & 0xFF) << 2;
+            let base = if rn == 15 {
+                align(pc.wrapping_add(4))
+            } else {
+                self.regs[rn as usize]
+            };
+
+            let offset_address = if add {
+                base.wrapping_add(imm)
+            } else {
+                base.wrapping_sub(imm)
+            };
+            let address = if pre_indexed { offset_address } else { base };
+
+            if load {
+                self.regs[rt] = bus.read_32(address);
+                self.regs[rt2] = bus.read_32(address.wrapping_add(4));
+            } else {
+                bus.write_32(address, self.regs[rt]);
+                bus.write_32(address.wrapping_add(4), self.regs[rt2]);
+            }
+
+            if writeback {
+                self.regs[rn as usize] = offset_address;
+            }
+        } else {
+            return Err(Fault::UndefinedInstruction(instruction));
+        }
+
+        Ok(Step::Executed)
+    }
+
+    /// The data processing instructions with a modified immediate or a shifted register
+    /// as the second operand, which share the encoding of the operation.
+    fn data_processing_32(
+        &mut self,
+        instruction: u32,
+        operand: u32,
+        shifter_carry: bool,
+        pc: u32,
+    ) -> Result<Step, Fault> {
+        let hw1 = instruction >> 16;
+        let rn = hw1 & 0xF;
+        let rd = (instruction >> 8) & 0xF;
+        let setflags = hw1 & (1 << 4) != 0;
+        let op = (hw1 >> 5) & 0xF;
+
+        // ORR and ORN with the PC as the first operand are MOV and MVN.
+        let n = if rn == 15 && (op == 0b0010 || op == 0b0011) {
+            0
+        } else {
+            self.reg(rn, pc)
+        };
+
+        let (result, carry, overflow) = match op {
+            0b0000 => (n & operand, shifter_carry, None),
+            0b0001 => (n & !operand, shifter_carry, None),
+            0b0010 => (n | operand, shifter_carry, None),
+            0b0011 => (n | !operand, shifter_carry, None),
+            0b0100 => (n ^ operand, shifter_carry, None),
+            0b1000 | 0b1010 | 0b1011 | 0b1101 | 0b1110 => {
+                let (result, carry, overflow) = match op {
+                    0b1000 => add_with_carry(n, operand, false),
+                    0b1010 => add_with_carry(n, operand, self.carry()),
+                    0b1011 => add_with_carry(n, !operand, self.carry()),
+                    0b1101 => add_with_carry(n, !operand, true),
+                    _ => add_with_carry(!n, operand, true),
+                };
+
+                (result, carry, Some(overflow))
+            }
+            _ => return Err(Fault::UndefinedInstruction(instruction)),
+        };
+
+        if rd == 15 {
+            // TST, TEQ, CMN and CMP only set the flags.
+            if !setflags || !matches!(op, 0b0000 | 0b0100 | 0b1000 | 0b1101) {
+                return Err(Fault::UndefinedInstruction(instruction));
+            }
+        } else {
+            self.regs[rd as usize] = result;
+        }
+
+        if setflags {
+            match overflow {
+                Some(overflow) => self.set_nzcv(result, carry, overflow),
+                None => self.set_nzc(result, carry),
+            }
+        }
+
+        Ok(Step::Executed)
+    }
+
+    fn plain_binary_immediate(&mut self, instruction: u32, pc: u32) -> Result<Step, Fault> {
+        let hw1 = instruction >> 16;
+        let hw2 = instruction & 0xFFFF;
+        let rn = hw1 & 0xF;
+        let rd = ((hw2 >> 8) & 0xF) as usize;
+
+        let imm12 = (((hw1 >> 10) & 1) << 11) | (((hw2 >> 12) & 0b111) << 8) | (hw2 & 0xFF);
+        let lsb = (((hw2 >> 12) & 0b111) << 2) | ((hw2 >> 6) & 0b11);
+
+        let n = if rn == 15 {
+            align(pc.wrapping_add(4))
+        } else {
+            self.regs[rn as usize]
+        };
+
+        self.regs[rd] = match (hw1 >> 4) & 0x1F {
+            // ADDW, ADR
+            0b00000 => n.wrapping_add(imm12),
+            // MOVW
+            0b00100 => ((hw1 & 0xF) << 12) | imm12,
+            // SUBW, ADR
+            0b01010 => n.wrapping_sub(imm12),
+            // MOVT
+            0b01100 => (self.regs[rd] & 0xFFFF) | (((hw1 & 0xF) << 12 | imm12) << 16),
+            // SBFX, UBFX
+            op @ (0b10100 | 0b11100) => {
+                let width = (hw2 & 0x1F) + 1;
+                let field = (u64::from(n) >> lsb) & ((1u64 << width) - 1);
+
+                if op == 0b10100 {
+                    sign_extend(field as u32, width)
+                } else {
+                    field as u32
+                }
+            }
+            // BFI, BFC
+            0b10110 => {
+                let msb = hw2 & 0x1F;
+
+                if msb < lsb {
+                    return Err(Fault::UndefinedInstruction(instruction));
+                }
+
+                let mask = (((1u64 << (msb - lsb + 1)) - 1) << lsb) as u32;
+                let source = if rn == 15 { 0 } else { n << lsb };
+
+                (self.regs[rd] & !mask) | (source & mask)
+            }
+            _ => return Err(Fault::UndefinedInstruction(instruction)),
+        };
+
+        Ok(Step::Executed)
+    }
+
+    fn branch_and_miscellaneous(&mut self, instruction: u32, pc: u32) -> Result<Step, Fault> {
+        let hw1 = instruction >> 16;
+        let hw2 = instruction & 0xFFFF;
+        let op = (hw1 >> 4) & 0x7F;
+
+        let s = (hw1 >> 10) & 1;
+        let j1 = (hw2 >> 13) & 1;
+        let j2 = (hw2 >> 11) & 1;
+
+        match (hw2 >> 12) & 0b101 {
+            0b000 => {
+                if op & 0b011_1000 != 0b011_1000 {
+                    // B (conditional)
+                    let condition = (hw1 >> 6) & 0xF;
+                    let offset = sign_extend(
+                        (s << 20)
+                            | (j2 << 19)
+                            | (j1 << 18)
+                            | ((hw1 & 0x3F) << 12)
+                            | ((hw2 & 0x7FF) << 1),
+                        21,
+                    );
+
+                    if self.condition_passed(condition) {
+                        self.regs[15] = pc.wrapping_add(4).wrapping_add(offset);
+                    }
+                } else if op & 0b111_1110 == 0b011_1000 {
+                    self.move_to_special_register(
+                        hw2 & 0xFF,
+                        (hw2 >> 10) & 0b11,
+                        self.regs[(hw1 & 0xF) as usize],
+                    );
+                } else if op == 0b011_1010 {
+                    return Ok(hint(hw2 & 0xFF));
+                } else if op == 0b011_1011 {
+                    // DSB, DMB, ISB and CLREX have no effect.
+                } else if op & 0b111_1110 == 0b011_1110 {
+                    self.regs[((hw2 >> 8) & 0xF) as usize] = self.special_register(hw2 & 0xFF);
+                } else {
+                    return Err(Fault::UndefinedInstruction(instruction));
+                }
+            }
+            // B, BL
+            0b001 | 0b101 => {
+                let i1 = !(j1 ^ s) & 1;
+                let i2 = !(j2 ^ s) & 1;
+                let offset = sign_extend(
+                    (s << 24)
+                        | (i1 << 23)
+                        | (i2 << 22)
+                        | ((hw1 & 0x3FF) << 12)
+                        | ((hw2 & 0x7FF) << 1),
+                    25,
+                );
+
+                if hw2 & (1 << 14) != 0 {
+                    self.regs[14] = pc.wrapping_add(4) | 1;
+                }
+
+                self.regs[15] = pc.wrapping_add(4).wrapping_add(offset);
+            }
+            _ => return Err(Fault::UndefinedInstruction(instruction)),
+        }
+
+        Ok(Step::Executed)
+    }
+
+    fn special_register(&self, sysm: u32) -> u32 {
+        match sysm {
+            0..=7 => self.apsr,
+            8 => self.msp(),
+            9 => self.psp(),
+            16 => self.primask,
+            17 | 18 => self.basepri,
+            19 => self.faultmask,
+            20 => self.control,
+            _ => 0,
+        }
+    }
+
+    fn move_to_special_register(&mut self, sysm: u32, mask: u32, value: u32) {
+        match sysm {
+            0..=3 if mask & 0b10 != 0 => self.apsr = value & 0xF800_0000,
+            8 => self.set_msp(value),
+            9 => self.set_psp(value),
+            16 => self.primask = value & 1,
+            17 => self.basepri = value & 0xFF,
+            18 => {
+                let value = value & 0xFF;
+                if value != 0 && (value < self.basepri || self.basepri == 0) {
+                    self.basepri = value;
+                }
+            }
+            19 => self.faultmask = value & 1,
+            20 => self.set_control(value),
+            _ => {}
+        }
+    }
+
+    fn load_store_single(
+        &mut self,
+        instruction: u32,
+        pc: u32,
+        bus: &mut impl Bus,
+    ) -> Result<Step, Fault> {
+        let hw1 = instruction >> 16;
+        let hw2 = instruction & 0xFFFF;
+        let rn = hw1 & 0xF;
+        let rt = hw2 >> 12;
+
+        let load = hw1 & (1 << 4) != 0;
+        let signed = hw1 & (1 << 8) != 0;
+        let size = (hw1 >> 5) & 0b11;
+
+        if size == 0b11 || (signed && (!load || size == 0b10)) {
+            return Err(Fault::UndefinedInstruction(instruction));
+        }
+
+        let (address, writeback) = if rn == 15 {
+            if !load {
+                return Err(Fault::UndefinedInstruction(instruction));
+            }
+
+            let base = align(pc.wrapping_add(4));
+            if hw1 & (1 << 7) != 0 {
+                (base.wrapping_add(hw2 & 0xFFF), None)
+            } else {
+                (base.wrapping_sub(hw2 & 0xFFF), None)
+            }
+        } else if hw1 & (1 << 7) != 0 {
+            (self.regs[rn as usize].wrapping_add(hw2 & 0xFFF), None)
+        } else if hw2 & (1 << 11) != 0 {
+            let imm = hw2 & 0xFF;
+            let base = self.regs[rn as usize];
+
+            let offset_address = if hw2 & (1 << 9) != 0 {
+                base.wrapping_add(imm)
+            } else {
+                base.wrapping_sub(imm)
+            };
+
+            let address = if hw2 & (1 << 10) != 0 {
+                offset_address
+            } else {
+                base
+            };
+
+            (
+                address,
+                Some(offset_address).filter(|_| hw2 & (1 << 8) != 0),
+            )
+        } else if (hw2 >> 6) & 0x3F == 0 {
+            let offset = self.regs[(hw2 & 0xF) as usize] << ((hw2 >> 4) & 0b11);
+
+            (self.regs[rn as usize].wrapping_add(offset), None)
+        } else {
+            return Err(Fault::UndefinedInstruction(instruction));
+        };
+
+        if load {
+            // Byte and halfword loads into the PC are preload hints.
+            if rt == 15 && size != 0b10 {
+                return Ok(Step::Executed);
+            }
+
+            let value = match (size, signed) {
+                (0b00, false) => u32::from(bus.read_8(address)),
+                (0b00, true) => bus.read_8(address) as i8 as u32,
+                (0b01, false) => u32::from(bus.read_16(address)),
+                (0b01, true) => bus.read_16(address) as i16 as u32,
+                _ => bus.read_32(address),
+            };
+
+            if let Some(writeback) = writeback {
+                self.regs[rn as usize] = writeback;
+            }
+
+            if rt == 15 {
+                self.bx_write_pc(value)?;
+            } else {
+                self.regs[rt as usize] = value;
+            }
+        } else {
+            let value = self.regs[rt as usize];
+
+            match size {
+                0b00 => bus.write_8(address, value as u8),
+                0b01 => bus.write_16(address, value as u16),
+                _ => bus.write_32(address, value),
+            }
+
+            if let Some(writeback) = writeback {
+                self.regs[rn as usize] = writeback;
+            }
+        }
+
+        Ok(Step::Executed)
+    }
+
+    fn data_processing_register(&mut self, instruction: u32) -> Result<Step, Fault> {
+        let hw1 = instruction >> 16;
+        let hw2 = instruction & 0xFFFF;
+        let rn = hw1 & 0xF;
+        let rd = ((hw2 >> 8) & 0xF) as usize;
+        let m = self.regs[(hw2 & 0xF) as usize];
+        let op1 = (hw1 >> 4) & 0xF;
+        let op2 = (hw2 >> 4) & 0xF;
+
+        if op1 & 0b1000 == 0 && op2 == 0 {
+            // LSL, LSR, ASR, ROR (register)
+            let shift = match (op1 >> 1) & 0b11 {
+                0b00 => Shift::Lsl,
+                0b01 => Shift::Lsr,
+                0b10 => Shift::Asr,
+                _ => Shift::Ror,
+            };
+
+            let (result, carry) = shift_c(self.regs[rn as usize], shift, m & 0xFF, self.carry());
+
+            self.regs[rd] = result;
+            if op1 & 1 != 0 {
+                self.set_nzc(result, carry);
+            }
+        } else if op1 & 0b1000 == 0 && op2 & 0b1000 != 0 {
+            // SXTAH, SXTH, UXTAH, UXTH, SXTAB, SXTB, UXTAB, UXTB
+            let rotated = m.rotate_right(((hw2 >> 4) & 0b11) * 8);
+            let extended = match op1 {
+                0b0000 => rotated as i16 as u32,
+                0b0001 => rotated & 0xFFFF,
+                0b0100 => rotated as i8 as u32,
+                0b0101 => rotated & 0xFF,
+                _ => return Err(Fault::UndefinedInstruction(instruction)),
+            };
+
+            self.regs[rd] = if rn == 15 {
+                extended
+            } else {
+                self.regs[rn as usize].wrapping_add(extended)
+            };
+        } else if op1 & 0b1100 == 0b1000 && op2 & 0b1100 == 0b1000 {
+            self.regs[rd] = match (op1 & 0b11, op2 & 0b11) {
+                (0b01, 0b00) => m.swap_bytes(),
+                (0b01, 0b01) => rev16(m),
+                (0b01, 0b10) => m.reverse_bits(),
+                (0b01, 0b11) => (m as u16).swap_bytes() as i16 as u32,
+                (0b11, 0b00) => m.leading_zeros(),
+                _ => return Err(Fault::UndefinedInstruction(instruction)),
+            };
+        } else {
+            return Err(Fault::UndefinedInstruction(instruction));
+        }
+
+        Ok(Step::Executed)
+    }
+
+    fn multiply(&mut self, instruction: u32) -> Result<Step, Fault> {
+        let hw1 = instruction >> 16;
+        let hw2 = instruction & 0xFFFF;
+        let ra = hw2 >> 12;
+        let rd = ((hw2 >> 8) & 0xF) as usize;
+
+        let n = self.regs[(hw1 & 0xF) as usize];
+        let m = self.regs[(hw2 & 0xF) as usize];
+
+        if (hw1 >> 4) & 0b111 != 0 {
+            return Err(Fault::UndefinedInstruction(instruction));
+        }
+
+        self.regs[rd] = match (hw2 >> 4) & 0b11 {
+            // MUL
+            0b00 if ra == 15 => n.wrapping_mul(m),
+            // MLA
+            0b00 => self.regs[ra as usize].wrapping_add(n.wrapping_mul(m)),
+            // MLS
+            0b01 => self.regs[ra as usize].wrapping_sub(n.wrapping_mul(m)),
+            _ => return Err(Fault::UndefinedInstruction(instruction)),
+        };
+
+        Ok(Step::Executed)
+    }
+
+    fn long_multiply_divide(&mut self, instruction: u32) -> Result<Step, Fault> {
+        let hw1 = instruction >> 16;
+        let hw2 = instruction & 0xFFFF;
+        let rd_lo = (hw2 >> 12) as usize;
+        let rd_hi = ((hw2 >> 8) & 0xF) as usize;
+
+        let n = self.regs[(hw1 & 0xF) as usize];
+        let m = self.regs[(hw2 & 0xF) as usize];
+        let accumulator = (u64::from(self.regs[rd_hi]) << 32) | u64::from(self.regs[rd_lo]);
+
+        let result = match ((hw1 >> 4) & 0b111, (hw2 >> 4) & 0xF) {
+            // SMULL
+            (0b000, 0b0000) => (i64::from(n as i32) * i64::from(m as i32)) as u64,
+            // UMULL
+            (0b010, 0b0000) => u64::from(n) * u64::from(m),
+            // SMLAL
+            (0b100, 0b0000) => {
+                accumulator.wrapping_add((i64::from(n as i32) * i64::from(m as i32)) as u64)
+            }
+            // UMLAL
+            (0b110, 0b0000) => accumulator.wrapping_add(u64::from(n) * u64::from(m)),
+            // SDIV. Division by zero results in zero, unless DIV_0_TRP is set.
+            (0b001, 0b1111) => {
+                self.regs[rd_hi] = if m == 0 {
+                    0
+                } else {
+                    (n as i32).wrapping_div(m as i32) as u32
+                };
+
+                return Ok(Step::Executed);
+            }
+            // UDIV
+            (0b011, 0b1111) => {
+                self.regs[rd_hi] = n.checked_div(m).unwrap_or(0);
+
+                return Ok(Step::Executed);
+            }
+            _ => return Err(Fault::UndefinedInstruction(instruction)),
+        };
+
+        self.regs[rd_lo] = result as u32;
+        self.regs[rd_hi] = (result >> 32) as u32;
+
+        Ok(Step::Executed)
+    }
+}
+
+fn hint(hint: u32) -> Step {
+    match hint {
+        // WFE, WFI
+        0b0010 | 0b0011 => Step::Sleep,
+        _ => Step::Executed,
+    }
+}
+
+/// The registers in a register list, in ascending order.
+fn registers(list: u32) -> impl Iterator<Item = usize> {
+    (0..16).filter(move |register| list & (1 << register) != 0)
+}
+
+fn align(address: u32) -> u32 {
+    address & !0b11
+}
+
+fn rev16(value: u32) -> u32 {
+    ((value & 0x00FF_00FF) << 8) | ((value >> 8) & 0x00FF_00FF)
+}
+
+fn sign_extend(value: u32, bits: u32) -> u32 {
+    let shift = 32 - bits;
+    (((value << shift) as i32) >> shift) as u32
+}
+
+fn add_with_carry(x: u32, y: u32, carry_in: bool) -> (u32, bool, bool) {
+    let unsigned = u64::from(x) + u64::from(y) + u64::from(carry_in);
+    let signed = i64::from(x as i32) + i64::from(y as i32) + i64::from(carry_in);
+    let result = unsigned as u32;
+
+    (
+        result,
+        u64::from(result) != unsigned,
+        i64::from(result as i32) != signed,
+    )
+}
+
+fn decode_imm_shift(shift_type: u32, imm5: u32) -> (Shift, u32) {
+    let amount = if imm5 == 0 { 32 } else { imm5 };
+
+    match shift_type {
+        0b00 => (Shift::Lsl, imm5),
+        0b01 => (Shift::Lsr, amount),
+        0b10 => (Shift::Asr, amount),
+        _ if imm5 == 0 => (Shift::Rrx, 1),
+        _ => (Shift::Ror, imm5),
+    }
+}
+
+fn shift_c(value: u32, shift: Shift, amount: u32, carry_in: bool) -> (u32, bool) {
+    if amount == 0 && shift != Shift::Rrx {
+        return (value, carry_in);
+    }
+
+    match shift {
+        Shift::Lsl => match amount {
+            1..=31 => (value << amount, (value >> (32 - amount)) & 1 != 0),
+            32 => (0, value & 1 != 0),
+            _ => (0, false),
+        },
+        Shift::Lsr => match amount {
+            1..=31 => (value >> amount, (value >> (amount - 1)) & 1 != 0),
+            32 => (0, value >> 31 != 0),
+            _ => (0, false),
+        },
+        Shift::Asr => {
+            if amount < 32 {
+                (
+                    ((value as i32) >> amount) as u32,
+                    (value >> (amount - 1)) & 1 != 0,
+                )
+            } else {
+                let result = ((value as i32) >> 31) as u32;
+                (result, result & 1 != 0)
+            }
+        }
+        Shift::Ror => {
+            let result = value.rotate_right(amount % 32);
+            (result, result >> 31 != 0)
+        }
+        Shift::Rrx => ((u32::from(carry_in) << 31) | (value >> 1), value & 1 != 0),
+    }
+}
+
+fn thumb_expand_imm_c(imm12: u32, carry_in: bool) -> (u32, bool) {
+    if imm12 >> 10 == 0 {
+        let byte = imm12 & 0xFF;
+
+        let value = match (imm12 >> 8) & 0b11 {
+            0b00 => byte,
+            0b01 => (byte << 16) | byte,
+            0b10 => (byte << 24) | (byte << 8),
+            _ => byte * 0x0101_0101,
+        };
+
+        (value, carry_in)
+    } else {
+        let value = (0x80 | (imm12 & 0x7F)).rotate_right(imm12 >> 7);
+
+        (value, value >> 31 != 0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Bus, Cpu, Fault, Step};
+
+    struct Ram(Vec<u8>);
+
+    impl Bus for Ram {
+        fn read_8(&mut self, address: u32) -> u8 {
+            self.0[address as usize]
+        }
+
+        fn read_16(&mut self, address: u32) -> u16 {
+            u16::from(self.read_8(address)) | u16::from(self.read_8(address + 1)) << 8
+        }
+
+        fn read_32(&mut self, address: u32) -> u32 {
+            u32::from(self.read_16(address)) | u32::from(self.read_16(address + 2)) << 16
+        }
+
+        fn write_8(&mut self, address: u32, value: u8) {
+            self.0[address as usize] = value;
+        }
+
+        fn write_16(&mut self, address: u32, value: u16) {
+            self.0[address as usize..][..2].copy_from_slice(&value.to_le_bytes());
+        }
+
+        fn write_32(&mut self, address: u32, value: u32) {
+            self.0[address as usize..][..4].copy_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    fn load(code: &[u16]) -> (Cpu, Ram) {
+        let mut ram = Ram(vec![0; 0x400]);
+
+        for (i, halfword) in code.iter().enumerate() {
+            ram.write_16(2 * i as u32, *halfword);
+        }
+
+        (Cpu::reset(0x400, 0x1), ram)
+    }
+
+    fn run(cpu: &mut Cpu, ram: &mut Ram) -> Step {
+        for _ in 0..1000 {
+            match cpu.step(ram).unwrap() {
+                Step::Executed => {}
+                step => return step,
+            }
+        }
+
+        panic!("The program did not finish");
+    }
+
+    #[test]
+    fn sum_loop() {
+        let (mut cpu, mut ram) = load(&[
+            0x2000, // movs r0, #0
+            0x210a, // movs r1, #10
+            0x1840, // adds r0, r0, r1
+            0x3901, // subs r1, #1
+            0xd1fc, // bne.n 4
+            0xbe00, // bkpt 0x0000
+        ]);
+
+        assert_eq!(run(&mut cpu, &mut ram), Step::Breakpoint);
+        assert_eq!(cpu.regs[0], 55);
+        assert_eq!(cpu.pc(), 0xA);
+    }
+
+    #[test]
+    fn call_and_return() {
+        let (mut cpu, mut ram) = load(&[
+            0xb500, // push {lr}
+            0xf000, 0xf803, // bl 0xc
+            0xbc02, // pop {r1}
+            0xbe00, // bkpt 0x0000
+            0x0000, // movs r0, r0
+            0xf240, 0x1023, // movw r0, #0x123
+            0xf2c4, 0x5067, // movt r0, #0x4567
+            0x4770, // bx lr
+        ]);
+
+        assert_eq!(run(&mut cpu, &mut ram), Step::Breakpoint);
+        assert_eq!(cpu.regs[0], 0x4567_0123);
+        assert_eq!(cpu.regs[1], 0xFFFF_FFFF);
+        assert_eq!(cpu.regs[13], 0x400);
+    }
+
+    #[test]
+    fn it_block_and_division() {
+        let (mut cpu, mut ram) = load(&[
+            0x2064, // movs r0, #100
+            0x2107, // movs r1, #7
+            0xfbb0, 0xf2f1, // udiv r2, r0, r1
+            0x2a0e, // cmp r2, #14
+            0xbf0c, // ite eq
+            0x2301, // moveq r3, #1
+            0x2302, // movne r3, #2
+            0xbe00, // bkpt 0x0000
+        ]);
+
+        assert_eq!(run(&mut cpu, &mut ram), Step::Breakpoint);
+        assert_eq!(cpu.regs[2], 14);
+        assert_eq!(cpu.regs[3], 1);
+    }
+
+    #[test]
+    fn undefined_instruction_faults() {
+        let (mut cpu, mut ram) = load(&[
+            0xde00, // udf #0
+        ]);
+
+        assert_eq!(cpu.step(&mut ram), Err(Fault::UndefinedInstruction(0xde00)));
+        assert_eq!(cpu.pc(), 0);
+    }
+}
@@ -159,6 +159,16 @@ pub enum SwdFrequencyToDelayCount {
 }
 
 impl SwdFrequencyToDelayCount {
+    /// All available settings.
+    pub(crate) const ALL: [Self; 12] = {
+        use SwdFrequencyToDelayCount::*;
+
+        [
+            Hz4600000, Hz1800000, Hz1200000, Hz950000, Hz650000, Hz480000, Hz400000, Hz360000,
+            Hz240000, Hz150000, Hz125000, Hz100000,
+        ]
+    };
+
     /// Try to find an appropriate setting for the given frequency in kHz.
     ///
     /// If a direct match is not found, return the setting for a lower frequency
@@ -220,6 +230,15 @@ pub enum JTagFrequencyToDivider {
 }
 
 impl JTagFrequencyToDivider {
+    /// All available settings.
+    pub(crate) const ALL: [Self; 8] = {
+        use JTagFrequencyToDivider::*;
+
+        [
+            Hz18000000, Hz9000000, Hz4500000, Hz2250000, Hz1120000, Hz560000, Hz280000, Hz140000,
+        ]
+    };
+
     /// Try to find an appropriate setting for the given frequency in kHz.
     ///
     /// If a direct match is not found, return the setting for a higher frequency
@@ -2,8 +2,10 @@ pub mod constants;
 pub mod tools;
 mod usb_interface;
 
-use self::usb_interface::{StLinkUsb, StLinkUsbDevice};
-use super::{DebugProbe, DebugProbeError, ProbeCreationError, WireProtocol};
+use self::usb_interface::{is_device_lost, StLinkUsb, StLinkUsbDevice};
+use super::{
+    DebugProbe, DebugProbeError, ProbeCreationError, ReconnectPolicy, SupportedSpeeds, WireProtocol,
+};
 use crate::{
     architecture::arm::{
         ap::{valid_access_ports, AccessPort, ApAccess, ApClass, MemoryAp, IDR},
@@ -16,7 +18,7 @@ use crate::{
         ApAddress, ApInformation, ArmChipInfo, DapAccess, DpAddress, Pins, SwoAccess, SwoConfig,
         SwoMode,
     },
-    DebugProbeSelector, Error as ProbeRsError, Memory, Probe,
+    DebugProbeSelector, Error as ProbeRsError, Memory, Probe, ProtocolTracer, TraceEvent,
 };
 use anyhow::anyhow;
 use constants::{commands, JTagFrequencyToDivider, Mode, Status, SwdFrequencyToDelayCount};
@@ -37,6 +39,15 @@ const STLINK_MAX_WRITE_LEN: usize = 0xFFFC;
 
 const DP_PORT: u16 = 0xFFFF;
 
+/// Size of the buffer of the ST-Link for SWO data, in bytes.
+const STLINK_SWO_BUFFER_SIZE: u16 = 4096;
+
+/// Maximum SWO baud rate of the ST-Link V2.
+const STLINK_SWO_MAX_BAUD: u32 = 2_250_000;
+
+/// Maximum SWO baud rate of the ST-Link V3.
+const STLINK_V3_SWO_MAX_BAUD: u32 = 24_000_000;
+
 #[derive(Debug)]
 pub struct StLink<D: StLinkUsb> {
     device: D,
@@ -50,13 +61,21 @@ pub struct StLink<D: StLinkUsb> {
 
     /// List of opened APs
     opened_aps: Vec<u8>,
+
+    /// Whether the probe is attached to the target, to attach again after reconnecting.
+    attached: bool,
+    /// The configuration of the running SWO reception, to start it again after reconnecting.
+    swo_config: Option<SwoConfig>,
+    reconnect_policy: ReconnectPolicy,
+    /// Set while reconnecting, so that a lost probe is not reconnected recursively.
+    reconnecting: bool,
+
+    tracer: Option<ProtocolTracer>,
 }
 
-impl DebugProbe for StLink<StLinkUsbDevice> {
-    fn new_from_selector(
-        selector: impl Into<DebugProbeSelector>,
-    ) -> Result<Box<Self>, DebugProbeError> {
-        let device = StLinkUsbDevice::new_from_selector(selector)?;
+impl StLink<StLinkUsbDevice> {
+    /// Initialize the ST-Link connected as the USB `device`.
+    pub(crate) fn new_from_device(device: StLinkUsbDevice) -> Result<Box<Self>, DebugProbeError> {
         let mut stlink = Self {
             name: format!("ST-Link {}", &device.info.version_name),
             device,
@@ -68,6 +87,13 @@ impl DebugProbe for StLink<StLinkUsbDevice> {
             swo_enabled: false,
 
             opened_aps: vec![],
+
+            attached: false,
+            swo_config: None,
+            reconnect_policy: ReconnectPolicy::default(),
+            reconnecting: false,
+
+            tracer: None,
         };
 
         stlink.init()?;
@@ -75,6 +101,33 @@ impl DebugProbe for StLink<StLinkUsbDevice> {
         Ok(Box::new(stlink))
     }
 
+    /// Initialize the ST-Link with the USB device `handle`, which was opened outside of
+    /// probe-rs.
+    pub(crate) fn new_from_handle(
+        handle: rusb::DeviceHandle<rusb::Context>,
+    ) -> Result<Box<Self>, DebugProbeError> {
+        let descriptor = handle
+            .device()
+            .device_descriptor()
+            .map_err(ProbeCreationError::from)?;
+
+        let selector = DebugProbeSelector {
+            vendor_id: descriptor.vendor_id(),
+            product_id: descriptor.product_id(),
+            serial_number: None,
+        };
+
+        Self::new_from_device(StLinkUsbDevice::new_from_handle(handle, selector)?)
+    }
+}
+
+impl DebugProbe for StLink<StLinkUsbDevice> {
+    fn new_from_selector(
+        selector: impl Into<DebugProbeSelector>,
+    ) -> Result<Box<Self>, DebugProbeError> {
+        Self::new_from_device(StLinkUsbDevice::new_from_selector(selector)?)
+    }
+
     fn get_name(&self) -> &str {
         &self.name
     }
@@ -87,71 +140,42 @@ impl DebugProbe for StLink<StLinkUsbDevice> {
     }
 
     fn set_speed(&mut self, speed_khz: u32) -> Result<u32, DebugProbeError> {
-        match self.hw_version.cmp(&3) {
-            Ordering::Less => match self.protocol {
-                WireProtocol::Swd => {
-                    let actual_speed = SwdFrequencyToDelayCount::find_setting(speed_khz);
-
-                    if let Some(actual_speed) = actual_speed {
-                        self.set_swd_frequency(actual_speed)?;
-
-                        self.swd_speed_khz = actual_speed.to_khz();
-
-                        Ok(actual_speed.to_khz())
-                    } else {
-                        Err(DebugProbeError::UnsupportedSpeed(speed_khz))
-                    }
-                }
-                WireProtocol::Jtag => {
-                    let actual_speed = JTagFrequencyToDivider::find_setting(speed_khz);
-
-                    if let Some(actual_speed) = actual_speed {
-                        self.set_jtag_frequency(actual_speed)?;
-
-                        self.jtag_speed_khz = actual_speed.to_khz();
-
-                        Ok(actual_speed.to_khz())
-                    } else {
-                        Err(DebugProbeError::UnsupportedSpeed(speed_khz))
-                    }
-                }
-            },
-            Ordering::Equal => {
-                let (available, _) = self.get_communication_frequencies(self.protocol)?;
+        self.configure_speed(speed_khz)
+    }
+
+    fn supported_speeds(&mut self) -> Result<SupportedSpeeds, DebugProbeError> {
+        let speeds = if self.hw_version < 3 {
+            match self.protocol {
+                WireProtocol::Swd => SwdFrequencyToDelayCount::ALL
+                    .iter()
+                    .map(|setting| setting.to_khz())
+                    .collect(),
+                WireProtocol::Jtag => JTagFrequencyToDivider::ALL
+                    .iter()
+                    .map(|setting| setting.to_khz())
+                    .collect(),
+            }
+        } else {
+            self.get_communication_frequencies(self.protocol)?.0
+        };
 
-                let actual_speed_khz = available
-                    .into_iter()
-                    .filter(|speed| *speed <= speed_khz)
-                    .max()
-                    .ok_or(DebugProbeError::UnsupportedSpeed(speed_khz))?;
+        Ok(SupportedSpeeds::Discrete(speeds))
+    }
 
-                self.set_communication_frequency(self.protocol, actual_speed_khz)?;
+    fn set_reconnect_policy(&mut self, policy: ReconnectPolicy) {
+        self.reconnect_policy = policy;
+    }
 
-                match self.protocol {
-                    WireProtocol::Swd => self.swd_speed_khz = actual_speed_khz,
-                    WireProtocol::Jtag => self.jtag_speed_khz = actual_speed_khz,
-                }
+    fn set_protocol_tracer(&mut self, tracer: Option<ProtocolTracer>) {
+        self.tracer = tracer;
+    }
 
-                Ok(actual_speed_khz)
-            }
-            Ordering::Greater => unimplemented!(),
-        }
+    fn protocol_tracer(&self) -> Option<&ProtocolTracer> {
+        self.tracer.as_ref()
     }
 
     fn attach(&mut self) -> Result<(), DebugProbeError> {
         log::debug!("attach({:?})", self.protocol);
-        self.enter_idle()?;
-
-        let param = match self.protocol {
-            WireProtocol::Jtag => {
-                log::debug!("Switching protocol to JTAG");
-                commands::JTAG_ENTER_JTAG_NO_CORE_RESET
-            }
-            WireProtocol::Swd => {
-                log::debug!("Switching protocol to SWD");
-                commands::JTAG_ENTER_SWD
-            }
-        };
 
         // Check and report the target voltage.
         let target_voltage = self
@@ -166,29 +190,8 @@ impl DebugProbe for StLink<StLinkUsbDevice> {
             log::info!("Target voltage (VAPP): {:2.2} V", target_voltage);
         }
 
-        let mut buf = [0; 2];
-        self.send_jtag_command(
-            &[commands::JTAG_COMMAND, commands::JTAG_ENTER2, param, 0],
-            &[],
-            &mut buf,
-            TIMEOUT,
-        )?;
-
-        log::debug!("Successfully initialized SWD.");
-
-        // If the speed is not manually set, the probe will
-        // use whatever speed has been configured before.
-        //
-        // To ensure the default speed is used if not changed,
-        // we set the speed again here.
-        match self.protocol {
-            WireProtocol::Jtag => {
-                self.set_speed(self.jtag_speed_khz)?;
-            }
-            WireProtocol::Swd => {
-                self.set_speed(self.swd_speed_khz)?;
-            }
-        }
+        self.enter_protocol()?;
+        self.attached = true;
 
         Ok(())
     }
@@ -199,6 +202,7 @@ impl DebugProbe for StLink<StLinkUsbDevice> {
             self.disable_swo()
                 .map_err(|e| DebugProbeError::ProbeSpecific(e.into()))?;
         }
+        self.attached = false;
         self.enter_idle()
     }
 
@@ -244,6 +248,38 @@ impl DebugProbe for StLink<StLinkUsbDevice> {
         )
     }
 
+    fn swj_pins(
+        &mut self,
+        pin_out: u32,
+        pin_select: u32,
+        pin_wait: u32,
+    ) -> Result<u32, DebugProbeError> {
+        let mut nreset = Pins(0);
+        nreset.set_nreset(true);
+
+        // Only the reset pin can be driven on ST-Links.
+        if pin_select & !(nreset.0 as u32) != 0 {
+            return Err(DebugProbeError::CommandNotSupportedByProbe("swj_pins"));
+        }
+
+        if Pins(pin_select as u8).nreset() {
+            // nRESET is active low.
+            if Pins(pin_out as u8).nreset() {
+                self.target_reset_deassert()?;
+            } else {
+                self.target_reset_assert()?;
+            }
+
+            // Normally this would be the timeout we pass to the probe to settle the pins.
+            // The ST-Link is not capable of this, so we just wait for this time on the host
+            // and assume it has settled until then.
+            std::thread::sleep(Duration::from_micros(pin_wait as u64));
+        }
+
+        // We signal that we cannot read the pin state.
+        Ok(0xFFFF_FFFF)
+    }
+
     fn select_protocol(&mut self, protocol: WireProtocol) -> Result<(), DebugProbeError> {
         match protocol {
             WireProtocol::Jtag => self.protocol = WireProtocol::Jtag,
@@ -277,8 +313,7 @@ impl DebugProbe for StLink<StLinkUsbDevice> {
 
     fn get_target_voltage(&mut self) -> Result<Option<f32>, DebugProbeError> {
         let mut buf = [0; 8];
-        self.device
-            .write(&[commands::GET_TARGET_VOLTAGE], &[], &mut buf, TIMEOUT)
+        self.usb_write(&[commands::GET_TARGET_VOLTAGE], &[], &mut buf, TIMEOUT)
             .and_then(|_| {
                 // The next two unwraps are safe!
                 let a0 = (&buf[0..4]).pread_with::<u32>(0, LE).unwrap();
@@ -303,40 +338,6 @@ impl<D: StLinkUsb> Drop for StLink<D> {
     }
 }
 
-impl StLink<StLinkUsbDevice> {
-    fn swj_pins(
-        &mut self,
-        pin_out: u32,
-        pin_select: u32,
-        pin_wait: u32,
-    ) -> Result<u32, ProbeRsError> {
-        let mut nreset = Pins(0);
-        nreset.set_nreset(true);
-        let nreset_mask = !(nreset.0 as u32);
-
-        // If only the reset pin is selected we perform the reset.
-        // If something else is selected return an error as this is not supported on ST-Links.
-        if pin_select & nreset_mask != 0 {
-            if Pins(pin_out as u8).nreset() {
-                self.target_reset_assert()?;
-            } else {
-                self.target_reset_deassert()?;
-            }
-
-            // Normally this would be the timeout we pass to the probe to settle the pins.
-            // The J-Link is not capable of this, so we just wait for this time on the host
-            // and assume it has settled until then.
-            std::thread::sleep(Duration::from_micros(pin_wait as u64));
-
-            // We signal that we cannot read the pin state.
-            Ok(0xFFFF_FFFF)
-        } else {
-            // This is not supported for ST-Links, unfortunately.
-            Err(DebugProbeError::CommandNotSupportedByProbe("swj_pins").into())
-        }
-    }
-}
-
 impl<D: StLinkUsb> StLink<D> {
     /// Maximum number of bytes to send or receive for 32- and 16- bit transfers.
     ///
@@ -354,12 +355,191 @@ impl<D: StLinkUsb> StLink<D> {
     /// Firmware version that adds multiple AP support.
     const MIN_JTAG_VERSION_MULTI_AP: u8 = 28;
 
+    /// Set the speed of the current protocol, see [`DebugProbe::set_speed`].
+    fn configure_speed(&mut self, speed_khz: u32) -> Result<u32, DebugProbeError> {
+        match self.hw_version.cmp(&3) {
+            Ordering::Less => match self.protocol {
+                WireProtocol::Swd => {
+                    let actual_speed = SwdFrequencyToDelayCount::find_setting(speed_khz);
+
+                    if let Some(actual_speed) = actual_speed {
+                        self.set_swd_frequency(actual_speed)?;
+
+                        self.swd_speed_khz = actual_speed.to_khz();
+
+                        Ok(actual_speed.to_khz())
+                    } else {
+                        Err(DebugProbeError::UnsupportedSpeed(speed_khz))
+                    }
+                }
+                WireProtocol::Jtag => {
+                    let actual_speed = JTagFrequencyToDivider::find_setting(speed_khz);
+
+                    if let Some(actual_speed) = actual_speed {
+                        self.set_jtag_frequency(actual_speed)?;
+
+                        self.jtag_speed_khz = actual_speed.to_khz();
+
+                        Ok(actual_speed.to_khz())
+                    } else {
+                        Err(DebugProbeError::UnsupportedSpeed(speed_khz))
+                    }
+                }
+            },
+            Ordering::Equal => {
+                let (available, _) = self.get_communication_frequencies(self.protocol)?;
+
+                let actual_speed_khz = available
+                    .into_iter()
+                    .filter(|speed| *speed <= speed_khz)
+                    .max()
+                    .ok_or(DebugProbeError::UnsupportedSpeed(speed_khz))?;
+
+                self.set_communication_frequency(self.protocol, actual_speed_khz)?;
+
+                match self.protocol {
+                    WireProtocol::Swd => self.swd_speed_khz = actual_speed_khz,
+                    WireProtocol::Jtag => self.jtag_speed_khz = actual_speed_khz,
+                }
+
+                Ok(actual_speed_khz)
+            }
+            Ordering::Greater => unimplemented!(),
+        }
+    }
+
+    /// Switch the ST-Link to the current protocol, see [`DebugProbe::attach`].
+    fn enter_protocol(&mut self) -> Result<(), DebugProbeError> {
+        self.enter_idle()?;
+
+        let param = match self.protocol {
+            WireProtocol::Jtag => {
+                log::debug!("Switching protocol to JTAG");
+                commands::JTAG_ENTER_JTAG_NO_CORE_RESET
+            }
+            WireProtocol::Swd => {
+                log::debug!("Switching protocol to SWD");
+                commands::JTAG_ENTER_SWD
+            }
+        };
+
+        let mut buf = [0; 2];
+        self.send_jtag_command(
+            &[commands::JTAG_COMMAND, commands::JTAG_ENTER2, param, 0],
+            &[],
+            &mut buf,
+            TIMEOUT,
+        )?;
+
+        log::debug!("Successfully initialized SWD.");
+
+        // If the speed is not manually set, the probe will
+        // use whatever speed has been configured before.
+        //
+        // To ensure the default speed is used if not changed,
+        // we set the speed again here.
+        match self.protocol {
+            WireProtocol::Jtag => {
+                self.configure_speed(self.jtag_speed_khz)?;
+            }
+            WireProtocol::Swd => {
+                self.configure_speed(self.swd_speed_khz)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Send a command to the ST-Link, see [`StLinkUsb::write`].
+    ///
+    /// If the ST-Link was lost from USB, it is reconnected according to the reconnect
+    /// policy, and the command is sent again.
+    fn usb_write(
+        &mut self,
+        cmd: &[u8],
+        write_data: &[u8],
+        read_data: &mut [u8],
+        timeout: Duration,
+    ) -> Result<(), DebugProbeError> {
+        if let Some(tracer) = &self.tracer {
+            tracer.record(TraceEvent::Command([cmd, write_data].concat()));
+        }
+
+        let result = match self.device.write(cmd, write_data, read_data, timeout) {
+            Err(error)
+                if is_device_lost(&error)
+                    && !self.reconnecting
+                    && self.reconnect_policy.attempts > 0 =>
+            {
+                log::warn!("The ST-Link was lost from USB, reconnecting");
+                self.reconnect()?;
+
+                self.device.write(cmd, write_data, read_data, timeout)
+            }
+            result => result,
+        };
+
+        if let (Some(tracer), Ok(())) = (&self.tracer, &result) {
+            tracer.record(TraceEvent::Response(read_data.to_vec()));
+        }
+
+        result
+    }
+
+    /// Open the ST-Link again after it was lost from USB, and restore the state of the
+    /// connection: the protocol, the speed, the opened APs and the SWO reception.
+    fn reconnect(&mut self) -> Result<(), DebugProbeError> {
+        self.reconnecting = true;
+        let result = self.restore_connection();
+        self.reconnecting = false;
+
+        result
+    }
+
+    fn restore_connection(&mut self) -> Result<(), DebugProbeError> {
+        let mut attempt = 0;
+
+        loop {
+            std::thread::sleep(self.reconnect_policy.delay);
+            attempt += 1;
+
+            match self.device.reopen() {
+                Ok(()) => break,
+                Err(error) if attempt < self.reconnect_policy.attempts => {
+                    log::debug!("Failed to reconnect to the ST-Link: {}", error);
+                }
+                Err(error) => return Err(error),
+            }
+        }
+
+        // The speeds are reset by `init` to the ones of the probe.
+        let (swd_speed_khz, jtag_speed_khz) = (self.swd_speed_khz, self.jtag_speed_khz);
+        self.init()?;
+        self.swd_speed_khz = swd_speed_khz;
+        self.jtag_speed_khz = jtag_speed_khz;
+
+        if self.attached {
+            self.enter_protocol()?;
+        }
+
+        for ap in std::mem::take(&mut self.opened_aps) {
+            self.select_ap(ap)?;
+        }
+
+        if let Some(config) = self.swo_config {
+            self.start_trace_reception(&config)?;
+        }
+
+        log::info!("Reconnected to the ST-Link");
+
+        Ok(())
+    }
+
     /// Get the current mode of the ST-Link
     fn get_current_mode(&mut self) -> Result<Mode, DebugProbeError> {
         log::trace!("Getting current mode of device...");
         let mut buf = [0; 2];
-        self.device
-            .write(&[commands::GET_CURRENT_MODE], &[], &mut buf, TIMEOUT)?;
+        self.usb_write(&[commands::GET_CURRENT_MODE], &[], &mut buf, TIMEOUT)?;
 
         use Mode::*;
 
@@ -382,13 +562,13 @@ impl<D: StLinkUsb> StLink<D> {
         let mode = self.get_current_mode()?;
 
         match mode {
-            Mode::Dfu => self.device.write(
+            Mode::Dfu => self.usb_write(
                 &[commands::DFU_COMMAND, commands::DFU_EXIT],
                 &[],
                 &mut [],
                 TIMEOUT,
             ),
-            Mode::Swim => self.device.write(
+            Mode::Swim => self.usb_write(
                 &[commands::SWIM_COMMAND, commands::SWIM_EXIT],
                 &[],
                 &mut [],
@@ -414,8 +594,7 @@ impl<D: StLinkUsb> StLink<D> {
         //   Byte 2-3: ST_VID
         //   Byte 4-5: STLINK_PID
         let mut buf = [0; 6];
-        self.device
-            .write(&[commands::GET_VERSION], &[], &mut buf, TIMEOUT)
+        self.usb_write(&[commands::GET_VERSION], &[], &mut buf, TIMEOUT)
             .map(|_| {
                 let version: u16 = (&buf[0..2]).pread_with(0, BE).unwrap();
                 self.hw_version = (version >> HW_VERSION_SHIFT) as u8 & HW_VERSION_MASK;
@@ -654,7 +833,7 @@ impl<D: StLinkUsb> StLink<D> {
         timeout: Duration,
     ) -> Result<(), DebugProbeError> {
         for attempt in 0..13 {
-            self.device.write(cmd, write_data, read_data, timeout)?;
+            self.usb_write(cmd, write_data, read_data, timeout)?;
 
             match Status::from(read_data[0]) {
                 Status::JtagOk => return Ok(()),
@@ -682,8 +861,22 @@ impl<D: StLinkUsb> StLink<D> {
     }
 
     pub fn start_trace_reception(&mut self, config: &SwoConfig) -> Result<(), DebugProbeError> {
+        let max_baud = if self.hw_version < 3 {
+            STLINK_SWO_MAX_BAUD
+        } else {
+            STLINK_V3_SWO_MAX_BAUD
+        };
+
+        if config.baud() > max_baud {
+            return Err(StlinkError::SwoBaudTooHigh {
+                baud: config.baud(),
+                max: max_baud,
+            }
+            .into());
+        }
+
         let mut buf = [0; 2];
-        let bufsize = 4096u16.to_le_bytes();
+        let bufsize = STLINK_SWO_BUFFER_SIZE.to_le_bytes();
         let baud = config.baud().to_le_bytes();
         let mut command = vec![commands::JTAG_COMMAND, commands::SWO_START_TRACE_RECEPTION];
         command.extend_from_slice(&bufsize);
@@ -692,6 +885,7 @@ impl<D: StLinkUsb> StLink<D> {
         self.send_jtag_command(&command, &[], &mut buf, TIMEOUT)?;
 
         self.swo_enabled = true;
+        self.swo_config = Some(*config);
 
         Ok(())
     }
@@ -707,6 +901,7 @@ impl<D: StLinkUsb> StLink<D> {
         )?;
 
         self.swo_enabled = false;
+        self.swo_config = None;
 
         Ok(())
     }
@@ -714,7 +909,7 @@ impl<D: StLinkUsb> StLink<D> {
     /// Gets the SWO count from the ST-Link probe.
     fn read_swo_available_byte_count(&mut self) -> Result<usize, DebugProbeError> {
         let mut buf = [0; 2];
-        self.device.write(
+        self.usb_write(
             &[
                 commands::JTAG_COMMAND,
                 commands::SWO_GET_TRACE_NEW_RECORD_NB,
@@ -727,10 +922,30 @@ impl<D: StLinkUsb> StLink<D> {
     }
 
     /// Reads the actual data from the SWO buffer on the ST-Link.
+    ///
+    /// Waits up to `timeout` for data, and returns an empty buffer if there is none.
     fn read_swo_data(&mut self, timeout: Duration) -> Result<Vec<u8>, DebugProbeError> {
+        let start = std::time::Instant::now();
+
         // The byte count always needs to be polled first, otherwise
         // the ST-Link won't return any data.
-        let mut buf = vec![0; self.read_swo_available_byte_count()?];
+        let count = loop {
+            let count = self.read_swo_available_byte_count()?;
+
+            if count > 0 || start.elapsed() >= timeout {
+                break count;
+            }
+
+            std::thread::sleep(
+                Duration::from_millis(1).min(timeout.saturating_sub(start.elapsed())),
+            );
+        };
+
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut buf = vec![0; count];
         let bytes_read = self.device.read_swo(&mut buf, timeout)?;
         buf.truncate(bytes_read);
         Ok(buf)
@@ -834,7 +1049,7 @@ impl<D: StLinkUsb> StLink<D> {
         let data_length = data.len().to_le_bytes();
 
         let addbytes = address.to_le_bytes();
-        self.device.write(
+        self.usb_write(
             &[
                 commands::JTAG_COMMAND,
                 commands::JTAG_READMEM_32BIT,
@@ -890,7 +1105,7 @@ impl<D: StLinkUsb> StLink<D> {
 
         let addbytes = address.to_le_bytes();
         let lenbytes = length.to_le_bytes();
-        self.device.write(
+        self.usb_write(
             &[
                 commands::JTAG_COMMAND,
                 commands::JTAG_READMEM_8BIT,
@@ -945,7 +1160,7 @@ impl<D: StLinkUsb> StLink<D> {
 
         let addbytes = address.to_le_bytes();
         let lenbytes = length.to_le_bytes();
-        self.device.write(
+        self.usb_write(
             &[
                 commands::JTAG_COMMAND,
                 commands::JTAG_WRITEMEM_32BIT,
@@ -990,7 +1205,7 @@ impl<D: StLinkUsb> StLink<D> {
 
         let addbytes = address.to_le_bytes();
         let lenbytes = byte_length.to_le_bytes();
-        self.device.write(
+        self.usb_write(
             &[
                 commands::JTAG_COMMAND,
                 commands::JTAG_WRITEMEM_8BIT,
@@ -1104,6 +1319,10 @@ impl<D: StLinkUsb> SwoAccess for StLink<D> {
         let data = self.read_swo_data(timeout)?;
         Ok(data)
     }
+
+    fn swo_buffer_size(&mut self) -> Option<usize> {
+        Some(STLINK_SWO_BUFFER_SIZE as usize)
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -1128,6 +1347,8 @@ pub(crate) enum StlinkError {
     MultidropNotSupported,
     #[error("Unaligned")]
     UnalignedAddress,
+    #[error("The SWO baud rate {baud} is higher than the maximum of the probe, {max}.")]
+    SwoBaudTooHigh { baud: u32, max: u32 },
 }
 
 impl From<StlinkError> for DebugProbeError {
@@ -1179,7 +1400,7 @@ impl SwdSequence for UninitializedStLink {
         pin_select: u32,
         pin_wait: u32,
     ) -> Result<u32, ProbeRsError> {
-        self.probe.swj_pins(pin_out, pin_select, pin_wait)
+        Ok(self.probe.swj_pins(pin_out, pin_select, pin_wait)?)
     }
 }
 
@@ -1216,6 +1437,13 @@ impl StlinkArmDebug {
 
         Ok(interface)
     }
+
+    /// Record the event returned by `event` in the protocol tracer of the probe, if it has one.
+    fn trace(&self, event: impl FnOnce() -> TraceEvent) {
+        if let Some(tracer) = &self.probe.tracer {
+            tracer.record(event());
+        }
+    }
 }
 
 impl DapAccess for StlinkArmDebug {
@@ -1223,8 +1451,14 @@ impl DapAccess for StlinkArmDebug {
         if dp != DpAddress::Default {
             return Err(StlinkError::MultidropNotSupported.into());
         }
-        let result = self.probe.read_register(DP_PORT, address)?;
-        Ok(result)
+        let result = self.probe.read_register(DP_PORT, address);
+
+        self.trace(|| TraceEvent::DpRead {
+            address,
+            value: result.as_ref().ok().copied(),
+        });
+
+        result
     }
 
     fn write_raw_dp_register(
@@ -1237,8 +1471,15 @@ impl DapAccess for StlinkArmDebug {
             return Err(StlinkError::MultidropNotSupported.into());
         }
 
-        self.probe.write_register(DP_PORT, address, value)?;
-        Ok(())
+        let result = self.probe.write_register(DP_PORT, address, value);
+
+        self.trace(|| TraceEvent::DpWrite {
+            address,
+            value,
+            ok: result.is_ok(),
+        });
+
+        result
     }
 
     fn read_raw_ap_register(&mut self, ap: ApAddress, address: u8) -> Result<u32, DebugProbeError> {
@@ -1246,7 +1487,15 @@ impl DapAccess for StlinkArmDebug {
             return Err(StlinkError::MultidropNotSupported.into());
         }
 
-        self.probe.read_register(ap.ap as u16, address)
+        let result = self.probe.read_register(ap.ap as u16, address);
+
+        self.trace(|| TraceEvent::ApRead {
+            ap: ap.ap,
+            address,
+            value: result.as_ref().ok().copied(),
+        });
+
+        result
     }
 
     fn write_raw_ap_register(
@@ -1259,7 +1508,16 @@ impl DapAccess for StlinkArmDebug {
             return Err(StlinkError::MultidropNotSupported.into());
         }
 
-        self.probe.write_register(ap.ap as u16, address, value)
+        let result = self.probe.write_register(ap.ap as u16, address, value);
+
+        self.trace(|| TraceEvent::ApWrite {
+            ap: ap.ap,
+            address,
+            value,
+            ok: result.is_ok(),
+        });
+
+        result
     }
 }
 
@@ -1351,7 +1609,7 @@ impl SwdSequence for StlinkArmDebug {
         pin_select: u32,
         pin_wait: u32,
     ) -> Result<u32, ProbeRsError> {
-        self.probe.swj_pins(pin_out, pin_select, pin_wait)
+        Ok(self.probe.swj_pins(pin_out, pin_select, pin_wait)?)
     }
 }
 
@@ -1367,6 +1625,10 @@ impl SwoAccess for StlinkArmDebug {
     fn read_swo_timeout(&mut self, timeout: Duration) -> Result<Vec<u8>, ProbeRsError> {
         self.probe.read_swo_timeout(timeout)
     }
+
+    fn swo_buffer_size(&mut self) -> Option<usize> {
+        self.probe.swo_buffer_size()
+    }
 }
 
 #[derive(Debug)]
@@ -1567,8 +1829,11 @@ impl ArmProbe for StLinkMemoryInterface<'_> {
 #[cfg(test)]
 mod test {
 
+    use std::time::Duration;
+
     use super::{constants::commands, usb_interface::StLinkUsb, StLink};
-    use crate::{DebugProbeError, WireProtocol};
+    use crate::architecture::arm::{SwoAccess, SwoConfig};
+    use crate::{DebugProbeError, ReconnectPolicy, WireProtocol};
 
     use scroll::Pwrite;
 
@@ -1580,6 +1845,13 @@ mod test {
 
         target_voltage_a0: f32,
         target_voltage_a1: f32,
+
+        /// The next command fails as if the ST-Link was lost from USB.
+        lost: bool,
+        reopened: usize,
+
+        /// SWO data which was received by the ST-Link.
+        swo_data: Vec<u8>,
     }
 
     impl MockUsb {
@@ -1594,6 +1866,11 @@ mod test {
                 jtag_speed_khz: 0,
                 swo_enabled: false,
                 opened_aps: vec![],
+                attached: false,
+                swo_config: None,
+                reconnect_policy: ReconnectPolicy::default(),
+                reconnecting: false,
+                tracer: None,
             }
         }
     }
@@ -1606,6 +1883,11 @@ mod test {
             read_data: &mut [u8],
             _timeout: std::time::Duration,
         ) -> Result<(), crate::DebugProbeError> {
+            if self.lost {
+                self.lost = false;
+                return Err(DebugProbeError::Usb(Some(Box::new(rusb::Error::NoDevice))));
+            }
+
             match cmd[0] {
                 commands::GET_VERSION => {
                     // GET_VERSION response structure:
@@ -1630,6 +1912,12 @@ mod test {
                     read_data.pwrite(self.target_voltage_a0, 4).unwrap();
                     Ok(())
                 }
+                commands::JTAG_COMMAND if cmd[1] == commands::SWO_GET_TRACE_NEW_RECORD_NB => {
+                    read_data
+                        .pwrite_with(self.swo_data.len() as u16, 0, scroll::LE)
+                        .unwrap();
+                    Ok(())
+                }
                 commands::JTAG_COMMAND => {
                     // Return a status of OK for JTAG commands
                     read_data[0] = 0x80;
@@ -1645,10 +1933,18 @@ mod test {
 
         fn read_swo(
             &mut self,
-            _read_data: &mut [u8],
+            read_data: &mut [u8],
             _timeout: std::time::Duration,
         ) -> Result<usize, DebugProbeError> {
-            unimplemented!("Not implemented for MockUSB")
+            let length = read_data.len().min(self.swo_data.len());
+            read_data[..length].copy_from_slice(&self.swo_data[..length]);
+            self.swo_data.drain(..length);
+            Ok(length)
+        }
+
+        fn reopen(&mut self) -> Result<(), DebugProbeError> {
+            self.reopened += 1;
+            Ok(())
         }
     }
 
@@ -1663,6 +1959,10 @@ mod test {
 
             target_voltage_a0: 1.0,
             target_voltage_a1: 2.0,
+
+            lost: false,
+            reopened: 0,
+            swo_data: vec![],
         };
 
         let mut probe = usb_mock.build();
@@ -1686,6 +1986,10 @@ mod test {
             swim_version: 0,
             target_voltage_a0: 1.0,
             target_voltage_a1: 2.0,
+
+            lost: false,
+            reopened: 0,
+            swo_data: vec![],
         };
 
         let mut probe = usb_mock.build();
@@ -1711,6 +2015,10 @@ mod test {
             swim_version: 0,
             target_voltage_a0: 1.0,
             target_voltage_a1: 2.0,
+
+            lost: false,
+            reopened: 0,
+            swo_data: vec![],
         };
 
         let mut probe = usb_mock.build();
@@ -1724,4 +2032,78 @@ mod test {
             .select_ap(1)
             .expect("Selecting AP other than AP 0 should work");
     }
+
+    #[test]
+    fn reconnect_lost_probe() {
+        let usb_mock = MockUsb {
+            hw_version: 2,
+            jtag_version: 30,
+            swim_version: 0,
+            target_voltage_a0: 1.0,
+            target_voltage_a1: 2.0,
+
+            lost: false,
+            reopened: 0,
+            swo_data: vec![],
+        };
+
+        let mut probe = usb_mock.build();
+        probe.reconnect_policy.delay = std::time::Duration::ZERO;
+
+        probe.init().expect("Init function failed");
+        probe.select_ap(1).expect("Select AP 1 failed.");
+
+        probe.device.lost = true;
+        probe
+            .get_current_mode()
+            .expect("The lost probe should be reconnected");
+
+        assert_eq!(probe.device.reopened, 1);
+        assert_eq!(probe.opened_aps, vec![1]);
+
+        probe.reconnect_policy = ReconnectPolicy::disabled();
+        probe.device.lost = true;
+        probe
+            .get_current_mode()
+            .expect_err("The probe should not be reconnected");
+    }
+
+    #[test]
+    fn swo_reception() {
+        let usb_mock = MockUsb {
+            hw_version: 2,
+            jtag_version: 30,
+            swim_version: 0,
+            target_voltage_a0: 1.0,
+            target_voltage_a1: 2.0,
+
+            lost: false,
+            reopened: 0,
+            swo_data: vec![],
+        };
+
+        let mut probe = usb_mock.build();
+        probe.init().expect("Init function failed");
+
+        let config = SwoConfig::new(0).set_baud(4_000_000);
+        assert!(probe.enable_swo(&config).is_err());
+
+        let config = SwoConfig::new(0).set_baud(2_000_000);
+        probe.enable_swo(&config).expect("Enabling SWO failed");
+        assert_eq!(probe.swo_buffer_size(), Some(4096));
+
+        assert_eq!(
+            probe.read_swo_timeout(Duration::from_millis(5)).unwrap(),
+            Vec::<u8>::new()
+        );
+
+        probe.device.swo_data = vec![1, 2, 3];
+        assert_eq!(
+            probe.read_swo_timeout(Duration::from_millis(5)).unwrap(),
+            vec![1, 2, 3]
+        );
+
+        probe.disable_swo().expect("Disabling SWO failed");
+        assert!(!probe.swo_enabled);
+    }
 }
@@ -8,7 +8,7 @@ use std::collections::HashMap;
 
 use super::tools::{is_stlink_device, read_serial_number};
 use crate::{
-    probe::{DebugProbeError, ProbeCreationError},
+    probe::{DebugProbeError, ProbeCreationError, UsbOperation},
     DebugProbeSelector,
 };
 
@@ -65,6 +65,8 @@ impl StLinkInfo {
 pub(crate) struct StLinkUsbDevice {
     device_handle: DeviceHandle<rusb::Context>,
     pub(crate) info: StLinkInfo,
+    /// Selects this ST-Link, to open it again after it was lost from USB.
+    selector: DebugProbeSelector,
 }
 
 impl std::fmt::Debug for StLinkUsbDevice {
@@ -72,6 +74,7 @@ impl std::fmt::Debug for StLinkUsbDevice {
         fmt.debug_struct("StLinkUsbDevice")
             .field("device_handle", &"DeviceHandle<rusb::Context>")
             .field("info", &self.info)
+            .field("selector", &self.selector)
             .finish()
     }
 }
@@ -89,6 +92,9 @@ pub trait StLinkUsb: std::fmt::Debug {
     /// STLink does not respond to USB requests.
     fn reset(&mut self) -> Result<(), DebugProbeError>;
 
+    /// Open the USB device again, after it was lost from USB.
+    fn reopen(&mut self) -> Result<(), DebugProbeError>;
+
     fn read_swo(
         &mut self,
         read_data: &mut [u8],
@@ -135,21 +141,57 @@ impl StLinkUsbDevice {
             })
             .map_or(Err(ProbeCreationError::NotFound), Ok)?;
 
-        let mut device_handle = device.open()?;
+        let descriptor = device.device_descriptor()?;
+
+        let device_handle = device.open().map_err(|error| {
+            ProbeCreationError::from_usb(
+                descriptor.vendor_id(),
+                descriptor.product_id(),
+                UsbOperation::Open,
+                error,
+            )
+        })?;
 
         log::debug!("Aquired handle for probe");
 
+        Self::new_from_handle(device_handle, selector)
+    }
+
+    /// Initializes a new USB device from an opened `device_handle`.
+    ///
+    /// `selector` is used to open the ST-Link again after it was lost from USB. The serial
+    /// number of the ST-Link is added to it.
+    pub fn new_from_handle(
+        mut device_handle: DeviceHandle<Context>,
+        selector: DebugProbeSelector,
+    ) -> Result<Self, ProbeCreationError> {
+        let device = device_handle.device();
+        let descriptor = device.device_descriptor()?;
+
         let config = device.active_config_descriptor()?;
 
         log::debug!("Active config descriptor: {:?}", &config);
 
-        let descriptor = device.device_descriptor()?;
-
         log::debug!("Device descriptor: {:?}", &descriptor);
 
         let info = USB_PID_EP_MAP[&descriptor.product_id()].clone();
 
-        device_handle.claim_interface(0)?;
+        // The serial number selects the same ST-Link when it is opened again.
+        let selector = DebugProbeSelector {
+            serial_number: read_serial_number(&device, &descriptor)
+                .ok()
+                .or(selector.serial_number),
+            ..selector
+        };
+
+        device_handle.claim_interface(0).map_err(|error| {
+            ProbeCreationError::from_usb(
+                descriptor.vendor_id(),
+                descriptor.product_id(),
+                UsbOperation::ClaimInterface(0),
+                error,
+            )
+        })?;
 
         log::debug!("Claimed interface 0 of USB device.");
 
@@ -186,6 +228,7 @@ impl StLinkUsbDevice {
         let usb_stlink = Self {
             device_handle,
             info,
+            selector,
         };
 
         log::debug!("Succesfully attached to STLink.");
@@ -318,6 +361,23 @@ impl StLinkUsb for StLinkUsbDevice {
             .reset()
             .map_err(|e| DebugProbeError::Usb(Some(Box::new(e))))
     }
+
+    fn reopen(&mut self) -> Result<(), DebugProbeError> {
+        log::debug!("Opening USB device of STLink again");
+        *self = Self::new_from_selector(self.selector.clone())?;
+
+        Ok(())
+    }
+}
+
+/// Whether `error` was caused by the USB device being disconnected.
+pub(crate) fn is_device_lost(error: &DebugProbeError) -> bool {
+    match error {
+        DebugProbeError::Usb(Some(source)) => {
+            matches!(source.downcast_ref::<Error>(), Some(Error::NoDevice))
+        }
+        _ => false,
+    }
 }
 
 impl Drop for StLinkUsbDevice {
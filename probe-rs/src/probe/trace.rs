@@ -0,0 +1,416 @@
+//! A trace of the operations of a debug probe, to diagnose problems with the communication
+//! between the probe and the target.
+
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
+use std::fs::File;
+use std::io::{LineWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use web_time::Instant;
+
+/// An operation of a debug probe, recorded by a [`ProtocolTracer`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceEvent {
+    /// A register of a debug port was read. The value is `None` if the read failed.
+    DpRead { address: u8, value: Option<u32> },
+    /// A register of a debug port was written.
+    DpWrite { address: u8, value: u32, ok: bool },
+    /// A register of an access port was read. The value is `None` if the read failed.
+    ApRead {
+        ap: u8,
+        address: u8,
+        value: Option<u32>,
+    },
+    /// A register of an access port was written.
+    ApWrite {
+        ap: u8,
+        address: u8,
+        value: u32,
+        ok: bool,
+    },
+    /// A register of an access port was read repeatedly.
+    ApReadBlock {
+        ap: u8,
+        address: u8,
+        values: Vec<u32>,
+        ok: bool,
+    },
+    /// A register of an access port was written repeatedly.
+    ApWriteBlock {
+        ap: u8,
+        address: u8,
+        values: Vec<u32>,
+        ok: bool,
+    },
+    /// A packet was sent to the probe.
+    Command(Vec<u8>),
+    /// A packet was received from the probe.
+    Response(Vec<u8>),
+}
+
+impl fmt::Display for TraceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn status(ok: bool) -> &'static str {
+            if ok {
+                ""
+            } else {
+                " failed"
+            }
+        }
+
+        match self {
+            TraceEvent::DpRead { address, value } => match value {
+                Some(value) => write!(f, "DP read  {:#04x}: {:#010x}", address, value),
+                None => write!(f, "DP read  {:#04x} failed", address),
+            },
+            TraceEvent::DpWrite { address, value, ok } => write!(
+                f,
+                "DP write {:#04x}: {:#010x}{}",
+                address,
+                value,
+                status(*ok)
+            ),
+            TraceEvent::ApRead { ap, address, value } => match value {
+                Some(value) => write!(f, "AP{} read  {:#04x}: {:#010x}", ap, address, value),
+                None => write!(f, "AP{} read  {:#04x} failed", ap, address),
+            },
+            TraceEvent::ApWrite {
+                ap,
+                address,
+                value,
+                ok,
+            } => write!(
+                f,
+                "AP{} write {:#04x}: {:#010x}{}",
+                ap,
+                address,
+                value,
+                status(*ok)
+            ),
+            TraceEvent::ApReadBlock {
+                ap,
+                address,
+                values,
+                ok,
+            } => write!(
+                f,
+                "AP{} read  {:#04x} x{}: {:08x?}{}",
+                ap,
+                address,
+                values.len(),
+                values,
+                status(*ok)
+            ),
+            TraceEvent::ApWriteBlock {
+                ap,
+                address,
+                values,
+                ok,
+            } => write!(
+                f,
+                "AP{} write {:#04x} x{}: {:08x?}{}",
+                ap,
+                address,
+                values.len(),
+                values,
+                status(*ok)
+            ),
+            TraceEvent::Command(data) => write!(f, "command  {:02x?}", data),
+            TraceEvent::Response(data) => write!(f, "response {:02x?}", data),
+        }
+    }
+}
+
+/// A line of a protocol trace which could not be parsed.
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid protocol trace line: {0:?}")]
+pub struct ParseTraceError(String);
+
+impl FromStr for TraceEvent {
+    type Err = ParseTraceError;
+
+    /// Parse an event in the format of its [`Display`](fmt::Display) implementation.
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let error = || ParseTraceError(line.to_string());
+
+        let (line, ok) = match line.strip_suffix(" failed") {
+            Some(line) => (line, false),
+            None => (line, true),
+        };
+        let (head, value) = match line.split_once(": ") {
+            Some((head, value)) => (head, Some(value)),
+            None => (line, None),
+        };
+
+        let mut words = head.split_whitespace();
+        let port = words.next().ok_or_else(error)?;
+
+        if port == "command" || port == "response" {
+            let list = words.collect::<Vec<_>>().join(" ");
+            let data = parse_list(&list, |byte| u8::from_str_radix(byte, 16)).ok_or_else(error)?;
+
+            return Ok(match port {
+                "command" => TraceEvent::Command(data),
+                _ => TraceEvent::Response(data),
+            });
+        }
+
+        let ap = match port {
+            "DP" => None,
+            _ => Some(
+                port.strip_prefix("AP")
+                    .and_then(|ap| ap.parse().ok())
+                    .ok_or_else(error)?,
+            ),
+        };
+        let write = match words.next() {
+            Some("read") => false,
+            Some("write") => true,
+            _ => return Err(error()),
+        };
+        let address = words.next().and_then(parse_hex).ok_or_else(error)?;
+        let block = words.next().is_some();
+
+        let event = match (ap, block) {
+            (Some(ap), true) => {
+                let values = value
+                    .and_then(|list| parse_list(list, |value| u32::from_str_radix(value, 16)))
+                    .ok_or_else(error)?;
+
+                if write {
+                    TraceEvent::ApWriteBlock {
+                        ap,
+                        address,
+                        values,
+                        ok,
+                    }
+                } else {
+                    TraceEvent::ApReadBlock {
+                        ap,
+                        address,
+                        values,
+                        ok,
+                    }
+                }
+            }
+            (None, true) => return Err(error()),
+            (ap, false) => {
+                let value = match value {
+                    Some(value) => Some(parse_hex(value).ok_or_else(error)?),
+                    None => None,
+                };
+
+                match (ap, write) {
+                    (None, false) => TraceEvent::DpRead { address, value },
+                    (Some(ap), false) => TraceEvent::ApRead { ap, address, value },
+                    (None, true) => TraceEvent::DpWrite {
+                        address,
+                        value: value.ok_or_else(error)?,
+                        ok,
+                    },
+                    (Some(ap), true) => TraceEvent::ApWrite {
+                        ap,
+                        address,
+                        value: value.ok_or_else(error)?,
+                        ok,
+                    },
+                }
+            }
+        };
+
+        Ok(event)
+    }
+}
+
+fn parse_hex<T: TryFrom<u32>>(value: &str) -> Option<T> {
+    let value = u32::from_str_radix(value.strip_prefix("0x")?, 16).ok()?;
+
+    value.try_into().ok()
+}
+
+/// Parse a list in the format of `{:x?}`, e.g. `[1f, 2a]`.
+fn parse_list<T, E>(list: &str, parse: impl Fn(&str) -> Result<T, E>) -> Option<Vec<T>> {
+    let list = list.strip_prefix('[')?.strip_suffix(']')?;
+
+    if list.is_empty() {
+        return Some(Vec::new());
+    }
+
+    list.split(", ").map(|item| parse(item).ok()).collect()
+}
+
+/// A [`TraceEvent`], with the time since the [`ProtocolTracer`] was created.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceRecord {
+    pub time: Duration,
+    pub event: TraceEvent,
+}
+
+impl fmt::Display for TraceRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:>12.6} {}", self.time.as_secs_f64(), self.event)
+    }
+}
+
+impl FromStr for TraceRecord {
+    type Err = ParseTraceError;
+
+    /// Parse a line of a trace written by [`ProtocolTracer::to_file`].
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let (time, event) = line
+            .trim_start()
+            .split_once(' ')
+            .ok_or_else(|| ParseTraceError(line.to_string()))?;
+
+        let time = time
+            .parse::<f64>()
+            .map_err(|_| ParseTraceError(line.to_string()))?;
+
+        Ok(Self {
+            time: Duration::from_secs_f64(time),
+            event: event.parse()?,
+        })
+    }
+}
+
+type Sink = dyn FnMut(&TraceRecord) + Send;
+
+/// Records the operations of a debug probe, set with [`Probe::set_protocol_tracer`](crate::Probe::set_protocol_tracer).
+///
+/// The transfers to the registers of the DP and the APs of ARM targets are recorded
+/// for all probes. The packets which are sent to and received from the probe are
+/// recorded for ST-Links, whose DAP transfers are done by the firmware of the probe.
+///
+/// Clones of a tracer record to the same destination.
+#[derive(Clone)]
+pub struct ProtocolTracer {
+    start: Instant,
+    sink: Arc<Mutex<Sink>>,
+}
+
+impl ProtocolTracer {
+    /// Create a tracer which calls `callback` with each record.
+    pub fn new(callback: impl FnMut(&TraceRecord) + Send + 'static) -> Self {
+        Self {
+            start: Instant::now(),
+            sink: Arc::new(Mutex::new(callback)),
+        }
+    }
+
+    /// Create a tracer which writes one line to the file at `path` for each record.
+    ///
+    /// The file is created, or truncated if it exists.
+    pub fn to_file(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let mut file = LineWriter::new(File::create(path)?);
+
+        Ok(Self::new(move |record| {
+            if let Err(error) = writeln!(file, "{}", record) {
+                log::warn!("Failed to write the protocol trace: {}", error);
+            }
+        }))
+    }
+
+    pub(crate) fn record(&self, event: TraceEvent) {
+        let record = TraceRecord {
+            time: self.start.elapsed(),
+            event,
+        };
+
+        // A callback which panicked does not stop the trace.
+        let mut sink = match self.sink.lock() {
+            Ok(sink) => sink,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        (*sink)(&record);
+    }
+}
+
+impl fmt::Debug for ProtocolTracer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProtocolTracer")
+            .field("start", &self.start)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::{ProtocolTracer, TraceEvent, TraceRecord};
+
+    #[test]
+    fn record_events() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+
+        let tracer = {
+            let events = events.clone();
+            ProtocolTracer::new(move |record| events.lock().unwrap().push(record.clone()))
+        };
+
+        tracer.record(TraceEvent::DpRead {
+            address: 0,
+            value: Some(0x2ba0_1477),
+        });
+        tracer.clone().record(TraceEvent::ApWrite {
+            ap: 1,
+            address: 4,
+            value: 0x2000_0000,
+            ok: false,
+        });
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events[0].time <= events[1].time);
+        assert_eq!(events[0].event.to_string(), "DP read  0x00: 0x2ba01477");
+        assert_eq!(
+            events[1].event.to_string(),
+            "AP1 write 0x04: 0x20000000 failed"
+        );
+    }
+
+    #[test]
+    fn parse_events() {
+        let events = vec![
+            TraceEvent::DpRead {
+                address: 4,
+                value: None,
+            },
+            TraceEvent::DpWrite {
+                address: 8,
+                value: 0xf0,
+                ok: true,
+            },
+            TraceEvent::ApRead {
+                ap: 2,
+                address: 0xfc,
+                value: Some(0x2477_0011),
+            },
+            TraceEvent::ApReadBlock {
+                ap: 0,
+                address: 0xc,
+                values: vec![1, 0xffff_ffff],
+                ok: false,
+            },
+            TraceEvent::ApWriteBlock {
+                ap: 0,
+                address: 0xc,
+                values: vec![],
+                ok: true,
+            },
+            TraceEvent::Command(vec![0xf2, 0x45, 0x00]),
+        ];
+
+        for event in events {
+            assert_eq!(event.to_string().parse::<TraceEvent>().unwrap(), event);
+        }
+
+        let record: TraceRecord = "    1.500000 DP read  0x00: 0x2ba01477".parse().unwrap();
+        assert_eq!(record.time.as_millis(), 1500);
+
+        assert!("DP jump 0x00".parse::<TraceEvent>().is_err());
+    }
+}
@@ -0,0 +1,250 @@
+//! Detection of debug probes which are attached or detached while a tool is running.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+#[cfg(feature = "libusb")]
+use rusb::{Device, UsbContext};
+
+use super::{DebugProbeInfo, Probe};
+
+/// A change of the attached debug probes, reported by [`ProbeWatcher`].
+#[derive(Debug, Clone)]
+pub enum ProbeEvent {
+    /// The probe was attached.
+    Attached(DebugProbeInfo),
+    /// The probe was detached.
+    Detached(DebugProbeInfo),
+}
+
+/// Reports debug probes which are attached or detached, created with [`Probe::watch`].
+///
+/// The probes are watched on a background thread, which stops when the watcher is dropped.
+/// Iterating over the watcher blocks until the next event.
+pub struct ProbeWatcher {
+    events: mpsc::Receiver<ProbeEvent>,
+    stopped: Arc<AtomicBool>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl ProbeWatcher {
+    pub(super) fn new(interval: Duration) -> Self {
+        let (sender, events) = mpsc::channel();
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let worker = {
+            let stopped = stopped.clone();
+            thread::spawn(move || watch(sender, &stopped, interval))
+        };
+
+        Self {
+            events,
+            stopped,
+            worker: Some(worker),
+        }
+    }
+
+    /// Wait up to `timeout` for the next event.
+    pub fn next_timeout(&mut self, timeout: Duration) -> Option<ProbeEvent> {
+        self.events.recv_timeout(timeout).ok()
+    }
+
+    /// Return the next event without waiting, if there is one.
+    pub fn try_next(&mut self) -> Option<ProbeEvent> {
+        self.events.try_recv().ok()
+    }
+}
+
+impl Iterator for ProbeWatcher {
+    type Item = ProbeEvent;
+
+    fn next(&mut self) -> Option<ProbeEvent> {
+        self.events.recv().ok()
+    }
+}
+
+impl Drop for ProbeWatcher {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Send the changes of the attached probes to `events`, until `stopped` is set or the
+/// watcher is dropped.
+///
+/// If libusb supports hotplug events, the probes are only listed again when a USB device
+/// was attached or detached. Otherwise they are listed every `interval`.
+fn watch(events: mpsc::Sender<ProbeEvent>, stopped: &AtomicBool, interval: Duration) {
+    let hotplug = Hotplug::register();
+
+    let mut probes = Probe::list_all();
+
+    while !stopped.load(Ordering::SeqCst) {
+        match &hotplug {
+            Some(hotplug) => {
+                if !hotplug.wait(interval) {
+                    continue;
+                }
+            }
+            None => thread::sleep(interval),
+        }
+
+        let current = Probe::list_all();
+
+        for event in changes(&probes, &current) {
+            if events.send(event).is_err() {
+                return;
+            }
+        }
+
+        probes = current;
+    }
+}
+
+/// The registration for the libusb hotplug events.
+#[cfg(feature = "libusb")]
+struct Hotplug {
+    context: rusb::Context,
+    _registration: rusb::Registration<rusb::Context>,
+    changed: Arc<AtomicBool>,
+}
+
+#[cfg(feature = "libusb")]
+impl Hotplug {
+    /// Register for the hotplug events, or return `None` if libusb doesn't support them.
+    fn register() -> Option<Self> {
+        if !rusb::has_hotplug() {
+            return None;
+        }
+
+        let changed = Arc::new(AtomicBool::new(false));
+
+        rusb::Context::new()
+            .and_then(|context| {
+                let registration = context.register_callback(
+                    None,
+                    None,
+                    None,
+                    Box::new(HotplugFlag(changed.clone())),
+                )?;
+
+                Ok(Self {
+                    context,
+                    _registration: registration,
+                    changed,
+                })
+            })
+            .map_err(|error| {
+                log::warn!(
+                    "Failed to register for USB hotplug events, polling instead: {}",
+                    error
+                );
+            })
+            .ok()
+    }
+
+    /// Wait up to `interval` for the hotplug events, and return whether a USB device was
+    /// attached or detached.
+    fn wait(&self, interval: Duration) -> bool {
+        if let Err(error) = self.context.handle_events(Some(interval)) {
+            log::warn!("Failed to handle USB hotplug events: {}", error);
+            thread::sleep(interval);
+        }
+
+        self.changed.swap(false, Ordering::SeqCst)
+    }
+}
+
+/// Without libusb, there are no hotplug events, and the probes are polled.
+#[cfg(not(feature = "libusb"))]
+enum Hotplug {}
+
+#[cfg(not(feature = "libusb"))]
+impl Hotplug {
+    fn register() -> Option<Self> {
+        None
+    }
+
+    fn wait(&self, _interval: Duration) -> bool {
+        match *self {}
+    }
+}
+
+/// Sets a flag when libusb reports that a USB device was attached or detached.
+#[cfg(feature = "libusb")]
+struct HotplugFlag(Arc<AtomicBool>);
+
+#[cfg(feature = "libusb")]
+impl<T: UsbContext> rusb::Hotplug<T> for HotplugFlag {
+    fn device_arrived(&mut self, _device: Device<T>) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    fn device_left(&mut self, _device: Device<T>) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+/// The events which turn the list of probes `old` into `new`.
+fn changes(old: &[DebugProbeInfo], new: &[DebugProbeInfo]) -> Vec<ProbeEvent> {
+    let detached = old
+        .iter()
+        .filter(|probe| !new.iter().any(|other| same_probe(probe, other)))
+        .map(|probe| ProbeEvent::Detached(probe.clone()));
+
+    let attached = new
+        .iter()
+        .filter(|probe| !old.iter().any(|other| same_probe(probe, other)))
+        .map(|probe| ProbeEvent::Attached(probe.clone()));
+
+    detached.chain(attached).collect()
+}
+
+fn same_probe(a: &DebugProbeInfo, b: &DebugProbeInfo) -> bool {
+    a.vendor_id == b.vendor_id
+        && a.product_id == b.product_id
+        && a.serial_number == b.serial_number
+        && a.probe_type == b.probe_type
+        && a.hid_interface == b.hid_interface
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{changes, ProbeEvent};
+    use crate::{DebugProbeInfo, DebugProbeType};
+
+    fn probe(serial_number: &str) -> DebugProbeInfo {
+        DebugProbeInfo::new(
+            "STLink V3",
+            0x0483,
+            0x374f,
+            Some(serial_number.to_string()),
+            DebugProbeType::StLink,
+            None,
+        )
+    }
+
+    #[test]
+    fn probe_changes() {
+        let old = vec![probe("A"), probe("B")];
+        let new = vec![probe("B"), probe("C")];
+
+        let events = changes(&old, &new);
+
+        assert_eq!(events.len(), 2);
+        assert!(
+            matches!(&events[0], ProbeEvent::Detached(info) if info.serial_number.as_deref() == Some("A"))
+        );
+        assert!(
+            matches!(&events[1], ProbeEvent::Attached(info) if info.serial_number.as_deref() == Some("C"))
+        );
+
+        assert!(changes(&new, &new).is_empty());
+    }
+}
@@ -1,24 +1,34 @@
 #![warn(missing_docs)]
 
-use crate::architecture::arm::sequences::DefaultArmSequence;
-use crate::architecture::arm::{ApAddress, DpAddress};
+use crate::architecture::arm::discovery::Topology;
+use crate::architecture::arm::sequences::{DebugCredentials, DefaultArmSequence};
+use crate::architecture::arm::{ApAddress, DpAddress, RetryPolicy};
+use crate::breakpoints::BreakpointManager;
 use crate::config::{ChipInfo, MemoryRegion, RegistryError, Target, TargetSelector};
 use crate::core::{Architecture, CoreState, SpecificCoreState};
+use crate::debug::SymbolTable;
+use crate::event::SessionEvents;
+use crate::interface_summary::InterfaceSummary;
+use crate::probe::ProbeLock;
 use crate::{
     architecture::{
         arm::{
             ap::{AccessPortError, GenericAp, MemoryAp},
             communication_interface::{ArmProbeInterface, MemoryApInformation},
+            component::{MtbConfig, TraceSink},
             memory::Component,
-            ApInformation, SwoConfig,
+            trace::MtbPacket,
+            ApInformation, DataTraceDecoder, DataTraceEvent, SwoConfig,
         },
         riscv::communication_interface::RiscvCommunicationInterface,
+        xtensa::communication_interface::XtensaCommunicationInterface,
     },
     config::DebugSequence,
 };
-use crate::{AttachMethod, Core, CoreType, Error, Probe};
-use anyhow::anyhow;
-use std::{fmt, time::Duration};
+use crate::{
+    AttachMethod, Core, CoreType, DebugProbeError, Error, Memory, Permissions, Probe, ProbeBridge,
+};
+use std::{fmt, ops::Range, time::Duration};
 
 /// The `Session` struct represents an active debug session.
 ///
@@ -43,11 +53,20 @@ pub struct Session {
     target: Target,
     interface: ArchitectureInterface,
     cores: Vec<(SpecificCoreState, CoreState)>,
+    pub(crate) events: SessionEvents,
+    pub(crate) symbols: Option<SymbolTable>,
+    pub(crate) breakpoints: BreakpointManager,
+    #[cfg(feature = "svd")]
+    pub(crate) svd: Option<crate::svd::Device>,
+    pub(crate) interface_summary: InterfaceSummary,
+    /// The lock of the probe, which is released after the interface is closed.
+    _probe_lock: Option<ProbeLock>,
 }
 
 enum ArchitectureInterface {
     Arm(Box<dyn ArmProbeInterface + 'static>),
     Riscv(Box<RiscvCommunicationInterface>),
+    Xtensa(Box<XtensaCommunicationInterface>),
 }
 
 impl fmt::Debug for ArchitectureInterface {
@@ -58,6 +77,10 @@ impl fmt::Debug for ArchitectureInterface {
                 .debug_tuple("ArchitectureInterface::Riscv")
                 .field(iface)
                 .finish(),
+            ArchitectureInterface::Xtensa(iface) => f
+                .debug_tuple("ArchitectureInterface::Xtensa")
+                .field(iface)
+                .finish(),
         }
     }
 }
@@ -67,6 +90,7 @@ impl From<ArchitectureInterface> for Architecture {
         match value {
             ArchitectureInterface::Arm(_) => Architecture::Arm,
             ArchitectureInterface::Riscv(_) => Architecture::Riscv,
+            ArchitectureInterface::Xtensa(_) => Architecture::Xtensa,
         }
     }
 }
@@ -86,9 +110,7 @@ impl ArchitectureInterface {
                     .ok_or_else(|| Error::CoreNotFound(core_state.id()))?;
                 let arm_core_access_options = match &config.core_access_options {
                     probe_rs_target::CoreAccessOptions::Arm(opt) => Ok(opt),
-                    probe_rs_target::CoreAccessOptions::Riscv(_) => {
-                        Err(AccessPortError::InvalidCoreAccessOption(config.clone()))
-                    }
+                    _ => Err(AccessPortError::InvalidCoreAccessOption(config.clone())),
                 }?;
 
                 let dp = match arm_core_access_options.psel {
@@ -100,11 +122,50 @@ impl ArchitectureInterface {
                     dp,
                     ap: arm_core_access_options.ap,
                 };
-                let memory = state.memory_interface(MemoryAp::new(ap))?;
+                let mut memory = state.memory_interface(MemoryAp::new(ap))?;
+
+                // Regions of the core which are behind another AP.
+                for region in &target.memory_map {
+                    let (range, cores) = match region {
+                        MemoryRegion::Ram(region) => (&region.range, &region.cores),
+                        MemoryRegion::Generic(region) => (&region.range, &region.cores),
+                        MemoryRegion::Nvm(region) => (&region.range, &region.cores),
+                    };
+
+                    match region.access_port() {
+                        Some(access_port) if cores.contains(&config.name) => {
+                            let access_port = MemoryAp::new(ApAddress {
+                                dp,
+                                ap: access_port,
+                            });
+
+                            memory.route(range.clone(), access_port);
+
+                            if let Some(alias) = region.alias_range() {
+                                memory.route(alias, access_port);
+                            }
+                        }
+                        _ => (),
+                    }
+                }
 
                 core.attach_arm(core_state, memory, target)
             }
-            ArchitectureInterface::Riscv(state) => core.attach_riscv(core_state, state),
+            ArchitectureInterface::Riscv(state) => {
+                let config = target
+                    .cores
+                    .get(core_state.id())
+                    .ok_or_else(|| Error::CoreNotFound(core_state.id()))?;
+                let riscv_core_access_options = match &config.core_access_options {
+                    probe_rs_target::CoreAccessOptions::Riscv(opt) => Ok(opt),
+                    _ => Err(AccessPortError::InvalidCoreAccessOption(config.clone())),
+                }?;
+
+                state.select_hart(riscv_core_access_options.hart_id)?;
+
+                core.attach_riscv(core_state, state, target)
+            }
+            ArchitectureInterface::Xtensa(state) => core.attach_xtensa(core_state, state, target),
         }
     }
 }
@@ -112,10 +173,14 @@ impl ArchitectureInterface {
 impl Session {
     /// Open a new session with a given debug target.
     pub(crate) fn new(
-        probe: Probe,
+        mut probe: Probe,
         target: TargetSelector,
         attach_method: AttachMethod,
+        permissions: Permissions,
     ) -> Result<Self, Error> {
+        // The probe is converted into interfaces and back, which doesn't keep the lock.
+        let probe_lock = probe.take_lock();
+
         let (mut probe, target) = get_target_from_selector(target, attach_method, probe)?;
 
         let cores = target
@@ -123,21 +188,22 @@ impl Session {
             .iter()
             .enumerate()
             .map(|(id, core)| {
-                (
-                    SpecificCoreState::from_core_type(core.core_type),
-                    Core::create_state(id),
-                )
+                let mut state = Core::create_state(id);
+                state.set_default_reset(target.default_reset);
+
+                (SpecificCoreState::from_core_type(core.core_type), state)
             })
             .collect();
 
+        // The probe is consumed by the interface, so the scan chain is kept for the summary.
+        let scan_chain;
+
         let mut session = match target.architecture() {
             Architecture::Arm => {
                 let config = target.cores[0].clone();
                 let arm_core_access_options = match config.core_access_options {
                     probe_rs_target::CoreAccessOptions::Arm(opt) => Ok(opt),
-                    probe_rs_target::CoreAccessOptions::Riscv(_) => {
-                        Err(AccessPortError::InvalidCoreAccessOption(config))
-                    }
+                    _ => Err(AccessPortError::InvalidCoreAccessOption(config)),
                 }?;
 
                 let default_memory_ap = MemoryAp::new(ApAddress {
@@ -150,7 +216,7 @@ impl Session {
 
                 let sequence_handle = match &target.debug_sequence {
                     DebugSequence::Arm(sequence) => sequence.clone(),
-                    DebugSequence::Riscv(_) => {
+                    _ => {
                         panic!("Mismatch between architecture and sequence type!")
                     }
                 };
@@ -169,6 +235,7 @@ impl Session {
                 }
 
                 probe.inner_attach()?;
+                scan_chain = probe.scan_chain().to_vec();
 
                 let interface = probe.try_into_arm_interface().map_err(|(_, err)| err)?;
 
@@ -178,10 +245,13 @@ impl Session {
                     let mut memory_interface = interface.memory_interface(default_memory_ap)?;
 
                     // Enable debug mode
-                    sequence_handle.debug_device_unlock(&mut memory_interface)?;
+                    sequence_handle.debug_device_unlock(&mut memory_interface, &permissions)?;
 
                     // Enable debug mode
                     sequence_handle.debug_core_start(&mut memory_interface)?;
+
+                    // Keep the debug connection when the core is sleeping
+                    sequence_handle.debug_sleep_enable(&mut memory_interface)?;
                 }
 
                 let session = if attach_method == AttachMethod::UnderReset {
@@ -197,10 +267,10 @@ impl Session {
                         .iter()
                         .enumerate()
                         .map(|(id, core)| {
-                            (
-                                SpecificCoreState::from_core_type(core.core_type),
-                                Core::create_state(id),
-                            )
+                            let mut state = Core::create_state(id);
+                            state.set_default_reset(target.default_reset);
+
+                            (SpecificCoreState::from_core_type(core.core_type), state)
                         })
                         .collect();
 
@@ -208,6 +278,13 @@ impl Session {
                         target,
                         interface: ArchitectureInterface::Arm(interface),
                         cores,
+                        events: SessionEvents::default(),
+                        symbols: None,
+                        breakpoints: BreakpointManager::default(),
+                        #[cfg(feature = "svd")]
+                        svd: None,
+                        interface_summary: InterfaceSummary::default(),
+                        _probe_lock: probe_lock,
                     };
 
                     {
@@ -234,6 +311,13 @@ impl Session {
                         target,
                         interface: ArchitectureInterface::Arm(interface),
                         cores,
+                        events: SessionEvents::default(),
+                        symbols: None,
+                        breakpoints: BreakpointManager::default(),
+                        #[cfg(feature = "svd")]
+                        svd: None,
+                        interface_summary: InterfaceSummary::default(),
+                        _probe_lock: probe_lock,
                     }
                 };
 
@@ -244,12 +328,13 @@ impl Session {
 
                 let sequence_handle = match &target.debug_sequence {
                     DebugSequence::Riscv(sequence) => sequence.clone(),
-                    DebugSequence::Arm(_) => {
+                    _ => {
                         panic!("Mismatch between architecture and sequence type!")
                     }
                 };
 
                 probe.inner_attach()?;
+                scan_chain = probe.scan_chain().to_vec();
 
                 let interface = probe
                     .try_into_riscv_interface()
@@ -259,6 +344,13 @@ impl Session {
                     target,
                     interface: ArchitectureInterface::Riscv(Box::new(interface)),
                     cores,
+                    events: SessionEvents::default(),
+                    symbols: None,
+                    breakpoints: BreakpointManager::default(),
+                    #[cfg(feature = "svd")]
+                    svd: None,
+                    interface_summary: InterfaceSummary::default(),
+                    _probe_lock: probe_lock,
                 };
 
                 {
@@ -270,10 +362,50 @@ impl Session {
 
                 sequence_handle.on_connect(session.get_riscv_interface()?)?;
 
+                session
+            }
+            Architecture::Xtensa => {
+                let sequence_handle = match &target.debug_sequence {
+                    DebugSequence::Xtensa(sequence) => sequence.clone(),
+                    _ => {
+                        panic!("Mismatch between architecture and sequence type!")
+                    }
+                };
+
+                probe.inner_attach()?;
+                scan_chain = probe.scan_chain().to_vec();
+
+                let interface = probe
+                    .try_into_xtensa_interface()
+                    .map_err(|(_probe, err)| err)?;
+
+                let mut session = Session {
+                    target,
+                    interface: ArchitectureInterface::Xtensa(Box::new(interface)),
+                    cores,
+                    events: SessionEvents::default(),
+                    symbols: None,
+                    breakpoints: BreakpointManager::default(),
+                    #[cfg(feature = "svd")]
+                    svd: None,
+                    interface_summary: InterfaceSummary::default(),
+                    _probe_lock: probe_lock,
+                };
+
+                {
+                    let mut core = session.core(0)?;
+
+                    core.halt(Duration::from_millis(100))?;
+                }
+
+                sequence_handle.on_connect(session.get_xtensa_interface()?)?;
+
                 session
             }
         };
 
+        session.interface_summary = InterfaceSummary::collect(&mut session, scan_chain);
+
         session.clear_all_hw_breakpoints()?;
 
         Ok(session)
@@ -332,18 +464,93 @@ impl Session {
         interface.read_swo()
     }
 
-    fn get_arm_interface(&mut self) -> Result<&mut Box<dyn ArmProbeInterface>, Error> {
+    /// Set the policy for retrying transfers which failed with a WAIT or FAULT response.
+    ///
+    /// This method is only supported for ARM-based targets, and will
+    /// return [Error::ArchitectureRequired] otherwise.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) -> Result<(), Error> {
+        let interface = self.get_arm_interface()?;
+        interface.set_retry_policy(policy);
+
+        Ok(())
+    }
+
+    /// Get the bridge of the probe to the SPI, I2C and UART interfaces of the target board,
+    /// e.g. to stimulate peripherals of the target in tests while it is debugged.
+    ///
+    /// Returns [`DebugProbeError::InterfaceNotAvailable`] if the probe has no bridge.
+    ///
+    /// This method is only supported for ARM-based targets, and will
+    /// return [Error::ArchitectureRequired] otherwise.
+    pub fn probe_bridge(&mut self) -> Result<&mut dyn ProbeBridge, Error> {
+        self.get_arm_interface()?.probe_bridge().ok_or(Error::Probe(
+            DebugProbeError::InterfaceNotAvailable("bridge"),
+        ))
+    }
+
+    /// Get the ARM debug interface of the session.
+    ///
+    /// This gives direct access to the registers of the debug ports and access ports,
+    /// e.g. using [`DpAccess`](crate::architecture::arm::dp::DpAccess) and
+    /// [`ApAccess`](crate::architecture::arm::ap::ApAccess). It can be used to implement
+    /// vendor specific unlock sequences or trace setup. Bank switching and AP selection
+    /// are done by the interface, writes to `SELECT` are tracked accordingly.
+    ///
+    /// This method is only supported for ARM-based targets, and will
+    /// return [Error::ArchitectureRequired] otherwise.
+    pub fn get_arm_interface(&mut self) -> Result<&mut dyn ArmProbeInterface, Error> {
         let interface = match &mut self.interface {
-            ArchitectureInterface::Arm(state) => state,
+            ArchitectureInterface::Arm(state) => state.as_mut(),
+            _ => return Err(Error::ArchitectureRequired(&["ARMv7", "ARMv8"])),
+        };
+
+        Ok(interface)
+    }
+
+    /// Unlock the debug access of the target, by answering its debug authentication challenge.
+    ///
+    /// The response to the challenge is computed by `credentials`, e.g. by signing it with a
+    /// key which is only known to the debugger. This is used by chips which restrict debug
+    /// access in the field, like the NXP LPC55xx, and is only supported by their debug sequence.
+    ///
+    /// This method is only supported for ARM-based targets, and will
+    /// return [Error::ArchitectureRequired] otherwise.
+    pub fn debug_unlock(&mut self, credentials: &dyn DebugCredentials) -> Result<(), Error> {
+        let sequence = match &self.target.debug_sequence {
+            DebugSequence::Arm(sequence) => sequence.clone(),
             _ => return Err(Error::ArchitectureRequired(&["ARMv7", "ARMv8"])),
         };
 
+        let dp = match &self.target.cores[0].core_access_options {
+            probe_rs_target::CoreAccessOptions::Arm(options) => match options.psel {
+                0 => DpAddress::Default,
+                x => DpAddress::Multidrop(x),
+            },
+            _ => DpAddress::Default,
+        };
+
+        sequence.debug_unlock(self.get_arm_interface()?, dp, credentials)
+    }
+
+    fn get_xtensa_interface(&mut self) -> Result<&mut XtensaCommunicationInterface, Error> {
+        let interface = match &mut self.interface {
+            ArchitectureInterface::Xtensa(interface) => interface.as_mut(),
+            _ => return Err(Error::ArchitectureRequired(&["Xtensa"])),
+        };
+
         Ok(interface)
     }
 
-    fn get_riscv_interface(&mut self) -> Result<&mut Box<RiscvCommunicationInterface>, Error> {
+    /// Get the RISC-V debug interface of the session.
+    ///
+    /// This can be used to list the harts of the debug module and their status,
+    /// using [`RiscvCommunicationInterface::hart_statuses`].
+    ///
+    /// This method is only supported for RISC-V-based targets, and will
+    /// return [Error::ArchitectureRequired] otherwise.
+    pub fn get_riscv_interface(&mut self) -> Result<&mut RiscvCommunicationInterface, Error> {
         let interface = match &mut self.interface {
-            ArchitectureInterface::Riscv(interface) => interface,
+            ArchitectureInterface::Riscv(interface) => interface.as_mut(),
             _ => return Err(Error::ArchitectureRequired(&["Riscv"])),
         };
 
@@ -369,14 +576,16 @@ impl Session {
 
             let component = match ap_information {
                 ApInformation::MemoryAp(MemoryApInformation {
+                    address,
                     debug_base_address: 0,
                     ..
-                }) => Err(Error::Other(anyhow!("AP has a base address of 0"))),
+                }) => Err(Error::MissingDebugBaseAddress(address)),
                 ApInformation::MemoryAp(MemoryApInformation {
                     address,
                     only_32bit_data_size: _,
                     debug_base_address,
                     supports_hnonsec: _,
+                    auto_increment_wrap: _,
                 }) => {
                     let mut memory = interface.memory_interface(MemoryAp::new(address))?;
                     Component::try_parse(&mut memory, debug_base_address)
@@ -384,10 +593,7 @@ impl Session {
                 }
                 ApInformation::Other { address } => {
                     // Return an error, only possible to get Component from MemoryAP
-                    Err(Error::Other(anyhow!(
-                        "AP {:#x?} is not a MemoryAP, unable to get ARM component.",
-                        address
-                    )))
+                    Err(Error::NotAMemoryAp(address))
                 }
             };
 
@@ -404,11 +610,99 @@ impl Session {
         Ok(components)
     }
 
+    /// Get access to the memory behind the memory AP `ap`, without attaching to a core.
+    ///
+    /// No core is halted or otherwise touched, so this can be used to access memory
+    /// which is not visible to the cores, e.g. external memories, mailboxes of a vendor
+    /// specific AP, or RAM shared between the cores of a heterogeneous chip. Accesses
+    /// to the memory of a running core are done while the core is running.
+    ///
+    /// This method is only supported for ARM-based targets, and will
+    /// return [Error::ArchitectureRequired] otherwise.
+    pub fn memory_interface(&mut self, ap: MemoryAp) -> Result<Memory<'_>, Error> {
+        let interface = self.get_arm_interface()?;
+
+        interface.memory_interface(ap)
+    }
+
+    /// Scans all access ports of the debug port `dp` and parses their ROM tables.
+    ///
+    /// In contrast to [`Session::get_arm_components`], this also lists the access ports
+    /// which are not memory APs, and the ROM tables which could not be parsed.
+    pub fn discover_arm_topology(&mut self, dp: DpAddress) -> Result<Topology, Error> {
+        let interface = self.get_arm_interface()?;
+
+        Topology::discover(interface, dp)
+    }
+
     /// Get the target description of the connected target.
     pub fn target(&self) -> &Target {
         &self.target
     }
 
+    /// Stop the watchdogs of the target while its cores are halted, so that stepping through
+    /// the code is not interrupted by watchdog resets, or let them run again.
+    ///
+    /// This uses the debug features of the chip, like the DBGMCU freeze bits of STM32 chips,
+    /// and is ignored for chips without a known way to stop the watchdogs.
+    pub fn set_watchdog_freeze(&mut self, freeze: bool) -> Result<(), Error> {
+        for core in 0..self.cores.len() {
+            self.core(core)?.set_watchdog_freeze(freeze)?;
+        }
+
+        Ok(())
+    }
+
+    /// Enable or disable the cache for reads of the flash of the target.
+    ///
+    /// While the cache is enabled, repeated reads of the same flash memory, e.g. when a debugger
+    /// disassembles the same code again, are served from host memory instead of reading it from
+    /// the target again. The cache is invalidated when the memory is written, when a core is
+    /// reset and when the flash is programmed. The cache is disabled by default.
+    ///
+    /// Other regions which don't change, like ROM tables, can be added with
+    /// [`Session::add_cached_region`].
+    pub fn set_memory_cache_enabled(&mut self, enabled: bool) {
+        for (index, (_, state)) in self.cores.iter_mut().enumerate() {
+            let core_name = &self.target.cores[index].name;
+            let cache = state.memory_cache_mut();
+
+            for region in &self.target.memory_map {
+                if let MemoryRegion::Nvm(region) = region {
+                    if region.cores.contains(core_name) {
+                        cache.add_region(region.range.clone());
+                    }
+                }
+            }
+
+            cache.set_enabled(enabled);
+        }
+    }
+
+    /// Returns `true` if the memory cache is enabled.
+    pub fn memory_cache_enabled(&self) -> bool {
+        self.cores
+            .iter()
+            .any(|(_, state)| state.memory_cache().is_enabled())
+    }
+
+    /// Cache reads of `range` while the memory cache is enabled.
+    ///
+    /// The memory in this range must not change while the target runs, otherwise stale data
+    /// will be read. Writes through the [`Core`] invalidate the cached data.
+    pub fn add_cached_region(&mut self, range: Range<u32>) {
+        for (_, state) in &mut self.cores {
+            state.memory_cache_mut().add_region(range.clone());
+        }
+    }
+
+    /// Discard all cached memory, e.g. after the memory was changed by another bus master.
+    pub fn invalidate_memory_cache(&mut self) {
+        for (_, state) in &mut self.cores {
+            state.memory_cache_mut().invalidate();
+        }
+    }
+
     /// Configure the target and probe for serial wire view (SWV) tracing.
     pub fn setup_swv(&mut self, core_index: usize, config: &SwoConfig) -> Result<(), Error> {
         // Configure SWO on the probe
@@ -451,6 +745,41 @@ impl Session {
         )
     }
 
+    /// Emit the value of every access to the `size` bytes at `address` over SWV, using the
+    /// DWT comparator `unit`.
+    ///
+    /// This allows following a variable without halting the core. The values can be read
+    /// with [`Session::read_swv_data_trace`].
+    pub fn add_swv_data_value_trace(
+        &mut self,
+        core_index: usize,
+        unit: usize,
+        address: u32,
+        size: u32,
+    ) -> Result<(), Error> {
+        let components = self.get_arm_components()?;
+        let mut core = self.core(core_index)?;
+        crate::architecture::arm::component::add_swv_data_value_trace(
+            &mut core,
+            &components,
+            unit,
+            address,
+            size,
+        )
+    }
+
+    /// Read the available SWO data without waiting, and return the values traced by DWT comparators.
+    ///
+    /// The same `decoder` has to be used for all reads, because packets may be split between them.
+    pub fn read_swv_data_trace(
+        &mut self,
+        decoder: &mut DataTraceDecoder,
+    ) -> Result<Vec<DataTraceEvent>, Error> {
+        let data = self.read_swo()?;
+
+        Ok(decoder.feed(&data))
+    }
+
     /// Stop tracing from a given SWV unit
     pub fn remove_swv_data_trace(&mut self, core_index: usize, unit: usize) -> Result<(), Error> {
         let components = self.get_arm_components()?;
@@ -458,10 +787,92 @@ impl Session {
         crate::architecture::arm::component::remove_swv_data_trace(&mut core, &components, unit)
     }
 
-    /// Returns the memory map of the target.
-    #[deprecated = "Use the Session::target function instead"]
-    pub fn memory_map(&self) -> &[MemoryRegion] {
-        &self.target.memory_map
+    /// Enable the ITM stimulus ports set in `ports` and disable all others, and enable or
+    /// disable the timestamps of the ITM packets.
+    ///
+    /// SWV has to be configured with [`Session::setup_swv`] before.
+    pub fn configure_itm(
+        &mut self,
+        core_index: usize,
+        ports: u32,
+        timestamps: bool,
+    ) -> Result<(), Error> {
+        let components = self.get_arm_components()?;
+        let mut core = self.core(core_index)?;
+        crate::architecture::arm::component::configure_itm(
+            &mut core,
+            &components,
+            ports,
+            timestamps,
+        )
+    }
+
+    /// Write `data` from the host to the ITM stimulus port `port`.
+    ///
+    /// The data is sent over SWO like the data written by the target, so host and target
+    /// messages share the same trace timeline. The port has to be enabled.
+    pub fn write_itm_stimulus(
+        &mut self,
+        core_index: usize,
+        port: u8,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        let components = self.get_arm_components()?;
+        let mut core = self.core(core_index)?;
+        crate::architecture::arm::component::write_itm_stimulus(&mut core, &components, port, data)
+    }
+
+    /// Configure the Micro Trace Buffer (MTB) to record the instruction flow of a core.
+    ///
+    /// The MTB is available on some ARMv6-M and ARMv8-M Baseline cores, and records every
+    /// branch into a part of the RAM of the target.
+    pub fn setup_mtb(&mut self, core_index: usize, config: &MtbConfig) -> Result<(), Error> {
+        let components = self.get_arm_components()?;
+        let mut core = self.core(core_index)?;
+        crate::architecture::arm::component::setup_mtb(&mut core, &components, config)
+    }
+
+    /// Stop recording the instruction flow into the MTB.
+    pub fn disable_mtb(&mut self, core_index: usize) -> Result<(), Error> {
+        let components = self.get_arm_components()?;
+        let mut core = self.core(core_index)?;
+        crate::architecture::arm::component::disable_mtb(&mut core, &components)
+    }
+
+    /// Read the branches recorded by the MTB, from the oldest to the newest.
+    ///
+    /// The executed instructions can be reconstructed from them with
+    /// [`reconstruct_flow`](crate::architecture::arm::trace::reconstruct_flow).
+    pub fn read_mtb_trace(&mut self, core_index: usize) -> Result<Vec<MtbPacket>, Error> {
+        let components = self.get_arm_components()?;
+        let mut core = self.core(core_index)?;
+        crate::architecture::arm::component::read_mtb_trace(&mut core, &components)
+    }
+
+    /// Configure the ETM of a core to emit instruction trace to `sink`.
+    ///
+    /// Capturing the trace of the parallel trace port requires a probe which supports it.
+    pub fn setup_etm_trace(&mut self, core_index: usize, sink: TraceSink) -> Result<(), Error> {
+        let components = self.get_arm_components()?;
+        let mut core = self.core(core_index)?;
+        crate::architecture::arm::component::setup_etm_trace(&mut core, &components, sink)
+    }
+
+    /// Stop the instruction trace of the ETM of a core.
+    pub fn disable_etm_trace(&mut self, core_index: usize) -> Result<(), Error> {
+        let components = self.get_arm_components()?;
+        let mut core = self.core(core_index)?;
+        crate::architecture::arm::component::disable_etm_trace(&mut core, &components)
+    }
+
+    /// Read the ETM instruction trace captured by the ETB.
+    ///
+    /// Capturing stops when the trace is read, and can be started again
+    /// with [`Session::setup_etm_trace`].
+    pub fn read_etb_trace(&mut self, core_index: usize) -> Result<Vec<u8>, Error> {
+        let components = self.get_arm_components()?;
+        let mut core = self.core(core_index)?;
+        crate::architecture::arm::component::read_etb_trace(&mut core, &components)
     }
 
     /// Return the `Architecture` of the currently connected chip.
@@ -469,6 +880,7 @@ impl Session {
         match self.interface {
             ArchitectureInterface::Arm(_) => Architecture::Arm,
             ArchitectureInterface::Riscv(_) => Architecture::Riscv,
+            ArchitectureInterface::Xtensa(_) => Architecture::Xtensa,
         }
     }
 
@@ -488,12 +900,17 @@ static_assertions::assert_impl_all!(Session: Send);
 impl Drop for Session {
     fn drop(&mut self) {
         let result = { 0..self.cores.len() }.try_for_each(|i| {
-            self.core(i)
-                .and_then(|mut core| core.clear_all_hw_breakpoints())
+            self.core(i).and_then(|mut core| {
+                core.clear_all_hw_breakpoints()?;
+                core.clear_all_hw_watchpoints()
+            })
         });
 
         if let Err(err) = result {
-            log::warn!("Could not clear all hardware breakpoints: {:?}", err);
+            log::warn!(
+                "Could not clear all hardware breakpoints and watchpoints: {:?}",
+                err
+            );
         }
     }
 }
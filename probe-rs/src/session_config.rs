@@ -0,0 +1,371 @@
+//! The configuration of a debug session, shared by all front-ends.
+//!
+//! A [`SessionConfig`] describes which probe and chip to use and how to connect to them,
+//! as well as the settings of the tools which run on top of the session, e.g. RTT and
+//! the GDB server. It can be built in code, from the arguments of a front-end, or
+//! deserialized from a configuration file.
+
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::config::{RegistryError, TargetSelector};
+use crate::{
+    DebugProbeError, DebugProbeSelector, Error, Permissions, Probe, Session, WireProtocol,
+};
+
+/// The default address of the GDB server.
+const DEFAULT_GDB_CONNECTION: &str = "127.0.0.1:1337";
+
+/// The default time to wait for the RTT control block, in milliseconds.
+const DEFAULT_RTT_TIMEOUT: u64 = 3000;
+
+/// An error while using a [`SessionConfig`].
+#[derive(thiserror::Error, Debug)]
+pub enum SessionConfigError {
+    #[error("The session configuration could not be parsed.")]
+    Parse(#[from] serde_yaml::Error),
+    #[error("The chip description file '{path}' could not be loaded.")]
+    ChipDescription {
+        path: PathBuf,
+        #[source]
+        source: RegistryError,
+    },
+    #[error("No probe was found.")]
+    NoProbeFound,
+    #[error("{0} probes were found, but none was selected.")]
+    MultipleProbesFound(usize),
+    #[error("The probe could not be opened or configured.")]
+    Probe(#[from] DebugProbeError),
+    #[error("Attaching to the target failed.")]
+    Attach(#[source] Error),
+    #[error("'{0}' is not a reset behavior, expected 'halt', 'run' or 'run-to-main'.")]
+    UnknownResetBehavior(String),
+}
+
+/// The configuration of a debug session.
+///
+/// All fields are optional in a configuration file:
+///
+/// ```yaml
+/// chip: nRF52840_xxAA
+/// probe: "1366:1015"
+/// speed: 4000
+/// reset:
+///   halt_afterwards: true
+/// rtt:
+///   enabled: true
+///   channels:
+///     - up: 0
+///       name: Terminal
+/// gdb:
+///   enabled: true
+///   connection: 127.0.0.1:2331
+///   verify: true
+///   reset: run_to_main
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionConfig {
+    /// The name of the chip. The chip is detected automatically if it is `None`.
+    pub chip: Option<String>,
+    /// Chip description files which are added to the registry, for chips which are not
+    /// built into probe-rs.
+    pub chip_descriptions: Vec<PathBuf>,
+    /// The probe to use. If it is `None`, the only connected probe is used.
+    pub probe: Option<DebugProbeSelector>,
+    /// The wire protocol, or the default protocol of the probe if it is `None`.
+    pub protocol: Option<WireProtocol>,
+    /// The protocol speed in kHz, or the default speed of the probe if it is `None`.
+    pub speed: Option<u32>,
+    /// Assert the reset pin while attaching to the target.
+    pub connect_under_reset: bool,
+    /// Erase the whole chip if it is locked and can only be unlocked by erasing it.
+    pub allow_erase_all: bool,
+    /// How the target is reset.
+    pub reset: ResetConfig,
+    /// The RTT settings.
+    pub rtt: RttConfig,
+    /// The GDB server settings.
+    pub gdb: GdbConfig,
+}
+
+/// How the target is reset, see [`SessionConfig::reset`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResetConfig {
+    /// Reset the target after flashing, or after attaching if nothing is flashed.
+    pub enabled: bool,
+    /// Halt the core after the reset.
+    pub halt_afterwards: bool,
+}
+
+impl Default for ResetConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            halt_afterwards: false,
+        }
+    }
+}
+
+/// An RTT up channel which is shown, see [`RttConfig`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RttChannelConfig {
+    /// The number of the up channel.
+    pub up: usize,
+    /// The name which is shown for the channel, instead of the name in the control block.
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// The RTT settings, see [`SessionConfig::rtt`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RttConfig {
+    /// Attach to RTT after the session is started.
+    pub enabled: bool,
+    /// The up channels which are shown. All channels are shown if it is empty.
+    pub channels: Vec<RttChannelConfig>,
+    /// The time to wait for the RTT control block, in milliseconds.
+    pub timeout: u64,
+}
+
+impl RttConfig {
+    /// The time to wait for the RTT control block.
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout)
+    }
+}
+
+impl Default for RttConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            channels: Vec::new(),
+            timeout: DEFAULT_RTT_TIMEOUT,
+        }
+    }
+}
+
+/// The GDB server settings, see [`SessionConfig::gdb`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GdbConfig {
+    /// Start the GDB server after the session is started.
+    pub enabled: bool,
+    /// The address the GDB server listens on.
+    pub connection: String,
+    /// Connect to a GDB or IDE which listens on [`connection`](Self::connection), instead
+    /// of listening for GDB, e.g. if GDB can't reach the server through a firewall.
+    pub connect_back: bool,
+    /// The ELF file which is flashed when the program is restarted with `run` in
+    /// extended-remote mode. If it is `None`, the target is only reset.
+    pub restart_image: Option<PathBuf>,
+    /// Read back the flash after GDB programmed it with `load`, and report an error to
+    /// GDB if it doesn't match.
+    pub verify: bool,
+    /// What `monitor reset` does.
+    pub reset: GdbResetBehavior,
+}
+
+/// What `monitor reset` does in the GDB server, see [`GdbConfig::reset`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GdbResetBehavior {
+    /// Reset the target, and halt it at the reset vector.
+    Halt,
+    /// Reset the target, and let it run.
+    Run,
+    /// Reset the target, and run it until it reaches `main`.
+    ///
+    /// This needs the symbols of the program, see [`Session::load_symbols`]. The target
+    /// is halted at the reset vector if there is no `main`.
+    RunToMain,
+}
+
+impl Default for GdbResetBehavior {
+    fn default() -> Self {
+        GdbResetBehavior::Halt
+    }
+}
+
+impl FromStr for GdbResetBehavior {
+    type Err = SessionConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "halt" => Ok(GdbResetBehavior::Halt),
+            "run" => Ok(GdbResetBehavior::Run),
+            "run-to-main" => Ok(GdbResetBehavior::RunToMain),
+            other => Err(SessionConfigError::UnknownResetBehavior(other.to_owned())),
+        }
+    }
+}
+
+impl Default for GdbConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            connection: DEFAULT_GDB_CONNECTION.to_owned(),
+            connect_back: false,
+            restart_image: None,
+            verify: false,
+            reset: GdbResetBehavior::default(),
+        }
+    }
+}
+
+impl SessionConfig {
+    /// Parse a configuration from YAML.
+    pub fn from_yaml(yaml: &str) -> Result<Self, SessionConfigError> {
+        Ok(serde_yaml::from_str(yaml)?)
+    }
+
+    /// Add the targets of [`chip_descriptions`](Self::chip_descriptions) to the registry.
+    ///
+    /// This has to be called before the chip is selected, e.g. by [`attach`](Self::attach).
+    pub fn load_chip_descriptions(&self) -> Result<(), SessionConfigError> {
+        for path in &self.chip_descriptions {
+            crate::config::add_target_from_yaml(path).map_err(|source| {
+                SessionConfigError::ChipDescription {
+                    path: path.clone(),
+                    source,
+                }
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// The target selector of the configured chip.
+    pub fn target_selector(&self) -> TargetSelector {
+        match &self.chip {
+            Some(chip) => TargetSelector::Unspecified(chip.clone()),
+            None => TargetSelector::Auto,
+        }
+    }
+
+    /// Open the configured probe, and set its protocol and speed.
+    pub fn open_probe(&self) -> Result<Probe, SessionConfigError> {
+        let mut probe = match &self.probe {
+            Some(selector) => Probe::open(selector.clone())?,
+            None => {
+                let probes = Probe::list_all();
+
+                match probes.as_slice() {
+                    [] => return Err(SessionConfigError::NoProbeFound),
+                    [probe] => probe.open()?,
+                    _ => return Err(SessionConfigError::MultipleProbesFound(probes.len())),
+                }
+            }
+        };
+
+        if let Some(protocol) = self.protocol {
+            probe.select_protocol(protocol)?;
+        }
+
+        if let Some(speed) = self.speed {
+            let actual_speed = probe.set_speed(speed)?;
+
+            if actual_speed != speed {
+                log::warn!(
+                    "The protocol speed was set to {} kHz instead of {} kHz.",
+                    actual_speed,
+                    speed
+                );
+            }
+        }
+
+        Ok(probe)
+    }
+
+    /// The [`Permissions`] which are granted when attaching to the target.
+    pub fn permissions(&self) -> Permissions {
+        if self.allow_erase_all {
+            Permissions::new().allow_erase_all()
+        } else {
+            Permissions::new()
+        }
+    }
+
+    /// Load the chip descriptions, open the probe and attach to the target.
+    ///
+    /// The target is not reset, see [`reset`](Self::reset).
+    pub fn attach(&self) -> Result<Session, SessionConfigError> {
+        self.load_chip_descriptions()?;
+
+        let probe = self.open_probe()?;
+
+        if self.connect_under_reset {
+            probe.attach_under_reset_with_permissions(self.target_selector(), self.permissions())
+        } else {
+            probe.attach_with_permissions(self.target_selector(), self.permissions())
+        }
+        .map_err(SessionConfigError::Attach)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{GdbResetBehavior, RttChannelConfig, SessionConfig};
+    use crate::{Permissions, WireProtocol};
+
+    #[test]
+    fn parse_config() {
+        let yaml = "
+chip: nRF52840_xxAA
+probe: \"1366:1015\"
+protocol: Swd
+rtt:
+  enabled: true
+  channels:
+    - up: 0
+      name: Terminal
+gdb:
+  enabled: true
+  reset: run_to_main
+";
+
+        let config = SessionConfig::from_yaml(yaml).unwrap();
+
+        assert_eq!(config.chip.as_deref(), Some("nRF52840_xxAA"));
+        assert_eq!(config.probe.as_ref().unwrap().vendor_id, 0x1366);
+        assert_eq!(config.protocol, Some(WireProtocol::Swd));
+        assert_eq!(config.speed, None);
+
+        // Unspecified settings keep their defaults.
+        assert!(config.reset.enabled);
+        assert!(!config.reset.halt_afterwards);
+        assert_eq!(config.rtt.timeout, 3000);
+        assert_eq!(
+            config.rtt.channels,
+            vec![RttChannelConfig {
+                up: 0,
+                name: Some("Terminal".to_owned())
+            }]
+        );
+        assert!(config.gdb.enabled);
+        assert_eq!(config.gdb.connection, "127.0.0.1:1337");
+        assert!(!config.gdb.connect_back);
+        assert_eq!(config.gdb.reset, GdbResetBehavior::RunToMain);
+        assert!(!config.allow_erase_all);
+        assert_eq!(config.permissions(), Permissions::new());
+
+        // The probe selector is written in the same format.
+        let config = SessionConfig::from_yaml(&serde_yaml::to_string(&config).unwrap()).unwrap();
+        assert_eq!(config.probe.unwrap().to_string(), "1366:1015");
+    }
+
+    #[test]
+    fn erase_all_is_only_permitted_explicitly() {
+        let config = SessionConfig::from_yaml("allow_erase_all: true").unwrap();
+
+        assert_eq!(config.permissions(), Permissions::new().allow_erase_all());
+        assert!(config.permissions().erase_all().is_ok());
+        assert!(matches!(
+            SessionConfig::default().permissions().erase_all(),
+            Err(crate::Error::MissingPermissions(_))
+        ));
+    }
+}
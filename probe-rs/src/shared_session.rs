@@ -0,0 +1,214 @@
+#![warn(missing_docs)]
+
+//! A [`Session`] which can be shared between threads.
+//!
+//! Most operations on a [`Session`] require exclusive access. [`SharedSession`] wraps a
+//! session into a lock, so that e.g. RTT polling, a GDB server and flashing can run on
+//! different threads. Threads waiting for the session are served in the order of their
+//! [`Priority`], and in the order of their requests for the same priority, so a thread
+//! polling in a loop cannot starve the others.
+
+use crate::{Core, Error, Session};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+/// The priority of an operation on a [`SharedSession`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Operations which can be delayed, e.g. polling for RTT data.
+    Background,
+    /// Regular operations.
+    Normal,
+    /// Operations which should be done as fast as possible, e.g. halting a core on user request.
+    High,
+}
+
+/// Lock state, which grants access in order of priority, and in order of arrival
+/// for requests of the same priority.
+#[derive(Debug, Default)]
+struct FairLockState {
+    locked: bool,
+    next_ticket: u64,
+    waiting: BinaryHeap<(Priority, Reverse<u64>)>,
+}
+
+#[derive(Debug, Default)]
+struct FairLock {
+    state: Mutex<FairLockState>,
+    released: Condvar,
+}
+
+impl FairLock {
+    fn state(&self) -> MutexGuard<'_, FairLockState> {
+        match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn acquire(&self, priority: Priority) {
+        let mut state = self.state();
+
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        state.waiting.push((priority, Reverse(ticket)));
+
+        while state.locked || state.waiting.peek() != Some(&(priority, Reverse(ticket))) {
+            state = match self.released.wait(state) {
+                Ok(state) => state,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+        }
+
+        state.waiting.pop();
+        state.locked = true;
+    }
+
+    fn release(&self) {
+        self.state().locked = false;
+        self.released.notify_all();
+    }
+}
+
+#[derive(Debug)]
+struct Shared {
+    lock: FairLock,
+    session: Mutex<Session>,
+}
+
+/// A [`Session`] which can be shared between threads.
+///
+/// Cloning a `SharedSession` creates a new handle to the same session.
+#[derive(Debug, Clone)]
+pub struct SharedSession {
+    shared: Arc<Shared>,
+}
+
+impl SharedSession {
+    /// Wrap the given session.
+    pub fn new(session: Session) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                lock: FairLock::default(),
+                session: Mutex::new(session),
+            }),
+        }
+    }
+
+    /// Wait for exclusive access to the session.
+    ///
+    /// The session is released when the returned guard is dropped. The guard should
+    /// be kept only as long as necessary, so that other threads can access the session.
+    pub fn lock(&self, priority: Priority) -> SessionGuard<'_> {
+        self.shared.lock.acquire(priority);
+
+        // The fair lock guarantees that the mutex is not held by anyone else. If a thread
+        // panicked while holding the session, it can still be used.
+        let session = match self.shared.session.lock() {
+            Ok(session) => session,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        SessionGuard {
+            session: Some(session),
+            lock: &self.shared.lock,
+        }
+    }
+
+    /// Run `operation` with exclusive access to the core with the given id.
+    pub fn with_core<T>(
+        &self,
+        core: usize,
+        priority: Priority,
+        operation: impl FnOnce(&mut Core<'_>) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let mut session = self.lock(priority);
+        let mut core = session.core(core)?;
+
+        operation(&mut core)
+    }
+}
+
+/// Exclusive access to a [`SharedSession`], returned by [`SharedSession::lock`].
+pub struct SessionGuard<'a> {
+    session: Option<MutexGuard<'a, Session>>,
+    lock: &'a FairLock,
+}
+
+impl Deref for SessionGuard<'_> {
+    type Target = Session;
+
+    fn deref(&self) -> &Session {
+        // note(unwrap): only taken in drop
+        self.session.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for SessionGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Session {
+        // note(unwrap): only taken in drop
+        self.session.as_mut().unwrap()
+    }
+}
+
+impl Drop for SessionGuard<'_> {
+    fn drop(&mut self) {
+        // Unlock the session before granting access to the next thread.
+        self.session.take();
+        self.lock.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FairLock, Priority};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    fn wait_for_waiters(lock: &FairLock, count: usize) {
+        while lock.state().waiting.len() < count {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn lock_is_granted_by_priority_and_order() {
+        let lock = Arc::new(FairLock::default());
+        let order = Arc::new(Mutex::new(Vec::new()));
+
+        lock.acquire(Priority::Normal);
+
+        let mut threads = Vec::new();
+
+        let priorities = vec![
+            Priority::Background,
+            Priority::Normal,
+            Priority::Normal,
+            Priority::High,
+        ];
+
+        for (index, priority) in priorities.into_iter().enumerate() {
+            let thread_lock = lock.clone();
+            let order = order.clone();
+
+            threads.push(thread::spawn(move || {
+                thread_lock.acquire(priority);
+                order.lock().unwrap().push(index);
+                thread_lock.release();
+            }));
+
+            wait_for_waiters(&lock, index + 1);
+        }
+
+        lock.release();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(*order.lock().unwrap(), vec![3, 1, 2, 0]);
+    }
+}
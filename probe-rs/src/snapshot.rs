@@ -0,0 +1,212 @@
+#![warn(missing_docs)]
+
+//! Snapshots of the target state.
+//!
+//! A [`SessionSnapshot`] captures the registers of all cores of a [`Session`], together
+//! with the contents of selected memory regions. It can be restored later on, e.g. to
+//! bring the target into a well known state before running a test, or to go back to an
+//! earlier point of execution during debugging.
+//!
+//! Snapshots can be serialized with `serde`, so they can be stored and restored in a
+//! different session.
+
+use crate::{Architecture, Core, CoreRegisterAddress, Error, MemoryInterface, Session};
+use std::ops::Range;
+
+/// The captured state of a single core.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CoreSnapshot {
+    /// The id of the core in the [`Session`].
+    pub id: usize,
+    /// The captured register values.
+    ///
+    /// The registers are restored in the order in which they appear here.
+    pub registers: Vec<(CoreRegisterAddress, u32)>,
+}
+
+/// The captured state of all cores of a [`Session`] and of selected memory regions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionSnapshot {
+    /// The state of all cores.
+    pub cores: Vec<CoreSnapshot>,
+    /// The captured memory regions, each consisting of the start address and the data.
+    pub memory: Vec<(u32, Vec<u8>)>,
+}
+
+/// The registers which are part of a snapshot.
+///
+/// The order is chosen so that restoring them in order results in the
+/// correct state, e.g. on ARM the `CONTROL` register, which selects the
+/// active stack pointer, is written before the stack pointers.
+fn snapshot_registers(core: &Core) -> Vec<CoreRegisterAddress> {
+    match core.architecture() {
+        Architecture::Arm => {
+            // CONTROL/FAULTMASK/BASEPRI/PRIMASK, MSP, PSP
+            let special = [0b1_0100, 0b1_0001, 0b1_0010];
+            // R0-R15, XPSR
+            special
+                .iter()
+                .copied()
+                .chain(0..=0b1_0000)
+                .map(CoreRegisterAddress)
+                .collect()
+        }
+        Architecture::Riscv => {
+            // x1-x31, the PC
+            (1..32)
+                .map(|i| CoreRegisterAddress(0x1000 + i))
+                .chain(std::iter::once(core.registers().program_counter().into()))
+                .collect()
+        }
+        Architecture::Xtensa => {
+            // WINDOWBASE, WINDOWSTART, SAR, PS
+            let special = [0x148, 0x149, 0x103, 0x1c6];
+            // All physical address registers, the PC
+            special
+                .iter()
+                .copied()
+                .chain(0x200..0x240)
+                .chain(std::iter::once(
+                    core.registers().program_counter().address.0,
+                ))
+                .map(CoreRegisterAddress)
+                .collect()
+        }
+    }
+}
+
+impl Session {
+    /// Capture the registers of all cores, and the contents of the given memory regions.
+    ///
+    /// All cores have to be halted, otherwise an error is returned.
+    pub fn snapshot(&mut self, regions: &[Range<u32>]) -> Result<SessionSnapshot, Error> {
+        let mut cores = Vec::new();
+
+        for id in 0..self.list_cores().len() {
+            let mut core = self.core(id)?;
+
+            if !core.core_halted()? {
+                return Err(Error::CoreNotHalted(id));
+            }
+
+            let mut registers = Vec::new();
+
+            for address in snapshot_registers(&core) {
+                registers.push((address, core.read_core_reg(address)?));
+            }
+
+            cores.push(CoreSnapshot { id, registers });
+        }
+
+        let mut memory = Vec::with_capacity(regions.len());
+
+        {
+            let mut core = self.core(0)?;
+
+            for region in regions {
+                let mut data = vec![0u8; region.len()];
+                core.read_8(region.start, &mut data)?;
+
+                memory.push((region.start, data));
+            }
+        }
+
+        Ok(SessionSnapshot { cores, memory })
+    }
+
+    /// Restore a snapshot taken with [`Session::snapshot`].
+    ///
+    /// The memory regions are restored first, then the registers of all cores.
+    /// All cores contained in the snapshot have to be halted, otherwise an error is returned.
+    pub fn restore(&mut self, snapshot: &SessionSnapshot) -> Result<(), Error> {
+        for core_snapshot in &snapshot.cores {
+            let mut core = self.core(core_snapshot.id)?;
+
+            if !core.core_halted()? {
+                return Err(Error::CoreNotHalted(core_snapshot.id));
+            }
+        }
+
+        {
+            let mut core = self.core(0)?;
+
+            for (address, data) in &snapshot.memory {
+                core.write_8(*address, data)?;
+            }
+
+            core.flush()?;
+        }
+
+        for core_snapshot in &snapshot.cores {
+            let mut core = self.core(core_snapshot.id)?;
+
+            for (address, value) in &core_snapshot.registers {
+                core.write_core_reg(*address, *value)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{snapshot_registers, CoreSnapshot, SessionSnapshot};
+    use crate::{Architecture, Core, CoreRegisterAddress, MockCore};
+
+    #[test]
+    fn arm_registers_are_restored_after_control() {
+        let mut state = Core::create_state(0);
+        let core = Core::new(MockCore::new(Architecture::Arm), &mut state);
+
+        let registers = snapshot_registers(&core);
+
+        // CONTROL selects the active stack pointer, so it is written before MSP and PSP,
+        // and before SP (R13), which is an alias of the active one.
+        assert_eq!(
+            &registers[..3],
+            &[
+                CoreRegisterAddress(0b1_0100),
+                CoreRegisterAddress(0b1_0001),
+                CoreRegisterAddress(0b1_0010)
+            ]
+        );
+        assert_eq!(registers[3], CoreRegisterAddress(0));
+        assert_eq!(registers.last(), Some(&CoreRegisterAddress(0b1_0000)));
+        assert_eq!(registers.len(), 3 + 17);
+    }
+
+    #[test]
+    fn riscv_registers_skip_x0() {
+        let mut state = Core::create_state(0);
+        let core = Core::new(MockCore::new(Architecture::Riscv), &mut state);
+
+        let registers = snapshot_registers(&core);
+
+        assert_eq!(registers.first(), Some(&CoreRegisterAddress(0x1001)));
+        assert!(!registers.contains(&CoreRegisterAddress(0x1000)));
+        assert_eq!(
+            registers.last(),
+            Some(&core.registers().program_counter().into())
+        );
+    }
+
+    #[test]
+    fn serialize_snapshot() {
+        let snapshot = SessionSnapshot {
+            cores: vec![CoreSnapshot {
+                id: 0,
+                registers: vec![
+                    (CoreRegisterAddress(0b1_0100), 0x2),
+                    (CoreRegisterAddress(15), 0x0800_0100),
+                ],
+            }],
+            memory: vec![(0x2000_0000, vec![0xde, 0xad, 0xbe, 0xef])],
+        };
+
+        let serialized = serde_json::to_string(&snapshot).unwrap();
+        let deserialized: SessionSnapshot = serde_json::from_str(&serialized).unwrap();
+
+        assert_eq!(deserialized, snapshot);
+    }
+}
@@ -0,0 +1,238 @@
+//! Access to the registers described by an SVD file.
+
+use std::fmt;
+use std::path::Path;
+
+use super::{Device, Peripheral, Register, SvdError};
+use crate::{MemoryInterface, Session};
+
+impl Session {
+    /// Load the SVD file at `path`, and attach it to the session, replacing any
+    /// previously loaded file.
+    pub fn load_svd<P: AsRef<Path>>(&mut self, path: P) -> Result<(), SvdError> {
+        self.set_svd(Device::from_file(path)?);
+
+        Ok(())
+    }
+
+    /// Attach the description of the peripherals of the target to the session.
+    pub fn set_svd(&mut self, device: Device) {
+        self.svd = Some(device);
+    }
+
+    /// The description of the peripherals attached to the session, if any.
+    pub fn svd(&self) -> Option<&Device> {
+        self.svd.as_ref()
+    }
+
+    /// Access the peripheral `name`, as described by the attached SVD file.
+    ///
+    /// The name is compared case insensitively.
+    pub fn peripheral(&mut self, name: &str) -> Result<PeripheralAccess<'_>, SvdError> {
+        let device = self.svd.as_ref().ok_or(SvdError::NotLoaded)?;
+
+        let peripheral = device
+            .peripherals
+            .iter()
+            .position(|peripheral| peripheral.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| SvdError::PeripheralNotFound(name.to_owned()))?;
+
+        Ok(PeripheralAccess {
+            session: self,
+            peripheral,
+        })
+    }
+}
+
+/// A peripheral of the target, returned by [`Session::peripheral`].
+pub struct PeripheralAccess<'session> {
+    session: &'session mut Session,
+    peripheral: usize,
+}
+
+impl<'session> PeripheralAccess<'session> {
+    /// The description of the peripheral.
+    pub fn peripheral(&self) -> &Peripheral {
+        peripheral(self.session, self.peripheral)
+    }
+
+    /// Access the register `name` of the peripheral.
+    ///
+    /// The name is compared case insensitively. Registers in clusters are named with the name
+    /// of the cluster as prefix, e.g. `CH0.CR`.
+    pub fn register(self, name: &str) -> Result<RegisterAccess<'session>, SvdError> {
+        let peripheral = self.peripheral();
+
+        let register = peripheral
+            .registers
+            .iter()
+            .position(|register| register.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| SvdError::RegisterNotFound {
+                peripheral: peripheral.name.clone(),
+                register: name.to_owned(),
+            })?;
+
+        Ok(RegisterAccess {
+            session: self.session,
+            peripheral: self.peripheral,
+            register,
+            core: 0,
+        })
+    }
+}
+
+/// A register of a peripheral, returned by [`PeripheralAccess::register`].
+///
+/// The register is accessed through core 0, unless another core is selected with
+/// [`RegisterAccess::on_core`].
+pub struct RegisterAccess<'session> {
+    session: &'session mut Session,
+    peripheral: usize,
+    register: usize,
+    core: usize,
+}
+
+impl<'session> RegisterAccess<'session> {
+    /// Access the register through the core `core`.
+    pub fn on_core(mut self, core: usize) -> Self {
+        self.core = core;
+        self
+    }
+
+    /// The description of the register.
+    pub fn register(&self) -> &Register {
+        &peripheral(self.session, self.peripheral).registers[self.register]
+    }
+
+    /// The address of the register.
+    pub fn address(&self) -> u32 {
+        let peripheral = peripheral(self.session, self.peripheral);
+
+        peripheral.base_address + peripheral.registers[self.register].address_offset
+    }
+
+    /// Read the value of the register.
+    pub fn read_raw(&mut self) -> Result<u32, SvdError> {
+        let address = self.address();
+        let size = self.register().size;
+        let mut core = self.session.core(self.core)?;
+
+        let value = match size {
+            32 => core.read_word_32(address)?,
+            16 => {
+                let mut bytes = [0u8; 2];
+                core.read_8(address, &mut bytes)?;
+                u16::from_le_bytes(bytes) as u32
+            }
+            8 => core.read_word_8(address)? as u32,
+            size => return Err(SvdError::UnsupportedSize(size)),
+        };
+
+        Ok(value)
+    }
+
+    /// Read the value of the register, and decode its fields.
+    pub fn read(&mut self) -> Result<RegisterValue, SvdError> {
+        let value = self.read_raw()?;
+        let register = self.register();
+
+        Ok(RegisterValue {
+            register: register.name.clone(),
+            address: self.address(),
+            value,
+            fields: register
+                .fields
+                .iter()
+                .map(|field| {
+                    let field_value = field.extract(value);
+
+                    FieldValue {
+                        name: field.name.clone(),
+                        value: field_value,
+                        value_name: field.value_name(field_value).map(str::to_owned),
+                    }
+                })
+                .collect(),
+        })
+    }
+
+    /// Write `value` to the register.
+    pub fn write(&mut self, value: u32) -> Result<(), SvdError> {
+        let address = self.address();
+        let size = self.register().size;
+        let mut core = self.session.core(self.core)?;
+
+        match size {
+            32 => core.write_word_32(address, value)?,
+            16 => core.write_8(address, &(value as u16).to_le_bytes())?,
+            8 => core.write_word_8(address, value as u8)?,
+            size => return Err(SvdError::UnsupportedSize(size)),
+        }
+
+        Ok(())
+    }
+
+    /// Change the field `name` of the register to `value`, keeping the other fields.
+    pub fn write_field(&mut self, name: &str, value: u32) -> Result<(), SvdError> {
+        let register = self.register();
+        let field = register
+            .field(name)
+            .ok_or_else(|| SvdError::FieldNotFound {
+                register: register.name.clone(),
+                field: name.to_owned(),
+            })?
+            .clone();
+
+        let current = self.read_raw()?;
+        self.write(field.insert(current, value))
+    }
+}
+
+fn peripheral(session: &Session, index: usize) -> &Peripheral {
+    // The SVD file can not be replaced while a peripheral is accessed.
+    &session
+        .svd
+        .as_ref()
+        .expect("The SVD file was removed")
+        .peripherals[index]
+}
+
+/// The decoded value of a register, returned by [`RegisterAccess::read`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegisterValue {
+    /// The name of the register.
+    pub register: String,
+    /// The address of the register.
+    pub address: u32,
+    /// The value of the register.
+    pub value: u32,
+    /// The values of the fields of the register.
+    pub fields: Vec<FieldValue>,
+}
+
+/// The value of a field of a [`RegisterValue`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldValue {
+    /// The name of the field.
+    pub name: String,
+    /// The value of the field.
+    pub value: u32,
+    /// The name of the value, if the SVD file defines one.
+    pub value_name: Option<String>,
+}
+
+impl fmt::Display for RegisterValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} = {:#010x}", self.register, self.value)?;
+
+        for field in &self.fields {
+            write!(f, "\n  {} = {:#x}", field.name, field.value)?;
+
+            if let Some(name) = &field.value_name {
+                write!(f, " ({})", name)?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,562 @@
+//! Descriptions of the peripheral registers of a device, read from CMSIS-SVD files.
+//!
+//! A [`Device`] is attached to a session with [`Session::load_svd`](crate::Session::load_svd),
+//! and then registers can be read and decoded by name:
+//!
+//! ```no_run
+//! # use probe_rs::Session;
+//! # let mut session = Session::auto_attach("STM32F411RETx")?;
+//! session.load_svd("STM32F411.svd")?;
+//!
+//! let value = session.peripheral("USART1")?.register("SR")?.read()?;
+//! println!("{}", value);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! This module is only available with the `svd` feature.
+
+mod access;
+
+use std::path::Path;
+
+use roxmltree::Node;
+
+pub use access::{FieldValue, PeripheralAccess, RegisterAccess, RegisterValue};
+
+/// An error which occurred while loading an SVD file, or while accessing a register.
+#[derive(Debug, thiserror::Error)]
+pub enum SvdError {
+    /// The SVD file could not be read.
+    #[error("The SVD file could not be read")]
+    Io(#[from] std::io::Error),
+    /// The SVD file is not valid XML.
+    #[error("The SVD file is not valid XML")]
+    Xml(#[from] roxmltree::Error),
+    /// A required element is missing.
+    #[error("The element `{element}` is missing in `{parent}`")]
+    MissingElement {
+        /// The name of the missing element.
+        element: &'static str,
+        /// The name of the element which should contain it.
+        parent: String,
+    },
+    /// A number could not be parsed.
+    #[error("`{0}` is not a valid number")]
+    InvalidNumber(String),
+    /// No SVD file is attached to the session.
+    #[error("No SVD file is loaded")]
+    NotLoaded,
+    /// The device has no peripheral of this name.
+    #[error("The peripheral `{0}` does not exist")]
+    PeripheralNotFound(String),
+    /// The peripheral has no register of this name.
+    #[error("The peripheral `{peripheral}` has no register `{register}`")]
+    RegisterNotFound {
+        /// The name of the peripheral.
+        peripheral: String,
+        /// The name of the register.
+        register: String,
+    },
+    /// The register has no field of this name.
+    #[error("The register `{register}` has no field `{field}`")]
+    FieldNotFound {
+        /// The name of the register.
+        register: String,
+        /// The name of the field.
+        field: String,
+    },
+    /// The size of the register is not supported.
+    #[error("Registers with a size of {0} bits are not supported")]
+    UnsupportedSize(u32),
+    /// An error occurred while accessing the register.
+    #[error("The register could not be accessed")]
+    Probe(#[from] crate::Error),
+}
+
+/// A device described by an SVD file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Device {
+    /// The name of the device.
+    pub name: String,
+    /// The peripherals of the device.
+    pub peripherals: Vec<Peripheral>,
+}
+
+/// A peripheral of a [`Device`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Peripheral {
+    /// The name of the peripheral.
+    pub name: String,
+    /// The description of the peripheral.
+    pub description: Option<String>,
+    /// The address of the peripheral, to which the offsets of the registers are relative.
+    pub base_address: u32,
+    /// The registers of the peripheral.
+    ///
+    /// Registers in clusters are included, with the name of the cluster as prefix, e.g. `CH0.CR`.
+    pub registers: Vec<Register>,
+}
+
+impl Peripheral {
+    /// The register with the name `name`.
+    pub fn register(&self, name: &str) -> Option<&Register> {
+        self.registers
+            .iter()
+            .find(|register| register.name.eq_ignore_ascii_case(name))
+    }
+}
+
+/// A register of a [`Peripheral`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Register {
+    /// The name of the register.
+    pub name: String,
+    /// The description of the register.
+    pub description: Option<String>,
+    /// The offset of the register from the base address of the peripheral.
+    pub address_offset: u32,
+    /// The size of the register in bits.
+    pub size: u32,
+    /// The value of the register after a reset.
+    pub reset_value: Option<u32>,
+    /// The bit fields of the register.
+    pub fields: Vec<Field>,
+}
+
+impl Register {
+    /// The field with the name `name`.
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        self.fields
+            .iter()
+            .find(|field| field.name.eq_ignore_ascii_case(name))
+    }
+}
+
+/// A bit field of a [`Register`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    /// The name of the field.
+    pub name: String,
+    /// The description of the field.
+    pub description: Option<String>,
+    /// The position of the lowest bit of the field.
+    pub bit_offset: u32,
+    /// The number of bits of the field.
+    pub bit_width: u32,
+    /// The names of the values of the field.
+    pub enumerated_values: Vec<EnumeratedValue>,
+}
+
+impl Field {
+    /// The value of the field in the register value `value`.
+    pub fn extract(&self, value: u32) -> u32 {
+        (value >> self.bit_offset) & self.mask()
+    }
+
+    /// Replace the value of the field in the register value `value` with `field`.
+    pub fn insert(&self, value: u32, field: u32) -> u32 {
+        (value & !(self.mask() << self.bit_offset)) | ((field & self.mask()) << self.bit_offset)
+    }
+
+    /// The name of the field value `value`, if it has one.
+    pub fn value_name(&self, value: u32) -> Option<&str> {
+        self.enumerated_values
+            .iter()
+            .find(|enumerated| enumerated.value == value)
+            .map(|enumerated| enumerated.name.as_str())
+    }
+
+    fn mask(&self) -> u32 {
+        if self.bit_width >= 32 {
+            u32::MAX
+        } else {
+            (1 << self.bit_width) - 1
+        }
+    }
+}
+
+/// A named value of a [`Field`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnumeratedValue {
+    /// The name of the value.
+    pub name: String,
+    /// The description of the value.
+    pub description: Option<String>,
+    /// The value.
+    pub value: u32,
+}
+
+impl Device {
+    /// Read a device from the SVD file at `path`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, SvdError> {
+        let content = std::fs::read_to_string(path)?;
+
+        Self::parse(&content)
+    }
+
+    /// Parse the content of an SVD file.
+    pub fn parse(svd: &str) -> Result<Self, SvdError> {
+        let document = roxmltree::Document::parse(svd)?;
+        let root = document.root_element();
+
+        let defaults = Defaults::default().inherit(root)?;
+
+        let mut peripherals = Vec::new();
+
+        if let Some(list) = child(root, "peripherals") {
+            for peripheral in children(list, "peripheral") {
+                peripherals.push(parse_peripheral(peripheral, &peripherals, &defaults)?);
+            }
+        }
+
+        Ok(Self {
+            name: required(root, "name")?.to_owned(),
+            peripherals,
+        })
+    }
+
+    /// The peripheral with the name `name`.
+    pub fn peripheral(&self, name: &str) -> Option<&Peripheral> {
+        self.peripherals
+            .iter()
+            .find(|peripheral| peripheral.name.eq_ignore_ascii_case(name))
+    }
+}
+
+/// The register properties which are inherited from the enclosing elements.
+#[derive(Debug, Clone, Copy)]
+struct Defaults {
+    size: u32,
+    reset_value: Option<u32>,
+}
+
+impl Default for Defaults {
+    fn default() -> Self {
+        Self {
+            size: 32,
+            reset_value: None,
+        }
+    }
+}
+
+impl Defaults {
+    fn inherit(self, element: Node) -> Result<Self, SvdError> {
+        Ok(Self {
+            size: optional_number(element, "size")?.unwrap_or(self.size),
+            reset_value: optional_number(element, "resetValue")?.or(self.reset_value),
+        })
+    }
+}
+
+fn parse_peripheral(
+    element: Node,
+    previous: &[Peripheral],
+    defaults: &Defaults,
+) -> Result<Peripheral, SvdError> {
+    let name = required(element, "name")?.to_owned();
+    let defaults = defaults.inherit(element)?;
+
+    let mut registers = Vec::new();
+    if let Some(list) = child(element, "registers") {
+        parse_registers(list, "", 0, &defaults, &mut registers)?;
+    }
+
+    // A derived peripheral uses the registers of the other one, unless it has its own.
+    let derived = element
+        .attribute("derivedFrom")
+        .and_then(|base| previous.iter().find(|peripheral| peripheral.name == base));
+
+    if let Some(base) = derived {
+        if registers.is_empty() {
+            registers = base.registers.clone();
+        }
+    }
+
+    Ok(Peripheral {
+        base_address: required_number(element, "baseAddress")?,
+        description: child_text(element, "description")
+            .map(str::to_owned)
+            .or_else(|| derived.and_then(|base| base.description.clone())),
+        name,
+        registers,
+    })
+}
+
+/// Parse the registers and clusters of `element`, adding `prefix` to the names and `offset` to
+/// the address offsets.
+fn parse_registers(
+    element: Node,
+    prefix: &str,
+    offset: u32,
+    defaults: &Defaults,
+    registers: &mut Vec<Register>,
+) -> Result<(), SvdError> {
+    for node in element.children().filter(Node::is_element) {
+        match node.tag_name().name() {
+            "register" => {
+                let defaults = defaults.inherit(node)?;
+                let fields = match child(node, "fields") {
+                    Some(fields) => children(fields, "field")
+                        .map(parse_field)
+                        .collect::<Result<_, _>>()?,
+                    None => Vec::new(),
+                };
+
+                for (name, element_offset) in dim_instances(node)? {
+                    registers.push(Register {
+                        name: format!("{}{}", prefix, name),
+                        description: child_text(node, "description").map(str::to_owned),
+                        address_offset: offset
+                            + required_number(node, "addressOffset")?
+                            + element_offset,
+                        size: defaults.size,
+                        reset_value: defaults.reset_value,
+                        fields: fields.clone(),
+                    });
+                }
+            }
+            "cluster" => {
+                let defaults = defaults.inherit(node)?;
+
+                for (name, element_offset) in dim_instances(node)? {
+                    parse_registers(
+                        node,
+                        &format!("{}{}.", prefix, name),
+                        offset + required_number(node, "addressOffset")? + element_offset,
+                        &defaults,
+                        registers,
+                    )?;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// The names and additional offsets of the instances of a register or cluster array.
+///
+/// Elements without `dim` have a single instance.
+fn dim_instances(element: Node) -> Result<Vec<(String, u32)>, SvdError> {
+    let name = required(element, "name")?;
+
+    let dim = match optional_number(element, "dim")? {
+        Some(dim) => dim,
+        None => return Ok(vec![(name.to_owned(), 0)]),
+    };
+    let increment = required_number(element, "dimIncrement")?;
+
+    let indices: Vec<String> = match child_text(element, "dimIndex") {
+        Some(list) => match list.split_once('-') {
+            Some((start, end)) if !list.contains(',') => {
+                match (start.trim().parse::<u32>(), end.trim().parse::<u32>()) {
+                    (Ok(start), Ok(end)) => (start..=end).map(|i| i.to_string()).collect(),
+                    _ => vec![list.to_owned()],
+                }
+            }
+            _ => list
+                .split(',')
+                .map(|index| index.trim().to_owned())
+                .collect(),
+        },
+        None => (0..dim).map(|i| i.to_string()).collect(),
+    };
+
+    Ok(indices
+        .iter()
+        .enumerate()
+        .map(|(i, index)| (name.replace("%s", index), i as u32 * increment))
+        .collect())
+}
+
+fn parse_field(element: Node) -> Result<Field, SvdError> {
+    let (bit_offset, bit_width) = if let Some(offset) = optional_number(element, "bitOffset")? {
+        (offset, optional_number(element, "bitWidth")?.unwrap_or(1))
+    } else if let Some(lsb) = optional_number(element, "lsb")? {
+        (lsb, required_number(element, "msb")? - lsb + 1)
+    } else {
+        let range = required(element, "bitRange")?;
+        let (msb, lsb) = range
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .split_once(':')
+            .ok_or_else(|| SvdError::InvalidNumber(range.to_owned()))?;
+        let (msb, lsb) = (parse_number(msb)?, parse_number(lsb)?);
+
+        (lsb, msb - lsb + 1)
+    };
+
+    let mut enumerated_values = Vec::new();
+    for list in children(element, "enumeratedValues") {
+        for value in children(list, "enumeratedValue") {
+            // Values with "don't care" bits and default values are not supported.
+            let number = match child_text(value, "value").map(parse_number) {
+                Some(Ok(number)) => number,
+                _ => continue,
+            };
+
+            enumerated_values.push(EnumeratedValue {
+                name: required(value, "name")?.to_owned(),
+                description: child_text(value, "description").map(str::to_owned),
+                value: number,
+            });
+        }
+    }
+
+    Ok(Field {
+        name: required(element, "name")?.to_owned(),
+        description: child_text(element, "description").map(str::to_owned),
+        bit_offset,
+        bit_width,
+        enumerated_values,
+    })
+}
+
+/// The first child element of `element` with the name `name`.
+fn child<'a, 'input>(element: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    element.children().find(|child| child.has_tag_name(name))
+}
+
+/// All child elements of `element` with the name `name`.
+fn children<'a, 'input: 'a>(
+    element: Node<'a, 'input>,
+    name: &'a str,
+) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    element
+        .children()
+        .filter(move |child| child.has_tag_name(name))
+}
+
+/// The text of the first child element of `element` with the name `name`, without
+/// leading and trailing whitespace.
+fn child_text<'a>(element: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    child(element, name).map(|child| child.text().unwrap_or_default().trim())
+}
+
+fn required<'a>(element: Node<'a, '_>, name: &'static str) -> Result<&'a str, SvdError> {
+    child_text(element, name).ok_or_else(|| SvdError::MissingElement {
+        element: name,
+        parent: child_text(element, "name")
+            .unwrap_or_else(|| element.tag_name().name())
+            .to_owned(),
+    })
+}
+
+fn required_number(element: Node, name: &'static str) -> Result<u32, SvdError> {
+    parse_number(required(element, name)?)
+}
+
+fn optional_number(element: Node, name: &str) -> Result<Option<u32>, SvdError> {
+    child_text(element, name).map(parse_number).transpose()
+}
+
+/// Parse a number in the formats of SVD files: decimal, hexadecimal with `0x`, or binary with `#`.
+fn parse_number(text: &str) -> Result<u32, SvdError> {
+    let text = text.trim();
+
+    let result = if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        u32::from_str_radix(hex, 16)
+    } else if let Some(binary) = text.strip_prefix('#') {
+        u32::from_str_radix(binary, 2)
+    } else {
+        text.parse()
+    };
+
+    result.map_err(|_| SvdError::InvalidNumber(text.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Device, SvdError};
+
+    const SVD: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<device schemaVersion="1.1">
+  <name>TEST</name>
+  <size>32</size>
+  <resetValue>0x00000000</resetValue>
+  <peripherals>
+    <peripheral>
+      <name>USART1</name>
+      <baseAddress>0x40011000</baseAddress>
+      <registers>
+        <register>
+          <name>SR</name>
+          <addressOffset>0x0</addressOffset>
+          <resetValue>0x00C0</resetValue>
+          <fields>
+            <field>
+              <name>TXE</name>
+              <bitOffset>7</bitOffset>
+              <bitWidth>1</bitWidth>
+            </field>
+            <field>
+              <name>MODE</name>
+              <bitRange>[2:1]</bitRange>
+              <enumeratedValues>
+                <enumeratedValue><name>Off</name><value>0</value></enumeratedValue>
+                <enumeratedValue><name>On</name><value>#11</value></enumeratedValue>
+              </enumeratedValues>
+            </field>
+          </fields>
+        </register>
+        <cluster>
+          <name>CH%s</name>
+          <dim>2</dim>
+          <dimIncrement>0x10</dimIncrement>
+          <addressOffset>0x20</addressOffset>
+          <register>
+            <name>CR</name>
+            <addressOffset>0x4</addressOffset>
+            <size>16</size>
+          </register>
+        </cluster>
+      </registers>
+    </peripheral>
+    <peripheral derivedFrom="USART1">
+      <name>USART2</name>
+      <baseAddress>0x40004400</baseAddress>
+    </peripheral>
+  </peripherals>
+</device>"#;
+
+    #[test]
+    fn parse_device() {
+        let device = Device::parse(SVD).unwrap();
+
+        assert_eq!(device.name, "TEST");
+
+        let usart = device.peripheral("usart1").unwrap();
+        assert_eq!(usart.base_address, 0x4001_1000);
+
+        let sr = usart.register("SR").unwrap();
+        assert_eq!(sr.reset_value, Some(0xc0));
+        assert_eq!(sr.size, 32);
+
+        let mode = sr.field("MODE").unwrap();
+        assert_eq!((mode.bit_offset, mode.bit_width), (1, 2));
+        assert_eq!(mode.extract(0b110), 3);
+        assert_eq!(mode.value_name(3), Some("On"));
+        assert_eq!(mode.insert(0xff, 0), 0xf9);
+
+        let cr = usart.register("CH1.CR").unwrap();
+        assert_eq!(cr.address_offset, 0x34);
+        assert_eq!(cr.size, 16);
+
+        let derived = device.peripheral("USART2").unwrap();
+        assert_eq!(derived.base_address, 0x4000_4400);
+        assert_eq!(derived.registers, usart.registers);
+    }
+
+    #[test]
+    fn report_invalid_xml() {
+        assert!(matches!(
+            Device::parse("<device>\n<name>a</nme>\n</device>"),
+            Err(SvdError::Xml(_))
+        ));
+        assert!(matches!(
+            Device::parse("<device><peripherals/></device>"),
+            Err(SvdError::MissingElement { element: "name", .. })
+        ));
+    }
+}
@@ -68,3 +68,35 @@ fn function_name_of_non_inlined_function() {
     let name = di.function_name(address, false).unwrap();
     assert_eq!(expected_name, name);
 }
+
+#[test]
+fn addresses_of_inlined_function() {
+    let di = DebugInfo::from_file("tests/inlined-function").unwrap();
+
+    // The function is only used inlined, the name can also be qualified.
+    assert_eq!(vec![0x15e], di.get_function_addresses("blink_on").unwrap());
+    assert_eq!(
+        vec![0x15e],
+        di.get_function_addresses("inlined_function::blink_on")
+            .unwrap()
+    );
+
+    assert_eq!(vec![0xf0], di.get_function_addresses("blink_off").unwrap());
+    assert!(di.get_function_addresses("blink").unwrap().is_empty());
+}
+
+#[test]
+fn breakpoint_locations_of_duplicated_line() {
+    let di = DebugInfo::from_file("tests/inlined-function").unwrap();
+
+    let path = std::path::Path::new("inlined-function/src/main.rs");
+
+    // The code of the line is contained twice.
+    assert_eq!(
+        vec![0x128, 0x186],
+        di.get_breakpoint_locations(path, 41).unwrap()
+    );
+
+    // The line of the inlined function.
+    assert_eq!(vec![0x15e], di.get_breakpoint_locations(path, 49).unwrap());
+}
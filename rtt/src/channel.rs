@@ -2,6 +2,8 @@ use crate::Error;
 use probe_rs::{config::MemoryRegion, Core, MemoryInterface};
 use scroll::{Pread, LE};
 use std::cmp::min;
+use std::thread;
+use std::time::{Duration, Instant};
 
 /// Trait for channel information shared between up and down channels.
 pub trait RttChannel {
@@ -47,8 +49,8 @@ impl Channel {
     const O_NAME: usize = 0;
     const O_BUFFER_PTR: usize = 4;
     const O_SIZE: usize = 8;
-    const O_WRITE: usize = 12;
-    const O_READ: usize = 16;
+    pub(crate) const O_WRITE: usize = 12;
+    pub(crate) const O_READ: usize = 16;
     const O_FLAGS: usize = 20;
 
     pub(crate) fn from(
@@ -106,6 +108,31 @@ impl Channel {
         self.size as usize
     }
 
+    fn mode(&self, core: &mut Core) -> Result<ChannelMode, Error> {
+        self.validate_core_id(core)?;
+
+        let flags = core.read_word_32(self.ptr + Self::O_FLAGS as u32)?;
+
+        match flags & 0x3 {
+            0 => Ok(ChannelMode::NoBlockSkip),
+            1 => Ok(ChannelMode::NoBlockTrim),
+            2 => Ok(ChannelMode::BlockIfFull),
+            _ => Err(Error::ControlBlockCorrupted(String::from(
+                "The channel mode flags are invalid",
+            ))),
+        }
+    }
+
+    fn set_mode(&self, core: &mut Core, mode: ChannelMode) -> Result<(), Error> {
+        self.validate_core_id(core)?;
+        let flags = core.read_word_32(self.ptr + Self::O_FLAGS as u32)?;
+
+        let new_flags = (flags & !3) | (mode as u32);
+        core.write_word_32(self.ptr + Self::O_FLAGS as u32, new_flags)?;
+
+        Ok(())
+    }
+
     fn read_pointers(&self, core: &mut Core, dir: &'static str) -> Result<(u32, u32), Error> {
         self.validate_core_id(core)?;
         let mut block = [0u32; 2];
@@ -114,6 +141,18 @@ impl Channel {
         let write: u32 = block[0];
         let read: u32 = block[1];
 
+        self.validate_pointers(write, read, dir)?;
+
+        Ok((write, read))
+    }
+
+    /// Checks that the pointers read from the channel are within its buffer.
+    pub(crate) fn validate_pointers(
+        &self,
+        write: u32,
+        read: u32,
+        dir: &'static str,
+    ) -> Result<(), Error> {
         let validate = |which, value| {
             if value >= self.size {
                 Err(Error::ControlBlockCorrupted(format!(
@@ -131,9 +170,7 @@ impl Channel {
         };
 
         validate("write", write)?;
-        validate("read", read)?;
-
-        Ok((write, read))
+        validate("read", read)
     }
 }
 
@@ -162,37 +199,33 @@ impl UpChannel {
     ///
     /// See [`ChannelMode`] for more information on what the modes mean.
     pub fn mode(&self, core: &mut Core) -> Result<ChannelMode, Error> {
-        self.0.validate_core_id(core)?;
-
-        let flags = core.read_word_32(self.0.ptr + Channel::O_FLAGS as u32)?;
-
-        match flags & 0x3 {
-            0 => Ok(ChannelMode::NoBlockSkip),
-            1 => Ok(ChannelMode::NoBlockTrim),
-            2 => Ok(ChannelMode::BlockIfFull),
-            _ => Err(Error::ControlBlockCorrupted(String::from(
-                "The channel mode flags are invalid",
-            ))),
-        }
+        self.0.mode(core)
     }
 
     /// Changes the channel mode on the target to the specified mode.
     ///
     /// See [`ChannelMode`] for more information on what the modes mean.
     pub fn set_mode(&self, core: &mut Core, mode: ChannelMode) -> Result<(), Error> {
-        self.0.validate_core_id(core)?;
-        let flags = core.read_word_32(self.0.ptr + Channel::O_FLAGS as u32)?;
-
-        let new_flags = (flags & !3) | (mode as u32);
-        core.write_word_32(self.0.ptr + Channel::O_FLAGS as u32, new_flags)?;
-
-        Ok(())
+        self.0.set_mode(core, mode)
     }
 
-    fn read_core(&self, core: &mut Core, mut buf: &mut [u8]) -> Result<(u32, usize), Error> {
+    fn read_core(&self, core: &mut Core, buf: &mut [u8]) -> Result<(u32, usize), Error> {
         self.0.validate_core_id(core)?;
-        let (write, mut read) = self.0.read_pointers(core, "up")?;
+        let (write, read) = self.0.read_pointers(core, "up")?;
 
+        self.read_buffer(core, write, read, buf)
+    }
+
+    /// Reads the data between `read` and `write` from the buffer, as much as fits into `buf`.
+    ///
+    /// Returns the new read pointer and the number of bytes read.
+    fn read_buffer(
+        &self,
+        core: &mut Core,
+        write: u32,
+        mut read: u32,
+        mut buf: &mut [u8],
+    ) -> Result<(u32, usize), Error> {
         let mut total = 0;
 
         // Read while buffer contains data and output buffer has space (maximum of two iterations)
@@ -235,6 +268,34 @@ impl UpChannel {
         Ok(total)
     }
 
+    /// Reads all data between the pointers `write` and `read`, which were already read from the
+    /// target, and writes the new read pointer back.
+    pub(crate) fn read_available(
+        &self,
+        core: &mut Core,
+        write: u32,
+        read: u32,
+    ) -> Result<Vec<u8>, Error> {
+        self.0.validate_core_id(core)?;
+        self.0.validate_pointers(write, read, "up")?;
+
+        let available = if write >= read {
+            write - read
+        } else {
+            self.0.size - read + write
+        };
+
+        let mut buf = vec![0u8; available as usize];
+        let (read, total) = self.read_buffer(core, write, read, &mut buf)?;
+        buf.truncate(total);
+
+        if total > 0 {
+            core.write_word_32(self.0.ptr + Channel::O_READ as u32, read)?;
+        }
+
+        Ok(buf)
+    }
+
     /// Peeks at the current data in the channel buffer, copies data into the specified buffer and
     /// returns how many bytes were read.
     ///
@@ -290,6 +351,64 @@ impl DownChannel {
         self.0.buffer_size()
     }
 
+    /// Reads the current channel mode from the target and returns its.
+    ///
+    /// See [`ChannelMode`] for more information on what the modes mean.
+    pub fn mode(&self, core: &mut Core) -> Result<ChannelMode, Error> {
+        self.0.mode(core)
+    }
+
+    /// Changes the channel mode on the target to the specified mode.
+    ///
+    /// See [`ChannelMode`] for more information on what the modes mean.
+    pub fn set_mode(&self, core: &mut Core, mode: ChannelMode) -> Result<(), Error> {
+        self.0.set_mode(core, mode)
+    }
+
+    /// Returns the number of bytes which can be written without overwriting data the target has
+    /// not read yet.
+    pub fn free_space(&self, core: &mut Core) -> Result<usize, Error> {
+        let (write, read) = self.0.read_pointers(core, "down")?;
+
+        Ok(self.writable(write, read))
+    }
+
+    /// Writes bytes into the channel buffer, handling a full buffer like the target would for the
+    /// current [`ChannelMode`] of the channel, and returns the number of bytes written.
+    ///
+    /// - [`ChannelMode::NoBlockSkip`]: nothing is written if `buf` does not fit completely.
+    /// - [`ChannelMode::NoBlockTrim`]: as much of `buf` as fits is written.
+    /// - [`ChannelMode::BlockIfFull`]: waits for the target to read from the buffer until all of
+    ///   `buf` is written, or `timeout` has elapsed.
+    pub fn write_with_mode(
+        &self,
+        core: &mut Core,
+        buf: &[u8],
+        timeout: Duration,
+    ) -> Result<usize, Error> {
+        match self.mode(core)? {
+            ChannelMode::NoBlockSkip => {
+                if self.free_space(core)? < buf.len() {
+                    return Ok(0);
+                }
+
+                self.write(core, buf)
+            }
+            ChannelMode::NoBlockTrim => self.write(core, buf),
+            ChannelMode::BlockIfFull => {
+                let start = Instant::now();
+                let mut total = self.write(core, buf)?;
+
+                while total < buf.len() && start.elapsed() < timeout {
+                    thread::sleep(Duration::from_millis(1));
+                    total += self.write(core, &buf[total..])?;
+                }
+
+                Ok(total)
+            }
+        }
+    }
+
     /// Writes some bytes into the channel buffer and returns the number of bytes written.
     ///
     /// This method will not block waiting for space to become available in the channel buffer, and
@@ -331,6 +450,15 @@ impl DownChannel {
         Ok(total)
     }
 
+    /// Calculates amount of space available for writing
+    fn writable(&self, write: u32, read: u32) -> usize {
+        (if read > write {
+            read - write - 1
+        } else {
+            self.0.size - write + read - 1
+        }) as usize
+    }
+
     /// Calculates amount of contiguous space available for writing
     fn writable_contiguous(&self, write: u32, read: u32) -> usize {
         (if read > write {
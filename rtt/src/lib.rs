@@ -49,9 +49,18 @@ pub use channel::*;
 pub mod channels;
 pub use channels::Channels;
 
+mod poller;
+pub use poller::{PollStatistics, Poller};
+
 mod rtt;
 pub use rtt::*;
 
+mod tcp;
+pub use tcp::{RttTcpServer, SEGGER_BASE_PORT};
+
+mod terminal;
+pub use terminal::*;
+
 /// Error type for RTT operations.
 #[derive(Error, Debug)]
 pub enum Error {
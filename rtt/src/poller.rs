@@ -0,0 +1,143 @@
+//! Polling of all up channels at a fixed interval.
+
+use crate::{Error, Rtt};
+use probe_rs::{CancellationToken, Core};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Statistics about the polls of a [`Poller`].
+#[derive(Debug, Clone, Default)]
+pub struct PollStatistics {
+    /// The number of polls.
+    pub polls: u64,
+    /// The number of polls which returned data.
+    pub polls_with_data: u64,
+    /// The total number of bytes read.
+    pub bytes: u64,
+    /// The time the last poll took.
+    pub last_latency: Duration,
+    /// The longest time a poll took.
+    pub max_latency: Duration,
+    /// The total time of all polls.
+    pub total_latency: Duration,
+}
+
+impl PollStatistics {
+    /// Returns the average time a poll took, or `None` if there was no poll yet.
+    pub fn average_latency(&self) -> Option<Duration> {
+        if self.polls == 0 {
+            None
+        } else {
+            Some(self.total_latency / self.polls as u32)
+        }
+    }
+
+    fn record(&mut self, latency: Duration, bytes: usize) {
+        self.polls += 1;
+        if bytes > 0 {
+            self.polls_with_data += 1;
+        }
+        self.bytes += bytes as u64;
+        self.last_latency = latency;
+        self.max_latency = self.max_latency.max(latency);
+        self.total_latency += latency;
+    }
+}
+
+/// Reads the data of all up channels at a fixed interval, using [`Rtt::read_all`].
+#[derive(Debug)]
+pub struct Poller {
+    interval: Duration,
+    last_poll: Option<Instant>,
+    statistics: PollStatistics,
+}
+
+impl Poller {
+    /// Creates a poller, which polls at most once per `interval`.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last_poll: None,
+            statistics: PollStatistics::default(),
+        }
+    }
+
+    /// Returns the polling interval.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Changes the polling interval.
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /// Returns the statistics of all polls so far.
+    pub fn statistics(&self) -> &PollStatistics {
+        &self.statistics
+    }
+
+    /// Waits until the polling interval has elapsed since the last poll, and then reads the
+    /// available data of all up channels.
+    ///
+    /// Returns the number of every channel which contained data with its data.
+    pub fn poll(&mut self, rtt: &mut Rtt, core: &mut Core) -> Result<Vec<(usize, Vec<u8>)>, Error> {
+        if let Some(last_poll) = self.last_poll {
+            if let Some(remaining) = self.interval.checked_sub(last_poll.elapsed()) {
+                thread::sleep(remaining);
+            }
+        }
+
+        let start = Instant::now();
+        self.last_poll = Some(start);
+
+        let data = rtt.read_all(core)?;
+
+        let bytes = data.iter().map(|(_, data)| data.len()).sum();
+        self.statistics.record(start.elapsed(), bytes);
+
+        Ok(data)
+    }
+
+    /// Polls the up channels until `cancellation` is cancelled, and calls `handler` with the
+    /// number of every channel which contained data and its data.
+    ///
+    /// The cancellation is checked after every poll, so this returns at most one polling
+    /// interval after it was cancelled.
+    pub fn run(
+        &mut self,
+        rtt: &mut Rtt,
+        core: &mut Core,
+        cancellation: &CancellationToken,
+        mut handler: impl FnMut(usize, &[u8]),
+    ) -> Result<(), Error> {
+        while !cancellation.is_cancelled() {
+            for (channel, data) in self.poll(rtt, core)? {
+                handler(channel, &data);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PollStatistics;
+    use std::time::Duration;
+
+    #[test]
+    fn latency_statistics() {
+        let mut statistics = PollStatistics::default();
+        assert_eq!(statistics.average_latency(), None);
+
+        statistics.record(Duration::from_millis(2), 0);
+        statistics.record(Duration::from_millis(4), 10);
+
+        assert_eq!(statistics.polls, 2);
+        assert_eq!(statistics.polls_with_data, 1);
+        assert_eq!(statistics.bytes, 10);
+        assert_eq!(statistics.max_latency, Duration::from_millis(4));
+        assert_eq!(statistics.average_latency(), Some(Duration::from_millis(3)));
+    }
+}
@@ -36,6 +36,7 @@ use std::ops::Range;
 #[derive(Debug)]
 pub struct Rtt {
     ptr: u32,
+    max_up_channels: usize,
     up_channels: Channels<UpChannel>,
     down_channels: Channels<DownChannel>,
 }
@@ -154,6 +155,7 @@ impl Rtt {
 
         Ok(Some(Rtt {
             ptr,
+            max_up_channels,
             up_channels: Channels(up_channels),
             down_channels: Channels(down_channels),
         }))
@@ -248,6 +250,43 @@ impl Rtt {
         self.ptr
     }
 
+    /// Reads the available data of all up channels, and returns the number of every channel which
+    /// contained data with its data.
+    ///
+    /// The pointers of all up channels are read with a single block read, and only the buffers
+    /// of the channels which contain data are read. This needs far less transfers than reading
+    /// every channel on its own, which matters for slow probes.
+    pub fn read_all(&mut self, core: &mut Core) -> Result<Vec<(usize, Vec<u8>)>, Error> {
+        let mut data = Vec::new();
+
+        if self.up_channels.is_empty() {
+            return Ok(data);
+        }
+
+        const WORDS_PER_CHANNEL: usize = Channel::SIZE / 4;
+
+        let mut words = vec![0u32; self.max_up_channels * WORDS_PER_CHANNEL];
+        core.read_32(self.ptr + Self::O_CHANNEL_ARRAYS as u32, &mut words)?;
+
+        for channel in self.up_channels.0.values() {
+            let pointers = &words[channel.number() * WORDS_PER_CHANNEL..];
+            let write = pointers[Channel::O_WRITE / 4];
+            let read = pointers[Channel::O_READ / 4];
+
+            if write == read {
+                continue;
+            }
+
+            let channel_data = channel.read_available(core, write, read)?;
+
+            if !channel_data.is_empty() {
+                data.push((channel.number(), channel_data));
+            }
+        }
+
+        Ok(data)
+    }
+
     /// Gets the detected up channels.
     pub fn up_channels(&mut self) -> &mut Channels<UpChannel> {
         &mut self.up_channels
@@ -0,0 +1,291 @@
+//! TCP ports for the RTT channels, like the RTT telnet server of SEGGER J-Link.
+//!
+//! Every channel number gets its own port: the data of the up channel is sent to all clients
+//! of the port, and the data received from the clients is written to the down channel with
+//! the same number. Channel `n` is served on [`SEGGER_BASE_PORT`]` + n` by default, so RTT
+//! clients which connect to a J-Link, e.g. a telnet client on port 19021, work unchanged.
+
+use crate::{Error, Rtt};
+use probe_rs::Core;
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream};
+
+/// The port of channel 0 in the RTT telnet server of SEGGER J-Link.
+pub const SEGGER_BASE_PORT: u16 = 19021;
+
+/// The maximum number of bytes which are buffered for a client, or for a down channel,
+/// before the data is dropped.
+const MAX_PENDING: usize = 64 * 1024;
+
+/// A client of a channel port.
+#[derive(Debug)]
+struct Client {
+    stream: TcpStream,
+    address: SocketAddr,
+    /// The data of the up channel which could not be sent yet.
+    pending: Vec<u8>,
+}
+
+impl Client {
+    fn new(stream: TcpStream, address: SocketAddr) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+
+        Ok(Self {
+            stream,
+            address,
+            pending: Vec::new(),
+        })
+    }
+
+    /// Queue `data`, and send as much of the queued data as possible without blocking.
+    ///
+    /// The oldest data is dropped if the client can't keep up.
+    fn send(&mut self, data: &[u8]) -> io::Result<()> {
+        self.pending.extend_from_slice(data);
+
+        while !self.pending.is_empty() {
+            match self.stream.write(&self.pending) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => {
+                    self.pending.drain(..n);
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        if self.pending.len() > MAX_PENDING {
+            let dropped = self.pending.len() - MAX_PENDING;
+            log::warn!("Dropping {} bytes for RTT client {}", dropped, self.address);
+            self.pending.drain(..dropped);
+        }
+
+        Ok(())
+    }
+
+    /// Read the data which the client sent into `data`, without blocking.
+    fn receive(&mut self, data: &mut Vec<u8>) -> io::Result<()> {
+        let mut buffer = [0; 1024];
+
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(n) => data.extend_from_slice(&buffer[..n]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// The port of a channel number.
+#[derive(Debug)]
+struct ChannelPort {
+    number: usize,
+    listener: TcpListener,
+    clients: Vec<Client>,
+    /// The data of the clients which was not written to the down channel yet.
+    down_pending: Vec<u8>,
+}
+
+impl ChannelPort {
+    /// Accept all pending clients.
+    fn accept(&mut self) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, address)) => match Client::new(stream, address) {
+                    Ok(client) => {
+                        log::info!("Accepted RTT client {} on channel {}", address, self.number);
+                        self.clients.push(client);
+                    }
+                    Err(e) => log::warn!("Failed to set up RTT client {}: {}", address, e),
+                },
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return,
+                Err(e) => {
+                    log::warn!(
+                        "Failed to accept a client on channel {}: {}",
+                        self.number,
+                        e
+                    );
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Send `data` to all clients, and collect the data which the clients sent. Clients
+    /// which are disconnected are dropped.
+    fn exchange(&mut self, data: &[u8]) {
+        let down_pending = &mut self.down_pending;
+
+        let mut index = 0;
+        while index < self.clients.len() {
+            let client = &mut self.clients[index];
+
+            let result = client.send(data).and_then(|_| client.receive(down_pending));
+
+            match result {
+                Ok(()) => index += 1,
+                Err(e) => {
+                    log::info!("RTT client {} disconnected: {}", client.address, e);
+                    self.clients.swap_remove(index);
+                }
+            }
+        }
+
+        if self.down_pending.len() > MAX_PENDING {
+            let dropped = self.down_pending.len() - MAX_PENDING;
+            log::warn!(
+                "Dropping {} bytes for down channel {}, the target doesn't read them",
+                dropped,
+                self.number
+            );
+            self.down_pending.drain(..dropped);
+        }
+    }
+}
+
+/// Serves the RTT channels on TCP ports, one port per channel number.
+///
+/// The server doesn't own a thread, [`poll`](Self::poll) has to be called regularly to
+/// exchange the data between the channels and the clients. The data of an up channel is
+/// dropped while no client is connected to its port.
+///
+/// ```no_run
+/// use probe_rs::Probe;
+/// use probe_rs_rtt::{Rtt, RttTcpServer, SEGGER_BASE_PORT};
+///
+/// let probe = Probe::list_all()[0].open()?;
+/// let mut session = probe.attach("somechip")?;
+/// let memory_map = session.target().memory_map.clone();
+/// let mut core = session.core(0)?;
+///
+/// let mut rtt = Rtt::attach(&mut core, &memory_map)?;
+/// let mut server = RttTcpServer::bind(&mut rtt, [127, 0, 0, 1].into(), SEGGER_BASE_PORT)?;
+///
+/// loop {
+///     server.poll(&mut rtt, &mut core)?;
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+pub struct RttTcpServer {
+    ports: Vec<ChannelPort>,
+}
+
+impl RttTcpServer {
+    /// Listen on `ip` for the clients of all up and down channels of `rtt`. Channel `n` is
+    /// served on port `base_port + n`.
+    pub fn bind(rtt: &mut Rtt, ip: IpAddr, base_port: u16) -> io::Result<Self> {
+        let mut numbers: Vec<usize> = rtt
+            .up_channels()
+            .iter()
+            .map(|channel| channel.number())
+            .collect();
+        numbers.extend(rtt.down_channels().iter().map(|channel| channel.number()));
+        numbers.sort_unstable();
+        numbers.dedup();
+
+        let mut ports = Vec::new();
+        for number in numbers {
+            let port = u16::try_from(number)
+                .ok()
+                .and_then(|number| base_port.checked_add(number))
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("There is no port for channel {}", number),
+                    )
+                })?;
+
+            let listener = TcpListener::bind((ip, port))?;
+            listener.set_nonblocking(true)?;
+
+            ports.push(ChannelPort {
+                number,
+                listener,
+                clients: Vec::new(),
+                down_pending: Vec::new(),
+            });
+        }
+
+        Ok(Self { ports })
+    }
+
+    /// The channel numbers, and the addresses their ports listen on.
+    pub fn addresses(&self) -> Vec<(usize, SocketAddr)> {
+        self.ports
+            .iter()
+            .filter_map(|port| Some((port.number, port.listener.local_addr().ok()?)))
+            .collect()
+    }
+
+    /// Accept new clients, send the data of the up channels to the clients, and write the
+    /// data received from the clients to the down channels.
+    ///
+    /// This only blocks for the accesses to the target. Data which the down channels can't
+    /// take yet is written by the following polls.
+    pub fn poll(&mut self, rtt: &mut Rtt, core: &mut Core) -> Result<(), Error> {
+        let mut up_data = rtt.read_all(core)?;
+
+        for port in &mut self.ports {
+            port.accept();
+
+            let data = up_data
+                .iter_mut()
+                .find(|(number, _)| *number == port.number)
+                .map(|(_, data)| std::mem::take(data))
+                .unwrap_or_default();
+
+            port.exchange(&data);
+
+            if port.down_pending.is_empty() {
+                continue;
+            }
+
+            match rtt.down_channels().get(port.number) {
+                Some(channel) => {
+                    let count = channel.write(core, &port.down_pending)?;
+                    port.down_pending.drain(..count);
+                }
+                // The channel has no down channel, so the input of the clients is dropped.
+                None => port.down_pending.clear(),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Client;
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    #[test]
+    fn client_exchanges_data() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut remote = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, address) = listener.accept().unwrap();
+
+        let mut client = Client::new(stream, address).unwrap();
+
+        client.send(b"up").unwrap();
+        let mut data = [0; 2];
+        remote.read_exact(&mut data).unwrap();
+        assert_eq!(&data, b"up");
+
+        remote.write_all(b"down").unwrap();
+        remote.flush().unwrap();
+
+        // Wait until the data arrives, as the client doesn't block.
+        let mut received = Vec::new();
+        while received.len() < 4 {
+            client.receive(&mut received).unwrap();
+        }
+        assert_eq!(received, b"down");
+    }
+}
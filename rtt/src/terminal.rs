@@ -0,0 +1,86 @@
+//! Virtual terminals on an up channel.
+//!
+//! The SEGGER RTT implementation can multiplex up to 16 virtual terminals on up channel 0. A
+//! terminal is selected by sending `0xFF` followed by the number of the terminal as a hexadecimal
+//! digit, and stays selected until another terminal is selected.
+
+/// The byte which starts the selection of a virtual terminal.
+const TERMINAL_ESCAPE: u8 = 0xFF;
+
+/// Splits the data of an up channel into the data of the virtual terminals.
+#[derive(Debug, Default)]
+pub struct TerminalDemux {
+    /// The currently selected terminal.
+    current: u8,
+    /// An escape byte was the last byte of the previous data.
+    pending_escape: bool,
+}
+
+impl TerminalDemux {
+    /// Creates a demultiplexer, with terminal 0 selected.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the currently selected terminal.
+    pub fn current_terminal(&self) -> u8 {
+        self.current
+    }
+
+    /// Splits `data` read from the channel into the data of the terminals.
+    ///
+    /// Returns the number of the terminal with its data, in the order the data was received.
+    /// Escape sequences which are split between two reads are handled.
+    pub fn process(&mut self, data: &[u8]) -> Vec<(u8, Vec<u8>)> {
+        let mut output: Vec<(u8, Vec<u8>)> = Vec::new();
+
+        for &byte in data {
+            if self.pending_escape {
+                self.pending_escape = false;
+
+                if let Some(terminal) = (byte as char).to_digit(16) {
+                    self.current = terminal as u8;
+                    continue;
+                }
+
+                // Not a terminal selection, so the escape byte was data.
+                push(&mut output, self.current, TERMINAL_ESCAPE);
+            }
+
+            if byte == TERMINAL_ESCAPE {
+                self.pending_escape = true;
+            } else {
+                push(&mut output, self.current, byte);
+            }
+        }
+
+        output
+    }
+}
+
+fn push(output: &mut Vec<(u8, Vec<u8>)>, terminal: u8, byte: u8) {
+    match output.last_mut() {
+        Some((last, data)) if *last == terminal => data.push(byte),
+        _ => output.push((terminal, vec![byte])),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TerminalDemux;
+
+    #[test]
+    fn demultiplex_terminals() {
+        let mut demux = TerminalDemux::new();
+
+        assert_eq!(
+            demux.process(b"boot\n\xff1error\n\xff"),
+            vec![(0, b"boot\n".to_vec()), (1, b"error\n".to_vec())]
+        );
+        assert_eq!(demux.current_terminal(), 1);
+
+        // The escape sequence was split between two reads.
+        assert_eq!(demux.process(b"Aok\n"), vec![(10, b"ok\n".to_vec())]);
+        assert_eq!(demux.current_terminal(), 10);
+    }
+}
@@ -1,5 +1,5 @@
-use probe_rs::{config::TargetSelector, DebugProbeInfo, Probe};
-use probe_rs_rtt::{Channels, Rtt, RttChannel, ScanRegion};
+use probe_rs::{config::TargetSelector, Core, DebugProbeInfo, Probe};
+use probe_rs_rtt::{Channels, Rtt, RttChannel, RttTcpServer, ScanRegion, TerminalDemux};
 use std::io::prelude::*;
 use std::io::{stdin, stdout};
 use std::sync::mpsc::{channel, Receiver};
@@ -88,6 +88,18 @@ struct Opts {
     )]
     down: Option<usize>,
 
+    #[structopt(
+        long,
+        help = "Split the output into the SEGGER virtual terminals, and prefix the output of every terminal except 0 with its number."
+    )]
+    terminals: bool,
+
+    #[structopt(
+        long,
+        help = "Serve all channels on TCP ports instead of stdin and stdout, one port per channel starting at the given port. SEGGER J-Link uses port 19021 for channel 0."
+    )]
+    tcp: Option<u16>,
+
     #[structopt(
         long,
         default_value="",
@@ -185,6 +197,10 @@ fn run() -> i32 {
         return 0;
     }
 
+    if let Some(base_port) = opts.tcp {
+        return serve_tcp(&mut rtt, &mut core, base_port);
+    }
+
     let up_channel = if let Some(up) = opts.up {
         let chan = rtt.up_channels().take(up);
 
@@ -218,6 +234,9 @@ fn run() -> i32 {
     let mut up_buf = [0u8; 1024];
     let mut down_buf = vec![];
 
+    let mut demux = TerminalDemux::new();
+    let mut last_terminal = 0;
+
     loop {
         if let Some(up_channel) = up_channel.as_ref() {
             let count = match up_channel.read(&mut core, up_buf.as_mut()) {
@@ -228,7 +247,13 @@ fn run() -> i32 {
                 }
             };
 
-            match stdout().write_all(&up_buf[..count]) {
+            let result = if opts.terminals {
+                write_terminals(&mut demux, &mut last_terminal, &up_buf[..count])
+            } else {
+                stdout().write_all(&up_buf[..count])
+            };
+
+            match result {
                 Ok(_) => {
                     stdout().flush().ok();
                 }
@@ -261,6 +286,51 @@ fn run() -> i32 {
     }
 }
 
+/// Serves all channels on TCP ports, starting at `base_port`.
+fn serve_tcp(rtt: &mut Rtt, core: &mut Core, base_port: u16) -> i32 {
+    let mut server = match RttTcpServer::bind(rtt, [127, 0, 0, 1].into(), base_port) {
+        Ok(server) => server,
+        Err(err) => {
+            eprintln!("Error opening the TCP ports: {}", err);
+            return 1;
+        }
+    };
+
+    for (number, address) in server.addresses() {
+        eprintln!("Serving channel {} on {}", number, address);
+    }
+
+    loop {
+        if let Err(err) = server.poll(rtt, core) {
+            eprintln!("\nError exchanging RTT data: {}", err);
+            return 1;
+        }
+    }
+}
+
+/// Writes the data of the virtual terminals to stdout, with a prefix when the terminal changes.
+fn write_terminals(
+    demux: &mut TerminalDemux,
+    last_terminal: &mut u8,
+    data: &[u8],
+) -> std::io::Result<()> {
+    let mut stdout = stdout();
+
+    for (terminal, data) in demux.process(data) {
+        if terminal != *last_terminal {
+            *last_terminal = terminal;
+
+            if terminal != 0 {
+                write!(stdout, "[{}] ", terminal)?;
+            }
+        }
+
+        stdout.write_all(&data)?;
+    }
+
+    Ok(())
+}
+
 fn list_probes(mut stream: impl std::io::Write, probes: &[DebugProbeInfo]) {
     writeln!(stream, "Available probes:").unwrap();
 